@@ -0,0 +1,189 @@
+//! Benchmarks for the per-message parsing path
+//!
+//! Fixtures live in `benches/fixtures` and are committed, so two machines (or
+//! two branches) measure the same input: a PumpSwap buy with minimal logs and
+//! the same buy buried in CPI noise.
+//!
+//! Workflow for a change touching the hot path:
+//!
+//! ```bash
+//! git checkout main
+//! cargo bench --bench hot_path -- --save-baseline main > bench.log
+//! git checkout my-branch
+//! cargo bench --bench hot_path -- --baseline main > bench.log
+//! ```
+//!
+//! The parsers still print every step, hence the redirect; the comparison
+//! report is under `target/criterion/report/index.html`, and each benchmark's
+//! change against the baseline is also in `bench.log`.
+//!
+//! For where the time goes inside a live run, build with the `profiling`
+//! feature and turn the folded stacks into a flamegraph:
+//!
+//! ```bash
+//! cargo run --release --features profiling
+//! # Ctrl-C, then
+//! inferno-flamegraph < tracing.folded > flame.svg
+//! ```
+//!
+//! There is no standalone event decoder or mock stream yet, so `per_message`
+//! chains the stages the transaction handler runs, in its order.
+
+use std::path::Path;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use serde::Deserialize;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use solana_vntr_sniper::application::monitor::{extract_pool_info_from_transaction, TradeInfoFromToken};
+use solana_vntr_sniper::infrastructure::dex::dex_registry::DEXRegistry;
+use solana_vntr_sniper::shared::latency::{LatencyTracer, Stage};
+use solana_vntr_sniper::shared::rng::SessionRng;
+use solana_vntr_sniper::record::transaction_logger::classify_protocol;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo};
+use yellowstone_grpc_proto::prelude::{
+    CompiledInstruction, ConfirmedTransaction, Message, MessageHeader, Transaction, TransactionStatusMeta,
+};
+
+const FIXTURES: [&str; 2] = ["pumpswap_buy_small", "pumpswap_buy_log_heavy"];
+
+/// PumpSwap, the program every fixture swaps on
+const PUMPSWAP_PROGRAM: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+
+#[derive(Deserialize)]
+struct FixtureInstruction {
+    program_id_index: u32,
+    accounts: Vec<u8>,
+    data_base64: String,
+}
+
+#[derive(Deserialize)]
+struct Fixture {
+    slot: u64,
+    signature: String,
+    account_keys: Vec<String>,
+    instructions: Vec<FixtureInstruction>,
+    log_messages: Vec<String>,
+}
+
+struct Loaded {
+    update: SubscribeUpdateTransaction,
+    confirmed: ConfirmedTransaction,
+    logs: Vec<String>,
+}
+
+fn load(name: &str) -> Loaded {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/fixtures").join(format!("{}.json", name));
+    let raw = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    let fixture: Fixture = serde_json::from_str(&raw).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+    let signature = bs58::decode(&fixture.signature).into_vec().expect("fixture signature is base58");
+    let message = Message {
+        header: Some(MessageHeader { num_required_signatures: 1, ..Default::default() }),
+        account_keys: fixture
+            .account_keys
+            .iter()
+            .map(|k| bs58::decode(k).into_vec().expect("fixture key is base58"))
+            .collect(),
+        recent_blockhash: vec![7; 32],
+        instructions: fixture
+            .instructions
+            .iter()
+            .map(|ix| CompiledInstruction {
+                program_id_index: ix.program_id_index,
+                accounts: ix.accounts.clone(),
+                data: base64::decode(&ix.data_base64).expect("fixture data is base64"),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let transaction = Transaction { signatures: vec![signature.clone()], message: Some(message) };
+    let meta = TransactionStatusMeta { log_messages: fixture.log_messages.clone(), ..Default::default() };
+
+    Loaded {
+        update: SubscribeUpdateTransaction {
+            transaction: Some(SubscribeUpdateTransactionInfo {
+                signature,
+                transaction: Some(transaction.clone()),
+                meta: Some(meta.clone()),
+                ..Default::default()
+            }),
+            slot: fixture.slot,
+        },
+        confirmed: ConfirmedTransaction { transaction: Some(transaction), meta: Some(meta) },
+        logs: fixture.log_messages,
+    }
+}
+
+/// A fresh tracer, as the stream handler starts one per message
+fn tracer(rng: &SessionRng) -> LatencyTracer {
+    LatencyTracer::start("bench", Stage::Received, tokio::time::Instant::now(), rng)
+}
+
+fn hot_path(c: &mut Criterion) {
+    let fixtures: Vec<(&str, Loaded)> = FIXTURES.iter().map(|name| (*name, load(name))).collect();
+    let rng = SessionRng::from_seed(0);
+
+    let mut group = c.benchmark_group("from_json");
+    for (name, loaded) in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(name), loaded, |b, loaded| {
+            b.iter_batched(
+                || (loaded.update.clone(), loaded.logs.clone(), tracer(&rng)),
+                |(txn, logs, latency)| black_box(TradeInfoFromToken::from_json(txn, logs, latency).ok()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("extract_pool_info");
+    for (name, loaded) in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(name), loaded, |b, loaded| {
+            b.iter(|| black_box(extract_pool_info_from_transaction(&loaded.confirmed, &loaded.logs).ok()))
+        });
+    }
+    group.finish();
+
+    // The parse path looks a program up per instruction; `rebuilt` is what
+    // each lookup cost when the registry was constructed for it
+    let program_id: Pubkey = PUMPSWAP_PROGRAM.parse().expect("PumpSwap program id");
+    let mut group = c.benchmark_group("dex_lookup");
+    group.bench_function("shared", |b| {
+        b.iter(|| black_box(DEXRegistry::shared().find_dex_by_program_id(black_box(&program_id)).is_some()))
+    });
+    group.bench_function("rebuilt", |b| {
+        b.iter(|| black_box(DEXRegistry::new().find_dex_by_program_id(black_box(&program_id)).is_some()))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("classify_protocol");
+    for (name, loaded) in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(name), loaded, |b, loaded| {
+            b.iter(|| black_box(classify_protocol(&loaded.logs)))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("per_message");
+    for (name, loaded) in &fixtures {
+        group.bench_with_input(BenchmarkId::from_parameter(name), loaded, |b, loaded| {
+            b.iter_batched(
+                || (loaded.update.clone(), tracer(&rng)),
+                |(txn, latency)| {
+                    let logs = txn
+                        .transaction
+                        .as_ref()
+                        .and_then(|t| t.meta.as_ref())
+                        .map(|meta| meta.log_messages.clone())
+                        .unwrap_or_default();
+                    let protocol = classify_protocol(&logs);
+                    black_box((protocol, TradeInfoFromToken::from_json(txn, logs, latency).ok()))
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, hot_path);
+criterion_main!(benches);
@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{Query, Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use colored::Colorize;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::common::{config::AppState, logger::Logger, metrics};
+use crate::core::compute_estimator::{ShapeEstimate, COMPUTE_ESTIMATOR};
+use crate::engine::admin_auth::{self, RequestVerifier, RuntimeControls, SignedRequest};
+use crate::engine::{capital::{CapitalSnapshot, Strategy}, dormancy::DormantToken, fill_quality::{self, StrategyFills}, inventory::{self, InventoryLedger}, liquidation, refresh_scheduler::TokenSchedule};
+use crate::engine::quote::{QuoteService, VenueQuote};
+use crate::engine::swap::SwapDirection;
+use crate::record::admin_audit::AdminAuditEntry;
+use crate::record::journal::{JournalEntry, JournalScope, JournalSource};
+
+/// Largest mutating request body read for signature checks
+const MAX_SIGNED_BODY: usize = 64 * 1024;
+
+/// Shared state for admin handlers
+#[derive(Clone)]
+pub struct AdminState {
+    pub app_state: Arc<AppState>,
+    /// Set when `ADMIN_HMAC_SECRET` is, mutations are then signed
+    pub verifier: Option<Arc<RequestVerifier>>,
+}
+
+/// Who made a mutating request, set by `require_signature`
+#[derive(Debug, Clone)]
+pub struct AdminActor(pub String);
+
+/// Address the admin API listens on, disabled when `ADMIN_BIND` is unset
+pub fn get_admin_bind() -> Option<String> {
+    std::env::var("ADMIN_BIND").ok().filter(|v| !v.trim().is_empty())
+}
+
+/// Serve the admin API until the process exits
+pub async fn serve_admin(bind: String, app_state: Arc<AppState>) -> anyhow::Result<()> {
+    let logger = Logger::new("[ADMIN] => ".magenta().bold().to_string());
+    let verifier = RequestVerifier::from_env().map(Arc::new);
+    if verifier.is_none() {
+        logger.log("[UNSIGNED] => ADMIN_HMAC_SECRET is unset, mutating endpoints accept unsigned requests".yellow().bold().to_string());
+    }
+    let state = AdminState { app_state, verifier };
+
+    let router = Router::new()
+        .route("/liquidate", post(liquidate))
+        .route("/journal", post(add_journal).get(query_journal))
+        .route("/entries", post(set_entries))
+        .route("/fill-quality", get(fill_quality_state))
+        .route("/fill-quality/resume", post(resume_fills))
+        .route("/audit", get(audit_log))
+        .route("/compute-units", get(compute_units))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/capital", get(capital_snapshot))
+        .route("/refresh-schedule", get(refresh_schedule))
+        .route("/dormant", get(dormant_tokens))
+        .route("/quote", get(quote))
+        .route("/inventory", get(inventory_ledgers))
+        .layer(middleware::from_fn_with_state(state.clone(), require_signature))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&bind).await?;
+    logger.log(format!("[ADMIN API] => Listening on {}", bind).green().to_string());
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Verify signed mutations, then tag the response with the config hash
+///
+/// Reads are passed through untouched.
+async fn require_signature(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    if request.method() == Method::GET {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let actor = match &state.verifier {
+        None => "unsigned".to_string(),
+        Some(verifier) => {
+            let Ok(body) = axum::body::to_bytes(body, MAX_SIGNED_BODY).await else {
+                return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+            };
+            let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+            let signed = SignedRequest {
+                method: parts.method.as_str(),
+                path,
+                timestamp: header(&parts.headers, admin_auth::TIMESTAMP_HEADER),
+                nonce: header(&parts.headers, admin_auth::NONCE_HEADER),
+                actor: header(&parts.headers, admin_auth::ACTOR_HEADER),
+                signature: header(&parts.headers, admin_auth::SIGNATURE_HEADER),
+                body: &body,
+            };
+            match verifier.verify(&signed, chrono::Utc::now().timestamp()) {
+                Ok(actor) => {
+                    parts.extensions.insert(AdminActor(actor));
+                    return with_config_hash(&state.app_state, next.run(Request::from_parts(parts, Body::from(body))).await);
+                }
+                Err(rejection) => {
+                    return (StatusCode::UNAUTHORIZED, format!("Request rejected: {}", rejection.code())).into_response();
+                }
+            }
+        }
+    };
+    parts.extensions.insert(AdminActor(actor));
+    with_config_hash(&state.app_state, next.run(Request::from_parts(parts, body)).await)
+}
+
+fn with_config_hash(app_state: &AppState, mut response: Response) -> Response {
+    if let Ok(value) = HeaderValue::from_str(&RuntimeControls::current(app_state).hash()) {
+        response.headers_mut().insert(admin_auth::CONFIG_HASH_HEADER, value);
+    }
+    response
+}
+
+/// Append to the audit log; a failed write is logged, the mutation already happened
+fn audit(
+    state: &AdminState,
+    actor: &AdminActor,
+    path: &str,
+    action: &str,
+    old_value: Option<serde_json::Value>,
+    new_value: Option<serde_json::Value>,
+) {
+    let entry = AdminAuditEntry {
+        id: 0,
+        created_at: 0,
+        actor: actor.0.clone(),
+        method: "POST".to_string(),
+        path: path.to_string(),
+        action: action.to_string(),
+        old_value: old_value.map(|v| v.to_string()),
+        new_value: new_value.map(|v| v.to_string()),
+        config_hash: RuntimeControls::current(&state.app_state).hash(),
+    };
+    if let Err(e) = state.app_state.store.append_admin_audit(&entry) {
+        Logger::new("[ADMIN] => ".magenta().bold().to_string())
+            .log(format!("[AUDIT ERROR] => Failed to record {}: {}", action, e).red().to_string());
+    }
+}
+
+/// POST /liquidate: sell every position, stop buying and exit
+async fn liquidate(
+    State(state): State<AdminState>,
+    Extension(actor): Extension<AdminActor>,
+) -> Result<Json<liquidation::LiquidationSummary>, (StatusCode, String)> {
+    // Recorded up front, the process exits once the sells are done
+    audit(
+        &state,
+        &actor,
+        "/liquidate",
+        "liquidate",
+        Some(json!({ "liquidating": liquidation::is_liquidating(&state.app_state) })),
+        Some(json!({ "liquidating": true })),
+    );
+    let summary = liquidation::liquidate_all(&state.app_state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Give the response a moment to flush before the process goes away
+    tokio::spawn(async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        std::process::exit(0);
+    });
+
+    Ok(Json(summary))
+}
+
+/// Body of POST /journal; the most specific of trade_id, mint or neither picks the scope
+#[derive(Debug, Deserialize)]
+pub struct JournalRequest {
+    pub note: String,
+    pub mint: Option<String>,
+    pub trade_id: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+impl JournalRequest {
+    pub fn scope(&self) -> JournalScope {
+        match (&self.trade_id, &self.mint) {
+            (Some(trade_id), _) => JournalScope::Trade { trade_id: trade_id.clone() },
+            (None, Some(mint)) => JournalScope::Mint { mint: mint.clone() },
+            (None, None) => JournalScope::Session,
+        }
+    }
+}
+
+/// POST /journal: attach an operator note to the session, a mint or a trade
+async fn add_journal(
+    State(state): State<AdminState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(request): Json<JournalRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let id = state
+        .app_state
+        .store
+        .add_journal_entry(&request.scope(), &request.note, &request.tags, JournalSource::User)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    audit(
+        &state,
+        &actor,
+        "/journal",
+        "journal_add",
+        None,
+        Some(json!({ "id": id, "note": request.note, "mint": request.mint, "trade_id": request.trade_id, "tags": request.tags })),
+    );
+    Ok(Json(serde_json::json!({ "id": id })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntriesRequest {
+    pub paused: bool,
+}
+
+/// POST /entries: pause or resume new entries, exits keep running
+async fn set_entries(
+    State(state): State<AdminState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(request): Json<EntriesRequest>,
+) -> Json<serde_json::Value> {
+    let was_paused = state.app_state.monitor.entries_paused();
+    state.app_state.monitor.set_entries_paused(request.paused);
+    audit(
+        &state,
+        &actor,
+        "/entries",
+        "entries_paused",
+        Some(json!({ "entries_paused": was_paused })),
+        Some(json!({ "entries_paused": request.paused })),
+    );
+    Json(json!({ "entries_paused": request.paused }))
+}
+
+/// GET /inventory: each inventory token's holdings and PnL split into spread capture and revaluation
+async fn inventory_ledgers(State(state): State<AdminState>) -> Result<Json<Vec<(String, InventoryLedger)>>, (StatusCode, String)> {
+    state
+        .app_state
+        .kv
+        .all(&inventory::INVENTORY)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// GET /fill-quality: rolling fill quality and pause state per strategy
+async fn fill_quality_state() -> Json<Vec<(String, StrategyFills)>> {
+    Json(fill_quality::snapshot())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResumeFillsRequest {
+    pub strategy: String,
+}
+
+/// POST /fill-quality/resume: lift a fill-quality pause before the median recovers
+async fn resume_fills(
+    State(state): State<AdminState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(request): Json<ResumeFillsRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let strategy = Strategy::ALL
+        .into_iter()
+        .find(|s| s.name() == request.strategy)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown strategy: {}", request.strategy)))?;
+    let was_paused = fill_quality::resume(strategy);
+    audit(
+        &state,
+        &actor,
+        "/fill-quality/resume",
+        "fill_quality_resume",
+        Some(json!({ "strategy": strategy.name(), "paused": was_paused })),
+        Some(json!({ "strategy": strategy.name(), "paused": false })),
+    );
+    Ok(Json(json!({ "strategy": strategy.name(), "was_paused": was_paused })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+}
+
+/// GET /audit?limit=..: admin mutations, newest first
+async fn audit_log(
+    State(state): State<AdminState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AdminAuditEntry>>, (StatusCode, String)> {
+    state
+        .app_state
+        .store
+        .admin_audit(query.limit.unwrap_or(100))
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JournalQuery {
+    pub mint: Option<String>,
+    /// `key` or `key=value`
+    pub tag: Option<String>,
+}
+
+/// GET /journal?mint=..|tag=key[=value]: list journal entries
+async fn query_journal(
+    State(state): State<AdminState>,
+    Query(query): Query<JournalQuery>,
+) -> Result<Json<Vec<JournalEntry>>, (StatusCode, String)> {
+    let store = &state.app_state.store;
+    let entries = match (&query.mint, &query.tag) {
+        (Some(mint), _) => store.journal_by_mint(mint),
+        (None, Some(tag)) => match tag.split_once('=') {
+            Some((key, value)) => store.journal_by_tag(key, Some(value)),
+            None => store.journal_by_tag(tag, None),
+        },
+        (None, None) => store.journal_for_session(),
+    };
+    entries
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// GET /compute-units: cached compute limits per instruction shape
+async fn compute_units() -> Json<Vec<(String, ShapeEstimate)>> {
+    Json(COMPUTE_ESTIMATOR.snapshot())
+}
+
+/// GET /metrics: Prometheus text exposition
+async fn prometheus_metrics() -> String {
+    metrics::gather_text()
+}
+
+/// GET /capital: investable balance, allocation and exposure per strategy
+async fn capital_snapshot(State(state): State<AdminState>) -> Json<CapitalSnapshot> {
+    Json(state.app_state.capital.snapshot())
+}
+
+/// GET /refresh-schedule: each token's relevance and effective refresh interval
+async fn refresh_schedule(State(state): State<AdminState>) -> Json<Vec<TokenSchedule>> {
+    let now = state.app_state.clock.now();
+    Json(state.app_state.refresh_scheduler.snapshot(now))
+}
+
+/// GET /dormant: tokens demoted for inactivity, promoted again by their next swap
+async fn dormant_tokens(State(state): State<AdminState>) -> Json<Vec<DormantToken>> {
+    let now = state.app_state.clock.now();
+    Json(state.app_state.dormancy.dormant(now))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteQuery {
+    pub mint: String,
+    /// `buy` or `sell`
+    pub direction: SwapDirection,
+    /// Size in lamports of SOL notional
+    pub amount: u64,
+    /// Only quote this DEX
+    pub venue: Option<String>,
+    /// Re-read stale venues from chain before answering
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// GET /quote?mint=..&direction=buy|sell&amount=..[&venue=..][&refresh=true]:
+/// executable price per venue, best first
+async fn quote(State(state): State<AdminState>, Query(query): Query<QuoteQuery>) -> Json<Vec<VenueQuote>> {
+    let service = QuoteService::new(Arc::clone(&state.app_state));
+    let venue = query.venue.as_deref();
+    Json(if query.refresh {
+        service.quote_refreshed(&query.mint, query.direction, query.amount, venue).await
+    } else {
+        service.quote(&query.mint, query.direction, query.amount, venue)
+    })
+}
@@ -0,0 +1,260 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::common::{
+    config::AppState,
+    hmac::{constant_time_eq, hmac_sha256, to_hex},
+};
+use crate::engine::{leader, liquidation};
+
+/// Headers a signed admin request carries
+pub const TIMESTAMP_HEADER: &str = "x-admin-timestamp";
+pub const NONCE_HEADER: &str = "x-admin-nonce";
+pub const ACTOR_HEADER: &str = "x-admin-actor";
+pub const SIGNATURE_HEADER: &str = "x-admin-signature";
+/// Set on every mutating response
+pub const CONFIG_HASH_HEADER: &str = "x-config-hash";
+
+/// Why a mutating admin request was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthRejection {
+    MissingHeader,
+    BadTimestamp,
+    /// Timestamp outside the accepted window around our clock
+    Expired,
+    /// (timestamp, nonce) already used
+    Replayed,
+    BadSignature,
+}
+
+impl AuthRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingHeader => "missing_header",
+            Self::BadTimestamp => "bad_timestamp",
+            Self::Expired => "expired",
+            Self::Replayed => "replayed",
+            Self::BadSignature => "bad_signature",
+        }
+    }
+}
+
+/// What the signature covers, one field per line
+pub fn signing_payload(method: &str, path: &str, timestamp: &str, nonce: &str, actor: &str, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{}\n{}\n{}\n{}\n{}\n", method.to_ascii_uppercase(), path, timestamp, nonce, actor).into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+/// Hex HMAC-SHA256 a client sends in `X-Admin-Signature`
+pub fn sign(secret: &[u8], payload: &[u8]) -> String {
+    to_hex(&hmac_sha256(secret, payload))
+}
+
+/// Signed headers of one request, as received
+pub struct SignedRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub timestamp: Option<&'a str>,
+    pub nonce: Option<&'a str>,
+    pub actor: Option<&'a str>,
+    pub signature: Option<&'a str>,
+    pub body: &'a [u8],
+}
+
+/// `ADMIN_HMAC_SECRET` and `ADMIN_SIGNATURE_WINDOW_SECS`
+///
+/// Checks signatures on mutating admin requests and remembers every
+/// (timestamp, nonce) pair until it falls out of the window, after which the
+/// timestamp check alone rejects it.
+pub struct RequestVerifier {
+    secret: Vec<u8>,
+    window_secs: i64,
+    seen: Mutex<HashSet<(i64, String)>>,
+}
+
+impl RequestVerifier {
+    pub fn new(secret: Vec<u8>, window_secs: i64) -> Self {
+        Self { secret, window_secs, seen: Mutex::new(HashSet::new()) }
+    }
+
+    /// None when `ADMIN_HMAC_SECRET` is unset, which leaves mutations unsigned
+    pub fn from_env() -> Option<Self> {
+        let secret = std::env::var("ADMIN_HMAC_SECRET").ok().filter(|v| !v.is_empty())?;
+        let window = std::env::var("ADMIN_SIGNATURE_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(30);
+        Some(Self::new(secret.into_bytes(), window))
+    }
+
+    /// Verify one request at `now_secs`, returning the signed actor
+    pub fn verify(&self, request: &SignedRequest<'_>, now_secs: i64) -> Result<String, AuthRejection> {
+        let (Some(timestamp), Some(nonce), Some(signature)) = (request.timestamp, request.nonce, request.signature) else {
+            return Err(AuthRejection::MissingHeader);
+        };
+        if nonce.is_empty() {
+            return Err(AuthRejection::MissingHeader);
+        }
+        let actor = request.actor.unwrap_or("");
+        let ts = timestamp.parse::<i64>().map_err(|_| AuthRejection::BadTimestamp)?;
+        if (now_secs - ts).abs() > self.window_secs {
+            return Err(AuthRejection::Expired);
+        }
+
+        let payload = signing_payload(request.method, request.path, timestamp, nonce, actor, request.body);
+        let expected = sign(&self.secret, &payload);
+        if !constant_time_eq(expected.as_bytes(), signature.trim().to_ascii_lowercase().as_bytes()) {
+            return Err(AuthRejection::BadSignature);
+        }
+
+        // Only a valid signature consumes its nonce, so garbage can't burn one
+        let mut seen = self.seen.lock().map_err(|_| AuthRejection::Replayed)?;
+        seen.retain(|(seen_ts, _)| (now_secs - seen_ts).abs() <= self.window_secs);
+        if !seen.insert((ts, nonce.to_string())) {
+            return Err(AuthRejection::Replayed);
+        }
+        Ok(if actor.is_empty() { "anonymous".to_string() } else { actor.to_string() })
+    }
+}
+
+/// Runtime controls admin mutations change, hashed into `X-Config-Hash`
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeControls {
+    pub entries_paused: bool,
+    pub liquidating: bool,
+    pub leader: bool,
+}
+
+impl RuntimeControls {
+    pub fn current(app_state: &AppState) -> Self {
+        Self {
+            entries_paused: app_state.monitor.entries_paused(),
+            liquidating: liquidation::is_liquidating(app_state),
+            leader: leader::is_leader(),
+        }
+    }
+
+    /// Short hash of the controls, equal on two instances configured alike
+    pub fn hash(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        to_hex(&anchor_client::solana_sdk::hash::hash(&json).to_bytes()[..8])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"admin secret";
+    const NOW: i64 = 1_700_000_000;
+    const BODY: &[u8] = br#"{"paused":true}"#;
+
+    /// Headers and signature a client would send
+    struct Signed {
+        timestamp: String,
+        nonce: String,
+        signature: String,
+    }
+
+    impl Signed {
+        fn new(secret: &[u8], timestamp: i64, nonce: &str) -> Self {
+            let timestamp = timestamp.to_string();
+            let payload = signing_payload("post", "/entries/pause", &timestamp, nonce, "ops", BODY);
+            Self { timestamp, nonce: nonce.to_string(), signature: sign(secret, &payload) }
+        }
+
+        fn request(&self) -> SignedRequest<'_> {
+            SignedRequest {
+                method: "POST",
+                path: "/entries/pause",
+                timestamp: Some(&self.timestamp),
+                nonce: Some(&self.nonce),
+                actor: Some("ops"),
+                signature: Some(&self.signature),
+                body: BODY,
+            }
+        }
+    }
+
+    fn verifier() -> RequestVerifier {
+        RequestVerifier::new(SECRET.to_vec(), 30)
+    }
+
+    #[test]
+    fn valid_signature_returns_the_actor() {
+        let signed = Signed::new(SECRET, NOW, "n-1");
+        assert_eq!(verifier().verify(&signed.request(), NOW), Ok("ops".to_string()));
+
+        // Clients may send the hex in upper case
+        let mut upper = Signed::new(SECRET, NOW, "n-2");
+        upper.signature = upper.signature.to_ascii_uppercase();
+        assert_eq!(verifier().verify(&upper.request(), NOW + 30), Ok("ops".to_string()));
+    }
+
+    #[test]
+    fn replayed_nonce_is_rejected() {
+        let verifier = verifier();
+        let signed = Signed::new(SECRET, NOW, "n-1");
+        assert!(verifier.verify(&signed.request(), NOW).is_ok());
+        assert_eq!(verifier.verify(&signed.request(), NOW + 1), Err(AuthRejection::Replayed));
+
+        // The same nonce with a fresh timestamp is a different request
+        let later = Signed::new(SECRET, NOW + 5, "n-1");
+        assert!(verifier.verify(&later.request(), NOW + 5).is_ok());
+        // Once out of the window, the timestamp check still stops the replay
+        assert_eq!(verifier.verify(&signed.request(), NOW + 31), Err(AuthRejection::Expired));
+    }
+
+    #[test]
+    fn stale_or_future_timestamp_is_rejected() {
+        let verifier = verifier();
+        let stale = Signed::new(SECRET, NOW - 31, "n-1");
+        assert_eq!(verifier.verify(&stale.request(), NOW), Err(AuthRejection::Expired));
+        let future = Signed::new(SECRET, NOW + 31, "n-2");
+        assert_eq!(verifier.verify(&future.request(), NOW), Err(AuthRejection::Expired));
+
+        let mut garbled = Signed::new(SECRET, NOW, "n-3");
+        garbled.timestamp = "yesterday".to_string();
+        assert_eq!(verifier.verify(&garbled.request(), NOW), Err(AuthRejection::BadTimestamp));
+    }
+
+    #[test]
+    fn bad_signature_is_rejected_without_burning_the_nonce() {
+        let verifier = verifier();
+        let forged = Signed::new(b"wrong secret", NOW, "n-1");
+        assert_eq!(verifier.verify(&forged.request(), NOW), Err(AuthRejection::BadSignature));
+
+        // A tampered body or path no longer matches the signature
+        let signed = Signed::new(SECRET, NOW, "n-1");
+        let tampered = SignedRequest { body: br#"{"paused":false}"#, ..signed.request() };
+        assert_eq!(verifier.verify(&tampered, NOW), Err(AuthRejection::BadSignature));
+        let moved = SignedRequest { path: "/liquidate", ..signed.request() };
+        assert_eq!(verifier.verify(&moved, NOW), Err(AuthRejection::BadSignature));
+
+        assert!(verifier.verify(&signed.request(), NOW).is_ok());
+    }
+
+    #[test]
+    fn missing_headers_are_rejected() {
+        let signed = Signed::new(SECRET, NOW, "n-1");
+        let verifier = verifier();
+        let no_signature = SignedRequest { signature: None, ..signed.request() };
+        assert_eq!(verifier.verify(&no_signature, NOW), Err(AuthRejection::MissingHeader));
+        let empty_nonce = SignedRequest { nonce: Some(""), ..signed.request() };
+        assert_eq!(verifier.verify(&empty_nonce, NOW), Err(AuthRejection::MissingHeader));
+    }
+
+    #[test]
+    fn config_hash_follows_the_controls() {
+        let controls = RuntimeControls { entries_paused: false, liquidating: false, leader: true };
+        let same = controls.clone();
+        let paused = RuntimeControls { entries_paused: true, ..controls.clone() };
+        assert_eq!(controls.hash(), same.hash());
+        assert_ne!(controls.hash(), paused.hash());
+        assert_eq!(controls.hash().len(), 16);
+    }
+}
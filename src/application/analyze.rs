@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{TimeZone, Utc};
+
+use crate::common::format::{fmt_sol, fmt_token};
+use crate::core::ids::OpportunityId;
+use crate::record::{
+    journal::JournalEntry,
+    math_checks::MathCheck,
+    rejections::RejectionRecord,
+    spreads::SpreadSummary,
+    store::{RecordStore, TradeRecord},
+    trace::assemble_trace,
+};
+
+fn format_time(millis: i64) -> String {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+fn format_entry(entry: &JournalEntry) -> String {
+    let tags = entry
+        .tags
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{}  [{} note] {}{}",
+        format_time(entry.created_at),
+        entry.source,
+        entry.note,
+        if tags.is_empty() { String::new() } else { format!("  ({})", tags) }
+    )
+}
+
+fn format_trade(trade: &TradeRecord) -> String {
+    format!(
+        "{}  {} {}  {}  tokens: {}  price: {}  trade: {}",
+        format_time(trade.created_at),
+        trade.side.to_uppercase(),
+        trade.mint,
+        trade.sol_amount.map(fmt_sol).unwrap_or_else(|| "-".to_string()),
+        trade.token_amount.map(|v| fmt_token(v, &trade.mint)).unwrap_or_else(|| "-".to_string()),
+        trade.price.map(|v| format!("{:.9}", v)).unwrap_or_else(|| "-".to_string()),
+        trade.trade_id,
+    )
+}
+
+/// Print trades in time order with journal notes next to the trades they refer to
+///
+/// With a mint only that token's trades and notes are shown; session notes are
+/// always included so operator interventions stay visible.
+pub fn print_trade_journal(store: &RecordStore, mint: Option<&str>) -> Result<()> {
+    let trades = store.trades(mint)?;
+    let mut notes = store.journal_for_session()?;
+    match mint {
+        Some(mint) => notes.extend(store.journal_by_mint(mint)?),
+        None => {
+            let mut mints: Vec<&str> = trades.iter().map(|t| t.mint.as_str()).collect();
+            mints.sort();
+            mints.dedup();
+            for mint in mints {
+                notes.extend(store.journal_by_mint(mint)?);
+            }
+        }
+    }
+
+    // Notes on a specific trade print under that trade, the rest interleave by time
+    let (trade_notes, mut timeline_notes): (Vec<JournalEntry>, Vec<JournalEntry>) =
+        notes.into_iter().partition(|n| n.trade_id.is_some());
+    timeline_notes.sort_by_key(|n| (n.created_at, n.id));
+
+    let mut pending = timeline_notes.into_iter().peekable();
+    for trade in &trades {
+        while let Some(note) = pending.next_if(|n| n.created_at <= trade.created_at) {
+            println!("{}", format_entry(&note));
+        }
+        println!("{}", format_trade(trade));
+        for note in trade_notes.iter().filter(|n| n.trade_id.as_deref() == Some(trade.trade_id.as_str())) {
+            println!("    {}", format_entry(note));
+        }
+    }
+    for note in pending {
+        println!("{}", format_entry(&note));
+    }
+
+    Ok(())
+}
+
+/// Upper bounds of the "distance from profitability" buckets, in bps
+const MISS_BUCKETS: [f64; 6] = [0.0, 5.0, 10.0, 25.0, 50.0, 100.0];
+
+fn bucket_label(index: usize) -> String {
+    match index {
+        0 => "profitable".to_string(),
+        i if i < MISS_BUCKETS.len() => format!("{}-{} bps", MISS_BUCKETS[i - 1], MISS_BUCKETS[i]),
+        _ => format!(">{} bps", MISS_BUCKETS[MISS_BUCKETS.len() - 1]),
+    }
+}
+
+fn miss_histogram<'a>(rejections: impl Iterator<Item = &'a RejectionRecord>) -> [usize; MISS_BUCKETS.len() + 1] {
+    let mut counts = [0; MISS_BUCKETS.len() + 1];
+    for r in rejections {
+        let index = MISS_BUCKETS
+            .iter()
+            .position(|bound| r.miss_bps <= *bound)
+            .unwrap_or(MISS_BUCKETS.len());
+        counts[index] += 1;
+    }
+    counts
+}
+
+fn print_histogram(title: &str, counts: &[usize]) {
+    let total: usize = counts.iter().sum();
+    let widest = counts.iter().copied().max().unwrap_or(0).max(1);
+    println!("{} ({} rejected)", title, total);
+    for (index, count) in counts.iter().enumerate() {
+        if *count == 0 {
+            continue;
+        }
+        let bar = "#".repeat((count * 40).div_ceil(widest));
+        println!("    {:>14}  {:>7}  {}", bucket_label(index), count, bar);
+    }
+}
+
+/// Aggregate rejected opportunities by cause and token, with how far each
+/// missed break-even and how many a cheaper cost component would have cleared
+pub fn print_rejection_report(store: &RecordStore, mint: Option<&str>) -> Result<()> {
+    let rejections = store.rejections(mint)?;
+    if rejections.is_empty() {
+        println!("No rejected opportunities recorded");
+        return Ok(());
+    }
+
+    println!("== Distance from profitability by cause ==");
+    let mut by_reason: BTreeMap<&str, Vec<&RejectionRecord>> = BTreeMap::new();
+    for r in &rejections {
+        by_reason.entry(r.reason.as_str()).or_default().push(r);
+    }
+    for (reason, group) in &by_reason {
+        print_histogram(reason, &miss_histogram(group.iter().copied()));
+    }
+
+    println!();
+    println!("== Distance from profitability by token ==");
+    let mut by_token: BTreeMap<&str, Vec<&RejectionRecord>> = BTreeMap::new();
+    for r in &rejections {
+        by_token.entry(r.token.as_str()).or_default().push(r);
+    }
+    for (token, group) in &by_token {
+        print_histogram(token, &miss_histogram(group.iter().copied()));
+    }
+
+    // Only fee misses can be fixed by spending less; gated ones already cleared fees
+    let unprofitable: Vec<&RejectionRecord> = rejections.iter().filter(|r| r.miss_bps > 0.0).collect();
+    if unprofitable.is_empty() {
+        return Ok(());
+    }
+    println!();
+    println!("== Rejections cleared by cutting one cost ({} unprofitable) ==", unprofitable.len());
+    println!("    {:>12}  {:>8}  {:>8}  {:>8}", "cost", "-25%", "-50%", "-100%");
+    let components: [(&str, fn(&RejectionRecord) -> f64); 4] = [
+        ("dex fees", |r| r.dex_fee_bps),
+        ("priority fee", |r| r.priority_fee_bps),
+        ("tip", |r| r.tip_bps),
+        ("conversion", |r| r.conversion_bps),
+    ];
+    for (name, component) in components {
+        let cleared = |cut: f64| {
+            unprofitable
+                .iter()
+                .filter(|r| component(r) * cut >= r.miss_bps)
+                .count()
+        };
+        println!("    {:>12}  {:>8}  {:>8}  {:>8}", name, cleared(0.25), cleared(0.5), cleared(1.0));
+    }
+
+    Ok(())
+}
+
+/// Daily roll-up of hourly spread summaries, one row per token per UTC day
+///
+/// p50 is the count-weighted mean of the hourly medians; p95 and max are the
+/// highest hourly values, so the day reads as a conservative upper bound.
+pub fn print_spread_report(store: &RecordStore, mint: Option<&str>) -> Result<()> {
+    let summaries = store.spread_summaries(mint)?;
+    if summaries.is_empty() {
+        println!("No spread summaries recorded");
+        return Ok(());
+    }
+
+    let mut by_token: BTreeMap<&str, BTreeMap<String, Vec<&SpreadSummary>>> = BTreeMap::new();
+    for s in &summaries {
+        let day = format_time(s.hour_start).chars().take(10).collect::<String>();
+        by_token.entry(s.token.as_str()).or_default().entry(day).or_default().push(s);
+    }
+
+    for (token, days) in &by_token {
+        println!("== {} ==", token);
+        println!("    {:>10}  {:>8}  {:>10}  {:>10}  {:>10}", "day", "scans", "p50 bps", "p95 bps", "max bps");
+        for (day, hours) in days {
+            let count: u64 = hours.iter().map(|h| h.count).sum();
+            let p50 = hours.iter().map(|h| h.p50_bps * h.count as f64).sum::<f64>() / count.max(1) as f64;
+            let p95 = hours.iter().map(|h| h.p95_bps).fold(0.0, f64::max);
+            let max = hours.iter().map(|h| h.max_bps).fold(0.0, f64::max);
+            println!("    {:>10}  {:>8}  {:>10.1}  {:>10.1}  {:>10.1}", day, count, p50, p95, max);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Programs copy targets used that no DEX adapter covers, most seen first
+pub fn print_unknown_programs(store: &RecordStore) -> Result<()> {
+    let programs = store.unknown_programs()?;
+    if programs.is_empty() {
+        println!("No unknown programs recorded");
+        return Ok(());
+    }
+
+    println!("{:<44}  {:>6}  {:>7}  {:>19}  {:>19}", "program", "seen", "skipped", "first seen", "last seen");
+    for p in &programs {
+        println!(
+            "{:<44}  {:>6}  {:>7}  {:>19}  {:>19}",
+            p.program_id, p.seen_count, p.skipped_count, format_time(p.first_seen), format_time(p.last_seen)
+        );
+    }
+    Ok(())
+}
+
+/// Daily accuracy of local swap math against simulation, per venue
+pub fn print_math_report(store: &RecordStore) -> Result<()> {
+    let checks = store.math_checks(None)?;
+    if checks.is_empty() {
+        println!("No math checks recorded; set MATH_VERIFY_SAMPLE_EVERY to sample swaps");
+        return Ok(());
+    }
+
+    let mut by_venue: BTreeMap<&str, BTreeMap<String, Vec<&MathCheck>>> = BTreeMap::new();
+    for c in &checks {
+        let day = format_time(c.checked_at).chars().take(10).collect::<String>();
+        by_venue.entry(c.venue.as_str()).or_default().entry(day).or_default().push(c);
+    }
+
+    for (venue, days) in &by_venue {
+        println!("== {} ==", venue);
+        println!("    {:>10}  {:>6}  {:>10}  {:>10}  {:>10}", "day", "checks", "mean bps", "p95 |bps|", "max |bps|");
+        for (day, day_checks) in days {
+            let mean = day_checks.iter().map(|c| c.rel_error).sum::<f64>() / day_checks.len() as f64;
+            let mut abs: Vec<f64> = day_checks.iter().map(|c| c.rel_error.abs()).collect();
+            abs.sort_by(f64::total_cmp);
+            let p95 = abs[((abs.len() as f64 * 0.95).ceil() as usize).clamp(1, abs.len()) - 1];
+            let max = abs[abs.len() - 1];
+            println!(
+                "    {:>10}  {:>6}  {:>+10.1}  {:>10.1}  {:>10.1}",
+                day, day_checks.len(), mean * 10_000.0, p95 * 10_000.0, max * 10_000.0
+            );
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Print the timeline of one opportunity, given its id or one of its trade ids
+pub fn print_trace(store: &RecordStore, id: &str, record_dir: &Path) -> Result<()> {
+    let opportunity_id = OpportunityId::parse(id).ok_or_else(|| anyhow!("Not an opportunity or trade id: {}", id))?;
+    let events = assemble_trace(store, &opportunity_id, record_dir)?;
+    if events.is_empty() {
+        println!("Nothing recorded for {}", opportunity_id);
+        return Ok(());
+    }
+    println!("== {} ==", opportunity_id);
+    for event in events {
+        println!("{}  {:<12} {}", format_time(event.at), event.source, event.detail);
+    }
+    Ok(())
+}
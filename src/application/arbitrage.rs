@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+
+use prometheus::Histogram;
+use serde::Serialize;
+
+use crate::common::metrics::SPREAD_BPS;
+use crate::core::price::{BookTouch, Price};
+use crate::dex::dex_registry::DEXRegistry;
+use crate::dex::pump_swap::SOL_MINT;
+use crate::engine::pool_discovery::PoolInfo;
+use crate::record::rejections::RejectionRecord;
+use crate::record::spreads::SpreadSummary;
+
+const HOUR_MS: i64 = 3_600_000;
+
+/// Histogram label for tokens outside the configured list
+const OTHER_TOKEN_LABEL: &str = "other";
+
+/// Reason an arbitrage opportunity was dropped before execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Trade size is below the minimum of the buy or sell venue
+    BelowVenueMinimum,
+    /// The arbitrage allocation can't fund the trade size
+    InsufficientCapital,
+    /// One side's pool holds less liquidity than the configured minimum
+    LiquidityShortfall,
+    /// One side's price is too many slots behind the newest observed price
+    StalePrice,
+    /// The spread doesn't cover DEX fees, priority fee and tip
+    BelowNetProfit,
+    /// One side's pool scores below `POOL_MIN_HEALTH`
+    PoolUnhealthy,
+    /// Re-reading the pools before executing left too little to clear the threshold
+    StaleOpportunity,
+    /// The pools could not be re-read before executing
+    PreflightFailed,
+}
+
+impl SkipReason {
+    /// Stable code used in logs and opportunity records
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BelowVenueMinimum => "below_venue_minimum",
+            Self::InsufficientCapital => "insufficient_capital",
+            Self::LiquidityShortfall => "liquidity_shortfall",
+            Self::StalePrice => "stale_price",
+            Self::BelowNetProfit => "below_net_profit",
+            Self::PoolUnhealthy => "pool_unhealthy",
+            Self::StaleOpportunity => "stale_opportunity",
+            Self::PreflightFailed => "preflight_failed",
+        }
+    }
+}
+
+/// Check a trade size against the minimums of both venues of an opportunity
+pub fn check_venue_minimums(
+    registry: &DEXRegistry,
+    buy_dex: &str,
+    sell_dex: &str,
+    amount_in_lamports: u64,
+) -> Result<(), SkipReason> {
+    let minimum = registry
+        .min_trade_in_lamports(buy_dex)
+        .max(registry.min_trade_in_lamports(sell_dex));
+
+    if amount_in_lamports < minimum {
+        return Err(SkipReason::BelowVenueMinimum);
+    }
+
+    Ok(())
+}
+
+/// What a venue fills at on each side: a pool at its price either way, an
+/// order book at the ask when buying and the bid when selling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VenueQuote {
+    pub buy: f64,
+    pub sell: f64,
+}
+
+impl VenueQuote {
+    pub fn new(price: Price, touch: Option<&BookTouch>) -> Self {
+        match touch {
+            Some(touch) => Self { buy: touch.ask.value(), sell: touch.bid.value() },
+            None => Self { buy: price.value(), sell: price.value() },
+        }
+    }
+}
+
+/// Direction with the wider executable spread between two venues, as
+/// (buy on `first`, buy price, sell price). The spread is negative when a
+/// book's touch straddles the other venue's price.
+pub fn best_direction(first: VenueQuote, second: VenueQuote) -> (bool, f64, f64) {
+    if second.sell - first.buy >= first.sell - second.buy {
+        (true, first.buy, second.sell)
+    } else {
+        (false, second.buy, first.sell)
+    }
+}
+
+/// Gross spread and every cost taken out of it, in basis points of the trade size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ProfitBreakdown {
+    pub gross_spread_bps: f64,
+    /// Swap fees of the buy and sell venues
+    pub dex_fee_bps: f64,
+    /// Signature fee plus compute unit price times limit, for both legs
+    pub priority_fee_bps: f64,
+    /// Jito tip, zero when not sending through Jito
+    pub tip_bps: f64,
+    /// Extra swap when the pair isn't quoted in SOL; every pair the scanner
+    /// prices today is, so this stays zero until non-SOL quotes are tracked
+    pub conversion_bps: f64,
+}
+
+impl ProfitBreakdown {
+    /// Fixed lamport costs are spread over `trade_size_lamports`
+    pub fn new(
+        buy_price: f64,
+        sell_price: f64,
+        dex_fee_bps: u64,
+        priority_fee_lamports: u64,
+        tip_lamports: u64,
+        trade_size_lamports: u64,
+    ) -> Self {
+        let size = trade_size_lamports.max(1) as f64;
+        Self {
+            gross_spread_bps: (sell_price - buy_price) / buy_price * 10_000.0,
+            dex_fee_bps: dex_fee_bps as f64,
+            // One transaction per leg
+            priority_fee_bps: 2.0 * priority_fee_lamports as f64 / size * 10_000.0,
+            tip_bps: tip_lamports as f64 / size * 10_000.0,
+            conversion_bps: 0.0,
+        }
+    }
+
+    pub fn cost_bps(&self) -> f64 {
+        self.dex_fee_bps + self.priority_fee_bps + self.tip_bps + self.conversion_bps
+    }
+
+    pub fn net_bps(&self) -> f64 {
+        self.gross_spread_bps - self.cost_bps()
+    }
+
+    /// Whether what is left after every cost beats `threshold_pct`,
+    /// `ARBITRAGE_THRESHOLD`; a negative threshold still asks for a profit
+    pub fn clears(&self, threshold_pct: f64) -> bool {
+        self.net_bps() > (threshold_pct * 100.0).max(0.0)
+    }
+}
+
+/// SOL and token reserves of a constant-product pool, in raw units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reserves {
+    pub sol: u64,
+    pub token: u64,
+}
+
+/// Swap fees of the two legs, in basis points of each leg's input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegFees {
+    pub buy_bps: u64,
+    pub sell_bps: u64,
+}
+
+/// Best size for a buy-then-sell across two pools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OptimalSize {
+    pub amount_in_lamports: u64,
+    /// SOL back from the sell less SOL in, after both swap fees
+    pub expected_profit_lamports: u64,
+    /// From the constant-product closed form; false when searched over quotes
+    pub closed_form: bool,
+}
+
+/// Input that maximizes profit when buying the token in `buy` and selling
+/// it in `sell`; None when no size is profitable
+///
+/// Chained through both pools, `a` lamports in come back as
+/// `C·a / (A + B·a)` with `A = x1·y2`, `B = g1·(y2 + g2·y1)` and
+/// `C = g1·g2·y1·x2`, where `x` is a pool's SOL reserve, `y` its token
+/// reserve and `g` one minus its fee. Setting the derivative to one gives
+/// `a* = (sqrt(A·C) - A) / B`, positive only when `C > A`.
+pub fn optimal_arbitrage_amount(buy: Reserves, sell: Reserves, fees: LegFees) -> Option<OptimalSize> {
+    if [buy.sol, buy.token, sell.sol, sell.token].contains(&0) {
+        return None;
+    }
+    let g1 = 1.0 - fees.buy_bps.min(10_000) as f64 / 10_000.0;
+    let g2 = 1.0 - fees.sell_bps.min(10_000) as f64 / 10_000.0;
+    let (x1, y1) = (buy.sol as f64, buy.token as f64);
+    let (x2, y2) = (sell.sol as f64, sell.token as f64);
+    let a = x1 * y2;
+    let b = g1 * (y2 + g2 * y1);
+    let c = g1 * g2 * y1 * x2;
+    if c <= a || b <= 0.0 {
+        return None;
+    }
+    let amount_in = ((a * c).sqrt() - a) / b;
+    let profit = c * amount_in / (a + b * amount_in) - amount_in;
+    (amount_in >= 1.0 && profit >= 1.0).then(|| OptimalSize {
+        amount_in_lamports: amount_in as u64,
+        expected_profit_lamports: profit as u64,
+        closed_form: true,
+    })
+}
+
+/// How a cached pool fills, in lamports of SOL and in tokens counted at
+/// one lamport per lamport of value at the pool's price; the scale cancels
+/// out across the two legs
+#[derive(Debug, Clone, Copy)]
+enum Curve {
+    ConstantProduct { sol: f64, token: f64 },
+    /// Quoted venues fill at their touch up to the depth behind it
+    Quoted { ask: f64, bid: f64, depth: f64 },
+}
+
+impl Curve {
+    fn of(pool: &PoolInfo) -> Option<Self> {
+        let sol_per_token = |price: f64| if pool.base_mint == SOL_MINT { 1.0 / price } else { price };
+        if let Some(book) = pool.book {
+            let (ask, bid) = (sol_per_token(book.ask), sol_per_token(book.bid));
+            // Inverting a SOL-base book swaps its sides
+            let (ask, bid) = if ask < bid { (bid, ask) } else { (ask, bid) };
+            return (ask > 0.0 && bid > 0.0 && book.depth > 0 && ask.is_finite())
+                .then(|| Self::Quoted { ask, bid, depth: book.depth as f64 });
+        }
+        let price = sol_per_token(pool.last_known_price?);
+        let sol = pool.liquidity.filter(|l| *l > 0)? as f64;
+        (price > 0.0 && price.is_finite()).then(|| Self::ConstantProduct { sol, token: sol / price })
+    }
+
+    /// Tokens for `lamports` after a fee leaving `g` of the input
+    fn buy(&self, lamports: f64, g: f64) -> f64 {
+        match *self {
+            Self::ConstantProduct { sol, token } => token * g * lamports / (sol + g * lamports),
+            Self::Quoted { ask, depth, .. } => g * lamports.min(depth) / ask,
+        }
+    }
+
+    /// Lamports for `tokens` after a fee leaving `g` of the input
+    fn sell(&self, tokens: f64, g: f64) -> f64 {
+        match *self {
+            Self::ConstantProduct { sol, token } => sol * g * tokens / (token + g * tokens),
+            Self::Quoted { bid, depth, .. } => (g * tokens * bid).min(depth),
+        }
+    }
+}
+
+/// Profit-maximizing input for buying in `buy_pool` and selling in
+/// `sell_pool` as cached, no more than `cap_lamports`; None when the pools
+/// lack what to size from or no size is profitable
+///
+/// Two constant-product pools are sized by [`optimal_arbitrage_amount`],
+/// and a peak past the cap is traded at the cap. With a quoted venue on
+/// either side, profit is still single-peaked in the input, and is ternary
+/// searched instead.
+pub fn compute_optimal_size(buy_pool: &PoolInfo, sell_pool: &PoolInfo, fees: LegFees, cap_lamports: u64) -> Option<OptimalSize> {
+    let (buy, sell) = (Curve::of(buy_pool)?, Curve::of(sell_pool)?);
+    let g1 = 1.0 - fees.buy_bps.min(10_000) as f64 / 10_000.0;
+    let g2 = 1.0 - fees.sell_bps.min(10_000) as f64 / 10_000.0;
+    let profit = |amount_in: f64| sell.sell(buy.buy(amount_in, g1), g2) - amount_in;
+    let cap = cap_lamports as f64;
+    if cap < 1.0 {
+        return None;
+    }
+
+    let (amount_in, closed_form) = match (buy, sell) {
+        (Curve::ConstantProduct { sol: x1, token: y1 }, Curve::ConstantProduct { sol: x2, token: y2 }) => {
+            let reserves = |sol: f64, token: f64| Reserves { sol: sol as u64, token: token as u64 };
+            let optimum = optimal_arbitrage_amount(reserves(x1, y1), reserves(x2, y2), fees)?;
+            if optimum.amount_in_lamports <= cap_lamports {
+                return Some(optimum);
+            }
+            (cap, true)
+        }
+        _ => {
+            let (mut lo, mut hi) = (1.0_f64, cap);
+            for _ in 0..100 {
+                if hi - lo < 1.0 {
+                    break;
+                }
+                let (m1, m2) = (lo + (hi - lo) / 3.0, hi - (hi - lo) / 3.0);
+                if profit(m1) < profit(m2) {
+                    lo = m1;
+                } else {
+                    hi = m2;
+                }
+            }
+            ((lo + hi) / 2.0, false)
+        }
+    };
+    let amount_in = amount_in.min(cap);
+    let expected = profit(amount_in);
+    (amount_in >= 1.0 && expected >= 1.0).then(|| OptimalSize {
+        amount_in_lamports: amount_in as u64,
+        expected_profit_lamports: expected as u64,
+        closed_form,
+    })
+}
+
+/// An opportunity that cleared the spread threshold but was not executed
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub token: String,
+    pub buy_dex: String,
+    pub sell_dex: String,
+    pub reason: SkipReason,
+    pub breakdown: ProfitBreakdown,
+    /// Lamports of liquidity missing on the thinner side, for `LiquidityShortfall`
+    pub liquidity_shortfall: Option<u64>,
+}
+
+impl Rejection {
+    /// How far net profit fell short of zero, in bps; zero when the
+    /// opportunity was profitable and a gate stopped it instead
+    pub fn miss_bps(&self) -> f64 {
+        (-self.breakdown.net_bps()).max(0.0)
+    }
+
+    pub fn to_record(&self, created_at: i64) -> RejectionRecord {
+        RejectionRecord {
+            created_at,
+            token: self.token.clone(),
+            buy_dex: self.buy_dex.clone(),
+            sell_dex: self.sell_dex.clone(),
+            reason: self.reason.code().to_string(),
+            gross_spread_bps: self.breakdown.gross_spread_bps,
+            dex_fee_bps: self.breakdown.dex_fee_bps,
+            priority_fee_bps: self.breakdown.priority_fee_bps,
+            tip_bps: self.breakdown.tip_bps,
+            conversion_bps: self.breakdown.conversion_bps,
+            net_bps: self.breakdown.net_bps(),
+            miss_bps: self.miss_bps(),
+            liquidity_shortfall: self.liquidity_shortfall,
+        }
+    }
+}
+
+/// Per-token samples for the current hour, reused across hours
+struct TokenSpreads {
+    histogram: Histogram,
+    samples: Vec<f64>,
+}
+
+/// Best spread per token per scan, fed to the `arbitrage_spread_bps` histogram
+/// and rolled up into hourly summaries
+///
+/// Sample buffers keep their capacity between hours, so once every token has
+/// been seen a scan records without allocating.
+pub struct SpreadTracker {
+    configured: Vec<String>,
+    tokens: HashMap<String, TokenSpreads>,
+    hour_start: i64,
+}
+
+impl SpreadTracker {
+    /// `configured` tokens get their own histogram label, the rest share "other"
+    pub fn new(configured: Vec<String>, now_ms: i64) -> Self {
+        Self {
+            configured,
+            tokens: HashMap::new(),
+            hour_start: now_ms - now_ms.rem_euclid(HOUR_MS),
+        }
+    }
+
+    pub fn record(&mut self, token: &str, spread_bps: f64) {
+        if !self.tokens.contains_key(token) {
+            let label = if self.configured.iter().any(|t| t == token) { token } else { OTHER_TOKEN_LABEL };
+            let histogram = SPREAD_BPS.with_label_values(&[label]);
+            self.tokens.insert(token.to_string(), TokenSpreads { histogram, samples: Vec::new() });
+        }
+        if let Some(entry) = self.tokens.get_mut(token) {
+            entry.histogram.observe(spread_bps);
+            entry.samples.push(spread_bps);
+        }
+    }
+
+    /// Summaries for the finished hour once `now_ms` has moved past it
+    pub fn flush_due(&mut self, now_ms: i64) -> Option<Vec<SpreadSummary>> {
+        if now_ms < self.hour_start + HOUR_MS {
+            return None;
+        }
+        let hour_start = self.hour_start;
+        self.hour_start = now_ms - now_ms.rem_euclid(HOUR_MS);
+
+        let mut summaries = Vec::new();
+        for (token, entry) in self.tokens.iter_mut() {
+            if entry.samples.is_empty() {
+                continue;
+            }
+            entry.samples.sort_by(|a, b| a.total_cmp(b));
+            let at = |q: f64| entry.samples[((entry.samples.len() - 1) as f64 * q).round() as usize];
+            summaries.push(SpreadSummary {
+                hour_start,
+                token: token.clone(),
+                count: entry.samples.len() as u64,
+                p50_bps: at(0.5),
+                p95_bps: at(0.95),
+                max_bps: at(1.0),
+            });
+            entry.samples.clear();
+        }
+        Some(summaries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::pool_discovery::BookSnapshot;
+
+    const NO_FEES: LegFees = LegFees { buy_bps: 0, sell_bps: 0 };
+
+    /// SOL back from buying with `amount` lamports and selling every token
+    /// received, swap by swap rather than through the closed form
+    fn round_trip(buy: Reserves, sell: Reserves, fees: LegFees, amount: f64) -> f64 {
+        let into_buy = amount * (1.0 - fees.buy_bps as f64 / 10_000.0);
+        let tokens = buy.token as f64 * into_buy / (buy.sol as f64 + into_buy);
+        let into_sell = tokens * (1.0 - fees.sell_bps as f64 / 10_000.0);
+        sell.sol as f64 * into_sell / (sell.token as f64 + into_sell)
+    }
+
+    #[test]
+    fn optimum_matches_the_hand_worked_example() {
+        // 100 SOL / 100 tokens against 400 SOL / 100 tokens, no fees:
+        // A = 100·100, B = 100 + 100, C = 100·400, so
+        // a* = (sqrt(A·C) - A) / B = (20000 - 10000) / 200 = 50 SOL, which
+        // buys 33.3 tokens that sell for 100 SOL: 50 SOL profit
+        let buy = Reserves { sol: 100_000_000_000, token: 100_000_000_000 };
+        let sell = Reserves { sol: 400_000_000_000, token: 100_000_000_000 };
+        let size = optimal_arbitrage_amount(buy, sell, NO_FEES).unwrap();
+        assert!(size.closed_form);
+        assert!(size.amount_in_lamports.abs_diff(50_000_000_000) <= 1, "{:?}", size);
+        assert!(size.expected_profit_lamports.abs_diff(50_000_000_000) <= 1, "{:?}", size);
+
+        // Either side of it earns less
+        let profit = |amount: f64| round_trip(buy, sell, NO_FEES, amount) - amount;
+        let best = profit(size.amount_in_lamports as f64);
+        assert!(best > profit(45_000_000_000.0) && best > profit(55_000_000_000.0));
+    }
+
+    #[test]
+    fn fees_shrink_the_optimum_and_its_profit() {
+        let buy = Reserves { sol: 100_000_000_000, token: 100_000_000_000 };
+        let sell = Reserves { sol: 400_000_000_000, token: 100_000_000_000 };
+        let fees = LegFees { buy_bps: 25, sell_bps: 30 };
+        let size = optimal_arbitrage_amount(buy, sell, fees).unwrap();
+        assert!(size.amount_in_lamports < 50_000_000_000);
+        assert!(size.expected_profit_lamports < 50_000_000_000);
+
+        // The reported profit is what the two swaps pay back at that size
+        let amount = size.amount_in_lamports as f64;
+        let profit = round_trip(buy, sell, fees, amount) - amount;
+        assert!((profit - size.expected_profit_lamports as f64).abs() < 2.0, "{} vs {:?}", profit, size);
+    }
+
+    #[test]
+    fn a_gross_spread_eaten_by_fees_is_filtered_out() {
+        let registry = DEXRegistry::new();
+        // 1.6% between a 1% fee DLMM pair and a 0.25% Raydium pool
+        let fees = registry.pool_fee_bps("meteora_dlmm", Some(100)) + registry.pool_fee_bps("raydium_amm", None);
+        assert_eq!(fees, 125);
+        let size = 1_000_000_000;
+        let breakdown = ProfitBreakdown::new(1.0, 1.016, fees, 5_000 + 100_000, 0, size);
+        assert!((breakdown.gross_spread_bps - 160.0).abs() < 1e-9);
+        // 160 - 125 - 2 legs of 1.05 bps network fee leaves 32.9 bps
+        assert!((breakdown.net_bps() - 32.9).abs() < 1e-9, "{:?}", breakdown);
+        assert!(breakdown.clears(0.3));
+        assert!(!breakdown.clears(0.5));
+
+        // The same gap with both pools at 1% costs more than it makes
+        let fees = registry.pool_fee_bps("meteora_dlmm", Some(100)) * 2;
+        let breakdown = ProfitBreakdown::new(1.0, 1.016, fees, 5_000 + 100_000, 0, size);
+        assert!(breakdown.gross_spread_bps > 0.0 && breakdown.net_bps() < 0.0);
+        assert!(!breakdown.clears(0.0));
+        assert!(!breakdown.clears(-1.0));
+    }
+
+    fn cached_pool(price: f64, liquidity: u64, book: Option<BookSnapshot>) -> PoolInfo {
+        PoolInfo {
+            pool_id: "pool".to_string(),
+            dex_name: "pumpswap".to_string(),
+            base_mint: "mint".to_string(),
+            quote_mint: SOL_MINT.to_string(),
+            last_known_price: Some(price),
+            last_updated: None,
+            liquidity: Some(liquidity),
+            subtype: None,
+            book,
+            health: None,
+            fee_bps: None,
+        }
+    }
+
+    /// Best profit over every `step` lamports up to `cap`, swap by swap
+    fn sweep(buy: &PoolInfo, sell: &PoolInfo, fees: LegFees, cap: u64, step: u64) -> (u64, f64) {
+        let (buy, sell) = (Curve::of(buy).unwrap(), Curve::of(sell).unwrap());
+        let g1 = 1.0 - fees.buy_bps as f64 / 10_000.0;
+        let g2 = 1.0 - fees.sell_bps as f64 / 10_000.0;
+        (step..=cap)
+            .step_by(step as usize)
+            .map(|amount| (amount, sell.sell(buy.buy(amount as f64, g1), g2) - amount as f64))
+            .fold((0, f64::MIN), |best, next| if next.1 > best.1 { next } else { best })
+    }
+
+    #[test]
+    fn constant_product_size_is_the_brute_force_maximum() {
+        let fees = LegFees { buy_bps: 25, sell_bps: 30 };
+        let cases = [
+            // 2% apart on 50 SOL pools, 5% apart with a thin sell side, 1% on deep pools
+            (cached_pool(0.000_001, 50_000_000_000, None), cached_pool(0.000_001_02, 50_000_000_000, None)),
+            (cached_pool(0.000_001, 80_000_000_000, None), cached_pool(0.000_001_05, 10_000_000_000, None)),
+            (cached_pool(0.000_002, 900_000_000_000, None), cached_pool(0.000_002_02, 700_000_000_000, None)),
+        ];
+        let cap = 20_000_000_000;
+        for (buy, sell) in &cases {
+            let size = compute_optimal_size(buy, sell, fees, cap).unwrap();
+            assert!(size.closed_form);
+            let (best_amount, best_profit) = sweep(buy, sell, fees, cap, 10_000_000);
+            // The sweep's grid brackets the optimum within one step
+            assert!(size.amount_in_lamports.abs_diff(best_amount) <= 10_000_000, "{:?} vs {}", size, best_amount);
+            assert!(size.expected_profit_lamports as f64 >= best_profit - 1.0, "{:?} vs {}", size, best_profit);
+        }
+    }
+
+    #[test]
+    fn a_peak_past_the_cap_is_traded_at_the_cap() {
+        let fees = LegFees { buy_bps: 25, sell_bps: 25 };
+        let (buy, sell) = (cached_pool(0.000_001, 50_000_000_000, None), cached_pool(0.000_001_02, 50_000_000_000, None));
+        let uncapped = compute_optimal_size(&buy, &sell, fees, u64::MAX).unwrap();
+        let cap = uncapped.amount_in_lamports / 2;
+        let capped = compute_optimal_size(&buy, &sell, fees, cap).unwrap();
+        assert_eq!(capped.amount_in_lamports, cap);
+        assert!(capped.expected_profit_lamports < uncapped.expected_profit_lamports);
+        let (_, best_profit) = sweep(&buy, &sell, fees, cap, cap / 1_000);
+        assert!(capped.expected_profit_lamports as f64 >= best_profit - 1.0);
+    }
+
+    #[test]
+    fn quoted_venues_are_searched_to_the_brute_force_maximum() {
+        let fees = LegFees { buy_bps: 10, sell_bps: 25 };
+        // A book 3% under the pool's price, 4 SOL deep at its touch
+        let book = BookSnapshot { bid: 0.000_000_969, ask: 0.000_000_97, depth: 4_000_000_000, slot: 1 };
+        let buy = cached_pool(0.000_000_97, 1, Some(book));
+        let sell = cached_pool(0.000_001, 60_000_000_000, None);
+        let cap = 10_000_000_000;
+        let size = compute_optimal_size(&buy, &sell, fees, cap).unwrap();
+        assert!(!size.closed_form);
+        let (best_amount, best_profit) = sweep(&buy, &sell, fees, cap, 10_000_000);
+        assert!(size.amount_in_lamports.abs_diff(best_amount) <= 10_000_000, "{:?} vs {}", size, best_amount);
+        assert!(size.expected_profit_lamports as f64 >= best_profit - 1.0, "{:?} vs {}", size, best_profit);
+    }
+
+    #[test]
+    fn no_size_pays_without_a_gap_wider_than_the_fees() {
+        let pool = Reserves { sol: 100_000_000_000, token: 100_000_000_000 };
+        assert_eq!(optimal_arbitrage_amount(pool, pool, NO_FEES), None);
+        // A 0.4% gap doesn't cover 25 bps on each leg
+        let sell = Reserves { sol: 100_400_000_000, token: 100_000_000_000 };
+        assert_eq!(optimal_arbitrage_amount(pool, sell, LegFees { buy_bps: 25, sell_bps: 25 }), None);
+        assert!(optimal_arbitrage_amount(pool, sell, NO_FEES).is_some());
+        assert_eq!(optimal_arbitrage_amount(Reserves { sol: 0, token: 1 }, sell, NO_FEES), None);
+    }
+}
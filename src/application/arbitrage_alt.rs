@@ -0,0 +1,193 @@
+//! The address lookup table arbitrage transactions compile against
+//!
+//! Two legs name both pools, their vaults, configs and fee accounts and the
+//! token programs, which often doesn't fit a legacy message. Set
+//! `ARBITRAGE_ALT` to a table and the atomic transaction falls back to a v0
+//! message over it, where each account it holds costs one byte instead of
+//! 32. The table is read over RPC and reused for `TABLE_TTL`.
+//!
+//! When the wallet is the table's authority, accounts an arbitrage used that
+//! the table lacks are appended in the background, so the table fills with
+//! the pools actually traded. `alt create` and `alt extend` do the same by
+//! hand.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{
+    address_lookup_table::{instruction, state::AddressLookupTable, AddressLookupTableAccount},
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    transaction::Transaction,
+};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use tokio::time::Instant;
+
+use crate::common::{config::AppState, logger::Logger};
+use crate::common::sync::LockExt;
+use crate::engine::blockhash_cache;
+use crate::services::rpc_pool::CallClass;
+
+/// How long a fetched table is used before it is read again
+const TABLE_TTL: Duration = Duration::from_secs(60);
+
+/// Addresses per extend transaction, which keeps it under the packet limit
+const EXTEND_CHUNK: usize = 20;
+
+/// A table holds at most this many addresses
+const MAX_TABLE_ADDRESSES: usize = 256;
+
+lazy_static::lazy_static! {
+    static ref CACHED: Mutex<Option<(Instant, AddressLookupTableAccount)>> = Mutex::new(None);
+    // One background extension at a time
+    static ref EXTENDING: AtomicBool = AtomicBool::new(false);
+}
+
+/// `ARBITRAGE_ALT`, None when unset
+pub fn configured_table() -> Result<Option<Pubkey>> {
+    match std::env::var("ARBITRAGE_ALT") {
+        Ok(raw) if !raw.trim().is_empty() => Pubkey::from_str(raw.trim())
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid ARBITRAGE_ALT {}: {}", raw, e)),
+        _ => Ok(None),
+    }
+}
+
+/// The configured table, from cache while fresh; empty when none is set
+/// or it can't be read, so the caller falls back to a legacy message
+pub async fn lookup_tables(app_state: &AppState, logger: &Logger) -> Vec<AddressLookupTableAccount> {
+    let table = match configured_table() {
+        Ok(Some(table)) => table,
+        Ok(None) => return Vec::new(),
+        Err(e) => {
+            logger.log(format!("[ALT] => {}", e).red().to_string());
+            return Vec::new();
+        }
+    };
+    if let Some((fetched_at, account)) = CACHED.lock_or_recover().as_ref() {
+        if account.key == table && fetched_at.elapsed() < TABLE_TTL {
+            return vec![account.clone()];
+        }
+    }
+    match fetch_table(app_state, table).await {
+        Ok(account) => {
+            *CACHED.lock_or_recover() = Some((Instant::now(), account.clone()));
+            vec![account]
+        }
+        Err(e) => {
+            logger.log(format!("[ALT] => Reading {} failed: {}", table, e).red().to_string());
+            Vec::new()
+        }
+    }
+}
+
+/// `table`'s addresses and authority as they are on-chain
+async fn read_table(app_state: &AppState, table: Pubkey) -> Result<(AddressLookupTableAccount, Option<Pubkey>)> {
+    let account = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| async move { client.get_account(&table).await })
+        .await?;
+    let state = AddressLookupTable::deserialize(&account.data)?;
+    let authority = state.meta.authority;
+    Ok((AddressLookupTableAccount { key: table, addresses: state.addresses.to_vec() }, authority))
+}
+
+async fn fetch_table(app_state: &AppState, table: Pubkey) -> Result<AddressLookupTableAccount> {
+    Ok(read_table(app_state, table).await?.0)
+}
+
+/// Create a table the wallet is authority of and pays for; returns its address
+pub async fn create_table(app_state: &AppState, logger: &Logger) -> Result<Pubkey> {
+    let wallet = app_state.wallet.pubkey();
+    // The table address derives from a recent slot, finalized so it is never on a dropped fork
+    let recent_slot = app_state
+        .rpc_pool
+        .call(CallClass::Confirm, |client| async move {
+            client.get_slot_with_commitment(anchor_client::solana_sdk::commitment_config::CommitmentConfig::finalized()).await
+        })
+        .await?;
+    let (create, table) = instruction::create_lookup_table(wallet, wallet, recent_slot);
+    let signature = send_and_confirm(app_state, vec![create], logger).await?;
+    logger.log(format!("[ALT] => Created {} ({})", table, signature).green().to_string());
+    Ok(table)
+}
+
+/// Append `addresses` the table doesn't have yet; the wallet must be its
+/// authority. Returns the extend transactions' signatures.
+pub async fn extend_table(app_state: &AppState, table: Pubkey, addresses: &[Pubkey], logger: &Logger) -> Result<Vec<Signature>> {
+    let wallet = app_state.wallet.pubkey();
+    let (current, authority) = read_table(app_state, table).await?;
+    if authority != Some(wallet) {
+        return Err(anyhow!("{} is not the authority of {}", wallet, table));
+    }
+    let known: HashSet<Pubkey> = current.addresses.iter().copied().collect();
+    let mut seen = HashSet::new();
+    let missing: Vec<Pubkey> = addresses.iter().copied().filter(|a| !known.contains(a) && seen.insert(*a)).collect();
+    if current.addresses.len() + missing.len() > MAX_TABLE_ADDRESSES {
+        return Err(anyhow!(
+            "{} holds {} addresses, {} more would pass the {} limit",
+            table, current.addresses.len(), missing.len(), MAX_TABLE_ADDRESSES
+        ));
+    }
+
+    let mut signatures = Vec::new();
+    for chunk in missing.chunks(EXTEND_CHUNK) {
+        let extend = instruction::extend_lookup_table(table, wallet, Some(wallet), chunk.to_vec());
+        signatures.push(send_and_confirm(app_state, vec![extend], logger).await?);
+    }
+    if !missing.is_empty() {
+        logger.log(format!("[ALT] => Added {} addresses to {}", missing.len(), table).green().to_string());
+        CACHED.lock_or_recover().take();
+    }
+    Ok(signatures)
+}
+
+/// Accounts in `instructions` worth a table slot: not signers, which a v0
+/// message has to list in full anyway
+pub fn table_candidates(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut seen = HashSet::new();
+    instructions
+        .iter()
+        .flat_map(|ix| {
+            std::iter::once(ix.program_id).chain(ix.accounts.iter().filter(|meta| !meta.is_signer).map(|meta| meta.pubkey))
+        })
+        .filter(|key| seen.insert(*key))
+        .collect()
+}
+
+/// Extend the configured table in the background with what `instructions`
+/// use and it lacks; quietly does nothing when the wallet isn't its authority
+pub fn learn_accounts(app_state: &Arc<AppState>, tables: &[AddressLookupTableAccount], instructions: &[Instruction], logger: &Logger) {
+    let Some(table) = tables.first() else {
+        return;
+    };
+    let known: HashSet<&Pubkey> = table.addresses.iter().collect();
+    let missing: Vec<Pubkey> = table_candidates(instructions).into_iter().filter(|key| !known.contains(key)).collect();
+    if missing.is_empty() || EXTENDING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let (app_state, table, logger) = (Arc::clone(app_state), table.key, logger.clone());
+    tokio::spawn(async move {
+        if let Err(e) = extend_table(&app_state, table, &missing, &logger).await {
+            logger.debug_with(|| format!("[ALT] => Not extending {}: {}", table, e));
+        }
+        EXTENDING.store(false, Ordering::SeqCst);
+    });
+}
+
+async fn send_and_confirm(app_state: &AppState, instructions: Vec<Instruction>, logger: &Logger) -> Result<Signature> {
+    let recent_blockhash = blockhash_cache::recent(app_state, CallClass::Confirm, logger).await?;
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&app_state.wallet.pubkey()),
+        &[app_state.wallet.as_ref()],
+        recent_blockhash,
+    );
+    Ok(app_state.rpc_nonblocking_client.send_and_confirm_transaction(&transaction).await?)
+}
@@ -0,0 +1,99 @@
+//! Per-owner balance changes of a streamed transaction
+//!
+//! What a wallet traded is read off what it ended up holding rather than the
+//! logs: SOL from the lamports of its own account, tokens from every token
+//! account it owns, however many. A token account missing from one side
+//! counts as empty there, which covers an ATA created or closed by the same
+//! transaction. Wrapped SOL is folded into its owner's SOL, so wrapping
+//! before a swap and unwrapping or closing the account after it net out, and
+//! only the SOL that went through the pool and any rent remain.
+
+use std::collections::HashMap;
+
+use yellowstone_grpc_proto::prelude::TokenBalance;
+
+use crate::engine::lookup_tables::{resolve_account_keys, static_keys};
+use crate::engine::swap::SwapDirection;
+
+const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+/// Signed balance changes per owner over one transaction
+#[derive(Debug, Clone, Default)]
+pub struct BalanceDeltas {
+    /// Lamports, wrapped SOL included
+    sol: HashMap<String, i64>,
+    /// Raw token amounts per (owner, mint), wrapped SOL excluded
+    tokens: HashMap<(String, String), i128>,
+    decimals: HashMap<String, u32>,
+}
+
+impl BalanceDeltas {
+    /// Lamports `owner` gained, negative when it spent SOL
+    pub fn sol(&self, owner: &str) -> i64 {
+        self.sol.get(owner).copied().unwrap_or(0)
+    }
+
+    /// Raw amount of `mint` `owner` gained, negative when it gave tokens up
+    pub fn token(&self, owner: &str, mint: &str) -> i128 {
+        self.tokens.get(&(owner.to_string(), mint.to_string())).copied().unwrap_or(0)
+    }
+
+    /// [`Self::token`] in whole tokens
+    pub fn token_ui(&self, owner: &str, mint: &str) -> f64 {
+        let decimals = self.decimals.get(mint).copied().unwrap_or(0);
+        self.token(owner, mint) as f64 / 10f64.powi(decimals as i32)
+    }
+
+    /// Buy when `owner` gained `mint` for SOL, sell when it gave `mint` up
+    /// for SOL; None for anything else, such as a transfer
+    pub fn direction(&self, owner: &str, mint: &str) -> Option<SwapDirection> {
+        match (self.token(owner, mint).signum(), self.sol(owner).signum()) {
+            (1, -1) => Some(SwapDirection::Buy),
+            (-1, 1) => Some(SwapDirection::Sell),
+            _ => None,
+        }
+    }
+}
+
+/// Diff a transaction's pre and post balances by owner
+pub fn compute_balance_deltas(transaction: &yellowstone_grpc_proto::geyser::ConfirmedTransaction) -> BalanceDeltas {
+    let mut deltas = BalanceDeltas::default();
+    let Some(meta) = &transaction.meta else {
+        return deltas;
+    };
+
+    // Balances index the full account list; wallets are static keys, so
+    // an unresolved lookup table only costs the looked-up accounts
+    let keys = transaction
+        .transaction
+        .as_ref()
+        .and_then(|t| t.message.as_ref())
+        .map(|message| resolve_account_keys(message, Some(meta)).unwrap_or_else(|| static_keys(message)))
+        .unwrap_or_default();
+    for ((key, pre), post) in keys.iter().zip(&meta.pre_balances).zip(&meta.post_balances) {
+        let change = *post as i64 - *pre as i64;
+        if change != 0 {
+            *deltas.sol.entry(key.to_string()).or_default() += change;
+        }
+    }
+
+    let mut add_token_balances = |balances: &[TokenBalance], sign: i128| {
+        for balance in balances {
+            let Some(ui) = &balance.ui_token_amount else {
+                continue;
+            };
+            let Ok(amount) = ui.amount.parse::<i128>() else {
+                continue;
+            };
+            if balance.mint == WSOL_MINT {
+                *deltas.sol.entry(balance.owner.clone()).or_default() += (sign * amount) as i64;
+            } else {
+                deltas.decimals.insert(balance.mint.clone(), ui.decimals);
+                *deltas.tokens.entry((balance.owner.clone(), balance.mint.clone())).or_default() += sign * amount;
+            }
+        }
+    };
+    add_token_balances(&meta.pre_token_balances, -1);
+    add_token_balances(&meta.post_token_balances, 1);
+    deltas
+}
@@ -0,0 +1,222 @@
+//! Recent blockhashes from the stream, so sends skip the RPC round trip
+//!
+//! Every streamed transaction carries the blockhash its sender signed
+//! against, and a copied or sniped transaction is by definition a recent
+//! one. The cache keeps the newest of those along with the slot it was seen
+//! in, and a send takes it when it is at most `BLOCKHASH_MAX_AGE_SLOTS`
+//! behind the newest slot seen. Only when the stream is quiet or behind does
+//! a send fall back to `getLatestBlockhash`; the background refresh does the
+//! same every `BLOCKHASH_REFRESH_SECS` while the cache is stale, so a send
+//! rarely has to. Fallbacks are counted on `/metrics` and logged with their
+//! running total.
+//!
+//! The slot a hash was seen in is not the slot it was produced in: a sender
+//! may sign against a hash a few seconds old. The age limit leaves room for
+//! that below the 150 slots a hash stays valid, and a sell whose hash
+//! expires before it lands is rebuilt against a fresh one.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_client::client_error::ClientError;
+use anchor_client::solana_sdk::hash::Hash;
+use colored::Colorize;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateTransaction;
+
+use crate::common::{config::AppState, logger::Logger, metrics::BLOCKHASH_CACHE_FALLBACKS};
+use crate::common::sync::LockExt;
+use crate::services::rpc_pool::CallClass;
+
+/// Slots a blockhash stays valid for after the slot it was produced in
+pub const MAX_PROCESSING_AGE: u64 = 150;
+
+/// Default for `BLOCKHASH_MAX_AGE_SLOTS`
+const DEFAULT_MAX_AGE_SLOTS: u64 = 60;
+
+/// Default for `BLOCKHASH_REFRESH_SECS`
+const DEFAULT_REFRESH: Duration = Duration::from_secs(10);
+
+/// Hashes whose first sighting is remembered for the expiry check
+const SEEN_CAPACITY: usize = 512;
+
+#[derive(Debug, Default)]
+struct Cached {
+    /// The hash seen in the highest slot, with that slot
+    newest: Option<(Hash, u64)>,
+    /// Highest slot the stream or RPC has reported
+    tip_slot: u64,
+    first_seen: HashMap<Hash, u64>,
+    order: VecDeque<Hash>,
+}
+
+#[derive(Debug)]
+pub struct BlockhashCache {
+    cached: Mutex<Cached>,
+    max_age_slots: u64,
+    fallbacks: AtomicU64,
+}
+
+impl BlockhashCache {
+    pub fn new(max_age_slots: u64) -> Self {
+        Self {
+            cached: Mutex::new(Cached::default()),
+            max_age_slots: max_age_slots.min(MAX_PROCESSING_AGE),
+            fallbacks: AtomicU64::new(0),
+        }
+    }
+
+    /// `BLOCKHASH_MAX_AGE_SLOTS`, at most 150
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("BLOCKHASH_MAX_AGE_SLOTS")
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(DEFAULT_MAX_AGE_SLOTS),
+        )
+    }
+
+    pub fn max_age_slots(&self) -> u64 {
+        self.max_age_slots
+    }
+
+    /// A transaction signed against `hash` was seen in `slot`
+    pub fn observe(&self, hash: Hash, slot: u64) {
+        let mut cached = self.cached.lock_or_recover();
+        cached.tip_slot = cached.tip_slot.max(slot);
+        if cached.newest.map_or(true, |(_, newest_slot)| slot >= newest_slot) {
+            cached.newest = Some((hash, slot));
+        }
+        if !cached.first_seen.contains_key(&hash) {
+            if cached.order.len() >= SEEN_CAPACITY {
+                if let Some(oldest) = cached.order.pop_front() {
+                    cached.first_seen.remove(&oldest);
+                }
+            }
+            cached.first_seen.insert(hash, slot);
+            cached.order.push_back(hash);
+        }
+    }
+
+    /// Take the hash a streamed transaction was signed against
+    pub fn observe_transaction(&self, txn: &SubscribeUpdateTransaction) {
+        let recent_blockhash = txn
+            .transaction
+            .as_ref()
+            .and_then(|info| info.transaction.as_ref())
+            .and_then(|transaction| transaction.message.as_ref())
+            .map(|message| message.recent_blockhash.as_slice());
+        if let Some(bytes) = recent_blockhash.filter(|bytes| bytes.len() == 32) {
+            self.observe(Hash::new(bytes), txn.slot);
+        }
+    }
+
+    /// The chain reached `slot`, which ages what is cached
+    pub fn note_slot(&self, slot: u64) {
+        let mut cached = self.cached.lock_or_recover();
+        cached.tip_slot = cached.tip_slot.max(slot);
+    }
+
+    /// The newest hash, if it was seen at most `max_age_slots` behind the tip
+    pub fn get(&self, max_age_slots: u64) -> Option<Hash> {
+        let cached = self.cached.lock_or_recover();
+        let (hash, slot) = cached.newest?;
+        (cached.tip_slot.saturating_sub(slot) <= max_age_slots).then_some(hash)
+    }
+
+    /// Whether `hash` was first seen long enough ago that nothing signed
+    /// against it can land anymore. Hashes never seen here, a durable
+    /// nonce's among them, are not judged.
+    pub fn expired(&self, hash: &Hash) -> bool {
+        let cached = self.cached.lock_or_recover();
+        cached
+            .first_seen
+            .get(hash)
+            .is_some_and(|seen| cached.tip_slot.saturating_sub(*seen) > MAX_PROCESSING_AGE)
+    }
+
+    /// Stop handing out `hash`, a node rejected it as unknown or expired
+    pub fn invalidate(&self, hash: &Hash) {
+        let mut cached = self.cached.lock_or_recover();
+        if cached.newest.is_some_and(|(newest, _)| newest == *hash) {
+            cached.newest = None;
+        }
+        if let Some(seen) = cached.first_seen.get_mut(hash) {
+            *seen = 0;
+        }
+    }
+
+    /// Fallbacks to RPC since start
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether a send error says the transaction's blockhash was no good
+pub fn is_expiry_error(error: &anyhow::Error) -> bool {
+    let message = format!("{:#}", error).to_ascii_lowercase();
+    ["blockhash not found", "blockhashnotfound", "block height exceeded"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+/// A hash for a transaction about to be signed: the cached one when fresh
+/// enough, otherwise one fetched over `class`
+pub async fn recent(app_state: &AppState, class: CallClass, logger: &Logger) -> anyhow::Result<Hash> {
+    let cache = &app_state.blockhashes;
+    if let Some(hash) = cache.get(cache.max_age_slots()) {
+        return Ok(hash);
+    }
+    let hash = fetch(app_state, class).await?;
+    let total = cache.fallbacks.fetch_add(1, Ordering::Relaxed) + 1;
+    BLOCKHASH_CACHE_FALLBACKS.with_label_values(&["send"]).inc();
+    logger.log(format!(
+        "[BLOCKHASH] => No streamed hash within {} slots, fetched one over RPC ({} fallbacks so far)",
+        cache.max_age_slots(), total
+    ).yellow().to_string());
+    Ok(hash)
+}
+
+/// `getLatestBlockhash` with the slot it was read at, cached for later sends
+async fn fetch(app_state: &AppState, class: CallClass) -> anyhow::Result<Hash> {
+    let (hash, slot) = app_state
+        .rpc_pool
+        .call(class, |client| async move {
+            let hash = client.get_latest_blockhash().await?;
+            let slot = client.get_slot().await?;
+            Ok::<_, ClientError>((hash, slot))
+        })
+        .await?;
+    app_state.blockhashes.observe(hash, slot);
+    Ok(hash)
+}
+
+/// `BLOCKHASH_REFRESH_SECS`, 10 seconds by default
+fn refresh_every() -> Duration {
+    std::env::var("BLOCKHASH_REFRESH_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_REFRESH)
+}
+
+/// Keep a usable hash cached while the stream doesn't provide one
+pub async fn run_refresh(app_state: Arc<AppState>) {
+    let logger = Logger::new("[BLOCKHASH] => ".cyan().bold().to_string());
+    let every = refresh_every();
+    loop {
+        let cache = &app_state.blockhashes;
+        if cache.get(cache.max_age_slots()).is_none() {
+            match fetch(&app_state, CallClass::Refresh).await {
+                Ok(_) => {
+                    BLOCKHASH_CACHE_FALLBACKS.with_label_values(&["refresh"]).inc();
+                    logger.debug_with(|| "[REFRESHED] => Stream hash stale, cached one from RPC".to_string());
+                }
+                Err(e) => logger.log(format!("[REFRESH FAILED] => {}", e).red().to_string()),
+            }
+        }
+        app_state.clock.sleep(app_state.rpc_pool.background_interval(every)).await;
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::signer::Signer;
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::common::{
+    config::AppState,
+    logger::Logger,
+    metrics::{CAPITAL_ALLOCATION, CAPITAL_EXPOSURE, CAPITAL_INVESTABLE},
+};
+use crate::common::sync::LockExt;
+use crate::services::rpc_pool::CallClass;
+
+/// Strategies that draw SOL from the shared wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    Arbitrage,
+    Copy,
+    Sniper,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 3] = [Strategy::Arbitrage, Strategy::Copy, Strategy::Sniper];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Arbitrage => "arbitrage",
+            Self::Copy => "copy",
+            Self::Sniper => "sniper",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
+
+/// Why a grant was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllocationError {
+    /// The strategy's allocation is used up, or what's left is below the minimum trade
+    Exhausted { strategy: Strategy, available: u64 },
+    /// The key already holds capital, release it first
+    AlreadyGranted(String),
+}
+
+impl std::fmt::Display for AllocationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exhausted { strategy, available } => {
+                write!(f, "{} allocation exhausted: {} lamports available", strategy.name(), available)
+            }
+            Self::AlreadyGranted(key) => write!(f, "Capital already granted for {}", key),
+        }
+    }
+}
+
+/// Per-strategy view for metrics and the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyAllocation {
+    pub strategy: Strategy,
+    pub weight_pct: u64,
+    pub allocation: u64,
+    pub exposure: u64,
+    pub open_grants: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapitalSnapshot {
+    pub wallet_lamports: u64,
+    pub reserve_lamports: u64,
+    pub fee_budget_lamports: u64,
+    pub investable: u64,
+    pub total_exposure: u64,
+    pub strategies: Vec<StrategyAllocation>,
+}
+
+#[derive(Debug, Default)]
+struct AllocatorState {
+    wallet_lamports: u64,
+    /// (strategy, key) -> lamports currently out, keyed by mint or opportunity id
+    grants: HashMap<(Strategy, String), u64>,
+}
+
+impl AllocatorState {
+    fn exposure(&self, strategy: Strategy) -> u64 {
+        self.grants
+            .iter()
+            .filter(|((s, _), _)| *s == strategy)
+            .map(|(_, amount)| *amount)
+            .sum()
+    }
+
+    fn total_exposure(&self) -> u64 {
+        self.grants.values().sum()
+    }
+}
+
+/// Owns the investable part of the wallet and splits it between strategies
+///
+/// Every grant and release happens under one lock, so total exposure never
+/// exceeds the investable balance no matter how strategies interleave.
+#[derive(Debug)]
+pub struct CapitalAllocator {
+    weights: HashMap<Strategy, u64>,
+    reserve_lamports: u64,
+    fee_budget_lamports: u64,
+    min_grant_lamports: u64,
+    state: Mutex<AllocatorState>,
+}
+
+impl CapitalAllocator {
+    pub fn new(
+        weights: HashMap<Strategy, u64>,
+        reserve_lamports: u64,
+        fee_budget_lamports: u64,
+        min_grant_lamports: u64,
+    ) -> Self {
+        Self {
+            weights,
+            reserve_lamports,
+            fee_budget_lamports,
+            min_grant_lamports,
+            state: Mutex::new(AllocatorState::default()),
+        }
+    }
+
+    /// `CAPITAL_WEIGHTS` ("arbitrage=50,copy=30,sniper=20"), `CAPITAL_RESERVE_LAMPORTS`,
+    /// `FEE_BUDGET_LAMPORTS` and `MIN_GRANT_LAMPORTS`
+    pub fn from_env() -> Self {
+        let weights = std::env::var("CAPITAL_WEIGHTS")
+            .ok()
+            .and_then(|v| parse_weights(&v))
+            .unwrap_or_else(|| {
+                HashMap::from([(Strategy::Arbitrage, 50), (Strategy::Copy, 30), (Strategy::Sniper, 20)])
+            });
+        let lamports = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| u64::from_str(&v).ok())
+                .unwrap_or(default)
+        };
+        Self::new(
+            weights,
+            lamports("CAPITAL_RESERVE_LAMPORTS", 50_000_000),
+            lamports("FEE_BUDGET_LAMPORTS", 20_000_000),
+            lamports("MIN_GRANT_LAMPORTS", 1_000_000),
+        )
+    }
+
+    fn investable_of(&self, wallet_lamports: u64) -> u64 {
+        wallet_lamports.saturating_sub(self.reserve_lamports + self.fee_budget_lamports)
+    }
+
+    fn allocation_of(&self, strategy: Strategy, investable: u64) -> u64 {
+        let total_weight: u64 = self.weights.values().sum();
+        if total_weight == 0 {
+            return 0;
+        }
+        let weight = self.weights.get(&strategy).copied().unwrap_or(0);
+        (investable as u128 * weight as u128 / total_weight as u128) as u64
+    }
+
+    /// Update the wallet balance the allocation is computed from.
+    /// Outstanding grants are kept; a shrinking balance only limits new grants.
+    pub fn set_wallet_balance(&self, wallet_lamports: u64) {
+        let mut state = self.state.lock_or_recover();
+        state.wallet_lamports = wallet_lamports;
+        self.publish(&state);
+    }
+
+    /// Lamports `strategy` could still be granted right now
+    pub fn available(&self, strategy: Strategy) -> u64 {
+        let state = self.state.lock_or_recover();
+        self.available_locked(&state, strategy)
+    }
+
+    fn available_locked(&self, state: &AllocatorState, strategy: Strategy) -> u64 {
+        let investable = self.investable_of(state.wallet_lamports);
+        let by_allocation = self
+            .allocation_of(strategy, investable)
+            .saturating_sub(state.exposure(strategy));
+        let by_total = investable.saturating_sub(state.total_exposure());
+        by_allocation.min(by_total)
+    }
+
+    /// Reserve up to `requested` lamports for `key`, downsizing to what the
+    /// strategy has left. Fails if that is below the minimum grant.
+    pub fn grant(&self, strategy: Strategy, key: &str, requested: u64) -> Result<u64, AllocationError> {
+        let mut state = self.state.lock_or_recover();
+        let grant_key = (strategy, key.to_string());
+        if state.grants.contains_key(&grant_key) {
+            return Err(AllocationError::AlreadyGranted(key.to_string()));
+        }
+
+        let available = self.available_locked(&state, strategy);
+        let granted = requested.min(available);
+        if granted == 0 || granted < self.min_grant_lamports.min(requested) {
+            return Err(AllocationError::Exhausted { strategy, available });
+        }
+
+        state.grants.insert(grant_key, granted);
+        self.publish(&state);
+        Ok(granted)
+    }
+
+    /// Return the capital held by `key` to the pool, returns the lamports released
+    pub fn release(&self, strategy: Strategy, key: &str) -> u64 {
+        let mut state = self.state.lock_or_recover();
+        let released = state.grants.remove(&(strategy, key.to_string())).unwrap_or(0);
+        self.publish(&state);
+        released
+    }
+
+    pub fn snapshot(&self) -> CapitalSnapshot {
+        let state = self.state.lock_or_recover();
+        self.snapshot_locked(&state)
+    }
+
+    fn snapshot_locked(&self, state: &AllocatorState) -> CapitalSnapshot {
+        let investable = self.investable_of(state.wallet_lamports);
+        let strategies = Strategy::ALL
+            .into_iter()
+            .map(|strategy| StrategyAllocation {
+                strategy,
+                weight_pct: self.weights.get(&strategy).copied().unwrap_or(0),
+                allocation: self.allocation_of(strategy, investable),
+                exposure: state.exposure(strategy),
+                open_grants: state.grants.keys().filter(|(s, _)| *s == strategy).count(),
+            })
+            .collect();
+
+        CapitalSnapshot {
+            wallet_lamports: state.wallet_lamports,
+            reserve_lamports: self.reserve_lamports,
+            fee_budget_lamports: self.fee_budget_lamports,
+            investable,
+            total_exposure: state.total_exposure(),
+            strategies,
+        }
+    }
+
+    fn publish(&self, state: &AllocatorState) {
+        let snapshot = self.snapshot_locked(state);
+        CAPITAL_INVESTABLE.set(snapshot.investable as i64);
+        for entry in &snapshot.strategies {
+            let name = entry.strategy.name();
+            CAPITAL_ALLOCATION.with_label_values(&[name]).set(entry.allocation as i64);
+            CAPITAL_EXPOSURE.with_label_values(&[name]).set(entry.exposure as i64);
+        }
+    }
+}
+
+/// Parse "arbitrage=50,copy=30,sniper=20"; None if any entry is malformed
+fn parse_weights(value: &str) -> Option<HashMap<Strategy, u64>> {
+    value
+        .split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| {
+            let (name, weight) = part.split_once('=')?;
+            let strategy = Strategy::from_name(name.trim())?;
+            let weight = u64::from_str(weight.trim()).ok()?;
+            Some((strategy, weight))
+        })
+        .collect()
+}
+
+/// Keep the allocator's wallet balance current; runs until the process exits
+pub async fn run_balance_refresh(app_state: Arc<AppState>, every: Duration) {
+    let logger = Logger::new("[CAPITAL] => ".cyan().bold().to_string());
+    let owner = app_state.wallet.pubkey();
+    loop {
+        match app_state
+            .rpc_pool
+            .call(CallClass::Refresh, |client| async move { client.get_balance(&owner).await })
+            .await
+        {
+            Ok(balance) => app_state.capital.set_wallet_balance(balance),
+            Err(e) => logger.log(format!("[BALANCE REFRESH FAILED] => {}", e).red().to_string()),
+        }
+        app_state.clock.sleep(app_state.rpc_pool.background_interval(every)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    const WALLET: u64 = 1_070_000_000;
+
+    fn allocator() -> CapitalAllocator {
+        let weights = HashMap::from([(Strategy::Arbitrage, 50), (Strategy::Copy, 30), (Strategy::Sniper, 20)]);
+        let allocator = CapitalAllocator::new(weights, 50_000_000, 20_000_000, 1_000_000);
+        allocator.set_wallet_balance(WALLET);
+        allocator
+    }
+
+    #[test]
+    fn grants_downsize_to_the_strategy_allocation() {
+        let allocator = allocator();
+        let snapshot = allocator.snapshot();
+        assert_eq!(snapshot.investable, 1_000_000_000);
+        assert_eq!(snapshot.strategies[1].allocation, 300_000_000);
+
+        assert_eq!(allocator.grant(Strategy::Copy, "a", 200_000_000), Ok(200_000_000));
+        assert_eq!(allocator.grant(Strategy::Copy, "b", 200_000_000), Ok(100_000_000));
+        assert_eq!(
+            allocator.grant(Strategy::Copy, "c", 5_000_000),
+            Err(AllocationError::Exhausted { strategy: Strategy::Copy, available: 0 })
+        );
+        assert_eq!(
+            allocator.grant(Strategy::Copy, "a", 1),
+            Err(AllocationError::AlreadyGranted("a".to_string()))
+        );
+        // Other strategies keep their share
+        assert_eq!(allocator.available(Strategy::Sniper), 200_000_000);
+
+        assert_eq!(allocator.release(Strategy::Copy, "a"), 200_000_000);
+        assert_eq!(allocator.release(Strategy::Copy, "a"), 0);
+        assert_eq!(allocator.available(Strategy::Copy), 200_000_000);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn concurrent_grants_and_releases_never_exceed_investable() {
+        let allocator = Arc::new(allocator());
+        let investable = allocator.snapshot().investable;
+        // What the tasks themselves believe is out, checked against the cap after every grant
+        let outstanding = Arc::new(AtomicU64::new(0));
+
+        let tasks = (0..64u64).map(|task| {
+            let allocator = Arc::clone(&allocator);
+            let outstanding = Arc::clone(&outstanding);
+            tokio::spawn(async move {
+                let strategy = Strategy::ALL[task as usize % 3];
+                for round in 0..200u64 {
+                    let key = format!("{}-{}", task, round);
+                    let requested = 10_000_000 + (task * 7_919 + round * 104_729) % 150_000_000;
+                    let Ok(granted) = allocator.grant(strategy, &key, requested) else {
+                        tokio::task::yield_now().await;
+                        continue;
+                    };
+                    assert!(granted <= requested);
+                    let out = outstanding.fetch_add(granted, Ordering::SeqCst) + granted;
+                    assert!(out <= investable, "{} lamports out of {}", out, investable);
+
+                    let snapshot = allocator.snapshot();
+                    assert!(snapshot.total_exposure <= snapshot.investable);
+                    for entry in &snapshot.strategies {
+                        assert!(entry.exposure <= entry.allocation, "{:?}", entry);
+                    }
+
+                    tokio::task::yield_now().await;
+                    outstanding.fetch_sub(granted, Ordering::SeqCst);
+                    assert_eq!(allocator.release(strategy, &key), granted);
+                }
+            })
+        });
+        for task in tasks.collect::<Vec<_>>() {
+            task.await.unwrap();
+        }
+
+        let snapshot = allocator.snapshot();
+        assert_eq!(snapshot.total_exposure, 0);
+        assert!(snapshot.strategies.iter().all(|entry| entry.open_grants == 0));
+    }
+
+    #[test]
+    fn weights_parse_or_fall_back_whole() {
+        let weights = parse_weights("arbitrage=60, copy=40").unwrap();
+        assert_eq!(weights, HashMap::from([(Strategy::Arbitrage, 60), (Strategy::Copy, 40)]));
+        assert_eq!(parse_weights("arbitrage=60,unknown=40"), None);
+        assert_eq!(parse_weights("copy=lots"), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Waiting for a sent transaction to land
+//!
+//! A relay accepting a transaction says nothing about whether it lands, so
+//! a buy is only marked `Bought` once `getSignatureStatuses` reports it
+//! confirmed without error, and `Failure` when it errors or the wait runs
+//! out. `OPTIMISTIC_CONFIRM` restores marking it on send, for users who
+//! would rather not hold the buying gate for a round trip.
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::common::clock::Clock;
+use crate::common::sync::LockExt;
+use crate::services::rpc_pool::{CallClass, RpcPool};
+
+/// Time between status polls
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default for `CONFIRM_TIMEOUT_SECS`
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What became of a sent transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Confirmation {
+    /// Confirmed without error
+    Confirmed,
+    /// Landed with an error, or the signature was malformed
+    Failed(String),
+    /// Not confirmed within the timeout
+    TimedOut,
+    /// Its blockhash expired while it was pending, so it can no longer land
+    Expired,
+}
+
+impl Confirmation {
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, Self::Confirmed)
+    }
+}
+
+/// One signature's status as far as confirmation cares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureState {
+    /// Unknown to the node, or not yet at confirmed commitment
+    Pending,
+    Confirmed,
+    Failed(String),
+}
+
+/// Where statuses are read from, mocked in tests
+#[async_trait]
+pub trait SignatureStatuses: Send + Sync {
+    async fn state(&self, signature: &Signature) -> Result<SignatureState>;
+}
+
+#[async_trait]
+impl SignatureStatuses for RpcPool<RpcClient> {
+    async fn state(&self, signature: &Signature) -> Result<SignatureState> {
+        let signature = *signature;
+        let response = self
+            .call(CallClass::Confirm, |client| async move { client.get_signature_statuses(&[signature]).await })
+            .await?;
+        Ok(match response.value.into_iter().next().flatten() {
+            Some(status) => match status.err {
+                Some(err) => SignatureState::Failed(err.to_string()),
+                None if status.satisfies_commitment(CommitmentConfig::confirmed()) => SignatureState::Confirmed,
+                None => SignatureState::Pending,
+            },
+            None => SignatureState::Pending,
+        })
+    }
+}
+
+/// Replays scripted states, repeating the last one once the script runs out
+#[derive(Debug, Default)]
+pub struct MockSignatureStatuses {
+    states: Mutex<VecDeque<Result<SignatureState, String>>>,
+}
+
+impl MockSignatureStatuses {
+    pub fn new(states: impl IntoIterator<Item = Result<SignatureState, String>>) -> Self {
+        Self { states: Mutex::new(states.into_iter().collect()) }
+    }
+}
+
+#[async_trait]
+impl SignatureStatuses for MockSignatureStatuses {
+    async fn state(&self, _signature: &Signature) -> Result<SignatureState> {
+        let mut states = self.states.lock_or_recover();
+        let next = if states.len() > 1 { states.pop_front() } else { states.front().cloned() };
+        next.unwrap_or(Ok(SignatureState::Pending)).map_err(anyhow::Error::msg)
+    }
+}
+
+/// Poll `signature` until it is confirmed, fails or `timeout` passes on
+/// `clock`. A status read that errors is retried on the next poll.
+pub async fn confirm_signature(
+    rpc: &dyn SignatureStatuses,
+    clock: &dyn Clock,
+    signature: &str,
+    timeout: Duration,
+) -> Confirmation {
+    confirm_before_expiry(rpc, clock, signature, timeout, &|| false).await
+}
+
+/// `confirm_signature`, giving up early once `expired` reports the
+/// transaction's blockhash too old for it to still land
+pub async fn confirm_before_expiry(
+    rpc: &dyn SignatureStatuses,
+    clock: &dyn Clock,
+    signature: &str,
+    timeout: Duration,
+    expired: &(dyn Fn() -> bool + Send + Sync),
+) -> Confirmation {
+    let Ok(signature) = Signature::from_str(signature) else {
+        return Confirmation::Failed(format!("invalid signature {}", signature));
+    };
+    let deadline = clock.now() + timeout;
+    loop {
+        match rpc.state(&signature).await {
+            Ok(SignatureState::Confirmed) => return Confirmation::Confirmed,
+            Ok(SignatureState::Failed(err)) => return Confirmation::Failed(err),
+            Ok(SignatureState::Pending) | Err(_) => {}
+        }
+        if expired() {
+            return Confirmation::Expired;
+        }
+        let now = clock.now();
+        if now >= deadline {
+            return Confirmation::TimedOut;
+        }
+        clock.sleep(POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+/// `OPTIMISTIC_CONFIRM`: treat a sent buy as bought without waiting
+pub fn optimistic() -> bool {
+    std::env::var("OPTIMISTIC_CONFIRM")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// `CONFIRM_TIMEOUT_SECS`, 30 seconds by default
+pub fn timeout() -> Duration {
+    std::env::var("CONFIRM_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::clock::MockClock;
+    use crate::common::config::AppState;
+    use crate::services::notifier::Notifier;
+
+    fn signature() -> String {
+        Signature::new_unique().to_string()
+    }
+
+    #[tokio::test]
+    async fn pending_until_confirmed() {
+        let clock = MockClock::new();
+        let started = clock.now();
+        let rpc = MockSignatureStatuses::new([
+            Ok(SignatureState::Pending),
+            Err("node behind".to_string()),
+            Ok(SignatureState::Confirmed),
+        ]);
+        let outcome = confirm_signature(&rpc, &clock, &signature(), Duration::from_secs(10)).await;
+        assert_eq!(outcome, Confirmation::Confirmed);
+        // A failed read is just another poll
+        assert_eq!(clock.now() - started, POLL_INTERVAL * 2);
+    }
+
+    #[tokio::test]
+    async fn a_landed_error_fails_at_once() {
+        let clock = MockClock::new();
+        let rpc = MockSignatureStatuses::new([Ok(SignatureState::Failed("custom program error: 0x1772".to_string()))]);
+        let outcome = confirm_signature(&rpc, &clock, &signature(), Duration::from_secs(10)).await;
+        assert_eq!(outcome, Confirmation::Failed("custom program error: 0x1772".to_string()));
+        assert!(!outcome.is_confirmed());
+    }
+
+    #[tokio::test]
+    async fn never_confirmed_times_out_on_the_clock() {
+        let clock = MockClock::new();
+        let started = clock.now();
+        let rpc = MockSignatureStatuses::new([Ok(SignatureState::Pending)]);
+        let outcome = confirm_signature(&rpc, &clock, &signature(), Duration::from_millis(1_200)).await;
+        assert_eq!(outcome, Confirmation::TimedOut);
+        assert_eq!(clock.now() - started, Duration::from_millis(1_200));
+    }
+
+    #[tokio::test]
+    async fn an_expired_blockhash_stops_the_wait() {
+        let clock = MockClock::new();
+        let started = clock.now();
+        let rpc = MockSignatureStatuses::new([Ok(SignatureState::Pending)]);
+        let outcome = confirm_before_expiry(&rpc, &clock, &signature(), Duration::from_secs(10), &|| true).await;
+        assert_eq!(outcome, Confirmation::Expired);
+        assert_eq!(clock.now(), started);
+
+        let malformed = confirm_signature(&rpc, &clock, "not-a-signature", Duration::from_secs(10)).await;
+        assert!(matches!(malformed, Confirmation::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn statuses_are_read_through_the_rpc_pool() {
+        // The mock node reports every signature finalized, or landed with an
+        // instruction error, or unknown
+        let confirm = |mock: &str| {
+            let app_state = AppState::for_tests_with_rpc(mock, Notifier::new(None));
+            async move {
+                confirm_signature(app_state.rpc_pool.as_ref(), app_state.clock.as_ref(), &signature(), Duration::from_secs(2)).await
+            }
+        };
+        assert_eq!(confirm("succeeds").await, Confirmation::Confirmed);
+        assert!(matches!(confirm("instruction_error").await, Confirmation::Failed(_)));
+        assert_eq!(confirm("sig_not_found").await, Confirmation::TimedOut);
+    }
+}
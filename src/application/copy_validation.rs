@@ -0,0 +1,462 @@
+use std::str::FromStr;
+
+use anchor_client::solana_client::rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use anchor_client::solana_sdk::{
+    hash::Hash, instruction::Instruction, program_pack::Pack, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    transaction::Transaction,
+};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use spl_associated_token_account::get_associated_token_address;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateTransaction;
+
+use crate::common::{config::AppState, logger::Logger};
+use crate::dex::dex_registry::DEXRegistry;
+use crate::services::rpc_pool::CallClass;
+
+/// Programs every swap touches that say nothing about the venue
+const INFRASTRUCTURE_PROGRAMS: [&str; 6] = [
+    "11111111111111111111111111111111",             // System
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",  // SPL Token
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",  // Token-2022
+    "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL", // Associated Token Account
+    "ComputeBudget111111111111111111111111111111",  // Compute Budget
+    "MemoSq4gqABAXKb96qAEuj1yXWqsDqFMAXNwpAtvZL2",  // Memo
+];
+
+/// Why a copy over an unfamiliar program was not mirrored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopySkip {
+    /// The target's own token or SOL change couldn't be read from its transaction
+    NoTargetDelta,
+    /// Our transaction failed in simulation
+    SimulationFailed,
+    /// Our simulated tokens per SOL are too far from what the target got
+    DeltaMismatch,
+}
+
+impl CopySkip {
+    /// Stable code used in logs
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoTargetDelta => "no_target_delta",
+            Self::SimulationFailed => "simulation_failed",
+            Self::DeltaMismatch => "delta_mismatch",
+        }
+    }
+}
+
+/// `UNKNOWN_PROGRAM_TOLERANCE_PCT`: how far our simulated tokens per SOL may
+/// be from the target's before the copy is skipped
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationConfig {
+    pub tolerance: f64,
+}
+
+impl ValidationConfig {
+    pub fn from_env() -> Self {
+        let pct = std::env::var("UNKNOWN_PROGRAM_TOLERANCE_PCT")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .unwrap_or(10.0);
+        Self { tolerance: pct / 100.0 }
+    }
+}
+
+/// What one side of the comparison did: tokens received for lamports spent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapDelta {
+    pub tokens: u64,
+    pub lamports: u64,
+}
+
+impl SwapDelta {
+    fn tokens_per_lamport(&self) -> Option<f64> {
+        (self.tokens > 0 && self.lamports > 0).then(|| self.tokens as f64 / self.lamports as f64)
+    }
+}
+
+/// Top-level programs of a transaction that are neither infrastructure nor a registered DEX
+pub fn unknown_programs(registry: &DEXRegistry, txn: &SubscribeUpdateTransaction) -> Vec<Pubkey> {
+    let Some(message) = txn
+        .transaction
+        .as_ref()
+        .and_then(|t| t.transaction.as_ref())
+        .and_then(|t| t.message.as_ref())
+    else {
+        return Vec::new();
+    };
+    let mut unknown = Vec::new();
+    for instruction in &message.instructions {
+        let Some(program_id) = message
+            .account_keys
+            .get(instruction.program_id_index as usize)
+            .and_then(|bytes| Pubkey::try_from(bytes.as_slice()).ok())
+        else {
+            continue;
+        };
+        let infrastructure = INFRASTRUCTURE_PROGRAMS
+            .iter()
+            .any(|p| Pubkey::from_str(p).map(|p| p == program_id).unwrap_or(false));
+        if !infrastructure && registry.find_dex_by_program_id(&program_id).is_none() && !unknown.contains(&program_id) {
+            unknown.push(program_id);
+        }
+    }
+    unknown
+}
+
+/// Raw amount of `mint` held by `owner` across a balance list
+fn token_holding(balances: &[yellowstone_grpc_proto::prelude::TokenBalance], owner: &str, mint: &str) -> u64 {
+    balances
+        .iter()
+        .filter(|b| b.owner == owner && b.mint == mint)
+        .filter_map(|b| b.ui_token_amount.as_ref()?.amount.parse::<u64>().ok())
+        .sum()
+}
+
+/// Tokens of `mint` the target received in its transaction
+pub fn observed_token_delta(txn: &SubscribeUpdateTransaction, owner: &str, mint: &str) -> Option<u64> {
+    let meta = txn.transaction.as_ref()?.meta.as_ref()?;
+    let before = token_holding(&meta.pre_token_balances, owner, mint);
+    let after = token_holding(&meta.post_token_balances, owner, mint);
+    after.checked_sub(before).filter(|delta| *delta > 0)
+}
+
+/// Accept when our tokens per lamport are within `tolerance` of the target's,
+/// returning the relative deviation
+pub fn compare_deltas(target: SwapDelta, ours: SwapDelta, tolerance: f64) -> Result<f64, CopySkip> {
+    let target_rate = target.tokens_per_lamport().ok_or(CopySkip::NoTargetDelta)?;
+    let our_rate = ours.tokens_per_lamport().ok_or(CopySkip::DeltaMismatch)?;
+    let deviation = (our_rate / target_rate - 1.0).abs();
+    if deviation <= tolerance {
+        Ok(deviation)
+    } else {
+        Err(CopySkip::DeltaMismatch)
+    }
+}
+
+fn token_amount(data: &[u8]) -> Option<u64> {
+    spl_token::state::Account::unpack(data).ok().map(|account| account.amount)
+}
+
+/// Tokens our `instructions` would add to the wallet's token account for `mint`
+pub async fn simulate_token_delta(
+    app_state: &AppState,
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    mint: &Pubkey,
+    recent_blockhash: Hash,
+) -> Result<u64> {
+    let ata = get_associated_token_address(&keypair.pubkey(), mint);
+    let (before, after) =
+        simulate_token_account(app_state, CallClass::Quote, keypair, instructions, &ata, recent_blockhash).await?;
+    Ok(after.saturating_sub(before))
+}
+
+/// Balance of token account `account` now and after simulating `instructions`,
+/// with both reads paced as `class`
+pub async fn simulate_token_account(
+    app_state: &AppState,
+    class: CallClass,
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    account: &Pubkey,
+    recent_blockhash: Hash,
+) -> Result<(u64, u64)> {
+    let account = *account;
+    let before = app_state
+        .rpc_pool
+        .call(class, |client| async move {
+            client.get_account_with_commitment(&account, client.commitment()).await
+        })
+        .await?
+        .value
+        .and_then(|account| token_amount(&account.data))
+        .unwrap_or(0);
+
+    let txn = Transaction::new_signed_with_payer(instructions, Some(&keypair.pubkey()), &[keypair], recent_blockhash);
+    let result = app_state
+        .rpc_pool
+        .call(class, |client| {
+            let txn = txn.clone();
+            let config = RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: vec![account.to_string()],
+                }),
+                ..RpcSimulateTransactionConfig::default()
+            };
+            async move { client.simulate_transaction_with_config(&txn, config).await }
+        })
+        .await?;
+    if let Some(err) = result.value.err {
+        return Err(anyhow!("Simulation returned error: {}", err));
+    }
+    let after = result
+        .value
+        .accounts
+        .and_then(|accounts| accounts.into_iter().next().flatten())
+        .and_then(|account| account.data.decode())
+        .and_then(|data| token_amount(&data))
+        .ok_or_else(|| anyhow!("Simulation did not return token account {}", account))?;
+    Ok((before, after))
+}
+
+/// `SIMULATE_SWAPS`: simulate copy buys and exit sells before sending them
+/// and drop the ones that would fail
+#[derive(Debug, Clone, Copy)]
+pub struct SimulationConfig {
+    pub enabled: bool,
+}
+
+impl SimulationConfig {
+    pub fn from_env() -> Self {
+        let enabled = std::env::var("SIMULATE_SWAPS")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        Self { enabled }
+    }
+}
+
+/// What a simulated swap would do
+#[derive(Debug, Clone, Copy)]
+pub struct SwapSimulation {
+    pub units_consumed: Option<u64>,
+    /// Change in the wallet's native SOL, fees not included
+    pub sol_change: i64,
+}
+
+/// Simulate `instructions` signed by `keypair` and log the compute units and
+/// the wallet's SOL change; a program error comes back as the error
+pub async fn simulate_and_check(
+    app_state: &AppState,
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    recent_blockhash: Hash,
+    logger: &Logger,
+) -> Result<SwapSimulation> {
+    let wallet = keypair.pubkey();
+    let before = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| async move { client.get_balance(&wallet).await })
+        .await?;
+
+    let txn = Transaction::new_signed_with_payer(instructions, Some(&wallet), &[keypair], recent_blockhash);
+    let result = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| {
+            let txn = txn.clone();
+            let config = RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: vec![wallet.to_string()],
+                }),
+                ..RpcSimulateTransactionConfig::default()
+            };
+            async move { client.simulate_transaction_with_config(&txn, config).await }
+        })
+        .await?
+        .value;
+    let units = result.units_consumed.map_or("?".to_string(), |units| units.to_string());
+    if let Some(err) = result.err {
+        // The program's own message is usually the last log line
+        let reason = result.logs.and_then(|logs| logs.last().cloned()).unwrap_or_default();
+        logger.log(format!("[SIMULATION] => {} units, failed: {} {}", units, err, reason).red().to_string());
+        return Err(anyhow!("simulation failed: {}", err));
+    }
+    let after = result
+        .accounts
+        .and_then(|accounts| accounts.into_iter().next().flatten())
+        .map(|account| account.lamports)
+        .unwrap_or(before);
+    let sol_change = after as i64 - before as i64;
+    logger.log(format!("[SIMULATION] => {} units, SOL change {} lamports", units, sol_change).cyan().to_string());
+    Ok(SwapSimulation { units_consumed: result.units_consumed, sol_change })
+}
+
+/// Simulate our copy and compare it with what the target got
+pub async fn validate_copy(
+    app_state: &AppState,
+    config: ValidationConfig,
+    target: SwapDelta,
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    mint: &Pubkey,
+    our_lamports: u64,
+    recent_blockhash: Hash,
+) -> Result<f64, CopySkip> {
+    if target.tokens_per_lamport().is_none() {
+        return Err(CopySkip::NoTargetDelta);
+    }
+    let tokens = simulate_token_delta(app_state, keypair, instructions, mint, recent_blockhash)
+        .await
+        .map_err(|_| CopySkip::SimulationFailed)?;
+    compare_deltas(target, SwapDelta { tokens, lamports: our_lamports }, config.tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anchor_client::solana_client::rpc_request::RpcRequest;
+    use anchor_client::solana_sdk::system_instruction;
+    use serde_json::json;
+    use spl_token::state::{Account as TokenAccount, AccountState};
+    use yellowstone_grpc_proto::geyser::SubscribeUpdateTransactionInfo;
+    use yellowstone_grpc_proto::prelude::{
+        CompiledInstruction, Message, TokenBalance, Transaction as ProtoTransaction, TransactionStatusMeta, UiTokenAmount,
+    };
+
+    use super::*;
+    use crate::services::notifier::Notifier;
+
+    const TARGET: SwapDelta = SwapDelta { tokens: 2_000_000, lamports: 1_000_000 };
+
+    #[test]
+    fn deltas_within_tolerance_are_accepted() {
+        let ours = SwapDelta { tokens: 1_900_000, lamports: 1_000_000 };
+        let deviation = compare_deltas(TARGET, ours, 0.1).unwrap();
+        assert!((deviation - 0.05).abs() < 1e-12);
+        // Rates are compared, not amounts: half the size at the same price passes
+        assert_eq!(compare_deltas(TARGET, SwapDelta { tokens: 1_000_000, lamports: 500_000 }, 0.0), Ok(0.0));
+
+        assert_eq!(compare_deltas(TARGET, SwapDelta { tokens: 2_500_000, lamports: 1_000_000 }, 0.1), Err(CopySkip::DeltaMismatch));
+        assert_eq!(compare_deltas(TARGET, SwapDelta { tokens: 0, lamports: 1_000_000 }, 0.1), Err(CopySkip::DeltaMismatch));
+        assert_eq!(compare_deltas(SwapDelta { tokens: 0, lamports: 1 }, ours, 0.1), Err(CopySkip::NoTargetDelta));
+    }
+
+    fn balance(owner: &str, mint: &str, amount: u64) -> TokenBalance {
+        TokenBalance {
+            mint: mint.to_string(),
+            owner: owner.to_string(),
+            ui_token_amount: Some(UiTokenAmount { amount: amount.to_string(), ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    fn copied_txn(programs: &[Pubkey], pre: Vec<TokenBalance>, post: Vec<TokenBalance>) -> SubscribeUpdateTransaction {
+        let message = Message {
+            account_keys: programs.iter().map(|p| p.to_bytes().to_vec()).collect(),
+            instructions: (0..programs.len())
+                .map(|i| CompiledInstruction { program_id_index: i as u32, ..Default::default() })
+                .collect(),
+            ..Default::default()
+        };
+        SubscribeUpdateTransaction {
+            transaction: Some(SubscribeUpdateTransactionInfo {
+                transaction: Some(ProtoTransaction { message: Some(message), ..Default::default() }),
+                meta: Some(TransactionStatusMeta {
+                    pre_token_balances: pre,
+                    post_token_balances: post,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            slot: 1,
+        }
+    }
+
+    #[test]
+    fn only_unregistered_non_infrastructure_programs_are_unknown() {
+        let registry = DEXRegistry::new();
+        let router = Pubkey::new_unique();
+        let pumpswap = Pubkey::from_str("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA").unwrap();
+        let compute_budget = Pubkey::from_str("ComputeBudget111111111111111111111111111111").unwrap();
+        let txn = copied_txn(&[compute_budget, pumpswap, router, router], Vec::new(), Vec::new());
+        assert_eq!(unknown_programs(&registry, &txn), vec![router]);
+
+        let txn = copied_txn(&[compute_budget, pumpswap], Vec::new(), Vec::new());
+        assert!(unknown_programs(&registry, &txn).is_empty());
+    }
+
+    #[test]
+    fn target_delta_is_what_its_owner_gained() {
+        let (owner, mint) = ("target", "mint");
+        let pre = vec![balance(owner, mint, 500), balance("pool", mint, 9_000)];
+        let post = vec![balance(owner, mint, 2_500), balance("pool", mint, 7_000)];
+        let txn = copied_txn(&[], pre.clone(), post.clone());
+        assert_eq!(observed_token_delta(&txn, owner, mint), Some(2_000));
+        assert_eq!(observed_token_delta(&txn, owner, "other"), None);
+        // A sell lowers the holding, which isn't a buy to compare against
+        assert_eq!(observed_token_delta(&copied_txn(&[], post, pre), owner, mint), None);
+    }
+
+    /// A `simulateTransaction` answer returning `token_amount` in the one
+    /// account asked for, or failing with `err`
+    fn simulation(token_amount: u64, err: Option<&str>) -> HashMap<RpcRequest, serde_json::Value> {
+        let account = TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: token_amount,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        let mut data = vec![0u8; TokenAccount::LEN];
+        TokenAccount::pack(account, &mut data).unwrap();
+        let result = json!({
+            "context": { "slot": 1 },
+            "value": {
+                "err": err,
+                "logs": [],
+                "accounts": [{
+                    "lamports": 2_039_280,
+                    "data": [base64::encode(&data), "base64"],
+                    "owner": spl_token::id().to_string(),
+                    "executable": false,
+                    "rentEpoch": 0,
+                    "space": TokenAccount::LEN,
+                }],
+                "unitsConsumed": 40_000,
+            }
+        });
+        HashMap::from([(RpcRequest::SimulateTransaction, result)])
+    }
+
+    async fn validate(mocks: HashMap<RpcRequest, serde_json::Value>, target: SwapDelta) -> Result<f64, CopySkip> {
+        let app_state = AppState::for_tests_with_mocks("succeeds", mocks, Notifier::new(None));
+        let keypair = Keypair::new();
+        let instructions = vec![system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1_000_000)];
+        validate_copy(
+            &app_state,
+            ValidationConfig { tolerance: 0.1 },
+            target,
+            &keypair,
+            &instructions,
+            &Pubkey::new_unique(),
+            1_000_000,
+            Hash::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn simulated_fill_close_to_the_target_is_accepted() {
+        let deviation = validate(simulation(1_950_000, None), TARGET).await.unwrap();
+        assert!((deviation - 0.025).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn simulated_fill_far_from_the_target_is_rejected() {
+        assert_eq!(validate(simulation(1_500_000, None), TARGET).await, Err(CopySkip::DeltaMismatch));
+    }
+
+    #[tokio::test]
+    async fn failed_simulation_is_rejected() {
+        let failed = simulation(2_000_000, Some("AccountNotFound"));
+        assert_eq!(validate(failed, TARGET).await, Err(CopySkip::SimulationFailed));
+    }
+
+    #[tokio::test]
+    async fn no_target_delta_skips_before_simulating() {
+        let unreadable = SwapDelta { tokens: 0, lamports: 1_000_000 };
+        assert_eq!(validate(HashMap::new(), unreadable).await, Err(CopySkip::NoTargetDelta));
+    }
+}
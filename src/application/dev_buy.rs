@@ -0,0 +1,187 @@
+//! Launches and their creators' buys, for the sniper
+//!
+//! A launch is a PumpFun `Create` or a PumpSwap `CreatePool`; its creator
+//! is the transaction's fee payer. A dev buy is that creator buying the
+//! token for SOL, in the launch transaction itself (PumpFun's create and
+//! buy in one) or in a later one. Its size is the creator's SOL balance
+//! change, so a buy bundled with the launch also counts the rent of the
+//! accounts the launch opened, a few hundredths of a SOL.
+//!
+//! Launches are remembered with the time they were seen so a dev buy can be
+//! judged by how long after its launch it came; one whose launch wasn't seen
+//! is not a dev buy.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::hash::Hash;
+use spl_token::solana_program::native_token::lamports_to_sol;
+use tokio::time::Instant;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo};
+
+use crate::common::format::fmt_sol;
+use crate::dex::pump_swap::{PUMP_PROGRAM as PUMP_SWAP_PROGRAM, SOL_MINT};
+use crate::engine::balance_deltas::compute_balance_deltas;
+use crate::engine::lookup_tables::static_keys;
+use crate::engine::swap::SwapDirection;
+
+pub const PUMP_FUN_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+
+/// Launches remembered at once; the oldest half goes past this
+const MAX_LAUNCHES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Launchpad {
+    PumpFun,
+    PumpSwap,
+}
+
+impl Launchpad {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PumpFun => "pumpfun",
+            Self::PumpSwap => "pumpswap",
+        }
+    }
+
+    /// The launchpad whose program ran in a transaction with these logs
+    fn from_logs(log_messages: &[String]) -> Option<Self> {
+        let invoked = |program: &str| {
+            let prefix = format!("Program {} invoke", program);
+            log_messages.iter().any(|log| log.starts_with(&prefix))
+        };
+        if invoked(PUMP_FUN_PROGRAM) {
+            Some(Self::PumpFun)
+        } else if invoked(PUMP_SWAP_PROGRAM) {
+            Some(Self::PumpSwap)
+        } else {
+            None
+        }
+    }
+
+    fn creates(&self, log_messages: &[String]) -> bool {
+        let instruction = match self {
+            Self::PumpFun => "Program log: Instruction: Create",
+            Self::PumpSwap => "Program log: Instruction: CreatePool",
+        };
+        log_messages.iter().any(|log| log == instruction)
+    }
+}
+
+/// A creator buying its own token
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevBuy {
+    pub launchpad: Launchpad,
+    pub mint: String,
+    pub dev: String,
+    /// SOL the creator spent, rent of a bundled launch included
+    pub lamports: u64,
+    /// Time since the launch was seen
+    pub age: Duration,
+    pub signature: String,
+    pub slot: u64,
+    pub recent_blockhash: Hash,
+}
+
+#[derive(Debug, Clone)]
+struct Launch {
+    dev: String,
+    seen_at: Instant,
+}
+
+/// Launches seen on the stream, by mint
+#[derive(Debug, Default)]
+pub struct Launches {
+    by_mint: HashMap<String, Launch>,
+}
+
+impl Launches {
+    /// Note a launch in `txn` and return the dev buy it carries, if any
+    pub fn observe(&mut self, txn: &SubscribeUpdateTransaction, log_messages: &[String], now: Instant) -> Option<DevBuy> {
+        let launchpad = Launchpad::from_logs(log_messages)?;
+        let transaction = txn.transaction.as_ref()?;
+        let message = transaction.transaction.as_ref()?.message.as_ref()?;
+        let payer = static_keys(message).first()?.to_string();
+        let mints = traded_mints(transaction);
+        let deltas = compute_balance_deltas(transaction);
+
+        if launchpad.creates(log_messages) {
+            for mint in &mints {
+                // A new pool's creator deposits the token and is paid LP tokens, which aren't a launch
+                if launchpad == Launchpad::PumpSwap && deltas.token(&payer, mint) >= 0 {
+                    continue;
+                }
+                self.remember(mint.clone(), Launch { dev: payer.clone(), seen_at: now });
+            }
+        }
+
+        let mint = mints.into_iter().find(|mint| {
+            self.by_mint.get(mint).is_some_and(|launch| launch.dev == payer)
+                && deltas.direction(&payer, mint) == Some(SwapDirection::Buy)
+        })?;
+        let launch = &self.by_mint[&mint];
+        Some(DevBuy {
+            launchpad,
+            age: now.saturating_duration_since(launch.seen_at),
+            lamports: deltas.sol(&payer).unsigned_abs(),
+            dev: payer,
+            mint,
+            signature: bs58::encode(&transaction.signature).into_string(),
+            slot: txn.slot,
+            recent_blockhash: Hash::new(&message.recent_blockhash),
+        })
+    }
+
+    fn remember(&mut self, mint: String, launch: Launch) {
+        if self.by_mint.len() >= MAX_LAUNCHES && !self.by_mint.contains_key(&mint) {
+            let mut seen: Vec<Instant> = self.by_mint.values().map(|launch| launch.seen_at).collect();
+            seen.sort_unstable();
+            let cutoff = seen[seen.len() / 2];
+            self.by_mint.retain(|_, launch| launch.seen_at > cutoff);
+        }
+        self.by_mint.entry(mint).or_insert(launch);
+    }
+}
+
+/// Token mints with a balance before or after `transaction`, wrapped SOL aside
+fn traded_mints(transaction: &SubscribeUpdateTransactionInfo) -> Vec<String> {
+    let Some(meta) = &transaction.meta else {
+        return Vec::new();
+    };
+    let mut seen = HashSet::new();
+    meta.post_token_balances
+        .iter()
+        .chain(&meta.pre_token_balances)
+        .map(|balance| balance.mint.clone())
+        .filter(|mint| mint != SOL_MINT && seen.insert(mint.clone()))
+        .collect()
+}
+
+/// `MIN_DEV_BUY`/`MAX_DEV_BUY` in whole SOL, with the sniper's other limits
+#[derive(Debug, Clone, Copy)]
+pub struct SniperLimits {
+    pub min_dev_buy_sol: u64,
+    pub max_dev_buy_sol: u64,
+    /// `TIME_EXCEED`: dev buys later than this after the launch are skipped; zero for no limit
+    pub max_age: Duration,
+    /// `COUNTER`: buys before the sniper stops buying; zero for no limit
+    pub max_buys: u64,
+}
+
+impl SniperLimits {
+    /// Why `buy` is out of bounds, None when the sniper may follow it
+    pub fn refusal(&self, buy: &DevBuy, buys_so_far: u64) -> Option<String> {
+        let sol = lamports_to_sol(buy.lamports);
+        if sol < self.min_dev_buy_sol as f64 {
+            Some(format!("dev buy {} below MIN_DEV_BUY {} SOL", fmt_sol(buy.lamports), self.min_dev_buy_sol))
+        } else if sol > self.max_dev_buy_sol as f64 {
+            Some(format!("dev buy {} above MAX_DEV_BUY {} SOL", fmt_sol(buy.lamports), self.max_dev_buy_sol))
+        } else if !self.max_age.is_zero() && buy.age > self.max_age {
+            Some(format!("launched {:?} ago, past TIME_EXCEED {:?}", buy.age, self.max_age))
+        } else if self.max_buys > 0 && buys_so_far >= self.max_buys {
+            Some(format!("COUNTER reached, {} buys made", buys_so_far))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Startup self-test of config, connectivity and wallet readiness
+//!
+//! A bad gRPC token, an unreachable RPC node or an empty wallet used to show
+//! up minutes into a run, as a stalled stream or a failed buy. `doctor`
+//! checks each up front and says how to fix what failed. Required checks
+//! fail the run; advisory ones only warn, since the bot can start without
+//! them (pools missing from the cache are discovered at startup, say).
+//!
+//! Everything is read straight from the environment rather than through
+//! `Config`, whose loader stops on a missing variable without saying much.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use colored::Colorize;
+use spl_token::solana_program::native_token::{lamports_to_sol, sol_to_lamports};
+use tokio::time::{timeout, Instant};
+use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient};
+
+use crate::common::clock::SystemClock;
+use crate::common::config::{ArbitrageSettings, POOL_CACHE_PATH};
+use crate::core::tx_sender::TxBackend;
+use crate::dex::pump_swap::SOL_MINT;
+use crate::engine::pool_discovery::PoolCacheManager;
+use crate::services::rpc_pool::RpcPool;
+
+/// How long any one network check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `DOCTOR_MIN_BALANCE_SOL` when unset
+const DEFAULT_MIN_BALANCE_SOL: f64 = 0.05;
+
+/// Outcome of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    /// A failed required check fails the whole run
+    pub required: bool,
+    pub detail: String,
+    /// What to change when it failed
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Self {
+        Self { name, passed: true, required: true, detail, hint: None }
+    }
+
+    fn fail(name: &'static str, detail: String, hint: impl Into<String>) -> Self {
+        Self { name, passed: false, required: true, detail, hint: Some(hint.into()) }
+    }
+
+    fn advisory(mut self) -> Self {
+        self.required = false;
+        self
+    }
+}
+
+/// Every check of one run, in the order they ran
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// True unless a required check failed
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|check| check.passed || !check.required)
+    }
+
+    pub fn print(&self) {
+        for check in &self.checks {
+            let status = match (check.passed, check.required) {
+                (true, _) => "PASS".green().bold(),
+                (false, true) => "FAIL".red().bold(),
+                (false, false) => "WARN".yellow().bold(),
+            };
+            println!("[{}] {:<16} {}", status, check.name, check.detail);
+            if let Some(hint) = check.hint.as_ref().filter(|_| !check.passed) {
+                println!("       {:<16} -> {}", "", hint);
+            }
+        }
+        let failed = self.checks.iter().filter(|check| !check.passed && check.required).count();
+        let warned = self.checks.iter().filter(|check| !check.passed && !check.required).count();
+        if failed == 0 {
+            println!("{}", format!("Doctor: all required checks passed, {} warnings", warned).green().bold());
+        } else {
+            println!("{}", format!("Doctor: {} required checks failed, {} warnings", failed, warned).red().bold());
+        }
+    }
+}
+
+/// `SKIP_PREFLIGHT`: the trading modes run the doctor before starting unless set
+pub fn preflight_enabled() -> bool {
+    !std::env::var("SKIP_PREFLIGHT")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Run every check; `check_mints` adds the `MONITOR_TOKEN_MINTS` ones the
+/// arbitrage mode needs
+pub async fn run_doctor(check_mints: bool) -> DoctorReport {
+    dotenv::dotenv().ok();
+    let mut report = DoctorReport::default();
+
+    report.checks.push(check_grpc().await);
+    report.checks.extend(check_rpc_endpoints().await);
+
+    let mints = if check_mints {
+        match ArbitrageSettings::from_env() {
+            Ok(settings) => {
+                report.checks.push(Check::pass("settings", format!("{} mints to monitor", settings.token_mints.len())));
+                settings.token_mints
+            }
+            Err(e) => {
+                report.checks.push(Check::fail(
+                    "settings",
+                    e.to_string(),
+                    "Fix the listed variables; MONITOR_TOKEN_MINTS takes comma-separated base58 mints",
+                ));
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    let wallet = match read_wallet() {
+        Ok(wallet) => {
+            report.checks.push(Check::pass("wallet key", wallet.pubkey().to_string()));
+            Some(wallet)
+        }
+        Err(e) => {
+            report.checks.push(Check::fail(
+                "wallet key",
+                e,
+                "Set PRIVATE_KEY to the wallet's base58-encoded 64-byte secret key",
+            ));
+            None
+        }
+    };
+    if let Some(wallet) = &wallet {
+        // Every mint traded may need its token account created first
+        let accounts = mints.len().max(1) as u64;
+        report.checks.extend(check_balance(wallet, accounts).await);
+    }
+
+    report.checks.push(check_pool_cache(&mints));
+
+    let use_jito = std::env::var("USE_JITO")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    let tx_backend = TxBackend::from_env();
+    match &tx_backend {
+        Ok(backend) => report.checks.push(Check::pass("tx backend", format!("{:?}", backend))),
+        Err(e) => report.checks.push(Check::fail("tx backend", e.to_string(), "Set TX_BACKEND to zeroslot, jito or rpc")),
+    }
+    if use_jito || matches!(tx_backend, Ok(TxBackend::Jito)) {
+        report.checks.push(check_jito().await);
+    }
+    report
+}
+
+/// Connect to the Yellowstone endpoint and wait for one ping round trip
+async fn check_grpc() -> Check {
+    let (Ok(url), Ok(token)) = (std::env::var("YELLOWSTONE_GRPC_HTTP"), std::env::var("YELLOWSTONE_GRPC_TOKEN")) else {
+        return Check::fail(
+            "grpc",
+            "YELLOWSTONE_GRPC_HTTP or YELLOWSTONE_GRPC_TOKEN is not set".to_string(),
+            "Set both to your Yellowstone gRPC endpoint and its x-token",
+        );
+    };
+    let started = Instant::now();
+    let result = timeout(CHECK_TIMEOUT, async {
+        let mut client = GeyserGrpcClient::build_from_shared(url.clone())?
+            .x_token(Some(token))?
+            .tls_config(ClientTlsConfig::new().with_native_roots())?
+            .connect()
+            .await?;
+        client.ping(1).await?;
+        anyhow::Ok(())
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => Check::pass("grpc", format!("{} answered a ping in {:?}", url, started.elapsed())),
+        Ok(Err(e)) => Check::fail(
+            "grpc",
+            format!("{}: {}", url, e),
+            "Check YELLOWSTONE_GRPC_HTTP includes the scheme and port, and that YELLOWSTONE_GRPC_TOKEN is current",
+        ),
+        Err(_) => Check::fail(
+            "grpc",
+            format!("{} did not answer within {:?}", url, CHECK_TIMEOUT),
+            "Check the endpoint is reachable from this host; a firewall or wrong port times out rather than failing",
+        ),
+    }
+}
+
+/// getSlot latency of `RPC_HTTP` and every `RPC_POOL_URLS` endpoint; only
+/// `RPC_HTTP` is required, the pool skips endpoints that fail
+async fn check_rpc_endpoints() -> Vec<Check> {
+    let primary = std::env::var("RPC_HTTP").unwrap_or_default();
+    if primary.trim().is_empty() {
+        return vec![Check::fail("rpc", "RPC_HTTP is not set".to_string(), "Set RPC_HTTP to your Solana RPC node's URL")];
+    }
+    let pool = RpcPool::from_env(Arc::new(SystemClock));
+    let mut checks = Vec::new();
+    for endpoint in pool.endpoints() {
+        let started = Instant::now();
+        let check = match timeout(CHECK_TIMEOUT, endpoint.client.get_slot()).await {
+            Ok(Ok(slot)) => Check::pass("rpc", format!("{} at slot {} in {:?}", endpoint.url, slot, started.elapsed())),
+            Ok(Err(e)) => Check::fail(
+                "rpc",
+                format!("{}: {}", endpoint.url, e),
+                "Check the URL and any API key in it; a 401 or 403 means the key was refused",
+            ),
+            Err(_) => Check::fail(
+                "rpc",
+                format!("{} did not answer within {:?}", endpoint.url, CHECK_TIMEOUT),
+                "Check the node is reachable from this host",
+            ),
+        };
+        checks.push(if endpoint.url == primary { check } else { check.advisory() });
+    }
+    checks
+}
+
+/// `PRIVATE_KEY` as a keypair, without the panic `Keypair::from_base58_string`
+/// has on bad input
+fn read_wallet() -> Result<Keypair, String> {
+    let key = std::env::var("PRIVATE_KEY").map_err(|_| "PRIVATE_KEY is not set".to_string())?;
+    let bytes = bs58::decode(key.trim()).into_vec().map_err(|e| format!("PRIVATE_KEY is not base58: {}", e))?;
+    Keypair::from_bytes(&bytes).map_err(|e| format!("PRIVATE_KEY is not a keypair ({} bytes): {}", bytes.len(), e))
+}
+
+/// Balance above `DOCTOR_MIN_BALANCE_SOL`, then room left over for the rent
+/// of `accounts` new token accounts
+async fn check_balance(wallet: &Keypair, accounts: u64) -> Vec<Check> {
+    let min_balance = std::env::var("DOCTOR_MIN_BALANCE_SOL")
+        .ok()
+        .and_then(|v| f64::from_str(v.trim()).ok())
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .unwrap_or(DEFAULT_MIN_BALANCE_SOL);
+    let pool = RpcPool::from_env(Arc::new(SystemClock));
+    let Some(endpoint) = pool.endpoints().first() else {
+        return Vec::new();
+    };
+    let client = &endpoint.client;
+
+    let balance = match timeout(CHECK_TIMEOUT, client.get_balance(&wallet.pubkey())).await {
+        Ok(Ok(balance)) => balance,
+        Ok(Err(e)) => return vec![Check::fail("balance", e.to_string(), "Check RPC_HTTP, the balance read goes through it")],
+        Err(_) => return vec![Check::fail("balance", format!("no answer within {:?}", CHECK_TIMEOUT), "Check RPC_HTTP")],
+    };
+    let mut checks = vec![if balance >= sol_to_lamports(min_balance) {
+        Check::pass("balance", format!("{} SOL", lamports_to_sol(balance)))
+    } else {
+        Check::fail(
+            "balance",
+            format!("{} SOL, below the {} SOL minimum", lamports_to_sol(balance), min_balance),
+            format!("Fund {} or lower DOCTOR_MIN_BALANCE_SOL", wallet.pubkey()),
+        )
+    }];
+
+    let rent = timeout(
+        CHECK_TIMEOUT,
+        client.get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN),
+    )
+    .await;
+    if let Ok(Ok(rent)) = rent {
+        let needed = sol_to_lamports(min_balance) + rent * accounts;
+        let check = if balance >= needed {
+            Check::pass("ata rent", format!("room for {} token accounts at {} SOL each", accounts, lamports_to_sol(rent)))
+        } else {
+            Check::fail(
+                "ata rent",
+                format!(
+                    "{} token accounts need {} SOL of rent on top of the minimum",
+                    accounts, lamports_to_sol(rent * accounts)
+                ),
+                "Fund the wallet so buys that create a token account don't fail for rent",
+            )
+        };
+        checks.push(check.advisory());
+    }
+    checks
+}
+
+/// The pool cache parses, and every monitored mint has a pool in it
+fn check_pool_cache(mints: &[Pubkey]) -> Check {
+    let manager = match PoolCacheManager::new(POOL_CACHE_PATH) {
+        Ok(manager) => manager,
+        Err(e) => {
+            return Check::fail(
+                "pool cache",
+                format!("{}: {}", POOL_CACHE_PATH, e),
+                format!("Move {} aside; it is rebuilt by discovery on the next start", POOL_CACHE_PATH),
+            )
+        }
+    };
+    let cache = manager.get_cache().unwrap_or_default();
+    let missing: Vec<String> = mints
+        .iter()
+        .map(|mint| mint.to_string())
+        .filter(|mint| mint != SOL_MINT)
+        .filter(|mint| cache.get_pools_for_token(mint).map_or(true, |pools| pools.is_empty()))
+        .collect();
+    if missing.is_empty() {
+        Check::pass("pool cache", format!("{} tokens cached, every monitored mint has a pool", cache.pools.len()))
+    } else {
+        Check::fail(
+            "pool cache",
+            format!("no cached pool for {}", missing.join(", ")),
+            format!("Run `discover-pools {}`, or check the mints trade on a supported DEX", missing.join(" ")),
+        )
+        .advisory()
+    }
+}
+
+/// The block engine answers `getTipAccounts`
+async fn check_jito() -> Check {
+    let Ok(url) = std::env::var("JITO_BLOCK_ENGINE_URL") else {
+        return Check::fail(
+            "jito",
+            "JITO_BLOCK_ENGINE_URL is not set".to_string(),
+            "Set it to a block engine such as https://mainnet.block-engine.jito.wtf, or turn off USE_JITO",
+        );
+    };
+    let request = reqwest::Client::new()
+        .post(format!("{}/api/v1/bundles", url.trim_end_matches('/')))
+        .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "getTipAccounts", "params": [] }))
+        .timeout(CHECK_TIMEOUT)
+        .send();
+    let started = Instant::now();
+    match request.await {
+        Ok(response) if response.status().is_success() => {
+            Check::pass("jito", format!("{} answered in {:?}", url, started.elapsed()))
+        }
+        Ok(response) => Check::fail(
+            "jito",
+            format!("{} answered {}", url, response.status()),
+            "Check JITO_BLOCK_ENGINE_URL is the block engine's base URL, without /api/v1",
+        ),
+        Err(e) => Check::fail("jito", format!("{}: {}", url, e), "Check the block engine is reachable from this host"),
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use colored::Colorize;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::common::{config::AppState, format::fmt_sol, logger::Logger, metrics::TOKEN_DORMANCY_TRANSITIONS};
+use crate::common::sync::LockExt;
+
+/// How often dormancy is re-evaluated
+const EVALUATE_EVERY: Duration = Duration::from_secs(60);
+
+/// `DORMANT_AFTER_SECS` and `DORMANT_LIQUIDITY_FLOOR`
+#[derive(Debug, Clone)]
+pub struct DormancyConfig {
+    /// How long a token must go without swaps before it can be demoted
+    pub inactive_for: Duration,
+    /// Lamports every pool of the token must be below to count as drained
+    pub liquidity_floor: u64,
+}
+
+impl DormancyConfig {
+    pub fn from_env() -> Self {
+        let parse = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            inactive_for: Duration::from_secs(parse("DORMANT_AFTER_SECS", 6 * 3600)),
+            liquidity_floor: parse("DORMANT_LIQUIDITY_FLOOR", 1_000_000_000),
+        }
+    }
+}
+
+/// A token moving in or out of dormancy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Demoted,
+    Promoted,
+}
+
+impl Transition {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Demoted => "demoted",
+            Self::Promoted => "promoted",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Liveness {
+    /// Last swap seen, or when tracking started for a token never seen trading
+    last_swap: Instant,
+    /// Latest liquidity per pool, in lamports
+    liquidity: HashMap<String, u64>,
+    dormant_since: Option<Instant>,
+    /// Dormant since before this run, restored from the pool cache
+    restored: bool,
+}
+
+impl Liveness {
+    fn new(now: Instant) -> Self {
+        Self { last_swap: now, liquidity: HashMap::new(), dormant_since: None, restored: false }
+    }
+
+    /// Deepest pool; 0 until a refresh reports one
+    fn max_liquidity(&self) -> u64 {
+        self.liquidity.values().copied().max().unwrap_or(0)
+    }
+}
+
+/// A dormant token, for the admin endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct DormantToken {
+    pub mint: String,
+    /// Seconds since demotion, None for tokens restored dormant from the cache
+    pub dormant_for_secs: Option<u64>,
+    pub last_swap_secs_ago: u64,
+    pub max_liquidity: u64,
+}
+
+/// Finds monitored tokens whose pools are drained and nobody trades anymore
+///
+/// Dormant tokens are skipped by the scanner and the pool refresher but stay
+/// in the pool cache. Swaps still show up through the program-level stream,
+/// and the first one promotes the token back.
+pub struct DormancyTracker {
+    config: DormancyConfig,
+    tokens: Mutex<HashMap<String, Liveness>>,
+}
+
+impl DormancyTracker {
+    /// `dormant` are mints the pool cache already has marked dormant
+    pub fn new(config: DormancyConfig, dormant: impl IntoIterator<Item = String>, now: Instant) -> Self {
+        let tokens = dormant
+            .into_iter()
+            .map(|mint| {
+                let mut liveness = Liveness::new(now);
+                liveness.dormant_since = Some(now);
+                liveness.restored = true;
+                (mint, liveness)
+            })
+            .collect();
+        Self { config, tokens: Mutex::new(tokens) }
+    }
+
+    /// Start watching `mint`, its inactivity clock starts now
+    pub fn track(&self, mint: &str, now: Instant) {
+        self.tokens.lock_or_recover().entry(mint.to_string()).or_insert_with(|| Liveness::new(now));
+    }
+
+    pub fn is_dormant(&self, mint: &str) -> bool {
+        self.tokens
+            .lock_or_recover()
+            .get(mint)
+            .is_some_and(|liveness| liveness.dormant_since.is_some())
+    }
+
+    /// Record a swap on `mint`, promoting it if it was dormant
+    pub fn note_swap(&self, mint: &str, now: Instant) -> Option<Transition> {
+        let mut tokens = self.tokens.lock_or_recover();
+        let liveness = tokens.entry(mint.to_string()).or_insert_with(|| Liveness::new(now));
+        liveness.last_swap = now;
+        liveness.restored = false;
+        liveness.dormant_since.take().map(|_| Transition::Promoted)
+    }
+
+    pub fn note_liquidity(&self, mint: &str, pool_id: &str, lamports: u64, now: Instant) {
+        self.tokens
+            .lock_or_recover()
+            .entry(mint.to_string())
+            .or_insert_with(|| Liveness::new(now))
+            .liquidity
+            .insert(pool_id.to_string(), lamports);
+    }
+
+    /// Demote every token inactive for long enough with all pools below the
+    /// floor; `protected` tokens (open positions) are never demoted
+    pub fn evaluate(&self, now: Instant, protected: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut tokens = self.tokens.lock_or_recover();
+        let mut demoted = Vec::new();
+        for (mint, liveness) in tokens.iter_mut() {
+            if liveness.dormant_since.is_some() || protected(mint) {
+                continue;
+            }
+            let inactive = now.saturating_duration_since(liveness.last_swap) >= self.config.inactive_for;
+            if inactive && liveness.max_liquidity() < self.config.liquidity_floor {
+                liveness.dormant_since = Some(now);
+                demoted.push(mint.clone());
+            }
+        }
+        demoted
+    }
+
+    pub fn dormant(&self, now: Instant) -> Vec<DormantToken> {
+        let tokens = self.tokens.lock_or_recover();
+        let mut dormant: Vec<DormantToken> = tokens
+            .iter()
+            .filter_map(|(mint, liveness)| {
+                let since = liveness.dormant_since?;
+                Some(DormantToken {
+                    mint: mint.clone(),
+                    dormant_for_secs: (!liveness.restored).then(|| now.saturating_duration_since(since).as_secs()),
+                    last_swap_secs_ago: now.saturating_duration_since(liveness.last_swap).as_secs(),
+                    max_liquidity: liveness.max_liquidity(),
+                })
+            })
+            .collect();
+        dormant.sort_by(|a, b| a.mint.cmp(&b.mint));
+        dormant
+    }
+}
+
+/// Log, count and persist a transition
+fn apply(app_state: &AppState, mint: &str, transition: Transition, logger: &Logger) {
+    TOKEN_DORMANCY_TRANSITIONS.with_label_values(&[transition.label()]).inc();
+    if let Err(e) = app_state.pool_cache_manager.set_dormant(mint, transition == Transition::Demoted) {
+        logger.log(format!("[CACHE ERROR] => {}", e).red().to_string());
+    }
+    match transition {
+        Transition::Demoted => logger.log(format!(
+            "[DEMOTED] => {} is dormant, no swaps and every pool below {}",
+            mint, fmt_sol(app_state.dormancy.config.liquidity_floor)
+        ).yellow().to_string()),
+        Transition::Promoted => logger.log(format!(
+            "[PROMOTED] => {} is trading again, back in active monitoring", mint
+        ).green().to_string()),
+    }
+}
+
+/// Record a swap seen on the stream, re-activating a dormant token
+pub fn note_swap(app_state: &AppState, mint: &str, logger: &Logger) {
+    if let Some(transition) = app_state.dormancy.note_swap(mint, app_state.clock.now()) {
+        apply(app_state, mint, transition, logger);
+    }
+}
+
+/// Demote inactive, drained tokens until the process exits
+pub async fn run_dormancy(app_state: Arc<AppState>) {
+    let logger = Logger::new("[DORMANCY] => ".yellow().to_string());
+    loop {
+        app_state.clock.sleep(EVALUATE_EVERY).await;
+        let now = app_state.clock.now();
+        if let Ok(cache) = app_state.pool_cache_manager.get_cache() {
+            for mint in cache.pools.keys() {
+                app_state.dormancy.track(mint, now);
+            }
+        }
+        let demoted = app_state
+            .dormancy
+            .evaluate(now, |mint| app_state.refresh_scheduler.has_open_position(mint));
+        for mint in demoted {
+            app_state.refresh_scheduler.untrack(&mint);
+            apply(&app_state, &mint, Transition::Demoted, &logger);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    const SOL: u64 = 1_000_000_000;
+
+    fn tracker(now: Instant) -> DormancyTracker {
+        let config = DormancyConfig { inactive_for: 6 * HOUR, liquidity_floor: SOL };
+        DormancyTracker::new(config, Vec::new(), now)
+    }
+
+    #[test]
+    fn drained_inactive_token_is_demoted_then_promoted_by_a_swap() {
+        let t0 = Instant::now();
+        let tracker = tracker(t0);
+        tracker.track("dead", t0);
+        tracker.note_liquidity("dead", "pool-a", SOL / 10, t0);
+        tracker.note_liquidity("dead", "pool-b", SOL / 2, t0);
+
+        assert!(tracker.evaluate(t0 + 6 * HOUR - Duration::from_secs(1), |_| false).is_empty());
+        assert_eq!(tracker.evaluate(t0 + 6 * HOUR, |_| false), vec!["dead".to_string()]);
+        assert!(tracker.is_dormant("dead"));
+        // Already dormant, not demoted twice
+        assert!(tracker.evaluate(t0 + 7 * HOUR, |_| false).is_empty());
+
+        let listed = tracker.dormant(t0 + 7 * HOUR);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].mint, "dead");
+        assert_eq!(listed[0].dormant_for_secs, Some(3600));
+        assert_eq!(listed[0].last_swap_secs_ago, 7 * 3600);
+        assert_eq!(listed[0].max_liquidity, SOL / 2);
+
+        assert_eq!(tracker.note_swap("dead", t0 + 8 * HOUR), Some(Transition::Promoted));
+        assert!(!tracker.is_dormant("dead"));
+        assert!(tracker.dormant(t0 + 8 * HOUR).is_empty());
+        assert_eq!(tracker.note_swap("dead", t0 + 8 * HOUR), None);
+
+        // The inactivity clock restarts from the swap
+        assert!(tracker.evaluate(t0 + 13 * HOUR, |_| false).is_empty());
+        assert_eq!(tracker.evaluate(t0 + 14 * HOUR, |_| false), vec!["dead".to_string()]);
+    }
+
+    #[test]
+    fn one_deep_pool_or_recent_swap_keeps_a_token_active() {
+        let t0 = Instant::now();
+        let tracker = tracker(t0);
+        tracker.note_liquidity("deep", "pool-a", 0, t0);
+        tracker.note_liquidity("deep", "pool-b", SOL, t0);
+        tracker.track("traded", t0);
+        tracker.note_swap("traded", t0 + 5 * HOUR);
+
+        assert!(tracker.evaluate(t0 + 10 * HOUR, |_| false).is_empty());
+
+        // Draining the deep pool is enough once it has been quiet long enough
+        tracker.note_liquidity("deep", "pool-b", SOL - 1, t0 + 10 * HOUR);
+        assert_eq!(tracker.evaluate(t0 + 10 * HOUR, |_| false), vec!["deep".to_string()]);
+        assert_eq!(tracker.evaluate(t0 + 11 * HOUR, |_| false), vec!["traded".to_string()]);
+    }
+
+    #[test]
+    fn open_positions_are_never_demoted() {
+        let t0 = Instant::now();
+        let tracker = tracker(t0);
+        tracker.track("held", t0);
+        assert!(tracker.evaluate(t0 + 24 * HOUR, |mint| mint == "held").is_empty());
+        assert!(!tracker.is_dormant("held"));
+        assert_eq!(tracker.evaluate(t0 + 24 * HOUR, |_| false), vec!["held".to_string()]);
+    }
+
+    #[test]
+    fn tokens_restored_dormant_are_promoted_on_their_first_swap() {
+        let t0 = Instant::now();
+        let config = DormancyConfig { inactive_for: 6 * HOUR, liquidity_floor: SOL };
+        let tracker = DormancyTracker::new(config, vec!["old".to_string()], t0);
+        assert!(tracker.is_dormant("old"));
+        // Tracking it again from the cache doesn't reset it
+        tracker.track("old", t0 + HOUR);
+        assert!(tracker.is_dormant("old"));
+        assert_eq!(tracker.dormant(t0 + HOUR)[0].dormant_for_secs, None);
+
+        assert_eq!(tracker.note_swap("old", t0 + 2 * HOUR), Some(Transition::Promoted));
+        assert!(!tracker.is_dormant("old"));
+    }
+}
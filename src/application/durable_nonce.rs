@@ -0,0 +1,270 @@
+//! Durable-nonce signing for sells that must not expire
+//!
+//! A sell signed against `get_latest_blockhash` is dead about a minute
+//! later, and under load the node it reaches may not know the hash yet
+//! ("blockhash not found"). With `NONCE_ACCOUNT` set, sells sign against the
+//! hash stored in that nonce account instead and lead with an
+//! `AdvanceNonceAccount` instruction, so they stay valid until the nonce is
+//! advanced. Landing one advances it, which also means a retry built on the
+//! same nonce can never land twice.
+//!
+//! There is one nonce, so sells that overlap (several positions timing out
+//! at once, a liquidation sweep) would all sign against the same hash and
+//! only the first to land would count. Nonce-backed sends take turns
+//! instead: each waits until the previous one's advance shows on-chain, it
+//! was released because it never went out, or `ADVANCE_WAIT` passed and it
+//! is taken to be dropped.
+//! Liquidation sells only wait `LIQUIDATION_WAIT` for their turn before
+//! signing against a recent blockhash.
+//!
+//! The nonce's authority is read from the account and has to be the signing
+//! wallet. Without a nonce account, or when it can't be used, sends fall
+//! back to a recent blockhash, the stream's when it is fresh enough.
+
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{hash::Hash, instruction::Instruction, pubkey::Pubkey, system_program};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use tokio::time::Instant;
+
+use crate::common::{clock::Clock, config::AppState, logger::Logger, sync::LockExt};
+use crate::core::nonce::NonceAccount;
+use crate::engine::{blockhash_cache, liquidation};
+use crate::services::rpc_pool::CallClass;
+
+/// How long a send waits for the previous nonce-signed send to advance the nonce
+const ADVANCE_WAIT: Duration = Duration::from_secs(30);
+
+/// Between two reads of the nonce while waiting for it to advance
+const ADVANCE_POLL: Duration = Duration::from_millis(500);
+
+/// How long a sell waits on the nonce during a liquidation before it signs
+/// against a recent blockhash; getting out matters more than the nonce then
+const LIQUIDATION_WAIT: Duration = Duration::from_secs(2);
+
+lazy_static::lazy_static! {
+    static ref SEQUENCER: NonceSequencer = NonceSequencer::default();
+}
+
+/// Hands out the nonce one send at a time
+#[derive(Debug, Default)]
+pub struct NonceSequencer {
+    turn: tokio::sync::Mutex<()>,
+    /// The stored hash the last send signed against, and when it was handed out
+    in_flight: Mutex<Option<(Hash, Instant)>>,
+}
+
+impl NonceSequencer {
+    /// The nonce for the next send, read with `fetch` once the previous
+    /// send's advance has landed, it was released, or `ADVANCE_WAIT` passed.
+    /// Errs once this send has waited `max_wait`, for its turn or for the
+    /// advance, so the caller can sign against a recent blockhash instead
+    pub async fn next<F, Fut>(&self, clock: &dyn Clock, max_wait: Duration, mut fetch: F) -> Result<NonceAccount>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<NonceAccount>>,
+    {
+        let started = clock.now();
+        let waited_out = || clock.now().saturating_duration_since(started) >= max_wait;
+        let _turn = loop {
+            if let Ok(turn) = self.turn.try_lock() {
+                break turn;
+            }
+            if waited_out() {
+                return Err(anyhow!("Another send held the nonce for {:?}", max_wait));
+            }
+            clock.sleep(ADVANCE_POLL).await;
+        };
+        loop {
+            let nonce = fetch().await?;
+            let now = clock.now();
+            let previous_pending = match *self.in_flight.lock_or_recover() {
+                Some((hash, handed_out)) => hash == nonce.blockhash && now.saturating_duration_since(handed_out) < ADVANCE_WAIT,
+                None => false,
+            };
+            if !previous_pending {
+                *self.in_flight.lock_or_recover() = Some((nonce.blockhash, now));
+                return Ok(nonce);
+            }
+            if waited_out() {
+                return Err(anyhow!("Nonce {} still in flight after {:?}", nonce.blockhash, max_wait));
+            }
+            clock.sleep(ADVANCE_POLL).await;
+        }
+    }
+
+    /// Give back a hash whose send never went out, so the next one needn't wait on it
+    pub fn release(&self, blockhash: &Hash) {
+        let mut in_flight = self.in_flight.lock_or_recover();
+        if in_flight.map_or(false, |(hash, _)| hash == *blockhash) {
+            *in_flight = None;
+        }
+    }
+}
+
+/// Release `blockhash` after a failed send; a recent blockhash is ignored
+pub fn release(blockhash: &Hash) {
+    SEQUENCER.release(blockhash);
+}
+
+/// `NONCE_ACCOUNT`, None when unset
+pub fn configured_account() -> Result<Option<Pubkey>> {
+    match std::env::var("NONCE_ACCOUNT") {
+        Ok(raw) if !raw.trim().is_empty() => Pubkey::from_str(raw.trim())
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid NONCE_ACCOUNT {}: {}", raw, e)),
+        _ => Ok(None),
+    }
+}
+
+pub async fn fetch(app_state: &AppState, address: Pubkey, class: CallClass) -> Result<NonceAccount> {
+    let account = app_state
+        .rpc_pool
+        .call(class, |client| async move { client.get_account(&address).await })
+        .await?;
+    if account.owner != system_program::id() {
+        return Err(anyhow!("Nonce account {} is owned by {}, not the system program", address, account.owner));
+    }
+    NonceAccount::parse(address, &account.data)
+}
+
+/// The hash a send by `signer` signs against. With a usable nonce account
+/// that is its stored hash, once the previous nonce-signed send is done with
+/// it, and the advance instruction is prepended to `instructions`; otherwise
+/// a recent blockhash over `class`. A send that fails after this should
+/// `release` the hash.
+pub async fn blockhash_for_send(
+    app_state: &AppState,
+    signer: &Pubkey,
+    instructions: &mut Vec<Instruction>,
+    class: CallClass,
+    logger: &Logger,
+) -> Result<Hash> {
+    let nonce = match configured_account() {
+        Ok(Some(address)) => {
+            let usable = || async move {
+                let nonce = fetch(app_state, address, class).await?;
+                if nonce.authority != *signer {
+                    return Err(anyhow!("{} is authorized to {}, not {}", nonce.address, nonce.authority, signer));
+                }
+                Ok(nonce)
+            };
+            let max_wait = if liquidation::is_liquidating(app_state) { LIQUIDATION_WAIT } else { ADVANCE_WAIT };
+            match SEQUENCER.next(app_state.clock.as_ref(), max_wait, usable).await {
+                Ok(nonce) => Some(nonce),
+                Err(e) => {
+                    logger.log(format!("[NONCE] => {}; using a recent blockhash", e).yellow().to_string());
+                    None
+                }
+            }
+        }
+        Ok(None) => None,
+        Err(e) => {
+            logger.log(format!("[NONCE] => {}", e).red().to_string());
+            None
+        }
+    };
+
+    match nonce {
+        Some(nonce) => {
+            nonce.lead(instructions);
+            logger.debug_with(|| format!("[NONCE] => Signing against {} from {}", nonce.blockhash, nonce.address));
+            Ok(nonce.blockhash)
+        }
+        None => blockhash_cache::recent(app_state, class, logger).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::clock::MockClock;
+
+    fn nonce(blockhash: Hash) -> NonceAccount {
+        NonceAccount {
+            address: Pubkey::new_from_array([7; 32]),
+            authority: Pubkey::new_from_array([8; 32]),
+            blockhash,
+        }
+    }
+
+    /// Reads of the nonce account returning `hashes` in turn, then the last one
+    fn reads(hashes: &[Hash]) -> impl FnMut() -> std::future::Ready<Result<NonceAccount>> + '_ {
+        let mut read = 0;
+        move || {
+            let hash = hashes[read.min(hashes.len() - 1)];
+            read += 1;
+            std::future::ready(Ok(nonce(hash)))
+        }
+    }
+
+    #[tokio::test]
+    async fn a_second_send_waits_for_the_first_to_advance_the_nonce() {
+        let (sequencer, clock) = (NonceSequencer::default(), MockClock::new());
+        let (first, second) = (Hash::new_unique(), Hash::new_unique());
+        let started = clock.now();
+
+        assert_eq!(sequencer.next(&clock, ADVANCE_WAIT, reads(&[first])).await.unwrap().blockhash, first);
+        // The first sell lands by the fourth read
+        let taken = sequencer.next(&clock, ADVANCE_WAIT, reads(&[first, first, first, second])).await.unwrap();
+        assert_eq!(taken.blockhash, second);
+        assert_eq!(clock.now() - started, ADVANCE_POLL * 3);
+    }
+
+    #[tokio::test]
+    async fn a_released_nonce_is_reused_at_once() {
+        let (sequencer, clock) = (NonceSequencer::default(), MockClock::new());
+        let hash = Hash::new_unique();
+        let started = clock.now();
+
+        sequencer.next(&clock, ADVANCE_WAIT, reads(&[hash])).await.unwrap();
+        sequencer.release(&hash);
+        assert_eq!(sequencer.next(&clock, ADVANCE_WAIT, reads(&[hash])).await.unwrap().blockhash, hash);
+        assert_eq!(clock.now(), started);
+    }
+
+    #[tokio::test]
+    async fn a_dropped_send_holds_the_nonce_for_the_advance_wait() {
+        let (sequencer, clock) = (NonceSequencer::default(), MockClock::new());
+        let hash = Hash::new_unique();
+        let started = clock.now();
+
+        sequencer.next(&clock, ADVANCE_WAIT, reads(&[hash])).await.unwrap();
+        // A recent blockhash handed back is not the one in flight
+        sequencer.release(&Hash::new_unique());
+        assert_eq!(sequencer.next(&clock, ADVANCE_WAIT, reads(&[hash])).await.unwrap().blockhash, hash);
+        assert_eq!(clock.now() - started, ADVANCE_WAIT);
+    }
+
+    #[tokio::test]
+    async fn a_failed_read_hands_out_nothing() {
+        let (sequencer, clock) = (NonceSequencer::default(), MockClock::new());
+        let failed = sequencer.next(&clock, ADVANCE_WAIT, || std::future::ready(Err(anyhow!("account not found")))).await;
+        assert!(failed.is_err());
+        assert!(sequencer.in_flight.lock_or_recover().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_liquidation_sell_gives_up_on_the_nonce_after_the_short_wait() {
+        let (sequencer, clock) = (NonceSequencer::default(), MockClock::new());
+        let hash = Hash::new_unique();
+        let started = clock.now();
+
+        sequencer.next(&clock, ADVANCE_WAIT, reads(&[hash])).await.unwrap();
+        assert!(sequencer.next(&clock, LIQUIDATION_WAIT, reads(&[hash])).await.is_err());
+        assert_eq!(clock.now() - started, LIQUIDATION_WAIT);
+        // The first send still holds the nonce
+        assert_eq!(sequencer.in_flight.lock_or_recover().map(|(hash, _)| hash), Some(hash));
+
+        // Nor does it queue behind a send that is itself waiting on the advance
+        let _waiting = sequencer.turn.lock().await;
+        let waited_from = clock.now();
+        assert!(sequencer.next(&clock, LIQUIDATION_WAIT, reads(&[Hash::new_unique()])).await.is_err());
+        assert_eq!(clock.now() - waited_from, LIQUIDATION_WAIT);
+    }
+}
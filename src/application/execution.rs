@@ -0,0 +1,764 @@
+//! Sends a detected arbitrage as one transaction
+//!
+//! The buy on the cheaper venue and the sell of the tokens it is guaranteed
+//! on the dearer one go out together, so a sell that misses its minimum
+//! reverts the buy with it. Both legs go through the venues' `DexAdapter`s. With `DRY_RUN=true` nothing is sent and opportunities are
+//! only logged and recorded, as signals.
+//!
+//! The signed transaction is simulated first and only sent when the wallet's
+//! simulated SOL gain, less the signature and priority fees, exceeds
+//! `MIN_NET_PROFIT_LAMPORTS`.
+//!
+//! Trades are sized from the two pools' reserves at the input that
+//! maximizes constant-product profit, capped by `MAX_POSITION_SIZE` and the
+//! wallet's SOL.
+//!
+//! Compute units are priced from the fees recently paid to write the two
+//! pools, like every other send. With `use_jito` the transaction goes to the
+//! Jito block engine as a bundle with a `JITO_TIP_VALUE` tip instead of
+//! through the RPC node.
+//!
+//! Legs that don't fit a legacy message go out as a v0 message over the
+//! `ARBITRAGE_ALT` lookup table.
+//!
+//! A leg in a USDC-quoted pool is bracketed by a SOL/USDC swap through the
+//! reference pool, or settled against the wallet's USDC with
+//! `USDC_LEG_MODE=float`; either way the simulation values USDC at the
+//! reference rate. Only Raydium AMM trades USDC pools so far.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anchor_client::solana_client::rpc_config::{
+    RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
+use anchor_client::solana_sdk::{
+    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, signer::Signer, transaction::VersionedTransaction,
+};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::solana_program::native_token::sol_to_lamports;
+
+use crate::common::{
+    config::{AppState, SwapConfig},
+    format::{fmt_pct, fmt_sol},
+    latency::{LatencyTracer, Stage},
+    logger::Logger,
+};
+use crate::core::{
+    dex_adapter::{DexAdapter, PoolInfo},
+    ids::TradeId,
+    priority_fee::{PriorityFeeStrategy, PRIORITY_FEES},
+    tx::{self, ComputeBudget, BASE_FEE_LAMPORTS},
+    tx_pruning::OptionalKind,
+};
+use crate::dex::slippage::min_amount_with_slippage;
+use crate::engine::arbitrage_alt;
+use crate::engine::blockhash_cache;
+use crate::engine::arbitrage::{check_venue_minimums, optimal_arbitrage_amount, LegFees, OptimalSize, Reserves};
+use crate::engine::capital::Strategy;
+use crate::engine::fill_quality;
+use crate::engine::leader;
+use crate::engine::liquidation;
+use crate::engine::quote_currency::{LegQuotes, QuoteAsset, UsdcLegMode};
+use crate::engine::swap::SwapDirection;
+use crate::record::store::TradeRecord;
+use crate::services::rpc_pool::CallClass;
+
+/// `DRY_RUN`: log and record opportunities without sending anything
+pub fn is_dry_run() -> bool {
+    std::env::var("DRY_RUN")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// `MIN_NET_PROFIT_LAMPORTS`: simulated net profit a transaction must beat to be sent
+fn min_net_profit_lamports() -> i64 {
+    std::env::var("MIN_NET_PROFIT_LAMPORTS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// `MAX_POSITION_SIZE` in SOL: the most one arbitrage puts in, the
+/// configured trade amount when unset
+pub fn max_position_lamports(swap_config: &SwapConfig) -> u64 {
+    let sol = std::env::var("MAX_POSITION_SIZE")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|sol| *sol > 0.0)
+        .unwrap_or(swap_config.amount_in);
+    sol_to_lamports(sol)
+}
+
+/// Tip paid with each bundle, 0 unless `use_jito` sends arbitrage as bundles
+fn bundle_tip_lamports(swap_config: &SwapConfig) -> u64 {
+    if swap_config.use_jito {
+        tx::jito_tip_lamports()
+    } else {
+        0
+    }
+}
+
+/// The venues and pools of one detected opportunity
+#[derive(Debug, Clone, Copy)]
+pub struct ArbitrageLegs<'a> {
+    pub token: &'a str,
+    pub buy_dex: &'a str,
+    pub buy_pool: &'a str,
+    pub sell_dex: &'a str,
+    pub sell_pool: &'a str,
+    /// Expected net profit in percent, as detected
+    pub profit_pct: f64,
+    /// Size the scan found best from the cached pools
+    pub planned: Option<OptimalSize>,
+    /// What each leg is quoted in, and the SOL/USDC rate prices were compared at
+    pub quotes: LegQuotes,
+}
+
+/// What simulating the signed arbitrage transaction showed
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    /// Error the simulated transaction failed with, if any
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Change of the wallet's SOL and wrapped SOL over the simulation, and
+    /// of its USDC at the reference rate when a leg is in USDC; the venues'
+    /// swap fees are already taken out of it
+    pub balance_change_lamports: i64,
+    /// Raw change of the wallet's USDC, when a leg is in USDC
+    pub usdc_change: Option<i64>,
+    pub base_fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    /// Jito tip paid alongside a bundle, 0 when sent through RPC
+    pub tip_lamports: u64,
+    /// Swap fees of both legs at the registry rates, for reference only
+    pub dex_fee_lamports: u64,
+    /// Balance change less the signature and priority fees and the tip
+    pub net_profit_lamports: i64,
+}
+
+/// How an execution attempt ended
+#[derive(Debug, Clone)]
+pub enum ExecutionOutcome {
+    Sent {
+        signature: String,
+        /// Set when the transaction went out in a Jito bundle
+        bundle_id: Option<String>,
+        tokens: u64,
+        expected_out_lamports: u64,
+        simulation: SimulationReport,
+    },
+    /// Simulated, but failed or didn't clear `MIN_NET_PROFIT_LAMPORTS`
+    Unprofitable { reason: String, simulation: SimulationReport },
+    Failed { reason: String },
+}
+
+impl ExecutionOutcome {
+    /// Stable code used for events and opportunity records
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Sent { .. } => "executed",
+            Self::Unprofitable { .. } => "simulation_rejected",
+            Self::Failed { .. } => "execution_failed",
+        }
+    }
+
+    /// `execution` object of the opportunity record file
+    pub fn to_record(&self, trade_id: &TradeId) -> serde_json::Value {
+        match self {
+            Self::Sent { signature, bundle_id, tokens, expected_out_lamports, simulation } => serde_json::json!({
+                "trade_id": trade_id,
+                "status": self.code(),
+                "signature": signature,
+                "bundle_id": bundle_id,
+                "tokens": tokens,
+                "expected_out_lamports": expected_out_lamports,
+                "simulation": simulation,
+            }),
+            Self::Unprofitable { reason, simulation } => serde_json::json!({
+                "trade_id": trade_id,
+                "status": self.code(),
+                "reason": reason,
+                "simulation": simulation,
+            }),
+            Self::Failed { reason } => serde_json::json!({
+                "trade_id": trade_id,
+                "status": self.code(),
+                "reason": reason,
+            }),
+        }
+    }
+}
+
+/// Instructions of one leg and the amount it is quoted to put out
+struct Leg {
+    instructions: Vec<Instruction>,
+    amount_out: u64,
+}
+
+/// Why new arbitrage positions may not be opened right now, if they may not
+fn entries_refused(app_state: &AppState) -> Option<String> {
+    if liquidation::is_liquidating(app_state) {
+        Some("panic liquidation in progress, no new entries".to_string())
+    } else if app_state.monitor.entries_paused() {
+        Some("entries paused by operator".to_string())
+    } else if fill_quality::is_paused(Strategy::Arbitrage) {
+        Some("entries paused on fill quality".to_string())
+    } else {
+        None
+    }
+}
+
+/// Both pools of an opportunity, read once for sizing and building the legs
+struct LegPools {
+    buy_adapter: Arc<dyn DexAdapter>,
+    buy: PoolInfo,
+    sell_adapter: Arc<dyn DexAdapter>,
+    sell: PoolInfo,
+    /// The SOL/USDC reference pool, trading USDC, when a USDC leg is converted
+    conversion: Option<(Arc<dyn DexAdapter>, PoolInfo)>,
+    /// Priced for writing these pools
+    budget: ComputeBudget,
+}
+
+/// Buy the token on the buy venue and sell the tokens that buy is
+/// guaranteed on the sell venue, in one transaction
+///
+/// The arbitrage allocation must cover the full size; the sell's minimum is
+/// its quote less slippage and the attempt is dropped before sending when
+/// that minimum doesn't return the SOL put in.
+pub async fn execute_arbitrage(
+    app_state: &Arc<AppState>,
+    legs: ArbitrageLegs<'_>,
+    trade_id: &TradeId,
+    swap_config: &SwapConfig,
+    latency: &mut LatencyTracer,
+    logger: &Logger,
+) -> ExecutionOutcome {
+    // Arbitrage buys the token too, so it stops with every other entry path
+    if let Some(reason) = entries_refused(app_state) {
+        return ExecutionOutcome::Failed { reason };
+    }
+    let pools = match read_leg_pools(app_state, legs, swap_config.priority_fee, logger).await {
+        Ok(pools) => pools,
+        Err(e) => return ExecutionOutcome::Failed { reason: e.to_string() },
+    };
+    let amount_in = match trade_size(app_state, legs, &pools, swap_config, logger).await {
+        Ok(amount_in) => amount_in,
+        Err(e) => return ExecutionOutcome::Failed { reason: e.to_string() },
+    };
+    match app_state.capital.grant(Strategy::Arbitrage, trade_id.as_str(), amount_in) {
+        Ok(granted) if granted >= amount_in => {}
+        Ok(granted) => {
+            app_state.capital.release(Strategy::Arbitrage, trade_id.as_str());
+            return ExecutionOutcome::Failed {
+                reason: format!("allocation covers {} of {}", fmt_sol(granted), fmt_sol(amount_in)),
+            };
+        }
+        Err(e) => return ExecutionOutcome::Failed { reason: e.to_string() },
+    }
+
+    let outcome = match send(app_state, legs, &pools, trade_id, swap_config, amount_in, latency, logger).await {
+        Ok(outcome) => outcome,
+        Err(e) => ExecutionOutcome::Failed { reason: e.to_string() },
+    };
+    // The transaction settles both legs or neither, nothing stays open
+    app_state.capital.release(Strategy::Arbitrage, trade_id.as_str());
+
+    match &outcome {
+        ExecutionOutcome::Sent { signature, tokens, expected_out_lamports, simulation, .. } => {
+            logger.log(format!(
+                "\n\t * [EXECUTED] => {} Token: {} ({} -> {}), in {}, expected out {}, simulated net {} lamports, TX_HASH: (https://solscan.io/tx/{})",
+                trade_id, legs.token, legs.buy_dex, legs.sell_dex,
+                fmt_sol(amount_in), fmt_sol(*expected_out_lamports), simulation.net_profit_lamports, signature
+            ).green().bold().to_string());
+            let now = chrono::Utc::now().timestamp_millis();
+            let trade = TradeRecord {
+                trade_id: trade_id.to_string(),
+                created_at: now,
+                mint: legs.token.to_string(),
+                side: "arbitrage".to_string(),
+                sol_amount: Some(amount_in),
+                token_amount: Some(*tokens),
+                price: (*tokens > 0).then(|| amount_in as f64 / *tokens as f64),
+                signature: Some(signature.clone()),
+                priority_fee_strategy: Some(swap_config.priority_fee.to_string()),
+                priority_fee_lamports: Some(simulation.priority_fee_lamports),
+            };
+            if let Err(e) = app_state.store.record_trade(&trade) {
+                logger.log(format!("[RECORD] => Failed to store trade: {}", e).red().to_string());
+            }
+        }
+        ExecutionOutcome::Unprofitable { reason, .. } => {
+            logger.log(format!(
+                "\n\t * [SIMULATION REJECTED] => {} Token: {} ({} -> {}), reason: {}",
+                trade_id, legs.token, legs.buy_dex, legs.sell_dex, reason
+            ).yellow().to_string());
+        }
+        ExecutionOutcome::Failed { reason } => {
+            logger.log(format!(
+                "\n\t * [EXECUTION FAILED] => {} Token: {} ({} -> {}), reason: {}",
+                trade_id, legs.token, legs.buy_dex, legs.sell_dex, reason
+            ).red().to_string());
+        }
+    }
+    outcome
+}
+
+async fn read_leg_pools(
+    app_state: &Arc<AppState>,
+    legs: ArbitrageLegs<'_>,
+    priority_fee: PriorityFeeStrategy,
+    logger: &Logger,
+) -> Result<LegPools> {
+    let mint = Pubkey::from_str(legs.token).map_err(|_| anyhow!("Invalid mint address {}", legs.token))?;
+    let (buy_adapter, buy) = read_pool(app_state, legs.buy_dex, legs.buy_pool, mint, legs.quotes.buy).await?;
+    let (sell_adapter, sell) = read_pool(app_state, legs.sell_dex, legs.sell_pool, mint, legs.quotes.sell).await?;
+    let conversion = if legs.quotes.uses_usdc() && UsdcLegMode::from_env() == UsdcLegMode::Convert {
+        let reference = app_state.price_normalizer.pool().to_string();
+        Some(read_pool(app_state, "raydium_amm", &reference, QuoteAsset::Usdc.mint(), QuoteAsset::Sol).await?)
+    } else {
+        None
+    };
+    let mut written = vec![buy.pool_id, sell.pool_id];
+    written.extend(conversion.as_ref().map(|(_, pool)| pool.pool_id));
+    let expected_profit = legs.planned.map(|planned| planned.expected_profit_lamports);
+    let budget = compute_budget(app_state, &written, priority_fee, expected_profit, logger).await;
+    logger.log(format!(
+        "[PRIORITY FEE] => {}: {} micro-lamports x {} units = {} lamports",
+        priority_fee, budget.unit_price, budget.unit_limit, budget.fee_lamports()
+    ).cyan().to_string());
+    Ok(LegPools { buy_adapter, buy, sell_adapter, sell, conversion, budget })
+}
+
+async fn read_pool(
+    app_state: &Arc<AppState>,
+    dex: &str,
+    pool_id: &str,
+    mint: Pubkey,
+    quote: QuoteAsset,
+) -> Result<(Arc<dyn DexAdapter>, PoolInfo)> {
+    let adapter = app_state.dex_registry.adapter(dex).ok_or_else(|| anyhow!("no swap builder for {}", dex))?;
+    let pool_id = Pubkey::from_str(pool_id).map_err(|_| anyhow!("No usable {} pool ({})", dex, pool_id))?;
+    let pool = adapter.read_quoted_pool(&pool_id, &mint, &quote.mint()).await?;
+    Ok((adapter, pool))
+}
+
+/// A pool's reserves with the quote side in lamports at the reference rate
+fn sol_reserves(app_state: &Arc<AppState>, pool: &PoolInfo, quote: QuoteAsset) -> Result<Reserves> {
+    let sol = app_state
+        .price_normalizer
+        .lamports(pool.quote_reserve, quote, app_state.clock.now())
+        .ok_or_else(|| anyhow!("no fresh SOL/USDC rate to price {} in SOL", pool.pool_id))?;
+    Ok(Reserves { sol, token: pool.token_reserve })
+}
+
+/// `lamports` as raw USDC at the reference rate
+fn usdc_for(app_state: &Arc<AppState>, lamports: u64) -> Result<u64> {
+    app_state
+        .price_normalizer
+        .from_lamports(lamports, QuoteAsset::Usdc, app_state.clock.now())
+        .ok_or_else(|| anyhow!("no fresh SOL/USDC rate to convert at"))
+}
+
+/// Raw USDC as lamports at the reference rate
+fn lamports_for(app_state: &Arc<AppState>, usdc: u64) -> Result<u64> {
+    app_state
+        .price_normalizer
+        .lamports(usdc, QuoteAsset::Usdc, app_state.clock.now())
+        .ok_or_else(|| anyhow!("no fresh SOL/USDC rate to convert at"))
+}
+
+/// The profit-maximizing input for the pools' current reserves, capped by
+/// `MAX_POSITION_SIZE` and by the wallet's SOL less this transaction's fees,
+/// or by its USDC when a USDC buy is floated
+async fn trade_size(
+    app_state: &Arc<AppState>,
+    legs: ArbitrageLegs<'_>,
+    pools: &LegPools,
+    swap_config: &SwapConfig,
+    logger: &Logger,
+) -> Result<u64> {
+    // A quoted venue's reserves don't say how it fills, so the size the scan
+    // searched over its quotes stands; reserves read now win otherwise
+    let optimal = match legs.planned.filter(|planned| !planned.closed_form) {
+        Some(planned) => planned,
+        None => {
+            // A converted USDC leg pays the reference pool's fee on top of its own
+            let conversion_bps = |quote: QuoteAsset| match (quote, &pools.conversion) {
+                (QuoteAsset::Usdc, Some((adapter, _))) => app_state.dex_registry.fee_bps(adapter.name()),
+                _ => 0,
+            };
+            optimal_arbitrage_amount(
+                sol_reserves(app_state, &pools.buy, legs.quotes.buy)?,
+                sol_reserves(app_state, &pools.sell, legs.quotes.sell)?,
+                LegFees {
+                    buy_bps: app_state.dex_registry.fee_bps(legs.buy_dex) + conversion_bps(legs.quotes.buy),
+                    sell_bps: app_state.dex_registry.fee_bps(legs.sell_dex) + conversion_bps(legs.quotes.sell),
+                },
+            )
+            .ok_or_else(|| anyhow!("reserves on {} and {} leave no profitable size", legs.buy_dex, legs.sell_dex))?
+        }
+    };
+    let fees = BASE_FEE_LAMPORTS + pools.budget.fee_lamports() + bundle_tip_lamports(swap_config);
+    let spendable = match (legs.quotes.buy, &pools.conversion) {
+        // A floated USDC buy spends the wallet's USDC; SOL only pays the fees
+        (QuoteAsset::Usdc, None) => lamports_for(app_state, wallet_usdc(app_state).await?)?,
+        _ => wallet_sol(app_state).await?.saturating_sub(fees),
+    };
+    let amount_in = optimal.amount_in_lamports.min(max_position_lamports(swap_config)).min(spendable);
+    check_venue_minimums(&app_state.dex_registry, legs.buy_dex, legs.sell_dex, amount_in)
+        .map_err(|reason| anyhow!("sized at {}: {}", fmt_sol(amount_in), reason.code()))?;
+    logger.log(format!(
+        "[SIZED] => {} on {} -> {}: optimal {} for {} expected, trading {}",
+        legs.token, legs.buy_dex, legs.sell_dex,
+        fmt_sol(optimal.amount_in_lamports), fmt_sol(optimal.expected_profit_lamports), fmt_sol(amount_in)
+    ).cyan().to_string());
+    Ok(amount_in)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn send(
+    app_state: &Arc<AppState>,
+    legs: ArbitrageLegs<'_>,
+    pools: &LegPools,
+    trade_id: &TradeId,
+    swap_config: &SwapConfig,
+    amount_in: u64,
+    latency: &mut LatencyTracer,
+    logger: &Logger,
+) -> Result<ExecutionOutcome> {
+    let slippage_bps = swap_config.slippage_bps;
+
+    // A USDC buy spends what converting the SOL guarantees, or the float's
+    // USDC worth the same
+    let (convert_in, buy_in) = match (legs.quotes.buy, &pools.conversion) {
+        (QuoteAsset::Sol, _) => (None, amount_in),
+        (QuoteAsset::Usdc, Some((adapter, pool))) => {
+            let leg = build_buy_leg(adapter.as_ref(), pool, amount_in, slippage_bps).await?;
+            let usdc = leg.amount_out;
+            (Some(leg), usdc)
+        }
+        (QuoteAsset::Usdc, None) => (None, usdc_for(app_state, amount_in)?),
+    };
+    let buy = build_buy_leg(pools.buy_adapter.as_ref(), &pools.buy, buy_in, slippage_bps).await?;
+    let sell = build_sell_leg(pools.sell_adapter.as_ref(), &pools.sell, buy.amount_out, slippage_bps).await?;
+    // and what a USDC sell returns is converted back, or kept and valued at the rate
+    let (convert_out, returned) = match (legs.quotes.sell, &pools.conversion) {
+        (QuoteAsset::Sol, _) => (None, sell.amount_out),
+        (QuoteAsset::Usdc, Some((adapter, pool))) => {
+            let leg = build_sell_leg(adapter.as_ref(), pool, sell.amount_out, slippage_bps).await?;
+            let lamports = leg.amount_out;
+            (Some(leg), lamports)
+        }
+        (QuoteAsset::Usdc, None) => (None, lamports_for(app_state, sell.amount_out)?),
+    };
+    if returned <= amount_in {
+        return Err(anyhow!(
+            "sell minimum {} doesn't cover the {} spent",
+            fmt_sol(returned),
+            fmt_sol(amount_in)
+        ));
+    }
+    if legs.quotes.uses_usdc() {
+        logger.log(format!(
+            "[USDC LEG] => {} buy in {}, sell in {}, {} {} USDC",
+            trade_id,
+            legs.quotes.buy.name(),
+            legs.quotes.sell.name(),
+            if pools.conversion.is_some() { "converting through the reference pool," } else { "settling against the wallet's float," },
+            if legs.quotes.buy == QuoteAsset::Usdc { buy_in } else { sell.amount_out },
+        ).cyan().to_string());
+    }
+    latency.mark(Stage::Built);
+
+    let buy_instructions = join_legs(convert_in.map(|leg| leg.instructions).unwrap_or_default(), buy.instructions);
+    let sell_instructions = join_legs(sell.instructions, convert_out.map(|leg| leg.instructions).unwrap_or_default());
+    let recent_blockhash = blockhash_cache::recent(app_state, CallClass::Quote, logger).await?;
+    latency.mark(Stage::Blockhash);
+    let lookup_tables = arbitrage_alt::lookup_tables(app_state, logger).await;
+    let leg_instructions: Vec<Instruction> = buy_instructions.iter().chain(&sell_instructions).cloned().collect();
+    let atomic = tx::build_atomic_arbitrage_tx(
+        &app_state.wallet,
+        buy_instructions,
+        sell_instructions,
+        recent_blockhash,
+        &lookup_tables,
+        pools.budget,
+    )?;
+    latency.mark(Stage::Signed);
+    arbitrage_alt::learn_accounts(app_state, &lookup_tables, &leg_instructions, logger);
+
+    let tip_lamports = bundle_tip_lamports(swap_config);
+    let track_usdc = legs.quotes.uses_usdc();
+    let mut simulation = simulate(app_state, &atomic.transaction, pools.budget, tip_lamports, track_usdc).await?;
+    simulation.dex_fee_lamports = swap_fee(app_state, legs.buy_dex, amount_in) + swap_fee(app_state, legs.sell_dex, returned);
+    if let Some(err) = &simulation.err {
+        return Ok(ExecutionOutcome::Unprofitable { reason: format!("simulation failed: {}", err), simulation });
+    }
+    let min_net = min_net_profit_lamports();
+    if simulation.net_profit_lamports <= min_net {
+        return Ok(ExecutionOutcome::Unprofitable {
+            reason: format!(
+                "simulated net {} lamports doesn't exceed MIN_NET_PROFIT_LAMPORTS {}",
+                simulation.net_profit_lamports, min_net
+            ),
+            simulation,
+        });
+    }
+
+    logger.log(format!(
+        "[EXECUTING] => {} buying {} on {}, selling on {} for at least {} (detected net {}, {} bytes)",
+        trade_id, fmt_sol(amount_in), legs.buy_dex, legs.sell_dex, fmt_sol(returned), fmt_pct(legs.profit_pct), atomic.size
+    ).yellow().to_string());
+    leader::ensure_leader()?;
+    let (signature, bundle_id) = if swap_config.use_jito {
+        let signature = atomic.transaction.signatures.first().map(|s| s.to_string()).unwrap_or_default();
+        let bundle_id = tx::submit_jito_bundle(&app_state.wallet, vec![atomic.transaction], tip_lamports, recent_blockhash, logger).await?;
+        logger.log(format!("[BUNDLE] => {} sent as bundle {}", trade_id, bundle_id).cyan().to_string());
+        (signature, Some(bundle_id))
+    } else {
+        let signature = app_state
+            .rpc_nonblocking_client
+            .send_transaction_with_config(
+                &atomic.transaction,
+                RpcSendTransactionConfig { skip_preflight: true, ..RpcSendTransactionConfig::default() },
+            )
+            .await?
+            .to_string();
+        (signature, None)
+    };
+    latency.mark(Stage::Submitted);
+
+    Ok(ExecutionOutcome::Sent {
+        signature,
+        bundle_id,
+        tokens: buy.amount_out,
+        expected_out_lamports: returned,
+        simulation,
+    })
+}
+
+/// Simulate `transaction` and measure what it does to the wallet's SOL,
+/// native and wrapped together since the legs wrap and unwrap as they go
+///
+/// The fees are taken off explicitly; a validator that already charges them
+/// in simulated balances only makes the check stricter. With `track_usdc`
+/// the wallet's USDC is measured too and counted at the reference rate.
+async fn simulate(
+    app_state: &Arc<AppState>,
+    transaction: &VersionedTransaction,
+    budget: ComputeBudget,
+    tip_lamports: u64,
+    track_usdc: bool,
+) -> Result<SimulationReport> {
+    let wallet = app_state.wallet.pubkey();
+    let wsol_account = get_associated_token_address(&wallet, &spl_token::native_mint::id());
+    let usdc_account = get_associated_token_address(&wallet, &QuoteAsset::Usdc.mint());
+    let before = wallet_sol(app_state).await?;
+    let usdc_before = if track_usdc { Some(wallet_usdc(app_state).await?) } else { None };
+    let mut addresses = vec![wallet.to_string(), wsol_account.to_string()];
+    if track_usdc {
+        addresses.push(usdc_account.to_string());
+    }
+
+    let result = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| {
+            let transaction = transaction.clone();
+            let config = RpcSimulateTransactionConfig {
+                sig_verify: false,
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: addresses.clone(),
+                }),
+                ..RpcSimulateTransactionConfig::default()
+            };
+            async move { client.simulate_transaction_with_config(&transaction, config).await }
+        })
+        .await?
+        .value;
+    let mut accounts = result.accounts.unwrap_or_default().into_iter();
+    let wallet_after = accounts.next().flatten().map(|a| a.lamports);
+    let wsol_after = accounts.next().flatten().and_then(|a| a.data.decode());
+    let after = sol_held(wallet_after, wsol_after);
+    let usdc_change = usdc_before.map(|before| {
+        let after = token_amount(accounts.next().flatten().and_then(|a| a.data.decode()));
+        after as i64 - before as i64
+    });
+    // USDC the trade leaves behind or takes counts at what it is worth in SOL
+    let usdc_lamports = match usdc_change {
+        Some(change) => {
+            let lamports = lamports_for(app_state, change.unsigned_abs())? as i64;
+            if change < 0 { -lamports } else { lamports }
+        }
+        None => 0,
+    };
+
+    let balance_change_lamports = after as i64 - before as i64 + usdc_lamports;
+    let priority_fee_lamports = budget.fee_lamports();
+    Ok(SimulationReport {
+        err: result.err.map(|e| e.to_string()),
+        logs: result.logs.unwrap_or_default(),
+        units_consumed: result.units_consumed,
+        balance_change_lamports,
+        usdc_change,
+        base_fee_lamports: BASE_FEE_LAMPORTS,
+        priority_fee_lamports,
+        tip_lamports,
+        dex_fee_lamports: 0,
+        net_profit_lamports: balance_change_lamports - (BASE_FEE_LAMPORTS + priority_fee_lamports + tip_lamports) as i64,
+    })
+}
+
+/// Compute budget for an arbitrage writing `pools` under `strategy`;
+/// a profit share needs the sized opportunity's `expected_profit`, and
+/// `UNIT_PRICE` stands in when recent fees can't be fetched or are all zero
+async fn compute_budget(
+    app_state: &Arc<AppState>,
+    pools: &[Pubkey],
+    strategy: PriorityFeeStrategy,
+    expected_profit: Option<u64>,
+    logger: &Logger,
+) -> ComputeBudget {
+    let configured = ComputeBudget::configured();
+    match (strategy, expected_profit) {
+        (PriorityFeeStrategy::Fixed, _) => return configured,
+        (PriorityFeeStrategy::ProfitShare { bps }, Some(profit)) => {
+            let unit_price = PriorityFeeStrategy::profit_share_price(bps, profit, configured.unit_limit);
+            return ComputeBudget { unit_price, ..configured };
+        }
+        _ => {}
+    }
+    let mut accounts = pools.to_vec();
+    accounts.sort_unstable();
+    accounts.dedup();
+    let estimated = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| {
+            let accounts = accounts.clone();
+            async move { PRIORITY_FEES.unit_price(&client, &accounts).await }
+        })
+        .await;
+    match estimated {
+        Ok(Some(unit_price)) => {
+            logger.debug_with(|| format!("[PRIORITY FEE] => {} micro-lamports per unit on the pools", unit_price));
+            ComputeBudget { unit_price, ..configured }
+        }
+        Ok(None) => configured,
+        Err(e) => {
+            logger.log(format!("[PRIORITY FEE] => Using UNIT_PRICE: {}", e).yellow().to_string());
+            configured
+        }
+    }
+}
+
+/// The wallet's SOL, native and wrapped
+async fn wallet_sol(app_state: &Arc<AppState>) -> Result<u64> {
+    let wallet = app_state.wallet.pubkey();
+    let wsol_account = get_associated_token_address(&wallet, &spl_token::native_mint::id());
+    let accounts = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| async move {
+            client.get_multiple_accounts(&[wallet, wsol_account]).await
+        })
+        .await?;
+    Ok(sol_held(accounts.first().cloned().flatten().map(|a| a.lamports), accounts.get(1).cloned().flatten().map(|a| a.data)))
+}
+
+/// Raw USDC in the wallet's USDC account, 0 without one
+async fn wallet_usdc(app_state: &Arc<AppState>) -> Result<u64> {
+    let usdc_account = get_associated_token_address(&app_state.wallet.pubkey(), &QuoteAsset::Usdc.mint());
+    let accounts = app_state
+        .rpc_pool
+        .call(CallClass::Quote, |client| async move { client.get_multiple_accounts(&[usdc_account]).await })
+        .await?;
+    Ok(token_amount(accounts.into_iter().next().flatten().map(|a| a.data)))
+}
+
+/// Wallet lamports plus the wrapped SOL in its WSOL account
+fn sol_held(lamports: Option<u64>, wsol_data: Option<Vec<u8>>) -> u64 {
+    lamports.unwrap_or(0) + token_amount(wsol_data)
+}
+
+/// Amount held by a token account, 0 for a missing or undecodable one
+fn token_amount(data: Option<Vec<u8>>) -> u64 {
+    data.and_then(|data| spl_token::state::Account::unpack(&data).ok())
+        .map(|account| account.amount)
+        .unwrap_or(0)
+}
+
+/// `first` then `then`, without the ATA creates `then` repeats from `first`
+fn join_legs(mut first: Vec<Instruction>, then: Vec<Instruction>) -> Vec<Instruction> {
+    let repeated_create = |ix: &Instruction| OptionalKind::of(ix) == Some(OptionalKind::AtaCreate) && first.contains(ix);
+    let then: Vec<Instruction> = then.into_iter().filter(|ix| !repeated_create(ix)).collect();
+    first.extend(then);
+    first
+}
+
+/// `amount` at the venue's registry fee rate
+fn swap_fee(app_state: &Arc<AppState>, dex: &str, amount: u64) -> u64 {
+    (amount as u128 * app_state.dex_registry.fee_bps(dex) as u128 / 10_000) as u64
+}
+
+/// Spend `amount_in` lamports on the token in `pool`; the leg's output is
+/// the buy's guaranteed minimum, so the sell never spends tokens it may not get
+async fn build_buy_leg(adapter: &dyn DexAdapter, pool: &PoolInfo, amount_in: u64, slippage_bps: u64) -> Result<Leg> {
+    let amount_out = min_amount_with_slippage(adapter.quote(pool, amount_in, SwapDirection::Buy).await?, slippage_bps);
+    let instructions = adapter.build_swap_instructions(pool, amount_in, amount_out, SwapDirection::Buy).await?;
+    Ok(Leg { instructions, amount_out })
+}
+
+/// Sell exactly `tokens` of the token for SOL in `pool`
+async fn build_sell_leg(adapter: &dyn DexAdapter, pool: &PoolInfo, tokens: u64, slippage_bps: u64) -> Result<Leg> {
+    let amount_out = min_amount_with_slippage(adapter.quote(pool, tokens, SwapDirection::Sell).await?, slippage_bps);
+    let instructions = adapter.build_swap_instructions(pool, tokens, amount_out, SwapDirection::Sell).await?;
+    Ok(Leg { instructions, amount_out })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::rng::SessionRng;
+
+    #[tokio::test]
+    async fn paused_entries_are_refused_before_any_pool_is_read() {
+        // Every RPC call errors, so reaching the pools would fail with an RPC reason instead
+        let app_state = AppState::for_tests_with_rpc("fails", crate::services::notifier::Notifier::new(None));
+        let legs = ArbitrageLegs {
+            token: "mint",
+            buy_dex: "pumpswap",
+            buy_pool: "buy",
+            sell_dex: "raydium_amm",
+            sell_pool: "sell",
+            profit_pct: 2.0,
+            planned: None,
+            quotes: LegQuotes { buy: QuoteAsset::Sol, sell: QuoteAsset::Sol, reference: None },
+        };
+        let swap_config = SwapConfig {
+            swap_direction: SwapDirection::Buy,
+            in_type: crate::engine::swap::SwapInType::Qty,
+            amount_in: 0.1,
+            slippage_bps: 50,
+            use_jito: false,
+            priority_fee: PriorityFeeStrategy::Fixed,
+        };
+        let trade_id = crate::core::ids::OpportunityId::derive("mint", "buy", "sell", 1, 0).trade(1);
+        let rng = SessionRng::from_seed(7);
+        let mut latency = LatencyTracer::start("arbitrage", Stage::Decided, app_state.clock.now(), &rng);
+        let logger = Logger::new(String::new());
+
+        app_state.monitor.set_entries_paused(true);
+        let outcome = execute_arbitrage(&app_state, legs, &trade_id, &swap_config, &mut latency, &logger).await;
+
+        match outcome {
+            ExecutionOutcome::Failed { reason } => assert!(reason.contains("entries"), "{}", reason),
+            other => panic!("expected a refusal, got {}", other.code()),
+        }
+    }
+}
@@ -0,0 +1,657 @@
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{hash::Hash, signer::Signer};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use colored::Colorize;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::common::{
+    clock::Clock,
+    config::{config_version, slippage_to_bps, AppState, SwapConfig, MAX_SLIPPAGE_BPS},
+    logger::Logger,
+};
+use crate::common::sync::LockExt;
+use crate::core::priority_fee::PriorityFeeStrategy;
+use crate::core::tx;
+use crate::dex::pump_swap::PumpSwap;
+use crate::engine::blockhash_cache;
+use crate::engine::confirmation::{self, Confirmation};
+use crate::engine::copy_validation::{self, SimulationConfig};
+use crate::engine::durable_nonce;
+use crate::engine::liquidation;
+use crate::engine::swap::{SwapDirection, SwapInType};
+use crate::record::journal::{JournalScope, JournalSource};
+use crate::services::rpc_pool::CallClass;
+
+/// How often a waiting ladder checks for an exit elsewhere or the kill switch
+const INTERRUPT_POLL: Duration = Duration::from_millis(500);
+
+/// Transaction path a stage sells through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitSender {
+    Normal,
+    Zeroslot,
+    Jito,
+    /// Jito, Nozomi and ZeroSlot at once, each with its own tip
+    Spam,
+}
+
+impl FromStr for ExitSender {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "normal" => Ok(Self::Normal),
+            "zeroslot" => Ok(Self::Zeroslot),
+            "jito" => Ok(Self::Jito),
+            "spam" => Ok(Self::Spam),
+            other => Err(anyhow!("Unknown exit sender: {}", other)),
+        }
+    }
+}
+
+/// One rung of the ladder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExitStage {
+    /// Position age at which this stage may start
+    pub after: Duration,
+    /// Slippage in bps
+    pub slippage_bps: u64,
+    pub sender: ExitSender,
+    /// Count the position as exited once sent, without waiting for confirmation
+    pub fire_and_forget: bool,
+    /// How long to wait for confirmation before moving to the next stage
+    pub confirm_timeout: Duration,
+}
+
+/// How often a stage resubmits a sell that failed to send before moving on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SellRetry {
+    /// Submissions per stage, the first included
+    pub max_attempts: u32,
+    /// Wait after the first failure, doubled after each further one
+    pub base_delay: Duration,
+}
+
+impl Default for SellRetry {
+    fn default() -> Self {
+        Self { max_attempts: 3, base_delay: Duration::from_millis(250) }
+    }
+}
+
+impl SellRetry {
+    /// `SELL_RETRY_ATTEMPTS` and `SELL_RETRY_BASE_MS`, defaults for what is unset
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: std::env::var("SELL_RETRY_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.trim().parse::<u32>().ok())
+                .filter(|attempts| *attempts > 0)
+                .unwrap_or(default.max_attempts),
+            base_delay: std::env::var("SELL_RETRY_BASE_MS")
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+        }
+    }
+
+    /// Wait before submission `attempt` (1-based, so never for the first)
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        match attempt {
+            0 | 1 => Duration::ZERO,
+            n => self.base_delay.saturating_mul(1 << (n - 2).min(16)),
+        }
+    }
+}
+
+/// Stages a timed-out position goes through, each more aggressive than the last
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitLadder {
+    pub stages: Vec<ExitStage>,
+    pub retry: SellRetry,
+}
+
+impl ExitLadder {
+    /// Default ladder scaled off MAX_WAIT_TIME: normal slippage, doubled
+    /// slippage, market through ZeroSlot, then fire-and-forget on every sender
+    pub fn default_for(max_wait: Duration, slippage_bps: u64) -> Self {
+        let confirm_timeout = Duration::from_secs(15);
+        let stage = |factor: f64, slippage_bps: u64, sender: ExitSender, fire_and_forget: bool| ExitStage {
+            after: max_wait.mul_f64(factor),
+            slippage_bps: slippage_bps.min(MAX_SLIPPAGE_BPS),
+            sender,
+            fire_and_forget,
+            confirm_timeout,
+        };
+        Self {
+            stages: vec![
+                stage(1.0, slippage_bps, ExitSender::Normal, false),
+                stage(1.5, slippage_bps.saturating_mul(2), ExitSender::Normal, false),
+                stage(2.0, MAX_SLIPPAGE_BPS, ExitSender::Zeroslot, false),
+                stage(3.0, MAX_SLIPPAGE_BPS, ExitSender::Spam, true),
+            ],
+            retry: SellRetry::from_env(),
+        }
+    }
+
+    /// `EXIT_LADDER` as comma-separated `after_ms:slippage:sender[:fire]`
+    /// stages with slippage in bps, e.g.
+    /// "60000:1000:normal,90000:2500:normal,120000:10000:zeroslot,180000:10000:spam:fire".
+    /// Falls back to `default_for` when unset.
+    pub fn from_env(max_wait: Duration, slippage_bps: u64) -> Result<Self> {
+        let Ok(value) = std::env::var("EXIT_LADDER") else {
+            return Ok(Self::default_for(max_wait, slippage_bps));
+        };
+        let confirm_timeout = std::env::var("EXIT_CONFIRM_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(15));
+        Ok(Self { retry: SellRetry::from_env(), ..Self::parse(&value, confirm_timeout, config_version())? })
+    }
+
+    /// Stage slippages are percent for `config_version` 1, like `SLIPPAGE`
+    pub fn parse(value: &str, confirm_timeout: Duration, config_version: u32) -> Result<Self> {
+        let mut stages = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let parts: Vec<&str> = entry.split(':').collect();
+            let (after, slippage, sender, flag) = match parts.as_slice() {
+                [after, slippage, sender] => (after, slippage, sender, None),
+                [after, slippage, sender, flag] => (after, slippage, sender, Some(*flag)),
+                _ => return Err(anyhow!("Bad exit stage '{}', expected after_ms:slippage:sender[:fire]", entry)),
+            };
+            let fire_and_forget = match flag {
+                None => false,
+                Some("fire") => true,
+                Some(other) => return Err(anyhow!("Unknown exit stage flag: {}", other)),
+            };
+            stages.push(ExitStage {
+                after: Duration::from_millis(after.parse()?),
+                slippage_bps: slippage_to_bps("EXIT_LADDER", slippage.parse::<u64>()?, config_version)?,
+                sender: ExitSender::from_str(sender)?,
+                fire_and_forget,
+                confirm_timeout,
+            });
+        }
+        if stages.is_empty() {
+            return Err(anyhow!("EXIT_LADDER has no stages"));
+        }
+        if stages.windows(2).any(|w| w[1].after < w[0].after) {
+            return Err(anyhow!("EXIT_LADDER stages must be in increasing age order"));
+        }
+        Ok(Self { stages, retry: SellRetry::default() })
+    }
+
+    /// Age at which the ladder starts
+    pub fn start_after(&self) -> Duration {
+        self.stages.first().map(|s| s.after).unwrap_or_default()
+    }
+}
+
+/// How a ladder run ended
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum LadderOutcome {
+    /// `stage` is the index into `ExitLadder::stages` that got the position out
+    Exited { stage: usize, signature: String, sell_price: f64 },
+    /// The position was exited elsewhere or the kill switch fired
+    Interrupted { before_stage: usize },
+    /// Every stage failed
+    Exhausted,
+}
+
+/// A sent sell, waiting for confirmation
+#[derive(Debug, Clone)]
+pub struct SellAttempt {
+    pub signature: String,
+    pub sell_price: f64,
+    /// What it was signed against, a durable nonce's hash included
+    pub blockhash: Hash,
+}
+
+/// What the ladder needs from the execution layer, mocked in tests
+#[async_trait]
+pub trait PositionSeller: Send + Sync {
+    async fn sell(&self, mint: &str, stage: &ExitStage) -> Result<SellAttempt>;
+    /// Wait up to `timeout` for the attempt to land, or until its blockhash expires
+    async fn confirm(&self, attempt: &SellAttempt, timeout: Duration) -> Confirmation;
+}
+
+/// Walk `ladder` for the position opened at `opened_at`
+///
+/// Stages run strictly one after another: a stage waits until the position
+/// reaches its age and the previous attempt has confirmed or timed out.
+/// `interrupted` is checked while waiting and before every attempt.
+pub async fn run_ladder(
+    ladder: &ExitLadder,
+    mint: &str,
+    opened_at: Instant,
+    clock: &dyn Clock,
+    seller: &dyn PositionSeller,
+    interrupted: &(dyn Fn() -> bool + Send + Sync),
+    logger: &Logger,
+) -> LadderOutcome {
+    for (index, stage) in ladder.stages.iter().enumerate() {
+        let due = opened_at + stage.after;
+        loop {
+            if interrupted() {
+                return LadderOutcome::Interrupted { before_stage: index };
+            }
+            let now = clock.now();
+            if now >= due {
+                break;
+            }
+            clock.sleep((due - now).min(INTERRUPT_POLL)).await;
+        }
+
+        logger.log(format!(
+            "\n\t * [EXIT STAGE {}] => Token: {}, slippage: {} bps, sender: {:?}{}",
+            index + 1, mint, stage.slippage_bps, stage.sender,
+            if stage.fire_and_forget { ", fire-and-forget" } else { "" }
+        ).yellow().to_string());
+
+        // A sell whose blockhash expires unlanded is rebuilt, as often as a failed send is retried
+        let mut rebuilds = 0;
+        while let Some(attempt) = sell_with_retry(&ladder.retry, index, mint, stage, clock, seller, interrupted, logger).await {
+            let outcome = if stage.fire_and_forget {
+                Confirmation::Confirmed
+            } else {
+                seller.confirm(&attempt, stage.confirm_timeout).await
+            };
+            match outcome {
+                Confirmation::Confirmed => {
+                    return LadderOutcome::Exited {
+                        stage: index,
+                        signature: attempt.signature,
+                        sell_price: attempt.sell_price,
+                    };
+                }
+                Confirmation::Expired if rebuilds + 1 < ladder.retry.max_attempts => {
+                    rebuilds += 1;
+                    logger.log(format!(
+                        "[EXIT STAGE {}] => Blockhash of {} expired before it landed, rebuilding ({}/{})",
+                        index + 1, attempt.signature, rebuilds, ladder.retry.max_attempts - 1
+                    ).yellow().to_string());
+                }
+                outcome => {
+                    logger.log(format!(
+                        "[EXIT STAGE {}] => {} not confirmed within {:?}: {:?}",
+                        index + 1, attempt.signature, stage.confirm_timeout, outcome
+                    ).red().to_string());
+                    break;
+                }
+            }
+        }
+    }
+    LadderOutcome::Exhausted
+}
+
+/// Submit the stage's sell, resubmitting with backoff while it fails to
+/// send; each submission builds a fresh transaction, blockhash included.
+/// None once the attempts are used up or the position was exited elsewhere.
+#[allow(clippy::too_many_arguments)]
+async fn sell_with_retry(
+    retry: &SellRetry,
+    index: usize,
+    mint: &str,
+    stage: &ExitStage,
+    clock: &dyn Clock,
+    seller: &dyn PositionSeller,
+    interrupted: &(dyn Fn() -> bool + Send + Sync),
+    logger: &Logger,
+) -> Option<SellAttempt> {
+    for attempt in 1..=retry.max_attempts {
+        let delay = retry.delay_before(attempt);
+        if !delay.is_zero() {
+            clock.sleep(delay).await;
+        }
+        if interrupted() {
+            return None;
+        }
+        match seller.sell(mint, stage).await {
+            Ok(sent) => return Some(sent),
+            Err(e) => logger.log(format!(
+                "[EXIT STAGE {}] => Sell attempt {}/{} failed for {}: {}",
+                index + 1, attempt, retry.max_attempts, mint, e
+            ).red().to_string()),
+        }
+    }
+    None
+}
+
+/// Mints with a ladder in flight, so the timeout check never starts a second one
+#[derive(Debug, Default)]
+pub struct ActiveLadders(Mutex<HashSet<String>>);
+
+impl ActiveLadders {
+    /// Claim `mint`, false if a ladder is already running for it
+    pub fn start(&self, mint: &str) -> bool {
+        self.0.lock_or_recover().insert(mint.to_string())
+    }
+
+    pub fn finish(&self, mint: &str) {
+        self.0.lock_or_recover().remove(mint);
+    }
+}
+
+/// Sells through PumpSwap with the stage's slippage and sender
+pub struct PumpSwapSeller {
+    pub app_state: Arc<AppState>,
+    pub logger: Logger,
+}
+
+#[async_trait]
+impl PositionSeller for PumpSwapSeller {
+    async fn sell(&self, mint: &str, stage: &ExitStage) -> Result<SellAttempt> {
+        let swapx = PumpSwap::new(
+            self.app_state.wallet.clone(),
+            Some(self.app_state.rpc_client.clone()),
+            Some(self.app_state.rpc_nonblocking_client.clone()),
+        );
+        let sell_config = SwapConfig {
+            swap_direction: SwapDirection::Sell,
+            in_type: SwapInType::Pct,
+            amount_in: 1_f64, // Sell 100%
+            slippage_bps: stage.slippage_bps,
+            use_jito: stage.sender == ExitSender::Jito,
+            priority_fee: PriorityFeeStrategy::from_env(),
+        };
+        let priority_fee = sell_config.priority_fee;
+        let (keypair, mut instructions, sell_price) = swapx
+            .build_swap_ixn_by_mint(mint, None, sell_config, Instant::now())
+            .await?;
+        let recent_blockhash =
+            durable_nonce::blockhash_for_send(&self.app_state, &keypair.pubkey(), &mut instructions, CallClass::Exit, &self.logger).await?;
+        if SimulationConfig::from_env().enabled {
+            // A failed simulation fails the stage, the ladder moves on to the next
+            copy_validation::simulate_and_check(&self.app_state, &keypair, &instructions, recent_blockhash, &self.logger)
+                .await
+                .inspect_err(|_| durable_nonce::release(&recent_blockhash))?;
+        }
+
+        let sent = match stage.sender {
+            ExitSender::Normal => tx::new_signed_and_send_normal(recent_blockhash, &keypair, instructions, priority_fee, &self.logger).await,
+            ExitSender::Zeroslot => tx::new_signed_and_send_zeroslot(recent_blockhash, &keypair, instructions, priority_fee, &self.logger).await,
+            ExitSender::Jito => tx::new_signed_and_send(recent_blockhash, &keypair, instructions, priority_fee, &self.logger).await,
+            ExitSender::Spam => tx::new_signed_and_send_spam(recent_blockhash, &keypair, instructions, priority_fee, &self.logger).await,
+        };
+        let signatures = sent.inspect_err(|e| {
+            durable_nonce::release(&recent_blockhash);
+            // So the retry doesn't sign against the same rejected hash
+            if blockhash_cache::is_expiry_error(e) {
+                self.app_state.blockhashes.invalidate(&recent_blockhash);
+            }
+        })?;
+        let signature = signatures
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Sender returned no signature"))?;
+        Ok(SellAttempt { signature, sell_price, blockhash: recent_blockhash })
+    }
+
+    async fn confirm(&self, attempt: &SellAttempt, timeout: Duration) -> Confirmation {
+        let blockhashes = Arc::clone(&self.app_state.blockhashes);
+        let blockhash = attempt.blockhash;
+        let outcome = confirmation::confirm_before_expiry(
+            self.app_state.rpc_pool.as_ref(),
+            self.app_state.clock.as_ref(),
+            &attempt.signature,
+            timeout,
+            &move || blockhashes.expired(&blockhash),
+        ).await;
+        if outcome == Confirmation::Expired {
+            self.app_state.blockhashes.invalidate(&blockhash);
+        }
+        outcome
+    }
+}
+
+/// Run the ladder for a timed-out position and journal which stage exited it
+///
+/// Stops early once `still_open` reports the position closed or a panic
+/// liquidation starts.
+pub async fn exit_position(
+    app_state: Arc<AppState>,
+    ladder: &ExitLadder,
+    mint: &str,
+    opened_at: Instant,
+    still_open: impl Fn() -> bool + Send + Sync,
+    logger: &Logger,
+) -> LadderOutcome {
+    let seller = PumpSwapSeller { app_state: Arc::clone(&app_state), logger: logger.clone() };
+    let interrupted = || !still_open() || liquidation::is_liquidating(&app_state);
+    let outcome = run_ladder(ladder, mint, opened_at, app_state.clock.as_ref(), &seller, &interrupted, logger).await;
+
+    if let LadderOutcome::Exited { stage, signature, .. } = &outcome {
+        let tags = BTreeMap::from([
+            ("event".to_string(), "timeout_exit".to_string()),
+            ("exit_stage".to_string(), (stage + 1).to_string()),
+        ]);
+        let note = format!("Timed-out position exited at stage {} ({})", stage + 1, signature);
+        if let Err(e) = app_state.store.add_journal_entry(
+            &JournalScope::Mint { mint: mint.to_string() },
+            &note,
+            &tags,
+            JournalSource::System,
+        ) {
+            logger.log(format!("[JOURNAL] => Failed to record exit stage: {}", e).red().to_string());
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::common::clock::MockClock;
+    use crate::common::config::CURRENT_CONFIG_VERSION;
+
+    fn ladder() -> ExitLadder {
+        let stage = |secs: u64, slippage_bps: u64, sender: ExitSender, fire_and_forget: bool| ExitStage {
+            after: Duration::from_secs(secs),
+            slippage_bps,
+            sender,
+            fire_and_forget,
+            confirm_timeout: Duration::from_secs(15),
+        };
+        ExitLadder {
+            stages: vec![
+                stage(60, 1_000, ExitSender::Normal, false),
+                stage(90, 2_000, ExitSender::Normal, false),
+                stage(120, 10_000, ExitSender::Zeroslot, false),
+                stage(180, 10_000, ExitSender::Spam, true),
+            ],
+            retry: SellRetry { max_attempts: 2, base_delay: Duration::from_millis(250) },
+        }
+    }
+
+    /// Sends every sell but only lands those at `lands_at_slippage`; the first
+    /// `failed_sends` sells never go out
+    struct MockSeller {
+        clock: Arc<MockClock>,
+        failed_sends: Mutex<u32>,
+        lands_at_slippage: Option<u64>,
+        expired: Mutex<u32>,
+        /// (slippage, position age) of every submission
+        sells: Mutex<Vec<(u64, Duration)>>,
+        opened_at: Instant,
+    }
+
+    impl MockSeller {
+        fn new(clock: &Arc<MockClock>, lands_at_slippage: Option<u64>) -> Self {
+            Self {
+                clock: Arc::clone(clock),
+                failed_sends: Mutex::new(0),
+                lands_at_slippage,
+                expired: Mutex::new(0),
+                sells: Mutex::new(Vec::new()),
+                opened_at: clock.now(),
+            }
+        }
+
+        fn sells(&self) -> Vec<(u64, Duration)> {
+            self.sells.lock_or_recover().clone()
+        }
+    }
+
+    #[async_trait]
+    impl PositionSeller for MockSeller {
+        async fn sell(&self, _mint: &str, stage: &ExitStage) -> Result<SellAttempt> {
+            let age = self.clock.now() - self.opened_at;
+            self.sells.lock_or_recover().push((stage.slippage_bps, age));
+            let mut failed = self.failed_sends.lock_or_recover();
+            if *failed > 0 {
+                *failed -= 1;
+                return Err(anyhow!("relay refused the transaction"));
+            }
+            Ok(SellAttempt { signature: format!("sig-{}", stage.slippage_bps), sell_price: 1.0, blockhash: Hash::default() })
+        }
+
+        async fn confirm(&self, attempt: &SellAttempt, timeout: Duration) -> Confirmation {
+            {
+                let mut expired = self.expired.lock_or_recover();
+                if *expired > 0 {
+                    *expired -= 1;
+                    return Confirmation::Expired;
+                }
+            }
+            if self.lands_at_slippage.is_some_and(|bps| attempt.signature == format!("sig-{}", bps)) {
+                return Confirmation::Confirmed;
+            }
+            self.clock.sleep(timeout).await;
+            Confirmation::TimedOut
+        }
+    }
+
+    async fn run(seller: &MockSeller, clock: &MockClock, interrupted: &(dyn Fn() -> bool + Send + Sync)) -> LadderOutcome {
+        let logger = Logger::new("[TEST] => ".to_string());
+        run_ladder(&ladder(), "mint", seller.opened_at, clock, seller, interrupted, &logger).await
+    }
+
+    #[tokio::test]
+    async fn escalates_until_a_stage_lands_and_records_which() {
+        let clock = Arc::new(MockClock::new());
+        let seller = MockSeller::new(&clock, Some(10_000));
+
+        let outcome = run(&seller, &clock, &|| false).await;
+
+        assert_eq!(outcome, LadderOutcome::Exited { stage: 2, signature: "sig-10000".to_string(), sell_price: 1.0 });
+        let ages: Vec<u64> = seller.sells().iter().map(|(_, age)| age.as_secs()).collect();
+        // Each stage starts at its age, never before, and only one sell per stage went out
+        assert_eq!(seller.sells().iter().map(|(bps, _)| *bps).collect::<Vec<_>>(), [1_000, 2_000, 10_000]);
+        assert_eq!(ages, [60, 90, 120]);
+    }
+
+    #[tokio::test]
+    async fn stages_never_overlap_a_pending_confirmation() {
+        let clock = Arc::new(MockClock::new());
+        let seller = MockSeller::new(&clock, None);
+        let mut ladder = ladder();
+        // Stage two is due while stage one is still waiting on its confirmation
+        ladder.stages[1].after = Duration::from_secs(65);
+        let logger = Logger::new("[TEST] => ".to_string());
+
+        let outcome = run_ladder(&ladder, "mint", seller.opened_at, clock.as_ref(), &seller, &|| false, &logger).await;
+
+        assert_eq!(outcome, LadderOutcome::Exited { stage: 3, signature: "sig-10000".to_string(), sell_price: 1.0 });
+        let sells = seller.sells();
+        assert_eq!(sells[1], (2_000, Duration::from_secs(75)));
+        for pair in sells.windows(2) {
+            assert!(pair[1].1 >= pair[0].1 + Duration::from_secs(15), "{:?}", sells);
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_sends_are_retried_within_the_stage_then_exhaust_the_ladder() {
+        let clock = Arc::new(MockClock::new());
+        let seller = MockSeller::new(&clock, None);
+        *seller.failed_sends.lock_or_recover() = u32::MAX;
+
+        let outcome = run(&seller, &clock, &|| false).await;
+
+        assert_eq!(outcome, LadderOutcome::Exhausted);
+        let sells = seller.sells();
+        // Two attempts per stage, the second after the base delay
+        assert_eq!(sells.len(), 8);
+        assert_eq!(sells[0], (1_000, Duration::from_secs(60)));
+        assert_eq!(sells[1], (1_000, Duration::from_millis(60_250)));
+    }
+
+    #[tokio::test]
+    async fn a_transient_send_failure_is_retried_and_exits_in_the_same_stage() {
+        let clock = Arc::new(MockClock::new());
+        let seller = MockSeller::new(&clock, Some(1_000));
+        *seller.failed_sends.lock_or_recover() = 1;
+
+        let outcome = run(&seller, &clock, &|| false).await;
+
+        assert_eq!(outcome, LadderOutcome::Exited { stage: 0, signature: "sig-1000".to_string(), sell_price: 1.0 });
+        assert_eq!(seller.sells(), [(1_000, Duration::from_secs(60)), (1_000, Duration::from_millis(60_250))]);
+    }
+
+    #[test]
+    fn retry_delay_doubles_after_the_base() {
+        let retry = SellRetry { max_attempts: 5, base_delay: Duration::from_millis(250) };
+        let delays: Vec<u64> = (1..=5).map(|attempt| retry.delay_before(attempt).as_millis() as u64).collect();
+        assert_eq!(delays, [0, 250, 500, 1_000, 2_000]);
+    }
+
+    #[tokio::test]
+    async fn expired_blockhash_rebuilds_the_sell_in_the_same_stage() {
+        let clock = Arc::new(MockClock::new());
+        let seller = MockSeller::new(&clock, Some(1_000));
+        *seller.expired.lock_or_recover() = 1;
+
+        let outcome = run(&seller, &clock, &|| false).await;
+
+        assert_eq!(outcome, LadderOutcome::Exited { stage: 0, signature: "sig-1000".to_string(), sell_price: 1.0 });
+        assert_eq!(seller.sells().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn kill_switch_interrupts_before_the_next_stage() {
+        let clock = Arc::new(MockClock::new());
+        let seller = MockSeller::new(&clock, None);
+        let killed = AtomicBool::new(false);
+        let interrupted = || {
+            // Fires once the first stage has sent
+            if !seller.sells().is_empty() {
+                killed.store(true, Ordering::SeqCst);
+            }
+            killed.load(Ordering::SeqCst)
+        };
+
+        let outcome = run(&seller, &clock, &interrupted).await;
+
+        assert_eq!(outcome, LadderOutcome::Interrupted { before_stage: 1 });
+        assert_eq!(seller.sells().len(), 1);
+    }
+
+    #[test]
+    fn ladder_slippage_follows_the_config_version() {
+        let value = "60000:10:normal,120000:100:zeroslot:fire";
+        let timeout = Duration::from_secs(15);
+
+        let current = ExitLadder::parse(value, timeout, CURRENT_CONFIG_VERSION).unwrap();
+        let bps: Vec<u64> = current.stages.iter().map(|stage| stage.slippage_bps).collect();
+        assert_eq!(bps, [10, 100]);
+
+        let legacy = ExitLadder::parse(value, timeout, 1).unwrap();
+        let bps: Vec<u64> = legacy.stages.iter().map(|stage| stage.slippage_bps).collect();
+        assert_eq!(bps, [1_000, MAX_SLIPPAGE_BPS]);
+        assert!(legacy.stages[1].fire_and_forget);
+
+        assert!(ExitLadder::parse("60000:101:normal", timeout, 1).is_err());
+        assert!(ExitLadder::parse("60000:10001:normal", timeout, CURRENT_CONFIG_VERSION).is_err());
+    }
+}
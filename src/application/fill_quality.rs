@@ -0,0 +1,469 @@
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{commitment_config::CommitmentConfig, signature::Signature, signer::Signer};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde::Serialize;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding, UiTransactionStatusMeta};
+
+use crate::common::{
+    config::AppState,
+    logger::Logger,
+    metrics::{FILL_QUALITY_MEDIAN, FILL_QUALITY_PAUSED},
+};
+use crate::engine::capital::Strategy;
+use crate::services::rpc_pool::CallClass;
+
+/// Rent of a token account the transaction created, not part of the price paid
+const TOKEN_ACCOUNT_RENT: u64 = 2_039_280;
+
+/// How long a sent transaction is polled for before its fill is given up on
+const FILL_WAIT: Duration = Duration::from_secs(30);
+const FILL_POLL: Duration = Duration::from_secs(2);
+
+lazy_static::lazy_static! {
+    static ref CONFIG: FillQualityConfig = FillQualityConfig::from_env();
+    static ref TRACKER: Mutex<FillQualityTracker> = Mutex::new(FillQualityTracker::default());
+}
+
+/// `FILL_QUALITY_FLOOR`, `FILL_QUALITY_WINDOW`, `FILL_QUALITY_BREACHES`,
+/// `FILL_QUALITY_RECOVER` and `FILL_QUALITY_COOLDOWN_SECS`
+#[derive(Debug, Clone, Copy)]
+pub struct FillQualityConfig {
+    /// Rolling median below which a trade counts as a breach, 0 only tracks
+    pub floor: f64,
+    /// Trades in the rolling median
+    pub window: usize,
+    /// Consecutive breaches that pause the strategy's entries
+    pub breaches: u32,
+    /// Rolling median at which a paused strategy resumes by itself
+    pub recover: f64,
+    /// A pause stops the entries whose fills would show recovery, so after
+    /// this long the strategy resumes with its window cleared and must breach again
+    pub cooldown: Duration,
+}
+
+impl FillQualityConfig {
+    pub fn from_env() -> Self {
+        let parse = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+        let floor = parse("FILL_QUALITY_FLOOR", 0.0);
+        Self {
+            floor,
+            window: (parse("FILL_QUALITY_WINDOW", 20.0) as usize).max(1),
+            breaches: (parse("FILL_QUALITY_BREACHES", 5.0) as u32).max(1),
+            recover: parse("FILL_QUALITY_RECOVER", floor).max(floor),
+            cooldown: Duration::from_secs(parse("FILL_QUALITY_COOLDOWN_SECS", 900.0) as u64),
+        }
+    }
+}
+
+/// One leg as quoted when the trade was decided and as it settled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillLeg {
+    pub quoted_in: u64,
+    pub quoted_out: u64,
+    pub realized_in: u64,
+    pub realized_out: u64,
+}
+
+impl FillLeg {
+    /// Realized output per unit in over quoted output per unit in
+    ///
+    /// Rates rather than totals, so a partial fill at the quoted rate scores
+    /// 1 and only the price it filled at counts. None when the leg is unusable:
+    /// nothing quoted, or nothing spent.
+    pub fn quality(&self) -> Option<f64> {
+        if self.quoted_in == 0 || self.quoted_out == 0 || self.realized_in == 0 {
+            return None;
+        }
+        let quoted = self.quoted_out as f64 / self.quoted_in as f64;
+        let realized = self.realized_out as f64 / self.realized_in as f64;
+        Some(realized / quoted)
+    }
+}
+
+/// Quality of a whole trade: each leg's output feeds the next, so the
+/// shortfalls multiply. A leg that spent nothing fails the trade to None.
+pub fn trade_quality(legs: &[FillLeg]) -> Option<f64> {
+    if legs.is_empty() {
+        return None;
+    }
+    legs.iter().map(FillLeg::quality).product()
+}
+
+fn median(values: &VecDeque<f64>) -> f64 {
+    let mut sorted: Vec<f64> = values.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// What recording one trade changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillVerdict {
+    Ok { median: f64 },
+    /// Below the floor, `streak` trades in a row
+    Breach { median: f64, streak: u32 },
+    /// This trade tipped the strategy into a pause
+    Paused { median: f64 },
+    /// The median came back to `recover`, entries resume
+    Recovered { median: f64 },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StrategyFills {
+    #[serde(skip)]
+    window: VecDeque<f64>,
+    pub median: Option<f64>,
+    pub trades: u64,
+    pub breach_streak: u32,
+    pub paused: bool,
+    /// Unix ms the pause started
+    pub paused_at_ms: Option<i64>,
+}
+
+/// Rolling fill quality and pause state per strategy
+#[derive(Debug, Default)]
+pub struct FillQualityTracker {
+    strategies: HashMap<&'static str, StrategyFills>,
+}
+
+impl FillQualityTracker {
+    pub fn record(&mut self, strategy: Strategy, quality: f64, now_ms: i64, config: &FillQualityConfig) -> FillVerdict {
+        let fills = self.strategies.entry(strategy.name()).or_default();
+        fills.window.push_back(quality);
+        while fills.window.len() > config.window {
+            fills.window.pop_front();
+        }
+        fills.trades += 1;
+        let median = median(&fills.window);
+        fills.median = Some(median);
+
+        if fills.paused {
+            if median >= config.recover {
+                fills.paused = false;
+                fills.paused_at_ms = None;
+                fills.breach_streak = 0;
+                return FillVerdict::Recovered { median };
+            }
+            return FillVerdict::Breach { median, streak: fills.breach_streak };
+        }
+        if config.floor <= 0.0 || median >= config.floor {
+            fills.breach_streak = 0;
+            return FillVerdict::Ok { median };
+        }
+        fills.breach_streak += 1;
+        if fills.breach_streak >= config.breaches {
+            fills.paused = true;
+            fills.paused_at_ms = Some(now_ms);
+            return FillVerdict::Paused { median };
+        }
+        FillVerdict::Breach { median, streak: fills.breach_streak }
+    }
+
+    /// Whether `strategy` is paused at `now_ms`, ending a pause whose cooldown ran out
+    pub fn is_paused(&mut self, strategy: Strategy, now_ms: i64, config: &FillQualityConfig) -> bool {
+        let Some(fills) = self.strategies.get_mut(strategy.name()) else { return false };
+        let cooled = fills
+            .paused_at_ms
+            .is_some_and(|at| now_ms - at >= config.cooldown.as_millis() as i64);
+        if fills.paused && cooled {
+            fills.paused = false;
+            fills.paused_at_ms = None;
+            fills.breach_streak = 0;
+            fills.window.clear();
+        }
+        fills.paused
+    }
+
+    /// Operator override: resume now; a new pause needs a fresh breach streak
+    pub fn resume(&mut self, strategy: Strategy) -> bool {
+        let Some(fills) = self.strategies.get_mut(strategy.name()) else { return false };
+        let was_paused = fills.paused;
+        fills.paused = false;
+        fills.paused_at_ms = None;
+        fills.breach_streak = 0;
+        was_paused
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, StrategyFills)> {
+        let mut snapshot: Vec<_> = self.strategies.iter().map(|(name, fills)| (name.to_string(), fills.clone())).collect();
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        snapshot
+    }
+}
+
+/// Whether bad fills paused new entries for `strategy`; exits are never paused
+pub fn is_paused(strategy: Strategy) -> bool {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let paused = TRACKER
+        .lock()
+        .map(|mut tracker| tracker.is_paused(strategy, now_ms, &CONFIG))
+        .unwrap_or(false);
+    if !paused {
+        FILL_QUALITY_PAUSED.with_label_values(&[strategy.name()]).set(0.0);
+    }
+    paused
+}
+
+/// Clear a fill-quality pause, returns whether one was active
+pub fn resume(strategy: Strategy) -> bool {
+    let resumed = TRACKER.lock().map(|mut tracker| tracker.resume(strategy)).unwrap_or(false);
+    FILL_QUALITY_PAUSED.with_label_values(&[strategy.name()]).set(0.0);
+    resumed
+}
+
+pub fn snapshot() -> Vec<(String, StrategyFills)> {
+    TRACKER.lock().map(|tracker| tracker.snapshot()).unwrap_or_default()
+}
+
+/// Fold a settled trade in, updating metrics and alerting on a pause or recovery
+pub async fn record_trade(app_state: &AppState, strategy: Strategy, legs: &[FillLeg], logger: &Logger) -> Option<FillVerdict> {
+    let quality = trade_quality(legs)?;
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let verdict = TRACKER.lock().ok()?.record(strategy, quality, now_ms, &CONFIG);
+    let name = strategy.name();
+    match verdict {
+        FillVerdict::Ok { median } | FillVerdict::Breach { median, .. } => {
+            FILL_QUALITY_MEDIAN.with_label_values(&[name]).set(median);
+        }
+        FillVerdict::Paused { median } => {
+            FILL_QUALITY_MEDIAN.with_label_values(&[name]).set(median);
+            FILL_QUALITY_PAUSED.with_label_values(&[name]).set(1.0);
+            logger.log(format!(
+                "[ENTRIES PAUSED] => {} fill quality median {:.4} below {:.4} for {} trades",
+                name, median, CONFIG.floor, CONFIG.breaches
+            ).red().bold().to_string());
+            let body = format!(
+                "{} fills are settling at {:.2}% of quote (median of last {}), below the {:.2}% floor for {} trades. New {} entries are paused for {}s or until an operator resumes them; exits continue.",
+                name, median * 100.0, CONFIG.window, CONFIG.floor * 100.0, CONFIG.breaches, name, CONFIG.cooldown.as_secs()
+            );
+            app_state.notifier.alert("Fill quality degraded", &body).await;
+        }
+        FillVerdict::Recovered { median } => {
+            FILL_QUALITY_MEDIAN.with_label_values(&[name]).set(median);
+            FILL_QUALITY_PAUSED.with_label_values(&[name]).set(0.0);
+            logger.log(format!("[ENTRIES RESUMED] => {} fill quality median back to {:.4}", name, median).green().to_string());
+            app_state
+                .notifier
+                .alert("Fill quality recovered", &format!("{} fills back to {:.2}% of quote, entries resumed", name, median * 100.0))
+                .await;
+        }
+    }
+    Some(verdict)
+}
+
+/// The wallet's side of a settled swap: (spent, received) with SOL spent
+/// excluding the network fee and the rent of token accounts it opened
+pub fn buy_leg_from_meta(meta: &UiTransactionStatusMeta, owner: &str, mint: &str) -> Option<(u64, u64)> {
+    let token_amount = |balances: &OptionSerializer<Vec<solana_transaction_status::UiTransactionTokenBalance>>| {
+        let balances: Option<&Vec<_>> = balances.as_ref().into();
+        balances.map(|balances| {
+            balances
+                .iter()
+                .filter(|b| b.mint == mint && Option::<&String>::from(b.owner.as_ref()).is_some_and(|o| o == owner))
+                .map(|b| b.ui_token_amount.amount.parse::<u64>().unwrap_or(0))
+                .sum::<u64>()
+        })
+    };
+    let received = token_amount(&meta.post_token_balances)?.saturating_sub(token_amount(&meta.pre_token_balances).unwrap_or(0));
+
+    let opened = {
+        let count = |balances: &OptionSerializer<Vec<solana_transaction_status::UiTransactionTokenBalance>>| {
+            let balances: Option<&Vec<_>> = balances.as_ref().into();
+            balances.map_or(0, |balances| {
+                balances
+                    .iter()
+                    .filter(|b| Option::<&String>::from(b.owner.as_ref()).is_some_and(|o| o == owner))
+                    .count()
+            })
+        };
+        count(&meta.post_token_balances).saturating_sub(count(&meta.pre_token_balances)) as u64
+    };
+    // The wallet pays the fee and is always the first account
+    let spent = meta
+        .pre_balances
+        .first()?
+        .saturating_sub(*meta.post_balances.first()?)
+        .saturating_sub(meta.fee)
+        .saturating_sub(opened * TOKEN_ACCOUNT_RENT);
+    Some((spent, received))
+}
+
+/// Wait for a sent buy to settle and score its fill against the quote
+///
+/// `quoted_lamports` and `quoted_tokens` are what the swap was priced at when
+/// it was decided. A transaction that never shows up or failed is not a fill
+/// and is left out.
+pub async fn watch_buy(
+    app_state: Arc<AppState>,
+    strategy: Strategy,
+    signature: String,
+    mint: String,
+    quoted_lamports: u64,
+    quoted_tokens: u64,
+    logger: Logger,
+) {
+    let result = async {
+        let signature = Signature::from_str(&signature)?;
+        let meta = wait_for_meta(&app_state, signature).await?;
+        if meta.err.is_some() {
+            return Err(anyhow!("transaction failed"));
+        }
+        let owner = app_state.wallet.pubkey().to_string();
+        let (spent, received) = buy_leg_from_meta(&meta, &owner, &mint).ok_or_else(|| anyhow!("no balance changes"))?;
+        Ok(FillLeg { quoted_in: quoted_lamports, quoted_out: quoted_tokens, realized_in: spent, realized_out: received })
+    }
+    .await;
+    match result {
+        Ok(leg) => {
+            logger.log(format!(
+                "[FILL] => {} {}: quoted {} -> {}, settled {} -> {} (quality {})",
+                strategy.name(), mint, leg.quoted_in, leg.quoted_out, leg.realized_in, leg.realized_out,
+                leg.quality().map_or("-".to_string(), |q| format!("{:.4}", q))
+            ).cyan().to_string());
+            record_trade(&app_state, strategy, &[leg], &logger).await;
+        }
+        Err(e) => logger.log(format!("[FILL] => No fill quality for {} {}: {}", strategy.name(), signature, e).yellow().to_string()),
+    }
+}
+
+async fn wait_for_meta(app_state: &AppState, signature: Signature) -> Result<UiTransactionStatusMeta> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let deadline = app_state.clock.now() + FILL_WAIT;
+    loop {
+        let fetched = app_state
+            .rpc_pool
+            .call(CallClass::Confirm, |client| async move { client.get_transaction_with_config(&signature, config).await })
+            .await;
+        if let Ok(transaction) = fetched {
+            return transaction.transaction.meta.ok_or_else(|| anyhow!("transaction has no meta"));
+        }
+        if app_state.clock.now() >= deadline {
+            return Err(anyhow!("not confirmed within {:?}", FILL_WAIT));
+        }
+        app_state.clock.sleep(FILL_POLL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::clock::{Clock, MockClock};
+
+    fn config() -> FillQualityConfig {
+        FillQualityConfig { floor: 0.97, window: 3, breaches: 2, recover: 0.99, cooldown: Duration::from_secs(600) }
+    }
+
+    fn now_ms(clock: &MockClock) -> i64 {
+        clock.wall_now().timestamp_millis()
+    }
+
+    /// A tracker whose copy entries were just paused by two bad fills in a row
+    fn paused(clock: &MockClock) -> FillQualityTracker {
+        let mut tracker = FillQualityTracker::default();
+        for quality in [1.0, 0.875, 0.875] {
+            tracker.record(Strategy::Copy, quality, now_ms(clock), &config());
+        }
+        assert!(tracker.is_paused(Strategy::Copy, now_ms(clock), &config()));
+        tracker
+    }
+
+    #[test]
+    fn consecutive_breaches_pause_and_a_good_median_resets_the_streak() {
+        let clock = MockClock::new();
+        let config = config();
+        let mut tracker = FillQualityTracker::default();
+        let mut record = |quality: f64| tracker.record(Strategy::Copy, quality, now_ms(&clock), &config);
+
+        assert_eq!(record(1.0), FillVerdict::Ok { median: 1.0 });
+        assert_eq!(record(0.875), FillVerdict::Breach { median: 0.9375, streak: 1 });
+        // Window [1.0, 0.875, 1.0]: back above the floor before the streak paused anything
+        assert_eq!(record(1.0), FillVerdict::Ok { median: 1.0 });
+        assert_eq!(record(0.875), FillVerdict::Breach { median: 0.875, streak: 1 });
+        assert_eq!(record(0.875), FillVerdict::Paused { median: 0.875 });
+
+        assert!(tracker.is_paused(Strategy::Copy, now_ms(&clock), &config));
+        assert!(!tracker.is_paused(Strategy::Arbitrage, now_ms(&clock), &config));
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].1.trades, 5);
+        assert_eq!(snapshot[0].1.paused_at_ms, Some(now_ms(&clock)));
+    }
+
+    #[test]
+    fn fills_recovering_to_the_recover_level_resume_entries() {
+        let clock = MockClock::new();
+        let mut tracker = paused(&clock);
+
+        // [0.875, 0.875, 0.995]: one good fill doesn't move the median
+        assert_eq!(tracker.record(Strategy::Copy, 0.995, now_ms(&clock), &config()), FillVerdict::Breach { median: 0.875, streak: 2 });
+        assert_eq!(tracker.record(Strategy::Copy, 1.0, now_ms(&clock), &config()), FillVerdict::Recovered { median: 0.995 });
+        assert!(!tracker.is_paused(Strategy::Copy, now_ms(&clock), &config()));
+        // A fresh streak is needed to pause again
+        assert!(matches!(tracker.record(Strategy::Copy, 0.5, now_ms(&clock), &config()), FillVerdict::Ok { .. }));
+    }
+
+    #[test]
+    fn pause_lifts_itself_after_the_cooldown_with_a_cleared_window() {
+        let clock = MockClock::new();
+        let mut tracker = paused(&clock);
+
+        clock.advance(Duration::from_secs(599));
+        assert!(tracker.is_paused(Strategy::Copy, now_ms(&clock), &config()));
+        clock.advance(Duration::from_secs(1));
+        assert!(!tracker.is_paused(Strategy::Copy, now_ms(&clock), &config()));
+
+        // The bad fills from before the pause are gone
+        assert_eq!(tracker.record(Strategy::Copy, 0.875, now_ms(&clock), &config()), FillVerdict::Breach { median: 0.875, streak: 1 });
+        assert_eq!(tracker.record(Strategy::Copy, 0.875, now_ms(&clock), &config()), FillVerdict::Paused { median: 0.875 });
+    }
+
+    #[test]
+    fn operator_resume_clears_the_pause_once() {
+        let clock = MockClock::new();
+        let mut tracker = paused(&clock);
+
+        assert!(tracker.resume(Strategy::Copy));
+        assert!(!tracker.is_paused(Strategy::Copy, now_ms(&clock), &config()));
+        assert!(!tracker.resume(Strategy::Copy));
+        assert!(!tracker.resume(Strategy::Sniper));
+        // The window still holds the bad fills, so one more breach is a streak of one
+        assert_eq!(tracker.record(Strategy::Copy, 0.875, now_ms(&clock), &config()), FillVerdict::Breach { median: 0.875, streak: 1 });
+    }
+
+    #[test]
+    fn partial_fills_score_their_rate_and_legs_multiply() {
+        let full = FillLeg { quoted_in: 1_000, quoted_out: 2_000, realized_in: 1_000, realized_out: 1_980 };
+        // Half filled, at the quoted rate
+        let partial = FillLeg { quoted_in: 1_000, quoted_out: 2_000, realized_in: 500, realized_out: 1_000 };
+        assert!((full.quality().unwrap() - 0.99).abs() < 1e-12);
+        assert_eq!(partial.quality(), Some(1.0));
+
+        let back = FillLeg { quoted_in: 2_000, quoted_out: 1_000, realized_in: 1_980, realized_out: 970 };
+        let quality = trade_quality(&[full, back]).unwrap();
+        assert!((quality - 0.99 * (970.0 / 1_980.0) / 0.5).abs() < 1e-12);
+
+        let unfilled = FillLeg { realized_in: 0, realized_out: 0, ..full };
+        assert_eq!(trade_quality(&[full, unfilled]), None);
+        assert_eq!(trade_quality(&[]), None);
+    }
+}
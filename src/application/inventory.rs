@@ -0,0 +1,466 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{config::AppState, logger::Logger};
+use crate::core::ids::TradeId;
+use crate::engine::pool_discovery::PoolInfo;
+use crate::record::journal::{JournalScope, JournalSource};
+use crate::record::kv::{KvStore, Namespace};
+
+/// Ledger per mint, kept across restarts
+pub const INVENTORY: Namespace<InventoryLedger> = Namespace::new("inventory", true);
+
+/// Standing inventory one token is traded against
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct InventoryTarget {
+    /// Tokens, in UI units
+    pub tokens: f64,
+    pub sol: f64,
+}
+
+/// `INVENTORY_TARGETS`, `INVENTORY_MAX_VALUE_SOL`, `INVENTORY_MAX_DEVIATION_PCT`,
+/// `INVENTORY_MIN_HEALTH`, `INVENTORY_REBALANCE_SECS`,
+/// `INVENTORY_CALM_VOLATILITY_BPS`, `INVENTORY_REBALANCE_EDGE_BPS` and
+/// `INVENTORY_REBALANCE_BAND_PCT`
+#[derive(Debug, Clone)]
+pub struct InventoryConfig {
+    /// Mint -> target; tokens without one trade both legs as before
+    pub targets: HashMap<String, InventoryTarget>,
+    /// Most a token's inventory (tokens at mark plus SOL) may be worth
+    pub max_value_sol: f64,
+    /// Furthest the token side may move from target, as a fraction of it
+    pub max_deviation: f64,
+    /// Below this pool health the inventory is sold off
+    pub min_health: f64,
+    pub rebalance_interval: Duration,
+    /// Rebalancing waits until the pools move less than this between refreshes
+    pub calm_volatility_bps: f64,
+    /// How far better than mark a rebalancing fill must be
+    pub rebalance_edge_bps: f64,
+    /// Deviation, as a fraction of target, that rebalancing leaves alone
+    pub rebalance_band: f64,
+}
+
+impl InventoryConfig {
+    /// None when `INVENTORY_TARGETS` is unset or empty
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(raw) = std::env::var("INVENTORY_TARGETS").ok().filter(|v| !v.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let targets: HashMap<String, InventoryTarget> = serde_json::from_str(&raw)?;
+        if targets.is_empty() {
+            return Ok(None);
+        }
+        let parse = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+        Ok(Some(Self {
+            targets,
+            max_value_sol: parse("INVENTORY_MAX_VALUE_SOL", 10.0),
+            max_deviation: parse("INVENTORY_MAX_DEVIATION_PCT", 50.0) / 100.0,
+            min_health: parse("INVENTORY_MIN_HEALTH", 30.0),
+            rebalance_interval: Duration::from_secs(parse("INVENTORY_REBALANCE_SECS", 300.0).max(1.0) as u64),
+            calm_volatility_bps: parse("INVENTORY_CALM_VOLATILITY_BPS", 30.0),
+            rebalance_edge_bps: parse("INVENTORY_REBALANCE_EDGE_BPS", 10.0),
+            rebalance_band: parse("INVENTORY_REBALANCE_BAND_PCT", 10.0) / 100.0,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Inventory of one token and where its PnL came from
+///
+/// Every fill is split against the mark: trading away from it is spread
+/// capture, and holding tokens while the mark moves is revaluation. The two
+/// always add up to the change in value at mark.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InventoryLedger {
+    pub tokens: f64,
+    pub sol: f64,
+    /// SOL per token the inventory is valued at
+    pub mark: Option<f64>,
+    pub spread_pnl: f64,
+    pub revaluation_pnl: f64,
+    /// Sold off on poor health; stays out until an operator clears it
+    pub flattened: bool,
+}
+
+impl InventoryLedger {
+    pub fn seeded(target: &InventoryTarget) -> Self {
+        Self { tokens: target.tokens, sol: target.sol, ..Self::default() }
+    }
+
+    /// Value in SOL at the current mark
+    pub fn value(&self) -> f64 {
+        self.sol + self.tokens * self.mark.unwrap_or(0.0)
+    }
+
+    /// Move the mark, booking the held tokens' change as revaluation
+    pub fn mark_to(&mut self, price: f64) {
+        if let Some(old) = self.mark {
+            self.revaluation_pnl += self.tokens * (price - old);
+        }
+        self.mark = Some(price);
+    }
+
+    /// Book a fill of `tokens` at `price` SOL each and return its spread PnL
+    ///
+    /// The first fill without a mark marks at its own price and captures nothing.
+    pub fn apply_fill(&mut self, side: Side, tokens: f64, price: f64) -> f64 {
+        let mark = *self.mark.get_or_insert(price);
+        let spread = match side {
+            Side::Sell => {
+                self.tokens -= tokens;
+                self.sol += tokens * price;
+                tokens * (price - mark)
+            }
+            Side::Buy => {
+                self.tokens += tokens;
+                self.sol -= tokens * price;
+                tokens * (mark - price)
+            }
+        };
+        self.spread_pnl += spread;
+        spread
+    }
+
+    pub fn total_pnl(&self) -> f64 {
+        self.spread_pnl + self.revaluation_pnl
+    }
+}
+
+/// One leg the inventory takes instead of a two-leg arbitrage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InventoryLeg {
+    pub side: Side,
+    pub venue: String,
+    pub tokens: f64,
+    pub price: f64,
+}
+
+/// Whether buying `tokens` more stays inside the risk limits
+fn can_buy(ledger: &InventoryLedger, target: &InventoryTarget, config: &InventoryConfig, tokens: f64, price: f64) -> bool {
+    let after_tokens = ledger.tokens + tokens;
+    ledger.sol >= tokens * price
+        && after_tokens <= target.tokens * (1.0 + config.max_deviation)
+        && ledger.sol - tokens * price + after_tokens * price <= config.max_value_sol
+}
+
+fn can_sell(ledger: &InventoryLedger, target: &InventoryTarget, config: &InventoryConfig, tokens: f64) -> bool {
+    ledger.tokens >= tokens && ledger.tokens - tokens >= target.tokens * (1.0 - config.max_deviation)
+}
+
+/// Pick the single leg to capture a spread with: the side that moves the
+/// inventory back toward target, or the other one when that's out of limits
+pub fn plan_capture(
+    ledger: &InventoryLedger,
+    target: &InventoryTarget,
+    config: &InventoryConfig,
+    buy: (&str, f64),
+    sell: (&str, f64),
+    size_sol: f64,
+) -> Option<InventoryLeg> {
+    if ledger.flattened || buy.1 <= 0.0 || sell.1 <= 0.0 {
+        return None;
+    }
+    let buy_leg = InventoryLeg { side: Side::Buy, venue: buy.0.to_string(), tokens: size_sol / buy.1, price: buy.1 };
+    let sell_leg = InventoryLeg { side: Side::Sell, venue: sell.0.to_string(), tokens: size_sol / sell.1, price: sell.1 };
+    let buy_ok = can_buy(ledger, target, config, buy_leg.tokens, buy_leg.price);
+    let sell_ok = can_sell(ledger, target, config, sell_leg.tokens);
+    let long = ledger.tokens > target.tokens;
+    match (long, sell_ok, buy_ok) {
+        (true, true, _) | (false, true, false) => Some(sell_leg),
+        (false, _, true) | (true, false, true) => Some(buy_leg),
+        _ => None,
+    }
+}
+
+/// Mark a token at the median of its venues' cached prices
+pub fn mark_price(pools: &[PoolInfo]) -> Option<f64> {
+    let mut prices: Vec<f64> = pools.iter().filter_map(|p| p.last_known_price).filter(|p| *p > 0.0).collect();
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    Some(if prices.len() % 2 == 0 { (prices[mid - 1] + prices[mid]) / 2.0 } else { prices[mid] })
+}
+
+/// A slow leg back toward target, only when the pools are calm and some
+/// venue is better than mark by the edge
+pub fn plan_rebalance(
+    ledger: &InventoryLedger,
+    target: &InventoryTarget,
+    config: &InventoryConfig,
+    pools: &[PoolInfo],
+) -> Option<InventoryLeg> {
+    let mark = ledger.mark?;
+    if ledger.flattened || target.tokens <= 0.0 {
+        return None;
+    }
+    let volatile = pools
+        .iter()
+        .filter_map(|p| p.health.as_ref())
+        .any(|h| h.volatility_bps > config.calm_volatility_bps);
+    let gap = target.tokens - ledger.tokens;
+    if volatile || gap.abs() <= target.tokens * config.rebalance_band {
+        return None;
+    }
+    let edge = config.rebalance_edge_bps / 10_000.0;
+    let priced = pools.iter().filter_map(|p| p.last_known_price.map(|price| (p.dex_name.as_str(), price)));
+    if gap > 0.0 {
+        let (venue, price) = priced.min_by(|a, b| a.1.total_cmp(&b.1))?;
+        let tokens = gap.min(ledger.sol / price);
+        (price <= mark * (1.0 - edge) && tokens > 0.0 && can_buy(ledger, target, config, tokens, price))
+            .then(|| InventoryLeg { side: Side::Buy, venue: venue.to_string(), tokens, price })
+    } else {
+        let (venue, price) = priced.max_by(|a, b| a.1.total_cmp(&b.1))?;
+        (price >= mark * (1.0 + edge))
+            .then(|| InventoryLeg { side: Side::Sell, venue: venue.to_string(), tokens: -gap, price })
+    }
+}
+
+/// Sell everything at the best venue when the token's pools turned unhealthy
+pub fn plan_flatten(ledger: &InventoryLedger, config: &InventoryConfig, pools: &[PoolInfo]) -> Option<InventoryLeg> {
+    if ledger.flattened || ledger.tokens <= 0.0 {
+        return None;
+    }
+    let best_health = pools.iter().filter_map(PoolInfo::health_score).fold(None, |best: Option<f64>, s| Some(best.map_or(s, |b| b.max(s))));
+    if best_health.map_or(true, |health| health >= config.min_health) {
+        return None;
+    }
+    let (venue, price) = pools
+        .iter()
+        .filter_map(|p| p.last_known_price.map(|price| (p.dex_name.as_str(), price)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    Some(InventoryLeg { side: Side::Sell, venue: venue.to_string(), tokens: ledger.tokens, price })
+}
+
+fn load(kv: &KvStore, mint: &str, target: &InventoryTarget) -> InventoryLedger {
+    kv.get(&INVENTORY, mint).ok().flatten().unwrap_or_else(|| InventoryLedger::seeded(target))
+}
+
+/// Book a leg on the ledger and persist it
+///
+/// Arbitrage execution only logs what it would trade, so inventory legs are
+/// booked at the price they were planned at, the same way.
+fn book(kv: &KvStore, mint: &str, ledger: &mut InventoryLedger, leg: &InventoryLeg, reason: &str, logger: &Logger) {
+    let spread = ledger.apply_fill(leg.side, leg.tokens, leg.price);
+    logger.log(format!(
+        "\n\t * [INVENTORY {}] => {} {:?} {:.6} on {} at {:.9}, spread {:+.9} SOL \n\t * [LEDGER] => {:.6} tokens, {:.6} SOL, spread {:+.9}, revaluation {:+.9}",
+        reason, mint, leg.side, leg.tokens, leg.venue, leg.price, spread,
+        ledger.tokens, ledger.sol, ledger.spread_pnl, ledger.revaluation_pnl
+    ).cyan().to_string());
+    if let Err(e) = kv.put(&INVENTORY, mint, ledger) {
+        logger.log(format!("[KV] => Failed to store inventory: {}", e).red().to_string());
+    }
+}
+
+/// Take a spread on an inventory token with one leg; false when the token
+/// isn't in inventory mode or no leg fits the limits, so both legs are needed
+pub fn on_opportunity(
+    app_state: &AppState,
+    config: &InventoryConfig,
+    trade_id: &TradeId,
+    mint: &str,
+    buy: (&str, f64),
+    sell: (&str, f64),
+    size_sol: f64,
+    logger: &Logger,
+) -> bool {
+    let Some(target) = config.targets.get(mint) else { return false };
+    let mut ledger = load(&app_state.kv, mint, target);
+    if let Some(pools) = app_state.pool_cache_manager.get_cache().ok().and_then(|c| c.pools.get(mint).cloned()) {
+        if let Some(mark) = mark_price(&pools) {
+            ledger.mark_to(mark);
+        }
+    }
+    let Some(leg) = plan_capture(&ledger, target, config, buy, sell, size_sol) else { return false };
+    book(&app_state.kv, mint, &mut ledger, &leg, "CAPTURE", logger);
+    let note = format!("Inventory capture: {:?} {:.6} on {} at {:.9}", leg.side, leg.tokens, leg.venue, leg.price);
+    let tags = BTreeMap::from([
+        ("opportunity_id".to_string(), trade_id.opportunity().to_string()),
+        ("trade_id".to_string(), trade_id.to_string()),
+    ]);
+    let scope = JournalScope::Mint { mint: mint.to_string() };
+    if let Err(e) = app_state.store.add_journal_entry(&scope, &note, &tags, JournalSource::System) {
+        logger.log(format!("[RECORD ERROR] => Failed to journal capture {}: {}", trade_id, e).red().to_string());
+    }
+    true
+}
+
+/// Re-mark every inventory token, flatten the unhealthy and rebalance the calm
+pub async fn run_rebalancer(app_state: Arc<AppState>, config: InventoryConfig) {
+    let logger = Logger::new("[INVENTORY] => ".cyan().bold().to_string());
+    loop {
+        app_state.clock.sleep(config.rebalance_interval).await;
+        let Ok(cache) = app_state.pool_cache_manager.get_cache() else { continue };
+        for (mint, target) in &config.targets {
+            let Some(pools) = cache.pools.get(mint) else { continue };
+            let mut ledger = load(&app_state.kv, mint, target);
+            let Some(mark) = mark_price(pools) else { continue };
+            ledger.mark_to(mark);
+
+            if let Some(leg) = plan_flatten(&ledger, &config, pools) {
+                book(&app_state.kv, mint, &mut ledger, &leg, "FLATTEN", &logger);
+                ledger.flattened = true;
+                let _ = app_state.kv.put(&INVENTORY, mint, &ledger);
+                app_state
+                    .notifier
+                    .alert("Inventory flattened", &format!("{} pool health fell below {:.0}, sold {:.6} tokens", mint, config.min_health, leg.tokens))
+                    .await;
+                continue;
+            }
+            match plan_rebalance(&ledger, target, &config, pools) {
+                Some(leg) => book(&app_state.kv, mint, &mut ledger, &leg, "REBALANCE", &logger),
+                None => {
+                    let _ = app_state.kv.put(&INVENTORY, mint, &ledger);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn target() -> InventoryTarget {
+        InventoryTarget { tokens: 100.0, sol: 10.0 }
+    }
+
+    fn config() -> InventoryConfig {
+        InventoryConfig {
+            targets: HashMap::from([("mint".to_string(), target())]),
+            max_value_sol: 100.0,
+            max_deviation: 0.5,
+            min_health: 30.0,
+            rebalance_interval: Duration::from_secs(300),
+            calm_volatility_bps: 30.0,
+            rebalance_edge_bps: 10.0,
+            rebalance_band: 0.1,
+        }
+    }
+
+    fn pool(dex_name: &str, price: f64) -> PoolInfo {
+        PoolInfo {
+            pool_id: format!("{}-pool", dex_name),
+            dex_name: dex_name.to_string(),
+            base_mint: "mint".to_string(),
+            quote_mint: "sol".to_string(),
+            last_known_price: Some(price),
+            last_updated: None,
+            liquidity: None,
+            subtype: None,
+            book: None,
+            health: None,
+            fee_bps: None,
+        }
+    }
+
+    #[test]
+    fn fills_are_spread_and_holding_through_a_move_is_revaluation() {
+        let mut ledger = InventoryLedger::seeded(&target());
+        ledger.mark_to(0.5);
+        assert_eq!(ledger.revaluation_pnl, 0.0);
+        let start = ledger.value();
+
+        // Sell 10 a quarter above mark: 1.25 SOL captured
+        assert!(close(ledger.apply_fill(Side::Sell, 10.0, 0.625), 1.25));
+        // The mark catches up while 90 tokens are held
+        ledger.mark_to(0.625);
+        assert!(close(ledger.revaluation_pnl, 90.0 * 0.125));
+        // Rebuy them a fifth below the new mark
+        assert!(close(ledger.apply_fill(Side::Buy, 10.0, 0.5), 1.25));
+
+        assert!(close(ledger.tokens, 100.0));
+        assert!(close(ledger.spread_pnl, 2.5));
+        assert!(close(ledger.total_pnl(), 2.5 + 11.25));
+        assert!(close(ledger.value() - start, ledger.total_pnl()));
+    }
+
+    #[test]
+    fn first_fill_marks_at_its_own_price_and_a_falling_mark_costs_revaluation() {
+        let mut ledger = InventoryLedger::seeded(&target());
+        assert_eq!(ledger.apply_fill(Side::Buy, 20.0, 0.5), 0.0);
+        assert_eq!(ledger.mark, Some(0.5));
+
+        ledger.mark_to(0.25);
+        assert!(close(ledger.revaluation_pnl, -120.0 * 0.25));
+        assert!(close(ledger.spread_pnl, 0.0));
+    }
+
+    #[test]
+    fn capture_leans_back_toward_target_within_limits() {
+        let config = config();
+        let target = target();
+        let mut ledger = InventoryLedger::seeded(&target);
+        ledger.mark_to(0.5);
+
+        ledger.tokens = 120.0;
+        let leg = plan_capture(&ledger, &target, &config, ("raydium_amm", 0.5), ("pumpswap", 0.625), 1.25).unwrap();
+        assert_eq!((leg.side, leg.venue.as_str()), (Side::Sell, "pumpswap"));
+        assert!(close(leg.tokens, 2.0));
+
+        ledger.tokens = 80.0;
+        let leg = plan_capture(&ledger, &target, &config, ("raydium_amm", 0.5), ("pumpswap", 0.625), 1.25).unwrap();
+        assert_eq!((leg.side, leg.venue.as_str()), (Side::Buy, "raydium_amm"));
+
+        // Short at the deviation floor with no SOL left: neither side fits
+        ledger.tokens = 50.0;
+        ledger.sol = 0.0;
+        assert!(plan_capture(&ledger, &target, &config, ("raydium_amm", 0.5), ("pumpswap", 0.625), 1.25).is_none());
+
+        ledger.flattened = true;
+        ledger.tokens = 120.0;
+        assert!(plan_capture(&ledger, &target, &config, ("raydium_amm", 0.5), ("pumpswap", 0.625), 1.25).is_none());
+    }
+
+    #[test]
+    fn rebalance_needs_an_edge_over_mark_and_leaves_the_band_alone() {
+        let config = config();
+        let target = target();
+        let mut ledger = InventoryLedger::seeded(&target);
+        ledger.mark_to(0.5);
+
+        ledger.tokens = 95.0;
+        assert!(plan_rebalance(&ledger, &target, &config, &[pool("raydium_amm", 0.4)]).is_none());
+
+        ledger.tokens = 80.0;
+        assert!(plan_rebalance(&ledger, &target, &config, &[pool("raydium_amm", 0.5)]).is_none());
+        let leg = plan_rebalance(&ledger, &target, &config, &[pool("raydium_amm", 0.5), pool("pumpswap", 0.4)]).unwrap();
+        assert_eq!((leg.side, leg.venue.as_str()), (Side::Buy, "pumpswap"));
+        assert!(close(leg.tokens, 20.0));
+
+        ledger.tokens = 130.0;
+        let leg = plan_rebalance(&ledger, &target, &config, &[pool("raydium_amm", 0.5), pool("pumpswap", 0.6)]).unwrap();
+        assert_eq!((leg.side, leg.venue.as_str()), (Side::Sell, "pumpswap"));
+        assert!(close(leg.tokens, 30.0));
+    }
+
+    #[test]
+    fn mark_is_the_median_venue_price() {
+        assert_eq!(mark_price(&[pool("a", 0.3), pool("b", 0.1), pool("c", 0.2)]), Some(0.2));
+        assert_eq!(mark_price(&[pool("a", 0.25), pool("b", 0.75)]), Some(0.5));
+        assert_eq!(mark_price(&[pool("a", 0.0)]), None);
+    }
+}
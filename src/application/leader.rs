@@ -0,0 +1,364 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use colored::Colorize;
+use tokio::time::Instant;
+
+use crate::common::{
+    config::AppState,
+    logger::Logger,
+    metrics::{IS_LEADER, LEADER_TRANSITIONS},
+    sync::LockExt,
+};
+
+lazy_static::lazy_static! {
+    // Off means single-region: this process always leads
+    static ref ELECTION_ENABLED: AtomicBool = AtomicBool::new(false);
+
+    // This process's own lease
+    static ref LEADERSHIP: Leadership = Leadership::default();
+}
+
+/// Whether this process may send transactions right now
+///
+/// Checked by every send path immediately before the transaction leaves, not
+/// only when the trade is planned.
+pub fn is_leader() -> bool {
+    if !ELECTION_ENABLED.load(Ordering::SeqCst) {
+        return true;
+    }
+    LEADERSHIP.is_leading(Instant::now())
+}
+
+/// Error out of a send path when another region holds the lease
+pub fn ensure_leader() -> Result<()> {
+    if is_leader() {
+        Ok(())
+    } else {
+        Err(anyhow!("Not the leader, transaction not sent"))
+    }
+}
+
+/// A lease only one holder can have at a time, expired by the backend
+#[async_trait]
+pub trait LeaseBackend: Send + Sync {
+    /// Take the lease if nobody holds it
+    async fn try_acquire(&self, holder: &str, ttl: Duration) -> Result<bool>;
+    /// Extend the lease, false if `holder` no longer has it
+    async fn renew(&self, holder: &str, ttl: Duration) -> Result<bool>;
+}
+
+/// `SET key holder NX PX ttl`, renewed only by the holder
+pub struct RedisLease {
+    client: redis::Client,
+    key: String,
+}
+
+const RENEW_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+impl RedisLease {
+    pub fn new(url: &str, key: String) -> Result<Self> {
+        Ok(Self { client: redis::Client::open(url)?, key })
+    }
+
+    async fn connection(&self) -> Result<redis::aio::MultiplexedConnection> {
+        Ok(self.client.get_multiplexed_async_connection().await?)
+    }
+}
+
+#[async_trait]
+impl LeaseBackend for RedisLease {
+    async fn try_acquire(&self, holder: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self.connection().await?;
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(&self.key)
+            .arg(holder)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    async fn renew(&self, holder: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self.connection().await?;
+        let extended: i64 = redis::Script::new(RENEW_SCRIPT)
+            .key(&self.key)
+            .arg(holder)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(extended == 1)
+    }
+}
+
+/// `LEADER_LOCK_URL`, `LEADER_LOCK_KEY`, `LEADER_ID`, `LEADER_LEASE_MS` and
+/// `LEADER_SKEW_MARGIN_MS`
+#[derive(Debug, Clone)]
+pub struct LeaderConfig {
+    /// Redis URL of the shared lease, election is off when unset
+    pub url: String,
+    pub key: String,
+    /// Identifies this process in the lease, unique per region
+    pub holder: String,
+    pub lease: Duration,
+    /// Stop leading this long before the lease could expire on the backend
+    pub skew_margin: Duration,
+}
+
+impl LeaderConfig {
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("LEADER_LOCK_URL").ok().filter(|v| !v.trim().is_empty())?;
+        let millis = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let holder = std::env::var("LEADER_ID").unwrap_or_else(|_| {
+            let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "host".to_string());
+            format!("{}-{}", host, std::process::id())
+        });
+        let lease = Duration::from_millis(millis("LEADER_LEASE_MS", 5_000));
+        Some(Self {
+            url,
+            key: std::env::var("LEADER_LOCK_KEY").unwrap_or_else(|_| "arbitrage-bot:leader".to_string()),
+            holder,
+            lease,
+            skew_margin: Duration::from_millis(millis("LEADER_SKEW_MARGIN_MS", 1_000)).min(lease / 2),
+        })
+    }
+
+    /// Renew well inside the lease so one slow round trip doesn't lose it
+    pub fn renew_every(&self) -> Duration {
+        self.lease / 3
+    }
+}
+
+/// Turn election on before anything can send; until the first lease is won
+/// this process is a follower
+pub fn enable() {
+    ELECTION_ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Until when a process may act as leader, by its own monotonic clock
+#[derive(Debug, Default)]
+pub struct Leadership {
+    until: Mutex<Option<Instant>>,
+}
+
+impl Leadership {
+    pub fn is_leading(&self, now: Instant) -> bool {
+        self.until.lock_or_recover().is_some_and(|until| now < until)
+    }
+
+    /// One acquire-or-renew round whose request went out at `sent_at`;
+    /// returns whether this process leads afterwards
+    ///
+    /// The local deadline is measured from before the request went out, so it
+    /// always ends before the backend's expiry regardless of how the hosts'
+    /// wall clocks disagree. The margin covers monotonic clock drift.
+    pub async fn round(
+        &self,
+        backend: &dyn LeaseBackend,
+        config: &LeaderConfig,
+        was_leader: bool,
+        sent_at: Instant,
+    ) -> Result<bool> {
+        let held = if was_leader {
+            backend.renew(&config.holder, config.lease).await?
+        } else {
+            backend.try_acquire(&config.holder, config.lease).await?
+        };
+        *self.until.lock_or_recover() = held.then(|| sent_at + config.lease - config.skew_margin);
+        Ok(held)
+    }
+}
+
+/// One acquire-or-renew round for this process
+pub async fn lease_round(backend: &dyn LeaseBackend, config: &LeaderConfig, was_leader: bool) -> Result<bool> {
+    LEADERSHIP.round(backend, config, was_leader, Instant::now()).await
+}
+
+fn note_transition(app_state: &AppState, config: &LeaderConfig, leader: bool, logger: &Logger) {
+    IS_LEADER.set(leader as i64);
+    let label = if leader { "promoted" } else { "demoted" };
+    LEADER_TRANSITIONS.with_label_values(&[label]).inc();
+    if leader {
+        logger.log(format!("[PROMOTED] => {} holds the lease and executes trades", config.holder).green().bold().to_string());
+    } else {
+        logger.log(format!("[DEMOTED] => {} lost the lease, observing only", config.holder).yellow().bold().to_string());
+    }
+    let notifier = app_state.notifier.clone();
+    let title = format!("Leader {}", label);
+    let body = format!("{} was {} (lease {})", config.holder, label, config.key);
+    tokio::spawn(async move { notifier.alert(&title, &body).await });
+}
+
+/// Hold or chase the lease until the process exits
+pub async fn run_leader_election(app_state: Arc<AppState>, config: LeaderConfig) {
+    let logger = Logger::new("[LEADER] => ".magenta().bold().to_string());
+    let backend = match RedisLease::new(&config.url, config.key.clone()) {
+        Ok(backend) => backend,
+        Err(e) => {
+            logger.log(format!("[LEASE ERROR] => Bad LEADER_LOCK_URL, staying follower: {}", e).red().to_string());
+            return;
+        }
+    };
+    logger.log(format!(
+        "[ELECTION] => {} competing for {} with a {:?} lease", config.holder, config.key, config.lease
+    ).cyan().to_string());
+
+    let mut leading = false;
+    loop {
+        if let Err(e) = lease_round(&backend, &config, leading).await {
+            // The local deadline still runs out on its own if the backend stays unreachable
+            logger.log(format!("[LEASE ERROR] => {}", e).red().to_string());
+        }
+        let now_leading = is_leader();
+        if now_leading != leading {
+            note_transition(&app_state, &config, now_leading, &logger);
+            leading = now_leading;
+        }
+        app_state.clock.sleep(config.renew_every()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEASE: Duration = Duration::from_secs(5);
+
+    /// A lease backend with its own clock, set by the test before each call
+    struct MockLease {
+        now: Mutex<Instant>,
+        /// Holder and when the backend expires the key, by its clock
+        held: Mutex<Option<(String, Instant)>>,
+    }
+
+    impl MockLease {
+        fn new(now: Instant) -> Self {
+            Self { now: Mutex::new(now), held: Mutex::new(None) }
+        }
+
+        fn at(&self, now: Instant) -> &Self {
+            *self.now.lock().unwrap() = now;
+            self
+        }
+    }
+
+    #[async_trait]
+    impl LeaseBackend for MockLease {
+        async fn try_acquire(&self, holder: &str, ttl: Duration) -> Result<bool> {
+            let now = *self.now.lock().unwrap();
+            let mut held = self.held.lock().unwrap();
+            if held.as_ref().is_some_and(|(_, expires)| now < *expires) {
+                return Ok(false);
+            }
+            *held = Some((holder.to_string(), now + ttl));
+            Ok(true)
+        }
+
+        async fn renew(&self, holder: &str, ttl: Duration) -> Result<bool> {
+            let now = *self.now.lock().unwrap();
+            let mut held = self.held.lock().unwrap();
+            match held.as_mut() {
+                Some((current, expires)) if current == holder && now < *expires => {
+                    *expires = now + ttl;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        }
+    }
+
+    fn config(holder: &str, skew_margin: Duration) -> LeaderConfig {
+        LeaderConfig {
+            url: "redis://mock".to_string(),
+            key: "leader".to_string(),
+            holder: holder.to_string(),
+            lease: LEASE,
+            skew_margin,
+        }
+    }
+
+    #[tokio::test]
+    async fn follower_is_promoted_once_the_leader_stops_renewing() {
+        let t0 = Instant::now();
+        let backend = MockLease::new(t0);
+        let (a, b) = (Leadership::default(), Leadership::default());
+        let (config_a, config_b) = (config("region-a", Duration::from_secs(1)), config("region-b", Duration::from_secs(1)));
+        let every = config_a.renew_every();
+
+        assert!(a.round(backend.at(t0), &config_a, false, t0).await.unwrap());
+        let mut last_renewal = t0;
+        for round in 1..=5 {
+            let now = t0 + every * round;
+            assert!(a.round(backend.at(now), &config_a, true, now).await.unwrap());
+            assert!(!b.round(backend.at(now), &config_b, false, now).await.unwrap());
+            assert!(a.is_leading(now) && !b.is_leading(now));
+            last_renewal = now;
+        }
+
+        // Region A goes quiet; B keeps polling at its renewal pace
+        let mut now = last_renewal;
+        let promoted_at = loop {
+            now += every;
+            if b.round(backend.at(now), &config_b, false, now).await.unwrap() {
+                break now;
+            }
+            assert!(now < last_renewal + LEASE + every, "B never promoted");
+        };
+        assert!(promoted_at <= last_renewal + LEASE + every);
+        assert!(b.is_leading(promoted_at));
+        assert!(!a.is_leading(promoted_at));
+        // A coming back can't renew a lease it has lost
+        assert!(!a.round(backend.at(promoted_at), &config_a, true, promoted_at).await.unwrap());
+        assert!(!a.is_leading(promoted_at));
+    }
+
+    /// Region A takes the lease then stalls, its clock running 10% slow and
+    /// its request reaching the backend 50ms late. Region B polls every 100ms
+    /// on an accurate clock. Returns whether both ever led at once.
+    async fn both_lead_under_skew(skew_margin: Duration) -> bool {
+        let t0 = Instant::now();
+        let a_clock = |t: Instant| t0 + (t - t0).mul_f64(0.9);
+        let backend = MockLease::new(t0);
+        let (a, b) = (Leadership::default(), Leadership::default());
+        let (config_a, config_b) = (config("region-a", skew_margin), config("region-b", skew_margin));
+
+        let arrived = t0 + Duration::from_millis(50);
+        assert!(a.round(backend.at(arrived), &config_a, false, a_clock(t0)).await.unwrap());
+
+        let mut both = false;
+        for tick in 1..=1_000u32 {
+            let t = t0 + Duration::from_millis(10) * tick;
+            if tick % 10 == 0 && !b.is_leading(t) {
+                b.round(backend.at(t), &config_b, false, t).await.unwrap();
+            }
+            both |= a.is_leading(a_clock(t)) && b.is_leading(t);
+        }
+        assert!(b.is_leading(t0 + Duration::from_secs(6)), "B should have taken over");
+        both
+    }
+
+    #[tokio::test]
+    async fn skew_margin_prevents_two_leaders() {
+        assert!(!both_lead_under_skew(Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn without_a_margin_a_slow_clock_leads_too_long() {
+        assert!(both_lead_under_skew(Duration::ZERO).await);
+    }
+}
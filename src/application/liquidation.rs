@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+
+use anchor_client::solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde::Serialize;
+use solana_account_decoder::UiAccountData;
+use solana_client::rpc_request::TokenAccountsFilter;
+use tokio::time::Instant;
+
+use crate::common::{
+    config::{config_version, slippage_to_bps, AppState, SwapConfig, MAX_SLIPPAGE_BPS},
+    events::{self, BotEvent},
+    format::{fmt_pct, fmt_token},
+    logger::Logger,
+};
+use crate::core::priority_fee::PriorityFeeStrategy;
+use crate::core::tx;
+use crate::dex::pump_swap::{PumpSwap, SOL_MINT};
+use crate::dex::slippage::min_amount_with_slippage;
+use crate::engine::capital::Strategy;
+use crate::engine::durable_nonce;
+use crate::engine::pool_discovery::PoolInfo as CachedPool;
+use crate::engine::swap::{SwapDirection, SwapInType};
+use crate::record::journal::{JournalScope, JournalSource};
+use crate::record::kv;
+use crate::record::store::TradeRecord;
+use crate::services::rpc_pool::CallClass;
+
+lazy_static::lazy_static! {
+    // Serializes liquidation runs so repeated triggers never double-sell
+    static ref LIQUIDATION_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+/// Outcome of a panic liquidation run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LiquidationSummary {
+    pub attempted: usize,
+    pub sold: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Whether a panic liquidation has been triggered on this state
+pub fn is_liquidating(app_state: &AppState) -> bool {
+    app_state.liquidating.load(Ordering::SeqCst)
+}
+
+/// Whether a buy path may open a position, given the monitor's own switch;
+/// a panic liquidation refuses every buy from then on
+pub fn buys_allowed(app_state: &AppState) -> bool {
+    app_state.monitor.buying_enabled() && !is_liquidating(app_state)
+}
+
+/// Slippage in bps used when dumping positions, read like `SLIPPAGE`
+///
+/// Too much slippage is capped instead of refused: a panic sell must still go out.
+fn get_emergency_slippage_bps() -> u64 {
+    std::env::var("EMERGENCY_SLIPPAGE")
+        .ok()
+        .and_then(|v| u64::from_str(&v).ok())
+        .map(|v| slippage_to_bps("EMERGENCY_SLIPPAGE", v, config_version()).unwrap_or(MAX_SLIPPAGE_BPS))
+        .unwrap_or(5_000)
+}
+
+/// Force-sell every token balance held by the wallet and stop all buying.
+///
+/// Safe to call repeatedly: concurrent calls wait for the running sweep, and a
+/// later sweep only sees balances the previous one could not sell.
+pub async fn liquidate_all(app_state: &AppState) -> Result<LiquidationSummary> {
+    let logger = Logger::new("[PANIC-LIQUIDATE] => ".red().bold().to_string());
+
+    if !app_state.liquidating.swap(true, Ordering::SeqCst) {
+        logger.log("[BUYING STOPPED] => Panic liquidation triggered".red().bold().to_string());
+        let tags = BTreeMap::from([("event".to_string(), "panic_liquidation".to_string())]);
+        if let Err(e) = app_state.store.add_journal_entry(
+            &JournalScope::Session,
+            "Panic liquidation triggered, buying stopped",
+            &tags,
+            JournalSource::System,
+        ) {
+            logger.log(format!("[JOURNAL] => Failed to record liquidation: {}", e).red().to_string());
+        }
+    }
+
+    let _guard = LIQUIDATION_LOCK.lock().await;
+    let mut summary = LiquidationSummary::default();
+
+    let owner = app_state.wallet.pubkey();
+    let sol_mint = Pubkey::from_str(SOL_MINT)?;
+    // Token-2022 mints keep their balances under their own program
+    let mut token_accounts = Vec::new();
+    for program in [spl_token::id(), spl_token_2022::id()] {
+        let accounts = app_state
+            .rpc_pool
+            .call(CallClass::Exit, |client| async move {
+                client
+                    .get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(program))
+                    .await
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to list token accounts of {}: {}", program, e))?;
+        token_accounts.extend(accounts);
+    }
+
+    // Collect (mint, raw amount) for every non-empty position
+    let positions: Vec<(String, u64)> = token_accounts
+        .into_iter()
+        .filter_map(|keyed| match keyed.account.data {
+            UiAccountData::Json(parsed) => {
+                let info = parsed.parsed.get("info")?;
+                let mint = info.get("mint")?.as_str()?.to_string();
+                let amount = info.get("tokenAmount")?.get("amount")?.as_str()?.parse::<u64>().ok()?;
+                Some((mint, amount))
+            }
+            _ => None,
+        })
+        .filter(|(mint, amount)| *amount > 0 && *mint != sol_mint.to_string())
+        .collect();
+
+    if positions.is_empty() {
+        logger.log("[NOTHING TO SELL] => Wallet holds no token positions".green().to_string());
+        return Ok(summary);
+    }
+
+    let swapx = PumpSwap::new(
+        app_state.wallet.clone(),
+        Some(app_state.rpc_client.clone()),
+        Some(app_state.rpc_nonblocking_client.clone()),
+    );
+    let slippage_bps = get_emergency_slippage_bps();
+    let cache = app_state.pool_cache_manager.get_cache().ok();
+
+    for (mint, amount) in positions {
+        summary.attempted += 1;
+        let recorded = app_state.kv.get(&kv::POSITIONS, &mint).ok().flatten().and_then(|position| position.dex);
+        let cached = cache.as_ref().and_then(|cache| cache.pools.get(&mint)).map(Vec::as_slice).unwrap_or(&[]);
+        let venue = sell_venue(recorded.as_deref(), cached, |dex| app_state.dex_registry.adapter(dex).is_some());
+        logger.log(format!(
+            "\n\t * [LIQUIDATING] => Token: {}, Amount: {}, Venue: {}, Slippage: {}",
+            mint,
+            fmt_token(amount, &mint),
+            venue.as_ref().map_or("pumpswap", |(dex, _)| dex.as_str()),
+            fmt_pct(slippage_bps as f64 / 100.0)
+        ).yellow().to_string());
+
+        let sell_config = SwapConfig {
+            swap_direction: SwapDirection::Sell,
+            in_type: SwapInType::Pct,
+            amount_in: 1_f64, // Sell 100%
+            slippage_bps,
+            use_jito: false,
+            priority_fee: PriorityFeeStrategy::from_env(),
+        };
+
+        let priority_fee = sell_config.priority_fee;
+        let start_time = Instant::now();
+        let result = async {
+            let (keypair, mut instructions) = match &venue {
+                Some((dex, pool_id)) => {
+                    let instructions = build_adapter_sell(app_state, dex, pool_id.as_deref(), &mint, amount, slippage_bps).await?;
+                    (app_state.wallet.clone(), instructions)
+                }
+                None => {
+                    let (keypair, instructions, _price) = swapx
+                        .build_swap_ixn_by_mint(&mint, None, sell_config, start_time)
+                        .await?;
+                    (keypair, instructions)
+                }
+            };
+            let recent_blockhash =
+                durable_nonce::blockhash_for_send(app_state, &keypair.pubkey(), &mut instructions, CallClass::Exit, &logger).await?;
+            app_state
+                .tx_sender
+                .send(recent_blockhash, &keypair, instructions, priority_fee, &logger)
+                .await
+                .inspect_err(|_| durable_nonce::release(&recent_blockhash))
+        }.await;
+
+        match result {
+            Ok(signatures) => {
+                logger.log(format!(
+                    "\n\t * [LIQUIDATED] => Token: {}, TX_HASH: (https://solscan.io/tx/{}) :: ({:?})",
+                    mint, signatures.first().cloned().unwrap_or_default(), start_time.elapsed()
+                ).green().to_string());
+                if let Some(signature) = signatures.first() {
+                    let fee = tx::take_sent_fee(signature);
+                    let trade = TradeRecord {
+                        trade_id: signature.clone(),
+                        created_at: chrono::Utc::now().timestamp_millis(),
+                        mint: mint.clone(),
+                        side: "sell".to_string(),
+                        sol_amount: None,
+                        token_amount: Some(amount),
+                        price: None,
+                        signature: Some(signature.clone()),
+                        priority_fee_strategy: fee.map(|fee| fee.strategy.to_string()),
+                        priority_fee_lamports: fee.map(|fee| fee.budget.fee_lamports()),
+                    };
+                    if let Err(e) = app_state.store.record_trade(&trade) {
+                        logger.log(format!("[RECORD] => Failed to store trade: {}", e).red().to_string());
+                    }
+                }
+                app_state.capital.release(Strategy::Copy, &mint);
+                app_state.refresh_scheduler.set_open_position(&mint, false);
+                if let Err(e) = app_state.kv.remove(&kv::POSITIONS, &mint) {
+                    logger.log(format!("[KV] => Failed to remove position: {}", e).red().to_string());
+                }
+                events::publish(BotEvent::PositionClosed { mint: mint.clone(), exit_price: None });
+                summary.sold.push(mint);
+            }
+            Err(e) => {
+                logger.log(format!(
+                    "\n\t * [LIQUIDATION FAILED] => Token: {}, Error: {}",
+                    mint, e
+                ).red().to_string());
+                summary.failed.push((mint, e.to_string()));
+            }
+        }
+    }
+
+    logger.log(format!(
+        "\n\t * [LIQUIDATION SUMMARY] => Attempted: {}, Sold: {}, Failed: {}",
+        summary.attempted, summary.sold.len(), summary.failed.len()
+    ).bold().to_string());
+
+    Ok(summary)
+}
+
+/// Where to sell `mint`: the venue its position was opened on when an
+/// adapter trades there, else the venue of its deepest cached pool that one
+/// does. Comes with the cached pool on that venue, if there is one; None
+/// leaves the sell to PumpSwap's own pool lookup.
+fn sell_venue(
+    recorded: Option<&str>,
+    cached: &[CachedPool],
+    has_adapter: impl Fn(&str) -> bool,
+) -> Option<(String, Option<String>)> {
+    let deepest_on = |dex: &str| {
+        cached
+            .iter()
+            .filter(|pool| pool.dex_name == dex)
+            .max_by_key(|pool| pool.liquidity.unwrap_or(0))
+            .map(|pool| pool.pool_id.clone())
+    };
+    if let Some(dex) = recorded.filter(|dex| has_adapter(dex)) {
+        return Some((dex.to_string(), deepest_on(dex)));
+    }
+    cached
+        .iter()
+        .filter(|pool| has_adapter(&pool.dex_name))
+        .max_by_key(|pool| pool.liquidity.unwrap_or(0))
+        .map(|pool| (pool.dex_name.clone(), Some(pool.pool_id.clone())))
+}
+
+/// Sell all `amount` of `mint` on `dex` through its adapter, in the cached
+/// pool when one is known and the venue's deepest otherwise
+async fn build_adapter_sell(
+    app_state: &AppState,
+    dex: &str,
+    pool_id: Option<&str>,
+    mint: &str,
+    amount: u64,
+    slippage_bps: u64,
+) -> Result<Vec<Instruction>> {
+    let adapter = app_state
+        .dex_registry
+        .adapter(dex)
+        .ok_or_else(|| anyhow!("No adapter trades on {}", dex))?;
+    let mint = Pubkey::from_str(mint)?;
+    let pool = match pool_id {
+        Some(pool_id) => adapter.read_pool(&Pubkey::from_str(pool_id)?, &mint).await?,
+        None => adapter.get_pool(&mint).await?,
+    };
+    let quoted = adapter.quote(&pool, amount, SwapDirection::Sell).await?;
+    adapter.build_swap_instructions(&pool, amount, min_amount_with_slippage(quoted, slippage_bps), SwapDirection::Sell).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(dex: &str, pool_id: &str, liquidity: u64) -> CachedPool {
+        CachedPool {
+            pool_id: pool_id.to_string(),
+            dex_name: dex.to_string(),
+            base_mint: "mint".to_string(),
+            quote_mint: SOL_MINT.to_string(),
+            last_known_price: None,
+            last_updated: None,
+            liquidity: Some(liquidity),
+            subtype: None,
+            book: None,
+            health: None,
+            fee_bps: None,
+        }
+    }
+
+    fn traded(dex: &str) -> bool {
+        matches!(dex, "pumpswap" | "raydium_amm" | "meteora_dlmm")
+    }
+
+    #[test]
+    fn buys_are_refused_while_liquidating() {
+        let app_state = AppState::for_tests();
+        assert!(buys_allowed(&app_state));
+        app_state.liquidating.store(true, Ordering::SeqCst);
+        assert!(!buys_allowed(&app_state));
+        assert!(!AppState::for_tests().liquidating.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn sells_on_the_recorded_venue_first() {
+        let cached = [pool("pumpswap", "deep", 900), pool("raydium_amm", "shallow", 100)];
+        let venue = sell_venue(Some("raydium_amm"), &cached, traded);
+        assert_eq!(venue, Some(("raydium_amm".to_string(), Some("shallow".to_string()))));
+    }
+
+    #[test]
+    fn a_recorded_pumpswap_position_is_sold_on_pumpswap() {
+        // The venue the monitors record for copy and sniper buys
+        let app_state = AppState::for_tests();
+        let cached = [pool("raydium_amm", "deep", 900), pool("pumpswap", "pump", 100)];
+        let venue = sell_venue(Some("pumpswap"), &cached, |dex| app_state.dex_registry.adapter(dex).is_some());
+        assert_eq!(venue, Some(("pumpswap".to_string(), Some("pump".to_string()))));
+    }
+
+    #[test]
+    fn falls_back_to_the_deepest_tradable_pool() {
+        let cached = [pool("phoenix", "book", 5_000), pool("pumpswap", "deep", 900), pool("raydium_amm", "shallow", 100)];
+        // pump.fun positions have no adapter to sell them back through
+        let venue = sell_venue(Some("pump_fun"), &cached, traded);
+        assert_eq!(venue, Some(("pumpswap".to_string(), Some("deep".to_string()))));
+        assert_eq!(sell_venue(None, &[pool("phoenix", "book", 5_000)], traded), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Full account lists of streamed v0 transactions
+//!
+//! A v0 message lists only its static keys; the rest are indexes into
+//! address lookup tables, and instruction account indexes run over the
+//! static keys, then the looked-up writables, then the looked-up readonlys.
+//! The stream's transaction meta usually carries the addresses it loaded,
+//! and those are used as they are. Otherwise the tables come from a cache
+//! filled over RPC in the background: a transaction whose table isn't
+//! cached yet, or that indexes past the cached end because the table was
+//! extended since, is left unresolved and the table is fetched again for
+//! the transactions after it.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use anchor_client::solana_sdk::{address_lookup_table::state::AddressLookupTable, pubkey::Pubkey};
+use yellowstone_grpc_proto::prelude::{Message, TransactionStatusMeta};
+
+use crate::common::config::create_nonblocking_rpc_client;
+use crate::common::sync::LockExt;
+
+/// Tables cached at once; the cache starts over past this
+const MAX_CACHED_TABLES: usize = 4_096;
+
+lazy_static::lazy_static! {
+    static ref TABLES: Mutex<HashMap<Pubkey, Arc<Vec<Pubkey>>>> = Mutex::new(HashMap::new());
+    // Tables with a fetch in flight, so a burst of misses fetches once
+    static ref FETCHING: Mutex<HashSet<Pubkey>> = Mutex::new(HashSet::new());
+}
+
+/// The message's own keys, which lead the full list either way
+pub fn static_keys(message: &Message) -> Vec<Pubkey> {
+    message.account_keys.iter().map(|key| Pubkey::try_from(key.as_slice()).unwrap_or_default()).collect()
+}
+
+/// Every account `message` references, in the order its instructions index
+/// them; None while a lookup table it needs isn't cached
+pub fn resolve_account_keys(message: &Message, meta: Option<&TransactionStatusMeta>) -> Option<Vec<Pubkey>> {
+    let mut keys = static_keys(message);
+    if message.address_table_lookups.is_empty() {
+        return Some(keys);
+    }
+
+    let (writable_count, readonly_count) = message
+        .address_table_lookups
+        .iter()
+        .fold((0, 0), |(w, r), lookup| (w + lookup.writable_indexes.len(), r + lookup.readonly_indexes.len()));
+    if let Some(meta) = meta.filter(|meta| {
+        meta.loaded_writable_addresses.len() == writable_count && meta.loaded_readonly_addresses.len() == readonly_count
+    }) {
+        keys.extend(
+            meta.loaded_writable_addresses
+                .iter()
+                .chain(&meta.loaded_readonly_addresses)
+                .map(|key| Pubkey::try_from(key.as_slice()).unwrap_or_default()),
+        );
+        return Some(keys);
+    }
+
+    let mut writable = Vec::with_capacity(writable_count);
+    let mut readonly = Vec::with_capacity(readonly_count);
+    let mut missing = false;
+    for lookup in &message.address_table_lookups {
+        let Ok(table_key) = Pubkey::try_from(lookup.account_key.as_slice()) else {
+            return None;
+        };
+        let table = TABLES.lock_or_recover().get(&table_key).cloned();
+        let resolve = |indexes: &[u8]| -> Option<Vec<Pubkey>> {
+            indexes.iter().map(|i| table.as_ref()?.get(*i as usize).copied()).collect()
+        };
+        match (resolve(&lookup.writable_indexes), resolve(&lookup.readonly_indexes)) {
+            (Some(w), Some(r)) => {
+                writable.extend(w);
+                readonly.extend(r);
+            }
+            // Not cached, or extended past what is
+            _ => {
+                fetch_table(table_key);
+                missing = true;
+            }
+        }
+    }
+    if missing {
+        return None;
+    }
+    keys.extend(writable);
+    keys.extend(readonly);
+    Some(keys)
+}
+
+/// Fetch `table` into the cache in the background, replacing what was there
+fn fetch_table(table: Pubkey) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if !FETCHING.lock_or_recover().insert(table) {
+        return;
+    }
+    runtime.spawn(async move {
+        let fetched = async {
+            let rpc_client = create_nonblocking_rpc_client().await?;
+            let account = rpc_client.get_account(&table).await?;
+            let addresses = AddressLookupTable::deserialize(&account.data)?.addresses.to_vec();
+            anyhow::Ok(addresses)
+        }
+        .await;
+        if let Ok(addresses) = fetched {
+            let mut tables = TABLES.lock_or_recover();
+            if tables.len() >= MAX_CACHED_TABLES && !tables.contains_key(&table) {
+                tables.clear();
+            }
+            tables.insert(table, Arc::new(addresses));
+        }
+        FETCHING.lock_or_recover().remove(&table);
+    });
+}
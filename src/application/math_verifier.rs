@@ -0,0 +1,348 @@
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{hash::Hash, pubkey::Pubkey, signer::Signer};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use colored::Colorize;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::solana_program::native_token::lamports_to_sol;
+use tokio::time::Instant;
+
+use crate::common::{
+    config::{AppState, SwapConfig},
+    logger::Logger,
+    metrics::MATH_REL_ERROR,
+};
+use crate::core::priority_fee::PriorityFeeStrategy;
+use crate::dex::pump_swap::{PumpSwap, SOL_MINT};
+use crate::engine::copy_validation::simulate_token_account;
+use crate::engine::swap::{SwapDirection, SwapInType};
+use crate::record::math_checks::MathCheck;
+use crate::services::rpc_pool::CallClass;
+
+/// Slippage the verification swap is built with, wide so the simulation
+/// reports what the program charged instead of failing the bound
+const VERIFY_SLIPPAGE_BPS: u64 = 5_000;
+
+lazy_static::lazy_static! {
+    static ref ENABLED: AtomicBool = AtomicBool::new(false);
+    static ref SAMPLE_EVERY: AtomicU64 = AtomicU64::new(0);
+    static ref OFFERED: AtomicU64 = AtomicU64::new(0);
+    // Latest sampled swap; older ones are simply replaced
+    static ref PENDING: Mutex<Option<MathSample>> = Mutex::new(None);
+}
+
+/// A swap the bot just priced with its own curve math
+#[derive(Debug, Clone)]
+pub struct MathSample {
+    pub venue: String,
+    pub mint: String,
+    /// Lamports in
+    pub amount_in: u64,
+}
+
+/// `MATH_VERIFY_SAMPLE_EVERY`, `MATH_VERIFY_INTERVAL_SECS`,
+/// `MATH_VERIFY_TOLERANCE_PCT` and `MATH_VERIFY_WINDOW`
+#[derive(Debug, Clone, Copy)]
+pub struct VerifierConfig {
+    /// Offer one in this many priced swaps, 0 turns the verifier off
+    pub sample_every: u64,
+    /// At most one simulation per interval, which caps its RPC use
+    pub interval: Duration,
+    /// Rolling mean error, as a ratio, above which a venue alerts
+    pub tolerance: f64,
+    /// Comparisons per venue in the rolling mean
+    pub window: usize,
+}
+
+impl VerifierConfig {
+    pub fn from_env() -> Option<Self> {
+        let parse = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+        let sample_every = parse("MATH_VERIFY_SAMPLE_EVERY", 0.0) as u64;
+        (sample_every > 0).then(|| Self {
+            sample_every,
+            interval: Duration::from_secs(parse("MATH_VERIFY_INTERVAL_SECS", 300.0).max(1.0) as u64),
+            tolerance: parse("MATH_VERIFY_TOLERANCE_PCT", 1.0) / 100.0,
+            window: (parse("MATH_VERIFY_WINDOW", 20.0) as usize).max(1),
+        })
+    }
+}
+
+/// Start accepting samples
+pub fn enable(config: &VerifierConfig) {
+    SAMPLE_EVERY.store(config.sample_every, Ordering::Relaxed);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Offer a priced swap for verification; never blocks the caller
+pub fn offer(sample: MathSample) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let every = SAMPLE_EVERY.load(Ordering::Relaxed).max(1);
+    if OFFERED.fetch_add(1, Ordering::Relaxed) % every != 0 {
+        return;
+    }
+    if let Ok(mut pending) = PENDING.try_lock() {
+        *pending = Some(sample);
+    }
+}
+
+/// Local expectation and simulated result for the same swap, in one unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    pub expected: u64,
+    pub simulated: u64,
+}
+
+impl Comparison {
+    pub fn rel_error(&self) -> Option<f64> {
+        (self.expected > 0).then(|| self.simulated as f64 / self.expected as f64 - 1.0)
+    }
+}
+
+/// Prices a sampled swap both ways
+#[async_trait]
+pub trait SwapSimulator: Send + Sync {
+    async fn compare(&self, sample: &MathSample) -> Result<Comparison>;
+}
+
+/// PumpSwap buys are exact-out: our math picks the tokens `amount_in` buys,
+/// the program charges what it computes for them. The comparison is the
+/// WSOL the simulation spent against `amount_in`.
+pub struct PumpSwapSimulator {
+    app_state: Arc<AppState>,
+}
+
+impl PumpSwapSimulator {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+}
+
+#[async_trait]
+impl SwapSimulator for PumpSwapSimulator {
+    async fn compare(&self, sample: &MathSample) -> Result<Comparison> {
+        if sample.venue != "pumpswap" {
+            return Err(anyhow!("No simulator for {}", sample.venue));
+        }
+        let swapx = PumpSwap::new(
+            self.app_state.wallet.clone(),
+            Some(self.app_state.rpc_client.clone()),
+            Some(self.app_state.rpc_nonblocking_client.clone()),
+        );
+        let swap_config = SwapConfig {
+            swap_direction: SwapDirection::Buy,
+            in_type: SwapInType::Qty,
+            amount_in: lamports_to_sol(sample.amount_in),
+            slippage_bps: VERIFY_SLIPPAGE_BPS,
+            use_jito: false,
+            priority_fee: PriorityFeeStrategy::from_env(),
+        };
+        let (keypair, instructions, _) = swapx
+            .build_swap_ixn_by_mint(&sample.mint, None, swap_config, Instant::now())
+            .await?;
+        let wsol_account = get_associated_token_address(&keypair.pubkey(), &Pubkey::from_str(SOL_MINT)?);
+        // The blockhash is replaced by the simulation
+        let (before, after) = simulate_token_account(
+            &self.app_state,
+            CallClass::Discovery,
+            &keypair,
+            &instructions,
+            &wsol_account,
+            Hash::default(),
+        )
+        .await?;
+        Ok(Comparison { expected: sample.amount_in, simulated: before.saturating_sub(after) })
+    }
+}
+
+/// Rolling comparisons per venue and which venues are currently alerting
+#[derive(Debug, Default)]
+pub struct ErrorTracker {
+    windows: HashMap<String, VecDeque<f64>>,
+    alerting: HashMap<String, bool>,
+}
+
+impl ErrorTracker {
+    /// Add a comparison and return the venue's rolling mean error, plus
+    /// whether it just crossed the tolerance
+    pub fn record(&mut self, venue: &str, rel_error: f64, config: &VerifierConfig) -> (f64, bool) {
+        let window = self.windows.entry(venue.to_string()).or_default();
+        window.push_back(rel_error);
+        while window.len() > config.window {
+            window.pop_front();
+        }
+        let mean = window.iter().sum::<f64>() / window.len() as f64;
+        // A bias needs a few samples before it is believed
+        let over = window.len() >= config.window.min(5) && mean.abs() > config.tolerance;
+        let was_over = self.alerting.insert(venue.to_string(), over).unwrap_or(false);
+        (mean, over && !was_over)
+    }
+}
+
+/// Check one sampled swap per interval until the process exits
+pub async fn run_math_verifier(app_state: Arc<AppState>, config: VerifierConfig) {
+    let logger = Logger::new("[MATH-VERIFY] => ".magenta().to_string());
+    let simulator = PumpSwapSimulator::new(Arc::clone(&app_state));
+    let mut tracker = ErrorTracker::default();
+    loop {
+        app_state.clock.sleep(config.interval).await;
+        let sample = PENDING.lock().ok().and_then(|mut pending| pending.take());
+        let Some(sample) = sample else { continue };
+        verify_sample(&app_state, &simulator, &mut tracker, &config, &sample, &logger).await;
+    }
+}
+
+/// Compare, store and alert for one sample
+pub async fn verify_sample(
+    app_state: &AppState,
+    simulator: &dyn SwapSimulator,
+    tracker: &mut ErrorTracker,
+    config: &VerifierConfig,
+    sample: &MathSample,
+    logger: &Logger,
+) -> Option<f64> {
+    let comparison = match simulator.compare(sample).await {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            logger.log(format!("[SKIPPED] => {} on {}: {}", sample.mint, sample.venue, e).yellow().to_string());
+            return None;
+        }
+    };
+    let rel_error = comparison.rel_error()?;
+    let check = MathCheck {
+        checked_at: chrono::Utc::now().timestamp_millis(),
+        venue: sample.venue.clone(),
+        mint: sample.mint.clone(),
+        amount_in: sample.amount_in,
+        expected: comparison.expected,
+        simulated: comparison.simulated,
+        rel_error,
+    };
+    if let Err(e) = app_state.store.record_math_check(&check) {
+        logger.log(format!("[RECORD ERROR] => {}", e).red().to_string());
+    }
+
+    let (mean, crossed) = tracker.record(&sample.venue, rel_error, config);
+    MATH_REL_ERROR.with_label_values(&[&sample.venue]).set(mean);
+    logger.log(format!(
+        "[CHECKED] => {} on {}: local {} vs simulated {} ({:+.3}%), rolling {:+.3}%",
+        sample.mint, sample.venue, comparison.expected, comparison.simulated, rel_error * 100.0, mean * 100.0
+    ).cyan().to_string());
+    if crossed {
+        let body = format!(
+            "{} local math is off by {:+.3}% on average against simulation (tolerance {:.3}%)",
+            sample.venue, mean * 100.0, config.tolerance * 100.0
+        );
+        app_state.notifier.alert("Swap math drift", &body).await;
+    }
+    Some(rel_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::sync::LockExt;
+    use crate::services::notifier::Notifier;
+
+    /// Simulates every swap `deviation` away from the local math
+    struct SkewedSimulator {
+        deviation: f64,
+    }
+
+    #[async_trait]
+    impl SwapSimulator for SkewedSimulator {
+        async fn compare(&self, sample: &MathSample) -> Result<Comparison> {
+            let simulated = (sample.amount_in as f64 * (1.0 + self.deviation)).round() as u64;
+            Ok(Comparison { expected: sample.amount_in, simulated })
+        }
+    }
+
+    struct FailingSimulator;
+
+    #[async_trait]
+    impl SwapSimulator for FailingSimulator {
+        async fn compare(&self, _sample: &MathSample) -> Result<Comparison> {
+            Err(anyhow!("simulation unavailable"))
+        }
+    }
+
+    fn config() -> VerifierConfig {
+        VerifierConfig { sample_every: 1, interval: Duration::from_secs(1), tolerance: 0.01, window: 5 }
+    }
+
+    fn sample(venue: &str) -> MathSample {
+        MathSample { venue: venue.to_string(), mint: Pubkey::new_unique().to_string(), amount_in: 1_000_000_000 }
+    }
+
+    #[tokio::test]
+    async fn biased_venue_alerts_once_its_rolling_error_passes_tolerance() {
+        let (notifier, sent) = Notifier::recording();
+        let app_state = AppState::for_tests_with_rpc("succeeds", notifier);
+        let simulator = SkewedSimulator { deviation: 0.03 };
+        let mut tracker = ErrorTracker::default();
+        let logger = Logger::new(String::new());
+
+        for round in 1..=8 {
+            let rel_error = verify_sample(&app_state, &simulator, &mut tracker, &config(), &sample("pumpswap"), &logger)
+                .await
+                .unwrap();
+            assert!((rel_error - 0.03).abs() < 1e-9);
+            // Four samples aren't enough to believe a bias
+            let alerts = sent.lock_or_recover().len();
+            assert_eq!(alerts, usize::from(round >= 5), "after {} samples", round);
+        }
+
+        let (title, body) = sent.lock_or_recover()[0].clone();
+        assert_eq!(title, "Swap math drift");
+        assert!(body.contains("pumpswap") && body.contains("+3.000%"));
+        let checks = app_state.store.math_checks(Some("pumpswap")).unwrap();
+        assert_eq!(checks.len(), 8);
+        assert!(checks.iter().all(|check| check.simulated == 1_030_000_000));
+    }
+
+    #[tokio::test]
+    async fn accurate_venue_and_failed_simulations_stay_quiet() {
+        let (notifier, sent) = Notifier::recording();
+        let app_state = AppState::for_tests_with_rpc("succeeds", notifier);
+        let mut tracker = ErrorTracker::default();
+        let logger = Logger::new(String::new());
+
+        for _ in 0..10 {
+            let accurate = SkewedSimulator { deviation: 0.002 };
+            verify_sample(&app_state, &accurate, &mut tracker, &config(), &sample("raydium_amm"), &logger).await;
+            let skipped = verify_sample(&app_state, &FailingSimulator, &mut tracker, &config(), &sample("whirlpool"), &logger).await;
+            assert!(skipped.is_none());
+        }
+
+        assert!(sent.lock_or_recover().is_empty());
+        assert_eq!(app_state.store.math_checks(Some("raydium_amm")).unwrap().len(), 10);
+        assert!(app_state.store.math_checks(Some("whirlpool")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn tracker_rearms_after_the_error_recovers() {
+        let mut tracker = ErrorTracker::default();
+        let config = config();
+        let crossings: Vec<bool> = [0.03; 5]
+            .into_iter()
+            .chain([0.0; 5])
+            .chain([0.03; 5])
+            .map(|error| tracker.record("pumpswap", error, &config).1)
+            .collect();
+        assert_eq!(crossings.iter().filter(|crossed| **crossed).count(), 2);
+        // Back under tolerance by the ninth, over again from the twelfth
+        assert!(crossings[4] && crossings[11]);
+    }
+}
@@ -1,3 +1,40 @@
 pub mod monitor;
 pub mod swap;
 pub mod pool_discovery;
+pub mod arbitrage;
+pub mod split_router;
+pub mod liquidation;
+pub mod admin;
+pub mod reorg;
+pub mod analyze;
+pub mod capital;
+pub mod exit_ladder;
+pub mod refresh_scheduler;
+pub mod sweep;
+pub mod dormancy;
+pub mod copy_validation;
+pub mod tui;
+pub mod supervisor;
+pub mod leader;
+pub mod admin_auth;
+pub mod pool_health;
+pub mod quote;
+pub mod math_verifier;
+pub mod fill_quality;
+pub mod inventory;
+pub mod rpc_fallback;
+pub mod execution;
+pub mod stream_reconnect;
+pub mod pool_accounts;
+pub mod balance_deltas;
+pub mod lookup_tables;
+pub mod triangular;
+pub mod monitor_context;
+pub mod doctor;
+pub mod arbitrage_alt;
+pub mod durable_nonce;
+pub mod dev_buy;
+pub mod confirmation;
+pub mod blockhash_cache;
+pub mod quote_currency;
+pub mod preflight;
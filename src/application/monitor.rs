@@ -2,11 +2,12 @@ use borsh::from_slice;
 use maplit::hashmap;
 use anchor_client::solana_sdk::signature::Signer;
 use anchor_client::solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature};
-use spl_token::solana_program::native_token::{lamports_to_sol, LAMPORTS_PER_SOL};
+use spl_token::solana_program::native_token::{lamports_to_sol, sol_to_lamports, LAMPORTS_PER_SOL};
 use tokio::process::Command;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::{collections::HashSet, time::Duration};
+use std::time::Duration;
 use base64;
 
 use super::swap::{SwapDirection, SwapInType};
@@ -23,14 +24,56 @@ use crate::common::config::{
     RAYDIUM_LAUNCHPAD_LOG_INSTRUCTION,
 };
 use crate::common::{    
-    config::{AppState, LiquidityPool, Status, SwapConfig},
-    logger::Logger,
+    clock::{SystemClock, Timestamps},
+    latency::{LatencyTracer, Stage},
+    config::{max_wait_time, AppState, ArbitrageSettings, LiquidityPool, Status, SwapConfig},
+    events::{self, BotEvent},
+    format::{cached_decimals, fmt_lamports_exact, fmt_pct, fmt_sol, fmt_sol_signed, remember_decimals},
+    logger::{LogLevel, Logger},
 };
+use crate::common::sync::LockExt;
 use crate::core::tx;
+use crate::core::ids;
+use crate::core::price::{BookTouch, Price, PriceGate, PriceVerdict};
+use crate::core::token;
+use crate::engine::balance_deltas::compute_balance_deltas;
+use crate::engine::capital::Strategy;
+use crate::engine::confirmation;
+use crate::engine::exit_ladder::{self, ActiveLadders, ExitLadder, LadderOutcome};
+use crate::engine::copy_validation::{self, CopySkip, SimulationConfig, SwapDelta, ValidationConfig};
+use crate::engine::dev_buy::{DevBuy, Launches, SniperLimits};
+use crate::engine::dormancy;
+use crate::engine::execution;
+use crate::engine::fill_quality;
+use crate::engine::inventory;
+use crate::engine::lookup_tables;
+use crate::engine::preflight::{self, Preflight};
+use crate::engine::monitor_context::TokenTrackingInfo;
+use crate::engine::math_verifier::{self, MathSample};
+use crate::engine::pool_accounts::{self, PoolAccountWatch};
+use crate::engine::rpc_fallback;
+use crate::engine::stream_reconnect::{self, ReconnectBackoff, StaleSignal, StreamCursor};
+use crate::engine::triangular::{self, TriangularConfig};
+use crate::engine::arbitrage::{
+    best_direction, compute_optimal_size, LegFees, ProfitBreakdown, Rejection, SkipReason, SpreadTracker, VenueQuote,
+};
 use crate::dex::dex_registry::{DEXRegistry, identify_dex_from_pool};
+use crate::dex::pump_swap::{PumpSwapEvent, SOL_MINT};
+use crate::dex::swap_accounts::decode_pool_accounts;
+use crate::engine::pool_discovery::PoolCacheManager;
+use crate::engine::pool_health::{self, HealthEvent};
+use crate::engine::quote_currency::{self, LegQuotes, QuoteAsset};
+use crate::engine::reorg::{self, PositionCheck, SlotTracker, TrackedPosition};
+use crate::services::notifier::Notifier;
+use crate::record::snapshot_recorder::{append_snapshots, is_observe_only, PriceSnapshot, SnapshotRecorderConfig};
+use crate::record::price_board::{PriceBoardConfig, PriceBoardWriter};
+use crate::record::kv;
+use crate::record::opportunities::{ArbitrageOpportunity, TriangleRecord, SOURCE_LIVE};
+use crate::record::store::TradeRecord;
 use anyhow::{anyhow, Result};
 use chrono::{Utc, Local};
 use colored::Colorize;
+use dashmap::DashMap;
 use futures_util::stream::StreamExt;
 use futures_util::{SinkExt, Sink};
 use tokio::{
@@ -45,12 +88,16 @@ use yellowstone_grpc_proto::geyser::{
     subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest, SubscribeRequestPing,
     SubscribeRequestFilterTransactions, SubscribeUpdateTransaction, SubscribeUpdate,
 };
+use yellowstone_grpc_proto::prelude::TransactionStatusMeta;
 use std::str::FromStr;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 use serde_json;
 
+/// Pools the copy trader has bought or sold, one entry per mint
+type TrackedPools = DashMap<String, LiquidityPool>;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum InstructionType {
     SwapBuy,
@@ -72,8 +119,16 @@ pub struct PoolInfo {
     pub quote_mint: Pubkey,
     pub pool_base_token_account: Pubkey,
     pub pool_quote_token_account: Pubkey,
-    pub base_reserve: u64,
-    pub quote_reserve: u64,
+    /// None when the swap's logs didn't carry it; see `fill_reserves_from_post_balances`
+    pub base_reserve: Option<u64>,
+    pub quote_reserve: Option<u64>,
+}
+
+impl PoolInfo {
+    /// Both reserves, once neither is missing
+    pub fn reserves(&self) -> Option<(u64, u64)> {
+        self.base_reserve.zip(self.quote_reserve)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -96,6 +151,15 @@ pub struct TradeInfoFromToken {
     pub target_dex: Option<String>,
     pub price_difference: Option<f64>,
     pub expected_profit: Option<f64>,
+    /// Target's net SOL change in lamports, wrapped SOL included, negative
+    /// when it spent SOL
+    pub volume_change: i64,
+    /// Only set for bonding-curve trades; PumpSwap pool trades carry `pool_info`
+    pub bonding_curve_info: Option<BondingCurveInfo>,
+    /// When the transaction was parsed off the stream
+    pub received_at: Timestamps,
+    /// Stage stamps from the gRPC receive on
+    pub latency: LatencyTracer,
 }
 
 pub struct FilterConfig {
@@ -103,13 +167,43 @@ pub struct FilterConfig {
     dex_program_ids: Vec<String>,
     arbitrage_threshold_pct: f64,
     min_liquidity: u64,
+    copy_trading_target_addresses: Vec<String>,
 }
 
-#[derive(Clone, Debug)]
-pub struct TokenTrackingInfo {
-    pub top_pnl: f64,
-    pub last_price_check: Instant,
-    pub price_history: Vec<(f64, Instant)>,  // Store price history with timestamps
+impl FilterConfig {
+    /// Stream filter for following `targets`, with `program_ids` the accounts to subscribe to
+    fn copy_trading(program_ids: Vec<String>, targets: Vec<String>) -> Self {
+        Self {
+            program_ids,
+            dex_program_ids: vec![],
+            arbitrage_threshold_pct: 0.0,
+            min_liquidity: 0,
+            copy_trading_target_addresses: targets,
+        }
+    }
+
+    /// Stream filter for the arbitrage scanner over every venue in `dex_program_ids`
+    fn arbitrage(dex_program_ids: Vec<String>, arbitrage_threshold_pct: f64, min_liquidity: u64) -> Self {
+        Self {
+            program_ids: dex_program_ids.clone(),
+            dex_program_ids,
+            arbitrage_threshold_pct,
+            min_liquidity,
+            copy_trading_target_addresses: vec![],
+        }
+    }
+}
+
+/// `COPY_TRADING_TARGET_ADDRESS` as a list: comma-separated when
+/// `IS_MULTI_COPY_TRADING` is set, one address otherwise
+fn copy_trading_targets(address: Option<String>, is_multi: bool) -> Vec<String> {
+    let Some(address) = address else {
+        return Vec::new();
+    };
+    if !is_multi {
+        return if address.is_empty() { Vec::new() } else { vec![address] };
+    }
+    address.split(',').map(str::trim).filter(|addr| !addr.is_empty()).map(str::to_string).collect()
 }
 
 #[derive(Clone, Debug)]
@@ -125,101 +219,37 @@ pub struct CopyTradeInfo {
 }
 
 lazy_static::lazy_static! {
-    static ref COUNTER: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
-    static ref SOLD: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
-    static ref BOUGHTS: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
-    static ref LAST_BUY_PAUSE_TIME: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
-    static ref BUYING_ENABLED: Arc<Mutex<bool>> = Arc::new(Mutex::new(true));
-    static ref TOKEN_TRACKING: Arc<Mutex<HashMap<String, TokenTrackingInfo>>> = Arc::new(Mutex::new(HashMap::new()));
-    
-    // Cache for THRESHOLD_BUY loaded from .env
-    static ref THRESHOLD_BUY: Arc<Mutex<u64>> = Arc::new(Mutex::new(
-        std::env::var("THRESHOLD_BUY")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(1_000_000_000) // Default to 1 SOL if not specified
-    ));
-    
-    // Cache for THRESHOLD_SELL loaded from .env
-    static ref THRESHOLD_SELL: Arc<Mutex<u64>> = Arc::new(Mutex::new(
-        std::env::var("THRESHOLD_SELL")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(1_000_000_000) // Default to 1 SOL if not specified
-    ));
-    
-    // Cache for MAX_WAIT_TIME loaded from .env
-    static ref MAX_WAIT_TIME: Arc<Mutex<u64>> = Arc::new(Mutex::new(
-        std::env::var("MAX_WAIT_TIME")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(60000) // Default to 60 seconds if not specified
-    ));
-    
-    // For tracking last received message time
-    static ref LAST_MESSAGE_TIME: Arc<Mutex<Instant>> = Arc::new(Mutex::new(Instant::now()));
-    
-    // For arbitrage settings
-    static ref ARBITRAGE_THRESHOLD: Arc<Mutex<f64>> = Arc::new(Mutex::new(
-        std::env::var("ARBITRAGE_THRESHOLD")
-            .ok()
-            .and_then(|v| v.parse::<f64>().ok())
-            .unwrap_or(1.5) // Default to 1.5% if not specified
-    ));
-    
-    // For minimum liquidity required for arbitrage
-    static ref MIN_LIQUIDITY: Arc<Mutex<u64>> = Arc::new(Mutex::new(
-        std::env::var("MIN_LIQUIDITY")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(10_000_000) // Default to 10 SOL if not specified
-    ));
-    
-    // For tracking price differences across DEXes
-    static ref PRICE_DIFFERENCES: Arc<Mutex<HashMap<String, HashMap<(String, String), f64>>>> = 
-        Arc::new(Mutex::new(HashMap::new()));
-}
-
-// Add this function to update the last message time
-fn update_last_message_time() {
-    let mut last_time = LAST_MESSAGE_TIME.lock().unwrap();
-    *last_time = Instant::now();
+    // Transaction parsing runs per stream message, so everything it says is debug or trace
+    static ref PARSE_LOGGER: Logger = Logger::new("[TX-PARSER] => ".white().to_string());
 }
 
-// Add this function to check connection health based on message reception
-async fn check_connection_health(logger: &Logger) {
-    let last_time = {
-        let time = LAST_MESSAGE_TIME.lock().unwrap();
-        *time
-    };
-    
-    let now = Instant::now();
-    let elapsed = now.duration_since(last_time);
-    
-    // If we haven't received a message in 5 minutes, log a warning
-    if elapsed > Duration::from_secs(300) { // 5 minutes
+// Check connection health based on message reception
+async fn check_connection_health(app_state: &AppState, stale: &StaleSignal, logger: &Logger) {
+    // If we haven't received a message in a while, have the stream loop resubscribe
+    if let Some(elapsed) = app_state.monitor.stale_for(app_state.clock.now()) {
         logger.log(format!(
-            "[CONNECTION WARNING] => No messages received in {:?}. Connection may be stale.",
+            "[CONNECTION WARNING] => No messages received in {:?}. Forcing a resubscribe.",
             elapsed
         ).yellow().to_string());
+        stale.report();
     }
 }
 
 impl TradeInfoFromToken {
-    pub fn from_json(txn: SubscribeUpdateTransaction, log_messages: Vec<String>) -> Result<Self> {
+    #[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all, fields(slot = txn.slot)))]
+    /// Parse a streamed transaction, stamping `latency` once it is parsed
+    pub fn from_json(txn: SubscribeUpdateTransaction, log_messages: Vec<String>, mut latency: LatencyTracer) -> Result<Self> {
         let slot = txn.slot;
-        println!("==== BEGIN TRANSACTION PARSING ====");
-        println!("Transaction slot: {}", slot);
-        println!("Log messages count: {}", log_messages.len());
+        PARSE_LOGGER.debug_with(|| format!("Parsing transaction at slot {} with {} log messages", slot, log_messages.len()));
         
-        for (i, log) in log_messages.iter().enumerate() {
-            println!("LOG[{}]: {}", i, log);
+        // The full transaction only at trace level, it is slow to format
+        if LogLevel::Trace.enabled() {
+            for (i, log) in log_messages.iter().enumerate() {
+                PARSE_LOGGER.trace(format!("LOG[{}]: {}", i, log));
+            }
+            PARSE_LOGGER.trace(format!("{:#?}", txn));
         }
         
-        // Print the full transaction object in detail for debugging
-        println!("=== DETAILED TRANSACTION OBJECT ===");
-        println!("{:#?}", txn);
-        
         let mut instruction_type = InstructionType::SwapBuy;
         let mut encoded_data = String::new();
         let mut amount: Option<u64> = None;
@@ -232,34 +262,35 @@ impl TradeInfoFromToken {
         let mut price_difference: Option<f64> = None;
         let mut expected_profit: Option<f64> = None;
             
-        println!("Searching for instruction type in logs...");
+        let event = PumpSwapEvent::find(&log_messages);
+        PARSE_LOGGER.debug_with(|| "Searching for instruction type in logs...".to_string());
         
         // First detect instruction type from logs
         for log in log_messages.iter() {
-            println!("Checking log: {}", log);
+            PARSE_LOGGER.trace_with(|| format!("Checking log: {}", log));
             
             if log.contains(PUMP_SWAP_BUY_LOG_INSTRUCTION) && log_messages.iter().any(|l| l.contains(PUMP_SWAP_BUY_PROGRAM_DATA_PREFIX)) {
                 instruction_type = InstructionType::SwapBuy;
-                println!("DETECTED SwapBuy instruction: {}", log);
+                PARSE_LOGGER.debug_with(|| format!("DETECTED SwapBuy instruction: {}", log));
                 break;
             } else if log.contains(PUMP_SWAP_SELL_LOG_INSTRUCTION) && log_messages.iter().any(|l| l.contains(PUMP_SWAP_SELL_PROGRAM_DATA_PREFIX)) {
                 instruction_type = InstructionType::SwapSell;
-                println!("DETECTED SwapSell instruction: {}", log);
+                PARSE_LOGGER.debug_with(|| format!("DETECTED SwapSell instruction: {}", log));
                 break;
             } else if log.contains("Program pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA") {
                 // This is a fallback check for PumpSwap program
                 for other_log in log_messages.iter() {
                     if other_log.contains("BuyEvent") {
                         instruction_type = InstructionType::SwapBuy;
-                        println!("DETECTED SwapBuy instruction via fallback: {}", other_log);
+                        PARSE_LOGGER.debug_with(|| format!("DETECTED SwapBuy instruction via fallback: {}", other_log));
                         break;
                     } else if other_log.contains("SellEvent") {
                         instruction_type = InstructionType::SwapSell;
-                        println!("DETECTED SwapSell instruction via fallback: {}", other_log);
+                        PARSE_LOGGER.debug_with(|| format!("DETECTED SwapSell instruction via fallback: {}", other_log));
                         break;
                     } else if other_log.contains("ArbitrageEvent") {
                         instruction_type = InstructionType::ArbitrageSwap;
-                        println!("DETECTED ArbitrageSwap instruction via fallback: {}", other_log);
+                        PARSE_LOGGER.debug_with(|| format!("DETECTED ArbitrageSwap instruction via fallback: {}", other_log));
                         break;
                     }
                 }
@@ -269,28 +300,40 @@ impl TradeInfoFromToken {
                 }
             }
         }
+        // A decoded event settles the direction whatever the log text said
+        match &event {
+            Some(PumpSwapEvent::Buy(_)) => instruction_type = InstructionType::SwapBuy,
+            Some(PumpSwapEvent::Sell(_)) => instruction_type = InstructionType::SwapSell,
+            None => {}
+        }
         
-        println!("Instruction type detected: {:?}", instruction_type);
+        PARSE_LOGGER.debug_with(|| format!("Instruction type detected: {:?}", instruction_type));
 
         // Process based on instruction type
         match instruction_type {
             InstructionType::SwapBuy => {
-                println!("Processing SwapBuy instruction");
-                // Extract swap buy parameters
-                for log in log_messages.iter() {
-                    if log.contains("base_amount_out:") {
-                        if let Some(value_str) = log.split("base_amount_out:").nth(1).map(|s| s.trim()) {
-                            if let Ok(value) = value_str.parse::<u64>() {
-                                base_amount_out = Some(value);
-                                println!("Extracted base_amount_out: {}", value);
+                PARSE_LOGGER.debug_with(|| "Processing SwapBuy instruction".to_string());
+                // The decoded event first, plaintext logs for older program versions
+                if let Some(PumpSwapEvent::Buy(buy)) = &event {
+                    base_amount_out = Some(buy.base_amount_out);
+                    max_quote_amount_in = Some(buy.max_quote_amount_in);
+                    PARSE_LOGGER.debug_with(|| format!("Decoded BuyEvent: base_amount_out {}, max_quote_amount_in {}", buy.base_amount_out, buy.max_quote_amount_in));
+                } else {
+                    for log in log_messages.iter() {
+                        if log.contains("base_amount_out:") {
+                            if let Some(value_str) = log.split("base_amount_out:").nth(1).map(|s| s.trim()) {
+                                if let Ok(value) = value_str.parse::<u64>() {
+                                    base_amount_out = Some(value);
+                                    PARSE_LOGGER.debug_with(|| format!("Extracted base_amount_out: {}", value));
+                                }
                             }
                         }
-                    }
-                    if log.contains("max_quote_amount_in:") {
-                        if let Some(value_str) = log.split("max_quote_amount_in:").nth(1).map(|s| s.trim()) {
-                            if let Ok(value) = value_str.parse::<u64>() {
-                                max_quote_amount_in = Some(value);
-                                println!("Extracted max_quote_amount_in: {}", value);
+                        if log.contains("max_quote_amount_in:") {
+                            if let Some(value_str) = log.split("max_quote_amount_in:").nth(1).map(|s| s.trim()) {
+                                if let Ok(value) = value_str.parse::<u64>() {
+                                    max_quote_amount_in = Some(value);
+                                    PARSE_LOGGER.debug_with(|| format!("Extracted max_quote_amount_in: {}", value));
+                                }
                             }
                         }
                     }
@@ -301,7 +344,7 @@ impl TradeInfoFromToken {
                     let signature = match Signature::try_from(transaction.signature.clone()) {
                         Ok(signature) => {
                             let sig_str = format!("{:?}", signature);
-                            println!("Parsed signature: {}", sig_str);
+                            PARSE_LOGGER.debug_with(|| format!("Parsed signature: {}", sig_str));
                             sig_str
                         },
                         Err(_) => "".to_string(),
@@ -311,11 +354,11 @@ impl TradeInfoFromToken {
                         .and_then(|t| t.message.as_ref())
                         .map(|m| &m.recent_blockhash) {
                         Some(hash) => {
-                            println!("Found blockhash");
+                            PARSE_LOGGER.debug_with(|| "Found blockhash".to_string());
                             hash
                         },
                         None => {
-                            println!("Failed to get blockhash");
+                            PARSE_LOGGER.debug_with(|| "Failed to get blockhash".to_string());
                             return Err(anyhow::anyhow!("Failed to get recent blockhash"));
                         }
                     };
@@ -325,27 +368,10 @@ impl TradeInfoFromToken {
                     // Extract pool information
                     let pool_info = extract_pool_info_from_transaction(&transaction, &log_messages)?;
                     
-                    // Extract target address
-                    let target = extract_target_address_from_transaction(&transaction)?;
-                    
-                    // Extract token amount
-                    let token_amount = if let Some(meta) = &transaction.meta {
-                        meta.post_token_balances
-                            .iter()
-                            .filter_map(|token_balance| {
-                                if token_balance.owner == target {
-                                    token_balance
-                                        .ui_token_amount
-                                        .as_ref()
-                                        .map(|ui| ui.ui_amount)
-                                } else {
-                                    None
-                                }
-                            })
-                            .next()
-                            .unwrap_or(0_f64)
-                    } else {
-                        0_f64
+                    // The event names the trader; the signer is only a guess at it
+                    let target = match &event {
+                        Some(event) => event.user().to_string(),
+                        None => extract_target_address_from_transaction(&transaction)?,
                     };
                     
                     // Get mint from pool info
@@ -355,6 +381,18 @@ impl TradeInfoFromToken {
                         "".to_string()
                     };
                     
+                    // Amounts from what the target's balances did, over all its accounts
+                    let deltas = compute_balance_deltas(&transaction);
+                    let volume_change = deltas.sol(&target);
+                    let token_amount = deltas.token_ui(&target, &mint).abs();
+                    // Without a decoded event the log text only guessed the direction
+                    let instruction_type = match (&event, deltas.direction(&target, &mint)) {
+                        (None, Some(SwapDirection::Buy)) => InstructionType::SwapBuy,
+                        (None, Some(SwapDirection::Sell)) => InstructionType::SwapSell,
+                        _ => instruction_type,
+                    };
+                    
+                    latency.mark(Stage::Parsed);
                     return Ok(Self {
                         instruction_type,
                         slot,
@@ -373,30 +411,40 @@ impl TradeInfoFromToken {
                         target_dex,
                         price_difference,
                         expected_profit,
+                        volume_change,
+                        bonding_curve_info: None,
+                        received_at: Timestamps::now(&SystemClock),
+                        latency,
                     });
                 } else {
-                    println!("Transaction is None, cannot proceed");
+                    PARSE_LOGGER.debug_with(|| "Transaction is None, cannot proceed".to_string());
                     return Err(anyhow::anyhow!("Transaction is None"));
                 }
             },
             
             InstructionType::SwapSell => {
-                println!("Processing SwapSell instruction");
-                // Extract swap sell parameters
-                for log in log_messages.iter() {
-                    if log.contains("base_amount_in:") {
-                        if let Some(value_str) = log.split("base_amount_in:").nth(1).map(|s| s.trim()) {
-                            if let Ok(value) = value_str.parse::<u64>() {
-                                base_amount_in = Some(value);
-                                println!("Extracted base_amount_in: {}", value);
+                PARSE_LOGGER.debug_with(|| "Processing SwapSell instruction".to_string());
+                // The decoded event first, plaintext logs for older program versions
+                if let Some(PumpSwapEvent::Sell(sell)) = &event {
+                    base_amount_in = Some(sell.base_amount_in);
+                    min_quote_amount_out = Some(sell.min_quote_amount_out);
+                    PARSE_LOGGER.debug_with(|| format!("Decoded SellEvent: base_amount_in {}, min_quote_amount_out {}", sell.base_amount_in, sell.min_quote_amount_out));
+                } else {
+                    for log in log_messages.iter() {
+                        if log.contains("base_amount_in:") {
+                            if let Some(value_str) = log.split("base_amount_in:").nth(1).map(|s| s.trim()) {
+                                if let Ok(value) = value_str.parse::<u64>() {
+                                    base_amount_in = Some(value);
+                                    PARSE_LOGGER.debug_with(|| format!("Extracted base_amount_in: {}", value));
+                                }
                             }
                         }
-                    }
-                    if log.contains("min_quote_amount_out:") {
-                        if let Some(value_str) = log.split("min_quote_amount_out:").nth(1).map(|s| s.trim()) {
-                            if let Ok(value) = value_str.parse::<u64>() {
-                                min_quote_amount_out = Some(value);
-                                println!("Extracted min_quote_amount_out: {}", value);
+                        if log.contains("min_quote_amount_out:") {
+                            if let Some(value_str) = log.split("min_quote_amount_out:").nth(1).map(|s| s.trim()) {
+                                if let Ok(value) = value_str.parse::<u64>() {
+                                    min_quote_amount_out = Some(value);
+                                    PARSE_LOGGER.debug_with(|| format!("Extracted min_quote_amount_out: {}", value));
+                                }
                             }
                         }
                     }
@@ -407,7 +455,7 @@ impl TradeInfoFromToken {
                     let signature = match Signature::try_from(transaction.signature.clone()) {
                         Ok(signature) => {
                             let sig_str = format!("{:?}", signature);
-                            println!("Parsed signature: {}", sig_str);
+                            PARSE_LOGGER.debug_with(|| format!("Parsed signature: {}", sig_str));
                             sig_str
                         },
                         Err(_) => "".to_string(),
@@ -417,11 +465,11 @@ impl TradeInfoFromToken {
                         .and_then(|t| t.message.as_ref())
                         .map(|m| &m.recent_blockhash) {
                         Some(hash) => {
-                            println!("Found blockhash");
+                            PARSE_LOGGER.debug_with(|| "Found blockhash".to_string());
                             hash
                         },
                         None => {
-                            println!("Failed to get blockhash");
+                            PARSE_LOGGER.debug_with(|| "Failed to get blockhash".to_string());
                             return Err(anyhow::anyhow!("Failed to get recent blockhash"));
                         }
                     };
@@ -431,27 +479,10 @@ impl TradeInfoFromToken {
                     // Extract pool information
                     let pool_info = extract_pool_info_from_transaction(&transaction, &log_messages)?;
                     
-                    // Extract target address
-                    let target = extract_target_address_from_transaction(&transaction)?;
-                    
-                    // Extract token amount
-                    let token_amount = if let Some(meta) = &transaction.meta {
-                        meta.post_token_balances
-                            .iter()
-                            .filter_map(|token_balance| {
-                                if token_balance.owner == target {
-                                    token_balance
-                                        .ui_token_amount
-                                        .as_ref()
-                                        .map(|ui| ui.ui_amount)
-                                } else {
-                                    None
-                                }
-                            })
-                            .next()
-                            .unwrap_or(0_f64)
-                    } else {
-                        0_f64
+                    // The event names the trader; the signer is only a guess at it
+                    let target = match &event {
+                        Some(event) => event.user().to_string(),
+                        None => extract_target_address_from_transaction(&transaction)?,
                     };
                     
                     // Get mint from pool info
@@ -461,6 +492,18 @@ impl TradeInfoFromToken {
                         "".to_string()
                     };
                     
+                    // Amounts from what the target's balances did, over all its accounts
+                    let deltas = compute_balance_deltas(&transaction);
+                    let volume_change = deltas.sol(&target);
+                    let token_amount = deltas.token_ui(&target, &mint).abs();
+                    // Without a decoded event the log text only guessed the direction
+                    let instruction_type = match (&event, deltas.direction(&target, &mint)) {
+                        (None, Some(SwapDirection::Buy)) => InstructionType::SwapBuy,
+                        (None, Some(SwapDirection::Sell)) => InstructionType::SwapSell,
+                        _ => instruction_type,
+                    };
+                    
+                    latency.mark(Stage::Parsed);
                     return Ok(Self {
                         instruction_type,
                         slot,
@@ -479,35 +522,39 @@ impl TradeInfoFromToken {
                         target_dex,
                         price_difference,
                         expected_profit,
+                        volume_change,
+                        bonding_curve_info: None,
+                        received_at: Timestamps::now(&SystemClock),
+                        latency,
                     });
                 } else {
-                    println!("Transaction is None, cannot proceed");
+                    PARSE_LOGGER.debug_with(|| "Transaction is None, cannot proceed".to_string());
                     return Err(anyhow::anyhow!("Transaction is None"));
                 }
             },
             
             InstructionType::ArbitrageSwap => {
-                println!("Processing ArbitrageSwap instruction");
+                PARSE_LOGGER.debug_with(|| "Processing ArbitrageSwap instruction".to_string());
                 
                 // Extract arbitrage parameters
                 for log in log_messages.iter() {
                     if log.contains("source_dex:") {
                         if let Some(value_str) = log.split("source_dex:").nth(1).map(|s| s.trim()) {
                             source_dex = Some(value_str.to_string());
-                            println!("Extracted source_dex: {}", value_str);
+                            PARSE_LOGGER.debug_with(|| format!("Extracted source_dex: {}", value_str));
                         }
                     }
                     if log.contains("target_dex:") {
                         if let Some(value_str) = log.split("target_dex:").nth(1).map(|s| s.trim()) {
                             target_dex = Some(value_str.to_string());
-                            println!("Extracted target_dex: {}", value_str);
+                            PARSE_LOGGER.debug_with(|| format!("Extracted target_dex: {}", value_str));
                         }
                     }
                     if log.contains("price_difference:") {
                         if let Some(value_str) = log.split("price_difference:").nth(1).map(|s| s.trim()) {
                             if let Ok(value) = value_str.parse::<f64>() {
                                 price_difference = Some(value);
-                                println!("Extracted price_difference: {}", value);
+                                PARSE_LOGGER.debug_with(|| format!("Extracted price_difference: {}", value));
                             }
                         }
                     }
@@ -515,7 +562,7 @@ impl TradeInfoFromToken {
                         if let Some(value_str) = log.split("expected_profit:").nth(1).map(|s| s.trim()) {
                             if let Ok(value) = value_str.parse::<f64>() {
                                 expected_profit = Some(value);
-                                println!("Extracted expected_profit: {}", value);
+                                PARSE_LOGGER.debug_with(|| format!("Extracted expected_profit: {}", value));
                             }
                         }
                     }
@@ -526,7 +573,7 @@ impl TradeInfoFromToken {
                     let signature = match Signature::try_from(transaction.signature.clone()) {
                         Ok(signature) => {
                             let sig_str = format!("{:?}", signature);
-                            println!("Parsed signature: {}", sig_str);
+                            PARSE_LOGGER.debug_with(|| format!("Parsed signature: {}", sig_str));
                             sig_str
                         },
                         Err(_) => "".to_string(),
@@ -536,19 +583,23 @@ impl TradeInfoFromToken {
                         .and_then(|t| t.message.as_ref())
                         .map(|m| &m.recent_blockhash) {
                         Some(hash) => {
-                            println!("Found blockhash");
+                            PARSE_LOGGER.debug_with(|| "Found blockhash".to_string());
                             hash
                         },
                         None => {
-                            println!("Failed to get blockhash");
+                            PARSE_LOGGER.debug_with(|| "Failed to get blockhash".to_string());
                             return Err(anyhow::anyhow!("Failed to get recent blockhash"));
                         }
                     };
                     
                     let recent_blockhash = Hash::new(recent_blockhash_slice);
                     
-                    // Extract target address
-                    let target = extract_target_address_from_transaction(&transaction)?;
+                    // The event names the trader; the signer is only a guess at it
+                    let target = match &event {
+                        Some(event) => event.user().to_string(),
+                        None => extract_target_address_from_transaction(&transaction)?,
+                    };
+                    let volume_change = compute_balance_deltas(&transaction).sol(&target);
                     
                     // Extract mint from logs
                     let mut mint = String::new();
@@ -556,12 +607,13 @@ impl TradeInfoFromToken {
                         if log.contains("token_mint:") {
                             if let Some(value_str) = log.split("token_mint:").nth(1).map(|s| s.trim()) {
                                 mint = value_str.to_string();
-                                println!("Extracted token_mint: {}", value_str);
+                                PARSE_LOGGER.debug_with(|| format!("Extracted token_mint: {}", value_str));
                                 break;
                             }
                         }
                     }
                     
+                    latency.mark(Stage::Parsed);
                     return Ok(Self {
                         instruction_type,
                         slot,
@@ -580,123 +632,113 @@ impl TradeInfoFromToken {
                         target_dex,
                         price_difference,
                         expected_profit,
+                        volume_change,
+                        bonding_curve_info: None,
+                        received_at: Timestamps::now(&SystemClock),
+                        latency,
                     });
                 } else {
-                    println!("Transaction is None, cannot proceed");
+                    PARSE_LOGGER.debug_with(|| "Transaction is None, cannot proceed".to_string());
                     return Err(anyhow::anyhow!("Transaction is None"));
                 }
             }
         }
         
         // If we reach here, we failed to parse the transaction
-        println!("Failed to parse transaction");
+        PARSE_LOGGER.debug_with(|| "Failed to parse transaction".to_string());
         Err(anyhow::anyhow!("Failed to parse transaction"))
     }
 }
 
 /// Helper function to extract pool information from a transaction
-fn extract_pool_info_from_transaction(
+#[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all))]
+pub fn extract_pool_info_from_transaction(
     transaction: &yellowstone_grpc_proto::geyser::ConfirmedTransaction,
     log_messages: &[String],
 ) -> Result<Option<PoolInfo>> {
     if let Some(message) = transaction.transaction.as_ref().and_then(|t| t.message.as_ref()) {
-        let account_keys = &message.account_keys;
-        
         // Extract pool, base_mint, and quote_mint information
         let mut pool_id = Pubkey::default();
         let mut base_mint = Pubkey::default();
         let mut quote_mint = Pubkey::default();
         let mut pool_base_token_account = Pubkey::default();
         let mut pool_quote_token_account = Pubkey::default();
-        let mut base_reserve = 0u64;
-        let mut quote_reserve = 0u64;
+        let mut base_reserve = None;
+        let mut quote_reserve = None;
         
-        // Find DEX program instructions
-        for instruction in &message.instructions {
-            let program_idx = instruction.program_id_index as usize;
-            if let Some(program_key) = account_keys.get(program_idx) {
-                if let Ok(program_key_pubkey) = Pubkey::try_from(program_key.clone()) {
-                    // Check if this is a DEX program
-                    let dex_registry = DEXRegistry::new();
-                    if dex_registry.find_dex_by_program_id(&program_key_pubkey).is_some() {
-                        // Get accounts from instruction
-                        let accounts = &instruction.accounts;
-                        
-                        // Pool ID is typically the first account
-                        if accounts.len() > 0 {
-                            if let Some(pool_account_key) = account_keys.get(accounts[0] as usize) {
-                                if let Ok(pubkey) = Pubkey::try_from(pool_account_key.clone()) {
-                                    pool_id = pubkey;
-                                    println!("Pool ID: {}", pool_id);
-                                }
-                            }
-                        }
-                        
-                        // Base mint is typically the 4th account
-                        if accounts.len() > 3 {
-                            if let Some(base_mint_key) = account_keys.get(accounts[3] as usize) {
-                                if let Ok(pubkey) = Pubkey::try_from(base_mint_key.clone()) {
-                                    base_mint = pubkey;
-                                    println!("Base mint: {}", base_mint);
-                                }
-                            }
-                        }
-                        
-                        // Quote mint is typically the 5th account
-                        if accounts.len() > 4 {
-                            if let Some(quote_mint_key) = account_keys.get(accounts[4] as usize) {
-                                if let Ok(pubkey) = Pubkey::try_from(quote_mint_key.clone()) {
-                                    quote_mint = pubkey;
-                                    println!("Quote mint: {}", quote_mint);
-                                }
-                            }
-                        }
-                        
-                        // Pool token accounts are typically after that
-                        if accounts.len() > 7 {
-                            if let Some(pool_base_key) = account_keys.get(accounts[7] as usize) {
-                                if let Ok(pubkey) = Pubkey::try_from(pool_base_key.clone()) {
-                                    pool_base_token_account = pubkey;
-                                    println!("Pool base token account: {}", pool_base_token_account);
-                                }
-                            }
-                        }
-                        
-                        if accounts.len() > 8 {
-                            if let Some(pool_quote_key) = account_keys.get(accounts[8] as usize) {
-                                if let Ok(pubkey) = Pubkey::try_from(pool_quote_key.clone()) {
-                                    pool_quote_token_account = pubkey;
-                                    println!("Pool quote token account: {}", pool_quote_token_account);
-                                }
-                            }
-                        }
-                        
-                        break;
-                    }
-                }
-            }
+        // Decode the first swap of a venue with a mapping; any other
+        // instruction of a DEX program names other accounts
+        let keys = lookup_tables::resolve_account_keys(message, transaction.meta.as_ref())
+            .unwrap_or_else(|| lookup_tables::static_keys(message));
+        let dex_registry = DEXRegistry::shared();
+        let swap = message.instructions.iter().find_map(|instruction| {
+            let dex = dex_registry.find_dex_by_program_id(keys.get(instruction.program_id_index as usize)?)?;
+            let accounts = instruction
+                .accounts
+                .iter()
+                .map(|index| keys.get(*index as usize).copied())
+                .collect::<Option<Vec<Pubkey>>>()?;
+            decode_pool_accounts(dex, &instruction.data, &accounts)
+        });
+        if let Some(swap) = swap {
+            pool_id = swap.pool_id;
+            pool_base_token_account = swap.base_vault;
+            pool_quote_token_account = swap.quote_vault;
+            // Swaps that don't pass the mints still name the vaults, whose token balances carry them
+            let vault_mint = |vault: &Pubkey| {
+                let index = keys.iter().position(|key| key == vault)? as u32;
+                let meta = transaction.meta.as_ref()?;
+                meta.post_token_balances
+                    .iter()
+                    .chain(&meta.pre_token_balances)
+                    .find(|balance| balance.account_index == index)
+                    .and_then(|balance| Pubkey::from_str(&balance.mint).ok())
+            };
+            base_mint = swap.base_mint.or_else(|| vault_mint(&swap.base_vault)).unwrap_or_default();
+            quote_mint = swap.quote_mint.or_else(|| vault_mint(&swap.quote_vault)).unwrap_or_default();
+            PARSE_LOGGER.debug_with(|| format!(
+                "Pool {}: base mint {} (vault {}), quote mint {} (vault {})",
+                pool_id, base_mint, pool_base_token_account, quote_mint, pool_quote_token_account
+            ));
         }
         
-        // Extract pool reserves from logs
-        for log in log_messages {
+        // Extract pool reserves from the decoded event, or the logs' plaintext
+        let event = PumpSwapEvent::find(log_messages);
+        if let Some(event) = &event {
+            let (base, quote) = event.pool_reserves();
+            (base_reserve, quote_reserve) = (Some(base), Some(quote));
+            // The instruction's first account is a guess; the event is not
+            pool_id = event.pool();
+            PARSE_LOGGER.debug_with(|| format!("Decoded pool {} reserves: {} / {}", pool_id, base, quote));
+        }
+        let plaintext_logs: &[String] = if event.is_some() { &[] } else { log_messages };
+        for log in plaintext_logs {
             if log.contains("pool_base_token_reserves:") {
                 if let Some(value_str) = log.split("pool_base_token_reserves:").nth(1).map(|s| s.trim()) {
                     if let Ok(value) = value_str.parse::<u64>() {
-                        base_reserve = value;
-                        println!("Extracted pool_base_token_reserves: {}", value);
+                        base_reserve = Some(value);
+                        PARSE_LOGGER.debug_with(|| format!("Extracted pool_base_token_reserves: {}", value));
                     }
                 }
             }
             if log.contains("pool_quote_token_reserves:") {
                 if let Some(value_str) = log.split("pool_quote_token_reserves:").nth(1).map(|s| s.trim()) {
                     if let Ok(value) = value_str.parse::<u64>() {
-                        quote_reserve = value;
-                        println!("Extracted pool_quote_token_reserves: {}", value);
+                        quote_reserve = Some(value);
+                        PARSE_LOGGER.debug_with(|| format!("Extracted pool_quote_token_reserves: {}", value));
                     }
                 }
             }
         }
         
+        // Only PumpSwap logs its reserves; a zero is no more use than none,
+        // either way the caller reads the vaults before pricing
+        let base_reserve = base_reserve.filter(|reserve| *reserve > 0);
+        let quote_reserve = quote_reserve.filter(|reserve| *reserve > 0);
+        if base_reserve.is_none() || quote_reserve.is_none() {
+            PARSE_LOGGER.debug_with(|| format!("Pool {} reserves not in the logs", pool_id));
+        }
+        
         // Only return pool info if we have valid data
         if pool_id != Pubkey::default() && base_mint != Pubkey::default() {
             return Ok(Some(PoolInfo {
@@ -714,6 +756,174 @@ fn extract_pool_info_from_transaction(
     Ok(None)
 }
 
+/// Fill in the reserves the logs left out from the vaults' post token
+/// balances in the transaction's own meta; nothing is read over RPC, so a
+/// vault the meta doesn't report stays None for `fill_reserves_from_vaults`
+pub fn fill_reserves_from_post_balances(pool_info: &mut PoolInfo, transaction: &yellowstone_grpc_proto::geyser::ConfirmedTransaction) {
+    let message = transaction.transaction.as_ref().and_then(|t| t.message.as_ref());
+    let (Some(message), Some(meta)) = (message, transaction.meta.as_ref()) else { return };
+    let keys = lookup_tables::resolve_account_keys(message, Some(meta)).unwrap_or_else(|| lookup_tables::static_keys(message));
+    let vault_balance = |vault: &Pubkey| {
+        if *vault == Pubkey::default() {
+            return None;
+        }
+        let index = keys.iter().position(|key| key == vault)? as u32;
+        meta.post_token_balances
+            .iter()
+            .find(|balance| balance.account_index == index)?
+            .ui_token_amount
+            .as_ref()?
+            .amount
+            .parse::<u64>()
+            .ok()
+            .filter(|amount| *amount > 0)
+    };
+    if pool_info.base_reserve.is_none() {
+        pool_info.base_reserve = vault_balance(&pool_info.pool_base_token_account);
+    }
+    if pool_info.quote_reserve.is_none() {
+        pool_info.quote_reserve = vault_balance(&pool_info.pool_quote_token_account);
+    }
+    debug_assert!(
+        pool_info.base_reserve != Some(0) && pool_info.quote_reserve != Some(0),
+        "zero reserve kept for pool {}",
+        pool_info.pool_id
+    );
+}
+
+/// Read the reserves neither the logs nor the meta gave from the vault
+/// accounts themselves; an empty or unreadable vault stays None
+async fn fill_reserves_from_vaults(app_state: &AppState, pool_info: &mut PoolInfo) -> Result<()> {
+    let vaults = [pool_info.pool_base_token_account, pool_info.pool_quote_token_account];
+    let (balances, _) = rpc_fallback::read_token_balances(app_state, &vaults).await?;
+    let held = |index: usize| balances.get(index).copied().flatten().filter(|amount| *amount > 0);
+    pool_info.base_reserve = pool_info.base_reserve.or(held(0));
+    pool_info.quote_reserve = pool_info.quote_reserve.or(held(1));
+    Ok(())
+}
+
+/// A streamed swap's price as it entered the price map
+struct StreamedPrice {
+    token_mint: String,
+    token_is_base: bool,
+    quote: QuoteAsset,
+    /// In the pool's own quote asset
+    quoted: Price,
+    /// In SOL, as stored
+    price: Price,
+    /// Quote side in lamports
+    liquidity: u64,
+    /// Quote side in raw quote units
+    quote_reserve: u64,
+}
+
+/// Price the pool a streamed swap went through and store it in `prices`
+/// if the gate lets it through. Reserves the logs left out come from the
+/// vaults' post balances in the meta, then from the vault accounts. None,
+/// with the reason logged, when the pool has no usable price or the gate
+/// held it back.
+#[allow(clippy::too_many_arguments)]
+async fn price_streamed_swap(
+    app_state: &AppState,
+    pool_info: &mut PoolInfo,
+    dex_name: &str,
+    transaction: &yellowstone_grpc_proto::geyser::ConfirmedTransaction,
+    slot: u64,
+    prices: &rpc_fallback::PriceMap,
+    price_gate: &mut PriceGate,
+    logger: &Logger,
+) -> Option<StreamedPrice> {
+    if pool_info.reserves().is_none() {
+        fill_reserves_from_post_balances(pool_info, transaction);
+    }
+    if pool_info.reserves().is_none() {
+        if let Err(e) = fill_reserves_from_vaults(app_state, pool_info).await {
+            logger.debug_with(|| format!("[PRICE SKIPPED] => DEX: {}, pool {} vaults unreadable: {}", dex_name, pool_info.pool_id, e));
+        }
+    }
+    let Some((base_reserve, quote_reserve)) = pool_info.reserves() else {
+        logger.debug_with(|| format!(
+            "[PRICE SKIPPED] => DEX: {}, pool {} reserves are neither logged nor in its vaults",
+            dex_name, pool_info.pool_id
+        ));
+        return None;
+    };
+    // Key by the token side, quoted in SOL or USDC
+    let Some((token_is_base, quote)) = QuoteAsset::orient(&pool_info.base_mint.to_string(), &pool_info.quote_mint.to_string()) else {
+        logger.debug_with(|| format!(
+            "[PRICE SKIPPED] => DEX: {}, pool {} is quoted in neither SOL nor USDC",
+            dex_name, pool_info.pool_id
+        ));
+        return None;
+    };
+    let (token_mint, token_reserve, quote_reserve) = if token_is_base {
+        (pool_info.base_mint.to_string(), base_reserve, quote_reserve)
+    } else {
+        (pool_info.quote_mint.to_string(), quote_reserve, base_reserve)
+    };
+    let now = app_state.clock.now();
+    // The reference pool prices USDC in SOL, the rate inverted
+    if quote == QuoteAsset::Sol && pool_info.pool_id == app_state.price_normalizer.pool() {
+        app_state.price_normalizer.observe_reserves(quote_reserve, token_reserve, slot, now);
+    }
+    // Prices are SOL per whole token, as polling and the pool cache keep
+    // them, so venues compare whatever the decimals
+    let Some(token_decimals) = swap_decimals(transaction.meta.as_ref(), &token_mint) else {
+        logger.debug_with(|| format!("[PRICE SKIPPED] => Token: {}, DEX: {}, decimals not known yet", token_mint, dex_name));
+        return None;
+    };
+    // An empty or one-sided pool has no price; this DEX sits the update out
+    // until its reserves are back
+    let quoted = match Price::from_reserves(&pool_info.pool_id.to_string(), token_reserve, quote_reserve, token_decimals, quote.decimals()) {
+        Ok(quoted) => quoted,
+        Err(e) => {
+            logger.log(format!("[PRICE SKIPPED] => Token: {}, DEX: {}, {}", token_mint, dex_name, e).yellow().to_string());
+            return None;
+        }
+    };
+    // USDC pools are compared in SOL, at the reference rate
+    let normalized = app_state.price_normalizer.to_sol(quoted, quote, now)
+        .zip(app_state.price_normalizer.lamports(quote_reserve, quote, now));
+    let Some((observed, liquidity)) = normalized else {
+        logger.debug_with(|| format!(
+            "[PRICE SKIPPED] => Token: {}, DEX: {}, quoted in USDC with no fresh SOL/USDC rate",
+            token_mint, dex_name
+        ));
+        return None;
+    };
+
+    // Update token prices, holding back jumps outside the sanity band
+    let verdict = {
+        let mut dex_prices = prices.entry(token_mint.clone()).or_default();
+        let last_known = dex_prices.get(dex_name).map(|(price, _, _, _)| *price);
+        let gate_key = format!("{}:{}", token_mint, dex_name);
+        let verdict = price_gate.observe(&gate_key, observed, last_known, now);
+        if let Some(price) = verdict.accepted() {
+            debug_assert!(price.value().is_finite() && price.value() > 0.0, "unusable price {} for {}", price, gate_key);
+            dex_prices.insert(dex_name.to_string(), (price, liquidity, slot, quote));
+        }
+        verdict
+    };
+    let price = match verdict {
+        PriceVerdict::Accept(price) => price,
+        PriceVerdict::Confirmed(price) => {
+            logger.log(format!(
+                "[PRICE CONFIRMED] => Token: {}, DEX: {}, jump to {} seen twice",
+                token_mint, dex_name, price
+            ).yellow().to_string());
+            price
+        }
+        PriceVerdict::Quarantined { price, last_known } => {
+            logger.log(format!(
+                "[PRICE QUARANTINED] => Token: {}, DEX: {}, {} vs last {}, waiting for confirmation",
+                token_mint, dex_name, price, last_known
+            ).yellow().to_string());
+            return None;
+        }
+    };
+    Some(StreamedPrice { token_mint, token_is_base, quote, quoted, price, liquidity, quote_reserve })
+}
+
 /// Helper function to extract target address from a transaction
 fn extract_target_address_from_transaction(
     transaction: &yellowstone_grpc_proto::geyser::ConfirmedTransaction,
@@ -769,9 +979,10 @@ async fn handle_ping_message(
 async fn process_stream_message(
     msg: &SubscribeUpdate,
     subscribe_tx: &Arc<tokio::sync::Mutex<impl Sink<SubscribeRequest, Error = impl std::fmt::Debug> + Unpin>>,
+    app_state: &AppState,
     logger: &Logger,
 ) -> Result<(), String> {
-    update_last_message_time();
+    app_state.monitor.note_message(app_state.clock.now());
     match &msg.update_oneof {
         Some(UpdateOneof::Ping(_)) => {
             handle_ping_message(subscribe_tx, logger).await?;
@@ -808,6 +1019,56 @@ async fn send_heartbeat_ping(
     }
 }
 
+/// Connect to Yellowstone and send `request`, retrying the subscribe a few
+/// times before giving up
+async fn connect_and_subscribe(
+    yellowstone_grpc_http: &str,
+    yellowstone_grpc_token: &str,
+    request: SubscribeRequest,
+    app_state: &AppState,
+    logger: &Logger,
+) -> Result<
+    (
+        impl Sink<SubscribeRequest, Error = impl std::fmt::Debug> + Unpin,
+        impl futures_util::Stream<Item = Result<SubscribeUpdate, impl std::fmt::Debug>> + Unpin,
+    ),
+    String,
+> {
+    let mut client = GeyserGrpcClient::build_from_shared(yellowstone_grpc_http.to_string())
+        .map_err(|e| format!("Failed to build client: {}", e))?
+        .x_token::<String>(Some(yellowstone_grpc_token.to_string()))
+        .map_err(|e| format!("Failed to set x_token: {}", e))?
+        .tls_config(ClientTlsConfig::new().with_native_roots())
+        .map_err(|e| format!("Failed to set tls config: {}", e))?
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+
+    let mut retry_count = 0;
+    const MAX_RETRIES: u32 = 3;
+    let (mut subscribe_tx, stream) = loop {
+        match client.subscribe().await {
+            Ok(pair) => break pair,
+            Err(e) => {
+                retry_count += 1;
+                if retry_count >= MAX_RETRIES {
+                    return Err(format!("Failed to subscribe after {} attempts: {}", MAX_RETRIES, e));
+                }
+                logger.log(format!(
+                    "[CONNECTION ERROR] => Failed to subscribe (attempt {}/{}): {}. Retrying in ~5 seconds...",
+                    retry_count, MAX_RETRIES, e
+                ).red().to_string());
+                app_state.clock.sleep(app_state.rng.jitter(Duration::from_secs(5), 20.0)).await;
+            }
+        }
+    };
+    subscribe_tx
+        .send(request)
+        .await
+        .map_err(|e| format!("Failed to send subscribe request: {:?}", e))?;
+    Ok((subscribe_tx, stream))
+}
+
 /// Function to ensure record directories exist
 fn ensure_record_dirs() -> Result<(), String> {
     let dirs = [
@@ -908,10 +1169,123 @@ fn extract_transaction_type(log_messages: &[String]) -> &'static str {
     "unknown"
 }
 
+/// Exit ladder from `EXIT_LADDER`, or the default scaled off MAX_WAIT_TIME
+fn load_exit_ladder(slippage_bps: u64, logger: &Logger) -> ExitLadder {
+    let max_wait = max_wait_time();
+    ExitLadder::from_env(max_wait, slippage_bps).unwrap_or_else(|e| {
+        logger.log(format!("[EXIT LADDER] => Invalid EXIT_LADDER ({}), using defaults", e).red().to_string());
+        ExitLadder::default_for(max_wait, slippage_bps)
+    })
+}
+
+/// Held positions opened more than `max_wait` before `now`, with when each was opened
+fn timed_out_positions(pools: &TrackedPools, now: Instant, max_wait: Duration) -> Vec<(String, Instant)> {
+    pools
+        .iter()
+        .filter(|pool| pool.status == Status::Bought)
+        .filter_map(|pool| Some((pool.mint.clone(), pool.timestamp?.instant)))
+        .filter(|(_, opened)| now.duration_since(*opened) > max_wait)
+        .collect()
+}
+
+/// Exit a timed-out position through the ladder in the background and mark
+/// it sold on success; capital held under `strategy` is released
+#[allow(clippy::too_many_arguments)]
+fn spawn_timeout_exit(
+    app_state: Arc<AppState>,
+    pools: Arc<TrackedPools>,
+    ladder: Arc<ExitLadder>,
+    active_ladders: Arc<ActiveLadders>,
+    mint: String,
+    opened_at: Instant,
+    strategy: Option<Strategy>,
+    logger: Logger,
+) {
+    tokio::spawn(async move {
+        let pools_for_check = Arc::clone(&pools);
+        let mint_for_check = mint.clone();
+        let still_open = move || {
+            pools_for_check
+                .get(&mint_for_check)
+                .is_some_and(|pool| pool.status == Status::Bought)
+        };
+        let outcome = exit_ladder::exit_position(
+            Arc::clone(&app_state),
+            &ladder,
+            &mint,
+            opened_at,
+            still_open,
+            &logger,
+        ).await;
+        active_ladders.finish(&mint);
+        settle_exit(&app_state, &pools, &mint, strategy, outcome, &logger);
+    });
+}
+
+/// Record how a position's exit ladder ended: sold and its capital
+/// released, or marked `Failure` once every stage and retry failed
+fn settle_exit(app_state: &AppState, pools: &TrackedPools, mint: &str, strategy: Option<Strategy>, outcome: LadderOutcome, logger: &Logger) {
+    match outcome {
+        LadderOutcome::Exited { stage, signature, sell_price } => {
+            if let Some(strategy) = strategy {
+                // Position closed, its capital goes back to the pool
+                app_state.capital.release(strategy, mint);
+            }
+            app_state.refresh_scheduler.set_open_position(mint, false);
+            close_position_state(app_state, mint, logger);
+            events::publish(BotEvent::PositionClosed { mint: mint.to_string(), exit_price: Some(sell_price) });
+
+            let all_sold = {
+                let buy_price = pools.get(mint).map(|pool| pool.buy_price).unwrap_or(0_f64);
+                pools.insert(mint.to_string(), LiquidityPool {
+                    mint: mint.to_string(),
+                    buy_price,
+                    sell_price,
+                    status: Status::Sold,
+                    timestamp: Some(Timestamps::now(app_state.clock.as_ref())),
+                });
+                !pools.iter().any(|pool| pool.status == Status::Bought)
+            };
+
+            logger.log(format!(
+                "\n\t * [SUCCESSFUL FORCE-SELL] => TX_HASH: (https://solscan.io/tx/{}) \n\t * [POOL] => ({}) \n\t * [STAGE] => {} :: {}.",
+                signature, mint, stage + 1, Utc::now()
+            ).green().to_string());
+
+            if all_sold {
+                app_state.monitor.set_buying_enabled(true);
+                logger.log(
+                    "\n\t * [BUYING ENABLED] => All tokens sold, can buy new tokens now"
+                    .green()
+                    .to_string(),
+                );
+            }
+        }
+        LadderOutcome::Interrupted { before_stage } => {
+            logger.log(format!(
+                "[EXIT LADDER] => {} stopped before stage {}, exited elsewhere or liquidating",
+                mint, before_stage + 1
+            ).yellow().to_string());
+        }
+        LadderOutcome::Exhausted => {
+            // Still holding the tokens, so the capital stays granted and
+            // buying stays closed until the position is liquidated
+            if let Some(mut pool) = pools.get_mut(mint) {
+                pool.status = Status::Failure;
+                pool.timestamp = Some(Timestamps::now(app_state.clock.as_ref()));
+            }
+            logger.log(format!(
+                "[EXIT LADDER] => Every stage and sell retry failed for {}, marked failed; run `liquidate` to close it",
+                mint
+            ).red().bold().to_string());
+        }
+    }
+}
+
 pub async fn new_token_trader_pumpfun(
     yellowstone_grpc_http: String,
     yellowstone_grpc_token: String,
-    app_state: AppState,
+    app_state: Arc<AppState>,
     swap_config: SwapConfig,
     time_exceed: u64,
     counter_limit: u64,
@@ -921,49 +1295,8 @@ pub async fn new_token_trader_pumpfun(
     // Log the copy trading configuration
     let logger = Logger::new("[PUMPFUN-MONITOR] => ".blue().bold().to_string());
 
-    // INITIAL SETTING FOR SUBSCIBE
-    // -----------------------------------------------------------------------------------------------------------------------------
-    let mut client = GeyserGrpcClient::build_from_shared(yellowstone_grpc_http.clone())
-        .map_err(|e| format!("Failed to build client: {}", e))?
-        .x_token::<String>(Some(yellowstone_grpc_token.clone()))
-        .map_err(|e| format!("Failed to set x_token: {}", e))?
-        .tls_config(ClientTlsConfig::new().with_native_roots())
-        .map_err(|e| format!("Failed to set tls config: {}", e))?
-        .connect()
-        .await
-        .map_err(|e| format!("Failed to connect: {}", e))?;
-
-    // Create additional clones for later use in tasks
-    let yellowstone_grpc_http = Arc::new(yellowstone_grpc_http);
-    let yellowstone_grpc_token = Arc::new(yellowstone_grpc_token);
-    let app_state = Arc::new(app_state);
     let swap_config = Arc::new(swap_config);
 
-    // Log the copy trading configuration
-    let logger = Logger::new("[PUMPFUN-MONITOR] => ".blue().bold().to_string());
-
-    let mut retry_count = 0;
-    const MAX_RETRIES: u32 = 3;
-    let (subscribe_tx, mut stream) = loop {
-        match client.subscribe().await {
-            Ok(pair) => break pair,
-            Err(e) => {
-                retry_count += 1;
-                if retry_count >= MAX_RETRIES {
-                    return Err(format!("Failed to subscribe after {} attempts: {}", MAX_RETRIES, e));
-                }
-                logger.log(format!(
-                    "[CONNECTION ERROR] => Failed to subscribe (attempt {}/{}): {}. Retrying in 5 seconds...",
-                    retry_count, MAX_RETRIES, e
-                ).red().to_string());
-                time::sleep(Duration::from_secs(5)).await;
-            }
-        }
-    };
-
-    // Convert to Arc to allow cloning across tasks
-    let subscribe_tx = Arc::new(tokio::sync::Mutex::new(subscribe_tx));
-
     // Get copy trading configuration from environment
     let copy_trading_target_address = std::env::var("COPY_TRADING_TARGET_ADDRESS").ok();
     let is_multi_copy_trading = std::env::var("IS_MULTI_COPY_TRADING")
@@ -978,34 +1311,11 @@ pub async fn new_token_trader_pumpfun(
         "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(), // Raydium
     ];
     
-    // Initialize the vector for copy trading target addresses
-    let mut copy_trading_target_addresses: Vec<String> = Vec::new();
-    // Handle multiple copy trading targets if enabled
-    if is_multi_copy_trading {
-        if let Some(address_str) = copy_trading_target_address {
-            // Parse comma-separated addresses
-            for addr in address_str.split(',') {
-                let trimmed_addr = addr.trim();
-                if !trimmed_addr.is_empty() {
-                    program_ids.push(trimmed_addr.to_string());
-                    copy_trading_target_addresses.push(trimmed_addr.to_string());
-                }
-            }
-        }
-    } else if let Some(address) = copy_trading_target_address {
-        // Single address mode
-        if !address.is_empty() {
-            program_ids.push(address.clone());
-            copy_trading_target_addresses.push(address);
-        }
-    }
+    // The targets are subscribed to as well as followed
+    let copy_trading_target_addresses = copy_trading_targets(copy_trading_target_address, is_multi_copy_trading);
+    program_ids.extend(copy_trading_target_addresses.iter().cloned());
 
-    let filter_config = FilterConfig {
-        program_ids: program_ids.clone(),
-        dex_program_ids: vec![],
-        arbitrage_threshold_pct: 0.0,
-        min_liquidity: 0,
-    };
+    let filter_config = FilterConfig::copy_trading(program_ids, copy_trading_target_addresses);
 
     // Log the copy trading configuration
     if !filter_config.copy_trading_target_addresses.is_empty() {
@@ -1022,39 +1332,54 @@ pub async fn new_token_trader_pumpfun(
         }
     }
 
-    subscribe_tx
-        .lock()
-        .await
-        .send(SubscribeRequest {
-            slots: HashMap::new(),
-            accounts: HashMap::new(),
-            transactions: hashmap! {
-                "All".to_owned() => SubscribeRequestFilterTransactions {
-                    vote: None,
-                    failed: Some(false),
-                    signature: None,
-                    account_include: vec![
-                        PUMP_PROGRAM.to_string(),                      // PumpFun
-                        "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA".to_string(), // PumpSwap
-                        "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(), // Raydium
-                    ],
-                    account_exclude: vec![JUPITER_PROGRAM.to_string(), OKX_DEX_PROGRAM.to_string()],
-                    account_required: Vec::<String>::new()
-                }
-            },
-            transactions_status: HashMap::new(),
-            entry: HashMap::new(),
-            blocks: HashMap::new(),
-            blocks_meta: HashMap::new(),
-            commitment: Some(CommitmentLevel::Processed as i32),
-            accounts_data_slice: vec![],
-            ping: None,
-            from_slot: None,
-        })
-        .await
-        .map_err(|e| format!("Failed to send subscribe request: {}", e))?;
+    let subscribe_request = SubscribeRequest {
+        slots: HashMap::new(),
+        accounts: HashMap::new(),
+        transactions: hashmap! {
+            "All".to_owned() => SubscribeRequestFilterTransactions {
+                vote: None,
+                failed: Some(false),
+                signature: None,
+                account_include: vec![
+                    PUMP_PROGRAM.to_string(),                      // PumpFun
+                    "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA".to_string(), // PumpSwap
+                    "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(), // Raydium
+                ],
+                account_exclude: vec![JUPITER_PROGRAM.to_string(), OKX_DEX_PROGRAM.to_string()],
+                account_required: Vec::<String>::new()
+            }
+        },
+        transactions_status: HashMap::new(),
+        entry: HashMap::new(),
+        blocks: HashMap::new(),
+        blocks_meta: HashMap::new(),
+        commitment: Some(CommitmentLevel::Processed as i32),
+        accounts_data_slice: vec![],
+        ping: None,
+        from_slot: None,
+    };
+    // The first subscription backs off like a resubscribe rather than exiting
+    let mut backoff = ReconnectBackoff::from_env();
+    let (subscribe_tx, mut stream) = loop {
+        match connect_and_subscribe(
+            &yellowstone_grpc_http,
+            &yellowstone_grpc_token,
+            subscribe_request.clone(),
+            &app_state,
+            &logger,
+        ).await {
+            Ok(pair) => break pair,
+            Err(e) => {
+                let delay = backoff.next_delay();
+                logger.log(format!("[CONNECT] => {}. Retrying in {:?}", e, delay).red().to_string());
+                app_state.clock.sleep(delay).await;
+            }
+        }
+    };
+    // Shared with the heartbeat task; a resubscribe swaps the sink inside
+    let subscribe_tx = Arc::new(tokio::sync::Mutex::new(subscribe_tx));
 
-    let existing_liquidity_pools = Arc::new(Mutex::new(HashSet::<LiquidityPool>::new()));
+    let existing_liquidity_pools = Arc::new(TrackedPools::new());
 
     let rpc_nonblocking_client = app_state.clone().rpc_nonblocking_client.clone();
     let rpc_client = app_state.clone().rpc_client.clone();
@@ -1068,10 +1393,7 @@ pub async fn new_token_trader_pumpfun(
     logger.log("[STARTED. MONITORING]...".blue().bold().to_string());
     
     // Set buying enabled to true at start
-    {
-        let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-        *buying_enabled = true;
-    }
+    app_state.monitor.set_buying_enabled(true);
 
     // After all setup and before the main loop, add a heartbeat ping task
     let subscribe_tx_clone = subscribe_tx.clone();
@@ -1084,9 +1406,9 @@ pub async fn new_token_trader_pumpfun(
         loop {
             interval.tick().await;
             
+            // A failed ping is left to the stream loop, which resubscribes into the same sink slot
             if let Err(e) = send_heartbeat_ping(&subscribe_tx_clone, &ping_logger).await {
                 ping_logger.log(format!("[CONNECTION ERROR] => {}", e).red().to_string());
-                break;
             }
         }
     });
@@ -1095,34 +1417,28 @@ pub async fn new_token_trader_pumpfun(
     let existing_liquidity_pools_clone = Arc::clone(&existing_liquidity_pools);
     let logger_clone = logger.clone();
     let app_state_for_background = Arc::clone(&app_state);
-    let swap_config_for_background = Arc::clone(&swap_config);
+    let exit_ladder = Arc::new(load_exit_ladder(swap_config.slippage_bps, &logger));
+    let active_ladders = Arc::new(ActiveLadders::default());
     
     tokio::spawn(async move {
         let pools_clone = Arc::clone(&existing_liquidity_pools_clone);
         let check_logger = logger_clone.clone();
         let app_state_clone = Arc::clone(&app_state_for_background);
-        let swap_config_clone = Arc::clone(&swap_config_for_background);
         
         loop {
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            app_state_clone.clock.sleep(Duration::from_secs(5)).await;
             
             // Check if there are any bought tokens and if any have exceeded MAX_WAIT_TIME
-            let now = Instant::now();
-            let max_wait_time_millis = *MAX_WAIT_TIME.lock().unwrap();
-            let max_wait_duration = Duration::from_millis(max_wait_time_millis);
+            let now = app_state_clone.clock.now();
+            let max_wait_duration = exit_ladder.start_after();
             
             let (has_bought_tokens, tokens_to_sell) = {
-                let pools = pools_clone.lock().unwrap();
-                let bought_tokens: Vec<String> = pools.iter()
+                let bought_tokens: Vec<String> = pools_clone.iter()
                     .filter(|pool| pool.status == Status::Bought)
                     .map(|pool| pool.mint.clone())
                     .collect();
                 
-                let timed_out_tokens: Vec<(String, Instant)> = pools.iter()
-                    .filter(|pool| pool.status == Status::Bought && 
-                           pool.timestamp.map_or(false, |ts| now.duration_since(ts) > max_wait_duration))
-                    .map(|pool| (pool.mint.clone(), pool.timestamp.unwrap()))
-                    .collect();
+                let timed_out_tokens = timed_out_positions(&pools_clone, now, max_wait_duration);
                 
                 // Log bought tokens that are waiting to be sold
                 if !bought_tokens.is_empty() {
@@ -1135,8 +1451,8 @@ pub async fn new_token_trader_pumpfun(
                 // Log tokens that have timed out and will be force-sold
                 if !timed_out_tokens.is_empty() {
                     check_logger.log(format!(
-                        "\n\t * [TIMEOUT DETECTED] => Will force-sell tokens that exceeded {} ms wait time: {:?}",
-                        max_wait_time_millis,
+                        "\n\t * [TIMEOUT DETECTED] => Will exit tokens that exceeded {:?} wait time: {:?}",
+                        max_wait_duration,
                         timed_out_tokens.iter().map(|(mint, _)| mint).collect::<Vec<_>>()
                     ).red().bold().to_string());
                 }
@@ -1145,131 +1461,27 @@ pub async fn new_token_trader_pumpfun(
             };
             
             // Update buying status
-            {
-                let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                *buying_enabled = !has_bought_tokens;
-            }
+            app_state_clone.monitor.set_buying_enabled(!has_bought_tokens);
             
-            // Force-sell tokens that have exceeded MAX_WAIT_TIME
+            // Walk timed-out tokens down the exit ladder, one ladder per token
             for (mint, timestamp) in tokens_to_sell {
-                // Clone the necessary state for this token
-                let logger_for_selling = check_logger.clone();
-                let pools_clone_for_selling = Arc::clone(&pools_clone);
-                let app_state_for_selling = app_state_clone.clone();
-                let swap_config_for_selling = swap_config_clone.clone();
-                
+                if !active_ladders.start(&mint) {
+                    continue;
+                }
                 check_logger.log(format!(
-                    "\n\t * [FORCE SELLING] => Token {} exceeded wait time (elapsed: {:?})",
+                    "\n\t * [EXIT LADDER] => Token {} exceeded wait time (elapsed: {:?})",
                     mint, now.duration_since(timestamp)
                 ).red().to_string());
-                
-                tokio::spawn(async move {
-                    // Get the existing pool for this mint
-                    let existing_pool = {
-                        let pools = pools_clone_for_selling.lock().unwrap();
-                        pools.iter()
-                            .find(|pool| pool.mint == mint)
-                            .cloned()
-                            .unwrap_or(LiquidityPool {
-                                mint: mint.clone(),
-                                buy_price: 0_f64,
-                                sell_price: 0_f64,
-                                status: Status::Bought,
-                                timestamp: Some(timestamp),
-                            })
-                    };
-                    
-                    // Set up sell config
-                    let sell_config = SwapConfig {
-                        swap_direction: SwapDirection::Sell,
-                        in_type: SwapInType::Pct,
-                        amount_in: 1_f64,  // Sell 100%
-                        slippage: 100_u64, // Use full slippage
-                        use_jito: swap_config_for_selling.clone().use_jito,
-                    };
-                    
-                    // Create Pump instance for selling
-                    let app_state_for_task = app_state_for_selling.clone();
-                    let rpc_nonblocking_client = app_state_for_task.rpc_nonblocking_client.clone();
-                    let rpc_client = app_state_for_task.rpc_client.clone();
-                    let wallet = app_state_for_task.wallet.clone();
-                    let swapx = Pump::new(rpc_nonblocking_client.clone(), rpc_client.clone(), wallet.clone());
-                    
-                    // Execute the sell operation
-                    let start_time = Instant::now();
-                    match swapx.build_swap_ixn_by_mint(&mint, None, sell_config, start_time).await {
-                        Ok(result) => {
-                            // Send instructions and confirm
-                            let (keypair, instructions, token_price) = (result.0, result.1, result.2);
-                            let recent_blockhash = match rpc_nonblocking_client.get_latest_blockhash().await {
-                                Ok(hash) => hash,
-                                Err(e) => {
-                                    logger_for_selling.log(format!(
-                                        "Error getting blockhash for force-selling {}: {}", mint, e
-                                    ).red().to_string());
-                                    return;
-                                }
-                            };
-                            
-                            match tx::new_signed_and_send_zeroslot(
-                                recent_blockhash,
-                                &keypair,
-                                instructions,
-                                &logger_for_selling,
-                            ).await {
-                                Ok(res) => {
-                                    let sold_pool = LiquidityPool {
-                                        mint: mint.clone(),
-                                        buy_price: existing_pool.buy_price,
-                                        sell_price: token_price,
-                                        status: Status::Sold,
-                                        timestamp: Some(Instant::now()),
-                                    };
-                                    
-                                    // Update pool status to sold
-                                    {
-                                        let mut pools = pools_clone_for_selling.lock().unwrap();
-                                        pools.retain(|pool| pool.mint != mint);
-                                        pools.insert(sold_pool.clone());
-                                    }
-                                    
-                                    logger_for_selling.log(format!(
-                                        "\n\t * [SUCCESSFUL FORCE-SELL] => TX_HASH: (https://solscan.io/tx/{}) \n\t * [POOL] => ({}) \n\t * [SOLD] => {} :: ({:?}).",
-                                        &res[0], mint, Utc::now(), start_time.elapsed()
-                                    ).green().to_string());
-                                    
-                                    // Check if all tokens are sold
-                                    let all_sold = {
-                                        let pools = pools_clone_for_selling.lock().unwrap();
-                                        !pools.iter().any(|pool| pool.status == Status::Bought)
-                                    };
-                                    
-                                    if all_sold {
-                                        // If all tokens are sold, enable buying
-                                        let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                                        *buying_enabled = true;
-                                        
-                                        logger_for_selling.log(
-                                            "\n\t * [BUYING ENABLED] => All tokens sold, can buy new tokens now"
-                                            .green()
-                                            .to_string(),
-                                        );
-                                    }
-                                },
-                                Err(e) => {
-                                    logger_for_selling.log(format!(
-                                        "Force-sell failed for {}: {}", mint, e
-                                    ).red().to_string());
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            logger_for_selling.log(format!(
-                                "Error building swap instruction for force-selling {}: {}", mint, e
-                            ).red().to_string());
-                        }
-                    }
-                });
+                spawn_timeout_exit(
+                    Arc::clone(&app_state_clone),
+                    Arc::clone(&pools_clone),
+                    Arc::clone(&exit_ladder),
+                    Arc::clone(&active_ladders),
+                    mint,
+                    timestamp,
+                    Some(Strategy::Sniper),
+                    check_logger.clone(),
+                );
             }
         }
     });
@@ -1290,30 +1502,54 @@ pub async fn new_token_trader_pumpfun(
     // In new_token_trader_pumpfun after the health check task:
     // Add a connection watchdog task
     let logger_watchdog = logger.clone();
+    let watchdog_app_state = Arc::clone(&app_state);
+    let stale_signal = Arc::new(StaleSignal::default());
+    let watchdog_signal = Arc::clone(&stale_signal);
     tokio::spawn(async move {
         let watchdog_logger = logger_watchdog;
-        let mut interval = time::interval(Duration::from_secs(120)); // Check every 2 minutes
+        let mut interval = time::interval(stream_reconnect::watchdog_interval(watchdog_app_state.monitor.stale_after()));
         
         loop {
             interval.tick().await;
-            check_connection_health(&watchdog_logger).await;
+            check_connection_health(&watchdog_app_state, &watchdog_signal, &watchdog_logger).await;
         }
     });
 
     // Ensure record directories exist
     ensure_record_dirs()?;
 
-    while let Some(message) = stream.next().await {
-        match message {
-            Ok(msg) => {
+    let limits = SniperLimits {
+        min_dev_buy_sol: min_dev_buy,
+        max_dev_buy_sol: max_dev_buy,
+        max_age: Duration::from_secs(time_exceed),
+        max_buys: counter_limit,
+    };
+    let mut launches = Launches::default();
+    let buys_made = Arc::new(AtomicU64::new(0));
+
+    backoff.reset();
+    let mut cursor = StreamCursor::default();
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            _ = stale_signal.stale() => None,
+        };
+        let reason = match message {
+            Some(Ok(msg)) => {
+                backoff.reset();
                 // Process ping/pong messages
-                if let Err(e) = process_stream_message(&msg, &subscribe_tx, &logger).await {
+                if let Err(e) = process_stream_message(&msg, &subscribe_tx, &app_state, &logger).await {
                     logger.log(format!("Error handling stream message: {}", e).red().to_string());
                     continue;
                 }
                 
                 // Process transaction messages
                 if let Some(UpdateOneof::Transaction(txn)) = msg.update_oneof {
+                    // A resumed stream replays the slot it stopped in
+                    if !cursor.first_sighting(&txn) {
+                        continue;
+                    }
+                    app_state.blockhashes.observe_transaction(&txn);
                     let start_time = Instant::now();
                     if let Some(log_messages) = txn
                         .clone()
@@ -1372,28 +1608,156 @@ pub async fn new_token_trader_pumpfun(
                             ).green().to_string());
                         }
                         
-                        // Continue with existing processing
-                        // ... rest of your transaction processing code ...
+                        let Some(dev_buy) = launches.observe(&txn, &log_messages, app_state.clock.now()) else {
+                            continue;
+                        };
+                        logger.log(format!(
+                            "\n\t * [DEV BUY] => (https://solscan.io/tx/{}) - SLOT:({}) \n\t * [LAUNCHPAD] => {} \n\t * [DEV] => ({}) \n\t * [TOKEN] => ({}) \n\t * [AMOUNT] => {} \n\t * [SINCE LAUNCH] => {:?}",
+                            dev_buy.signature,
+                            dev_buy.slot,
+                            dev_buy.launchpad.name(),
+                            dev_buy.dev,
+                            dev_buy.mint,
+                            fmt_sol(dev_buy.lamports),
+                            dev_buy.age,
+                        ).blue().to_string());
+
+                        if let Some(reason) = limits.refusal(&dev_buy, buys_made.load(Ordering::Relaxed)) {
+                            logger.log(format!("\n\t * [SKIPPING BUY] => {}, {}", dev_buy.mint, reason).yellow().to_string());
+                            continue;
+                        }
+                        if existing_liquidity_pools.contains_key(&dev_buy.mint) {
+                            logger.log(format!(
+                                "\n\t * [DUPLICATE TOKEN] => Token already in our pools: {}", dev_buy.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+                        // A panic liquidation disables buying for good
+                        if !crate::engine::liquidation::buys_allowed(&app_state) {
+                            logger.log("\n\t * [SKIPPING BUY] => Waiting for all tokens to be sold first".yellow().to_string());
+                            continue;
+                        }
+                        if app_state.monitor.entries_paused() {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Entries paused by operator: {}", dev_buy.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+                        if fill_quality::is_paused(Strategy::Sniper) {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Entries paused on fill quality: {}", dev_buy.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+                        if !crate::engine::leader::is_leader() {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Follower region, observing only: {}", dev_buy.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+
+                        // Closed until this buy settles, and after it while the position is held
+                        app_state.monitor.set_buying_enabled(false);
+                        spawn_sniper_buy(
+                            Arc::clone(&app_state),
+                            swapx.clone(),
+                            (*swap_config).clone(),
+                            dev_buy,
+                            Arc::clone(&existing_liquidity_pools),
+                            Arc::clone(&buys_made),
+                            start_time,
+                            logger.clone(),
+                        );
                     }
                 }
+                continue;
             }
-            Err(error) => {
-                logger.log(
-                    format!("Yellowstone gRpc Error: {:?}", error)
-                        .red()
-                        .to_string(),
-                );
-                break;
+            Some(Err(error)) => format!("Yellowstone gRpc Error: {:?}", error),
+            None => "stream closed or went stale".to_string(),
+        };
+
+        // Resubscribe with the same filters, replaying from the last slot seen
+        logger.log(format!("[STREAM DOWN] => {}, resubscribing", reason).red().to_string());
+        let request = SubscribeRequest { from_slot: cursor.resume_from(), ..subscribe_request.clone() };
+        let (resumed_tx, resumed_stream) = loop {
+            match connect_and_subscribe(
+                &yellowstone_grpc_http,
+                &yellowstone_grpc_token,
+                request.clone(),
+                &app_state,
+                &logger,
+            ).await {
+                Ok(pair) => break pair,
+                Err(e) => {
+                    let delay = backoff.next_delay();
+                    logger.log(format!("[RECONNECT] => {}. Retrying in {:?}", e, delay).red().to_string());
+                    app_state.clock.sleep(delay).await;
+                }
+            }
+        };
+        *subscribe_tx.lock().await = resumed_tx;
+        stream = resumed_stream;
+        app_state.monitor.note_message(app_state.clock.now());
+        stale_signal.clear();
+        logger.log(format!("[STREAM RESUMED] => Resubscribed from slot {:?}", request.from_slot).green().to_string());
+    }
+}
+
+/// Re-check positions whose originating slot died and correct local state
+async fn reconcile_reorged_positions(
+    slot: u64,
+    positions: Vec<TrackedPosition>,
+    app_state: Arc<AppState>,
+    existing_liquidity_pools: Arc<TrackedPools>,
+    notifier: Notifier,
+    logger: Logger,
+) {
+    for position in positions {
+        let record_dir = std::path::Path::new(crate::common::config::RECORD_BASE_DIR);
+        let Some(check) = reorg::reverify_after_dead_slot(&app_state, &notifier, record_dir, slot, &position).await else {
+            continue;
+        };
+
+        match check {
+            PositionCheck::Landed { balance } => {
+                logger.log(format!(
+                    "[REORG] => Slot {} is dead but position {} is on-chain (balance: {})",
+                    slot, position.mint, balance
+                ).yellow().to_string());
+            }
+            PositionCheck::Pending => {
+                // Not provably gone, so it stays held until the exits or a liquidation settle it
+                logger.log(format!(
+                    "[REORG] => Slot {} is dead and {} ({}) has no status yet, keeping the position",
+                    slot, position.mint, position.signature
+                ).yellow().to_string());
+            }
+            PositionCheck::Missing | PositionCheck::Failed(_) => {
+                // The buy never happened on the canonical fork, stop treating it as held
+                app_state.capital.release(Strategy::Copy, &position.mint);
+                app_state.refresh_scheduler.set_open_position(&position.mint, false);
+                close_position_state(&app_state, &position.mint, &logger);
+                events::publish(BotEvent::PositionClosed { mint: position.mint.clone(), exit_price: None });
+                {
+                    existing_liquidity_pools.remove(&position.mint);
+                    let all_sold = !existing_liquidity_pools.iter().any(|pool| pool.status == Status::Bought);
+                    if all_sold {
+                        app_state.monitor.set_buying_enabled(true);
+                    }
+                }
+                logger.log(format!(
+                    "[REORG] => Slot {} is dead, position {} not found on-chain ({:?}), removed",
+                    slot, position.mint, check
+                ).red().bold().to_string());
             }
         }
     }
-    Ok(())
 }
 
 pub async fn copy_trader_pumpfun(
     yellowstone_grpc_http: String,
     yellowstone_grpc_token: String,
-    app_state: AppState,
+    app_state: Arc<AppState>,
     swap_config: SwapConfig,
     time_exceed: u64,
     counter_limit: u64,
@@ -1403,49 +1767,8 @@ pub async fn copy_trader_pumpfun(
     // Log the copy trading configuration
     let logger = Logger::new("[COPY-TRADER] => ".blue().bold().to_string());
     
-    // INITIAL SETTING FOR SUBSCRIBE
-    // -----------------------------------------------------------------------------------------------------------------------------
-    let mut client = GeyserGrpcClient::build_from_shared(yellowstone_grpc_http.clone())
-        .map_err(|e| format!("Failed to build client: {}", e))?
-        .x_token::<String>(Some(yellowstone_grpc_token.clone()))
-        .map_err(|e| format!("Failed to set x_token: {}", e))?
-        .tls_config(ClientTlsConfig::new().with_native_roots())
-        .map_err(|e| format!("Failed to set tls config: {}", e))?
-        .connect()
-        .await
-        .map_err(|e| format!("Failed to connect: {}", e))?;
-
-    // Create additional clones for later use in tasks
-    let yellowstone_grpc_http = Arc::new(yellowstone_grpc_http);
-    let yellowstone_grpc_token = Arc::new(yellowstone_grpc_token);
-    let app_state = Arc::new(app_state);
     let swap_config = Arc::new(swap_config);
 
-    // Log the copy trading configuration
-    let logger = Logger::new("[COPY-TRADER] => ".blue().bold().to_string());
-
-    let mut retry_count = 0;
-    const MAX_RETRIES: u32 = 3;
-    let (subscribe_tx, mut stream) = loop {
-        match client.subscribe().await {
-            Ok(pair) => break pair,
-            Err(e) => {
-                retry_count += 1;
-                if retry_count >= MAX_RETRIES {
-                    return Err(format!("Failed to subscribe after {} attempts: {}", MAX_RETRIES, e));
-                }
-                logger.log(format!(
-                    "[CONNECTION ERROR] => Failed to subscribe (attempt {}/{}): {}. Retrying in 5 seconds...",
-                    retry_count, MAX_RETRIES, e
-                ).red().to_string());
-                time::sleep(Duration::from_secs(5)).await;
-            }
-        }
-    };
-
-    // Convert to Arc to allow cloning across tasks
-    let subscribe_tx = Arc::new(tokio::sync::Mutex::new(subscribe_tx));
-
     // Get copy trading configuration from environment
     let copy_trading_target_address = std::env::var("COPY_TRADING_TARGET_ADDRESS").ok();
     let is_multi_copy_trading = std::env::var("IS_MULTI_COPY_TRADING")
@@ -1454,40 +1777,15 @@ pub async fn copy_trader_pumpfun(
         .unwrap_or(false);
     
     // Prepare target addresses for monitoring
-    let mut program_ids = vec![];
-    let mut copy_trading_target_addresses: Vec<String> = Vec::new();
-    
-    // Handle multiple copy trading targets if enabled
-    if is_multi_copy_trading {
-        if let Some(address_str) = copy_trading_target_address {
-            // Parse comma-separated addresses
-            for addr in address_str.split(',') {
-                let trimmed_addr = addr.trim();
-                if !trimmed_addr.is_empty() {
-                    program_ids.push(trimmed_addr.to_string());
-                    copy_trading_target_addresses.push(trimmed_addr.to_string());
-                }
-            }
-        }
-    } else if let Some(address) = copy_trading_target_address {
-        // Single address mode
-        if !address.is_empty() {
-            program_ids.push(address.clone());
-            copy_trading_target_addresses.push(address);
-        }
-    }
+    let copy_trading_target_addresses = copy_trading_targets(copy_trading_target_address, is_multi_copy_trading);
+    let program_ids = copy_trading_target_addresses.clone();
 
     // Ensure we have at least one target address
     if copy_trading_target_addresses.is_empty() {
         return Err("No COPY_TRADING_TARGET_ADDRESS specified. Please set this environment variable.".to_string());
     }
 
-    let filter_config = FilterConfig {
-        program_ids: program_ids.clone(),
-        dex_program_ids: vec![],
-        arbitrage_threshold_pct: 0.0,
-        min_liquidity: 0,
-    };
+    let filter_config = FilterConfig::copy_trading(program_ids, copy_trading_target_addresses);
 
     // Log the copy trading configuration starts here
     logger.log(format!(
@@ -1502,39 +1800,58 @@ pub async fn copy_trader_pumpfun(
         ).green().to_string());
     }
 
-    subscribe_tx
-        .lock()
-        .await
-        .send(SubscribeRequest {
-            slots: HashMap::new(),
-            accounts: HashMap::new(),
-            transactions: hashmap! {
-                "All".to_owned() => SubscribeRequestFilterTransactions {
-                    vote: None,
-                    failed: Some(false),
-                    signature: None,
-                    account_include: vec![
-                        PUMP_PROGRAM.to_string(),                      // PumpFun
-                        "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA".to_string(), // PumpSwap
-                        "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(), // Raydium
-                    ],
-                    account_exclude: vec![JUPITER_PROGRAM.to_string(), OKX_DEX_PROGRAM.to_string()],
-                    account_required: Vec::<String>::new()
-                }
-            },
-            transactions_status: HashMap::new(),
-            entry: HashMap::new(),
-            blocks: HashMap::new(),
-            blocks_meta: HashMap::new(),
-            commitment: Some(CommitmentLevel::Processed as i32),
-            accounts_data_slice: vec![],
-            ping: None,
-            from_slot: None,
-        })
-        .await
-        .map_err(|e| format!("Failed to send subscribe request: {}", e))?;
+    let subscribe_request = SubscribeRequest {
+        slots: hashmap! {
+            "slots".to_owned() => reorg::slot_status_filter()
+        },
+        accounts: HashMap::new(),
+        transactions: hashmap! {
+            "All".to_owned() => SubscribeRequestFilterTransactions {
+                vote: None,
+                failed: Some(false),
+                signature: None,
+                account_include: vec![
+                    PUMP_PROGRAM.to_string(),                      // PumpFun
+                    "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA".to_string(), // PumpSwap
+                    "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(), // Raydium
+                ],
+                account_exclude: vec![JUPITER_PROGRAM.to_string(), OKX_DEX_PROGRAM.to_string()],
+                account_required: Vec::<String>::new()
+            }
+        },
+        transactions_status: HashMap::new(),
+        entry: HashMap::new(),
+        blocks: HashMap::new(),
+        blocks_meta: HashMap::new(),
+        commitment: Some(CommitmentLevel::Processed as i32),
+        accounts_data_slice: vec![],
+        ping: None,
+        from_slot: None,
+    };
+
+    // While the stream is down, targets can still be watched over RPC with a delay
+    let fallback_config = rpc_fallback::FallbackConfig::from_env();
+    let target_pubkeys: Vec<Pubkey> = filter_config.copy_trading_target_addresses
+        .iter()
+        .filter_map(|addr| Pubkey::from_str(addr).ok())
+        .collect();
+    let mut newest_seen = HashMap::new();
+    let mut backoff = ReconnectBackoff::from_env();
+    let (subscribe_tx, mut stream) = rpc_fallback::connect_with_fallback(
+        &app_state,
+        fallback_config,
+        &mut backoff,
+        &mut rpc_fallback::CopyTargetPoller { targets: &target_pubkeys, newest_seen: &mut newest_seen },
+        "CONNECT",
+        &logger,
+        || connect_and_subscribe(&yellowstone_grpc_http, &yellowstone_grpc_token, subscribe_request.clone(), &app_state, &logger),
+    ).await;
+    let subscribe_tx = Arc::new(tokio::sync::Mutex::new(subscribe_tx));
 
-    let existing_liquidity_pools = Arc::new(Mutex::new(HashSet::<LiquidityPool>::new()));
+
+    let existing_liquidity_pools = Arc::new(TrackedPools::new());
+    let slot_tracker = Arc::new(Mutex::new(SlotTracker::new()));
+    let notifier = app_state.notifier.clone();
 
     let rpc_nonblocking_client = app_state.clone().rpc_nonblocking_client.clone();
     let rpc_client = app_state.clone().rpc_client.clone();
@@ -1548,10 +1865,7 @@ pub async fn copy_trader_pumpfun(
     logger.log("[STARTED. MONITORING COPY TARGETS]...".blue().bold().to_string());
     
     // Set buying enabled to true at start
-    {
-        let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-        *buying_enabled = true;
-    }
+    app_state.monitor.set_buying_enabled(true);
 
     // After all setup and before the main loop, add a heartbeat ping task
     let subscribe_tx_clone = subscribe_tx.clone();
@@ -1564,9 +1878,9 @@ pub async fn copy_trader_pumpfun(
         loop {
             interval.tick().await;
             
+            // A failed ping is left to the stream loop, which resubscribes into the same sink slot
             if let Err(e) = send_heartbeat_ping(&subscribe_tx_clone, &ping_logger).await {
                 ping_logger.log(format!("[CONNECTION ERROR] => {}", e).red().to_string());
-                break;
             }
         }
     });
@@ -1575,34 +1889,28 @@ pub async fn copy_trader_pumpfun(
     let existing_liquidity_pools_clone = Arc::clone(&existing_liquidity_pools);
     let logger_clone = logger.clone();
     let app_state_for_background = Arc::clone(&app_state);
-    let swap_config_for_background = Arc::clone(&swap_config);
+    let exit_ladder = Arc::new(load_exit_ladder(swap_config.slippage_bps, &logger));
+    let active_ladders = Arc::new(ActiveLadders::default());
     
     tokio::spawn(async move {
         let pools_clone = Arc::clone(&existing_liquidity_pools_clone);
         let check_logger = logger_clone.clone();
         let app_state_clone = Arc::clone(&app_state_for_background);
-        let swap_config_clone = Arc::clone(&swap_config_for_background);
         
         loop {
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            app_state_clone.clock.sleep(Duration::from_secs(5)).await;
             
             // Check if there are any bought tokens and if any have exceeded MAX_WAIT_TIME
-            let now = Instant::now();
-            let max_wait_time_millis = *MAX_WAIT_TIME.lock().unwrap();
-            let max_wait_duration = Duration::from_millis(max_wait_time_millis);
+            let now = app_state_clone.clock.now();
+            let max_wait_duration = exit_ladder.start_after();
             
             let (has_bought_tokens, tokens_to_sell) = {
-                let pools = pools_clone.lock().unwrap();
-                let bought_tokens: Vec<String> = pools.iter()
+                let bought_tokens: Vec<String> = pools_clone.iter()
                     .filter(|pool| pool.status == Status::Bought)
                     .map(|pool| pool.mint.clone())
                     .collect();
                 
-                let timed_out_tokens: Vec<(String, Instant)> = pools.iter()
-                    .filter(|pool| pool.status == Status::Bought && 
-                           pool.timestamp.map_or(false, |ts| now.duration_since(ts) > max_wait_duration))
-                    .map(|pool| (pool.mint.clone(), pool.timestamp.unwrap()))
-                    .collect();
+                let timed_out_tokens = timed_out_positions(&pools_clone, now, max_wait_duration);
                 
                 // Log bought tokens that are waiting to be sold
                 if !bought_tokens.is_empty() {
@@ -1615,8 +1923,8 @@ pub async fn copy_trader_pumpfun(
                 // Log tokens that have timed out and will be force-sold
                 if !timed_out_tokens.is_empty() {
                     check_logger.log(format!(
-                        "\n\t * [TIMEOUT DETECTED] => Will force-sell tokens that exceeded {} ms wait time: {:?}",
-                        max_wait_time_millis,
+                        "\n\t * [TIMEOUT DETECTED] => Will exit tokens that exceeded {:?} wait time: {:?}",
+                        max_wait_duration,
                         timed_out_tokens.iter().map(|(mint, _)| mint).collect::<Vec<_>>()
                     ).red().bold().to_string());
                 }
@@ -1625,132 +1933,27 @@ pub async fn copy_trader_pumpfun(
             };
             
             // Update buying status
-            {
-                let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                *buying_enabled = !has_bought_tokens;
-                
-            }
+            app_state_clone.monitor.set_buying_enabled(!has_bought_tokens);
             
-            // Force-sell tokens that have exceeded MAX_WAIT_TIME
+            // Walk timed-out tokens down the exit ladder, one ladder per token
             for (mint, timestamp) in tokens_to_sell {
-                // Clone the necessary state for this token
-                let logger_for_selling = check_logger.clone();
-                let pools_clone_for_selling = Arc::clone(&pools_clone);
-                let app_state_for_selling = app_state_clone.clone();
-                let swap_config_for_selling = swap_config_clone.clone();
-                
+                if !active_ladders.start(&mint) {
+                    continue;
+                }
                 check_logger.log(format!(
-                    "\n\t * [FORCE SELLING] => Token {} exceeded wait time (elapsed: {:?})",
+                    "\n\t * [EXIT LADDER] => Token {} exceeded wait time (elapsed: {:?})",
                     mint, now.duration_since(timestamp)
                 ).red().to_string());
-                
-                tokio::spawn(async move {
-                    // Get the existing pool for this mint
-                    let existing_pool = {
-                        let pools = pools_clone_for_selling.lock().unwrap();
-                        pools.iter()
-                            .find(|pool| pool.mint == mint)
-                            .cloned()
-                            .unwrap_or(LiquidityPool {
-                                mint: mint.clone(),
-                                buy_price: 0_f64,
-                                sell_price: 0_f64,
-                                status: Status::Bought,
-                                timestamp: Some(timestamp),
-                            })
-                    };
-                    
-                    // Set up sell config
-                    let sell_config = SwapConfig {
-                        swap_direction: SwapDirection::Sell,
-                        in_type: SwapInType::Pct,
-                        amount_in: 1_f64,  // Sell 100%
-                        slippage: 100_u64, // Use full slippage
-                        use_jito: swap_config_for_selling.clone().use_jito,
-                    };
-                    
-                    // Create Pump instance for selling
-                    let app_state_for_task = app_state_for_selling.clone();
-                    let rpc_nonblocking_client = app_state_for_task.rpc_nonblocking_client.clone();
-                    let rpc_client = app_state_for_task.rpc_client.clone();
-                    let wallet = app_state_for_task.wallet.clone();
-                    let swapx = Pump::new(rpc_nonblocking_client.clone(), rpc_client.clone(), wallet.clone());
-                    
-                    // Execute the sell operation
-                    let start_time = Instant::now();
-                    match swapx.build_swap_ixn_by_mint(&mint, None, sell_config, start_time).await {
-                        Ok(result) => {
-                            // Send instructions and confirm
-                            let (keypair, instructions, token_price) = (result.0, result.1, result.2);
-                            let recent_blockhash = match rpc_nonblocking_client.get_latest_blockhash().await {
-                                Ok(hash) => hash,
-                                Err(e) => {
-                                    logger_for_selling.log(format!(
-                                        "Error getting blockhash for force-selling {}: {}", mint, e
-                                    ).red().to_string());
-                                    return;
-                                }
-                            };
-                            
-                            match tx::new_signed_and_send_zeroslot(
-                                recent_blockhash,
-                                &keypair,
-                                instructions,
-                                &logger_for_selling,
-                            ).await {
-                                Ok(res) => {
-                                    let sold_pool = LiquidityPool {
-                                        mint: mint.clone(),
-                                        buy_price: existing_pool.buy_price,
-                                        sell_price: token_price,
-                                        status: Status::Sold,
-                                        timestamp: Some(Instant::now()),
-                                    };
-                                    
-                                    // Update pool status to sold
-                                    {
-                                        let mut pools = pools_clone_for_selling.lock().unwrap();
-                                        pools.retain(|pool| pool.mint != mint);
-                                        pools.insert(sold_pool.clone());
-                                    }
-                                    
-                                    logger_for_selling.log(format!(
-                                        "\n\t * [SUCCESSFUL FORCE-SELL] => TX_HASH: (https://solscan.io/tx/{}) \n\t * [POOL] => ({}) \n\t * [SOLD] => {} :: ({:?}).",
-                                        &res[0], mint, Utc::now(), start_time.elapsed()
-                                    ).green().to_string());
-                                    
-                                    // Check if all tokens are sold
-                                    let all_sold = {
-                                        let pools = pools_clone_for_selling.lock().unwrap();
-                                        !pools.iter().any(|pool| pool.status == Status::Bought)
-                                    };
-                                    
-                                    if all_sold {
-                                        // If all tokens are sold, enable buying
-                                        let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                                        *buying_enabled = true;
-                                        
-                                        logger_for_selling.log(
-                                            "\n\t * [BUYING ENABLED] => All tokens sold, can buy new tokens now"
-                                            .green()
-                                            .to_string(),
-                                        );
-                                    }
-                                },
-                                Err(e) => {
-                                    logger_for_selling.log(format!(
-                                        "Force-sell failed for {}: {}", mint, e
-                                    ).red().to_string());
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            logger_for_selling.log(format!(
-                                "Error building swap instruction for force-selling {}: {}", mint, e
-                            ).red().to_string());
-                        }
-                    }
-                });
+                spawn_timeout_exit(
+                    Arc::clone(&app_state_clone),
+                    Arc::clone(&pools_clone),
+                    Arc::clone(&exit_ladder),
+                    Arc::clone(&active_ladders),
+                    mint,
+                    timestamp,
+                    Some(Strategy::Copy),
+                    check_logger.clone(),
+                );
             }
         }
     });
@@ -1769,13 +1972,16 @@ pub async fn copy_trader_pumpfun(
     // In copy_trader_pumpfun after the health check task:
     // Add a connection watchdog task
     let logger_watchdog = logger.clone();
+    let watchdog_app_state = Arc::clone(&app_state);
+    let stale_signal = Arc::new(StaleSignal::default());
+    let watchdog_signal = Arc::clone(&stale_signal);
     tokio::spawn(async move {
         let watchdog_logger = logger_watchdog;
-        let mut interval = time::interval(Duration::from_secs(120)); // Check every 2 minutes
+        let mut interval = time::interval(stream_reconnect::watchdog_interval(watchdog_app_state.monitor.stale_after()));
         
         loop {
             interval.tick().await;
-            check_connection_health(&watchdog_logger).await;
+            check_connection_health(&watchdog_app_state, &watchdog_signal, &watchdog_logger).await;
         }
     });
 
@@ -1784,7 +1990,7 @@ pub async fn copy_trader_pumpfun(
     let price_monitoring_pools_clone = Arc::clone(&existing_liquidity_pools);
     let price_monitoring_logger_clone = logger.clone();
     let price_monitoring_app_state_clone = Arc::clone(&app_state);
-    let price_monitoring_token_tracking = Arc::clone(&TOKEN_TRACKING);
+    let price_monitoring_token_tracking = Arc::clone(&app_state.monitor.token_tracking);
 
     tokio::spawn(async move {
         let pools_clone = Arc::clone(&price_monitoring_pools_clone);
@@ -1800,10 +2006,9 @@ pub async fn copy_trader_pumpfun(
             
             // Get current pools to check
             let tokens_to_check = {
-                let pools = pools_clone.lock().unwrap();
-                pools.iter()
+                pools_clone.iter()
                     .filter(|pool| pool.status == Status::Bought)
-                    .map(|pool| pool.clone())
+                    .map(|pool| pool.value().clone())
                     .collect::<Vec<LiquidityPool>>()
             };
             
@@ -1820,8 +2025,9 @@ pub async fn copy_trader_pumpfun(
             for pool in tokens_to_check {
                 let mint = pool.mint.clone();
                 let buy_price = pool.buy_price;
-                let bought_time = pool.timestamp.unwrap_or(Instant::now());
-                let time_elapsed = Instant::now().duration_since(bought_time);
+                let now = app_state_clone.clock.now();
+                let bought_time = pool.timestamp.map_or(now, |ts| ts.instant);
+                let time_elapsed = now.duration_since(bought_time);
                 
                 // Clone necessary variables
                 let logger_for_price = monitor_logger.clone();
@@ -1833,6 +2039,7 @@ pub async fn copy_trader_pumpfun(
                 let rpc_client = app_state_for_price.rpc_client.clone();
                 let wallet = app_state_for_price.wallet.clone();
                 let swapx = Pump::new(rpc_nonblocking_client.clone(), rpc_client.clone(), wallet.clone());
+                let clock = app_state_for_price.clock.clone();
                 
                 // Execute as a separate task to avoid blocking price check loop
                 tokio::spawn(async move {
@@ -1848,6 +2055,8 @@ pub async fn copy_trader_pumpfun(
                         }
                     };
                     
+                    events::publish(BotEvent::PositionPrice { mint: mint.clone(), price: current_price });
+                    
                     // Calculate PNL for informational purposes
                     let pnl = if buy_price > 0.0 {
                         ((current_price - buy_price) / buy_price) * 100.0
@@ -1857,21 +2066,19 @@ pub async fn copy_trader_pumpfun(
                     
                     // Get or create token tracking info
                     let mut tracking_info = {
-                        let mut tracking = token_tracking_clone.lock().unwrap();
-                        tracking.entry(mint.clone()).or_insert_with(|| TokenTrackingInfo {
+                        token_tracking_clone.entry(mint.clone()).or_insert_with(|| TokenTrackingInfo {
                             top_pnl: pnl,
-                            last_price_check: Instant::now(),
+                            last_price_check: Timestamps::now(clock.as_ref()),
                             price_history: Vec::new(),
-                        }).clone()
+                        }).value().clone()
                     };
                     
                     // Update top PNL if current PNL is higher (for informational purposes)
                     if pnl > tracking_info.top_pnl {
-                        let mut tracking = token_tracking_clone.lock().unwrap();
-                        if let Some(info) = tracking.get_mut(&mint) {
+                        if let Some(mut info) = token_tracking_clone.get_mut(&mint) {
                             info.top_pnl = pnl;
                             // Add price to history
-                            info.price_history.push((current_price, Instant::now()));
+                            info.price_history.push((current_price, Timestamps::now(clock.as_ref())));
                             // Keep only the last 100 price points
                             if info.price_history.len() > 100 {
                                 info.price_history.remove(0);
@@ -1893,11 +2100,10 @@ pub async fn copy_trader_pumpfun(
                     
                     // Update last price check time
                     {
-                        let mut tracking = token_tracking_clone.lock().unwrap();
-                        if let Some(info) = tracking.get_mut(&mint) {
-                            info.last_price_check = Instant::now();
+                        if let Some(mut info) = token_tracking_clone.get_mut(&mint) {
+                            info.last_price_check = Timestamps::now(clock.as_ref());
                             // Add price to history
-                            info.price_history.push((current_price, Instant::now()));
+                            info.price_history.push((current_price, Timestamps::now(clock.as_ref())));
                             // Keep only the last 100 price points
                             if info.price_history.len() > 100 {
                                 info.price_history.remove(0);
@@ -1907,12 +2113,11 @@ pub async fn copy_trader_pumpfun(
                     
                     // Calculate price change rate over the last few data points
                     let price_change_rate = {
-                        let tracking = token_tracking_clone.lock().unwrap();
-                        if let Some(info) = tracking.get(&mint) {
+                        if let Some(info) = token_tracking_clone.get(&mint) {
                             if info.price_history.len() >= 2 {
                                 let newest = &info.price_history[info.price_history.len() - 1];
                                 let oldest = &info.price_history[0];
-                                let time_diff = newest.1.duration_since(oldest.1).as_secs_f64();
+                                let time_diff = oldest.1.until(&newest.1).as_secs_f64();
                                 if time_diff > 0.0 {
                                     (newest.0 - oldest.0) / time_diff
                                 } else {
@@ -1938,17 +2143,52 @@ pub async fn copy_trader_pumpfun(
         }
     });
 
-    while let Some(message) = stream.next().await {
-        match message {
-            Ok(msg) => {
+    backoff.reset();
+    let mut cursor = StreamCursor::default();
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            _ = stale_signal.stale() => None,
+        };
+        let reason = match message {
+            Some(Ok(msg)) => {
+                backoff.reset();
                 // Process ping/pong messages
-                if let Err(e) = process_stream_message(&msg, &subscribe_tx, &logger).await {
+                if let Err(e) = process_stream_message(&msg, &subscribe_tx, &app_state, &logger).await {
                     logger.log(format!("Error handling stream message: {}", e).red().to_string());
                     continue;
                 }
                 
+                // Roll back positions opened on the strength of a dead slot
+                if let Some(UpdateOneof::Slot(slot_update)) = &msg.update_oneof {
+                    cursor.note_slot(slot_update.slot);
+                    app_state.blockhashes.note_slot(slot_update.slot);
+                    if reorg::is_dead_slot(slot_update) {
+                        let (_, positions) = slot_tracker.lock_or_recover().take_slot(slot_update.slot);
+                        if !positions.is_empty() {
+                            tokio::spawn(reconcile_reorged_positions(
+                                slot_update.slot,
+                                positions,
+                                Arc::clone(&app_state),
+                                Arc::clone(&existing_liquidity_pools),
+                                notifier.clone(),
+                                logger.clone(),
+                            ));
+                        }
+                    } else if reorg::is_finalized_slot(slot_update) {
+                        slot_tracker.lock_or_recover().prune_through(slot_update.slot);
+                        note_last_slot(&app_state, slot_update.slot, &logger);
+                    }
+                    continue;
+                }
+                
                 // Process transaction messages
                 if let Some(UpdateOneof::Transaction(txn)) = msg.update_oneof {
+                    // A resumed stream replays the slot it stopped in
+                    if !cursor.first_sighting(&txn) {
+                        continue;
+                    }
+                    app_state.blockhashes.observe_transaction(&txn);
                     let start_time = Instant::now();
                     if let Some(log_messages) = txn
                         .clone()
@@ -1957,7 +2197,8 @@ pub async fn copy_trader_pumpfun(
                         .map(|meta| meta.log_messages)
                     {
                         // Process transaction to extract trade information
-                        let trade_info = match TradeInfoFromToken::from_json(txn.clone(), log_messages.clone()) {
+                        let latency = LatencyTracer::start("copy", Stage::Received, start_time, &app_state.rng);
+                        let trade_info = match TradeInfoFromToken::from_json(txn.clone(), log_messages.clone(), latency) {
                             Ok(info) => info,
                             Err(e) => {
                                 logger.log(
@@ -1976,17 +2217,18 @@ pub async fn copy_trader_pumpfun(
                         
                         if !is_copy_trading_tx {
                             // Skip transactions not from our copy targets
+                            trade_info.latency.finish_untraded(&trade_info.signature, &logger);
                             continue;
                         }
 
                         // Process the buy transaction from target addresses only
                         logger.log(format!(
-                            "\n\t * [COPY TARGET ACTION] => (https://solscan.io/tx/{}) - SLOT:({}) \n\t * [TARGET] => ({}) \n\t * [TOKEN] => ({}) \n\t * [BUY AMOUNT] => ({}) SOL \n\t * [TIMESTAMP] => {} :: ({:?}).",
+                            "\n\t * [COPY TARGET ACTION] => (https://solscan.io/tx/{}) - SLOT:({}) \n\t * [TARGET] => ({}) \n\t * [TOKEN] => ({}) \n\t * [BUY AMOUNT] => ({}) \n\t * [TIMESTAMP] => {} :: ({:?}).",
                             trade_info.signature,
                             trade_info.slot,
                             trade_info.target,
                             trade_info.mint,
-                            lamports_to_sol(trade_info.volume_change.abs() as u64),
+                            fmt_sol(trade_info.volume_change.unsigned_abs()),
                             Utc::now(),
                             start_time.elapsed(),
                         ).blue().to_string());
@@ -1999,23 +2241,20 @@ pub async fn copy_trader_pumpfun(
                         if buy_amount > max_dev_buy as f64 {
                             logger.log(format!(
                                 "\n\t * [BUY AMOUNT EXCEEDS MAX] => {} > {}",
-                                buy_amount, max_dev_buy
+                                fmt_sol(trade_info.volume_change.unsigned_abs()), fmt_sol(sol_to_lamports(max_dev_buy as f64))
                             ).yellow().to_string());
                             continue;
                         }
                         if buy_amount < min_dev_buy as f64 {
                             logger.log(format!(
                                 "\n\t * [BUY AMOUNT BELOW MIN] => {} < {}",
-                                buy_amount, min_dev_buy
+                                fmt_sol(trade_info.volume_change.unsigned_abs()), fmt_sol(sol_to_lamports(min_dev_buy as f64))
                             ).yellow().to_string());
                             continue;
                         }
 
                         // Check if this token is already in our pools
-                        let is_duplicate = {
-                            let pools = existing_liquidity_pools.lock().unwrap();
-                            pools.iter().any(|pool| pool.mint == trade_info.mint)
-                        };
+                        let is_duplicate = existing_liquidity_pools.contains_key(&trade_info.mint);
                         
                         if is_duplicate {
                             logger.log(format!(
@@ -2025,11 +2264,8 @@ pub async fn copy_trader_pumpfun(
                             continue;
                         }
 
-                        // Check if buying is enabled
-                        let buying_enabled = {
-                            let enabled = BUYING_ENABLED.lock().unwrap();
-                            *enabled
-                        };
+                        // Check if buying is enabled, a panic liquidation disables it for good
+                        let buying_enabled = crate::engine::liquidation::buys_allowed(&app_state);
                         
                         if !buying_enabled {
                             logger.log(format!(
@@ -2037,12 +2273,33 @@ pub async fn copy_trader_pumpfun(
                             ).yellow().to_string());
                             continue;
                         }
+                        if app_state.monitor.entries_paused() {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Entries paused by operator: {}", trade_info.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+                        if fill_quality::is_paused(Strategy::Copy) {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Entries paused on fill quality: {}", trade_info.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+                        if rpc_fallback::copy_paused() {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Running on RPC polling, copy latency no longer holds: {}", trade_info.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
+                        if !crate::engine::leader::is_leader() {
+                            logger.log(format!(
+                                "\n\t * [SKIPPING BUY] => Follower region, observing only: {}", trade_info.mint
+                            ).yellow().to_string());
+                            continue;
+                        }
 
                         // Temporarily disable buying while we're processing this buy
-                        {
-                            let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                            *buying_enabled = false;
-                        }
+                        app_state.monitor.set_buying_enabled(false);
 
                         // Clone the shared variables for this task
                         let swapx_clone = swapx.clone();
@@ -2054,6 +2311,8 @@ pub async fn copy_trader_pumpfun(
                         let bonding_curve_info = trade_info.bonding_curve_info.clone();
                         let existing_liquidity_pools_clone = Arc::clone(&existing_liquidity_pools);
                         let recent_blockhash = trade_info.clone().recent_blockhash;
+                        let trade_slot = trade_info.slot;
+                        let slot_tracker_clone = Arc::clone(&slot_tracker);
 
                         // Determine trading amount based on comparing SOL amount and TOKEN_AMOUNT
                         let sol_amount = lamports_to_sol(trade_info.volume_change.abs() as u64);
@@ -2069,11 +2328,51 @@ pub async fn copy_trader_pumpfun(
                             ).green().to_string());
                         }
 
+                        // Draw the buy from the copy strategy's share of the wallet
+                        let requested = sol_to_lamports(swap_config_clone.amount_in);
+                        match app_state.capital.grant(Strategy::Copy, &mint_str, requested) {
+                            Ok(granted) => {
+                                if granted < requested {
+                                    swap_config_clone.amount_in = lamports_to_sol(granted);
+                                    logger.log(format!(
+                                        "\n\t * [DOWNSIZED] => Copy allocation allows {} of {}",
+                                        fmt_sol(granted), fmt_sol(requested)
+                                    ).yellow().to_string());
+                                }
+                            }
+                            Err(e) => {
+                                logger.log(format!("\n\t * [SKIPPING BUY] => {}", e).yellow().to_string());
+                                app_state.monitor.set_buying_enabled(true);
+                                continue;
+                            }
+                        }
+                        let capital = Arc::clone(&app_state.capital);
+                        let monitor = Arc::clone(&app_state.monitor);
+
+                        // A router we have no adapter for gets its copy simulated before it is sent
+                        let unknown_programs = copy_validation::unknown_programs(&app_state.dex_registry, &txn);
+                        for program_id in &unknown_programs {
+                            if let Err(e) = app_state.store.note_unknown_program(&program_id.to_string(), Utc::now().timestamp_millis()) {
+                                logger.log(format!("[RECORD] => Failed to store unknown program: {}", e).red().to_string());
+                            }
+                        }
+                        let target_delta = SwapDelta {
+                            tokens: copy_validation::observed_token_delta(&txn, &trade_info.target, &trade_info.mint).unwrap_or(0),
+                            lamports: trade_info.volume_change.unsigned_abs(),
+                        };
+
                         logger.log(format!(
                             "\n\t * [COPYING BUY] => Token: {}, Amount: {}",
                             mint_str, swap_config_clone.amount_in
                         ).green().to_string());
 
+                        let clock = app_state.clock.clone();
+                        let store = Arc::clone(&app_state.store);
+                        let refresh_scheduler = Arc::clone(&app_state.refresh_scheduler);
+                        let traded_pool = trade_info.pool_info.as_ref().map(|pool| pool.pool_id.to_string());
+                        let copy_target = trade_info.target.clone();
+                        let mut latency = trade_info.latency.clone();
+                        latency.mark(Stage::Decided);
                         let task = tokio::spawn(async move {
                             match swapx_clone
                                 .build_swap_ixn_by_mint(
@@ -2085,49 +2384,177 @@ pub async fn copy_trader_pumpfun(
                                 .await
                             {
                                 Ok(result) => {
+                                    latency.mark(Stage::Built);
                                     let (keypair, instructions, token_price) =
                                         (result.0, result.1, result.2);
+                                    math_verifier::offer(MathSample {
+                                        venue: "pumpswap".to_string(),
+                                        mint: mint_str.clone(),
+                                        amount_in: sol_to_lamports(swap_config_clone.amount_in),
+                                    });
+                                    
+                                    if !unknown_programs.is_empty() {
+                                        let validation = match Pubkey::from_str(&mint_str) {
+                                            Ok(mint) => copy_validation::validate_copy(
+                                                &app_state_clone,
+                                                ValidationConfig::from_env(),
+                                                target_delta,
+                                                &keypair,
+                                                &instructions,
+                                                &mint,
+                                                sol_to_lamports(swap_config_clone.amount_in),
+                                                recent_blockhash,
+                                            ).await,
+                                            Err(_) => Err(CopySkip::SimulationFailed),
+                                        };
+                                        match validation {
+                                            Ok(deviation) => logger_clone.log(format!(
+                                                "\n\t * [VALIDATED] => {} via unknown program(s) {:?}, simulated fill within {}",
+                                                mint_str, unknown_programs, fmt_pct(deviation * 100.0)
+                                            ).green().to_string()),
+                                            Err(skip) => {
+                                                logger_clone.log(format!(
+                                                    "\n\t * [SKIPPING BUY] => {} via unknown program(s) {:?}, reason: {}",
+                                                    mint_str, unknown_programs, skip.code()
+                                                ).yellow().to_string());
+                                                for program_id in &unknown_programs {
+                                                    if let Err(e) = store.note_unknown_program_skip(&program_id.to_string()) {
+                                                        logger_clone.log(format!("[RECORD] => Failed to store unknown program skip: {}", e).red().to_string());
+                                                    }
+                                                }
+                                                capital.release(Strategy::Copy, &mint_str);
+                                                monitor.set_buying_enabled(true);
+                                                return;
+                                            }
+                                        }
+                                    }
                                     
-                                    match tx::new_signed_and_send_zeroslot(
+                                    if SimulationConfig::from_env().enabled {
+                                        if let Err(e) = copy_validation::simulate_and_check(
+                                            &app_state_clone,
+                                            &keypair,
+                                            &instructions,
+                                            recent_blockhash,
+                                            &logger_clone,
+                                        ).await {
+                                            logger_clone.log(format!(
+                                                "\n\t * [SKIPPING BUY] => {}, {}", mint_str, e
+                                            ).yellow().to_string());
+                                            capital.release(Strategy::Copy, &mint_str);
+                                            monitor.set_buying_enabled(true);
+                                            return;
+                                        }
+                                    }
+                                    
+                                    match app_state_clone.tx_sender.send(
                                         recent_blockhash,
                                         &keypair,
                                         instructions,
+                                        swap_config_clone.priority_fee,
                                         &logger_clone,
                                     ).await {
                                         Ok(res) => {
+                                            latency.mark(Stage::Submitted);
+                                            if !buy_landed(&app_state_clone, &existing_liquidity_pools_clone, Strategy::Copy, &mint_str, &res[0], recent_blockhash, &logger_clone).await {
+                                                latency.finish(&res[0], "not_landed", &logger_clone);
+                                                note_execution(&app_state_clone, &mint_str, traded_pool.as_deref(), HealthEvent::ExecutionFailed, &logger_clone);
+                                                note_target(&app_state_clone, &copy_target, false, &logger_clone);
+                                                return;
+                                            }
+                                            if !confirmation::optimistic() {
+                                                latency.mark(Stage::Confirmed);
+                                            }
+                                            latency.finish(&res[0], "bought", &logger_clone);
+                                            // One moment for the ledger, the stored position and the timeout
+                                            let opened = Timestamps::now(clock.as_ref());
+                                            note_execution(&app_state_clone, &mint_str, traded_pool.as_deref(), HealthEvent::ExecutionSucceeded, &logger_clone);
+                                            note_target(&app_state_clone, &copy_target, true, &logger_clone);
+                                            // Remember the slot this buy was based on in case it gets orphaned
+                                            slot_tracker_clone
+                                                .lock()
+                                                .unwrap()
+                                                .record_position(trade_slot, &mint_str, &res[0]);
+
+                                            let fee = tx::take_sent_fee(&res[0]);
+                                            let trade = TradeRecord {
+                                                trade_id: res[0].clone(),
+                                                created_at: opened.wall.timestamp_millis(),
+                                                mint: mint_str.clone(),
+                                                side: "buy".to_string(),
+                                                sol_amount: Some(sol_to_lamports(swap_config_clone.amount_in)),
+                                                token_amount: None,
+                                                price: Some(token_price),
+                                                signature: Some(res[0].clone()),
+                                                priority_fee_strategy: fee.map(|fee| fee.strategy.to_string()),
+                                                priority_fee_lamports: fee.map(|fee| fee.budget.fee_lamports()),
+                                            };
+                                            if let Err(e) = store.record_trade(&trade) {
+                                                logger_clone.log(format!("[RECORD] => Failed to store trade: {}", e).red().to_string());
+                                            }
+                                            refresh_scheduler.set_open_position(&mint_str, true);
+                                            let position = kv::PositionState {
+                                                mint: mint_str.clone(),
+                                                signature: res[0].clone(),
+                                                strategy: Strategy::Copy.name().to_string(),
+                                                slot: trade_slot,
+                                                entry_price: token_price,
+                                                lamports: sol_to_lamports(swap_config_clone.amount_in),
+                                                opened_at_ms: opened.wall.timestamp_millis(),
+                                                dex: Some("pumpswap".to_string()),
+                                            };
+                                            if let Err(e) = app_state_clone.kv.put(&kv::POSITIONS, &mint_str, &position) {
+                                                logger_clone.log(format!("[KV] => Failed to store position: {}", e).red().to_string());
+                                            }
+                                            // Price is lamports per raw token unit, so this is the quoted output
+                                            let quoted_lamports = sol_to_lamports(swap_config_clone.amount_in);
+                                            tokio::spawn(fill_quality::watch_buy(
+                                                Arc::clone(&app_state_clone),
+                                                Strategy::Copy,
+                                                res[0].clone(),
+                                                mint_str.clone(),
+                                                quoted_lamports,
+                                                (quoted_lamports as f64 / token_price) as u64,
+                                                logger_clone.clone(),
+                                            ));
+                                            events::publish(BotEvent::PositionOpened {
+                                                mint: mint_str.clone(),
+                                                strategy: Strategy::Copy.name().to_string(),
+                                                entry_price: token_price,
+                                                lamports: sol_to_lamports(swap_config_clone.amount_in),
+                                            });
+                                            
                                             let bought_pool = LiquidityPool {
                                                 mint: mint_str.clone(),
                                                 buy_price: token_price,
                                                 sell_price: 0_f64,
                                                 status: Status::Bought,
-                                                timestamp: Some(Instant::now()),
+                                                timestamp: Some(opened),
                                             };
                                             
                                             // Create a local copy before modifying
                                             {
-                                                let mut existing_pools =
-                                                    existing_liquidity_pools_clone.lock().unwrap();
-                                                existing_pools.retain(|pool| pool.mint != mint_str);
-                                                existing_pools.insert(bought_pool.clone());
+                                                existing_liquidity_pools_clone.insert(mint_str.clone(), bought_pool.clone());
                                                 
-                                                // Log after modification within the lock scope
                                                 logger_clone.log(format!(
                                                     "\n\t * [SUCCESSFUL-COPY-BUY] => TX_HASH: (https://solscan.io/tx/{}) \n\t * [TOKEN] => ({}) \n\t * [DONE] => {} :: ({:?}) \n\t * [TOTAL TOKENS] => {}",
-                                                    &res[0], mint_str, Utc::now(), start_time.elapsed(), existing_pools.len()
+                                                    &res[0], mint_str, Utc::now(), start_time.elapsed(), existing_liquidity_pools_clone.len()
                                                 ).green().to_string());
                                             }
                                         },
                                         Err(e) => {
+                                            latency.finish(&mint_str, "send_failed", &logger_clone);
                                             logger_clone.log(
                                                 format!("Failed to copy buy for {}: {}", mint_str.clone(), e)
                                                     .red()
                                                     .italic()
                                                     .to_string(),
                                             );
+                                            note_execution(&app_state_clone, &mint_str, traded_pool.as_deref(), HealthEvent::ExecutionFailed, &logger_clone);
+                                            note_target(&app_state_clone, &copy_target, false, &logger_clone);
                                             
                                             // Re-enable buying since this one failed
-                                            let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                                            *buying_enabled = true;
+                                            capital.release(Strategy::Copy, &mint_str);
+                                            monitor.set_buying_enabled(true);
                                             
                                             let failed_pool = LiquidityPool {
                                                 mint: mint_str.clone(),
@@ -2137,13 +2564,7 @@ pub async fn copy_trader_pumpfun(
                                                 timestamp: None,
                                             };
                                             
-                                            // Use a local scope for the mutex lock
-                                            {
-                                                let mut update_pools =
-                                                    existing_liquidity_pools_clone.lock().unwrap();
-                                                update_pools.retain(|pool| pool.mint != mint_str);
-                                                update_pools.insert(failed_pool.clone());
-                                            }
+                                            existing_liquidity_pools_clone.insert(mint_str.clone(), failed_pool.clone());
                                         }
                                     }
                                 },
@@ -2156,8 +2577,8 @@ pub async fn copy_trader_pumpfun(
                                     );
                                     
                                     // Re-enable buying since this one failed
-                                    let mut buying_enabled = BUYING_ENABLED.lock().unwrap();
-                                    *buying_enabled = true;
+                                    capital.release(Strategy::Copy, &mint_str);
+                                    monitor.set_buying_enabled(true);
                                     
                                     let failed_pool = LiquidityPool {
                                         mint: mint_str.clone(),
@@ -2167,93 +2588,328 @@ pub async fn copy_trader_pumpfun(
                                         timestamp: None,
                                     };
                                     
-                                    // Use a local scope for the mutex lock
-                                    {
-                                        let mut update_pools =
-                                            existing_liquidity_pools_clone.lock().unwrap();
-                                        update_pools.retain(|pool| pool.mint != mint_str);
-                                        update_pools.insert(failed_pool.clone());
-                                    }
+                                    existing_liquidity_pools_clone.insert(mint_str.clone(), failed_pool.clone());
                                 }
                             }
                         });
                     }
                 }
+                continue;
             }
-            Err(error) => {
-                logger.log(
-                    format!("Yellowstone gRpc Error: {:?}", error)
-                        .red()
-                        .to_string(),
-                );
-                break;
-            }
-        }
+            Some(Err(error)) => format!("Yellowstone gRpc Error: {:?}", error),
+            None => "stream closed or went stale".to_string(),
+        };
+
+        // Resubscribe with the same filters, replaying from the last slot seen
+        logger.log(format!("[STREAM DOWN] => {}, resubscribing", reason).red().to_string());
+        let request = SubscribeRequest { from_slot: cursor.resume_from(), ..subscribe_request.clone() };
+        let (resumed_tx, resumed_stream) = rpc_fallback::connect_with_fallback(
+            &app_state,
+            fallback_config,
+            &mut backoff,
+            &mut rpc_fallback::CopyTargetPoller { targets: &target_pubkeys, newest_seen: &mut newest_seen },
+            "RECONNECT",
+            &logger,
+            || connect_and_subscribe(&yellowstone_grpc_http, &yellowstone_grpc_token, request.clone(), &app_state, &logger),
+        ).await;
+        *subscribe_tx.lock().await = resumed_tx;
+        stream = resumed_stream;
+        app_state.monitor.note_message(app_state.clock.now());
+        stale_signal.clear();
+        logger.log(format!("[STREAM RESUMED] => Resubscribed from slot {:?}", request.from_slot).green().to_string());
     }
-    Ok(())
 }
 
-/// Function to monitor for arbitrage opportunities
-pub async fn arbitrage_monitor(
-    yellowstone_grpc_http: String,
-    yellowstone_grpc_token: String,
-    app_state: AppState,
-    swap_config: SwapConfig,
-    arbitrage_threshold_pct: f64,
-    min_liquidity: u64,
-) -> Result<(), String> {
-    use crate::engine::pool_discovery::{PoolCacheManager, PoolInfo};
-    use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
-    use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
-    use std::time::Duration;
-    use std::env;
-    
-    // Log the arbitrage configuration
-    let logger = Logger::new("[ARBITRAGE-MONITOR] => ".blue().bold().to_string());
+/// Remember the newest finalized slot, the point a restarted stream can resume from
+fn note_last_slot(app_state: &AppState, slot: u64, logger: &Logger) {
+    if let Err(e) = app_state.kv.put(&kv::STREAM, kv::LAST_SLOT_KEY, &slot) {
+        logger.log(format!("[KV] => Failed to store last slot: {}", e).red().to_string());
+    }
+}
 
-    // Initialize RPC client for initial pool discovery
-    let rpc_url = env::var("RPC_URL").unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
-    let rpc_client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
-    
-    // Initialize pool cache manager
-    let cache_path = "pool_cache.json";
-    let pool_cache_manager = match crate::engine::pool_discovery::PoolCacheManager::new(cache_path) {
-        Ok(manager) => Arc::new(manager),
-        Err(e) => return Err(format!("Failed to initialize pool cache: {}", e)),
-    };
-    
-    // Get list of token mints to monitor from environment or use defaults
-    let token_mints_str = env::var("MONITOR_TOKEN_MINTS").unwrap_or_else(|_| "".to_string());
-    let mut token_mints = Vec::new();
-    
-    if !token_mints_str.is_empty() {
-        for mint_str in token_mints_str.split(',') {
-            if let Ok(pubkey) = Pubkey::from_str(mint_str.trim()) {
-                token_mints.push(pubkey);
-            } else {
-                logger.log(format!("Invalid token mint: {}", mint_str).red().to_string());
-            }
+/// Count a copy of `target` as landed or failed
+fn note_target(app_state: &AppState, target: &str, landed: bool, logger: &Logger) {
+    let result = app_state.kv.get(&kv::TARGET_STATS, target).and_then(|stats| {
+        let mut stats = stats.unwrap_or_default();
+        if landed {
+            stats.copied += 1;
+        } else {
+            stats.failed += 1;
         }
+        stats.last_seen_ms = Utc::now().timestamp_millis();
+        app_state.kv.put(&kv::TARGET_STATS, target, &stats)
+    });
+    if let Err(e) = result {
+        logger.log(format!("[KV] => Failed to store target stats: {}", e).red().to_string());
     }
-    
-    // If no token mints specified, use some popular tokens as default
-    if token_mints.is_empty() {
-        // Add some default popular tokens like SOL, USDC, BONK, JUP, etc.
-        let default_mints = [
-            "So11111111111111111111111111111111111111112", // SOL
-            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", // USDC
-            "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263", // BONK
-            "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", // JUP
-        ];
-        
-        for mint_str in default_mints.iter() {
-            if let Ok(pubkey) = Pubkey::from_str(mint_str) {
-                token_mints.push(pubkey);
-            }
-        }
+}
+
+/// Wait for a sent buy to land, unless `OPTIMISTIC_CONFIRM` is set. The
+/// pool reads `Buying` meanwhile; a buy that fails, never confirms or
+/// outlives its blockhash is marked `Failure`, its capital released and
+/// buying reopened. True when the position may be tracked as bought.
+async fn buy_landed(
+    app_state: &AppState,
+    pools: &TrackedPools,
+    strategy: Strategy,
+    mint: &str,
+    signature: &str,
+    blockhash: Hash,
+    logger: &Logger,
+) -> bool {
+    if confirmation::optimistic() {
+        return true;
     }
-    
-    // Log the tokens we're monitoring
+    pools.insert(mint.to_string(), LiquidityPool {
+        mint: mint.to_string(),
+        buy_price: 0_f64,
+        sell_price: 0_f64,
+        status: Status::Buying,
+        timestamp: None,
+    });
+    let outcome = confirmation::confirm_before_expiry(
+        app_state.rpc_pool.as_ref(),
+        app_state.clock.as_ref(),
+        signature,
+        confirmation::timeout(),
+        &|| app_state.blockhashes.expired(&blockhash),
+    ).await;
+    if outcome.is_confirmed() {
+        return true;
+    }
+    logger.log(format!(
+        "\n\t * [BUY NOT LANDED] => Token: {}, TX_HASH: (https://solscan.io/tx/{}) :: {:?}",
+        mint, signature, outcome
+    ).red().to_string());
+    app_state.capital.release(strategy, mint);
+    app_state.monitor.set_buying_enabled(true);
+    pools.insert(mint.to_string(), LiquidityPool {
+        mint: mint.to_string(),
+        buy_price: 0_f64,
+        sell_price: 0_f64,
+        status: Status::Failure,
+        timestamp: None,
+    });
+    false
+}
+
+/// Follow `dev_buy` with the configured buy and track the position like a
+/// copied one; buying is reopened when the buy fails
+#[allow(clippy::too_many_arguments)]
+fn spawn_sniper_buy(
+    app_state: Arc<AppState>,
+    swapx: Pump,
+    mut swap_config: SwapConfig,
+    dev_buy: DevBuy,
+    pools: Arc<TrackedPools>,
+    buys_made: Arc<AtomicU64>,
+    start_time: Instant,
+    logger: Logger,
+) {
+    let mint = dev_buy.mint.clone();
+    let requested = sol_to_lamports(swap_config.amount_in);
+    match app_state.capital.grant(Strategy::Sniper, &mint, requested) {
+        Ok(granted) => {
+            if granted < requested {
+                swap_config.amount_in = lamports_to_sol(granted);
+                logger.log(format!(
+                    "\n\t * [DOWNSIZED] => Sniper allocation allows {} of {}",
+                    fmt_sol(granted), fmt_sol(requested)
+                ).yellow().to_string());
+            }
+        }
+        Err(e) => {
+            logger.log(format!("\n\t * [SKIPPING BUY] => {}", e).yellow().to_string());
+            app_state.monitor.set_buying_enabled(true);
+            return;
+        }
+    }
+
+    logger.log(format!(
+        "\n\t * [SNIPING] => Token: {}, Amount: {}", mint, swap_config.amount_in
+    ).green().to_string());
+
+    tokio::spawn(async move {
+        let failed = |logger: &Logger, reason: String| {
+            logger.log(format!("Failed to snipe {}: {}", mint, reason).red().italic().to_string());
+            app_state.capital.release(Strategy::Sniper, &mint);
+            app_state.monitor.set_buying_enabled(true);
+            pools.insert(mint.clone(), LiquidityPool {
+                mint: mint.clone(),
+                buy_price: 0_f64,
+                sell_price: 0_f64,
+                status: Status::Failure,
+                timestamp: None,
+            });
+        };
+
+        let (keypair, instructions, token_price) = match swapx
+            .build_swap_ixn_by_mint(&mint, None, swap_config.clone(), start_time)
+            .await
+        {
+            Ok(built) => built,
+            Err(e) => return failed(&logger, format!("building swap instruction: {}", e)),
+        };
+        if SimulationConfig::from_env().enabled {
+            if let Err(e) = copy_validation::simulate_and_check(&app_state, &keypair, &instructions, dev_buy.recent_blockhash, &logger).await {
+                return failed(&logger, e.to_string());
+            }
+        }
+        let signatures = match app_state
+            .tx_sender
+            .send(dev_buy.recent_blockhash, &keypair, instructions, swap_config.priority_fee, &logger)
+            .await
+        {
+            Ok(signatures) => signatures,
+            Err(e) => return failed(&logger, e.to_string()),
+        };
+        let Some(signature) = signatures.first().cloned() else {
+            return failed(&logger, "sender returned no signature".to_string());
+        };
+        if !buy_landed(&app_state, &pools, Strategy::Sniper, &mint, &signature, dev_buy.recent_blockhash, &logger).await {
+            return;
+        }
+
+        let opened = Timestamps::now(app_state.clock.as_ref());
+        let lamports = sol_to_lamports(swap_config.amount_in);
+        buys_made.fetch_add(1, Ordering::Relaxed);
+        let fee = tx::take_sent_fee(&signature);
+        let trade = TradeRecord {
+            trade_id: signature.clone(),
+            created_at: opened.wall.timestamp_millis(),
+            mint: mint.clone(),
+            side: "buy".to_string(),
+            sol_amount: Some(lamports),
+            token_amount: None,
+            price: Some(token_price),
+            signature: Some(signature.clone()),
+            priority_fee_strategy: fee.map(|fee| fee.strategy.to_string()),
+            priority_fee_lamports: fee.map(|fee| fee.budget.fee_lamports()),
+        };
+        if let Err(e) = app_state.store.record_trade(&trade) {
+            logger.log(format!("[RECORD] => Failed to store trade: {}", e).red().to_string());
+        }
+        app_state.refresh_scheduler.set_open_position(&mint, true);
+        let position = kv::PositionState {
+            mint: mint.clone(),
+            signature: signature.clone(),
+            strategy: Strategy::Sniper.name().to_string(),
+            slot: dev_buy.slot,
+            entry_price: token_price,
+            lamports,
+            opened_at_ms: opened.wall.timestamp_millis(),
+            dex: Some("pumpswap".to_string()),
+        };
+        if let Err(e) = app_state.kv.put(&kv::POSITIONS, &mint, &position) {
+            logger.log(format!("[KV] => Failed to store position: {}", e).red().to_string());
+        }
+        tokio::spawn(fill_quality::watch_buy(
+            Arc::clone(&app_state),
+            Strategy::Sniper,
+            signature.clone(),
+            mint.clone(),
+            lamports,
+            (lamports as f64 / token_price) as u64,
+            logger.clone(),
+        ));
+        events::publish(BotEvent::PositionOpened {
+            mint: mint.clone(),
+            strategy: Strategy::Sniper.name().to_string(),
+            entry_price: token_price,
+            lamports,
+        });
+        pools.insert(mint.clone(), LiquidityPool {
+            mint: mint.clone(),
+            buy_price: token_price,
+            sell_price: 0_f64,
+            status: Status::Bought,
+            timestamp: Some(opened),
+        });
+        logger.log(format!(
+            "\n\t * [SUCCESSFUL-SNIPE] => TX_HASH: (https://solscan.io/tx/{}) \n\t * [TOKEN] => ({}) \n\t * [DONE] => {} :: ({:?}) \n\t * [BUYS] => {}",
+            signature, mint, Utc::now(), start_time.elapsed(), buys_made.load(Ordering::Relaxed)
+        ).green().to_string());
+    });
+}
+
+/// Forget a closed position so a restart doesn't report it as open
+fn close_position_state(app_state: &AppState, mint: &str, logger: &Logger) {
+    if let Err(e) = app_state.kv.remove(&kv::POSITIONS, mint) {
+        logger.log(format!("[KV] => Failed to remove position: {}", e).red().to_string());
+    }
+}
+
+/// Count a copy trade's outcome against the pool it went through
+fn note_execution(app_state: &AppState, mint: &str, pool_id: Option<&str>, event: HealthEvent, logger: &Logger) {
+    let Some(pool_id) = pool_id else { return };
+    if let Err(e) = app_state.pool_cache_manager.record_health_event(mint, pool_id, event) {
+        logger.log(format!("[CACHE ERROR] => {}", e).red().to_string());
+    }
+}
+
+/// Function to monitor for arbitrage opportunities
+/// Pool id a (token, dex) price is published under on the price board,
+/// `<mint>:<dex>` when discovery hasn't found the pool
+fn board_pool_id(pool_cache_manager: &PoolCacheManager, token_mint: &str, dex_name: &str) -> String {
+    pool_cache_manager
+        .get_cache()
+        .ok()
+        .and_then(|cache| {
+            cache
+                .get_pools_for_token(token_mint)
+                .and_then(|pools| pools.iter().find(|p| p.dex_name == dex_name))
+                .map(|p| p.pool_id.clone())
+        })
+        .unwrap_or_else(|| format!("{}:{}", token_mint, dex_name))
+}
+
+/// Decimals of `mint` as the swap's token balances report them, else as
+/// cached; an unknown mint is looked up in the background and None until then
+fn swap_decimals(meta: Option<&TransactionStatusMeta>, mint: &str) -> Option<u8> {
+    let reported = meta
+        .into_iter()
+        .flat_map(|meta| meta.pre_token_balances.iter().chain(&meta.post_token_balances))
+        .find(|balance| balance.mint == mint)
+        .and_then(|balance| balance.ui_token_amount.as_ref())
+        .and_then(|ui| u8::try_from(ui.decimals).ok());
+    let cached = cached_decimals(mint);
+    match reported {
+        Some(decimals) => {
+            if cached != Some(decimals) {
+                remember_decimals(mint, decimals);
+            }
+            Some(decimals)
+        }
+        None => {
+            if cached.is_none() {
+                if let Ok(mint) = Pubkey::from_str(mint) {
+                    token::prefetch_decimals(mint);
+                }
+            }
+            cached
+        }
+    }
+}
+
+pub async fn arbitrage_monitor(
+    yellowstone_grpc_http: String,
+    yellowstone_grpc_token: String,
+    app_state: Arc<AppState>,
+    swap_config: SwapConfig,
+    settings: ArbitrageSettings,
+) -> Result<(), String> {
+    use std::time::Duration;
+    use std::env;
+    
+    // Log the arbitrage configuration
+    let logger = Logger::new("[ARBITRAGE-MONITOR] => ".blue().bold().to_string());
+
+    // Pool cache comes from the app state, discovery goes through its paced RPC pool
+    let pool_cache_manager = Arc::clone(&app_state.pool_cache_manager);
+    
+    let token_mints = settings.token_mints.clone();
+    
+    // Log the tokens we're monitoring
     logger.log(format!(
         "[TOKEN MONITORING] => Tracking {} tokens for arbitrage opportunities",
         token_mints.len()
@@ -2263,93 +2919,71 @@ pub async fn arbitrage_monitor(
         logger.log(format!("\t * [TOKEN] => {}", token_mint).green().to_string());
     }
     
-    // Initialize pool cache with discovered pools
-    logger.log("[POOL DISCOVERY] => Discovering pools for monitored tokens...".blue().to_string());
+    // Start from the persisted cache and let discovery fill it in behind the scanner.
+    // The stream subscribes per DEX program, so newly found pools need no resubscribe:
+    // the scanner and refresh scheduler pick them up from the cache on their next pass.
+    let discovery_state = Arc::clone(&app_state);
+    let discovery_mints = token_mints.clone();
+    let (cached_pools, discovery) = crate::engine::pool_discovery::discover_in_background(
+        &pool_cache_manager,
+        async move {
+            crate::engine::pool_discovery::initialize_pool_cache(
+                &discovery_state.rpc_pool,
+                &discovery_mints,
+                &discovery_state.pool_cache_manager,
+            ).await
+        },
+    );
+    logger.log(format!(
+        "[POOL DISCOVERY] => Scanning with {} cached pools, discovery in progress",
+        cached_pools
+    ).blue().to_string());
     
-    match crate::engine::pool_discovery::initialize_pool_cache(
-        &rpc_client, 
-        &token_mints, 
-        &pool_cache_manager
-    ).await {
-        Ok(_) => {
-            if let Ok(cache) = pool_cache_manager.get_cache() {
-                let total_pools = cache.pools.values().map(|v| v.len()).sum::<usize>();
-                logger.log(format!(
-                    "[POOL DISCOVERY] => Found {} pools for {} tokens",
-                    total_pools,
-                    cache.pools.len()
-                ).green().to_string());
-                
-                // Log pools per token
-                for (token_mint, pools) in &cache.pools {
-                    logger.log(format!(
-                        "\t * [TOKEN] => {} has {} pools",
-                        token_mint,
-                        pools.len()
+    let report_state = Arc::clone(&app_state);
+    let discovery_logger = logger.clone();
+    tokio::spawn(async move {
+        let pool_cache_manager = &report_state.pool_cache_manager;
+        match discovery.await.unwrap_or_else(|e| Err(anyhow::anyhow!(e))) {
+            Ok(_) => {
+                if let Ok(cache) = pool_cache_manager.get_cache() {
+                    discovery_logger.log(format!(
+                        "[POOL DISCOVERY] => Done, {} pools for {} tokens",
+                        crate::engine::pool_discovery::cached_pool_count(&cache),
+                        cache.pools.len()
                     ).green().to_string());
                     
-                    for pool in pools {
-                        logger.log(format!(
-                            "\t\t - [POOL] => {} on {}",
-                            pool.pool_id,
-                            pool.dex_name
-                        ).cyan().to_string());
+                    // Log pools per token
+                    for (token_mint, pools) in &cache.pools {
+                        discovery_logger.log(format!(
+                            "\t * [TOKEN] => {} has {} pools",
+                            token_mint,
+                            pools.len()
+                        ).green().to_string());
+                        
+                        for pool in pools {
+                            discovery_logger.log(format!(
+                                "\t\t - [POOL] => {} on {}",
+                                pool.pool_id,
+                                pool.dex_name
+                            ).cyan().to_string());
+                        }
                     }
                 }
-            }
-        },
-        Err(e) => {
-            logger.log(format!(
-                "[POOL DISCOVERY ERROR] => Failed to initialize pool cache: {}",
-                e
-            ).red().to_string());
-            // Continue anyway, we might discover pools during monitoring
-        }
-    }
-
-    // INITIAL SETTING FOR SUBSCRIBE
-    // -----------------------------------------------------------------------------------------------------------------------------
-    let mut client = GeyserGrpcClient::build_from_shared(yellowstone_grpc_http.clone())
-        .map_err(|e| format!("Failed to build client: {}", e))?
-        .x_token::<String>(Some(yellowstone_grpc_token.clone()))
-        .map_err(|e| format!("Failed to set x_token: {}", e))?
-        .tls_config(ClientTlsConfig::new().with_native_roots())
-        .map_err(|e| format!("Failed to set tls config: {}", e))?
-        .connect()
-        .await
-        .map_err(|e| format!("Failed to connect: {}", e))?;
-
-    // Create additional clones for later use in tasks
-    let yellowstone_grpc_http = Arc::new(yellowstone_grpc_http);
-    let yellowstone_grpc_token = Arc::new(yellowstone_grpc_token);
-    let app_state = Arc::new(app_state);
-    let swap_config = Arc::new(swap_config);
-    let pool_cache_manager = Arc::new(pool_cache_manager);
-
-    let mut retry_count = 0;
-    const MAX_RETRIES: u32 = 3;
-    let (subscribe_tx, mut stream) = loop {
-        match client.subscribe().await {
-            Ok(pair) => break pair,
+            },
             Err(e) => {
-                retry_count += 1;
-                if retry_count >= MAX_RETRIES {
-                    return Err(format!("Failed to subscribe after {} attempts: {}", MAX_RETRIES, e));
-                }
-                logger.log(format!(
-                    "[CONNECTION ERROR] => Failed to subscribe (attempt {}/{}): {}. Retrying in 5 seconds...",
-                    retry_count, MAX_RETRIES, e
+                discovery_logger.log(format!(
+                    "[POOL DISCOVERY ERROR] => Background discovery stopped: {}",
+                    e
                 ).red().to_string());
-                time::sleep(Duration::from_secs(5)).await;
+                // Keep scanning with what the cache already holds
             }
         }
-    };
+    });
 
-    // Convert to Arc to allow cloning across tasks
-    let subscribe_tx = Arc::new(tokio::sync::Mutex::new(subscribe_tx));
+    let swap_config = Arc::new(swap_config);
 
-    // Initialize DEX registry to get program IDs
-    let dex_registry = DEXRegistry::new();
+    // Shared DEX registry to get program IDs
+    let dex_registry = Arc::clone(&app_state.dex_registry);
     
     // Prepare program IDs for monitoring - include all DEXes
     let mut program_ids = Vec::new();
@@ -2364,69 +2998,84 @@ pub async fn arbitrage_monitor(
     }
 
     // Create filter config
-    let filter_config = FilterConfig {
-        program_ids: program_ids.clone(),
-        dex_program_ids: program_ids.clone(),
-        arbitrage_threshold_pct,
-        min_liquidity,
-    };
+    let filter_config = FilterConfig::arbitrage(program_ids.clone(), settings.threshold_pct, settings.min_liquidity);
 
     logger.log(format!(
-        "[ARBITRAGE CONFIG] => Threshold: {}%, Min Liquidity: {} SOL",
-        filter_config.arbitrage_threshold_pct,
-        lamports_to_sol(filter_config.min_liquidity)
+        "[ARBITRAGE CONFIG] => Net Threshold: {}, Min Liquidity: {}",
+        fmt_pct(filter_config.arbitrage_threshold_pct),
+        fmt_lamports_exact(filter_config.min_liquidity)
     ).green().to_string());
 
-    subscribe_tx
-        .lock()
-        .await
-        .send(SubscribeRequest {
-            slots: HashMap::new(),
-            accounts: HashMap::new(),
-            transactions: hashmap! {
-                "All".to_owned() => SubscribeRequestFilterTransactions {
-                    vote: None,
-                    failed: Some(false),
-                    signature: None,
-                    account_include: program_ids.clone(),
-                    account_exclude: vec![JUPITER_PROGRAM.to_string(), OKX_DEX_PROGRAM.to_string()],
-                    account_required: Vec::<String>::new()
-                }
-            },
-            transactions_status: HashMap::new(),
-            entry: HashMap::new(),
-            blocks: HashMap::new(),
-            blocks_meta: HashMap::new(),
-            commitment: Some(CommitmentLevel::Processed as i32),
-            accounts_data_slice: vec![],
-            ping: None,
-            from_slot: None,
-        })
-        .await
-        .map_err(|e| format!("Failed to send subscribe request: {}", e))?;
+    let subscribe_request = SubscribeRequest {
+        slots: hashmap! {
+            "slots".to_owned() => reorg::slot_status_filter()
+        },
+        accounts: HashMap::new(),
+        transactions: hashmap! {
+            "All".to_owned() => SubscribeRequestFilterTransactions {
+                vote: None,
+                failed: Some(false),
+                signature: None,
+                account_include: program_ids.clone(),
+                account_exclude: vec![JUPITER_PROGRAM.to_string(), OKX_DEX_PROGRAM.to_string()],
+                account_required: Vec::<String>::new()
+            }
+        },
+        transactions_status: HashMap::new(),
+        entry: HashMap::new(),
+        blocks: HashMap::new(),
+        blocks_meta: HashMap::new(),
+        commitment: Some(CommitmentLevel::Processed as i32),
+        accounts_data_slice: vec![],
+        ping: None,
+        from_slot: None,
+    };
 
     // Use a HashMap to track token prices across different DEXes
-    let token_prices = Arc::new(Mutex::new(HashMap::<String, HashMap<String, (f64, u64)>>::new()));
+    let token_prices = Arc::new(rpc_fallback::PriceMap::new());
+    let slot_tracker = Arc::new(Mutex::new(SlotTracker::new()));
 
     logger.log("[STARTED. MONITORING FOR ARBITRAGE OPPORTUNITIES]...".blue().bold().to_string());
 
-    // After all setup and before the main loop, add a heartbeat ping task
-    let subscribe_tx_clone = subscribe_tx.clone();
-    let logger_clone = logger.clone();
-    
-    tokio::spawn(async move {
-        let ping_logger = logger_clone.clone();
-        let mut interval = time::interval(Duration::from_secs(30));
+    // Observe-only runs just record prices, they never look for opportunities
+    let observe_only = is_observe_only();
+    if observe_only {
+        let recorder_config = SnapshotRecorderConfig::from_env();
+        let token_prices_for_recorder = Arc::clone(&token_prices);
+        let recorder_logger = logger.clone();
+        logger.log(format!(
+            "[OBSERVE ONLY] => Recording price snapshots every {:?} to {}",
+            recorder_config.interval, recorder_config.path
+        ).yellow().bold().to_string());
         
-        loop {
-            interval.tick().await;
-            
-            if let Err(e) = send_heartbeat_ping(&subscribe_tx_clone, &ping_logger).await {
-                ping_logger.log(format!("[CONNECTION ERROR] => {}", e).red().to_string());
-                break;
+        tokio::spawn(async move {
+            let mut interval = time::interval(recorder_config.interval);
+            loop {
+                interval.tick().await;
+                
+                let timestamp_ms = Utc::now().timestamp_millis();
+                let snapshots: Vec<PriceSnapshot> = {
+                    token_prices_for_recorder.iter()
+                        .flat_map(|entry| {
+                            // Built per shard entry, its guard can't outlive the closure
+                            entry.value().iter().map(|(dex, (price, liquidity, slot, _))| PriceSnapshot {
+                                timestamp_ms,
+                                token_mint: entry.key().clone(),
+                                dex: dex.clone(),
+                                price: price.value(),
+                                liquidity: *liquidity,
+                                slot: *slot,
+                            }).collect::<Vec<_>>()
+                        })
+                        .collect()
+                };
+                
+                if let Err(e) = append_snapshots(&recorder_config.path, &snapshots) {
+                    recorder_logger.log(format!("[RECORDER ERROR] => {}", e).red().to_string());
+                }
             }
-        }
-    });
+        });
+    }
 
     // Start a background task to check for arbitrage opportunities periodically
     let token_prices_clone = Arc::clone(&token_prices);
@@ -2434,8 +3083,46 @@ pub async fn arbitrage_monitor(
     let pool_cache_manager_clone = Arc::clone(&pool_cache_manager);
     let arbitrage_threshold = filter_config.arbitrage_threshold_pct;
     let min_liquidity_value = filter_config.min_liquidity;
+    let trade_size_lamports = sol_to_lamports(swap_config.amount_in);
+    let scan_registry = Arc::clone(&app_state.dex_registry);
+    let scan_capital = Arc::clone(&app_state.capital);
+    let scan_store = Arc::clone(&app_state.store);
+    let scan_scheduler = Arc::clone(&app_state.refresh_scheduler);
+    let scan_clock = Arc::clone(&app_state.clock);
+    let scan_normalizer = Arc::clone(&app_state.price_normalizer);
+    let scan_dormancy = Arc::clone(&app_state.dormancy);
+    let scan_app_state = Arc::clone(&app_state);
+    let scan_swap_config = Arc::clone(&swap_config);
+    let max_position = execution::max_position_lamports(&swap_config);
+    let preflight_max_age = preflight::max_age();
+    let dry_run = execution::is_dry_run();
+    if dry_run {
+        logger.log("[DRY RUN] => Arbitrage opportunities are logged and recorded, never sent".yellow().bold().to_string());
+    }
+    let inventory_config = inventory::InventoryConfig::from_env().unwrap_or_else(|e| {
+        logger.log(format!("[INVENTORY] => Invalid INVENTORY_TARGETS ({}), inventory mode off", e).red().to_string());
+        None
+    });
+    let health_config = pool_cache_manager.health_config().clone();
+    let mut spread_tracker = SpreadTracker::new(
+        token_mints.iter().map(|mint| mint.to_string()).collect(),
+        Utc::now().timestamp_millis(),
+    );
+    let tip_lamports = if swap_config.use_jito { tx::jito_tip_lamports() } else { 0 };
+    let max_price_slot_lag = env::var("MAX_PRICE_SLOT_LAG")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(150);
+    let triangular_config = TriangularConfig::from_env();
+    if let Some(config) = &triangular_config {
+        logger.log(format!("[TRIANGULAR] => Searching SOL cycles of up to {} hops", config.max_hops).green().to_string());
+    }
     
     tokio::spawn(async move {
+        if observe_only {
+            return;
+        }
+        
         let prices_clone = Arc::clone(&token_prices_clone);
         let arb_logger = logger_clone.clone();
         let cache_manager = Arc::clone(&pool_cache_manager_clone);
@@ -2446,9 +3133,9 @@ pub async fn arbitrage_monitor(
         loop {
             interval.tick().await;
             
-            // Check for arbitrage opportunities
-            let opportunities = {
-                let prices = prices_clone.lock().unwrap();
+            // Check for arbitrage opportunities, keeping a breakdown of every one dropped
+            let mut rejections: Vec<Rejection> = Vec::new();
+            let (opportunities, triangles, scan_slot) = {
                 let mut arb_opportunities = Vec::new();
                 
                 // Get the current cache
@@ -2460,70 +3147,202 @@ pub async fn arbitrage_monitor(
                     }
                 };
                 
+                // Order book markets join the feed as synthetic pools priced at their mid;
+                // their touch is kept so they are compared on the side that would fill
+                let scanned_at = scan_clock.now();
+                let mut book_touches: HashMap<&str, HashMap<&str, BookTouch>> = HashMap::new();
+                for (token_mint, pools) in &cache.pools {
+                    for pool in pools {
+                        // USDC markets are compared in SOL, and wait for a fresh reference rate
+                        let Some(quote) = quote_currency::pool_quote(pool) else { continue };
+                        let Some(book) = scan_normalizer.pool_in_sol(pool, scanned_at).and_then(|pool| pool.book) else {
+                            continue;
+                        };
+                        let Some(touch) = book.touch() else { continue };
+                        prices_clone
+                            .entry(token_mint.clone())
+                            .or_insert_with(HashMap::new)
+                            .insert(pool.dex_name.clone(), (touch.mid(), book.depth, book.slot, quote));
+                        book_touches.entry(token_mint.as_str()).or_default().insert(pool.dex_name.as_str(), touch);
+                    }
+                }
+                
+                // The pairwise scan is quadratic per token; it runs on a copy so
+                // the stream and the pollers keep writing prices meanwhile
+                let prices = rpc_fallback::snapshot(&prices_clone);
+                
+                // Newest slot any price was seen at, the reference for staleness
+                let newest_slot = prices
+                    .values()
+                    .flat_map(|dex_prices| dex_prices.values().map(|(_, _, slot, _)| *slot))
+                    .max()
+                    .unwrap_or(0);
+                
                 for (token_mint, dex_prices) in prices.iter() {
-                    // Need at least 2 DEXes to compare
-                    if dex_prices.len() < 2 {
+                    // Need at least 2 DEXes to compare; dormant tokens wait for a swap to promote them
+                    if dex_prices.len() < 2 || scan_dormancy.is_dormant(token_mint) {
                         continue;
                     }
                     
-                    // Convert to a vector for easier comparison
-                    let dex_price_vec: Vec<(&String, &(f64, u64))> = dex_prices.iter().collect();
-                    
-                    for i in 0..dex_price_vec.len() {
-                        for j in i+1..dex_price_vec.len() {
-                            let (dex1, (price1, liquidity1)) = dex_price_vec[i];
-                            let (dex2, (price2, liquidity2)) = dex_price_vec[j];
+                    // Best spread for the token this scan, kept even when below threshold.
+                    // Pairs are walked in place so sub-threshold scans don't allocate.
+                    let mut best_spread_pct = 0.0_f64;
+                    let touches = book_touches.get(token_mint.as_str());
+                    let touch = |dex: &String| touches.and_then(|t| t.get(dex.as_str()));
+                    for (i, (dex1, (price1, liquidity1, slot1, quote1))) in dex_prices.iter().enumerate() {
+                        for (dex2, (price2, liquidity2, slot2, quote2)) in dex_prices.iter().skip(i + 1) {
+                            // Buy at the cheaper venue's ask and sell at the other's bid;
+                            // for pools both are the pool price
+                            let (buy_first, buy_price, sell_price) = best_direction(
+                                VenueQuote::new(*price1, touch(dex1)),
+                                VenueQuote::new(*price2, touch(dex2)),
+                            );
+                            let (buy_dex, sell_dex) = if buy_first { (dex1, dex2) } else { (dex2, dex1) };
+                            let (buy_quote, sell_quote) = if buy_first { (*quote1, *quote2) } else { (*quote2, *quote1) };
                             
-                            // Calculate price difference percentage
-                            let price_diff_pct = ((price1 - price2).abs() / price2) * 100.0;
+                            // Gross spread; under the threshold, nothing net of fees can clear it
+                            let price_diff_pct = (sell_price - buy_price) / buy_price * 100.0;
+                            if price_diff_pct.is_finite() {
+                                best_spread_pct = best_spread_pct.max(price_diff_pct);
+                            }
+                            if price_diff_pct <= arbitrage_threshold {
+                                continue;
+                            }
                             
-                            // Check if price difference exceeds threshold and both have sufficient liquidity
-                            if price_diff_pct > arbitrage_threshold && 
-                               *liquidity1 >= min_liquidity_value && 
-                               *liquidity2 >= min_liquidity_value {
-                                
-                                // Determine buy and sell DEXes based on price
-                                let (buy_dex, buy_price, sell_dex, sell_price) = if price1 < price2 {
-                                    (dex1, price1, dex2, price2)
-                                } else {
-                                    (dex2, price2, dex1, price1)
-                                };
-                                
-                                // Calculate expected profit percentage
-                                let expected_profit_pct = ((sell_price - buy_price) / buy_price) * 100.0;
-                                
-                                // Find the pool IDs from the cache
-                                let mut buy_pool_id = "unknown";
-                                let mut sell_pool_id = "unknown";
-                                
-                                if let Some(pools) = cache.pools.get(token_mint) {
-                                    for pool in pools {
-                                        if &pool.dex_name == *buy_dex {
-                                            buy_pool_id = &pool.pool_id;
-                                        } else if &pool.dex_name == *sell_dex {
-                                            sell_pool_id = &pool.pool_id;
-                                        }
-                                    }
-                                }
-                                
-                                arb_opportunities.push((
-                                    token_mint.clone(),
-                                    buy_dex.clone(),
-                                    *buy_price,
-                                    buy_pool_id.to_string(),
-                                    sell_dex.clone(),
-                                    *sell_price,
-                                    sell_pool_id.to_string(),
-                                    expected_profit_pct
-                                ));
+                            // Route each leg through the healthiest cached pool of its venue
+                            // quoted in what the leg was priced in
+                            let healthiest = |dex: &String, quote: QuoteAsset| {
+                                cache.pools.get(token_mint).and_then(|pools| {
+                                    pools
+                                        .iter()
+                                        .filter(|pool| &pool.dex_name == dex && quote_currency::pool_quote(pool) == Some(quote))
+                                        .max_by(|a, b| {
+                                            a.health_score().unwrap_or(0.0).total_cmp(&b.health_score().unwrap_or(0.0))
+                                        })
+                                })
+                            };
+                            let (buy_pool, sell_pool) = (healthiest(buy_dex, buy_quote), healthiest(sell_dex, sell_quote));
+                            let fees = LegFees {
+                                buy_bps: scan_registry.pool_fee_bps(buy_dex, buy_pool.and_then(|pool| pool.fee_bps)),
+                                sell_bps: scan_registry.pool_fee_bps(sell_dex, sell_pool.and_then(|pool| pool.fee_bps)),
+                            };
+                            
+                            // Break the spread down into what each cost takes from it
+                            let breakdown = ProfitBreakdown::new(
+                                buy_price,
+                                sell_price,
+                                fees.buy_bps + fees.sell_bps,
+                                tx::BASE_FEE_LAMPORTS + tx::priority_fee_lamports(),
+                                tip_lamports,
+                                trade_size_lamports,
+                            );
+                            let thinner = (*liquidity1).min(*liquidity2);
+                            let oldest_slot = (*slot1).min(*slot2);
+                            let reason = if thinner < min_liquidity_value {
+                                Some(SkipReason::LiquidityShortfall)
+                            } else if newest_slot.saturating_sub(oldest_slot) > max_price_slot_lag {
+                                Some(SkipReason::StalePrice)
+                            } else if !breakdown.clears(arbitrage_threshold) {
+                                Some(SkipReason::BelowNetProfit)
+                            } else {
+                                None
+                            };
+                            if let Some(reason) = reason {
+                                rejections.push(Rejection {
+                                    token: token_mint.clone(),
+                                    buy_dex: buy_dex.clone(),
+                                    sell_dex: sell_dex.clone(),
+                                    reason,
+                                    breakdown,
+                                    liquidity_shortfall: (reason == SkipReason::LiquidityShortfall)
+                                        .then(|| min_liquidity_value - thinner),
+                                });
+                                continue;
+                            }
+                            
+                            let buy_health = buy_pool.and_then(|pool| pool.health_score());
+                            let sell_health = sell_pool.and_then(|pool| pool.health_score());
+                            if !health_config.allows(buy_health) || !health_config.allows(sell_health) {
+                                rejections.push(Rejection {
+                                    token: token_mint.clone(),
+                                    buy_dex: buy_dex.clone(),
+                                    sell_dex: sell_dex.clone(),
+                                    reason: SkipReason::PoolUnhealthy,
+                                    breakdown,
+                                    liquidity_shortfall: None,
+                                });
+                                continue;
+                            }
+                            let buy_pool_id = buy_pool.map_or("unknown", |pool| pool.pool_id.as_str());
+                            let sell_pool_id = sell_pool.map_or("unknown", |pool| pool.pool_id.as_str());
+                            let weaker_health = buy_health.unwrap_or(0.0).min(sell_health.unwrap_or(0.0));
+                            // Size against what the cached pools hold, within the position cap and allocation
+                            let sizing = buy_pool.zip(sell_pool).and_then(|(buy_pool, sell_pool)| {
+                                compute_optimal_size(
+                                    &scan_normalizer.pool_in_sol(buy_pool, scanned_at)?,
+                                    &scan_normalizer.pool_in_sol(sell_pool, scanned_at)?,
+                                    fees,
+                                    max_position.min(scan_capital.available(Strategy::Arbitrage)),
+                                )
+                            });
+                            let mut quotes = LegQuotes { buy: buy_quote, sell: sell_quote, reference: None };
+                            if quotes.uses_usdc() {
+                                quotes.reference = scan_normalizer.rate(scanned_at);
                             }
+                            
+                            arb_opportunities.push((
+                                token_mint.clone(),
+                                buy_dex.clone(),
+                                buy_price,
+                                buy_pool_id.to_string(),
+                                sell_dex.clone(),
+                                sell_price,
+                                sell_pool_id.to_string(),
+                                breakdown,
+                                weaker_health,
+                                (*slot1).max(*slot2),
+                                sizing,
+                                quotes,
+                            ));
                         }
                     }
+                    spread_tracker.record(token_mint, best_spread_pct * 100.0);
                 }
                 
-                arb_opportunities
+                arb_opportunities.sort_by(|a, b| {
+                    pool_health::rank_opportunities((a.7.net_bps(), a.8), (b.7.net_bps(), b.8))
+                });
+                
+                // Cycles through three or more pools, over whatever the cache links
+                let triangles = triangular_config.as_ref().map_or_else(Vec::new, |config| {
+                    triangular::find_triangles(
+                        &cache,
+                        config,
+                        |dex| scan_registry.fee_bps(dex),
+                        |pool| {
+                            let touches_sol = pool.base_mint == SOL_MINT || pool.quote_mint == SOL_MINT;
+                            health_config.allows(pool.health_score())
+                                && (!touches_sol || pool.liquidity.unwrap_or(0) >= min_liquidity_value)
+                        },
+                        trade_size_lamports,
+                        tx::priority_fee_lamports() + tip_lamports,
+                    )
+                });
+                (arb_opportunities, triangles, newest_slot)
             };
             
+            if let Some(summaries) = spread_tracker.flush_due(Utc::now().timestamp_millis()) {
+                if let Err(e) = scan_store.record_spread_summaries(&summaries) {
+                    arb_logger.log(format!("[RECORD ERROR] => Failed to record spread summaries: {}", e).red().to_string());
+                }
+            }
+            
+            // Tokens with a spread over the threshold get their pools refreshed sooner
+            let scanned_at = scan_clock.now();
+            for token in opportunities.iter().map(|o| &o.0).chain(rejections.iter().map(|r| &r.token)) {
+                scan_scheduler.note_opportunity(token, scanned_at);
+            }
+            
             // Log arbitrage opportunities
             if !opportunities.is_empty() {
                 arb_logger.log(format!(
@@ -2531,27 +3350,179 @@ pub async fn arbitrage_monitor(
                     opportunities.len()
                 ).green().bold().to_string());
                 
-                for (token, buy_dex, buy_price, buy_pool, sell_dex, sell_price, sell_pool, profit) in opportunities {
+                for (token, buy_dex, buy_price, buy_pool, sell_dex, sell_price, sell_pool, breakdown, health, detected_slot, sizing, quotes) in opportunities {
+                    let profit = breakdown.net_bps() / 100.0;
+                    let opportunity_id = ids::next_opportunity_id(&token, &buy_pool, &sell_pool, detected_slot);
+                    // Skip opportunities that either venue would reject as dust
+                    if let Err(reason) = crate::engine::arbitrage::check_venue_minimums(
+                        &scan_registry,
+                        &buy_dex,
+                        &sell_dex,
+                        trade_size_lamports,
+                    ) {
+                        arb_logger.log(format!(
+                            "[SKIPPED] => Token: {} ({} -> {}), size {}, reason: {}",
+                            token, buy_dex, sell_dex, fmt_sol(trade_size_lamports), reason.code()
+                        ).yellow().to_string());
+                        rejections.push(Rejection {
+                            token, buy_dex, sell_dex, reason, breakdown, liquidity_shortfall: None,
+                        });
+                        continue;
+                    }
+
+                    if scan_capital.available(Strategy::Arbitrage) < trade_size_lamports {
+                        arb_logger.log(format!(
+                            "[SKIPPED] => Token: {} ({} -> {}), size {}, reason: {}",
+                            token, buy_dex, sell_dex, fmt_sol(trade_size_lamports),
+                            SkipReason::InsufficientCapital.code()
+                        ).yellow().to_string());
+                        rejections.push(Rejection {
+                            token, buy_dex, sell_dex,
+                            reason: SkipReason::InsufficientCapital,
+                            breakdown,
+                            liquidity_shortfall: None,
+                        });
+                        continue;
+                    }
+                    
                     arb_logger.log(format!(
-                        "\n\t * [ARBITRAGE] => Token: {} \n\t * [BUY] => {} at ${:.6} (Pool: {}) \n\t * [SELL] => {} at ${:.6} (Pool: {}) \n\t * [PROFIT] => {:.2}%",
-                        token, buy_dex, buy_price, buy_pool, sell_dex, sell_price, sell_pool, profit
+                        "\n\t * [ARBITRAGE] => {} Token: {} \n\t * [BUY] => {} at ${:.6} (Pool: {}) \n\t * [SELL] => {} at ${:.6} (Pool: {}) \n\t * [NET PROFIT] => {} \n\t * [HEALTH] => {:.0}",
+                        opportunity_id, token, buy_dex, buy_price, buy_pool, sell_dex, sell_price, sell_pool, fmt_pct(profit), health
                     ).cyan().to_string());
+                    if let Some(sizing) = &sizing {
+                        arb_logger.log(format!(
+                            "\t * [OPTIMAL SIZE] => {} for {} projected",
+                            fmt_sol(sizing.amount_in_lamports), fmt_sol(sizing.expected_profit_lamports)
+                        ).cyan().to_string());
+                    }
+                    if let Some(rate) = quotes.reference {
+                        arb_logger.log(format!(
+                            "\t * [QUOTES] => buy in {}, sell in {}, at {:.4} USDC per SOL (slot {})",
+                            quotes.buy.name(), quotes.sell.name(), rate.usdc_per_sol, rate.slot
+                        ).cyan().to_string());
+                    }
                     
-                    // Here you would implement the actual arbitrage execution
-                    // This would involve:
-                    // 1. Buy the token on the cheaper DEX
-                    // 2. Sell the token on the more expensive DEX
-                    // 3. Calculate actual profit after fees
-                    
-                    // For now, just log that we would execute the trade
-                    arb_logger.log(format!(
-                        "\n\t * [WOULD EXECUTE] => Arbitrage trade for token {} between {} and {}",
-                        token, buy_dex, sell_dex
-                    ).yellow().to_string());
+                    // Inventory tokens take the spread with one leg against standing inventory
+                    let trade_id = opportunity_id.trade(1);
+                    let single_leg = inventory_config.as_ref().is_some_and(|config| {
+                        inventory::on_opportunity(
+                            &scan_app_state,
+                            config,
+                            &trade_id,
+                            &token,
+                            (&buy_dex, buy_price),
+                            (&sell_dex, sell_price),
+                            lamports_to_sol(trade_size_lamports),
+                            &arb_logger,
+                        )
+                    });
+
+                    // Both legs go out in one transaction unless this is a dry run
+                    let execution = if single_leg || dry_run {
+                        None
+                    } else {
+                        let mut legs = execution::ArbitrageLegs {
+                            token: &token,
+                            buy_dex: &buy_dex,
+                            buy_pool: &buy_pool,
+                            sell_dex: &sell_dex,
+                            sell_pool: &sell_pool,
+                            profit_pct: profit,
+                            planned: sizing,
+                            quotes,
+                        };
+                        // The gap may have closed since the scan; re-read the pools before paying to find out
+                        let hurdle = preflight::Hurdle {
+                            cap_lamports: max_position.min(scan_capital.available(Strategy::Arbitrage)),
+                            fixed_cost_lamports: tx::BASE_FEE_LAMPORTS + tx::priority_fee_lamports() + tip_lamports,
+                            threshold_bps: (arbitrage_threshold * 100.0).max(0.0),
+                        };
+                        match preflight::refresh(&scan_app_state, legs, scanned_at, preflight_max_age, hurdle).await {
+                            Ok(Preflight::Stale { net_bps, slot }) => {
+                                arb_logger.log(format!(
+                                    "[STALE OPPORTUNITY] => {} Token: {} ({} -> {}), detected net {}, {} on reserves at slot {}",
+                                    opportunity_id, token, buy_dex, sell_dex, fmt_pct(profit),
+                                    net_bps.map_or_else(|| "no profitable size".to_string(), |bps| format!("net {}", fmt_pct(bps / 100.0))),
+                                    slot
+                                ).yellow().to_string());
+                                rejections.push(Rejection {
+                                    token, buy_dex, sell_dex,
+                                    reason: SkipReason::StaleOpportunity,
+                                    breakdown,
+                                    liquidity_shortfall: None,
+                                });
+                                continue;
+                            }
+                            Ok(Preflight::Confirmed { sizing, net_bps, slot }) => {
+                                arb_logger.debug_with(|| format!(
+                                    "[PREFLIGHT] => {} still nets {} at {} on reserves at slot {}",
+                                    opportunity_id, fmt_pct(net_bps / 100.0), fmt_sol(sizing.amount_in_lamports), slot
+                                ));
+                                legs.planned = Some(sizing);
+                            }
+                            Ok(Preflight::Fresh | Preflight::Unchecked) => {}
+                            Err(e) => {
+                                // Unverified prices are no better than stale ones, so don't pay to find out
+                                arb_logger.log(format!(
+                                    "[PREFLIGHT FAILED] => {} Token: {} ({} -> {}), could not re-read the pools: {}",
+                                    opportunity_id, token, buy_dex, sell_dex, e
+                                ).yellow().to_string());
+                                rejections.push(Rejection {
+                                    token, buy_dex, sell_dex,
+                                    reason: SkipReason::PreflightFailed,
+                                    breakdown,
+                                    liquidity_shortfall: None,
+                                });
+                                continue;
+                            }
+                        }
+                        let mut latency = LatencyTracer::start("arbitrage", Stage::Decided, Instant::now(), &scan_app_state.rng);
+                        let outcome = execution::execute_arbitrage(&scan_app_state, legs, &trade_id, &scan_swap_config, &mut latency, &arb_logger).await;
+                        latency.finish(trade_id.as_str(), outcome.code(), &arb_logger);
+                        Some(outcome)
+                    };
+                    events::publish(BotEvent::Opportunity {
+                        token: token.clone(),
+                        buy_dex: buy_dex.clone(),
+                        sell_dex: sell_dex.clone(),
+                        net_bps: breakdown.net_bps(),
+                        outcome: match &execution {
+                            Some(outcome) => outcome.code(),
+                            None if single_leg => "inventory_capture",
+                            None => "would_execute",
+                        }.to_string(),
+                    });
+                    if execution.is_none() && !single_leg {
+                        arb_logger.log(format!(
+                            "\n\t * [WOULD EXECUTE] => {} Arbitrage trade for token {} between {} and {}",
+                            opportunity_id, token, buy_dex, sell_dex
+                        ).yellow().to_string());
+                    }
                     
                     // Save arbitrage opportunity to a file for later analysis
-                    let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
+                    let found_at = chrono::Utc::now();
+                    let timestamp = found_at.format("%Y%m%d%H%M%S").to_string();
+                    // Whole seconds, like the file name, so a migrated copy of this file dedupes
+                    let opportunity = ArbitrageOpportunity {
+                        created_at: found_at.timestamp() * 1000,
+                        token: token.clone(),
+                        token_is_prefix: false,
+                        buy_dex: buy_dex.clone(),
+                        buy_price,
+                        buy_pool: buy_pool.clone(),
+                        sell_dex: sell_dex.clone(),
+                        sell_price,
+                        sell_pool: sell_pool.clone(),
+                        profit_pct: Some(profit),
+                        source: SOURCE_LIVE.to_string(),
+                        opportunity_id: Some(opportunity_id.to_string()),
+                    };
+                    if let Err(e) = scan_store.record_opportunity(&opportunity) {
+                        arb_logger.log(format!("[RECORD] => Failed to store opportunity: {}", e).red().to_string());
+                    }
                     let record = serde_json::json!({
+                        "opportunity_id": opportunity_id,
+                        "detected_slot": detected_slot,
                         "timestamp": timestamp,
                         "token_mint": token,
                         "buy_dex": buy_dex,
@@ -2560,8 +3531,16 @@ pub async fn arbitrage_monitor(
                         "sell_dex": sell_dex,
                         "sell_price": sell_price,
                         "sell_pool": sell_pool,
-                        "price_difference_pct": profit,
-                        "min_liquidity": lamports_to_sol(min_liquidity_value)
+                        "net_profit_pct": profit,
+                        "min_pool_health": health,
+                        "buy_fee_bps": scan_registry.fee_bps(&buy_dex),
+                        "sell_fee_bps": scan_registry.fee_bps(&sell_dex),
+                        "min_liquidity_lamports": min_liquidity_value,
+                        "min_liquidity": fmt_sol(min_liquidity_value),
+                        "optimal_size_lamports": sizing.map(|sizing| sizing.amount_in_lamports),
+                        "projected_profit_lamports": sizing.map(|sizing| sizing.expected_profit_lamports),
+                        "quotes": quotes.to_record(),
+                        "execution": execution.as_ref().map(|outcome| outcome.to_record(&trade_id))
                     });
                     
                     // Ensure the directory exists
@@ -2581,35 +3560,267 @@ pub async fn arbitrage_monitor(
                     }
                 }
             }
+            
+            for triangle in triangles {
+                let route = triangle.route();
+                let mints: Vec<&str> = triangle.hops.iter().map(|hop| hop.to_mint.as_str()).collect();
+                let first_pool = triangle.hops.first().map_or("unknown", |hop| hop.pool_id.as_str());
+                let last_pool = triangle.hops.last().map_or("unknown", |hop| hop.pool_id.as_str());
+                let opportunity_id = ids::next_opportunity_id(&mints.join(","), first_pool, last_pool, scan_slot);
+                arb_logger.log(format!(
+                    "\n\t * [TRIANGULAR] => {} {} hops: {} \n\t * [SIZE] => {} -> {} \n\t * [NET PROFIT] => {} ({})",
+                    opportunity_id,
+                    triangle.hops.len(),
+                    route,
+                    fmt_sol(triangle.input_lamports),
+                    fmt_sol(triangle.output_lamports),
+                    fmt_sol_signed(triangle.net_profit_lamports()),
+                    fmt_pct(triangle.profit_pct()),
+                ).cyan().to_string());
+                
+                let found_at = chrono::Utc::now();
+                let record = TriangleRecord {
+                    created_at: found_at.timestamp_millis(),
+                    opportunity_id: opportunity_id.to_string(),
+                    route,
+                    hops: serde_json::to_string(&triangle.hops).unwrap_or_default(),
+                    input_lamports: triangle.input_lamports,
+                    output_lamports: triangle.output_lamports,
+                    net_profit_lamports: triangle.net_profit_lamports(),
+                    profit_pct: triangle.profit_pct(),
+                };
+                if let Err(e) = scan_store.record_triangle(&record) {
+                    arb_logger.log(format!("[RECORD] => Failed to store triangular opportunity: {}", e).red().to_string());
+                }
+                
+                // Kept apart from the two-leg files, which the legacy import reads
+                let record_dir = "triangular_opportunities";
+                if let Err(e) = fs::create_dir_all(record_dir) {
+                    arb_logger.log(format!("[ERROR] => Failed to create directory: {}", e).red().to_string());
+                }
+                let filename = format!("{}/tri_{}_{}.json", record_dir, opportunity_id, found_at.format("%Y%m%d%H%M%S"));
+                let json = serde_json::json!({
+                    "opportunity_id": opportunity_id,
+                    "detected_slot": scan_slot,
+                    "timestamp": found_at.format("%Y%m%d%H%M%S").to_string(),
+                    "opportunity": triangle,
+                    "net_profit_lamports": triangle.net_profit_lamports(),
+                    "net_profit_pct": triangle.profit_pct(),
+                });
+                if let Ok(mut file) = File::create(&filename) {
+                    if let Err(e) = file.write_all(serde_json::to_string_pretty(&json).unwrap_or_default().as_bytes()) {
+                        arb_logger.log(format!("[ERROR] => Failed to write to file: {}", e).red().to_string());
+                    }
+                }
+            }
+            
+            if !rejections.is_empty() {
+                if events::has_subscribers() {
+                    for r in &rejections {
+                        events::publish(BotEvent::Opportunity {
+                            token: r.token.clone(),
+                            buy_dex: r.buy_dex.clone(),
+                            sell_dex: r.sell_dex.clone(),
+                            net_bps: r.breakdown.net_bps(),
+                            outcome: r.reason.code().to_string(),
+                        });
+                    }
+                }
+                let now = chrono::Utc::now().timestamp_millis();
+                let records: Vec<_> = rejections.iter().map(|r| r.to_record(now)).collect();
+                if let Err(e) = scan_store.record_rejections(&records) {
+                    arb_logger.log(format!("[RECORD ERROR] => Failed to record rejections: {}", e).red().to_string());
+                }
+            }
         }
     });
 
     // Add a connection health check task
     let logger_health = logger.clone(); 
+    let health_app_state = Arc::clone(&app_state);
+    let stale_signal = Arc::new(StaleSignal::default());
+    let health_signal = Arc::clone(&stale_signal);
     tokio::spawn(async move {
         let health_logger = logger_health.clone();
-        let mut interval = time::interval(Duration::from_secs(300)); // 5 minutes
+        let mut interval = time::interval(stream_reconnect::watchdog_interval(health_app_state.monitor.stale_after()));
         
         loop {
             interval.tick().await;
-            check_connection_health(&health_logger).await;
+            check_connection_health(&health_app_state, &health_signal, &health_logger).await;
         }
     });
 
     // Ensure record directories exist
     ensure_record_dirs()?;
 
-    while let Some(message) = stream.next().await {
-        match message {
-            Ok(msg) => {
+    // Optional shared-memory copy of every price update for other processes
+    let mut price_board = match PriceBoardConfig::from_env() {
+        Some(config) => match PriceBoardWriter::create(&config) {
+            Ok(writer) => {
+                logger.log(format!(
+                    "[PRICE BOARD] => Publishing prices to {} ({} slots)",
+                    config.path, config.slot_count
+                ).green().to_string());
+                Some(writer)
+            }
+            Err(e) => {
+                logger.log(format!("[PRICE BOARD] => Disabled, failed to create {}: {}", config.path, e).red().to_string());
+                None
+            }
+        },
+        None => None,
+    };
+    let mut board_pool_ids: HashMap<(String, String), String> = HashMap::new();
+    let mut price_gate = PriceGate::from_env();
+
+    // Connect last so RPC polling can stand in for a dead stream, with the
+    // scanner above already reading whatever prices the polling writes
+    let fallback_config = rpc_fallback::FallbackConfig::from_env();
+    let mut backoff = ReconnectBackoff::from_env();
+    let (subscribe_tx, mut stream) = rpc_fallback::connect_with_fallback(
+        &app_state,
+        fallback_config,
+        &mut backoff,
+        &mut rpc_fallback::PricePoller { prices: &token_prices },
+        "CONNECT",
+        &logger,
+        || connect_and_subscribe(&yellowstone_grpc_http, &yellowstone_grpc_token, subscribe_request.clone(), &app_state, &logger),
+    ).await;
+    let subscribe_tx = Arc::new(tokio::sync::Mutex::new(subscribe_tx));
+
+    // After all setup and before the main loop, add a heartbeat ping task
+    let subscribe_tx_clone = subscribe_tx.clone();
+    let logger_clone = logger.clone();
+    
+    tokio::spawn(async move {
+        let ping_logger = logger_clone.clone();
+        let mut interval = time::interval(Duration::from_secs(30));
+        
+        loop {
+            interval.tick().await;
+            
+            // A failed ping is left to the stream loop, which resubscribes into the same sink slot
+            if let Err(e) = send_heartbeat_ping(&subscribe_tx_clone, &ping_logger).await {
+                ping_logger.log(format!("[CONNECTION ERROR] => {}", e).red().to_string());
+            }
+        }
+    });
+
+    // Subscribe to the vaults of cached pools, adding pools as the cache grows
+    let pool_watch = Arc::new(Mutex::new(PoolAccountWatch::default()));
+    if let Some(refresh_interval) = pool_accounts::refresh_interval() {
+        let watch_app_state = Arc::clone(&app_state);
+        let watch = Arc::clone(&pool_watch);
+        let watch_tx = subscribe_tx.clone();
+        let watch_request = subscribe_request.clone();
+        let watch_logger = logger.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(refresh_interval);
+            loop {
+                interval.tick().await;
+                match pool_accounts::watch_new_pools(&watch_app_state, &watch).await {
+                    Ok(0) => {}
+                    Ok(added) => {
+                        let (request, watched) = {
+                            let watch = watch.lock_or_recover();
+                            (watch.request(&watch_request), watch.len())
+                        };
+                        match watch_tx.lock().await.send(request).await {
+                            Ok(()) => watch_logger.log(format!(
+                                "[POOL ACCOUNTS] => Watching {} more pools, {} in total", added, watched
+                            ).cyan().to_string()),
+                            // The stream loop resubscribes with the full watch list
+                            Err(e) => watch_logger.log(format!(
+                                "[POOL ACCOUNTS] => Updating the subscription failed: {:?}", e
+                            ).red().to_string()),
+                        }
+                    }
+                    Err(e) => watch_logger.log(format!(
+                        "[POOL ACCOUNTS] => Reading pool accounts failed: {}", e
+                    ).red().to_string()),
+                }
+            }
+        });
+    }
+
+    events::publish(BotEvent::Stream { name: "arbitrage".to_string(), connected: true, slot: None });
+    backoff.reset();
+    let mut cursor = StreamCursor::default();
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            _ = stale_signal.stale() => None,
+        };
+        let reason = match message {
+            Some(Ok(msg)) => {
+                backoff.reset();
                 // Process ping/pong messages
-                if let Err(e) = process_stream_message(&msg, &subscribe_tx, &logger).await {
+                if let Err(e) = process_stream_message(&msg, &subscribe_tx, &app_state, &logger).await {
                     logger.log(format!("Error handling stream message: {}", e).red().to_string());
                     continue;
                 }
                 
+                // Drop prices that came from a slot that has since been marked dead
+                if let Some(UpdateOneof::Slot(slot_update)) = &msg.update_oneof {
+                    cursor.note_slot(slot_update.slot);
+                    app_state.blockhashes.note_slot(slot_update.slot);
+                    events::publish(BotEvent::Stream {
+                        name: "arbitrage".to_string(),
+                        connected: true,
+                        slot: Some(slot_update.slot),
+                    });
+                    if reorg::is_dead_slot(slot_update) {
+                        let (rolled_back, _) = slot_tracker.lock_or_recover().take_slot(slot_update.slot);
+                        if !rolled_back.is_empty() {
+                            for (token_mint, dex_name) in &rolled_back {
+                                if let Some(mut dex_prices) = token_prices.get_mut(token_mint) {
+                                    dex_prices.remove(dex_name);
+                                }
+                            }
+                            logger.log(format!(
+                                "[REORG] => Slot {} is dead, rolled back {} price entries",
+                                slot_update.slot, rolled_back.len()
+                            ).yellow().to_string());
+                        }
+                    } else if reorg::is_finalized_slot(slot_update) {
+                        slot_tracker.lock_or_recover().prune_through(slot_update.slot);
+                        note_last_slot(&app_state, slot_update.slot, &logger);
+                    }
+                    continue;
+                }
+                
+                // Reserves straight from a watched vault, no swap log needed
+                if let Some(UpdateOneof::Account(account_update)) = &msg.update_oneof {
+                    cursor.note_slot(account_update.slot);
+                    let reserves = pool_watch.lock_or_recover().apply(account_update);
+                    if let Some((pair, base, quote)) = reserves {
+                        if rpc_fallback::record_reserves(
+                            &app_state,
+                            &pair,
+                            base,
+                            quote,
+                            account_update.slot,
+                            &token_prices,
+                            &mut price_gate,
+                            &logger,
+                        ) {
+                            slot_tracker.lock_or_recover().record_price(account_update.slot, &pair.mint, &pair.dex);
+                            logger.debug_with(|| format!(
+                                "[ACCOUNT UPDATE] => Token: {}, DEX: {}, Pool: {}, slot {}",
+                                pair.mint, pair.dex, pair.pool_id, account_update.slot
+                            ));
+                        }
+                    }
+                    continue;
+                }
+                
                 // Process transaction messages
                 if let Some(UpdateOneof::Transaction(txn)) = msg.update_oneof {
+                    // A resumed stream replays the slot it stopped in
+                    if !cursor.first_sighting(&txn) {
+                        continue;
+                    }
+                    app_state.blockhashes.observe_transaction(&txn);
                     let start_time = Instant::now();
                     if let Some(log_messages) = txn
                         .clone()
@@ -2619,7 +3830,7 @@ pub async fn arbitrage_monitor(
                     {
                         // Extract DEX program ID from transaction
                         if let Some(transaction) = txn.transaction.clone() {
-                            if let Some(message) = transaction.transaction.and_then(|t| t.message) {
+                            if let Some(message) = transaction.transaction.clone().and_then(|t| t.message) {
                                 for instruction in message.instructions {
                                     let program_idx = instruction.program_id_index as usize;
                                     if let Some(program_id_bytes) = message.account_keys.get(program_idx) {
@@ -2632,34 +3843,84 @@ pub async fn arbitrage_monitor(
                                                     bs58::encode(&transaction.signature).into_string()
                                                 ).blue().to_string());
                                                 
-                                                // Extract pool information and token prices
-                                                // This would involve parsing the transaction logs and data
-                                                // For now, we'll just log that we detected a DEX transaction
-                                                
-                                                // In a real implementation, you would:
-                                                // 1. Extract the token mint address
-                                                // 2. Extract the pool information
-                                                // 3. Calculate the token price based on the pool reserves
-                                                // 4. Update the token_prices HashMap
-                                                
-                                                // Mock implementation for demonstration
-                                                let mock_token_mint = "TokenMintAddress";
-                                                let mock_price = 1.0 + (rand::random::<f64>() * 0.1); // Random price between 1.0 and 1.1
-                                                let mock_liquidity = 1_000_000_000; // 1 SOL
-                                                
-                                                // Update token prices
-                                                {
-                                                    let mut prices = token_prices.lock().unwrap();
-                                                    let dex_prices = prices
-                                                        .entry(mock_token_mint.to_string())
-                                                        .or_insert_with(HashMap::new);
-                                                    
-                                                    dex_prices.insert(dex.name.clone(), (mock_price, mock_liquidity));
+                                                // Price the pool from the reserves the swap logged, or its
+                                                // vaults' balances when it logged none
+                                                let mut pool_info = match extract_pool_info_from_transaction(&transaction, &log_messages) {
+                                                    Ok(Some(pool_info)) => pool_info,
+                                                    Ok(None) => continue,
+                                                    Err(e) => {
+                                                        logger.log(format!(
+                                                            "[PRICE SKIPPED] => DEX: {}, could not read pool info: {}",
+                                                            dex.name, e
+                                                        ).yellow().to_string());
+                                                        continue;
+                                                    }
+                                                };
+                                                let Some(streamed) = price_streamed_swap(
+                                                    &app_state,
+                                                    &mut pool_info,
+                                                    &dex.name,
+                                                    &transaction,
+                                                    txn.slot,
+                                                    &token_prices,
+                                                    &mut price_gate,
+                                                    &logger,
+                                                ).await else {
+                                                    continue;
+                                                };
+                                                let StreamedPrice { token_mint, token_is_base, quote, quoted, price, liquidity, quote_reserve } = streamed;
+                                                let token_mint = token_mint.as_str();
+                                                slot_tracker.lock_or_recover().record_price(txn.slot, token_mint, &dex.name);
+                                                app_state.refresh_scheduler.note_activity(token_mint, app_state.clock.now());
+                                                dormancy::note_swap(&app_state, token_mint, &logger);
+                                                // Pools found trading join the cache discovery built; it keeps
+                                                // a USDC pool's own figures, quote per base
+                                                let (cache_price, cache_liquidity) = match quote {
+                                                    QuoteAsset::Sol => (price.value(), liquidity),
+                                                    QuoteAsset::Usdc if token_is_base => (quoted.value(), quote_reserve),
+                                                    QuoteAsset::Usdc => (1.0 / quoted.value(), quote_reserve),
+                                                };
+                                                let observed_pool = crate::engine::pool_discovery::PoolInfo {
+                                                    pool_id: pool_info.pool_id.to_string(),
+                                                    dex_name: dex.name.clone(),
+                                                    base_mint: pool_info.base_mint.to_string(),
+                                                    quote_mint: pool_info.quote_mint.to_string(),
+                                                    last_known_price: Some(cache_price),
+                                                    last_updated: Some(chrono::Utc::now().timestamp()),
+                                                    liquidity: Some(cache_liquidity),
+                                                    subtype: None,
+                                                    book: None,
+                                                    health: None,
+                                                    fee_bps: None,
+                                                };
+                                                match pool_cache_manager.observe_pool(token_mint, observed_pool) {
+                                                    Ok(true) => {
+                                                        logger.log(format!(
+                                                            "[POOL ADDED] => Token: {}, DEX: {}, Pool: {}",
+                                                            token_mint, dex.name, pool_info.pool_id
+                                                        ).cyan().to_string());
+                                                        // A placeholder id chosen before the pool was known is stale now
+                                                        board_pool_ids.remove(&(token_mint.to_string(), dex.name.clone()));
+                                                    }
+                                                    Ok(false) => {}
+                                                    Err(e) => logger.log(format!("[CACHE ERROR] => {}", e).red().to_string()),
+                                                }
+                                                let pool_id = board_pool_ids
+                                                    .entry((token_mint.to_string(), dex.name.clone()))
+                                                    .or_insert_with(|| board_pool_id(&pool_cache_manager, token_mint, &dex.name));
+                                                if let Err(e) = pool_cache_manager.record_health_event(token_mint, pool_id, HealthEvent::Swap) {
+                                                    logger.log(format!("[CACHE ERROR] => {}", e).red().to_string());
+                                                }
+                                                if let Some(board) = price_board.as_mut() {
+                                                    let was_full = board.is_full();
+                                                    if !board.update(pool_id, price.value(), liquidity, txn.slot) && !was_full {
+                                                        logger.log("[PRICE BOARD] => Board full, raise PRICE_BOARD_SLOTS".red().to_string());
+                                                    }
                                                 }
                                                 
                                                 logger.log(format!(
-                                                    "[PRICE UPDATE] => Token: {}, DEX: {}, Price: ${:.6}, Liquidity: {} SOL",
-                                                    mock_token_mint, dex.name, mock_price, lamports_to_sol(mock_liquidity)
+                                                    "[PRICE UPDATE] => Token: {}, DEX: {}, Price: ${:.6}, Liquidity: {}",
+                                                    token_mint, dex.name, price.value(), fmt_sol(liquidity)
                                                 ).green().to_string());
                                             }
                                         }
@@ -2669,17 +3930,454 @@ pub async fn arbitrage_monitor(
                         }
                     }
                 }
+                continue;
             }
-            Err(error) => {
-                logger.log(
-                    format!("Yellowstone gRpc Error: {:?}", error)
-                        .red()
-                        .to_string(),
-                );
-                break;
+            Some(Err(error)) => format!("Yellowstone gRpc Error: {:?}", error),
+            None => "stream closed or went stale".to_string(),
+        };
+
+        events::publish(BotEvent::Stream { name: "arbitrage".to_string(), connected: false, slot: None });
+        // Resubscribe with the same filters, replaying from the last slot seen
+        logger.log(format!("[STREAM DOWN] => {}, resubscribing", reason).red().to_string());
+        let request = SubscribeRequest { from_slot: cursor.resume_from(), ..pool_watch.lock_or_recover().request(&subscribe_request) };
+        let (resumed_tx, resumed_stream) = rpc_fallback::connect_with_fallback(
+            &app_state,
+            fallback_config,
+            &mut backoff,
+            &mut rpc_fallback::PricePoller { prices: &token_prices },
+            "RECONNECT",
+            &logger,
+            || connect_and_subscribe(&yellowstone_grpc_http, &yellowstone_grpc_token, request.clone(), &app_state, &logger),
+        ).await;
+        *subscribe_tx.lock().await = resumed_tx;
+        stream = resumed_stream;
+        app_state.monitor.note_message(app_state.clock.now());
+        stale_signal.clear();
+        logger.log(format!("[STREAM RESUMED] => Resubscribed from slot {:?}", request.from_slot).green().to_string());
+        events::publish(BotEvent::Stream { name: "arbitrage".to_string(), connected: true, slot: None });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_grpc_proto::geyser::SubscribeUpdateTransactionInfo;
+    use yellowstone_grpc_proto::prelude::{
+        CompiledInstruction, ConfirmedTransaction, Message, TokenBalance, Transaction as ProtoTransaction, UiTokenAmount,
+    };
+
+    use super::*;
+    use crate::common::rng::SessionRng;
+
+    /// A transaction signed by `signer` that moved its SOL from `pre` to `post`
+    fn signed_txn(signer: Pubkey, pre: u64, post: u64) -> SubscribeUpdateTransaction {
+        let message = Message {
+            account_keys: vec![signer.to_bytes().to_vec(), Pubkey::new_unique().to_bytes().to_vec()],
+            recent_blockhash: Hash::new_unique().to_bytes().to_vec(),
+            ..Default::default()
+        };
+        SubscribeUpdateTransaction {
+            transaction: Some(SubscribeUpdateTransactionInfo {
+                transaction: Some(ProtoTransaction { message: Some(message), ..Default::default() }),
+                meta: Some(TransactionStatusMeta {
+                    // The other account takes what the signer spent, less the fee
+                    pre_balances: vec![pre, 2_039_280],
+                    post_balances: vec![post, 2_039_280 + pre.saturating_sub(post).saturating_sub(5_000)],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            slot: 7,
+        }
+    }
+
+    #[tokio::test]
+    async fn plaintext_amounts_are_read_when_no_event_decodes() {
+        let signer = Pubkey::new_unique();
+        let logs = vec![
+            "Program log: Instruction: Buy".to_string(),
+            // The discriminator of a buy event over a body too short to be one
+            "Program data: Z/RSHyz1d3e44fgL1wrgeEx4VaRRcxt0X922d0nSP2N76QgrdelXWw==".to_string(),
+            "Program log: base_amount_out: 35000000000".to_string(),
+            "Program log: max_quote_amount_in: 1010000000".to_string(),
+        ];
+        let latency = LatencyTracer::start("copy", Stage::Received, tokio::time::Instant::now(), &SessionRng::from_seed(1));
+        let parsed = TradeInfoFromToken::from_json(signed_txn(signer, 5_000_000_000, 3_999_995_000), logs, latency).unwrap();
+        assert!(matches!(parsed.instruction_type, InstructionType::SwapBuy));
+        assert_eq!(parsed.base_amount_out, Some(35_000_000_000));
+        assert_eq!(parsed.max_quote_amount_in, Some(1_010_000_000));
+        // No event names the trader, so the signer stands in
+        assert_eq!(parsed.target, signer.to_string());
+    }
+
+    #[tokio::test]
+    async fn parsing_writes_nothing_at_info_level() {
+        if LogLevel::Debug.enabled() {
+            // LOG_LEVEL asked for the parser's detail
+            return;
+        }
+        let signer = Pubkey::new_unique();
+        let written = crate::common::logger::lines_written(|| {
+            parse(signed_txn(signer, 5_000_000_000, 3_999_995_000));
+        });
+        assert!(written.is_empty(), "{:?}", written);
+    }
+
+    #[tokio::test]
+    async fn watchdog_signals_only_the_quiet_stream() {
+        use futures::FutureExt;
+
+        let app_state = AppState::for_tests();
+        let logger = Logger::new(String::new());
+        let (quiet, other) = (StaleSignal::default(), StaleSignal::default());
+
+        // No message since the state was built, but not for long enough yet
+        app_state.clock.sleep(app_state.monitor.stale_after()).await;
+        check_connection_health(&app_state, &quiet, &logger).await;
+        assert!(quiet.stale().now_or_never().is_none());
+
+        app_state.clock.sleep(Duration::from_secs(1)).await;
+        check_connection_health(&app_state, &quiet, &logger).await;
+        assert!(quiet.stale().now_or_never().is_some());
+        // The report is consumed by the monitor acting on it
+        assert!(quiet.stale().now_or_never().is_none());
+        assert!(other.stale().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_waiting_monitor_wakes_on_its_report() {
+        let signal = Arc::new(StaleSignal::default());
+        let waiter = tokio::spawn({
+            let signal = Arc::clone(&signal);
+            async move { signal.stale().await }
+        });
+        tokio::task::yield_now().await;
+        signal.report();
+        tokio::time::timeout(Duration::from_secs(5), waiter).await.unwrap().unwrap();
+
+        // A report made while resubscribing is about the old stream
+        signal.report();
+        signal.clear();
+        assert!(futures::FutureExt::now_or_never(signal.stale()).is_none());
+    }
+
+    #[test]
+    fn copy_targets_survive_into_the_filter() {
+        let targets = copy_trading_targets(Some(" walletA, ,walletB ".to_string()), true);
+        assert_eq!(targets, vec!["walletA".to_string(), "walletB".to_string()]);
+        // Without multi mode the variable is one address, commas and all
+        assert_eq!(copy_trading_targets(Some("walletA".to_string()), false), vec!["walletA".to_string()]);
+        assert!(copy_trading_targets(Some(String::new()), false).is_empty());
+        assert!(copy_trading_targets(None, true).is_empty());
+
+        let filter = FilterConfig::copy_trading(vec![PUMP_PROGRAM.to_string()], targets.clone());
+        assert_eq!(filter.copy_trading_target_addresses, targets);
+        assert!(filter.dex_program_ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_buy_that_lands_with_an_error_reopens_buying() {
+        // The mock node reports every signature landed with an instruction error
+        let app_state = AppState::for_tests_with_rpc("instruction_error", Notifier::new(None));
+        app_state.monitor.set_buying_enabled(false);
+        let pools = TrackedPools::new();
+        let logger = Logger::new("[TEST] => ".to_string());
+
+        let signature = Signature::new_unique().to_string();
+        let landed = buy_landed(&app_state, &pools, Strategy::Copy, "mint", &signature, Hash::new_unique(), &logger).await;
+        assert!(!landed);
+        assert_eq!(pools.get("mint").unwrap().status, Status::Failure);
+        assert!(app_state.monitor.buying_enabled());
+    }
+
+    /// Refuses the first sell as a relay would under load, lands every one after
+    struct FlakySeller {
+        refused: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl exit_ladder::PositionSeller for FlakySeller {
+        async fn sell(&self, mint: &str, _stage: &exit_ladder::ExitStage) -> Result<exit_ladder::SellAttempt> {
+            if !self.refused.swap(true, Ordering::SeqCst) {
+                return Err(anyhow!("blockhash not found"));
             }
+            Ok(exit_ladder::SellAttempt { signature: format!("sold-{}", mint), sell_price: 2.0, blockhash: Hash::default() })
+        }
+
+        async fn confirm(&self, _attempt: &exit_ladder::SellAttempt, _timeout: Duration) -> confirmation::Confirmation {
+            confirmation::Confirmation::Confirmed
         }
     }
-    Ok(())
-}
 
+    #[tokio::test]
+    async fn a_sell_that_fails_once_then_lands_marks_the_position_sold() {
+        let app_state = AppState::for_tests();
+        app_state.monitor.set_buying_enabled(false);
+        let pools = TrackedPools::new();
+        pools.insert("mint".to_string(), LiquidityPool {
+            mint: "mint".to_string(),
+            buy_price: 1.0,
+            sell_price: 0.0,
+            status: Status::Bought,
+            timestamp: Some(Timestamps::now(app_state.clock.as_ref())),
+        });
+        let ladder = ExitLadder::default_for(Duration::from_secs(60), 1_000);
+        let seller = FlakySeller { refused: std::sync::atomic::AtomicBool::new(false) };
+        let logger = Logger::new("[TEST] => ".to_string());
+
+        let outcome = exit_ladder::run_ladder(&ladder, "mint", app_state.clock.now(), app_state.clock.as_ref(), &seller, &|| false, &logger).await;
+        assert!(matches!(outcome, LadderOutcome::Exited { stage: 0, .. }), "{:?}", outcome);
+        settle_exit(&app_state, &pools, "mint", None, outcome, &logger);
+
+        let pool = pools.get("mint").unwrap().clone();
+        assert_eq!(pool.status, Status::Sold);
+        assert_eq!((pool.buy_price, pool.sell_price), (1.0, 2.0));
+        assert!(app_state.monitor.buying_enabled());
+    }
+
+    #[tokio::test]
+    async fn a_position_held_past_max_wait_is_force_sold() {
+        let app_state = AppState::for_tests();
+        let clock = app_state.clock.as_ref();
+        let pools = TrackedPools::new();
+        pools.insert("mint".to_string(), LiquidityPool {
+            mint: "mint".to_string(),
+            buy_price: 1.0,
+            sell_price: 0.0,
+            status: Status::Bought,
+            timestamp: Some(Timestamps::now(clock)),
+        });
+        let ladder = ExitLadder::default_for(Duration::from_secs(60), 1_000);
+        let active_ladders = ActiveLadders::default();
+
+        clock.sleep(ladder.start_after()).await;
+        assert!(timed_out_positions(&pools, clock.now(), ladder.start_after()).is_empty());
+
+        // One more monitor tick on the mock clock, no real time passes
+        clock.sleep(Duration::from_secs(5)).await;
+        let due = timed_out_positions(&pools, clock.now(), ladder.start_after());
+        assert_eq!(due.iter().map(|(mint, _)| mint.as_str()).collect::<Vec<_>>(), ["mint"]);
+        let (mint, opened_at) = due[0].clone();
+        assert!(active_ladders.start(&mint));
+        assert!(!active_ladders.start(&mint), "a second tick must not start another ladder");
+
+        let seller = FlakySeller { refused: std::sync::atomic::AtomicBool::new(false) };
+        let logger = Logger::new("[TEST] => ".to_string());
+        let outcome = exit_ladder::run_ladder(&ladder, &mint, opened_at, clock, &seller, &|| false, &logger).await;
+        assert!(matches!(outcome, LadderOutcome::Exited { stage: 0, .. }), "{:?}", outcome);
+        settle_exit(&app_state, &pools, &mint, None, outcome, &logger);
+        assert_eq!(pools.get("mint").unwrap().status, Status::Sold);
+    }
+
+    #[test]
+    fn an_exhausted_ladder_marks_the_position_failed() {
+        let app_state = AppState::for_tests();
+        app_state.monitor.set_buying_enabled(false);
+        let pools = TrackedPools::new();
+        pools.insert("mint".to_string(), LiquidityPool {
+            mint: "mint".to_string(),
+            buy_price: 1.0,
+            sell_price: 0.0,
+            status: Status::Bought,
+            timestamp: None,
+        });
+
+        settle_exit(&app_state, &pools, "mint", None, LadderOutcome::Exhausted, &Logger::new("[TEST] => ".to_string()));
+
+        assert_eq!(pools.get("mint").unwrap().status, Status::Failure);
+        // Still holding the tokens, so buying stays closed
+        assert!(!app_state.monitor.buying_enabled());
+    }
+
+    /// A Raydium AMM v2 swap of `token` against SOL whose logs carry no
+    /// reserves, with the vaults' post balances as given
+    fn raydium_swap(token: Pubkey, base_vault_amount: &str, quote_vault_amount: &str) -> ConfirmedTransaction {
+        let keys = [
+            Pubkey::new_unique(),
+            spl_token::id(),
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_unique(),
+            Pubkey::new_from_array([4; 32]),
+            Pubkey::new_from_array([5; 32]),
+            Pubkey::from_str(crate::dex::raydium_amm::RAYDIUM_AMM_PROGRAM).unwrap(),
+        ];
+        let message = Message {
+            account_keys: keys.iter().map(|key| key.to_bytes().to_vec()).collect(),
+            recent_blockhash: Hash::new_unique().to_bytes().to_vec(),
+            instructions: vec![CompiledInstruction {
+                program_id_index: 6,
+                // token program, amm, authority, base and quote vaults, user
+                accounts: vec![1, 2, 3, 4, 5, 0],
+                data: vec![crate::dex::raydium_amm::SWAP_BASE_IN_V2, 0, 0, 0, 0, 0, 0, 0, 0],
+            }],
+            ..Default::default()
+        };
+        let balance = |account_index: u32, mint: String, amount: &str, decimals: u32| TokenBalance {
+            account_index,
+            mint,
+            ui_token_amount: Some(UiTokenAmount { amount: amount.to_string(), decimals, ..Default::default() }),
+            ..Default::default()
+        };
+        let meta = TransactionStatusMeta {
+            post_token_balances: vec![
+                balance(4, token.to_string(), base_vault_amount, 6),
+                balance(5, spl_token::native_mint::id().to_string(), quote_vault_amount, 9),
+            ],
+            ..Default::default()
+        };
+        ConfirmedTransaction {
+            transaction: Some(ProtoTransaction { message: Some(message), ..Default::default() }),
+            meta: Some(meta),
+        }
+    }
+
+    const RAYDIUM_LOGS: [&str; 3] = [
+        "Program 675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8 invoke [1]",
+        "Program log: ray_log: A0BCDwAAAAAAAAAAAAAAAAACAAAAAAAAAA==",
+        "Program 675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8 success",
+    ];
+
+    #[test]
+    fn a_swap_without_logged_reserves_is_priced_from_its_vaults() {
+        let token = Pubkey::new_unique();
+        let txn = raydium_swap(token, "800000000000000", "40000000000");
+        let logs: Vec<String> = RAYDIUM_LOGS.iter().map(|log| log.to_string()).collect();
+
+        let mut pool_info = extract_pool_info_from_transaction(&txn, &logs).unwrap().unwrap();
+        assert_eq!(pool_info.pool_id, Pubkey::new_from_array([1; 32]));
+        assert_eq!((pool_info.base_mint, pool_info.quote_mint), (token, spl_token::native_mint::id()));
+        assert_eq!(pool_info.reserves(), None);
+
+        fill_reserves_from_post_balances(&mut pool_info, &txn);
+        let (base, quote) = pool_info.reserves().unwrap();
+        assert_eq!((base, quote), (800_000_000_000_000, 40_000_000_000));
+        // 40 SOL over 800M tokens
+        let price = Price::from_reserves(&pool_info.pool_id.to_string(), base, quote, 6, 9).unwrap();
+        assert!((price.value() - 5e-8).abs() < 1e-18, "{}", price.value());
+    }
+
+    #[test]
+    fn an_empty_vault_leaves_the_swap_unpriced() {
+        let txn = raydium_swap(Pubkey::new_unique(), "800000000000000", "0");
+        let logs: Vec<String> = RAYDIUM_LOGS.iter().map(|log| log.to_string()).collect();
+
+        let mut pool_info = extract_pool_info_from_transaction(&txn, &logs).unwrap().unwrap();
+        fill_reserves_from_post_balances(&mut pool_info, &txn);
+        assert_eq!(pool_info.base_reserve, Some(800_000_000_000_000));
+        assert_eq!(pool_info.quote_reserve, None);
+        assert_eq!(pool_info.reserves(), None);
+    }
+
+    /// Gate that holds back any move of more than 50% in one update
+    fn fifty_percent_gate() -> PriceGate {
+        PriceGate::new(crate::core::price::PriceSanity {
+            max_jump: 1.5,
+            confirm_tolerance: 0.05,
+            quarantine_ttl: Duration::from_secs(60),
+        })
+    }
+
+    /// Run a log-less Raydium swap through the stream's pricing step
+    async fn price_raydium_swap(
+        app_state: &AppState,
+        txn: &ConfirmedTransaction,
+        prices: &rpc_fallback::PriceMap,
+        gate: &mut PriceGate,
+    ) -> Option<StreamedPrice> {
+        let logs: Vec<String> = RAYDIUM_LOGS.iter().map(|log| log.to_string()).collect();
+        let mut pool_info = extract_pool_info_from_transaction(txn, &logs).unwrap().unwrap();
+        price_streamed_swap(app_state, &mut pool_info, "raydium_amm", txn, 7, prices, gate, &Logger::new(String::new())).await
+    }
+
+    fn assert_all_usable(prices: &rpc_fallback::PriceMap) {
+        for entry in prices.iter() {
+            for (dex, (price, _, _, _)) in entry.value() {
+                assert!(price.value().is_finite() && price.value() > 0.0, "{} on {} stored at {}", entry.key(), dex, price);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_log_less_swap_enters_the_map_at_its_vault_price() {
+        let app_state = AppState::for_tests();
+        let (prices, mut gate) = (rpc_fallback::PriceMap::new(), fifty_percent_gate());
+        let token = Pubkey::new_unique();
+
+        let streamed = price_raydium_swap(&app_state, &raydium_swap(token, "800000000000000", "40000000000"), &prices, &mut gate).await;
+        let streamed = streamed.expect("priced from the meta's vault balances");
+        assert_eq!(streamed.token_mint, token.to_string());
+        assert_all_usable(&prices);
+        let stored = prices.get(&token.to_string()).unwrap()["raydium_amm"];
+        assert!((stored.0.value() - 5e-8).abs() < 1e-18, "{}", stored.0);
+        assert_eq!(stored.1, 40_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn a_vault_the_meta_leaves_empty_is_read_from_its_account() {
+        // The quote vault's account holds the 40 SOL the meta didn't report
+        let mocks = HashMap::from([(
+            anchor_client::solana_client::rpc_request::RpcRequest::GetMultipleAccounts,
+            rpc_fallback::mock_token_accounts(8, &[800_000_000_000_000, 40_000_000_000]),
+        )]);
+        let app_state = AppState::for_tests_with_mocks("succeeds", mocks, Notifier::new(None));
+        let (prices, mut gate) = (rpc_fallback::PriceMap::new(), fifty_percent_gate());
+        let token = Pubkey::new_unique();
+
+        let streamed = price_raydium_swap(&app_state, &raydium_swap(token, "800000000000000", "0"), &prices, &mut gate).await;
+        assert!(streamed.is_some());
+        assert_all_usable(&prices);
+        assert_eq!(prices.get(&token.to_string()).unwrap()["raydium_amm"].1, 40_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn a_vault_empty_everywhere_keeps_the_swap_out_of_the_map() {
+        let mocks = HashMap::from([(
+            anchor_client::solana_client::rpc_request::RpcRequest::GetMultipleAccounts,
+            rpc_fallback::mock_token_accounts(8, &[800_000_000_000_000, 0]),
+        )]);
+        let app_state = AppState::for_tests_with_mocks("succeeds", mocks, Notifier::new(None));
+        let (prices, mut gate) = (rpc_fallback::PriceMap::new(), fifty_percent_gate());
+        let token = Pubkey::new_unique();
+
+        let streamed = price_raydium_swap(&app_state, &raydium_swap(token, "800000000000000", "0"), &prices, &mut gate).await;
+        assert!(streamed.is_none());
+        assert!(prices.get(&token.to_string()).map_or(true, |dex_prices| dex_prices.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn a_jump_past_the_sanity_band_is_held_back() {
+        let app_state = AppState::for_tests();
+        let (prices, mut gate) = (rpc_fallback::PriceMap::new(), fifty_percent_gate());
+        let token = Pubkey::new_unique();
+        price_raydium_swap(&app_state, &raydium_swap(token, "800000000000000", "40000000000"), &prices, &mut gate).await.unwrap();
+
+        // Twice the SOL for the same tokens, double the price in one update
+        let jumped = price_raydium_swap(&app_state, &raydium_swap(token, "800000000000000", "80000000000"), &prices, &mut gate).await;
+        assert!(jumped.is_none());
+        let stored = prices.get(&token.to_string()).unwrap()["raydium_amm"].0;
+        assert!((stored.value() - 5e-8).abs() < 1e-18, "{}", stored);
+
+        // 40% up stays inside the band
+        let moved = price_raydium_swap(&app_state, &raydium_swap(token, "800000000000000", "56000000000"), &prices, &mut gate).await;
+        assert!(moved.is_some());
+        assert_all_usable(&prices);
+    }
+
+    fn parse(txn: SubscribeUpdateTransaction) -> TradeInfoFromToken {
+        let latency = LatencyTracer::start("copy", Stage::Received, tokio::time::Instant::now(), &SessionRng::from_seed(1));
+        TradeInfoFromToken::from_json(txn, Vec::new(), latency).unwrap()
+    }
+
+    #[tokio::test]
+    async fn volume_change_is_the_signers_signed_sol_delta() {
+        let signer = Pubkey::new_unique();
+        // Spent 1 SOL plus the 5000 lamport fee
+        let bought = parse(signed_txn(signer, 5_000_000_000, 3_999_995_000));
+        assert_eq!(bought.target, signer.to_string());
+        assert_eq!(bought.volume_change, -1_000_005_000);
+        assert!(bought.bonding_curve_info.is_none());
+
+        let sold = parse(signed_txn(signer, 1_000_000_000, 1_500_000_000));
+        assert_eq!(sold.volume_change, 500_000_000);
+    }
+}
@@ -0,0 +1,89 @@
+//! State the trading monitors share across their tasks for one run
+//!
+//! Whether a new position may be opened, the operator's entry pause, the
+//! price history of held tokens and when the stream last delivered used to
+//! be process globals. Kept on `AppState` instead, two monitors in one
+//! process (or a test) each get their own.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use tokio::time::Instant;
+
+use crate::common::clock::Timestamps;
+use crate::common::sync::LockExt;
+use crate::engine::stream_reconnect;
+
+#[derive(Clone, Debug)]
+pub struct TokenTrackingInfo {
+    pub top_pnl: f64,
+    pub last_price_check: Timestamps,
+    pub price_history: Vec<(f64, Timestamps)>,  // Store price history with timestamps
+}
+
+#[derive(Debug)]
+pub struct MonitorContext {
+    /// Cleared while a buy is in flight or a position is held
+    buying_enabled: Mutex<bool>,
+    /// Set from the TUI or admin API; unlike the buying gate the trade logic never clears it
+    entries_paused: Mutex<bool>,
+    pub token_tracking: Arc<DashMap<String, TokenTrackingInfo>>,
+    last_message_at: Mutex<Instant>,
+    /// Silence after which the watchdog forces a resubscribe
+    stale_after: Duration,
+}
+
+impl MonitorContext {
+    pub fn new(stale_after: Duration, now: Instant) -> Self {
+        Self {
+            buying_enabled: Mutex::new(true),
+            entries_paused: Mutex::new(false),
+            token_tracking: Arc::new(DashMap::new()),
+            last_message_at: Mutex::new(now),
+            stale_after,
+        }
+    }
+
+    /// `STALE_CONNECTION_SECS` for the watchdog, with the silence counted from `now`
+    pub fn from_env(now: Instant) -> Self {
+        Self::new(stream_reconnect::stale_after(), now)
+    }
+
+    pub fn buying_enabled(&self) -> bool {
+        *self.buying_enabled.lock_or_recover()
+    }
+
+    pub fn set_buying_enabled(&self, enabled: bool) {
+        *self.buying_enabled.lock_or_recover() = enabled;
+    }
+
+    pub fn entries_paused(&self) -> bool {
+        *self.entries_paused.lock_or_recover()
+    }
+
+    /// Stop (or resume) opening new positions; exits keep running
+    pub fn set_entries_paused(&self, paused: bool) {
+        *self.entries_paused.lock_or_recover() = paused;
+    }
+
+    pub fn stale_after(&self) -> Duration {
+        self.stale_after
+    }
+
+    /// A stream message arrived at `now`
+    pub fn note_message(&self, now: Instant) {
+        *self.last_message_at.lock_or_recover() = now;
+    }
+
+    /// How long the stream has been quiet as of `now`
+    pub fn silence(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(*self.last_message_at.lock_or_recover())
+    }
+
+    /// The silence as of `now` when it is past the stale threshold
+    pub fn stale_for(&self, now: Instant) -> Option<Duration> {
+        let silence = self.silence(now);
+        (silence > self.stale_after).then_some(silence)
+    }
+}
@@ -0,0 +1,139 @@
+//! Pool vault and state accounts the arbitrage stream subscribes to
+//!
+//! Swap logs only reprice pools someone trades through, so quiet pools go
+//! stale. Subscribing to the vaults of the cached pools delivers their
+//! balances whenever they change; for Raydium AMM the pool state account is
+//! watched too, since the PnL owed to the pool owner lives there and isn't
+//! swappable. Vaults are planned the same way the RPC fallback plans them.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use anyhow::Result;
+use spl_token::state::Account as TokenAccount;
+use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeUpdateAccount};
+
+use crate::common::config::AppState;
+use crate::common::sync::LockExt;
+use crate::dex::raydium_amm::RaydiumAmmPool;
+use crate::engine::rpc_fallback::{self, VaultPair};
+
+/// Name of the accounts filter in the subscribe request
+const FILTER_NAME: &str = "pool_accounts";
+
+/// `POOL_ACCOUNT_REFRESH_SECS`: how often pools new to the cache are added to
+/// the subscription, 0 to stream swaps only
+pub fn refresh_interval() -> Option<Duration> {
+    let secs = std::env::var("POOL_ACCOUNT_REFRESH_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Role {
+    BaseVault,
+    QuoteVault,
+    /// Raydium AMM pool state, for the PnL not in the reserves
+    AmmState,
+}
+
+/// Subscribed accounts and the last balances each pool's vaults reported
+#[derive(Debug, Default)]
+pub struct PoolAccountWatch {
+    pairs: Vec<VaultPair>,
+    balances: Vec<(Option<u64>, Option<u64>)>,
+    roles: HashMap<Pubkey, (usize, Role)>,
+}
+
+impl PoolAccountWatch {
+    pub fn watches(&self, pool_id: &str) -> bool {
+        self.pairs.iter().any(|pair| pair.pool_id == pool_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Start watching `pairs`; returns how many were new
+    pub fn add(&mut self, pairs: Vec<VaultPair>) -> usize {
+        let mut added = 0;
+        for pair in pairs {
+            if self.watches(&pair.pool_id) {
+                continue;
+            }
+            let index = self.pairs.len();
+            self.roles.insert(pair.base_vault, (index, Role::BaseVault));
+            self.roles.insert(pair.quote_vault, (index, Role::QuoteVault));
+            if pair.dex == "raydium_amm" {
+                if let Ok(pool_id) = pair.pool_id.parse::<Pubkey>() {
+                    self.roles.insert(pool_id, (index, Role::AmmState));
+                }
+            }
+            self.pairs.push(pair);
+            self.balances.push((None, None));
+            added += 1;
+        }
+        added
+    }
+
+    /// `base` with an accounts filter over everything watched. Yellowstone
+    /// replaces a subscription's filters with each request sent on it, so
+    /// this is both the resubscribe request and the live update.
+    pub fn request(&self, base: &SubscribeRequest) -> SubscribeRequest {
+        let mut request = base.clone();
+        if !self.roles.is_empty() {
+            request.accounts.insert(
+                FILTER_NAME.to_string(),
+                SubscribeRequestFilterAccounts {
+                    account: self.roles.keys().map(|key| key.to_string()).collect(),
+                    ..Default::default()
+                },
+            );
+        }
+        request
+    }
+
+    /// Fold an account update in; returns the pool with its raw vault
+    /// balances once both are known
+    pub fn apply(&mut self, update: &SubscribeUpdateAccount) -> Option<(VaultPair, u64, u64)> {
+        let account = update.account.as_ref()?;
+        let key = Pubkey::try_from(account.pubkey.as_slice()).ok()?;
+        let (index, role) = *self.roles.get(&key)?;
+        match role {
+            Role::BaseVault => self.balances[index].0 = Some(TokenAccount::unpack(&account.data).ok()?.amount),
+            Role::QuoteVault => self.balances[index].1 = Some(TokenAccount::unpack(&account.data).ok()?.amount),
+            Role::AmmState => {
+                let amm = RaydiumAmmPool::decode(key, &account.data).ok()?;
+                let pair = &mut self.pairs[index];
+                pair.base_pnl = amm.need_take_pnl_coin;
+                pair.quote_pnl = amm.need_take_pnl_pc;
+            }
+        }
+        let (Some(base), Some(quote)) = self.balances[index] else {
+            return None;
+        };
+        Some((self.pairs[index].clone(), base, quote))
+    }
+}
+
+/// Plan vaults for cached pools `watch` doesn't cover yet and add them;
+/// returns how many were added
+pub async fn watch_new_pools(app_state: &AppState, watch: &Mutex<PoolAccountWatch>) -> Result<usize> {
+    let pools = {
+        let watch = watch.lock_or_recover();
+        rpc_fallback::active_pools(app_state, |pool_id| watch.watches(pool_id))
+    };
+    if pools.is_empty() {
+        return Ok(0);
+    }
+    let pairs = rpc_fallback::plan_vaults(app_state, &pools).await?;
+    Ok(watch.lock_or_recover().add(pairs))
+}
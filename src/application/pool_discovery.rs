@@ -8,10 +8,36 @@ use solana_client::{
     rpc_filter::{RpcFilterType, Memcmp, MemcmpEncodedBytes},
 };
 use anchor_client::solana_sdk::{pubkey::Pubkey, account::Account};
-use std::{collections::HashMap, fs::{self, File}, path::Path, io::{Write, Read}, sync::{Arc, Mutex}};
+use std::{collections::{BTreeSet, HashMap}, fs::{self, File}, path::Path, io::{Write, Read}, sync::{Arc, Mutex}};
 use serde::{Serialize, Deserialize};
 
+use crate::common::metrics::POOL_HEALTH_SCORE;
+use crate::core::price::{BookTouch, Price};
 use crate::dex::dex_registry::DEXRegistry;
+use crate::dex::meteora_dlmm;
+use crate::dex::phoenix;
+use crate::dex::pump_swap;
+use crate::dex::raydium_amm::{self, RaydiumAmmPool};
+use crate::dex::whirlpool;
+use crate::engine::pool_health::{HealthConfig, HealthEvent, PoolHealth};
+use crate::services::rpc_pool::{CallClass, RpcPool};
+
+/// Pause between tokens during discovery, stretched while the RPC is throttled
+const DISCOVERY_PAUSE: std::time::Duration = std::time::Duration::from_millis(200);
+
+// Mint offsets of the pool layouts no adapter decodes yet
+/// Raydium CLMM `PoolState`: discriminator, bump, amm_config, owner, then the mints
+const RAYDIUM_CLMM_MINT_0_OFFSET: usize = 73;
+const RAYDIUM_CLMM_MINT_1_OFFSET: usize = 105;
+/// Raydium CPMM `PoolState`: discriminator, amm_config, creator, both vaults and the LP mint first
+const RAYDIUM_CPMM_MINT_0_OFFSET: usize = 168;
+const RAYDIUM_CPMM_MINT_1_OFFSET: usize = 200;
+/// Meteora dynamic AMM `Pool`: discriminator and LP mint, then the mints
+const METEORA_POOLS_MINT_A_OFFSET: usize = 40;
+const METEORA_POOLS_MINT_B_OFFSET: usize = 72;
+
+/// Pools kept per token when `MAX_POOLS_PER_TOKEN` is unset
+const DEFAULT_MAX_POOLS_PER_TOKEN: usize = 8;
 
 /// Structure to store pool information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +49,43 @@ pub struct PoolInfo {
     pub last_known_price: Option<f64>,
     pub last_updated: Option<i64>,
     pub liquidity: Option<u64>,
+    /// DEX-specific pool variant the executor needs up front, e.g. "amm_v4_no_market"
+    #[serde(default)]
+    pub subtype: Option<String>,
+    /// Top of book for order book venues, Whirlpool quotes at trade size and
+    /// DLMM active bins; None for other pools
+    #[serde(default)]
+    pub book: Option<BookSnapshot>,
+    /// None until the pool's first health event
+    #[serde(default)]
+    pub health: Option<PoolHealth>,
+    /// Swap fee read from the pool account, for venues that set it per pool;
+    /// None falls back to the venue's fee
+    #[serde(default)]
+    pub fee_bps: Option<u64>,
+}
+
+impl PoolInfo {
+    pub fn health_score(&self) -> Option<f64> {
+        self.health.as_ref().map(|health| health.score)
+    }
+}
+
+/// Best bid and ask of an order book market as of `slot`; `depth` is the
+/// quote liquidity near the touch that stands in for pool liquidity. A
+/// Whirlpool's are what a trade-sized buy and sell fill at.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub bid: f64,
+    pub ask: f64,
+    pub depth: u64,
+    pub slot: u64,
+}
+
+impl BookSnapshot {
+    pub fn touch(&self) -> Option<BookTouch> {
+        BookTouch::new(Price::new(self.bid)?, Price::new(self.ask)?)
+    }
 }
 
 /// Cache for token pools across different DEXes
@@ -30,6 +93,9 @@ pub struct PoolInfo {
 pub struct PoolCache {
     pub pools: HashMap<String, Vec<PoolInfo>>, // token_mint -> pools
     pub last_updated: Option<i64>,
+    /// Tokens demoted for inactivity; their pools stay cached but aren't scanned or refreshed
+    #[serde(default)]
+    pub dormant: BTreeSet<String>,
 }
 
 impl PoolCache {
@@ -38,6 +104,7 @@ impl PoolCache {
         Self {
             pools: HashMap::new(),
             last_updated: None,
+            dormant: BTreeSet::new(),
         }
     }
 
@@ -83,6 +150,27 @@ impl PoolCache {
         self.last_updated = Some(chrono::Utc::now().timestamp());
     }
 
+    /// Drop the lowest-liquidity pools of `token_mint` until at most `max`
+    /// remain; pools never priced count as empty
+    pub fn evict_beyond(&mut self, token_mint: &str, max: usize) -> Vec<PoolInfo> {
+        let Some(pools) = self.pools.get_mut(token_mint) else {
+            return Vec::new();
+        };
+        let mut evicted = Vec::new();
+        while pools.len() > max.max(1) {
+            let Some(lowest) = pools
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, pool)| pool.liquidity.unwrap_or(0))
+                .map(|(index, _)| index)
+            else {
+                break;
+            };
+            evicted.push(pools.swap_remove(lowest));
+        }
+        evicted
+    }
+
     /// Get pools for a token
     pub fn get_pools_for_token(&self, token_mint: &str) -> Option<&Vec<PoolInfo>> {
         self.pools.get(token_mint)
@@ -98,6 +186,9 @@ impl PoolCache {
 pub struct PoolCacheManager {
     cache: Arc<Mutex<PoolCache>>,
     file_path: String,
+    health: HealthConfig,
+    /// `MAX_POOLS_PER_TOKEN`: beyond it the lowest-liquidity pool is evicted
+    max_pools_per_token: usize,
 }
 
 impl PoolCacheManager {
@@ -107,6 +198,11 @@ impl PoolCacheManager {
         Ok(Self {
             cache: Arc::new(Mutex::new(cache)),
             file_path: file_path.to_string(),
+            health: HealthConfig::from_env(),
+            max_pools_per_token: std::env::var("MAX_POOLS_PER_TOKEN")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(DEFAULT_MAX_POOLS_PER_TOKEN),
         })
     }
 
@@ -116,24 +212,68 @@ impl PoolCacheManager {
         Ok(cache.clone())
     }
 
+    /// Scoring weights and the minimum score legs are routed through
+    pub fn health_config(&self) -> &HealthConfig {
+        &self.health
+    }
+
     /// Add a pool to the cache and save to disk
-    pub fn add_pool(&self, token_mint: &str, pool_info: PoolInfo) -> Result<()> {
+    pub fn add_pool(&self, token_mint: &str, mut pool_info: PoolInfo) -> Result<()> {
         let mut cache = self.cache.lock().map_err(|_| anyhow!("Failed to lock cache"))?;
+        // Rediscovering a pool keeps what refreshes and scoring learned about it,
+        // since discovery now runs while the scanner is already reading the cache
+        if let Some(existing) = cache
+            .get_pools_for_token(token_mint)
+            .and_then(|pools| pools.iter().find(|pool| pool.pool_id == pool_info.pool_id))
+        {
+            pool_info.last_known_price = pool_info.last_known_price.or(existing.last_known_price);
+            pool_info.last_updated = pool_info.last_updated.or(existing.last_updated);
+            pool_info.liquidity = pool_info.liquidity.or(existing.liquidity);
+            pool_info.book = pool_info.book.or(existing.book);
+            pool_info.health = pool_info.health.or_else(|| existing.health.clone());
+            pool_info.fee_bps = pool_info.fee_bps.or(existing.fee_bps);
+        }
         cache.add_pool(token_mint, pool_info);
+        cache.evict_beyond(token_mint, self.max_pools_per_token);
         cache.save(&self.file_path)?;
         Ok(())
     }
 
+    /// Upsert a pool seen trading on the stream, returning true when it was new
+    ///
+    /// Known pools only take the new price and liquidity in memory, like swap
+    /// health events; a new pool is written out straight away. Adding past the
+    /// per-token cap evicts the lowest-liquidity pool, which may be the new one.
+    pub fn observe_pool(&self, token_mint: &str, pool_info: PoolInfo) -> Result<bool> {
+        let mut cache = self.cache.lock().map_err(|_| anyhow!("Failed to lock cache"))?;
+        if let Some(existing) = cache
+            .pools
+            .get_mut(token_mint)
+            .and_then(|pools| pools.iter_mut().find(|pool| pool.pool_id == pool_info.pool_id))
+        {
+            existing.last_known_price = pool_info.last_known_price.or(existing.last_known_price);
+            existing.last_updated = pool_info.last_updated.or(existing.last_updated);
+            existing.liquidity = pool_info.liquidity.or(existing.liquidity);
+            return Ok(false);
+        }
+        let pool_id = pool_info.pool_id.clone();
+        cache.add_pool(token_mint, pool_info);
+        let evicted = cache.evict_beyond(token_mint, self.max_pools_per_token);
+        cache.save(&self.file_path)?;
+        Ok(!evicted.iter().any(|pool| pool.pool_id == pool_id))
+    }
+
     /// Update price information for a pool
-    pub fn update_pool_price(&self, token_mint: &str, pool_id: &str, price: f64, liquidity: u64) -> Result<()> {
+    pub fn update_pool_price(&self, token_mint: &str, pool_id: &str, price: Price, liquidity: u64) -> Result<()> {
         let mut cache = self.cache.lock().map_err(|_| anyhow!("Failed to lock cache"))?;
         
         if let Some(pools) = cache.pools.get_mut(token_mint) {
             for pool in pools.iter_mut() {
                 if pool.pool_id == pool_id {
-                    pool.last_known_price = Some(price);
+                    pool.last_known_price = Some(price.value());
                     pool.last_updated = Some(chrono::Utc::now().timestamp());
                     pool.liquidity = Some(liquidity);
+                    apply_health(pool, HealthEvent::Refreshed { price: price.value(), liquidity }, &self.health);
                     break;
                 }
             }
@@ -142,60 +282,125 @@ impl PoolCacheManager {
         cache.save(&self.file_path)?;
         Ok(())
     }
+
+    /// Mark a token dormant or active again and save to disk
+    pub fn set_dormant(&self, token_mint: &str, dormant: bool) -> Result<()> {
+        let mut cache = self.cache.lock().map_err(|_| anyhow!("Failed to lock cache"))?;
+        let changed = if dormant {
+            cache.dormant.insert(token_mint.to_string())
+        } else {
+            cache.dormant.remove(token_mint)
+        };
+        if changed {
+            cache.save(&self.file_path)?;
+        }
+        Ok(())
+    }
+
+    /// Update an order book market: its price is the mid, its liquidity the depth near the touch
+    pub fn update_book(&self, token_mint: &str, pool_id: &str, touch: BookTouch, depth: u64, slot: u64) -> Result<()> {
+        let mut cache = self.cache.lock().map_err(|_| anyhow!("Failed to lock cache"))?;
+        
+        if let Some(pool) = cache
+            .pools
+            .get_mut(token_mint)
+            .and_then(|pools| pools.iter_mut().find(|pool| pool.pool_id == pool_id))
+        {
+            pool.last_known_price = Some(touch.mid().value());
+            pool.last_updated = Some(chrono::Utc::now().timestamp());
+            pool.liquidity = Some(depth);
+            pool.book = Some(BookSnapshot { bid: touch.bid.value(), ask: touch.ask.value(), depth, slot });
+            apply_health(pool, HealthEvent::Refreshed { price: touch.mid().value(), liquidity: depth }, &self.health);
+        }
+        
+        cache.save(&self.file_path)?;
+        Ok(())
+    }
+
+    /// Fold a health event into a cached pool, returning its new score
+    ///
+    /// Unknown pools are ignored. Swaps are kept in memory only and reach the
+    /// file with the pool's next refresh.
+    pub fn record_health_event(&self, token_mint: &str, pool_id: &str, event: HealthEvent) -> Result<Option<f64>> {
+        let mut cache = self.cache.lock().map_err(|_| anyhow!("Failed to lock cache"))?;
+        let Some(pool) = cache
+            .pools
+            .get_mut(token_mint)
+            .and_then(|pools| pools.iter_mut().find(|pool| pool.pool_id == pool_id))
+        else {
+            return Ok(None);
+        };
+        let score = apply_health(pool, event, &self.health);
+        if event.persists() {
+            cache.save(&self.file_path)?;
+        }
+        Ok(Some(score))
+    }
+}
+
+fn apply_health(pool: &mut PoolInfo, event: HealthEvent, config: &HealthConfig) -> f64 {
+    let score = pool
+        .health
+        .get_or_insert_with(PoolHealth::default)
+        .apply(event, chrono::Utc::now().timestamp_millis(), config);
+    POOL_HEALTH_SCORE.with_label_values(&[&pool.dex_name, &pool.pool_id]).set(score);
+    score
 }
 
 /// Discover pools for a token across all supported DEXes
 pub async fn discover_pools_for_token(
-    rpc_client: &RpcClient, 
+    rpc_pool: &RpcPool,
     token_mint: &Pubkey,
 ) -> Result<Vec<PoolInfo>> {
     let mut pools = Vec::new();
-    let dex_registry = DEXRegistry::new();
-    
-    for dex in dex_registry.get_all_dexes() {
+    for dex in DEXRegistry::shared().get_all_dexes() {
         println!("Searching for {} pools for token {}", dex.name, token_mint);
         
-        // Get the offset for the token mint in the pool account data
-        // This is DEX-specific and would need to be adjusted for each DEX
-        let offset = match dex.name.as_str() {
-            "pumpswap" => 8, // Example offset, would need actual value
-            "raydium_amm" => 200, // Example offset
-            "raydium_clmm" => 300, // Example offset
-            "raydium_cpmm" => 100, // Example offset
-            "whirlpool" => 200, // Example offset
-            "meteora_dlmm" => 250, // Example offset
-            "meteora_pools" => 150, // Example offset
-            _ => continue, // Skip if offset is unknown
+        let Some(sides) = mint_sides(&dex.name) else {
+            continue;
         };
         
-        // Create filter to find pools containing the token mint
-        let filters = vec![
-            RpcFilterType::DataSize(dex.pool_account_size as u64),
-            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, &token_mint.to_string())),
-        ];
-        
-        // Query for pools
-        match get_program_accounts_with_filters(rpc_client, dex.program_id, Some(filters)) {
-            Ok(accounts) => {
-                for (pubkey, _account) in accounts {
-                    // Here we would parse the account data to extract more information
-                    // For now, we'll just create a basic PoolInfo
-                    let pool_info = PoolInfo {
-                        pool_id: pubkey.to_string(),
-                        dex_name: dex.name.clone(),
-                        base_mint: token_mint.to_string(),
-                        quote_mint: "11111111111111111111111111111111".to_string(), // Placeholder, would extract from account data
-                        last_known_price: None,
-                        last_updated: None,
-                        liquidity: None,
-                    };
-                    
-                    pools.push(pool_info);
-                    println!("Found pool {} on {}", pubkey, dex.name);
+        // The token can sit on either side of a pair, so each side is one query
+        for (token_offset, quote_offset) in sides {
+            // Order book markets vary in size, so they are matched on the mint alone
+            let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(token_offset, &token_mint.to_bytes()))];
+            if dex.pool_account_size > 0 {
+                filters.push(RpcFilterType::DataSize(dex.pool_account_size as u64));
+            }
+            
+            // Query for pools, paced as discovery so trading calls go first
+            let program_id = dex.program_id;
+            let query = rpc_pool.call(CallClass::Discovery, |client| {
+                let config = program_accounts_config(Some(filters.clone()));
+                async move { client.get_program_accounts_with_config(&program_id, config).await }
+            });
+            match query.await {
+                Ok(accounts) => {
+                    for (pubkey, account) in accounts {
+                        let Some(quote_mint) = read_mint(&account.data, quote_offset) else {
+                            continue;
+                        };
+                        let pool_info = PoolInfo {
+                            pool_id: pubkey.to_string(),
+                            dex_name: dex.name.clone(),
+                            base_mint: token_mint.to_string(),
+                            quote_mint: quote_mint.to_string(),
+                            last_known_price: None,
+                            last_updated: None,
+                            liquidity: None,
+                            subtype: pool_subtype(&dex.name, &pubkey, &account.data),
+                            book: None,
+                            health: None,
+                            fee_bps: pool_fee_bps(&dex.name, &pubkey, &account.data),
+                        };
+                        
+                        pools.push(pool_info);
+                        println!("Found pool {} on {}", pubkey, dex.name);
+                    }
+                },
+                Err(e) => {
+                    println!("Error discovering pools for {} on {}: {}", token_mint, dex.name, e);
                 }
-            },
-            Err(e) => {
-                println!("Error discovering pools for {} on {}: {}", token_mint, dex.name, e);
             }
         }
     }
@@ -203,36 +408,101 @@ pub async fn discover_pools_for_token(
     Ok(pools)
 }
 
+/// Variant of a discovered pool that changes how swaps against it are built
+fn pool_subtype(dex_name: &str, pool_id: &Pubkey, data: &[u8]) -> Option<String> {
+    match dex_name {
+        "raydium_amm" => RaydiumAmmPool::decode(*pool_id, data)
+            .ok()
+            .map(|pool| pool.subtype().as_str().to_string()),
+        _ => None,
+    }
+}
+
+/// Fee a pool account sets for itself: a DLMM pair's base fee, the
+/// volatility fee on top of it left out, and a Phoenix market's taker fee
+fn pool_fee_bps(dex_name: &str, pool_id: &Pubkey, data: &[u8]) -> Option<u64> {
+    match dex_name {
+        "meteora_dlmm" => meteora_dlmm::MeteoraDlmmPool::decode(*pool_id, data)
+            .ok()
+            .map(|pair| (pair.base_fee() * 10_000.0).round() as u64),
+        "phoenix" => phoenix::PhoenixMarket::decode(*pool_id, data).ok().map(|market| market.taker_fee_bps),
+        _ => None,
+    }
+}
+
+/// Quote mint read from the pool account, for layouts that are decoded
+/// (token offset, quote offset) pairs to search a DEX's pool accounts by;
+/// None for DEXes whose layout isn't known
+fn mint_sides(dex_name: &str) -> Option<Vec<(usize, usize)>> {
+    let (first, second) = match dex_name {
+        "pumpswap" => (pump_swap::POOL_BASE_MINT_OFFSET, pump_swap::POOL_QUOTE_MINT_OFFSET),
+        "raydium_amm" => (raydium_amm::COIN_MINT_OFFSET, raydium_amm::PC_MINT_OFFSET),
+        "raydium_clmm" => (RAYDIUM_CLMM_MINT_0_OFFSET, RAYDIUM_CLMM_MINT_1_OFFSET),
+        "raydium_cpmm" => (RAYDIUM_CPMM_MINT_0_OFFSET, RAYDIUM_CPMM_MINT_1_OFFSET),
+        "whirlpool" => (whirlpool::TOKEN_MINT_A_OFFSET, whirlpool::TOKEN_MINT_B_OFFSET),
+        "meteora_dlmm" => (meteora_dlmm::TOKEN_X_MINT_OFFSET, meteora_dlmm::TOKEN_Y_MINT_OFFSET),
+        "meteora_pools" => (METEORA_POOLS_MINT_A_OFFSET, METEORA_POOLS_MINT_B_OFFSET),
+        // Markets are priced with the token as base, so only that side is searched
+        "phoenix" => return Some(vec![(phoenix::BASE_MINT_OFFSET, phoenix::QUOTE_MINT_OFFSET)]),
+        _ => return None,
+    };
+    Some(vec![(first, second), (second, first)])
+}
+
+fn read_mint(data: &[u8], offset: usize) -> Option<Pubkey> {
+    data.get(offset..offset + 32).and_then(|bytes| Pubkey::try_from(bytes).ok())
+}
+
+fn program_accounts_config(filters: Option<Vec<RpcFilterType>>) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64Zstd),
+            ..RpcAccountInfoConfig::default()
+        },
+        with_context: Some(false),
+    }
+}
+
 /// Helper function to get program accounts with filters
 pub fn get_program_accounts_with_filters(
     client: &RpcClient,
     program: Pubkey,
     filters: Option<Vec<RpcFilterType>>,
 ) -> Result<Vec<(Pubkey, Account)>> {
-    let accounts = client
-        .get_program_accounts_with_config(
-            &program,
-            RpcProgramAccountsConfig {
-                filters,
-                account_config: RpcAccountInfoConfig {
-                    encoding: Some(UiAccountEncoding::Base64Zstd),
-                    ..RpcAccountInfoConfig::default()
-                },
-                with_context: Some(false),
-            },
-        )?;
+    let accounts = client.get_program_accounts_with_config(&program, program_accounts_config(filters))?;
     Ok(accounts)
 }
 
-/// Function to initialize pool cache for a list of token mints
+/// Number of pools across all tokens in the cache
+pub fn cached_pool_count(cache: &PoolCache) -> usize {
+    cache.pools.values().map(Vec::len).sum()
+}
+
+/// Start `discovery` on its own task, returning how many pools the cache
+/// already holds to scan with while it runs
+pub fn discover_in_background<F>(
+    cache_manager: &PoolCacheManager,
+    discovery: F,
+) -> (usize, tokio::task::JoinHandle<Result<()>>)
+where
+    F: std::future::Future<Output = Result<()>> + Send + 'static,
+{
+    let cached = cache_manager.get_cache().map(|cache| cached_pool_count(&cache)).unwrap_or(0);
+    (cached, tokio::spawn(discovery))
+}
+
+/// Discover pools for a list of token mints, adding each token's pools to
+/// the cache as soon as that token is done
 pub async fn initialize_pool_cache(
-    rpc_client: &RpcClient,
+    rpc_pool: &RpcPool,
     token_mints: &[Pubkey],
     cache_manager: &PoolCacheManager,
 ) -> Result<()> {
     for token_mint in token_mints {
         println!("Discovering pools for token {}", token_mint);
-        let pools = discover_pools_for_token(rpc_client, token_mint).await?;
+        let pools = discover_pools_for_token(rpc_pool, token_mint).await?;
+        tokio::time::sleep(rpc_pool.background_interval(DISCOVERY_PAUSE)).await;
         
         for pool in pools {
             cache_manager.add_pool(&token_mint.to_string(), pool)?;
@@ -240,4 +510,85 @@ pub async fn initialize_pool_cache(
     }
     
     Ok(())
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::pump_swap::SOL_MINT;
+    use crate::engine::arbitrage::{compute_optimal_size, LegFees};
+
+    const TOKEN_A: &str = "TokenA1111111111111111111111111111111111111";
+    const TOKEN_B: &str = "TokenB1111111111111111111111111111111111111";
+
+    fn pool(pool_id: &str, dex_name: &str, mint: &str, price: Option<f64>) -> PoolInfo {
+        PoolInfo {
+            pool_id: pool_id.to_string(),
+            dex_name: dex_name.to_string(),
+            base_mint: mint.to_string(),
+            quote_mint: SOL_MINT.to_string(),
+            last_known_price: price,
+            last_updated: price.map(|_| 1_700_000_000),
+            liquidity: price.map(|_| 100_000_000_000),
+            subtype: None,
+            book: None,
+            health: None,
+            fee_bps: None,
+        }
+    }
+
+    fn seeded_manager(name: &str) -> (Arc<PoolCacheManager>, String) {
+        let path = std::env::temp_dir()
+            .join(format!("pool_discovery_{}_{}.json", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let _ = fs::remove_file(&path);
+        let manager = Arc::new(PoolCacheManager::new(&path).unwrap());
+        manager.add_pool(TOKEN_A, pool("pool_a_pump", "PumpSwap", TOKEN_A, Some(0.001))).unwrap();
+        manager.add_pool(TOKEN_A, pool("pool_a_raydium", "Raydium AMM", TOKEN_A, Some(0.00105))).unwrap();
+        (manager, path)
+    }
+
+    #[tokio::test]
+    async fn cached_pools_are_scannable_before_discovery_finishes() {
+        let (manager, path) = seeded_manager("fast_path");
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+
+        let discovering = Arc::clone(&manager);
+        let (cached, discovery) = discover_in_background(&manager, async move {
+            // Stands in for a discovery stuck behind slow RPC calls
+            released.await.map_err(|_| anyhow!("discovery was never released"))?;
+            discovering.add_pool(TOKEN_B, pool("pool_b_pump", "PumpSwap", TOKEN_B, None))?;
+            // Rediscovered without a price, like a fresh program account scan
+            discovering.add_pool(TOKEN_A, pool("pool_a_pump", "PumpSwap", TOKEN_A, None))?;
+            Ok(())
+        });
+        assert_eq!(cached, 2);
+
+        tokio::task::yield_now().await;
+        assert!(!discovery.is_finished());
+        let cache = manager.get_cache().unwrap();
+        let pools = cache.get_pools_for_token(TOKEN_A).unwrap();
+        let buy = pools.iter().find(|p| p.pool_id == "pool_a_pump").unwrap();
+        let sell = pools.iter().find(|p| p.pool_id == "pool_a_raydium").unwrap();
+        let sizing = compute_optimal_size(buy, sell, LegFees { buy_bps: 25, sell_bps: 25 }, 10_000_000_000)
+            .expect("a 5% gap on the cached pools is an opportunity");
+        assert!(sizing.expected_profit_lamports > 0);
+        assert!(cache.get_pools_for_token(TOKEN_B).is_none());
+
+        release.send(()).unwrap();
+        discovery.await.unwrap().unwrap();
+
+        let cache = manager.get_cache().unwrap();
+        assert_eq!(cached_pool_count(&cache), 3);
+        assert!(cache.get_pools_for_token(TOKEN_B).is_some());
+        let kept = cache
+            .get_pools_for_token(TOKEN_A)
+            .unwrap()
+            .iter()
+            .find(|p| p.pool_id == "pool_a_pump")
+            .unwrap();
+        assert_eq!(kept.last_known_price, Some(0.001));
+        assert_eq!(kept.liquidity, Some(100_000_000_000));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,293 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+const HOUR_MS: f64 = 3_600_000.0;
+
+/// Opportunities whose net profit falls in the same band of this many bps tie
+const TIE_BAND_BPS: f64 = 5.0;
+
+/// Smoothing of the per-refresh price move, higher reacts faster
+const VOLATILITY_ALPHA: f64 = 0.2;
+
+/// Relative weight of each component in the score
+#[derive(Debug, Clone, Copy)]
+pub struct HealthWeights {
+    pub liquidity: f64,
+    pub volume: f64,
+    pub stability: f64,
+    pub reliability: f64,
+}
+
+/// `HEALTH_WEIGHT_LIQUIDITY`, `HEALTH_WEIGHT_VOLUME`, `HEALTH_WEIGHT_STABILITY`,
+/// `HEALTH_WEIGHT_RELIABILITY`, `HEALTH_LIQUIDITY_TARGET`,
+/// `HEALTH_SWAPS_PER_HOUR_TARGET`, `HEALTH_VOLATILITY_SCALE_BPS`,
+/// `HEALTH_FAILURE_HALF_LIFE_SECS` and `POOL_MIN_HEALTH`
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    pub weights: HealthWeights,
+    /// Lamports of liquidity that earn the full liquidity component
+    pub liquidity_target: u64,
+    /// Swap rate that earns the full volume component
+    pub swaps_per_hour_target: f64,
+    /// Typical move between refreshes, in bps, that halves the stability component
+    pub volatility_scale_bps: f64,
+    /// How fast a failure stops counting against a pool
+    pub failure_half_life_secs: f64,
+    /// Score a pool needs before a leg is routed through it, 0 disables the check
+    pub min_score: f64,
+}
+
+impl HealthConfig {
+    pub fn from_env() -> Self {
+        let parse = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .unwrap_or(default)
+        };
+        Self {
+            weights: HealthWeights {
+                liquidity: parse("HEALTH_WEIGHT_LIQUIDITY", 0.35),
+                volume: parse("HEALTH_WEIGHT_VOLUME", 0.2),
+                stability: parse("HEALTH_WEIGHT_STABILITY", 0.2),
+                reliability: parse("HEALTH_WEIGHT_RELIABILITY", 0.25),
+            },
+            liquidity_target: parse("HEALTH_LIQUIDITY_TARGET", 100_000_000_000.0) as u64,
+            swaps_per_hour_target: parse("HEALTH_SWAPS_PER_HOUR_TARGET", 60.0).max(1.0),
+            volatility_scale_bps: parse("HEALTH_VOLATILITY_SCALE_BPS", 100.0).max(1.0),
+            failure_half_life_secs: parse("HEALTH_FAILURE_HALF_LIFE_SECS", 3_600.0).max(1.0),
+            min_score: parse("POOL_MIN_HEALTH", 40.0).min(100.0),
+        }
+    }
+
+    /// Whether a pool with `score` (None when never scored) may carry a leg
+    pub fn allows(&self, score: Option<f64>) -> bool {
+        self.min_score <= 0.0 || score.is_some_and(|score| score >= self.min_score)
+    }
+}
+
+/// Something that happened to a pool and moves its score
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HealthEvent {
+    /// A refresh read the pool's price and liquidity
+    Refreshed { price: f64, liquidity: u64 },
+    /// A swap against the pool was seen on the stream
+    Swap,
+    /// A refreshed price was held back by the sanity band
+    Quarantined,
+    /// A transaction routed through the pool failed
+    ExecutionFailed,
+    /// A transaction routed through the pool landed
+    ExecutionSucceeded,
+}
+
+impl HealthEvent {
+    /// Swaps are too frequent to save the cache for; the next refresh persists them
+    pub fn persists(&self) -> bool {
+        !matches!(self, Self::Swap)
+    }
+}
+
+/// Each component on a 0 to 1 scale
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HealthComponents {
+    pub liquidity: f64,
+    pub volume: f64,
+    pub stability: f64,
+    pub reliability: f64,
+}
+
+/// Running health of one pool, kept in the pool cache
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoolHealth {
+    /// 0 to 100
+    pub score: f64,
+    /// Last refreshed liquidity in lamports
+    pub liquidity: u64,
+    /// Swaps seen, decayed with a one hour time constant
+    pub swaps_per_hour: f64,
+    /// Smoothed price move between refreshes, in bps
+    pub volatility_bps: f64,
+    /// Failures and quarantines, halved every failure half-life
+    pub failures: f64,
+    pub last_price: Option<f64>,
+    pub updated_at_ms: i64,
+}
+
+impl PoolHealth {
+    /// Fold `event` in at `now_ms` and recompute the score
+    pub fn apply(&mut self, event: HealthEvent, now_ms: i64, config: &HealthConfig) -> f64 {
+        self.decay(now_ms, config);
+        match event {
+            HealthEvent::Refreshed { price, liquidity } => {
+                if let Some(last) = self.last_price.filter(|last| *last > 0.0) {
+                    let move_bps = ((price / last) - 1.0).abs() * 10_000.0;
+                    if move_bps.is_finite() {
+                        self.volatility_bps += VOLATILITY_ALPHA * (move_bps - self.volatility_bps);
+                    }
+                }
+                self.last_price = Some(price);
+                self.liquidity = liquidity;
+            }
+            HealthEvent::Swap => self.swaps_per_hour += 1.0,
+            HealthEvent::Quarantined | HealthEvent::ExecutionFailed => self.failures += 1.0,
+            // A fill is direct evidence the pool works; it forgives half the history
+            HealthEvent::ExecutionSucceeded => self.failures /= 2.0,
+        }
+        self.score = score(&self.components(config), &config.weights);
+        self.score
+    }
+
+    pub fn components(&self, config: &HealthConfig) -> HealthComponents {
+        let depth = self.liquidity as f64 / config.liquidity_target.max(1) as f64;
+        HealthComponents {
+            // Square root so a half-deep pool isn't scored half as good
+            liquidity: depth.min(1.0).sqrt(),
+            volume: (self.swaps_per_hour / config.swaps_per_hour_target).min(1.0),
+            stability: 1.0 / (1.0 + self.volatility_bps / config.volatility_scale_bps),
+            reliability: 1.0 / (1.0 + self.failures),
+        }
+    }
+
+    fn decay(&mut self, now_ms: i64, config: &HealthConfig) {
+        if self.updated_at_ms > 0 && now_ms > self.updated_at_ms {
+            let elapsed_ms = (now_ms - self.updated_at_ms) as f64;
+            self.swaps_per_hour *= (-elapsed_ms / HOUR_MS).exp();
+            self.failures *= 0.5_f64.powf(elapsed_ms / 1_000.0 / config.failure_half_life_secs);
+        }
+        self.updated_at_ms = self.updated_at_ms.max(now_ms);
+    }
+}
+
+/// Weighted average of the components, scaled to 0 to 100
+pub fn score(components: &HealthComponents, weights: &HealthWeights) -> f64 {
+    let total = weights.liquidity + weights.volume + weights.stability + weights.reliability;
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let weighted = components.liquidity * weights.liquidity
+        + components.volume * weights.volume
+        + components.stability * weights.stability
+        + components.reliability * weights.reliability;
+    (weighted / total * 100.0).clamp(0.0, 100.0)
+}
+
+/// Rank two opportunities given as (net bps, health of the weaker leg): more
+/// profitable first, and within the same profit band the healthier pools first
+pub fn rank_opportunities(a: (f64, f64), b: (f64, f64)) -> Ordering {
+    let band = |net_bps: f64| (net_bps / TIE_BAND_BPS).floor();
+    band(b.0).total_cmp(&band(a.0)).then(b.1.total_cmp(&a.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: i64 = 1_700_000_000_000;
+    const SOL: u64 = 1_000_000_000;
+
+    fn config() -> HealthConfig {
+        HealthConfig {
+            weights: HealthWeights { liquidity: 0.35, volume: 0.2, stability: 0.2, reliability: 0.25 },
+            liquidity_target: 100 * SOL,
+            swaps_per_hour_target: 60.0,
+            volatility_scale_bps: 100.0,
+            failure_half_life_secs: 3_600.0,
+            min_score: 40.0,
+        }
+    }
+
+    /// A deep, steady pool that was just refreshed
+    fn refreshed(config: &HealthConfig) -> PoolHealth {
+        let mut health = PoolHealth::default();
+        health.apply(HealthEvent::Refreshed { price: 1.0, liquidity: 100 * SOL }, T0, config);
+        health
+    }
+
+    #[test]
+    fn failures_lower_the_score_and_fade() {
+        let config = config();
+        let mut health = refreshed(&config);
+        let clean = health.score;
+        // Full liquidity, stability and reliability, no volume yet
+        assert!((clean - 80.0).abs() < 1e-9, "{}", clean);
+
+        let once = health.apply(HealthEvent::ExecutionFailed, T0, &config);
+        let twice = health.apply(HealthEvent::Quarantined, T0, &config);
+        assert!(once < clean && twice < once, "{} {} {}", clean, once, twice);
+        assert!((health.components(&config).reliability - 1.0 / 3.0).abs() < 1e-9);
+
+        // One half-life later the two failures count as one
+        let faded = health.apply(HealthEvent::Swap, T0 + 3_600_000, &config);
+        assert!((health.failures - 1.0).abs() < 1e-9);
+        assert!(faded > twice);
+
+        // A fill forgives half of what is left
+        health.apply(HealthEvent::ExecutionSucceeded, T0 + 3_600_000, &config);
+        assert!((health.failures - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn swaps_raise_the_volume_component_until_the_target() {
+        let config = config();
+        let mut health = refreshed(&config);
+        let idle = health.score;
+        for _ in 0..30 {
+            health.apply(HealthEvent::Swap, T0, &config);
+        }
+        assert!((health.components(&config).volume - 0.5).abs() < 1e-9);
+        assert!((health.score - (idle + 10.0)).abs() < 1e-9, "{}", health.score);
+        for _ in 0..100 {
+            health.apply(HealthEvent::Swap, T0, &config);
+        }
+        assert_eq!(health.components(&config).volume, 1.0);
+        assert!((health.score - 100.0).abs() < 1e-9);
+
+        // An hour without swaps decays the rate by e
+        health.apply(HealthEvent::ExecutionSucceeded, T0 + 3_600_000, &config);
+        assert!((health.swaps_per_hour - 130.0 / std::f64::consts::E).abs() < 1e-6);
+    }
+
+    #[test]
+    fn price_jumps_between_refreshes_cost_stability() {
+        let config = config();
+        let mut health = refreshed(&config);
+        let steady = health.score;
+        health.apply(HealthEvent::Refreshed { price: 1.05, liquidity: 100 * SOL }, T0 + 1_000, &config);
+        // A 500 bps move folds in at 20%: 100 bps of volatility halves stability
+        assert!((health.volatility_bps - 100.0).abs() < 1e-6, "{}", health.volatility_bps);
+        assert!((health.components(&config).stability - 0.5).abs() < 1e-6);
+        assert!(health.score < steady);
+    }
+
+    #[test]
+    fn shallow_pools_score_lower() {
+        let config = config();
+        let mut health = PoolHealth::default();
+        health.apply(HealthEvent::Refreshed { price: 1.0, liquidity: 25 * SOL }, T0, &config);
+        assert!((health.components(&config).liquidity - 0.5).abs() < 1e-9);
+        health.apply(HealthEvent::Refreshed { price: 1.0, liquidity: 0 }, T0, &config);
+        assert_eq!(health.components(&config).liquidity, 0.0);
+        // Only stability and reliability left
+        assert!((health.score - 45.0).abs() < 1e-9, "{}", health.score);
+    }
+
+    #[test]
+    fn minimum_score_gates_unscored_and_weak_pools() {
+        let config = config();
+        assert!(config.allows(Some(40.0)));
+        assert!(!config.allows(Some(39.9)));
+        assert!(!config.allows(None));
+        let off = HealthConfig { min_score: 0.0, ..config };
+        assert!(off.allows(None));
+    }
+
+    #[test]
+    fn ties_in_profit_go_to_the_healthier_pools() {
+        let mut opportunities = vec![(12.0, 90.0), (31.0, 20.0), (11.0, 40.0), (14.0, 95.0)];
+        opportunities.sort_by(|a, b| rank_opportunities(*a, *b));
+        assert_eq!(opportunities, vec![(31.0, 20.0), (14.0, 95.0), (12.0, 90.0), (11.0, 40.0)]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Re-reading an opportunity's pools right before it is executed
+//!
+//! The scan compares prices as the map held them when it ran, and every
+//! execution ahead of an opportunity in the same scan adds to its age. By
+//! the time it is acted on, someone else may already have closed the gap.
+//! An opportunity older than `PREFLIGHT_MAX_AGE_MS` has both pools' vaults
+//! read again in one batched `getMultipleAccounts`, the same reader RPC
+//! polling uses, and is sized and costed again on those reserves. When the
+//! net left at that size no longer clears the arbitrage threshold it is
+//! dropped as `[STALE OPPORTUNITY]`, before any fee is estimated or anything
+//! is signed. One whose vaults can't be read is dropped too.
+//!
+//! Only pools whose vaults the reader knows (PumpSwap and Raydium AMM) can be
+//! re-read. Opportunities with a leg elsewhere go on to execution, which
+//! reads both pools itself before sizing.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use tokio::time::Instant;
+
+use crate::common::config::AppState;
+use crate::core::price::Price;
+use crate::dex::pump_swap::SOL_MINT;
+use crate::engine::arbitrage::{compute_optimal_size, LegFees, OptimalSize};
+use crate::engine::execution::ArbitrageLegs;
+use crate::engine::pool_discovery::PoolInfo;
+use crate::engine::rpc_fallback::{self, VaultPair};
+
+/// Default for `PREFLIGHT_MAX_AGE_MS`: every opportunity is re-read
+const DEFAULT_MAX_AGE: Duration = Duration::ZERO;
+
+/// `PREFLIGHT_MAX_AGE_MS`: how old an opportunity may be and still go out
+/// on the scan's prices
+pub fn max_age() -> Duration {
+    std::env::var("PREFLIGHT_MAX_AGE_MS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_MAX_AGE)
+}
+
+/// What an opportunity has to clear once re-read
+#[derive(Debug, Clone, Copy)]
+pub struct Hurdle {
+    /// Most it may put in: the position cap within the arbitrage allocation
+    pub cap_lamports: u64,
+    /// Signature fee, priority fee and tip of the one transaction
+    pub fixed_cost_lamports: u64,
+    /// Net profit in basis points of the size that it must beat
+    pub threshold_bps: f64,
+}
+
+/// What the refresh made of an opportunity
+#[derive(Debug, Clone, Copy)]
+pub enum Preflight {
+    /// Younger than `PREFLIGHT_MAX_AGE_MS`, the scan's prices stand
+    Fresh,
+    /// A leg the batched reader can't price
+    Unchecked,
+    /// Still clears the threshold on reserves read at `slot`
+    Confirmed { sizing: OptimalSize, net_bps: f64, slot: u64 },
+    /// No longer clears it; `net_bps` is None when no size is profitable at all
+    Stale { net_bps: Option<f64>, slot: u64 },
+}
+
+/// Re-read both pools of `legs`, detected at `detected_at`, when it is
+/// older than `max_age`, and check it against `hurdle` at its new size
+pub async fn refresh(
+    app_state: &Arc<AppState>,
+    legs: ArbitrageLegs<'_>,
+    detected_at: Instant,
+    max_age: Duration,
+    hurdle: Hurdle,
+) -> Result<Preflight> {
+    if app_state.clock.now().saturating_duration_since(detected_at) < max_age {
+        return Ok(Preflight::Fresh);
+    }
+    let cache = app_state.pool_cache_manager.get_cache()?;
+    let cached = |pool_id: &str| {
+        cache
+            .pools
+            .get(legs.token)
+            .and_then(|pools| pools.iter().find(|pool| pool.pool_id == pool_id))
+            .cloned()
+    };
+    let (Some(buy_pool), Some(sell_pool)) = (cached(legs.buy_pool), cached(legs.sell_pool)) else {
+        return Ok(Preflight::Unchecked);
+    };
+    let pools = [(legs.token.to_string(), buy_pool.clone()), (legs.token.to_string(), sell_pool.clone())];
+    let pairs = rpc_fallback::plan_vaults(app_state, &pools).await?;
+    let pair_of = |pool: &PoolInfo| pairs.iter().find(|pair| pair.pool_id == pool.pool_id).cloned();
+    let (Some(buy_pair), Some(sell_pair)) = (pair_of(&buy_pool), pair_of(&sell_pool)) else {
+        return Ok(Preflight::Unchecked);
+    };
+
+    let (balances, slot) = rpc_fallback::read_vaults(app_state, &[buy_pair.clone(), sell_pair.clone()]).await?;
+    let balance = |index: usize| balances.get(index).copied().flatten();
+    let buy = reread(app_state, buy_pool, &buy_pair, balance(0))?;
+    let sell = reread(app_state, sell_pool, &sell_pair, balance(1))?;
+
+    let registry = &app_state.dex_registry;
+    let fees = LegFees {
+        buy_bps: registry.pool_fee_bps(legs.buy_dex, buy.fee_bps),
+        sell_bps: registry.pool_fee_bps(legs.sell_dex, sell.fee_bps),
+    };
+    let Some(sizing) = compute_optimal_size(&buy, &sell, fees, hurdle.cap_lamports) else {
+        return Ok(Preflight::Stale { net_bps: None, slot });
+    };
+    let net = sizing.expected_profit_lamports as f64 - hurdle.fixed_cost_lamports as f64;
+    let net_bps = net / sizing.amount_in_lamports.max(1) as f64 * 10_000.0;
+    Ok(if net_bps > hurdle.threshold_bps {
+        Preflight::Confirmed { sizing, net_bps, slot }
+    } else {
+        Preflight::Stale { net_bps: Some(net_bps), slot }
+    })
+}
+
+/// `pool` as its vaults hold it now, priced in SOL; the book of a quoted
+/// venue doesn't apply to reserves, so it is dropped
+fn reread(app_state: &AppState, pool: PoolInfo, pair: &VaultPair, balance: Option<(u64, u64)>) -> Result<PoolInfo> {
+    let (base, quote) = balance.ok_or_else(|| anyhow!("vaults of {} could not be read", pair.pool_id))?;
+    let (base, quote) = pair.swappable(base, quote);
+    let now = app_state.clock.now();
+    let normalizer = &app_state.price_normalizer;
+    let quoted = Price::from_reserves(&pair.pool_id, base, quote, pair.base_decimals, pair.quote_decimals)?;
+    let (Some(price), Some(liquidity)) = (normalizer.to_sol(quoted, pair.quote, now), normalizer.lamports(quote, pair.quote, now)) else {
+        return Err(anyhow!("{} is quoted in {}, no fresh SOL/USDC rate to convert at", pair.pool_id, pair.quote.name()));
+    };
+    Ok(PoolInfo {
+        base_mint: pair.mint.clone(),
+        quote_mint: SOL_MINT.to_string(),
+        last_known_price: Some(price.value()),
+        liquidity: Some(liquidity),
+        book: None,
+        ..pool
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anchor_client::solana_client::rpc_request::RpcRequest;
+    use anchor_client::solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::common::format::remember_decimals;
+    use crate::engine::quote_currency::{LegQuotes, QuoteAsset};
+    use crate::services::notifier::Notifier;
+
+    const SLOT: u64 = 42;
+    const HURDLE: Hurdle = Hurdle { cap_lamports: 100_000_000, fixed_cost_lamports: 5_000, threshold_bps: 50.0 };
+
+    /// Two cached PumpSwap pools of a fresh 6-decimal token
+    struct Opportunity {
+        token: String,
+        buy_pool: String,
+        sell_pool: String,
+    }
+
+    impl Opportunity {
+        fn cached_in(app_state: &AppState) -> Self {
+            let token = Pubkey::new_unique().to_string();
+            remember_decimals(&token, 6);
+            let (buy_pool, sell_pool) = (Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string());
+            for pool_id in [&buy_pool, &sell_pool] {
+                let pool = PoolInfo {
+                    pool_id: pool_id.clone(),
+                    dex_name: "pumpswap".to_string(),
+                    base_mint: token.clone(),
+                    quote_mint: SOL_MINT.to_string(),
+                    last_known_price: Some(0.0001),
+                    last_updated: None,
+                    liquidity: Some(100_000_000_000),
+                    subtype: None,
+                    book: None,
+                    health: None,
+                    fee_bps: None,
+                };
+                app_state.pool_cache_manager.add_pool(&token, pool).unwrap();
+            }
+            Self { token, buy_pool, sell_pool }
+        }
+
+        fn legs(&self) -> ArbitrageLegs<'_> {
+            ArbitrageLegs {
+                token: &self.token,
+                buy_dex: "pumpswap",
+                buy_pool: &self.buy_pool,
+                sell_dex: "pumpswap",
+                sell_pool: &self.sell_pool,
+                profit_pct: 10.0,
+                planned: None,
+                quotes: LegQuotes { buy: QuoteAsset::Sol, sell: QuoteAsset::Sol, reference: None },
+            }
+        }
+    }
+
+    /// A state whose RPC pool answers one vault read with `amounts`:
+    /// base and quote of the buy pool, then of the sell pool
+    fn reading(amounts: &[u64]) -> Arc<AppState> {
+        let mocks = HashMap::from([(RpcRequest::GetMultipleAccounts, rpc_fallback::mock_token_accounts(SLOT, amounts))]);
+        AppState::for_tests_with_mocks("succeeds", mocks, Notifier::new(None))
+    }
+
+    #[tokio::test]
+    async fn an_opportunity_inside_the_window_keeps_the_scan_prices() {
+        let app_state = AppState::for_tests();
+        let opportunity = Opportunity::cached_in(&app_state);
+        let detected_at = app_state.clock.now();
+
+        app_state.clock.sleep(Duration::from_millis(999)).await;
+        let preflight = refresh(&app_state, opportunity.legs(), detected_at, Duration::from_secs(1), HURDLE).await.unwrap();
+        assert!(matches!(preflight, Preflight::Fresh), "{:?}", preflight);
+    }
+
+    #[tokio::test]
+    async fn reserves_that_closed_the_gap_make_it_stale() {
+        // Both pools hold 1M tokens against 100 SOL again
+        let app_state = reading(&[1_000_000_000_000, 100_000_000_000, 1_000_000_000_000, 100_000_000_000]);
+        let opportunity = Opportunity::cached_in(&app_state);
+
+        let preflight = refresh(&app_state, opportunity.legs(), app_state.clock.now(), Duration::ZERO, HURDLE).await.unwrap();
+        assert!(matches!(preflight, Preflight::Stale { net_bps: None, slot: SLOT }), "{:?}", preflight);
+    }
+
+    #[tokio::test]
+    async fn a_gap_that_still_clears_is_confirmed_at_its_new_size() {
+        // The sell pool pays 10% more; the best size is past the cap, so the cap is traded
+        let app_state = reading(&[1_000_000_000_000, 100_000_000_000, 1_000_000_000_000, 110_000_000_000]);
+        let opportunity = Opportunity::cached_in(&app_state);
+
+        let preflight = refresh(&app_state, opportunity.legs(), app_state.clock.now(), Duration::ZERO, HURDLE).await.unwrap();
+        let Preflight::Confirmed { sizing, net_bps, slot } = preflight else {
+            panic!("expected a confirmation, got {:?}", preflight);
+        };
+        assert_eq!((sizing.amount_in_lamports, slot), (HURDLE.cap_lamports, SLOT));
+        assert!((9_000_000..9_500_000).contains(&sizing.expected_profit_lamports), "{:?}", sizing);
+        assert!((net_bps - 922.8).abs() < 1.0, "{}", net_bps);
+    }
+
+    #[tokio::test]
+    async fn vaults_that_cannot_be_read_are_an_error() {
+        // The mock node has no vault accounts to hand back
+        let app_state = AppState::for_tests_with_rpc("fails", Notifier::new(None));
+        let opportunity = Opportunity::cached_in(&app_state);
+
+        assert!(refresh(&app_state, opportunity.legs(), app_state.clock.now(), Duration::ZERO, HURDLE).await.is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::common::config::AppState;
+use crate::engine::pool_discovery::{BookSnapshot, PoolInfo};
+use crate::engine::refresh_scheduler::{book_depth_bps, read_book, read_pool_price};
+use crate::engine::swap::SwapDirection;
+
+/// Executable price of one venue for a quoted size
+#[derive(Debug, Clone, Serialize)]
+pub struct VenueQuote {
+    pub venue: String,
+    pub pool_id: String,
+    /// SOL per token before impact and fees: pool price, or the touch for order books
+    pub price: f64,
+    /// Average SOL per token the whole size fills at, fees included
+    pub executable_price: f64,
+    /// Price impact of the size, None when the venue's liquidity is unknown or
+    /// the size runs past an order book's visible depth
+    pub impact_bps: Option<f64>,
+    pub fee_bps: u64,
+    /// Lamports of liquidity the impact was estimated against
+    pub liquidity: Option<u64>,
+    /// Seconds since the price was read
+    pub age_secs: Option<i64>,
+    /// Older than `QUOTE_MAX_AGE_SECS`
+    pub stale: bool,
+    /// Read from chain for this quote instead of served from the cache
+    pub refreshed: bool,
+    pub health: Option<f64>,
+}
+
+/// Per-venue executable prices from the bot's pool cache, for outside tools
+///
+/// Quotes are sized in lamports of SOL notional ("sell 2 SOL worth of X") and
+/// ranked best first for the requested side, fresh quotes ahead of stale ones.
+/// Impact is the constant-product estimate against the cached SOL-side
+/// liquidity; order books fill at the touch within their visible depth.
+pub struct QuoteService {
+    app_state: Arc<AppState>,
+    max_age_secs: i64,
+}
+
+impl QuoteService {
+    /// `QUOTE_MAX_AGE_SECS` sets when a cached price counts as stale (default 30)
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        let max_age_secs = std::env::var("QUOTE_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(30);
+        Self { app_state, max_age_secs }
+    }
+
+    /// Quote `amount_lamports` of `mint` on every cached venue, or only `venue`
+    pub fn quote(&self, mint: &str, direction: SwapDirection, amount_lamports: u64, venue: Option<&str>) -> Vec<VenueQuote> {
+        let now = chrono::Utc::now().timestamp();
+        let quotes = self
+            .pools(mint, venue)
+            .iter()
+            .filter_map(|pool| self.venue_quote(pool, &direction, amount_lamports, now, false))
+            .collect();
+        rank(quotes, &direction)
+    }
+
+    /// Like `quote`, re-reading stale venues from chain first
+    ///
+    /// Refreshed values are only used for the answer; the cache keeps being
+    /// written by the refresh loop, behind its price sanity gate.
+    pub async fn quote_refreshed(
+        &self,
+        mint: &str,
+        direction: SwapDirection,
+        amount_lamports: u64,
+        venue: Option<&str>,
+    ) -> Vec<VenueQuote> {
+        let now = chrono::Utc::now().timestamp();
+        let mut quotes = Vec::new();
+        for mut pool in self.pools(mint, venue) {
+            let stale = pool.last_updated.map_or(true, |at| now - at > self.max_age_secs);
+            let refreshed = stale && self.refresh(&mut pool).await;
+            if refreshed {
+                pool.last_updated = Some(now);
+            }
+            if let Some(quote) = self.venue_quote(&pool, &direction, amount_lamports, now, refreshed) {
+                quotes.push(quote);
+            }
+        }
+        rank(quotes, &direction)
+    }
+
+    fn pools(&self, mint: &str, venue: Option<&str>) -> Vec<PoolInfo> {
+        self.app_state
+            .pool_cache_manager
+            .get_cache()
+            .ok()
+            .and_then(|cache| cache.pools.get(mint).cloned())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|pool| venue.map_or(true, |venue| pool.dex_name == venue))
+            .collect()
+    }
+
+    /// Overwrite the pool's price and liquidity with a chain read, false if it failed
+    async fn refresh(&self, pool: &mut PoolInfo) -> bool {
+        if self.app_state.dex_registry.is_order_book(&pool.dex_name) {
+            let Ok((touch, depth, slot)) = read_book(&self.app_state, pool, book_depth_bps()).await else {
+                return false;
+            };
+            let depth = depth.or(pool.liquidity).unwrap_or(0);
+            pool.last_known_price = Some(touch.mid().value());
+            pool.liquidity = Some(depth);
+            pool.book = Some(BookSnapshot { bid: touch.bid.value(), ask: touch.ask.value(), depth, slot });
+            return true;
+        }
+        match read_pool_price(&self.app_state, pool).await {
+            Ok(Some((price, liquidity))) => {
+                pool.last_known_price = Some(price.value());
+                pool.liquidity = liquidity.or(pool.liquidity);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn venue_quote(
+        &self,
+        pool: &PoolInfo,
+        direction: &SwapDirection,
+        amount_lamports: u64,
+        now: i64,
+        refreshed: bool,
+    ) -> Option<VenueQuote> {
+        let fee_bps = self.app_state.dex_registry.fee_bps(&pool.dex_name);
+        let (price, executable_price, impact_bps) = match pool.book {
+            Some(book) => book_fill(&book, direction, amount_lamports, fee_bps)?,
+            None => pool_fill(pool.last_known_price?, pool.liquidity, direction, amount_lamports, fee_bps)?,
+        };
+        let age_secs = pool.last_updated.map(|at| (now - at).max(0));
+        Some(VenueQuote {
+            venue: pool.dex_name.clone(),
+            pool_id: pool.pool_id.clone(),
+            price,
+            executable_price,
+            impact_bps,
+            fee_bps,
+            liquidity: pool.liquidity,
+            age_secs,
+            stale: age_secs.map_or(true, |age| age > self.max_age_secs),
+            refreshed,
+            health: pool.health_score(),
+        })
+    }
+}
+
+/// Constant-product fill of `amount` lamports against `liquidity` lamports on the SOL side
+fn pool_fill(
+    price: f64,
+    liquidity: Option<u64>,
+    direction: &SwapDirection,
+    amount: u64,
+    fee_bps: u64,
+) -> Option<(f64, f64, Option<f64>)> {
+    if !price.is_finite() || price <= 0.0 {
+        return None;
+    }
+    let fee = fee_bps as f64 / 10_000.0;
+    let ratio = liquidity
+        .filter(|liquidity| *liquidity > 0)
+        .map(|liquidity| amount as f64 * (1.0 - fee) / liquidity as f64);
+    // Unknown liquidity quotes at the price plus fees, with the impact left unknown
+    let r = ratio.unwrap_or(0.0);
+    Some(match direction {
+        SwapDirection::Buy => (price, price * (1.0 + r) / (1.0 - fee), ratio.map(|r| r * 10_000.0)),
+        SwapDirection::Sell => (price, price * (1.0 - fee) / (1.0 + r), ratio.map(|r| r / (1.0 + r) * 10_000.0)),
+    })
+}
+
+/// Order books fill at the touch while the size stays within the visible depth
+fn book_fill(book: &BookSnapshot, direction: &SwapDirection, amount: u64, fee_bps: u64) -> Option<(f64, f64, Option<f64>)> {
+    let fee = fee_bps as f64 / 10_000.0;
+    let within_depth = (amount <= book.depth).then_some(0.0);
+    match direction {
+        SwapDirection::Buy if book.ask > 0.0 => Some((book.ask, book.ask * (1.0 + fee), within_depth)),
+        SwapDirection::Sell if book.bid > 0.0 => Some((book.bid, book.bid * (1.0 - fee), within_depth)),
+        _ => None,
+    }
+}
+
+/// Fresh before stale, then cheapest first when buying and richest first when selling
+fn rank(mut quotes: Vec<VenueQuote>, direction: &SwapDirection) -> Vec<VenueQuote> {
+    quotes.sort_by(|a, b| {
+        let by_price = match direction {
+            SwapDirection::Buy => a.executable_price.total_cmp(&b.executable_price),
+            SwapDirection::Sell => b.executable_price.total_cmp(&a.executable_price),
+        };
+        a.stale.cmp(&b.stale).then(by_price)
+    });
+    quotes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::pump_swap::SOL_MINT;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn pool(pool_id: &str, dex_name: &str, mint: &str, price: f64, liquidity: Option<u64>, age_secs: i64) -> PoolInfo {
+        PoolInfo {
+            pool_id: pool_id.to_string(),
+            dex_name: dex_name.to_string(),
+            base_mint: mint.to_string(),
+            quote_mint: SOL_MINT.to_string(),
+            last_known_price: Some(price),
+            last_updated: Some(chrono::Utc::now().timestamp() - age_secs),
+            liquidity,
+            subtype: None,
+            book: None,
+            health: None,
+            fee_bps: None,
+        }
+    }
+
+    /// Three AMM venues and an order book for `mint`, one AMM quoting cheapest but an hour old
+    fn seeded(mint: &str) -> QuoteService {
+        let app_state = AppState::for_tests();
+        let cache = &app_state.pool_cache_manager;
+        cache.add_pool(mint, pool("ray", "raydium_amm", mint, 0.001, Some(1_000 * SOL), 5)).unwrap();
+        cache.add_pool(mint, pool("orca", "whirlpool", mint, 0.00099, Some(1_000 * SOL), 2)).unwrap();
+        cache.add_pool(mint, pool("pump", "pumpswap", mint, 0.0009, Some(1_000 * SOL), 3_600)).unwrap();
+        let mut book = pool("phx", "phoenix", mint, 0.00103, Some(5 * SOL), 1);
+        book.book = Some(BookSnapshot { bid: 0.00102, ask: 0.00104, depth: 5 * SOL, slot: 1 });
+        cache.add_pool(mint, book).unwrap();
+        QuoteService::new(app_state)
+    }
+
+    fn ids(quotes: &[VenueQuote]) -> Vec<&str> {
+        quotes.iter().map(|quote| quote.pool_id.as_str()).collect()
+    }
+
+    #[test]
+    fn buys_rank_cheapest_fresh_venue_first_and_stale_last() {
+        let quotes = seeded("QuoteBuyMint").quote("QuoteBuyMint", SwapDirection::Buy, SOL, None);
+
+        assert_eq!(ids(&quotes), ["orca", "ray", "phx", "pump"]);
+        let stale = quotes.last().unwrap();
+        assert!(stale.stale);
+        assert!(stale.age_secs.unwrap() >= 3_600);
+        assert!(quotes[..3].iter().all(|quote| !quote.stale && quote.age_secs.unwrap() <= 30));
+        assert!(quotes.iter().all(|quote| !quote.refreshed));
+        // Paying up for size and fees on top of the pool price
+        assert!(quotes.iter().all(|quote| quote.executable_price > quote.price));
+    }
+
+    #[test]
+    fn sells_rank_the_richest_bid_first() {
+        let quotes = seeded("QuoteSellMint").quote("QuoteSellMint", SwapDirection::Sell, SOL, None);
+
+        assert_eq!(ids(&quotes), ["phx", "ray", "orca", "pump"]);
+        let book = &quotes[0];
+        assert_eq!(book.price, 0.00102);
+        assert!((book.executable_price - 0.00102 * 0.9995).abs() < 1e-12);
+        assert_eq!(book.impact_bps, Some(0.0));
+        assert!(quotes.iter().all(|quote| quote.executable_price < quote.price));
+    }
+
+    #[test]
+    fn impact_grows_with_size_and_is_unknown_past_book_depth() {
+        let service = seeded("QuoteSizeMint");
+        let impact = |amount: u64, venue: &str| {
+            service.quote("QuoteSizeMint", SwapDirection::Sell, amount, Some(venue))[0].impact_bps
+        };
+
+        let small = impact(SOL, "raydium_amm").unwrap();
+        let large = impact(100 * SOL, "raydium_amm").unwrap();
+        assert!(small > 0.0 && large > small * 50.0);
+        assert_eq!(impact(10 * SOL, "phoenix"), None);
+    }
+
+    #[test]
+    fn venue_filter_and_unknown_liquidity() {
+        let service = seeded("QuoteVenueMint");
+        service
+            .app_state
+            .pool_cache_manager
+            .add_pool("QuoteVenueMint", pool("thin", "meteora_dlmm", "QuoteVenueMint", 0.001, None, 1))
+            .unwrap();
+
+        let quotes = service.quote("QuoteVenueMint", SwapDirection::Buy, SOL, Some("meteora_dlmm"));
+        assert_eq!(ids(&quotes), ["thin"]);
+        assert_eq!(quotes[0].impact_bps, None);
+        assert_eq!(quotes[0].fee_bps, 25);
+        assert!(service.quote("QuoteUnknownMint", SwapDirection::Buy, SOL, None).is_empty());
+    }
+}
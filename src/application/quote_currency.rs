@@ -0,0 +1,314 @@
+//! Pools quoted in USDC, priced and traded in SOL terms
+//!
+//! Many deep pools pair their token with USDC rather than SOL. Their prices
+//! enter the price map converted to SOL per token at the SOL/USDC rate of a
+//! reference pool (`SOL_USDC_POOL`, a Raydium AMM v4 pool), so a USDC pool
+//! and a SOL pool of one token compare directly. The rate is read from that
+//! pool every few seconds and whenever the stream sees its reserves; one
+//! older than `REFERENCE_RATE_MAX_AGE_SECS` is not used, and USDC prices sit
+//! out until it is fresh again.
+//!
+//! An arbitrage with a USDC leg either converts through the reference pool
+//! in the same transaction (`USDC_LEG_MODE=convert`, the default), so it
+//! still starts and ends in SOL, or spends and receives the wallet's own
+//! USDC (`USDC_LEG_MODE=float`), which saves the conversion's fee but leaves
+//! the profit split across SOL and USDC.
+
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use colored::Colorize;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::common::{config::AppState, logger::Logger};
+use crate::common::sync::LockExt;
+use crate::core::price::{BookTouch, Price};
+use crate::dex::pump_swap::SOL_MINT;
+use crate::dex::raydium_amm;
+use crate::engine::pool_discovery::{BookSnapshot, PoolInfo};
+use crate::services::rpc_pool::CallClass;
+
+pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// Default for `SOL_USDC_POOL`, Raydium's SOL/USDC AMM v4 pool
+const DEFAULT_REFERENCE_POOL: &str = "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2";
+
+/// Default for `REFERENCE_RATE_MAX_AGE_SECS`
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Between two reads of the reference pool
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What the other side of a priced pool is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum QuoteAsset {
+    #[serde(rename = "SOL")]
+    Sol,
+    #[serde(rename = "USDC")]
+    Usdc,
+}
+
+impl QuoteAsset {
+    pub fn from_mint(mint: &str) -> Option<Self> {
+        match mint {
+            SOL_MINT => Some(Self::Sol),
+            USDC_MINT => Some(Self::Usdc),
+            _ => None,
+        }
+    }
+
+    pub fn mint(&self) -> Pubkey {
+        let mint = match self {
+            Self::Sol => SOL_MINT,
+            Self::Usdc => USDC_MINT,
+        };
+        Pubkey::from_str(mint).expect("quote mints are valid pubkeys")
+    }
+
+    pub fn decimals(&self) -> u8 {
+        match self {
+            Self::Sol => 9,
+            Self::Usdc => 6,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sol => "SOL",
+            Self::Usdc => "USDC",
+        }
+    }
+
+    /// Which side of a `base`/`quote` pool is the token and what quotes it:
+    /// true when the base is the token. SOL wins over USDC, so the SOL/USDC
+    /// pool prices USDC in SOL; None when neither side is a quote asset.
+    pub fn orient(base_mint: &str, quote_mint: &str) -> Option<(bool, Self)> {
+        match (Self::from_mint(base_mint), Self::from_mint(quote_mint)) {
+            (_, Some(Self::Sol)) => Some((true, Self::Sol)),
+            (Some(Self::Sol), _) => Some((false, Self::Sol)),
+            (_, Some(quote)) => Some((true, quote)),
+            (Some(quote), _) => Some((false, quote)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// SOL/USDC as last read from the reference pool
+#[derive(Debug, Clone, Copy)]
+pub struct ReferenceRate {
+    pub usdc_per_sol: f64,
+    pub pool: Pubkey,
+    pub slot: u64,
+    pub at: Instant,
+}
+
+/// Converts prices and amounts quoted in USDC into SOL terms
+#[derive(Debug)]
+pub struct PriceNormalizer {
+    pool: Pubkey,
+    rate: Mutex<Option<ReferenceRate>>,
+    max_age: Duration,
+}
+
+impl PriceNormalizer {
+    pub fn new(pool: Pubkey, max_age: Duration) -> Self {
+        Self { pool, rate: Mutex::new(None), max_age }
+    }
+
+    /// `SOL_USDC_POOL` and `REFERENCE_RATE_MAX_AGE_SECS`
+    pub fn from_env() -> Self {
+        let pool = std::env::var("SOL_USDC_POOL")
+            .ok()
+            .and_then(|v| Pubkey::from_str(v.trim()).ok())
+            .unwrap_or_else(|| Pubkey::from_str(DEFAULT_REFERENCE_POOL).expect("default reference pool is valid"));
+        let max_age = std::env::var("REFERENCE_RATE_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_MAX_AGE);
+        Self::new(pool, max_age)
+    }
+
+    /// The reference pool
+    pub fn pool(&self) -> Pubkey {
+        self.pool
+    }
+
+    /// The reference pool holds `sol_reserve` lamports against
+    /// `usdc_reserve` raw USDC as of `slot`; returns the rate they give
+    pub fn observe_reserves(&self, sol_reserve: u64, usdc_reserve: u64, slot: u64, at: Instant) -> Option<f64> {
+        let price = Price::from_reserves(
+            &self.pool.to_string(),
+            sol_reserve,
+            usdc_reserve,
+            QuoteAsset::Sol.decimals(),
+            QuoteAsset::Usdc.decimals(),
+        )
+        .ok()?;
+        let mut rate = self.rate.lock_or_recover();
+        // A read answered at an older slot than the stream's doesn't replace it
+        if rate.map_or(true, |known| known.slot <= slot) {
+            *rate = Some(ReferenceRate { usdc_per_sol: price.value(), pool: self.pool, slot, at });
+        }
+        Some(price.value())
+    }
+
+    /// The rate, unless none was read within the max age of `now`
+    pub fn rate(&self, now: Instant) -> Option<ReferenceRate> {
+        let rate = (*self.rate.lock_or_recover())?;
+        (now.saturating_duration_since(rate.at) <= self.max_age).then_some(rate)
+    }
+
+    /// `price` in whole `quote` per whole token, as SOL per whole token
+    pub fn to_sol(&self, price: Price, quote: QuoteAsset, now: Instant) -> Option<Price> {
+        match quote {
+            QuoteAsset::Sol => Some(price),
+            QuoteAsset::Usdc => Price::new(price.value() / self.rate(now)?.usdc_per_sol),
+        }
+    }
+
+    /// Raw `quote` units as lamports
+    pub fn lamports(&self, amount: u64, quote: QuoteAsset, now: Instant) -> Option<u64> {
+        match quote {
+            QuoteAsset::Sol => Some(amount),
+            QuoteAsset::Usdc => Some((amount as f64 * 1_000.0 / self.rate(now)?.usdc_per_sol) as u64),
+        }
+    }
+
+    /// Lamports as raw `quote` units
+    pub fn from_lamports(&self, lamports: u64, quote: QuoteAsset, now: Instant) -> Option<u64> {
+        match quote {
+            QuoteAsset::Sol => Some(lamports),
+            QuoteAsset::Usdc => Some((lamports as f64 * self.rate(now)?.usdc_per_sol / 1_000.0) as u64),
+        }
+    }
+
+    /// An order book's touch in `quote`, as SOL
+    pub fn touch_to_sol(&self, touch: BookTouch, quote: QuoteAsset, now: Instant) -> Option<BookTouch> {
+        BookTouch::new(self.to_sol(touch.bid, quote, now)?, self.to_sol(touch.ask, quote, now)?)
+    }
+
+    /// A cached pool with its price, liquidity and book in SOL per token,
+    /// as the sizing math reads them. The cache keeps a USDC pool's own
+    /// figures, in whatever orientation the pool has.
+    pub fn pool_in_sol<'a>(&self, pool: &'a PoolInfo, now: Instant) -> Option<Cow<'a, PoolInfo>> {
+        let Some((token_is_base, QuoteAsset::Usdc)) = QuoteAsset::orient(&pool.base_mint, &pool.quote_mint) else {
+            return Some(Cow::Borrowed(pool));
+        };
+        let usdc_per_sol = self.rate(now)?.usdc_per_sol;
+        let sol_per_token = |price: f64| if token_is_base { price / usdc_per_sol } else { 1.0 / (price * usdc_per_sol) };
+        let lamports = |amount: u64| (amount as f64 * 1_000.0 / usdc_per_sol) as u64;
+        Some(Cow::Owned(PoolInfo {
+            base_mint: if token_is_base { pool.base_mint.clone() } else { pool.quote_mint.clone() },
+            quote_mint: QuoteAsset::Sol.mint().to_string(),
+            last_known_price: pool.last_known_price.map(sol_per_token),
+            liquidity: pool.liquidity.map(lamports),
+            book: pool.book.map(|book| {
+                let (bid, ask) = (sol_per_token(book.bid), sol_per_token(book.ask));
+                // Inverting a USDC-base book swaps its sides
+                BookSnapshot { bid: bid.min(ask), ask: bid.max(ask), depth: lamports(book.depth), slot: book.slot }
+            }),
+            ..pool.clone()
+        }))
+    }
+}
+
+/// What quotes a cached pool's token, None for pools SOL and USDC are both absent from
+pub fn pool_quote(pool: &PoolInfo) -> Option<QuoteAsset> {
+    QuoteAsset::orient(&pool.base_mint, &pool.quote_mint).map(|(_, quote)| quote)
+}
+
+/// The quote asset of each leg of a two-leg opportunity and, when either is
+/// USDC, the reference rate its prices were converted at
+#[derive(Debug, Clone, Copy)]
+pub struct LegQuotes {
+    pub buy: QuoteAsset,
+    pub sell: QuoteAsset,
+    pub reference: Option<ReferenceRate>,
+}
+
+impl LegQuotes {
+    pub fn uses_usdc(&self) -> bool {
+        self.buy == QuoteAsset::Usdc || self.sell == QuoteAsset::Usdc
+    }
+
+    /// Fields of the opportunity record file
+    pub fn to_record(&self) -> serde_json::Value {
+        serde_json::json!({
+            "buy_quote": self.buy,
+            "sell_quote": self.sell,
+            "reference_rate": self.reference.map(|rate| serde_json::json!({
+                "usdc_per_sol": rate.usdc_per_sol,
+                "pool": rate.pool.to_string(),
+                "slot": rate.slot,
+            })),
+        })
+    }
+}
+
+/// How an arbitrage settles a USDC leg, from `USDC_LEG_MODE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsdcLegMode {
+    /// Swap SOL to USDC before a USDC buy and USDC to SOL after a USDC sell,
+    /// through the reference pool in the same transaction
+    Convert,
+    /// Spend and receive the wallet's USDC and leave it there
+    Float,
+}
+
+impl UsdcLegMode {
+    /// `convert` unless `USDC_LEG_MODE=float`
+    pub fn from_env() -> Self {
+        match std::env::var("USDC_LEG_MODE").map(|v| v.trim().to_ascii_lowercase()) {
+            Ok(mode) if mode == "float" => Self::Float,
+            _ => Self::Convert,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Convert => "convert",
+            Self::Float => "float",
+        }
+    }
+}
+
+/// Fold a read of the reference pool into the normalizer
+async fn refresh_reference(app_state: &AppState) -> anyhow::Result<f64> {
+    let pool_id = app_state.price_normalizer.pool();
+    let (pool, slot) = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| async move {
+            let slot = client.get_slot().await?;
+            let pool = raydium_amm::read_amm_pool(client, pool_id).await?;
+            Ok::<_, anyhow::Error>((pool, slot))
+        })
+        .await?;
+    let (sol, usdc) = (QuoteAsset::Sol.mint(), QuoteAsset::Usdc.mint());
+    let (sol_reserve, usdc_reserve) = match (pool.pool.coin_mint, pool.pool.pc_mint) {
+        (coin, pc) if coin == sol && pc == usdc => (pool.coin_reserve, pool.pc_reserve),
+        (coin, pc) if coin == usdc && pc == sol => (pool.pc_reserve, pool.coin_reserve),
+        _ => return Err(anyhow::anyhow!("{} doesn't pair SOL with USDC", pool_id)),
+    };
+    app_state
+        .price_normalizer
+        .observe_reserves(sol_reserve, usdc_reserve, slot, app_state.clock.now())
+        .ok_or_else(|| anyhow::anyhow!("{} has no usable reserves at slot {}", pool_id, slot))
+}
+
+/// Keep the SOL/USDC rate fresh while the stream doesn't
+pub async fn run_reference_refresh(app_state: Arc<AppState>) {
+    let logger = Logger::new("[SOL/USDC] => ".cyan().bold().to_string());
+    loop {
+        match refresh_reference(&app_state).await {
+            Ok(usdc_per_sol) => logger.debug_with(|| format!("[REFERENCE RATE] => {:.4} USDC per SOL", usdc_per_sol)),
+            Err(e) => logger.log(format!("[REFERENCE RATE] => Reading the SOL/USDC pool failed: {}", e).red().to_string()),
+        }
+        app_state.clock.sleep(app_state.rpc_pool.background_interval(REFRESH_INTERVAL)).await;
+    }
+}
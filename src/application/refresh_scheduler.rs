@@ -0,0 +1,584 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde::Serialize;
+use spl_token::solana_program::native_token::sol_to_lamports;
+use tokio::time::Instant;
+
+use crate::common::{config::AppState, logger::Logger};
+use crate::common::sync::LockExt;
+use crate::core::price::{BookTouch, PoolDataUnavailable, Price, PriceGate, PriceVerdict, ReserveProblem};
+use crate::core::token::get_pumpswap_token_price;
+use crate::dex::meteora_dlmm;
+use crate::dex::phoenix::PhoenixMarket;
+use crate::dex::pump_swap::SOL_MINT;
+use crate::dex::raydium_amm::RaydiumAmmPool;
+use crate::dex::whirlpool;
+use crate::engine::pool_discovery::PoolInfo;
+use crate::engine::pool_health::HealthEvent;
+use crate::services::rpc_pool::CallClass;
+
+/// Score contributions; a token's refresh share is proportional to its total
+const OPEN_POSITION_WEIGHT: f64 = 100.0;
+const OPPORTUNITY_WEIGHT: f64 = 20.0;
+const ACTIVITY_WEIGHT: f64 = 5.0;
+const IDLE_WEIGHT: f64 = 1.0;
+
+/// Half-lives of the opportunity and activity contributions
+const OPPORTUNITY_HALF_LIFE: Duration = Duration::from_secs(120);
+const ACTIVITY_HALF_LIFE: Duration = Duration::from_secs(300);
+
+/// Default band around the touch counted as order book liquidity, `BOOK_DEPTH_BPS` overrides
+const DEFAULT_BOOK_DEPTH_BPS: u64 = 50;
+
+/// Longest the refresher sleeps before re-checking, so new events reorder it quickly
+const MAX_IDLE_SLEEP: Duration = Duration::from_secs(1);
+
+/// Why a token is refreshed as often as it is, highest applicable tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relevance {
+    OpenPosition,
+    RecentOpportunity,
+    RecentlyActive,
+    Idle,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TokenActivity {
+    open_position: bool,
+    last_opportunity: Option<Instant>,
+    last_activity: Option<Instant>,
+    last_refresh: Option<Instant>,
+}
+
+/// `0.5^(age / half_life)`, 0 for events that never happened
+fn decay(event: Option<Instant>, now: Instant, half_life: Duration) -> f64 {
+    match event {
+        Some(at) => {
+            let age = now.saturating_duration_since(at).as_secs_f64();
+            0.5_f64.powf(age / half_life.as_secs_f64())
+        }
+        None => 0.0,
+    }
+}
+
+impl TokenActivity {
+    fn score(&self, now: Instant) -> f64 {
+        let open = if self.open_position { OPEN_POSITION_WEIGHT } else { 0.0 };
+        open + OPPORTUNITY_WEIGHT * decay(self.last_opportunity, now, OPPORTUNITY_HALF_LIFE)
+            + ACTIVITY_WEIGHT * decay(self.last_activity, now, ACTIVITY_HALF_LIFE)
+            + IDLE_WEIGHT
+    }
+
+    fn relevance(&self, now: Instant) -> Relevance {
+        if self.open_position {
+            Relevance::OpenPosition
+        } else if decay(self.last_opportunity, now, OPPORTUNITY_HALF_LIFE) >= 0.5 {
+            Relevance::RecentOpportunity
+        } else if decay(self.last_activity, now, ACTIVITY_HALF_LIFE) >= 0.5 {
+            Relevance::RecentlyActive
+        } else {
+            Relevance::Idle
+        }
+    }
+}
+
+/// `REFRESH_BUDGET_RPS`, `REFRESH_MIN_INTERVAL_MS`, `REFRESH_MAX_INTERVAL_MS`
+#[derive(Debug, Clone)]
+pub struct RefreshConfig {
+    /// Token refreshes per second shared by all tokens
+    pub budget_per_sec: f64,
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+}
+
+impl RefreshConfig {
+    pub fn from_env() -> Self {
+        let ms = |key: &str, default: u64| {
+            Duration::from_millis(
+                std::env::var(key)
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(default),
+            )
+        };
+        Self {
+            budget_per_sec: std::env::var("REFRESH_BUDGET_RPS")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| *v > 0.0)
+                .unwrap_or(2.0),
+            min_interval: ms("REFRESH_MIN_INTERVAL_MS", 3_000),
+            max_interval: ms("REFRESH_MAX_INTERVAL_MS", 300_000),
+        }
+    }
+}
+
+/// One token's place in the refresh schedule, for the admin endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenSchedule {
+    pub mint: String,
+    pub relevance: Relevance,
+    pub score: f64,
+    pub interval_ms: u64,
+    /// Milliseconds since the last refresh, None if never refreshed
+    pub since_refresh_ms: Option<u64>,
+}
+
+/// Orders pool-cache refreshes by how much each token matters to trading
+///
+/// The refresh budget is split in proportion to each token's score, then
+/// clamped between the minimum and maximum interval.
+pub struct RefreshScheduler {
+    config: RefreshConfig,
+    tokens: Mutex<HashMap<String, TokenActivity>>,
+}
+
+impl RefreshScheduler {
+    pub fn new(config: RefreshConfig) -> Self {
+        Self { config, tokens: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(RefreshConfig::from_env())
+    }
+
+    /// Start scheduling `mint` as idle if it isn't already
+    pub fn track(&self, mint: &str) {
+        self.tokens.lock_or_recover().entry(mint.to_string()).or_default();
+    }
+
+    /// Stop scheduling `mint`, e.g. once it is dormant
+    pub fn untrack(&self, mint: &str) {
+        self.tokens.lock_or_recover().remove(mint);
+    }
+
+    pub fn has_open_position(&self, mint: &str) -> bool {
+        self.tokens.lock_or_recover().get(mint).is_some_and(|a| a.open_position)
+    }
+
+    pub fn set_open_position(&self, mint: &str, open: bool) {
+        self.tokens.lock_or_recover().entry(mint.to_string()).or_default().open_position = open;
+    }
+
+    pub fn note_opportunity(&self, mint: &str, now: Instant) {
+        self.tokens.lock_or_recover().entry(mint.to_string()).or_default().last_opportunity = Some(now);
+    }
+
+    pub fn note_activity(&self, mint: &str, now: Instant) {
+        self.tokens.lock_or_recover().entry(mint.to_string()).or_default().last_activity = Some(now);
+    }
+
+    pub fn mark_refreshed(&self, mint: &str, now: Instant) {
+        self.tokens.lock_or_recover().entry(mint.to_string()).or_default().last_refresh = Some(now);
+    }
+
+    /// Interval for a token with `score` when all scores add up to `total_score`
+    pub fn interval_for(&self, score: f64, total_score: f64) -> Duration {
+        let share = score / total_score.max(score);
+        let interval = Duration::from_secs_f64(1.0 / (self.config.budget_per_sec * share));
+        interval.clamp(self.config.min_interval, self.config.max_interval)
+    }
+
+    fn intervals(&self, tokens: &HashMap<String, TokenActivity>, now: Instant) -> Vec<(String, f64, Duration)> {
+        let scores: Vec<(String, f64)> = tokens.iter().map(|(mint, a)| (mint.clone(), a.score(now))).collect();
+        let total: f64 = scores.iter().map(|(_, s)| s).sum();
+        scores
+            .into_iter()
+            .map(|(mint, score)| {
+                let interval = self.interval_for(score, total);
+                (mint, score, interval)
+            })
+            .collect()
+    }
+
+    /// The most overdue token and when it is due; never-refreshed tokens are due now
+    pub fn next_due(&self, now: Instant) -> Option<(String, Instant)> {
+        let tokens = self.tokens.lock_or_recover();
+        self.intervals(&tokens, now)
+            .into_iter()
+            .map(|(mint, _, interval)| {
+                let due = tokens[&mint].last_refresh.map(|at| at + interval).unwrap_or(now);
+                (mint, due)
+            })
+            .min_by_key(|(_, due)| *due)
+    }
+
+    /// Current schedule, most frequently refreshed first
+    pub fn snapshot(&self, now: Instant) -> Vec<TokenSchedule> {
+        let tokens = self.tokens.lock_or_recover();
+        let mut schedule: Vec<TokenSchedule> = self
+            .intervals(&tokens, now)
+            .into_iter()
+            .map(|(mint, score, interval)| {
+                let activity = &tokens[&mint];
+                TokenSchedule {
+                    relevance: activity.relevance(now),
+                    score,
+                    interval_ms: interval.as_millis() as u64,
+                    since_refresh_ms: activity
+                        .last_refresh
+                        .map(|at| now.saturating_duration_since(at).as_millis() as u64),
+                    mint,
+                }
+            })
+            .collect();
+        schedule.sort_by_key(|s| s.interval_ms);
+        schedule
+    }
+}
+
+/// Fresh (price, liquidity in lamports) for a cached pool, None for DEXes
+/// without a reader yet
+pub(crate) async fn read_pool_price(app_state: &AppState, pool: &PoolInfo) -> Result<Option<(Price, Option<u64>)>> {
+    let pool_id = Pubkey::from_str(&pool.pool_id)?;
+    match pool.dex_name.as_str() {
+        "pumpswap" => {
+            let price = app_state
+                .rpc_pool
+                .call(CallClass::Refresh, |client| get_pumpswap_token_price(client, &pool_id))
+                .await?;
+            let price = Price::new(price)
+                .ok_or_else(|| PoolDataUnavailable::new(pool_id, ReserveProblem::NotFinite))?;
+            Ok(Some((price, None)))
+        }
+        "raydium_amm" => {
+            let account = app_state
+                .rpc_pool
+                .call(CallClass::Refresh, |client| async move { client.get_account(&pool_id).await })
+                .await?;
+            let amm = RaydiumAmmPool::decode(pool_id, &account.data).map_err(|e| anyhow!("{}", e))?;
+            let balance = |vault: Pubkey| async move {
+                app_state
+                    .rpc_pool
+                    .call(CallClass::Refresh, |client| async move { client.get_token_account_balance(&vault).await })
+                    .await
+                    .and_then(|b| b.amount.parse::<u64>().map_err(|e| anyhow!("{}", e)))
+            };
+            let coin = balance(amm.coin_vault).await?.saturating_sub(amm.need_take_pnl_coin);
+            let pc = balance(amm.pc_vault).await?.saturating_sub(amm.need_take_pnl_pc);
+            let price = Price::from_reserves(&pool.pool_id, coin, pc, amm.coin_decimals as u8, amm.pc_decimals as u8)?;
+            let sol_side = (amm.pc_mint.to_string() == SOL_MINT).then_some(pc);
+            Ok(Some((price, sol_side)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Fresh touch, liquidity in lamports within `depth_bps` of it, and the
+/// slot it was read at, for an order book market
+pub(crate) async fn read_book(app_state: &AppState, pool: &PoolInfo, depth_bps: u64) -> Result<(BookTouch, Option<u64>, u64)> {
+    let market_id = Pubkey::from_str(&pool.pool_id)?;
+    let response = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| async move {
+            client.get_account_with_commitment(&market_id, client.commitment()).await
+        })
+        .await?;
+    let account = response
+        .value
+        .ok_or_else(|| PoolDataUnavailable::new(market_id, ReserveProblem::Missing))?;
+    let market = PhoenixMarket::decode(market_id, &account.data).map_err(|e| anyhow!("{}", e))?;
+    // An empty side means nothing to fill against, same as a zero reserve
+    let touch = market
+        .touch()
+        .ok_or_else(|| PoolDataUnavailable::new(market_id, ReserveProblem::Zero))?;
+    let sol_side = (market.quote_mint.to_string() == SOL_MINT).then(|| market.liquidity_within(depth_bps));
+    Ok((touch, sol_side, response.context.slot))
+}
+
+/// What a Whirlpool fills a buy and a sell of `size_lamports` at, the SOL
+/// in its vault and the slot it was read at. Concentrated liquidity makes the
+/// spot price a poor guide at trade size, so the pool is quoted like a book.
+pub(crate) async fn read_whirlpool_touch(
+    app_state: &AppState,
+    mint: &str,
+    pool: &PoolInfo,
+    size_lamports: u64,
+) -> Result<(BookTouch, Option<u64>, u64)> {
+    let pool_id = Pubkey::from_str(&pool.pool_id)?;
+    let mint = Pubkey::from_str(mint)?;
+    let loaded = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| whirlpool::read_whirlpool(client, pool_id))
+        .await?;
+    let touch = loaded.touch_at_size(&mint, size_lamports)?;
+    let sol_vault = if loaded.state.token_mint_a.to_string() == SOL_MINT {
+        loaded.state.token_vault_a
+    } else {
+        loaded.state.token_vault_b
+    };
+    let sol_side = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| async move { client.get_token_account_balance(&sol_vault).await })
+        .await
+        .ok()
+        .and_then(|balance| balance.amount.parse::<u64>().ok());
+    Ok((touch, sol_side, loaded.slot))
+}
+
+/// What a Meteora DLMM pair's active bin fills at, the SOL it holds and the
+/// slot it was read at
+pub(crate) async fn read_dlmm_touch(app_state: &AppState, pool: &PoolInfo) -> Result<(BookTouch, Option<u64>, u64)> {
+    let pool_id = Pubkey::from_str(&pool.pool_id)?;
+    let pair = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| meteora_dlmm::read_lb_pair(client, pool_id))
+        .await?;
+    let touch = pair.touch()?;
+    Ok((touch, Some(pair.reserves().1), pair.slot))
+}
+
+/// Pools without reserves to divide, refreshed as a bid/ask touch like a book
+fn quoted_as_book(dex_name: &str) -> bool {
+    matches!(dex_name, "whirlpool" | "meteora_dlmm")
+}
+
+/// Trade size pools are quoted at: `TOKEN_AMOUNT`, raised to the venue minimum
+fn quote_size_lamports(app_state: &AppState, dex_name: &str) -> u64 {
+    let amount_in = std::env::var("TOKEN_AMOUNT")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(0.0000001);
+    sol_to_lamports(amount_in).max(app_state.dex_registry.min_trade_in_lamports(dex_name))
+}
+
+/// `BOOK_DEPTH_BPS`: band around the touch counted as a book's liquidity
+pub(crate) fn book_depth_bps() -> u64 {
+    std::env::var("BOOK_DEPTH_BPS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_BOOK_DEPTH_BPS)
+}
+
+/// A quarantined price counts against the pool's health
+fn note_quarantine(app_state: &AppState, mint: &str, pool: &PoolInfo, logger: &Logger) {
+    if let Err(e) = app_state.pool_cache_manager.record_health_event(mint, &pool.pool_id, HealthEvent::Quarantined) {
+        logger.log(format!("[CACHE ERROR] => {}", e).red().to_string());
+    }
+}
+
+/// Refresh cached pool prices forever, most relevant tokens first
+pub async fn run_pool_refresh(app_state: Arc<AppState>) {
+    let logger = Logger::new("[POOL-REFRESH] => ".cyan().to_string());
+    let scheduler = Arc::clone(&app_state.refresh_scheduler);
+    let mut price_gate = PriceGate::from_env();
+    let book_depth_bps = book_depth_bps();
+
+    loop {
+        if let Ok(cache) = app_state.pool_cache_manager.get_cache() {
+            for mint in cache.pools.keys() {
+                if !app_state.dormancy.is_dormant(mint) {
+                    scheduler.track(mint);
+                }
+            }
+        }
+
+        let now = app_state.clock.now();
+        let Some((mint, due)) = scheduler.next_due(now) else {
+            app_state.clock.sleep(MAX_IDLE_SLEEP).await;
+            continue;
+        };
+        if due > now {
+            app_state.clock.sleep((due - now).min(MAX_IDLE_SLEEP)).await;
+            continue;
+        }
+
+        let pools = app_state
+            .pool_cache_manager
+            .get_cache()
+            .ok()
+            .and_then(|cache| cache.pools.get(&mint).cloned())
+            .unwrap_or_default();
+        for pool in &pools {
+            if app_state.dex_registry.is_order_book(&pool.dex_name) || quoted_as_book(&pool.dex_name) {
+                let read = match pool.dex_name.as_str() {
+                    "whirlpool" => {
+                        read_whirlpool_touch(&app_state, &mint, pool, quote_size_lamports(&app_state, &pool.dex_name)).await
+                    }
+                    "meteora_dlmm" => read_dlmm_touch(&app_state, pool).await,
+                    _ => read_book(&app_state, pool, book_depth_bps).await,
+                };
+                match read {
+                    Ok((touch, depth, slot)) => {
+                        let last_known = pool.last_known_price.and_then(Price::new);
+                        if let PriceVerdict::Quarantined { price, last_known } =
+                            price_gate.observe(&pool.pool_id, touch.mid(), last_known, app_state.clock.now())
+                        {
+                            logger.log(format!(
+                                "[PRICE QUARANTINED] => {} on {}: mid {} vs last {}, waiting for confirmation",
+                                pool.pool_id, pool.dex_name, price, last_known
+                            ).yellow().to_string());
+                            note_quarantine(&app_state, &mint, pool, &logger);
+                            continue;
+                        }
+                        let depth = depth.or(pool.liquidity).unwrap_or(0);
+                        app_state.dormancy.note_liquidity(&mint, &pool.pool_id, depth, app_state.clock.now());
+                        if let Err(e) = app_state.pool_cache_manager.update_book(&mint, &pool.pool_id, touch, depth, slot) {
+                            logger.log(format!("[CACHE ERROR] => {}", e).red().to_string());
+                        }
+                    }
+                    Err(e) => logger.log(format!(
+                        "[REFRESH FAILED] => {} on {}: {}", pool.pool_id, pool.dex_name, e
+                    ).red().to_string()),
+                }
+                continue;
+            }
+            match read_pool_price(&app_state, pool).await {
+                Ok(Some((observed, liquidity))) => {
+                    let last_known = pool.last_known_price.and_then(Price::new);
+                    let price = match price_gate.observe(&pool.pool_id, observed, last_known, app_state.clock.now()) {
+                        PriceVerdict::Accept(price) | PriceVerdict::Confirmed(price) => price,
+                        PriceVerdict::Quarantined { price, last_known } => {
+                            logger.log(format!(
+                                "[PRICE QUARANTINED] => {} on {}: {} vs last {}, waiting for confirmation",
+                                pool.pool_id, pool.dex_name, price, last_known
+                            ).yellow().to_string());
+                            note_quarantine(&app_state, &mint, pool, &logger);
+                            continue;
+                        }
+                    };
+                    let liquidity = liquidity.or(pool.liquidity).unwrap_or(0);
+                    app_state.dormancy.note_liquidity(&mint, &pool.pool_id, liquidity, app_state.clock.now());
+                    if let Err(e) = app_state.pool_cache_manager.update_pool_price(&mint, &pool.pool_id, price, liquidity) {
+                        logger.log(format!("[CACHE ERROR] => {}", e).red().to_string());
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => logger.log(format!(
+                    "[REFRESH FAILED] => {} on {}: {}", pool.pool_id, pool.dex_name, e
+                ).red().to_string()),
+            }
+        }
+        scheduler.mark_refreshed(&mint, app_state.clock.now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler() -> RefreshScheduler {
+        RefreshScheduler::new(RefreshConfig {
+            budget_per_sec: 2.0,
+            min_interval: Duration::from_secs(3),
+            max_interval: Duration::from_secs(300),
+        })
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn contributions_halve_every_half_life() {
+        let t0 = Instant::now();
+        let half_life = Duration::from_secs(120);
+        assert!(close(decay(Some(t0), t0, half_life), 1.0));
+        assert!(close(decay(Some(t0), t0 + half_life, half_life), 0.5));
+        assert!(close(decay(Some(t0), t0 + half_life * 2, half_life), 0.25));
+        assert_eq!(decay(None, t0, half_life), 0.0);
+        // An event stamped after `now` counts as brand new
+        assert!(close(decay(Some(t0 + half_life), t0, half_life), 1.0));
+    }
+
+    #[test]
+    fn score_decays_back_to_idle() {
+        let t0 = Instant::now();
+        let activity = TokenActivity { last_opportunity: Some(t0), ..Default::default() };
+        assert!(close(activity.score(t0), OPPORTUNITY_WEIGHT + IDLE_WEIGHT));
+        assert!(close(activity.score(t0 + OPPORTUNITY_HALF_LIFE), OPPORTUNITY_WEIGHT / 2.0 + IDLE_WEIGHT));
+        assert!(activity.score(t0 + OPPORTUNITY_HALF_LIFE * 40) - IDLE_WEIGHT < 1e-9);
+
+        let holding = TokenActivity { open_position: true, ..Default::default() };
+        assert!(close(holding.score(t0 + Duration::from_secs(86_400)), OPEN_POSITION_WEIGHT + IDLE_WEIGHT));
+    }
+
+    #[test]
+    fn relevance_takes_the_highest_tier() {
+        let t0 = Instant::now();
+        let mut activity = TokenActivity { last_activity: Some(t0), ..Default::default() };
+        assert_eq!(activity.relevance(t0), Relevance::RecentlyActive);
+        assert_eq!(activity.relevance(t0 + ACTIVITY_HALF_LIFE * 2), Relevance::Idle);
+
+        activity.last_opportunity = Some(t0);
+        assert_eq!(activity.relevance(t0), Relevance::RecentOpportunity);
+        // Past its half-life the opportunity no longer counts, the activity still does
+        assert_eq!(activity.relevance(t0 + OPPORTUNITY_HALF_LIFE * 2), Relevance::RecentlyActive);
+
+        activity.open_position = true;
+        assert_eq!(activity.relevance(t0 + ACTIVITY_HALF_LIFE * 10), Relevance::OpenPosition);
+    }
+
+    #[test]
+    fn budget_is_split_by_score_and_clamped() {
+        let scheduler = scheduler();
+        let open = OPEN_POSITION_WEIGHT + IDLE_WEIGHT;
+
+        // Alone with one idle token the holding would refresh twice a second, floored at the minimum
+        assert_eq!(scheduler.interval_for(open, open + IDLE_WEIGHT), Duration::from_secs(3));
+        let idle = scheduler.interval_for(IDLE_WEIGHT, open + IDLE_WEIGHT).as_secs_f64();
+        assert!((idle - 51.0).abs() < 1e-6, "{}", idle);
+
+        // Among a thousand idle tokens the holding still comes round every few seconds
+        let total = open + 1_000.0 * IDLE_WEIGHT;
+        let holding = scheduler.interval_for(open, total);
+        assert!(holding > Duration::from_secs(5) && holding < Duration::from_secs(6), "{:?}", holding);
+        assert_eq!(scheduler.interval_for(IDLE_WEIGHT, total), Duration::from_secs(300));
+
+        // A score larger than the total it was measured against can't take more than the budget
+        assert_eq!(scheduler.interval_for(10.0, 1.0), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn never_refreshed_tokens_are_due_first() {
+        let scheduler = scheduler();
+        let t0 = Instant::now();
+        scheduler.set_open_position("held", true);
+        scheduler.mark_refreshed("held", t0);
+        scheduler.track("new");
+
+        let (mint, due) = scheduler.next_due(t0).unwrap();
+        assert_eq!((mint.as_str(), due), ("new", t0));
+
+        scheduler.mark_refreshed("new", t0);
+        let (mint, due) = scheduler.next_due(t0).unwrap();
+        assert_eq!(mint, "held");
+        assert_eq!(due, t0 + Duration::from_secs(3));
+    }
+
+    #[test]
+    fn snapshot_lists_the_effective_intervals() {
+        let scheduler = scheduler();
+        let t0 = Instant::now();
+        for i in 0..1_000 {
+            scheduler.track(&format!("idle{}", i));
+        }
+        scheduler.set_open_position("held", true);
+        scheduler.note_opportunity("spotted", t0);
+        scheduler.mark_refreshed("held", t0);
+
+        let snapshot = scheduler.snapshot(t0 + Duration::from_secs(2));
+        assert_eq!(snapshot.len(), 1_002);
+        assert_eq!(snapshot[0].mint, "held");
+        assert_eq!(snapshot[0].relevance, Relevance::OpenPosition);
+        assert_eq!(snapshot[0].since_refresh_ms, Some(2_000));
+        assert_eq!(snapshot[1].mint, "spotted");
+        assert_eq!(snapshot[1].relevance, Relevance::RecentOpportunity);
+        assert!(snapshot[0].interval_ms < snapshot[1].interval_ms);
+        let last = snapshot.last().unwrap();
+        assert_eq!(last.relevance, Relevance::Idle);
+        assert_eq!(last.interval_ms, 300_000);
+        assert_eq!(last.since_refresh_ms, None);
+
+        scheduler.untrack("held");
+        assert!(!scheduler.has_open_position("held"));
+        assert_eq!(scheduler.snapshot(t0).len(), 1_001);
+    }
+}
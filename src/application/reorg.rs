@@ -0,0 +1,332 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Signature, signer::Signer};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use colored::Colorize;
+use serde_json::json;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use yellowstone_grpc_proto::geyser::{SlotStatus, SubscribeRequestFilterSlots, SubscribeUpdateSlot};
+
+use crate::common::config::AppState;
+use crate::common::logger::Logger;
+use crate::core::compute_estimator::COMPUTE_ESTIMATOR;
+use crate::services::notifier::Notifier;
+use crate::services::rpc_pool::CallClass;
+
+/// A position we opened because of a transaction seen in a given slot
+#[derive(Debug, Clone)]
+pub struct TrackedPosition {
+    pub mint: String,
+    pub signature: String,
+}
+
+/// Result of re-checking a position on-chain after its slot died
+#[derive(Debug, Clone)]
+pub enum PositionCheck {
+    /// Our transaction landed and the wallet holds the token
+    Landed { balance: u64 },
+    /// Transaction landed but the wallet no longer holds the token
+    Missing,
+    /// The cluster has no status for the transaction yet, the position stays tracked
+    Pending,
+    /// Transaction landed but failed
+    Failed(String),
+}
+
+/// Times a position whose transaction has no status yet is checked again
+const PENDING_RECHECKS: u32 = 12;
+/// Wait between those checks; all of them outlast a blockhash, so a
+/// transaction still unknown by then can no longer land
+const PENDING_RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Remembers which state was derived from which processed slot
+///
+/// At processed commitment a slot can still be marked dead; everything recorded
+/// against it is then handed back by `take_slot` so callers can roll it back.
+#[derive(Debug, Default)]
+pub struct SlotTracker {
+    price_updates: BTreeMap<u64, Vec<(String, String)>>,
+    latest_price_slot: HashMap<(String, String), u64>,
+    positions: BTreeMap<u64, Vec<TrackedPosition>>,
+}
+
+impl SlotTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that the price of `token_mint` on `dex_name` was last updated from `slot`
+    pub fn record_price(&mut self, slot: u64, token_mint: &str, dex_name: &str) {
+        let key = (token_mint.to_string(), dex_name.to_string());
+        self.latest_price_slot.insert(key.clone(), slot);
+        self.price_updates.entry(slot).or_default().push(key);
+    }
+
+    /// Note that a position was opened on the strength of `slot`
+    pub fn record_position(&mut self, slot: u64, mint: &str, signature: &str) {
+        self.positions.entry(slot).or_default().push(TrackedPosition {
+            mint: mint.to_string(),
+            signature: signature.to_string(),
+        });
+    }
+
+    /// Remove and return everything derived from a dead slot.
+    ///
+    /// Price entries are only returned if no later slot has overwritten them.
+    pub fn take_slot(&mut self, slot: u64) -> (Vec<(String, String)>, Vec<TrackedPosition>) {
+        let prices = self
+            .price_updates
+            .remove(&slot)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|key| self.latest_price_slot.get(key) == Some(&slot))
+            .collect::<Vec<_>>();
+        for key in &prices {
+            self.latest_price_slot.remove(key);
+        }
+
+        let positions = self.positions.remove(&slot).unwrap_or_default();
+        (prices, positions)
+    }
+
+    /// Forget slots at or below a finalized slot, they can no longer roll back
+    pub fn prune_through(&mut self, finalized_slot: u64) {
+        self.price_updates = self.price_updates.split_off(&(finalized_slot + 1));
+        self.positions = self.positions.split_off(&(finalized_slot + 1));
+        self.latest_price_slot.retain(|_, slot| *slot > finalized_slot);
+    }
+}
+
+/// Slot filter that also delivers dead-slot notifications
+pub fn slot_status_filter() -> SubscribeRequestFilterSlots {
+    SubscribeRequestFilterSlots {
+        filter_by_commitment: Some(false),
+        interslot_updates: Some(true),
+    }
+}
+
+pub fn is_dead_slot(update: &SubscribeUpdateSlot) -> bool {
+    update.status == SlotStatus::SlotDead as i32
+}
+
+pub fn is_finalized_slot(update: &SubscribeUpdateSlot) -> bool {
+    update.status == SlotStatus::SlotFinalized as i32
+}
+
+/// Check a position against the chain instead of trusting local state
+pub async fn reverify_position(app_state: &AppState, position: &TrackedPosition) -> Result<PositionCheck> {
+    let signature = Signature::from_str(&position.signature)
+        .map_err(|e| anyhow!("Invalid signature {}: {}", position.signature, e))?;
+    let statuses = app_state
+        .rpc_pool
+        .call(CallClass::Confirm, |client| async move {
+            client.get_signature_statuses_with_history(&[signature]).await
+        })
+        .await?;
+
+    match statuses.value.into_iter().next().flatten() {
+        None => return Ok(PositionCheck::Pending),
+        Some(status) => {
+            if let Some(err) = status.err {
+                return Ok(PositionCheck::Failed(err.to_string()));
+            }
+        }
+    }
+
+    let mint = Pubkey::from_str(&position.mint)?;
+    // Token-2022 mints keep their balances at a different ATA
+    let token_program = app_state
+        .rpc_pool
+        .call(CallClass::Confirm, |client| async move { client.get_account(&mint).await })
+        .await
+        .map_err(|e| anyhow!("Failed to read mint {}: {}", position.mint, e))?
+        .owner;
+    let ata = get_associated_token_address_with_program_id(&app_state.wallet.pubkey(), &mint, &token_program);
+    let balance = match app_state
+        .rpc_pool
+        .call(CallClass::Confirm, |client| async move { client.get_token_account_balance(&ata).await })
+        .await
+    {
+        Ok(balance) => balance.amount.parse::<u64>().unwrap_or(0),
+        Err(_) => 0,
+    };
+
+    if balance == 0 {
+        return Ok(PositionCheck::Missing);
+    }
+    Ok(PositionCheck::Landed { balance })
+}
+
+/// Re-verify a position opened from `slot` once that slot died, annotate
+/// the records in `record_dir` with the outcome and alert on it. A transaction the cluster
+/// has no status for yet is checked again for a while before it is reported
+/// `Pending`. None when the chain could not be asked; the position is then
+/// left as it is.
+pub async fn reverify_after_dead_slot(
+    app_state: &AppState,
+    notifier: &Notifier,
+    record_dir: &Path,
+    slot: u64,
+    position: &TrackedPosition,
+) -> Option<PositionCheck> {
+    let mut rechecks = 0;
+    let check = loop {
+        match reverify_position(app_state, position).await {
+            Ok(PositionCheck::Pending) if rechecks < PENDING_RECHECKS => {
+                rechecks += 1;
+                app_state.clock.sleep(PENDING_RECHECK_INTERVAL).await;
+            }
+            Ok(check) => break check,
+            Err(e) => {
+                notifier.alert(
+                    "Reorg re-verification failed",
+                    &format!("Slot {} died, could not verify {} ({}): {}", slot, position.mint, position.signature, e),
+                ).await;
+                return None;
+            }
+        }
+    };
+
+    if let PositionCheck::Failed(err) = &check {
+        // A buy that ran out of compute means the cached limit for its shape is too tight
+        COMPUTE_ESTIMATOR.report_failure(&position.signature, err);
+    }
+    if let Err(e) = record_reorg_annotation(record_dir, slot, position, &check) {
+        Logger::new("[REORG] => ".to_string()).log(format!("Failed to record annotation: {}", e).red().to_string());
+    }
+
+    match &check {
+        PositionCheck::Landed { balance } => {
+            notifier.alert(
+                "Position affected by reorg",
+                &format!("Slot {} died; {} re-verified on-chain, balance {}", slot, position.mint, balance),
+            ).await;
+        }
+        PositionCheck::Pending => {
+            notifier.alert(
+                "Position unconfirmed after reorg",
+                &format!("Slot {} died; {} ({}) has no status on-chain yet, still tracked", slot, position.mint, position.signature),
+            ).await;
+        }
+        PositionCheck::Missing | PositionCheck::Failed(_) => {
+            notifier.alert(
+                "Position rolled back by reorg",
+                &format!("Slot {} died; {} ({}) is not on-chain: {:?}", slot, position.mint, position.signature, check),
+            ).await;
+        }
+    }
+    Some(check)
+}
+
+/// Append a reorg annotation next to the transaction records in `record_dir`
+pub fn record_reorg_annotation(
+    record_dir: &Path,
+    slot: u64,
+    position: &TrackedPosition,
+    check: &PositionCheck,
+) -> Result<(), String> {
+    let timestamp = Utc::now().format("%Y%m%d%H%M%S").to_string();
+    let record = json!({
+        "timestamp": timestamp,
+        "event": "reorg",
+        "dead_slot": slot,
+        "mint": position.mint,
+        "signature": position.signature,
+        "outcome": format!("{:?}", check),
+    });
+
+    std::fs::create_dir_all(record_dir).map_err(|e| format!("Failed to create {}: {}", record_dir.display(), e))?;
+    let filename = record_dir.join(format!("reorg_{}_{}.json", position.signature, timestamp));
+    std::fs::write(&filename, serde_json::to_string_pretty(&record).unwrap_or_default())
+        .map_err(|e| format!("Failed to write {}: {}", filename.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::sync::LockExt;
+
+    /// Fresh record directory per test, so annotations never land in `./record`
+    fn record_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("reorg_test_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Outcomes annotated in `dir`
+    fn annotations(dir: &Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| std::fs::read_to_string(entry.ok()?.path()).ok())
+                    .filter_map(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+                    .filter_map(|record| record["outcome"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn dead(slot: u64) -> SubscribeUpdateSlot {
+        SubscribeUpdateSlot { slot, status: SlotStatus::SlotDead as i32, ..Default::default() }
+    }
+
+    #[test]
+    fn dead_slot_hands_back_the_positions_bought_from_it() {
+        let mut tracker = SlotTracker::new();
+        tracker.record_position(100, "mint", "sig");
+        tracker.record_price(100, "mint", "pumpswap");
+        tracker.record_price(101, "mint", "pumpswap");
+
+        let update = dead(100);
+        assert!(is_dead_slot(&update));
+        let (prices, positions) = tracker.take_slot(update.slot);
+        // The price was overwritten by a live slot since, only the position rolls back
+        assert!(prices.is_empty());
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].mint, "mint");
+        assert!(tracker.take_slot(update.slot).1.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unseen_buy_from_a_dead_slot_is_rechecked_and_kept() {
+        let (notifier, sent) = Notifier::recording();
+        // The cluster has never seen the buy's signature
+        let app_state = AppState::for_tests_with_rpc("sig_not_found", notifier.clone());
+        let mut tracker = SlotTracker::new();
+        let signature = Signature::new_unique().to_string();
+        tracker.record_position(100, &Pubkey::new_unique().to_string(), &signature);
+        let started = app_state.clock.now();
+        let dir = record_dir("unseen");
+
+        let (_, positions) = tracker.take_slot(dead(100).slot);
+        let check = reverify_after_dead_slot(&app_state, &notifier, &dir, 100, &positions[0]).await;
+
+        assert!(matches!(check, Some(PositionCheck::Pending)));
+        assert_eq!(annotations(&dir), ["Pending"]);
+        assert_eq!(app_state.clock.now() - started, PENDING_RECHECK_INTERVAL * PENDING_RECHECKS);
+        let sent = sent.lock_or_recover();
+        assert!(sent.iter().any(|(title, body)| title == "Position unconfirmed after reorg" && body.contains(&signature)));
+        assert!(!sent.iter().any(|(title, _)| title == "Position rolled back by reorg"));
+    }
+
+    #[tokio::test]
+    async fn failed_buy_is_not_trusted_either() {
+        let (notifier, sent) = Notifier::recording();
+        let app_state = AppState::for_tests_with_rpc("instruction_error", notifier.clone());
+        let position = TrackedPosition { mint: Pubkey::new_unique().to_string(), signature: Signature::new_unique().to_string() };
+
+        let dir = record_dir("failed");
+
+        let check = reverify_after_dead_slot(&app_state, &notifier, &dir, 7, &position).await;
+
+        assert!(matches!(check, Some(PositionCheck::Failed(_))));
+        let outcomes = annotations(&dir);
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].starts_with("Failed"), "{:?}", outcomes);
+        assert!(sent.lock_or_recover().iter().any(|(title, _)| title == "Position rolled back by reorg"));
+    }
+}
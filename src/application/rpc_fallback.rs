@@ -0,0 +1,737 @@
+//! Plain-RPC data source for when the Geyser stream can't be reached
+//!
+//! Prices come from batched `getMultipleAccounts` reads of the monitored
+//! pools' vaults every few seconds, and copy targets are watched through
+//! `getSignaturesForAddress`, which trails the stream by seconds. The monitor
+//! switches here once gRPC used up its retries and back as soon as a probe
+//! connects again.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anchor_client::solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use anchor_client::solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Signature};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use colored::Colorize;
+use dashmap::DashMap;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenAccount;
+
+use crate::common::{
+    config::AppState,
+    events::{self, BotEvent},
+    format::cached_decimals,
+    logger::Logger,
+    metrics::STREAM_FALLBACK_ACTIVE,
+};
+use crate::core::price::{Price, PriceGate, PriceVerdict};
+use crate::dex::pump_swap::SOL_MINT;
+use crate::dex::raydium_amm::RaydiumAmmPool;
+use crate::engine::pool_discovery::PoolInfo;
+use crate::engine::quote_currency::QuoteAsset;
+use crate::engine::stream_reconnect::ReconnectBackoff;
+use crate::services::rpc_pool::CallClass;
+
+/// `getMultipleAccounts` takes at most this many keys
+const MAX_ACCOUNTS_PER_CALL: usize = 100;
+
+/// (price in SOL, liquidity in lamports, slot, what the pool is quoted in)
+/// per DEX per mint, as the monitor keeps them; USDC pools are converted at
+/// the reference rate. Sharded so writers of different mints don't wait on
+/// each other.
+pub type PriceMap = DashMap<String, HashMap<String, (Price, u64, u64, QuoteAsset)>>;
+
+/// A plain copy of the price map, for scans that walk every pair of every
+/// mint without holding a shard while they do
+pub fn snapshot(prices: &PriceMap) -> HashMap<String, HashMap<String, (Price, u64, u64, QuoteAsset)>> {
+    prices.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect()
+}
+
+lazy_static::lazy_static! {
+    static ref ACTIVE: AtomicBool = AtomicBool::new(false);
+    static ref COPY_IN_FALLBACK: AtomicBool = AtomicBool::new(false);
+}
+
+/// `RPC_FALLBACK`, `RPC_FALLBACK_POLL_MS`, `RPC_FALLBACK_PROBE_SECS` and `RPC_FALLBACK_COPY`
+#[derive(Debug, Clone, Copy)]
+pub struct FallbackConfig {
+    /// Between two rounds of vault reads
+    pub poll_interval: Duration,
+    /// Between two attempts to get the gRPC stream back
+    pub probe_interval: Duration,
+    /// Keep copy trading on, on delayed signals, while polling
+    pub copy_enabled: bool,
+}
+
+impl FallbackConfig {
+    /// None when `RPC_FALLBACK=false`, in which case a dead stream stays an error
+    pub fn from_env() -> Option<Self> {
+        let flag = |key: &str, default: bool| {
+            std::env::var(key)
+                .ok()
+                .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(default)
+        };
+        let parse = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        flag("RPC_FALLBACK", true).then(|| Self {
+            poll_interval: Duration::from_millis(parse("RPC_FALLBACK_POLL_MS", 3_000).max(500)),
+            probe_interval: Duration::from_secs(parse("RPC_FALLBACK_PROBE_SECS", 60).max(5)),
+            copy_enabled: flag("RPC_FALLBACK_COPY", false),
+        })
+    }
+}
+
+/// Whether prices currently come from RPC polling instead of the stream
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Copy trading assumes stream latency; unless told otherwise it stands
+/// down while polling
+pub fn copy_paused() -> bool {
+    is_active() && !COPY_IN_FALLBACK.load(Ordering::Relaxed)
+}
+
+/// Flip to RPC polling; returns false when already there
+pub async fn enter(app_state: &AppState, config: &FallbackConfig, reason: &str, logger: &Logger) -> bool {
+    COPY_IN_FALLBACK.store(config.copy_enabled, Ordering::Relaxed);
+    if ACTIVE.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    STREAM_FALLBACK_ACTIVE.set(1);
+    events::publish(BotEvent::Stream { name: "rpc_fallback".to_string(), connected: true, slot: None });
+    logger.log(format!(
+        "[RPC FALLBACK ACTIVE] => gRPC unavailable ({}). Polling pool vaults every {:?}, retrying gRPC every {:?}. \
+         Prices are seconds behind; copy trading {}",
+        reason,
+        config.poll_interval,
+        config.probe_interval,
+        if config.copy_enabled { "continues on delayed signals" } else { "is paused" },
+    ).red().bold().to_string());
+    let body = format!("gRPC unavailable ({}), running on RPC polling", reason);
+    app_state.notifier.alert("Stream fallback active", &body).await;
+    true
+}
+
+/// Back to the stream; returns false when polling wasn't active
+pub async fn leave(app_state: &AppState, logger: &Logger) -> bool {
+    if !ACTIVE.swap(false, Ordering::Relaxed) {
+        return false;
+    }
+    STREAM_FALLBACK_ACTIVE.set(0);
+    events::publish(BotEvent::Stream { name: "rpc_fallback".to_string(), connected: false, slot: None });
+    logger.log("[RPC FALLBACK ENDED] => gRPC stream is back, polling stopped".green().bold().to_string());
+    app_state.notifier.alert("Stream recovered", "gRPC stream is back, RPC polling stopped").await;
+    true
+}
+
+/// What a monitor keeps doing over plain RPC while its stream is down
+#[async_trait]
+pub trait FallbackPoller: Send {
+    /// Poll for `duration`, then hand back so the stream can be probed
+    async fn poll_for(&mut self, app_state: &AppState, config: &FallbackConfig, duration: Duration, logger: &Logger);
+}
+
+/// Vault prices into the arbitrage monitor's price map
+pub struct PricePoller<'a> {
+    pub prices: &'a PriceMap,
+}
+
+#[async_trait]
+impl FallbackPoller for PricePoller<'_> {
+    async fn poll_for(&mut self, app_state: &AppState, config: &FallbackConfig, duration: Duration, logger: &Logger) {
+        poll_prices_for(app_state, self.prices, config, duration, logger).await;
+    }
+}
+
+/// Copy targets' transactions, seconds late
+pub struct CopyTargetPoller<'a> {
+    pub targets: &'a [Pubkey],
+    pub newest_seen: &'a mut HashMap<Pubkey, Signature>,
+}
+
+#[async_trait]
+impl FallbackPoller for CopyTargetPoller<'_> {
+    async fn poll_for(&mut self, app_state: &AppState, config: &FallbackConfig, duration: Duration, logger: &Logger) {
+        watch_copy_targets_for(app_state, self.targets, self.newest_seen, config, duration, logger).await;
+    }
+}
+
+/// Connect through `connect`, retrying until it succeeds
+///
+/// With a fallback config every failed attempt switches to RPC polling with
+/// `poller` for one probe interval; without one, attempts are spaced by
+/// `backoff`. Polling this call switched to ends once connected, so one
+/// monitor reconnecting never ends another's fallback.
+pub async fn connect_with_fallback<T, C, F>(
+    app_state: &AppState,
+    config: Option<FallbackConfig>,
+    backoff: &mut ReconnectBackoff,
+    poller: &mut dyn FallbackPoller,
+    label: &str,
+    logger: &Logger,
+    mut connect: C,
+) -> T
+where
+    C: FnMut() -> F,
+    F: std::future::Future<Output = Result<T, String>>,
+{
+    let mut entered = false;
+    let connected = loop {
+        match connect().await {
+            Ok(connected) => break connected,
+            Err(e) => match config {
+                Some(config) => {
+                    entered |= enter(app_state, &config, &e, logger).await;
+                    poller.poll_for(app_state, &config, config.probe_interval, logger).await;
+                    logger.log("[RPC FALLBACK] => Probing gRPC".yellow().to_string());
+                }
+                None => {
+                    let delay = backoff.next_delay();
+                    logger.log(format!("[{}] => {}. Retrying in {:?}", label, e, delay).red().to_string());
+                    app_state.clock.sleep(delay).await;
+                }
+            },
+        }
+    };
+    if entered {
+        leave(app_state, logger).await;
+    }
+    connected
+}
+
+/// The two vaults that price one cached pool
+#[derive(Debug, Clone)]
+pub struct VaultPair {
+    pub mint: String,
+    pub dex: String,
+    pub pool_id: String,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    /// Owed to the pool owner, not swappable
+    pub base_pnl: u64,
+    pub quote_pnl: u64,
+    /// What the quote vault holds; the base vault holds `mint`
+    pub quote: QuoteAsset,
+}
+
+impl VaultPair {
+    /// Vault balances less what is owed to the pool owner
+    pub fn swappable(&self, base: u64, quote: u64) -> (u64, u64) {
+        (base.saturating_sub(self.base_pnl), quote.saturating_sub(self.quote_pnl))
+    }
+}
+
+/// Work out vault pairs for the pools that have none yet. PumpSwap vaults
+/// are the pool's associated token accounts; Raydium AMM vaults come from
+/// one batched read of the pool accounts, oriented so the base is the
+/// cached token and skipped unless SOL or USDC quotes it. Other DEXes are
+/// left to the refresh scheduler.
+pub async fn plan_vaults(app_state: &AppState, pools: &[(String, PoolInfo)]) -> Result<Vec<VaultPair>> {
+    let sol_mint = Pubkey::from_str(SOL_MINT)?;
+    let mut pairs = Vec::new();
+    let mut raydium = Vec::new();
+    for (mint, pool) in pools {
+        let pool_id = Pubkey::from_str(&pool.pool_id)?;
+        match pool.dex_name.as_str() {
+            "pumpswap" => {
+                // Decimals are only known once something read the mint
+                let Some(base_decimals) = cached_decimals(mint) else { continue };
+                let base_mint = Pubkey::from_str(mint)?;
+                pairs.push(VaultPair {
+                    mint: mint.clone(),
+                    dex: pool.dex_name.clone(),
+                    pool_id: pool.pool_id.clone(),
+                    base_vault: get_associated_token_address(&pool_id, &base_mint),
+                    quote_vault: get_associated_token_address(&pool_id, &sol_mint),
+                    base_decimals,
+                    quote_decimals: 9,
+                    base_pnl: 0,
+                    quote_pnl: 0,
+                    quote: QuoteAsset::Sol,
+                });
+            }
+            "raydium_amm" => raydium.push((mint.clone(), pool.dex_name.clone(), pool_id)),
+            _ => {}
+        }
+    }
+
+    let pool_ids: Vec<Pubkey> = raydium.iter().map(|(_, _, pool_id)| *pool_id).collect();
+    let (accounts, _) = get_multiple(app_state, &pool_ids).await?;
+    for ((mint, dex, pool_id), account) in raydium.into_iter().zip(accounts) {
+        let Some(data) = account else { continue };
+        let amm = RaydiumAmmPool::decode(pool_id, &data).map_err(|e| anyhow!("{}", e))?;
+        let Some((token_is_coin, quote)) = QuoteAsset::orient(&amm.coin_mint.to_string(), &amm.pc_mint.to_string()) else {
+            continue;
+        };
+        let coin = (amm.coin_vault, amm.coin_decimals as u8, amm.need_take_pnl_coin, amm.coin_mint);
+        let pc = (amm.pc_vault, amm.pc_decimals as u8, amm.need_take_pnl_pc, amm.pc_mint);
+        let (base, quote_side) = if token_is_coin { (coin, pc) } else { (pc, coin) };
+        // A pool of another token that came up in this one's discovery
+        if base.3.to_string() != mint {
+            continue;
+        }
+        pairs.push(VaultPair {
+            mint,
+            dex,
+            pool_id: pool_id.to_string(),
+            base_vault: base.0,
+            quote_vault: quote_side.0,
+            base_decimals: base.1,
+            quote_decimals: quote_side.1,
+            base_pnl: base.2,
+            quote_pnl: quote_side.2,
+            quote,
+        });
+    }
+    Ok(pairs)
+}
+
+/// Account data for `keys` in calls of at most 100, and the lowest slot any
+/// call was answered at
+async fn get_multiple(app_state: &AppState, keys: &[Pubkey]) -> Result<(Vec<Option<Vec<u8>>>, u64)> {
+    let mut data = Vec::with_capacity(keys.len());
+    let mut slot = u64::MAX;
+    for chunk in keys.chunks(MAX_ACCOUNTS_PER_CALL) {
+        let response = app_state
+            .rpc_pool
+            .call(CallClass::Refresh, |client| {
+                let chunk = chunk.to_vec();
+                async move { client.get_multiple_accounts_with_commitment(&chunk, client.commitment()).await }
+            })
+            .await?;
+        slot = slot.min(response.context.slot);
+        data.extend(response.value.into_iter().map(|account| account.map(|account| account.data)));
+    }
+    Ok((data, if slot == u64::MAX { 0 } else { slot }))
+}
+
+/// Raw balances of the token accounts `accounts`, None where one couldn't
+/// be read, and the slot they were read at
+pub async fn read_token_balances(app_state: &AppState, accounts: &[Pubkey]) -> Result<(Vec<Option<u64>>, u64)> {
+    let (data, slot) = get_multiple(app_state, accounts).await?;
+    let amount = |data: Option<Vec<u8>>| data.as_deref().and_then(|data| TokenAccount::unpack(data).ok()).map(|a| a.amount);
+    Ok((data.into_iter().map(amount).collect(), slot))
+}
+
+/// A `getMultipleAccounts` answer at `slot` of token accounts holding
+/// `amounts`, for tests that mock vault reads
+#[cfg(test)]
+pub(crate) fn mock_token_accounts(slot: u64, amounts: &[u64]) -> serde_json::Value {
+    use anchor_client::solana_sdk::account::Account;
+    use solana_account_decoder::{UiAccount, UiAccountEncoding};
+    use solana_client::rpc_response::{Response, RpcResponseContext};
+    use spl_token::state::AccountState;
+
+    let accounts: Vec<Option<UiAccount>> = amounts
+        .iter()
+        .map(|amount| {
+            let mut data = vec![0; TokenAccount::LEN];
+            TokenAccount { amount: *amount, state: AccountState::Initialized, ..Default::default() }.pack_into_slice(&mut data);
+            let account = Account { lamports: 2_039_280, data, owner: spl_token::id(), executable: false, rent_epoch: 0 };
+            Some(UiAccount::encode(&Pubkey::new_unique(), &account, UiAccountEncoding::Base64, None, None))
+        })
+        .collect();
+    serde_json::to_value(Response { context: RpcResponseContext::new(slot), value: accounts }).unwrap()
+}
+
+/// Raw base and quote vault balances of each pair, None where either
+/// vault couldn't be read, and the slot they were read at
+pub async fn read_vaults(app_state: &AppState, pairs: &[VaultPair]) -> Result<(Vec<Option<(u64, u64)>>, u64)> {
+    let vaults: Vec<Pubkey> = pairs.iter().flat_map(|pair| [pair.base_vault, pair.quote_vault]).collect();
+    let (amounts, slot) = read_token_balances(app_state, &vaults).await?;
+    let balances = amounts.chunks(2).map(|vaults| vaults[0].zip(vaults[1])).collect();
+    Ok((balances, slot))
+}
+
+/// One round of vault reads into `prices`; returns how many pools were priced
+pub async fn poll_once(
+    app_state: &AppState,
+    pairs: &[VaultPair],
+    prices: &PriceMap,
+    price_gate: &mut PriceGate,
+    logger: &Logger,
+) -> Result<usize> {
+    let (balances, slot) = read_vaults(app_state, pairs).await?;
+    let mut priced = 0;
+    for (pair, balance) in pairs.iter().zip(balances) {
+        let Some((base, quote)) = balance else { continue };
+        if record_reserves(app_state, pair, base, quote, slot, prices, price_gate, logger) {
+            priced += 1;
+        }
+    }
+    Ok(priced)
+}
+
+/// Price `pair` from raw vault balances read at `slot` and store it in
+/// `prices` if the gate lets it through; true when stored
+#[allow(clippy::too_many_arguments)]
+pub fn record_reserves(
+    app_state: &AppState,
+    pair: &VaultPair,
+    base: u64,
+    quote: u64,
+    slot: u64,
+    prices: &PriceMap,
+    price_gate: &mut PriceGate,
+    logger: &Logger,
+) -> bool {
+    let (base, quote) = pair.swappable(base, quote);
+    let now = app_state.clock.now();
+    let normalizer = &app_state.price_normalizer;
+    // The SOL/USDC reference pool is kept as USDC priced in SOL
+    if pair.quote == QuoteAsset::Sol && pair.pool_id == normalizer.pool().to_string() {
+        normalizer.observe_reserves(quote, base, slot, now);
+    }
+    let quoted = match Price::from_reserves(&pair.pool_id, base, quote, pair.base_decimals, pair.quote_decimals) {
+        Ok(price) => price,
+        Err(e) => {
+            logger.log(format!("[POLL SKIPPED] => {} on {}: {}", pair.pool_id, pair.dex, e).yellow().to_string());
+            return false;
+        }
+    };
+    let (Some(observed), Some(liquidity)) = (normalizer.to_sol(quoted, pair.quote, now), normalizer.lamports(quote, pair.quote, now)) else {
+        logger.debug_with(|| format!(
+            "[POLL SKIPPED] => {} on {}: quoted in {}, no fresh SOL/USDC rate to convert at",
+            pair.pool_id, pair.dex, pair.quote.name()
+        ));
+        return false;
+    };
+
+    let mut dex_prices = prices.entry(pair.mint.clone()).or_default();
+    // Several pools of one DEX share its slot in the map, the deepest wins
+    if let Some((_, known_liquidity, known_slot, _)) = dex_prices.get(&pair.dex) {
+        if *known_slot == slot && *known_liquidity > liquidity {
+            return false;
+        }
+    }
+    let last_known = dex_prices.get(&pair.dex).map(|(price, _, _, _)| *price);
+    let gate_key = format!("{}:{}", pair.mint, pair.dex);
+    match price_gate.observe(&gate_key, observed, last_known, now) {
+        PriceVerdict::Accept(price) | PriceVerdict::Confirmed(price) => {
+            debug_assert!(price.value().is_finite() && price.value() > 0.0, "unusable price {} for {}", price, gate_key);
+            dex_prices.insert(pair.dex.clone(), (price, liquidity, slot, pair.quote));
+            true
+        }
+        PriceVerdict::Quarantined { price, last_known } => {
+            logger.log(format!(
+                "[PRICE QUARANTINED] => {} on {}: {} vs last {}, waiting for confirmation",
+                pair.mint, pair.dex, price, last_known
+            ).yellow().to_string());
+            false
+        }
+    }
+}
+
+/// Cached pools of tokens that aren't dormant, minus those `known` already covers
+pub fn active_pools(app_state: &AppState, known: impl Fn(&str) -> bool) -> Vec<(String, PoolInfo)> {
+    app_state
+        .pool_cache_manager
+        .get_cache()
+        .map(|cache| {
+            cache
+                .pools
+                .iter()
+                .filter(|(mint, _)| !app_state.dormancy.is_dormant(mint))
+                .flat_map(|(mint, pools)| pools.iter().map(move |pool| (mint.clone(), pool.clone())))
+                .filter(|(_, pool)| !known(&pool.pool_id))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Poll vault prices into `prices` for `duration`, then hand back so the
+/// caller can probe gRPC
+pub async fn poll_prices_for(
+    app_state: &AppState,
+    prices: &PriceMap,
+    config: &FallbackConfig,
+    duration: Duration,
+    logger: &Logger,
+) {
+    let started = app_state.clock.now();
+    let mut price_gate = PriceGate::from_env();
+    let mut pairs: Vec<VaultPair> = Vec::new();
+    while app_state.clock.now().saturating_duration_since(started) < duration {
+        // Pools discovered since the last round join on the next one
+        let pools = active_pools(app_state, |pool_id| pairs.iter().any(|pair| pair.pool_id == pool_id));
+        if !pools.is_empty() {
+            match plan_vaults(app_state, &pools).await {
+                Ok(new_pairs) => pairs.extend(new_pairs),
+                Err(e) => logger.log(format!("[RPC FALLBACK] => Reading pool accounts failed: {}", e).red().to_string()),
+            }
+        }
+
+        match poll_once(app_state, &pairs, prices, &mut price_gate, logger).await {
+            Ok(priced) => logger.log(format!(
+                "[RPC FALLBACK] => Polled {} of {} pools over RPC", priced, pairs.len()
+            ).yellow().to_string()),
+            Err(e) => logger.log(format!("[RPC FALLBACK] => Vault poll failed: {}", e).red().to_string()),
+        }
+        app_state.clock.sleep(config.poll_interval).await;
+    }
+}
+
+/// A copy target transaction seen through polling
+#[derive(Debug, Clone)]
+pub struct DelayedSignal {
+    pub target: Pubkey,
+    pub signature: Signature,
+    pub slot: u64,
+    /// From block time to when polling saw it, when the block time is known
+    pub lag: Option<Duration>,
+}
+
+/// Signatures each target sent since the last call. The first call for a
+/// target only records where to start from.
+pub async fn poll_copy_targets(
+    app_state: &AppState,
+    targets: &[Pubkey],
+    newest_seen: &mut HashMap<Pubkey, Signature>,
+) -> Result<Vec<DelayedSignal>> {
+    let mut signals = Vec::new();
+    for target in targets {
+        let until = newest_seen.get(target).copied();
+        let statuses = app_state
+            .rpc_pool
+            .call(CallClass::Refresh, |client| {
+                let target = *target;
+                let config = GetConfirmedSignaturesForAddress2Config {
+                    until,
+                    limit: Some(if until.is_some() { 50 } else { 1 }),
+                    ..Default::default()
+                };
+                async move { client.get_signatures_for_address_with_config(&target, config).await }
+            })
+            .await?;
+        // Newest first
+        let Some(newest) = statuses.first().and_then(|status| Signature::from_str(&status.signature).ok()) else {
+            continue;
+        };
+        newest_seen.insert(*target, newest);
+        if until.is_none() {
+            continue;
+        }
+        let now = chrono::Utc::now().timestamp();
+        for status in statuses.iter().rev().filter(|status| status.err.is_none()) {
+            let Ok(signature) = Signature::from_str(&status.signature) else { continue };
+            signals.push(DelayedSignal {
+                target: *target,
+                signature,
+                slot: status.slot,
+                lag: status.block_time.map(|t| Duration::from_secs(now.saturating_sub(t).max(0) as u64)),
+            });
+        }
+    }
+    Ok(signals)
+}
+
+/// Report copy targets' trades from polling for `duration`; these arrive
+/// seconds late, so they are logged and published for review rather than
+/// copied blind
+pub async fn watch_copy_targets_for(
+    app_state: &AppState,
+    targets: &[Pubkey],
+    newest_seen: &mut HashMap<Pubkey, Signature>,
+    config: &FallbackConfig,
+    duration: Duration,
+    logger: &Logger,
+) {
+    let started = app_state.clock.now();
+    while app_state.clock.now().saturating_duration_since(started) < duration {
+        if !copy_paused() {
+            match poll_copy_targets(app_state, targets, newest_seen).await {
+                Ok(signals) => {
+                    for signal in signals {
+                        logger.log(format!(
+                            "[DELAYED COPY SIGNAL] => {} sent {} at slot {} ({} behind, via RPC polling)",
+                            signal.target,
+                            signal.signature,
+                            signal.slot,
+                            signal.lag.map(|lag| format!("{}s", lag.as_secs())).unwrap_or_else(|| "unknown".to_string()),
+                        ).yellow().to_string());
+                    }
+                }
+                Err(e) => logger.log(format!("[RPC FALLBACK] => Polling copy targets failed: {}", e).red().to_string()),
+            }
+        }
+        app_state.clock.sleep(config.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    use crate::common::sync::LockExt;
+    use crate::services::notifier::Notifier;
+
+    /// Stands in for vault or signature polling, noting the mode it ran in
+    #[derive(Default)]
+    struct MockPoller {
+        /// (fallback active, copy paused) per round
+        rounds: Vec<(bool, bool)>,
+    }
+
+    #[async_trait]
+    impl FallbackPoller for MockPoller {
+        async fn poll_for(&mut self, app_state: &AppState, _config: &FallbackConfig, duration: Duration, _logger: &Logger) {
+            self.rounds.push((is_active(), copy_paused()));
+            app_state.clock.sleep(duration).await;
+        }
+    }
+
+    /// Fails the first `failures` attempts, then connects
+    fn flaky(failures: u32, attempts: &Cell<u32>) -> impl FnMut() -> std::future::Ready<Result<u32, String>> + '_ {
+        move || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            std::future::ready(if attempt <= failures { Err("connection refused".to_string()) } else { Ok(attempt) })
+        }
+    }
+
+    #[tokio::test]
+    async fn polls_while_the_stream_is_down_and_switches_back_once_it_connects() {
+        let (notifier, sent) = Notifier::recording();
+        let app_state = AppState::for_tests_with_rpc("succeeds", notifier);
+        let config = FallbackConfig {
+            poll_interval: Duration::from_secs(3),
+            probe_interval: Duration::from_secs(60),
+            copy_enabled: false,
+        };
+        let logger = Logger::new(String::new());
+        let mut backoff = ReconnectBackoff::from_env();
+        let mut poller = MockPoller::default();
+        let attempts = Cell::new(0);
+        let started = app_state.clock.now();
+
+        let connected =
+            connect_with_fallback(&app_state, Some(config), &mut backoff, &mut poller, "CONNECT", &logger, flaky(2, &attempts)).await;
+
+        assert_eq!(connected, 3);
+        // Two failed attempts, each followed by one probe interval of polling
+        assert_eq!(poller.rounds, [(true, true), (true, true)]);
+        assert_eq!(app_state.clock.now() - started, Duration::from_secs(120));
+        assert!(!is_active());
+        assert!(!copy_paused());
+        assert_eq!(STREAM_FALLBACK_ACTIVE.get(), 0);
+        let titles: Vec<String> = sent.lock_or_recover().iter().map(|(title, _)| title.clone()).collect();
+        assert_eq!(titles, ["Stream fallback active", "Stream recovered"]);
+    }
+
+    #[tokio::test]
+    async fn without_fallback_it_backs_off_and_never_polls() {
+        let (notifier, sent) = Notifier::recording();
+        let app_state = AppState::for_tests_with_rpc("succeeds", notifier);
+        let logger = Logger::new(String::new());
+        let mut backoff = ReconnectBackoff::from_env();
+        let mut poller = MockPoller::default();
+        let attempts = Cell::new(0);
+        let started = app_state.clock.now();
+
+        let connected = connect_with_fallback(&app_state, None, &mut backoff, &mut poller, "RECONNECT", &logger, flaky(3, &attempts)).await;
+
+        assert_eq!(connected, 4);
+        assert!(poller.rounds.is_empty());
+        // 1s, 2s and 4s with the default backoff
+        assert_eq!(app_state.clock.now() - started, Duration::from_secs(7));
+        assert!(sent.lock_or_recover().is_empty());
+    }
+
+    /// What five scans of a 1_000 mint map did while a writer kept pricing it
+    struct ScanRun {
+        pairs: usize,
+        best_spread_pct: f64,
+        scan_time: Duration,
+        writes: u64,
+        slowest_write: Duration,
+        mints: usize,
+    }
+
+    fn scan_while_writing() -> ScanRun {
+        const DEXES: [&str; 6] = ["PumpSwap", "Raydium AMM", "Raydium CPMM", "Raydium CLMM", "Orca", "Meteora DLMM"];
+        let mints: Vec<String> = (0..1_000).map(|i| format!("mint{}", i)).collect();
+        let prices = Arc::new(PriceMap::new());
+        for (i, mint) in mints.iter().enumerate() {
+            let dex_prices = DEXES
+                .iter()
+                .enumerate()
+                .map(|(j, dex)| {
+                    let price = Price::new(1e-6 * (1.0 + (i * 6 + j) as f64 / 1e4)).unwrap();
+                    (dex.to_string(), (price, 50_000_000_000, 1, QuoteAsset::Sol))
+                })
+                .collect();
+            prices.insert(mint.clone(), dex_prices);
+        }
+
+        // The stream keeps pricing every mint while the scan runs
+        let scanning = Arc::new(AtomicBool::new(true));
+        let writer = {
+            let (prices, scanning, mints) = (Arc::clone(&prices), Arc::clone(&scanning), mints.clone());
+            std::thread::spawn(move || {
+                let (mut writes, mut slowest) = (0u64, Duration::ZERO);
+                while scanning.load(Ordering::Acquire) {
+                    for mint in &mints {
+                        let started = std::time::Instant::now();
+                        let price = Price::new(1e-6 * (1.0 + writes as f64 / 1e9)).unwrap();
+                        prices
+                            .entry(mint.clone())
+                            .or_insert_with(HashMap::new)
+                            .insert("PumpSwap".to_string(), (price, 50_000_000_000, 2 + writes, QuoteAsset::Sol));
+                        slowest = slowest.max(started.elapsed());
+                        writes += 1;
+                    }
+                }
+                (writes, slowest)
+            })
+        };
+
+        let started = std::time::Instant::now();
+        let (mut pairs, mut best_spread_pct) = (0usize, 0.0_f64);
+        for _ in 0..5 {
+            for dex_prices in snapshot(&prices).values() {
+                for (i, (_, (price1, ..))) in dex_prices.iter().enumerate() {
+                    for (_, (price2, ..)) in dex_prices.iter().skip(i + 1) {
+                        let (low, high) = (price1.value().min(price2.value()), price1.value().max(price2.value()));
+                        best_spread_pct = best_spread_pct.max((high - low) / low * 100.0);
+                        pairs += 1;
+                    }
+                }
+            }
+        }
+        let scan_time = started.elapsed();
+        scanning.store(false, Ordering::Release);
+        let (writes, slowest_write) = writer.join().unwrap();
+        ScanRun { pairs, best_spread_pct, scan_time, writes, slowest_write, mints: prices.len() }
+    }
+
+    #[test]
+    fn scanning_a_snapshot_does_not_hold_up_writers() {
+        let run = scan_while_writing();
+        assert_eq!(run.pairs, 5 * 1_000 * 15);
+        assert!(run.best_spread_pct > 0.0);
+        assert!(run.writes > 0, "no price landed while the scan ran");
+        assert_eq!(run.mints, 1_000);
+    }
+
+    // Wall-clock budgets flake on loaded CI runners: `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn scans_and_writes_stay_within_their_time_budgets() {
+        let run = scan_while_writing();
+        assert!(run.scan_time < Duration::from_secs(2), "five scans took {:?}", run.scan_time);
+        assert!(run.slowest_write < Duration::from_millis(100), "a write waited {:?}", run.slowest_write);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::dex::dex_registry::DEXRegistry;
+
+/// One leg of a trade that has been split across several pools
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteLeg {
+    pub dex_name: String,
+    pub pool_id: String,
+    pub amount_in: u64,
+}
+
+/// Split `total_in` lamports across pools in proportion to their liquidity.
+///
+/// `pools` holds `(dex_name, pool_id, liquidity)` tuples. Legs that end up below
+/// their venue minimum are folded into the remaining legs, see `merge_dust_legs`.
+pub fn split_by_liquidity(
+    total_in: u64,
+    pools: &[(String, String, u64)],
+    registry: &DEXRegistry,
+) -> Vec<RouteLeg> {
+    let total_liquidity: u128 = pools.iter().map(|(_, _, liquidity)| *liquidity as u128).sum();
+    if total_in == 0 || total_liquidity == 0 {
+        return Vec::new();
+    }
+
+    let mut legs: Vec<RouteLeg> = pools
+        .iter()
+        .map(|(dex_name, pool_id, liquidity)| RouteLeg {
+            dex_name: dex_name.clone(),
+            pool_id: pool_id.clone(),
+            amount_in: ((total_in as u128 * *liquidity as u128) / total_liquidity) as u64,
+        })
+        .collect();
+
+    // Integer division leaves a few lamports behind, give them to the deepest pool
+    let allocated: u64 = legs.iter().map(|leg| leg.amount_in).sum();
+    if let Some(largest) = legs.iter_mut().max_by_key(|leg| leg.amount_in) {
+        largest.amount_in += total_in - allocated;
+    }
+
+    merge_dust_legs(legs, registry)
+}
+
+/// Drop legs below their venue minimum and hand their amount to the largest remaining leg.
+///
+/// If no leg clears its minimum the whole route is dust and an empty vector is returned.
+pub fn merge_dust_legs(legs: Vec<RouteLeg>, registry: &DEXRegistry) -> Vec<RouteLeg> {
+    let (mut kept, dust): (Vec<RouteLeg>, Vec<RouteLeg>) = legs
+        .into_iter()
+        .filter(|leg| leg.amount_in > 0)
+        .partition(|leg| leg.amount_in >= registry.min_trade_in_lamports(&leg.dex_name));
+
+    let dust_total: u64 = dust.iter().map(|leg| leg.amount_in).sum();
+    if dust_total == 0 {
+        return kept;
+    }
+
+    match kept.iter_mut().max_by_key(|leg| leg.amount_in) {
+        Some(largest) => {
+            largest.amount_in += dust_total;
+            kept
+        }
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(dex: &str, id: &str, liquidity: u64) -> (String, String, u64) {
+        (dex.to_string(), id.to_string(), liquidity)
+    }
+
+    #[test]
+    fn dust_leg_is_merged_into_the_larger_leg() {
+        let registry = DEXRegistry::new();
+        // PumpSwap's share is 0.5M lamports, under its 1M minimum
+        let pools = [pool("pumpswap", "pump", 5), pool("raydium_amm", "ray", 95)];
+        let legs = split_by_liquidity(10_000_000, &pools, &registry);
+
+        assert_eq!(legs, vec![RouteLeg { dex_name: "raydium_amm".to_string(), pool_id: "ray".to_string(), amount_in: 10_000_000 }]);
+    }
+
+    #[test]
+    fn no_leg_is_left_below_its_venue_minimum() {
+        let registry = DEXRegistry::new();
+        let pools = [
+            pool("pumpswap", "a", 1),
+            pool("raydium_amm", "b", 40),
+            pool("pumpswap", "c", 59),
+            pool("raydium_amm", "d", 0),
+        ];
+        let total = 20_000_000;
+        let legs = split_by_liquidity(total, &pools, &registry);
+
+        assert!(!legs.is_empty());
+        for leg in &legs {
+            assert!(leg.amount_in >= registry.min_trade_in_lamports(&leg.dex_name), "{:?} is dust", leg);
+        }
+        assert_eq!(legs.iter().map(|leg| leg.amount_in).sum::<u64>(), total);
+    }
+
+    #[test]
+    fn route_of_only_dust_is_dropped() {
+        let registry = DEXRegistry::new();
+        let legs = vec![
+            RouteLeg { dex_name: "pumpswap".to_string(), pool_id: "a".to_string(), amount_in: 400_000 },
+            RouteLeg { dex_name: "pumpswap".to_string(), pool_id: "b".to_string(), amount_in: 500_000 },
+        ];
+        assert!(merge_dust_legs(legs, &registry).is_empty());
+    }
+}
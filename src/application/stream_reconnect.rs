@@ -0,0 +1,152 @@
+//! Keeps the Yellowstone subscriptions alive across stream errors and stalls
+//!
+//! A monitor whose stream errors, ends or goes quiet resubscribes with the
+//! same request and exponential backoff, asking for `from_slot` at the last
+//! slot it saw so the gap is replayed instead of lost. Transactions the
+//! replay repeats are recognised by signature and skipped.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateTransaction;
+
+/// Signatures remembered for replay detection, well over one slot's worth
+const SEEN_SIGNATURES: usize = 20_000;
+
+/// `STALE_CONNECTION_SECS`: how long a stream may go without a message
+/// before the watchdog forces a resubscribe
+pub fn stale_after() -> Duration {
+    let secs = std::env::var("STALE_CONNECTION_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(300);
+    Duration::from_secs(secs.max(1))
+}
+
+/// How often the watchdog looks, often enough to catch a stall within half
+/// the threshold past it
+pub fn watchdog_interval(stale_after: Duration) -> Duration {
+    (stale_after / 2).clamp(Duration::from_secs(1), Duration::from_secs(120))
+}
+
+/// `GRPC_RECONNECT_BACKOFF_MS` / `GRPC_RECONNECT_BACKOFF_MAX_MS`: delay before
+/// the first resubscribe attempt, doubled per failed attempt up to the maximum
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl ReconnectBackoff {
+    pub fn from_env() -> Self {
+        let parse = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let initial = Duration::from_millis(parse("GRPC_RECONNECT_BACKOFF_MS", 1_000).max(1));
+        let max = Duration::from_millis(parse("GRPC_RECONNECT_BACKOFF_MAX_MS", 60_000)).max(initial);
+        Self { initial, max, next: initial }
+    }
+
+    /// Wait before the next attempt
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+
+    /// Back to the initial delay once a connection delivers again
+    pub fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+/// How far a monitor's stream got, and what it already handled
+#[derive(Debug, Default)]
+pub struct StreamCursor {
+    last_slot: Option<u64>,
+    /// Whether the current connection delivered any slot or transaction
+    live: bool,
+    seen: HashSet<Vec<u8>>,
+    seen_order: VecDeque<Vec<u8>>,
+}
+
+impl StreamCursor {
+    pub fn note_slot(&mut self, slot: u64) {
+        self.live = true;
+        if self.last_slot.map_or(true, |last| slot > last) {
+            self.last_slot = Some(slot);
+        }
+    }
+
+    /// Record `txn`; false when it was already handled before a resubscribe
+    pub fn first_sighting(&mut self, txn: &SubscribeUpdateTransaction) -> bool {
+        self.note_slot(txn.slot);
+        let Some(signature) = txn.transaction.as_ref().map(|info| info.signature.clone()) else {
+            return true;
+        };
+        if !self.seen.insert(signature.clone()) {
+            return false;
+        }
+        self.seen_order.push_back(signature);
+        if self.seen_order.len() > SEEN_SIGNATURES {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// `from_slot` for the next subscription: the last slot seen, unless the
+    /// connection being replaced never delivered, in which case the resume
+    /// point itself may be what the endpoint rejects and the live tip is used
+    pub fn resume_from(&mut self) -> Option<u64> {
+        let from_slot = if self.live { self.last_slot } else { None };
+        self.live = false;
+        from_slot
+    }
+}
+
+/// One stream's watchdog reports; each monitor owns its own, so a quiet
+/// stream never makes a busy one resubscribe
+#[derive(Debug, Default)]
+pub struct StaleSignal {
+    /// Woken when the watchdog finds no message arrived for too long
+    notify: Notify,
+    /// Set with each report until the monitor acts on it, so a report made
+    /// while the monitor is busy with a message isn't lost
+    pending: AtomicBool,
+}
+
+impl StaleSignal {
+    /// Ask the monitor to drop its stream and resubscribe, now if it is
+    /// waiting in `stale()` and otherwise the next time it does
+    pub fn report(&self) {
+        self.pending.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Resolves when the watchdog reports the stream stale, consuming the report
+    pub async fn stale(&self) {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            // Registered before the flag is read, so a report in between wakes it
+            notified.as_mut().enable();
+            if self.pending.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Drop a report made while resubscribing; it was about the old stream
+    pub fn clear(&self) {
+        self.pending.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use colored::Colorize;
+use tokio::task::{Id, JoinHandle};
+
+use crate::common::{config::AppState, logger::Logger, metrics::TASK_RESTARTS};
+use crate::engine::liquidation;
+
+/// A task that stays up this long has its restart count and backoff reset
+const STABLE_AFTER: Duration = Duration::from_secs(600);
+
+lazy_static::lazy_static! {
+    // Backtraces captured by the panic hook, picked up by the supervisor of the panicking task
+    static ref PANIC_BACKTRACES: Mutex<HashMap<Id, String>> = Mutex::new(HashMap::new());
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// `TASK_MAX_RESTARTS`, `TASK_RESTART_BACKOFF_MS` and `TASK_RESTART_BACKOFF_MAX_MS`
+#[derive(Debug, Clone, Copy)]
+pub struct SupervisorConfig {
+    /// Restarts allowed before the task is given up on and the kill switch fires
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled on every further one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl SupervisorConfig {
+    pub fn from_env() -> Self {
+        let parse = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            max_restarts: parse("TASK_MAX_RESTARTS", 5) as u32,
+            initial_backoff: Duration::from_millis(parse("TASK_RESTART_BACKOFF_MS", 1_000)),
+            max_backoff: Duration::from_millis(parse("TASK_RESTART_BACKOFF_MAX_MS", 60_000)),
+        }
+    }
+
+    /// Delay before restart number `restart` (1-based)
+    pub fn backoff(&self, restart: u32) -> Duration {
+        let factor = 2u32.saturating_pow(restart.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Keep the default panic output and also record a backtrace for the supervisor
+fn install_panic_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(id) = tokio::task::try_id() {
+                let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+                if let Ok(mut backtraces) = PANIC_BACKTRACES.lock() {
+                    backtraces.insert(id, backtrace);
+                }
+            }
+            previous(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+/// Run `task` in its own tokio task and restart it whenever it panics
+///
+/// `task` is called again for every restart, so whatever it needs must come
+/// from `app_state` (or global monitor state) rather than from values it
+/// consumed on the previous run. A task that returns normally is not
+/// restarted. After `max_restarts` panics without a stable run in between the
+/// supervisor alerts and triggers panic liquidation.
+pub fn supervise<F, Fut>(app_state: Arc<AppState>, name: &'static str, config: SupervisorConfig, task: F) -> JoinHandle<()>
+where
+    F: Fn(Arc<AppState>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    install_panic_hook();
+    tokio::spawn(async move {
+        let logger = Logger::new(format!("[SUPERVISOR:{}] => ", name).magenta().to_string());
+        let mut restarts = 0u32;
+        loop {
+            let started = app_state.clock.now();
+            let error = match tokio::spawn(task(Arc::clone(&app_state))).await {
+                Ok(()) => {
+                    logger.log("[TASK EXITED] => Returned normally, not restarting".to_string());
+                    return;
+                }
+                Err(error) => error,
+            };
+            if !error.is_panic() {
+                logger.log("[TASK CANCELLED] => Not restarting".yellow().to_string());
+                return;
+            }
+
+            let id = error.id();
+            let message = panic_message(&*error.into_panic());
+            let backtrace = PANIC_BACKTRACES
+                .lock()
+                .ok()
+                .and_then(|mut backtraces| backtraces.remove(&id))
+                .unwrap_or_else(|| "no backtrace captured".to_string());
+            logger.log(format!("[TASK PANICKED] => {}\n{}", message, backtrace).red().bold().to_string());
+
+            if app_state.clock.now().saturating_duration_since(started) >= STABLE_AFTER {
+                restarts = 0;
+            }
+            restarts += 1;
+            if restarts > config.max_restarts {
+                let body = format!("{} panicked {} times, last: {}", name, restarts, message);
+                app_state.notifier.alert("Task gave up restarting", &body).await;
+                if let Err(e) = liquidation::liquidate_all(&app_state).await {
+                    logger.log(format!("[KILL SWITCH] => Liquidation failed: {}", e).red().bold().to_string());
+                }
+                return;
+            }
+
+            TASK_RESTARTS.with_label_values(&[name]).inc();
+            let delay = config.backoff(restarts);
+            logger.log(format!(
+                "[RESTARTING] => Attempt {}/{} in {:?}", restarts, config.max_restarts, delay
+            ).yellow().to_string());
+            app_state.clock.sleep(delay).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::services::notifier::Notifier;
+
+    fn config(max_restarts: u32) -> SupervisorConfig {
+        SupervisorConfig {
+            max_restarts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(250),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let config = config(5);
+        let delays: Vec<u64> = (1..=4).map(|n| config.backoff(n).as_millis() as u64).collect();
+        assert_eq!(delays, vec![100, 200, 250, 250]);
+        assert_eq!(config.backoff(u32::MAX), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn panic_payloads_are_readable() {
+        assert_eq!(panic_message(&"static message"), "static message");
+        assert_eq!(panic_message(&"owned message".to_string()), "owned message");
+        assert_eq!(panic_message(&42u32), "non-string panic payload");
+    }
+
+    #[tokio::test]
+    async fn panicking_task_is_restarted_and_counted() {
+        let app_state = AppState::for_tests();
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = Arc::clone(&runs);
+        let before = TASK_RESTARTS.with_label_values(&["test_flaky"]).get();
+
+        let handle = supervise(app_state, "test_flaky", config(5), move |_| {
+            let runs = Arc::clone(&task_runs);
+            async move {
+                // Panics twice, then finishes cleanly
+                if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("injected failure");
+                }
+            }
+        });
+        handle.await.unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(TASK_RESTARTS.with_label_values(&["test_flaky"]).get() - before, 2);
+    }
+
+    #[tokio::test]
+    async fn task_that_keeps_panicking_is_given_up_with_an_alert() {
+        let (notifier, sent) = Notifier::recording();
+        // RPC that fails every call, so the liquidation the give-up triggers ends quickly
+        let app_state = AppState::for_tests_with_rpc("fails", notifier);
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = Arc::clone(&runs);
+        let before = TASK_RESTARTS.with_label_values(&["test_doomed"]).get();
+
+        let handle = supervise(app_state, "test_doomed", config(2), move |_| {
+            let runs = Arc::clone(&task_runs);
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                panic!("always fails");
+            }
+        });
+        handle.await.unwrap();
+
+        // The first run and two restarts, the third panic gives up
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(TASK_RESTARTS.with_label_values(&["test_doomed"]).get() - before, 2);
+        let sent = sent.lock().unwrap();
+        let (_, body) = sent.iter().find(|(title, _)| title == "Task gave up restarting").expect("give-up alert");
+        assert!(body.contains("test_doomed panicked 3 times, last: always fails"), "{}", body);
+        assert!(liquidation::is_liquidating(&app_state));
+    }
+}
@@ -0,0 +1,287 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signer::Signer, system_instruction, system_program};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+
+use crate::common::{
+    config::AppState,
+    format::{fmt_lamports_exact, fmt_sol},
+    logger::Logger,
+};
+use crate::core::priority_fee::PriorityFeeStrategy;
+use crate::core::tx;
+use crate::dex::pump_swap::SOL_MINT;
+use crate::engine::blockhash_cache;
+use crate::record::store::TradeRecord;
+use crate::services::rpc_pool::CallClass;
+
+/// Fee the sweep transfer itself pays, kept back from the swept amount
+const SWEEP_FEE_LAMPORTS: u64 = 5_000;
+
+/// Why the sweep won't run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SweepRefusal {
+    /// `SWEEP_ENABLED` is not "true"
+    Disabled,
+    MissingColdAddress,
+    InvalidColdAddress(String),
+    /// The cold address is the trading wallet itself
+    ColdIsHot,
+    /// The cold address is off the ed25519 curve, so no key can sign for it
+    ColdOffCurve,
+    /// The cold account exists but isn't a plain system-owned wallet
+    ColdNotWallet { owner: Pubkey, executable: bool },
+}
+
+impl std::fmt::Display for SweepRefusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Disabled => write!(f, "Sweep disabled, set SWEEP_ENABLED=true to enable"),
+            Self::MissingColdAddress => write!(f, "COLD_WALLET_ADDRESS is not set"),
+            Self::InvalidColdAddress(value) => write!(f, "COLD_WALLET_ADDRESS {} is not a valid pubkey", value),
+            Self::ColdIsHot => write!(f, "COLD_WALLET_ADDRESS is the trading wallet"),
+            Self::ColdOffCurve => write!(f, "COLD_WALLET_ADDRESS is a program address, not a wallet"),
+            Self::ColdNotWallet { owner, executable } => write!(
+                f,
+                "COLD_WALLET_ADDRESS is owned by {} (executable: {}), expected a system wallet",
+                owner, executable
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SweepRefusal {}
+
+/// `SWEEP_ENABLED`, `COLD_WALLET_ADDRESS`, `TARGET_OPERATING_BALANCE`,
+/// `SWEEP_THRESHOLD` and `SWEEP_INTERVAL_SECS`
+#[derive(Debug, Clone)]
+pub struct SweepConfig {
+    pub cold_address: Pubkey,
+    /// Lamports always left in the trading wallet
+    pub target_operating_balance: u64,
+    /// How far the balance must exceed the target before a sweep runs
+    pub sweep_threshold: u64,
+    pub every: Duration,
+}
+
+impl SweepConfig {
+    /// Read the sweep settings and check the cold address against the trading wallet
+    pub fn from_env(hot: &Pubkey) -> Result<Self, SweepRefusal> {
+        let enabled = std::env::var("SWEEP_ENABLED")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if !enabled {
+            return Err(SweepRefusal::Disabled);
+        }
+        let cold = std::env::var("COLD_WALLET_ADDRESS")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .ok_or(SweepRefusal::MissingColdAddress)?;
+        let cold_address = check_cold_address(cold.trim(), hot)?;
+
+        let lamports = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| u64::from_str(&v).ok())
+                .unwrap_or(default)
+        };
+        Ok(Self {
+            cold_address,
+            target_operating_balance: lamports("TARGET_OPERATING_BALANCE", 1_000_000_000),
+            sweep_threshold: lamports("SWEEP_THRESHOLD", 500_000_000),
+            every: Duration::from_secs(lamports("SWEEP_INTERVAL_SECS", 300)),
+        })
+    }
+
+    /// Lamports to sweep from `balance` while `exposure` is out in open
+    /// positions; 0 until the balance clears target + threshold
+    pub fn sweep_amount(&self, balance: u64, exposure: u64) -> u64 {
+        if balance <= self.target_operating_balance.saturating_add(self.sweep_threshold) {
+            return 0;
+        }
+        // Exposure is SOL already promised to trades, it can't leave the wallet
+        balance.saturating_sub(
+            self.target_operating_balance
+                .saturating_add(exposure)
+                .saturating_add(SWEEP_FEE_LAMPORTS),
+        )
+    }
+}
+
+/// Parse the cold address and refuse anything that can't be a separate wallet
+pub fn check_cold_address(value: &str, hot: &Pubkey) -> Result<Pubkey, SweepRefusal> {
+    let cold = Pubkey::from_str(value).map_err(|_| SweepRefusal::InvalidColdAddress(value.to_string()))?;
+    if &cold == hot {
+        return Err(SweepRefusal::ColdIsHot);
+    }
+    if !cold.is_on_curve() {
+        return Err(SweepRefusal::ColdOffCurve);
+    }
+    Ok(cold)
+}
+
+/// Refuse a cold account that exists as anything other than a system wallet.
+/// An account that doesn't exist yet is fine, the transfer creates it.
+async fn check_cold_owner(app_state: &AppState, cold: Pubkey) -> Result<()> {
+    let account = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| async move {
+            client
+                .get_account_with_commitment(&cold, client.commitment())
+                .await
+        })
+        .await?
+        .value;
+    if let Some(account) = account {
+        if account.owner != system_program::id() || account.executable {
+            return Err(SweepRefusal::ColdNotWallet {
+                owner: account.owner,
+                executable: account.executable,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Check the balance once and sweep the excess, returns the lamports sent
+async fn sweep_once(app_state: &AppState, config: &SweepConfig, logger: &Logger) -> Result<u64> {
+    let hot = app_state.wallet.pubkey();
+    let balance = app_state
+        .rpc_pool
+        .call(CallClass::Refresh, |client| async move { client.get_balance(&hot).await })
+        .await?;
+    let exposure = app_state.capital.snapshot().total_exposure;
+    let amount = config.sweep_amount(balance, exposure);
+    if amount == 0 {
+        return Ok(0);
+    }
+
+    check_cold_owner(app_state, config.cold_address).await?;
+
+    let recent_blockhash = blockhash_cache::recent(app_state, CallClass::Refresh, logger).await?;
+    let instructions = vec![system_instruction::transfer(&hot, &config.cold_address, amount)];
+    let signature = tx::new_signed_and_send_normal(recent_blockhash, &app_state.wallet, instructions, PriorityFeeStrategy::from_env(), logger)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Sender returned no signature"))?;
+
+    // Recorded as a withdrawal so the ledger balances against the wallet
+    let fee = tx::take_sent_fee(&signature);
+    let withdrawal = TradeRecord {
+        trade_id: signature.clone(),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        mint: SOL_MINT.to_string(),
+        side: "withdrawal".to_string(),
+        sol_amount: Some(amount),
+        token_amount: None,
+        price: None,
+        signature: Some(signature.clone()),
+        priority_fee_strategy: fee.map(|fee| fee.strategy.to_string()),
+        priority_fee_lamports: fee.map(|fee| fee.budget.fee_lamports()),
+    };
+    if let Err(e) = app_state.store.record_trade(&withdrawal) {
+        logger.log(format!("[RECORD] => Failed to store withdrawal: {}", e).red().to_string());
+    }
+
+    logger.log(format!(
+        "[SWEPT] => {} to {} (balance {}, exposure {}) TX_HASH: (https://solscan.io/tx/{})",
+        fmt_sol(amount), config.cold_address, fmt_sol(balance), fmt_sol(exposure), signature
+    ).green().to_string());
+    app_state.notifier.alert(
+        "Profits swept to cold wallet",
+        &format!(
+            "Sent {} to {}, operating balance kept at {}. Signature: {}",
+            fmt_lamports_exact(amount),
+            config.cold_address,
+            fmt_sol(balance - amount),
+            signature
+        ),
+    ).await;
+    Ok(amount)
+}
+
+/// Sweep profits above the operating balance to the cold wallet until the process exits
+pub async fn run_sweep(app_state: Arc<AppState>, config: SweepConfig) {
+    let logger = Logger::new("[SWEEP] => ".blue().bold().to_string());
+    logger.log(format!(
+        "[SWEEP ENABLED] => Cold wallet {}, keeping {} + threshold {}",
+        config.cold_address,
+        fmt_sol(config.target_operating_balance),
+        fmt_sol(config.sweep_threshold)
+    ).blue().to_string());
+
+    loop {
+        if let Err(e) = sweep_once(&app_state, &config, &logger).await {
+            logger.log(format!("[SWEEP FAILED] => {}", e).red().to_string());
+            if e.downcast_ref::<SweepRefusal>().is_some() {
+                app_state.notifier.alert("Cold sweep stopped", &e.to_string()).await;
+                return;
+            }
+        }
+        app_state.clock.sleep(app_state.rpc_pool.background_interval(config.every)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_sdk::signature::Keypair;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn config() -> SweepConfig {
+        SweepConfig {
+            cold_address: Keypair::new().pubkey(),
+            target_operating_balance: SOL,
+            sweep_threshold: SOL / 2,
+            every: Duration::from_secs(300),
+        }
+    }
+
+    #[test]
+    fn nothing_is_swept_until_the_threshold_is_cleared() {
+        let config = config();
+        assert_eq!(config.sweep_amount(0, 0), 0);
+        assert_eq!(config.sweep_amount(SOL, 0), 0);
+        assert_eq!(config.sweep_amount(SOL + SOL / 2, 0), 0);
+        assert_eq!(config.sweep_amount(SOL + SOL / 2 + 1, 0), SOL / 2 + 1 - SWEEP_FEE_LAMPORTS);
+    }
+
+    #[test]
+    fn exposure_and_fee_stay_in_the_wallet() {
+        let config = config();
+        let balance = 3 * SOL;
+        assert_eq!(config.sweep_amount(balance, 0), 2 * SOL - SWEEP_FEE_LAMPORTS);
+        assert_eq!(config.sweep_amount(balance, SOL / 4), 2 * SOL - SOL / 4 - SWEEP_FEE_LAMPORTS);
+
+        // Whatever is swept, the operating balance and open exposure are left behind
+        for exposure in [0, SOL / 3, SOL, 2 * SOL, 5 * SOL, u64::MAX] {
+            let swept = config.sweep_amount(balance, exposure);
+            assert!(balance - swept >= config.target_operating_balance.saturating_add(exposure).min(balance));
+        }
+        assert_eq!(config.sweep_amount(balance, 2 * SOL), 0);
+        assert_eq!(config.sweep_amount(u64::MAX, u64::MAX), 0);
+    }
+
+    #[test]
+    fn cold_address_must_be_a_separate_wallet() {
+        let hot = Keypair::new().pubkey();
+        let cold = Keypair::new().pubkey();
+        assert_eq!(check_cold_address(&cold.to_string(), &hot), Ok(cold));
+
+        assert_eq!(check_cold_address(&hot.to_string(), &hot), Err(SweepRefusal::ColdIsHot));
+        assert_eq!(
+            check_cold_address("not-a-pubkey", &hot),
+            Err(SweepRefusal::InvalidColdAddress("not-a-pubkey".to_string()))
+        );
+        assert!(matches!(check_cold_address("0OIl", &hot), Err(SweepRefusal::InvalidColdAddress(_))));
+
+        let (pda, _) = Pubkey::find_program_address(&[b"vault"], &system_program::id());
+        assert_eq!(check_cold_address(&pda.to_string(), &hot), Err(SweepRefusal::ColdOffCurve));
+    }
+}
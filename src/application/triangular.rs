@@ -0,0 +1,253 @@
+//! Triangular arbitrage across the cached pools
+//!
+//! Every cached pool with a price links its two mints both ways: base to
+//! quote at the price, quote to base at its inverse, each less the venue's
+//! swap fee. A round trip from SOL whose rates multiply past one returns
+//! more SOL than it put in, which on weights of `-ln(rate)` is a negative
+//! cycle. Bellman-Ford bounded by the hop count finds the cheapest closing
+//! walk of each length from SOL, so each scan reports at most one cycle per
+//! length; the two-hop ones are left to the pairwise scan.
+//!
+//! Hop outputs for the trade size are walked through the pool's reserves
+//! where the pool trades against SOL and its SOL side is known, at the price
+//! otherwise. Amounts are in whole tokens, as pool prices are.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use serde::Serialize;
+use spl_token::solana_program::native_token::{lamports_to_sol, sol_to_lamports};
+
+use crate::dex::pump_swap::SOL_MINT;
+use crate::engine::pool_discovery::{PoolCache, PoolInfo};
+
+/// `TRIANGULAR_ARBITRAGE` and `TRIANGULAR_MAX_HOPS`
+#[derive(Debug, Clone)]
+pub struct TriangularConfig {
+    /// Longest cycle searched, at least 3
+    pub max_hops: usize,
+}
+
+impl TriangularConfig {
+    /// None unless `TRIANGULAR_ARBITRAGE` is set
+    pub fn from_env() -> Option<Self> {
+        let enabled = std::env::var("TRIANGULAR_ARBITRAGE")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        let max_hops = std::env::var("TRIANGULAR_MAX_HOPS")
+            .ok()
+            .and_then(|v| usize::from_str(v.trim()).ok())
+            .unwrap_or(3)
+            .max(3);
+        Some(Self { max_hops })
+    }
+}
+
+/// One swap of a cycle and what it is expected to turn out
+#[derive(Debug, Clone, Serialize)]
+pub struct Hop {
+    pub pool_id: String,
+    pub dex: String,
+    pub from_mint: String,
+    pub to_mint: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+}
+
+/// A profitable SOL round trip through three or more pools
+#[derive(Debug, Clone, Serialize)]
+pub struct TriangularOpportunity {
+    pub hops: Vec<Hop>,
+    pub input_lamports: u64,
+    /// SOL back from the last hop, after every swap fee
+    pub output_lamports: u64,
+    /// Priority fee and tip of sending it
+    pub cost_lamports: u64,
+}
+
+impl TriangularOpportunity {
+    pub fn net_profit_lamports(&self) -> i64 {
+        self.output_lamports as i64 - self.input_lamports as i64 - self.cost_lamports as i64
+    }
+
+    pub fn profit_pct(&self) -> f64 {
+        self.net_profit_lamports() as f64 / self.input_lamports.max(1) as f64 * 100.0
+    }
+
+    /// Pools in trade order, as `dex:pool`
+    pub fn route(&self) -> String {
+        self.hops.iter().map(|hop| format!("{}:{}", hop.dex, hop.pool_id)).collect::<Vec<_>>().join(" -> ")
+    }
+}
+
+/// One direction through a pool
+struct Edge<'a> {
+    pool: &'a PoolInfo,
+    from: usize,
+    to: usize,
+    /// Output per unit of input, net of the swap fee
+    rate: f64,
+    weight: f64,
+    /// Input and output reserves in whole units, when known
+    reserves: Option<(f64, f64)>,
+    fee: f64,
+}
+
+impl Edge<'_> {
+    fn output(&self, amount_in: f64) -> f64 {
+        match self.reserves {
+            Some((reserve_in, reserve_out)) => {
+                let effective = amount_in * (1.0 - self.fee);
+                reserve_out * effective / (reserve_in + effective)
+            }
+            None => amount_in * self.rate,
+        }
+    }
+}
+
+/// Profitable cycles from SOL through `cache`'s pools at `trade_size_lamports`
+///
+/// `fee_bps` gives a venue's swap fee and `usable` screens pools out, for
+/// health or liquidity; dormant tokens' pools are never used.
+pub fn find_triangles(
+    cache: &PoolCache,
+    config: &TriangularConfig,
+    fee_bps: impl Fn(&str) -> u64,
+    usable: impl Fn(&PoolInfo) -> bool,
+    trade_size_lamports: u64,
+    cost_lamports: u64,
+) -> Vec<TriangularOpportunity> {
+    let mut mints: Vec<&str> = vec![SOL_MINT];
+    let mut index: HashMap<&str, usize> = HashMap::from([(SOL_MINT, 0)]);
+
+    // A pool can be cached under both its mints; it is one pair of edges
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for (token, pools) in &cache.pools {
+        if cache.dormant.contains(token) {
+            continue;
+        }
+        for pool in pools {
+            let Some(price) = pool.last_known_price.filter(|p| p.is_finite() && *p > 0.0) else {
+                continue;
+            };
+            if pool.base_mint == pool.quote_mint || !usable(pool) || !seen.insert(pool.pool_id.as_str()) {
+                continue;
+            }
+            let fee = fee_bps(&pool.dex_name).min(10_000) as f64 / 10_000.0;
+            let base = node(&pool.base_mint, &mut mints, &mut index);
+            let quote = node(&pool.quote_mint, &mut mints, &mut index);
+            // Liquidity is the SOL side's lamports, so reserves are only
+            // known for pools against SOL
+            let sol_side = pool.liquidity.filter(|l| *l > 0).map(lamports_to_sol);
+            let (base_reserve, quote_reserve) = match sol_side {
+                Some(sol) if pool.quote_mint == SOL_MINT => (Some(sol / price), Some(sol)),
+                Some(sol) if pool.base_mint == SOL_MINT => (Some(sol), Some(sol * price)),
+                _ => (None, None),
+            };
+            let reserves = |input: Option<f64>, output: Option<f64>| input.zip(output);
+            for (from, to, gross, reserves) in [
+                (base, quote, price, reserves(base_reserve, quote_reserve)),
+                (quote, base, 1.0 / price, reserves(quote_reserve, base_reserve)),
+            ] {
+                let rate = gross * (1.0 - fee);
+                if rate > 0.0 && rate.is_finite() {
+                    edges.push(Edge { pool, from, to, rate, weight: -rate.ln(), reserves, fee });
+                }
+            }
+        }
+    }
+
+    // dist[k][v]: cheapest k-hop walk from SOL to v, through pred[k][v]
+    let nodes = mints.len();
+    let mut dist = vec![vec![f64::INFINITY; nodes]; config.max_hops + 1];
+    let mut pred: Vec<Vec<Option<usize>>> = vec![vec![None; nodes]; config.max_hops + 1];
+    dist[0][0] = 0.0;
+    for k in 1..=config.max_hops {
+        for (e, edge) in edges.iter().enumerate() {
+            let reached = dist[k - 1][edge.from];
+            if !reached.is_finite() {
+                continue;
+            }
+            // Straight back through the pool just taken only pays the fee twice
+            if pred[k - 1][edge.from].is_some_and(|p| edges[p].pool.pool_id == edge.pool.pool_id) {
+                continue;
+            }
+            if reached + edge.weight < dist[k][edge.to] {
+                dist[k][edge.to] = reached + edge.weight;
+                pred[k][edge.to] = Some(e);
+            }
+        }
+    }
+
+    let size = lamports_to_sol(trade_size_lamports);
+    let mut found = Vec::new();
+    for k in 3..=config.max_hops {
+        if dist[k][0] >= 0.0 {
+            continue;
+        }
+        let Some(path) = walk_back(&edges, &pred, k) else {
+            continue;
+        };
+        let mut amount = size;
+        let mut hops = Vec::with_capacity(path.len());
+        for edge in path.iter().map(|e| &edges[*e]) {
+            let amount_out = edge.output(amount);
+            hops.push(Hop {
+                pool_id: edge.pool.pool_id.clone(),
+                dex: edge.pool.dex_name.clone(),
+                from_mint: mints[edge.from].to_string(),
+                to_mint: mints[edge.to].to_string(),
+                amount_in: amount,
+                amount_out,
+            });
+            amount = amount_out;
+        }
+        let opportunity = TriangularOpportunity {
+            hops,
+            input_lamports: trade_size_lamports,
+            output_lamports: sol_to_lamports(amount),
+            cost_lamports,
+        };
+        if opportunity.net_profit_lamports() > 0 {
+            found.push(opportunity);
+        }
+    }
+    found.sort_by(|a, b| b.net_profit_lamports().cmp(&a.net_profit_lamports()));
+    found
+}
+
+/// Index of `mint` in the graph, added on first sight
+fn node<'a>(mint: &'a str, mints: &mut Vec<&'a str>, index: &mut HashMap<&'a str, usize>) -> usize {
+    *index.entry(mint).or_insert_with(|| {
+        mints.push(mint);
+        mints.len() - 1
+    })
+}
+
+/// The `k` edges of the walk ending back at SOL, in trade order; None when
+/// it passes a mint or a pool twice, which makes it two shorter cycles
+fn walk_back(edges: &[Edge<'_>], pred: &[Vec<Option<usize>>], k: usize) -> Option<Vec<usize>> {
+    let mut path = Vec::with_capacity(k);
+    let mut at = 0;
+    for step in (1..=k).rev() {
+        let e = pred[step][at]?;
+        path.push(e);
+        at = edges[e].from;
+    }
+    if at != 0 {
+        return None;
+    }
+    path.reverse();
+    let mut visited = HashSet::new();
+    let mut pools = HashSet::new();
+    for edge in path.iter().map(|e| &edges[*e]) {
+        if !visited.insert(edge.to) || !pools.insert(edge.pool.pool_id.as_str()) {
+            return None;
+        }
+    }
+    Some(path)
+}
@@ -0,0 +1,384 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Stdout};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table},
+    Frame, Terminal,
+};
+use tokio::sync::broadcast::error::TryRecvError;
+
+use crate::common::{
+    config::AppState,
+    events::{self, BotEvent},
+    format::{fmt_bps, fmt_pct, fmt_sol, fmt_sol_signed},
+    logger,
+};
+use crate::engine::{fill_quality, liquidation};
+
+/// Rendering never runs more often than this, so the UI can't compete with trading
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_LOG_LINES: usize = 500;
+const MAX_OPPORTUNITIES: usize = 50;
+/// A second `k` within this window fires the kill switch
+const KILL_CONFIRM_WINDOW: Duration = Duration::from_secs(5);
+
+struct StreamView {
+    connected: bool,
+    slot: Option<u64>,
+    last_seen: Instant,
+}
+
+struct PositionView {
+    strategy: String,
+    entry_price: f64,
+    last_price: Option<f64>,
+    lamports: u64,
+}
+
+impl PositionView {
+    fn pnl_pct(&self) -> Option<f64> {
+        let last = self.last_price?;
+        (self.entry_price > 0.0).then(|| (last - self.entry_price) / self.entry_price * 100.0)
+    }
+}
+
+struct OpportunityView {
+    token: String,
+    route: String,
+    net_bps: f64,
+    outcome: String,
+}
+
+#[derive(Default)]
+struct StrategyCounters {
+    opened: u64,
+    closed: u64,
+}
+
+/// Everything the screen shows, rebuilt only from events
+#[derive(Default)]
+struct TuiState {
+    streams: BTreeMap<String, StreamView>,
+    positions: BTreeMap<String, PositionView>,
+    opportunities: VecDeque<OpportunityView>,
+    outcomes: BTreeMap<String, u64>,
+    strategies: BTreeMap<String, StrategyCounters>,
+    log: VecDeque<String>,
+    dropped_events: u64,
+    kill_armed_at: Option<tokio::time::Instant>,
+}
+
+impl TuiState {
+    /// A `k` press at `now`, true when it confirms one made within the window
+    fn press_kill(&mut self, now: tokio::time::Instant) -> bool {
+        match self.kill_armed_at.take() {
+            Some(at) if now.duration_since(at) <= KILL_CONFIRM_WINDOW => true,
+            _ => {
+                self.kill_armed_at = Some(now);
+                false
+            }
+        }
+    }
+
+    /// Disarm a kill switch left unconfirmed past the window
+    fn expire_kill(&mut self, now: tokio::time::Instant) {
+        if self.kill_armed_at.is_some_and(|at| now.duration_since(at) > KILL_CONFIRM_WINDOW) {
+            self.kill_armed_at = None;
+        }
+    }
+
+    fn apply(&mut self, event: BotEvent) {
+        match event {
+            BotEvent::Log { line } => {
+                if self.log.len() == MAX_LOG_LINES {
+                    self.log.pop_front();
+                }
+                self.log.push_back(line);
+            }
+            BotEvent::Stream { name, connected, slot } => {
+                let view = self.streams.entry(name).or_insert(StreamView {
+                    connected,
+                    slot: None,
+                    last_seen: Instant::now(),
+                });
+                view.connected = connected;
+                view.slot = slot.or(view.slot);
+                view.last_seen = Instant::now();
+            }
+            BotEvent::PositionOpened { mint, strategy, entry_price, lamports } => {
+                self.strategies.entry(strategy.clone()).or_default().opened += 1;
+                self.positions.insert(mint, PositionView { strategy, entry_price, last_price: None, lamports });
+            }
+            BotEvent::PositionPrice { mint, price } => {
+                if let Some(position) = self.positions.get_mut(&mint) {
+                    position.last_price = Some(price);
+                }
+            }
+            BotEvent::PositionClosed { mint, .. } => {
+                if let Some(position) = self.positions.remove(&mint) {
+                    self.strategies.entry(position.strategy).or_default().closed += 1;
+                }
+            }
+            BotEvent::Opportunity { token, buy_dex, sell_dex, net_bps, outcome } => {
+                *self.outcomes.entry(outcome.clone()).or_default() += 1;
+                if self.opportunities.len() == MAX_OPPORTUNITIES {
+                    self.opportunities.pop_back();
+                }
+                self.opportunities.push_front(OpportunityView {
+                    token,
+                    route: format!("{} -> {}", buy_dex, sell_dex),
+                    net_bps,
+                    outcome,
+                });
+            }
+        }
+    }
+}
+
+fn short(mint: &str) -> String {
+    if mint.len() > 12 {
+        format!("{}..{}", &mint[..5], &mint[mint.len() - 5..])
+    } else {
+        mint.to_string()
+    }
+}
+
+fn draw(frame: &mut Frame, app_state: &AppState, state: &TuiState) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Percentage(40),
+            Constraint::Min(6),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(30), Constraint::Percentage(20)])
+        .split(rows[1]);
+
+    draw_status(frame, rows[0], app_state, state);
+    draw_positions(frame, middle[0], state);
+    draw_opportunities(frame, middle[1], state);
+    draw_counters(frame, middle[2], state);
+    draw_log(frame, rows[2], state);
+    draw_help(frame, rows[3], app_state, state);
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, app_state: &AppState, state: &TuiState) {
+    let mut spans = Vec::new();
+    for (name, stream) in &state.streams {
+        let lag = stream.last_seen.elapsed().as_secs_f64();
+        let (label, color) = match (stream.connected, lag) {
+            (false, _) => ("DOWN", Color::Red),
+            (true, lag) if lag > 5.0 => ("STALE", Color::Yellow),
+            _ => ("OK", Color::Green),
+        };
+        spans.push(ratatui::text::Span::styled(
+            format!(" {} {} slot {} lag {:.1}s ", name, label, stream.slot.map(|s| s.to_string()).unwrap_or("-".into()), lag),
+            Style::default().fg(color),
+        ));
+    }
+    if app_state.monitor.entries_paused() {
+        spans.push(ratatui::text::Span::styled(" ENTRIES PAUSED ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
+    }
+    for (strategy, fills) in fill_quality::snapshot() {
+        let Some(median) = fills.median else { continue };
+        let style = if fills.paused {
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let paused = if fills.paused { " PAUSED" } else { "" };
+        spans.push(ratatui::text::Span::styled(format!(" {} fills {:.1}%{} ", strategy, median * 100.0, paused), style));
+    }
+    if liquidation::is_liquidating(app_state) {
+        spans.push(ratatui::text::Span::styled(" LIQUIDATING ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+    }
+    if state.dropped_events > 0 {
+        spans.push(ratatui::text::Span::raw(format!(" {} events dropped ", state.dropped_events)));
+    }
+    frame.render_widget(
+        Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::ALL).title("Connection")),
+        area,
+    );
+}
+
+fn draw_positions(frame: &mut Frame, area: Rect, state: &TuiState) {
+    let rows = state.positions.iter().map(|(mint, p)| {
+        let pnl = p.pnl_pct();
+        let color = match pnl {
+            Some(pct) if pct < 0.0 => Color::Red,
+            Some(_) => Color::Green,
+            None => Color::Gray,
+        };
+        Row::new(vec![
+            short(mint),
+            p.strategy.clone(),
+            fmt_sol(p.lamports),
+            pnl.map(fmt_pct).unwrap_or_else(|| "-".into()),
+            pnl.map(|pct| fmt_sol_signed((p.lamports as f64 * pct / 100.0) as i64)).unwrap_or_else(|| "-".into()),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let table = Table::new(
+        rows,
+        [Constraint::Length(12), Constraint::Length(9), Constraint::Length(16), Constraint::Length(9), Constraint::Min(10)],
+    )
+    .header(Row::new(vec!["mint", "strategy", "size", "pnl", "pnl sol"]).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(Block::default().borders(Borders::ALL).title(format!("Open positions ({})", state.positions.len())));
+    frame.render_widget(table, area);
+}
+
+fn draw_opportunities(frame: &mut Frame, area: Rect, state: &TuiState) {
+    let items: Vec<ListItem> = state
+        .opportunities
+        .iter()
+        .map(|o| {
+            let color = if o.outcome == "would_execute" { Color::Green } else { Color::DarkGray };
+            ListItem::new(format!("{} {} {} {}", short(&o.token), o.route, fmt_bps(o.net_bps), o.outcome))
+                .style(Style::default().fg(color))
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title("Recent opportunities")), area);
+}
+
+fn draw_counters(frame: &mut Frame, area: Rect, state: &TuiState) {
+    let mut lines = Vec::new();
+    for (strategy, counters) in &state.strategies {
+        lines.push(Line::from(format!("{}: {} opened, {} closed", strategy, counters.opened, counters.closed)));
+    }
+    for (outcome, count) in &state.outcomes {
+        lines.push(Line::from(format!("{}: {}", outcome, count)));
+    }
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Counters")), area);
+}
+
+fn draw_log(frame: &mut Frame, area: Rect, state: &TuiState) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = state
+        .log
+        .iter()
+        .skip(state.log.len().saturating_sub(visible))
+        .map(|line| ListItem::new(line.as_str()))
+        .collect();
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title("Events")), area);
+}
+
+fn draw_help(frame: &mut Frame, area: Rect, app_state: &AppState, state: &TuiState) {
+    let kill = if state.kill_armed_at.is_some() {
+        "k again: CONFIRM KILL SWITCH"
+    } else {
+        "k: kill switch"
+    };
+    let pause = if app_state.monitor.entries_paused() { "p: resume entries" } else { "p: pause entries" };
+    frame.render_widget(Paragraph::new(format!(" {}  |  {}  |  q: quit", pause, kill)), area);
+}
+
+/// Handle pending key presses, returns true when the operator asked to quit
+fn handle_keys(app_state: &Arc<AppState>, state: &mut TuiState) -> Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+            KeyCode::Char('p') => app_state.monitor.set_entries_paused(!app_state.monitor.entries_paused()),
+            KeyCode::Char('k') => {
+                if state.press_kill(app_state.clock.now()) {
+                    let app_state = Arc::clone(app_state);
+                    tokio::spawn(async move {
+                        if let Err(e) = liquidation::liquidate_all(&app_state).await {
+                            events::publish(BotEvent::Log { line: format!("Kill switch liquidation failed: {}", e) });
+                        }
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    state.expire_kill(app_state.clock.now());
+    Ok(false)
+}
+
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) {
+    let _ = disable_raw_mode();
+    let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
+    let _ = terminal.show_cursor();
+}
+
+async fn event_loop(app_state: &Arc<AppState>, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    let mut events = events::subscribe();
+    let mut state = TuiState::default();
+    let mut frames = tokio::time::interval(FRAME_INTERVAL);
+    frames.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        frames.tick().await;
+        loop {
+            match events.try_recv() {
+                Ok(event) => state.apply(event),
+                Err(TryRecvError::Lagged(missed)) => state.dropped_events += missed,
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+            }
+        }
+        if handle_keys(app_state, &mut state)? {
+            return Ok(());
+        }
+        terminal.draw(|frame| draw(frame, app_state, &state))?;
+    }
+}
+
+/// Take over the terminal until the operator quits; log lines go to the
+/// events panel meanwhile and back to stdout afterwards
+pub async fn run_tui(app_state: Arc<AppState>) -> Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    logger::capture_console(true);
+
+    let result = event_loop(&app_state, &mut terminal).await;
+
+    logger::capture_console(false);
+    restore_terminal(&mut terminal);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::clock::{Clock, MockClock};
+
+    #[tokio::test]
+    async fn kill_switch_needs_a_second_press_within_the_window() {
+        let clock = MockClock::new();
+        let mut state = TuiState::default();
+
+        assert!(!state.press_kill(clock.now()));
+        clock.sleep(KILL_CONFIRM_WINDOW).await;
+        assert!(state.press_kill(clock.now()));
+        assert!(state.kill_armed_at.is_none());
+
+        // Armed again, then left alone past the window
+        assert!(!state.press_kill(clock.now()));
+        clock.advance(KILL_CONFIRM_WINDOW + Duration::from_millis(1));
+        state.expire_kill(clock.now());
+        assert!(state.kill_armed_at.is_none());
+        assert!(!state.press_kill(clock.now()));
+    }
+}
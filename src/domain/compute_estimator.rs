@@ -0,0 +1,342 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_client::rpc_config::RpcSimulateTransactionConfig;
+use anchor_client::solana_sdk::{
+    compute_budget::{self, ComputeBudgetInstruction},
+    hash::Hash,
+    instruction::Instruction,
+    signature::Keypair,
+    signer::Signer,
+    system_program,
+    transaction::Transaction,
+};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::common::sync::LockExt;
+
+/// Highest limit a transaction may request, also used while simulating
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Room for the compute budget and tip instructions, which are not part of the simulated shape
+const BUDGET_HEADROOM_UNITS: u64 = 1_000;
+
+/// Signatures remembered for attributing late failures back to a shape
+const MAX_PENDING_SIGNATURES: usize = 1_024;
+
+lazy_static::lazy_static! {
+    /// Process-wide estimator shared by every execution strategy
+    pub static ref COMPUTE_ESTIMATOR: ComputeEstimator = ComputeEstimator::new(get_cu_margin_pct());
+}
+
+/// Safety margin added on top of simulated units, in percent
+fn get_cu_margin_pct() -> u64 {
+    std::env::var("CU_MARGIN_PCT")
+        .ok()
+        .and_then(|v| u64::from_str(&v).ok())
+        .unwrap_or(20)
+}
+
+/// What makes two transactions cost the same compute: the swap program, the
+/// swap instruction (anchor discriminator, i.e. buy vs sell) and whether an
+/// ATA gets created or an account closed alongside it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct InstructionShape {
+    pub program: String,
+    pub discriminator: String,
+    pub creates_ata: bool,
+    pub closes_account: bool,
+}
+
+impl InstructionShape {
+    /// Derive the shape from the instructions a builder produced, before any
+    /// compute budget or tip instructions are added. None when there is no swap.
+    pub fn from_instructions(instructions: &[Instruction]) -> Option<Self> {
+        let swap = instructions.iter().find(|ix| {
+            ix.program_id != compute_budget::id()
+                && ix.program_id != system_program::id()
+                && ix.program_id != spl_token::id()
+                && ix.program_id != spl_associated_token_account::id()
+        })?;
+
+        let discriminator = swap
+            .data
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let creates_ata = instructions
+            .iter()
+            .any(|ix| ix.program_id == spl_associated_token_account::id());
+        // CloseAccount is instruction 9 of the token program
+        let closes_account = instructions
+            .iter()
+            .any(|ix| ix.program_id == spl_token::id() && ix.data.first() == Some(&9));
+
+        Some(Self {
+            program: swap.program_id.to_string(),
+            discriminator,
+            creates_ata,
+            closes_account,
+        })
+    }
+}
+
+impl fmt::Display for InstructionShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.program, self.discriminator)?;
+        if self.creates_ata {
+            write!(f, "+create_ata")?;
+        }
+        if self.closes_account {
+            write!(f, "+close")?;
+        }
+        Ok(())
+    }
+}
+
+/// Cached compute estimate for one shape
+#[derive(Debug, Clone, Serialize)]
+pub struct ShapeEstimate {
+    pub units_consumed: u64,
+    pub unit_limit: u32,
+    /// Transactions that reused this limit since the last simulation
+    pub uses: u64,
+    pub compute_failures: u32,
+    /// Set when a transaction of this shape ran out of compute
+    pub stale: bool,
+}
+
+#[derive(Default)]
+struct PendingSignatures {
+    shapes: HashMap<String, InstructionShape>,
+    order: VecDeque<String>,
+}
+
+/// Simulates the first transaction of each shape and reuses the measured
+/// limit for later ones, re-simulating once a shape starts failing on compute
+pub struct ComputeEstimator {
+    margin_pct: u64,
+    estimates: Mutex<HashMap<InstructionShape, ShapeEstimate>>,
+    pending: Mutex<PendingSignatures>,
+}
+
+impl ComputeEstimator {
+    pub fn new(margin_pct: u64) -> Self {
+        Self {
+            margin_pct,
+            estimates: Mutex::new(HashMap::new()),
+            pending: Mutex::new(PendingSignatures::default()),
+        }
+    }
+
+    /// Limit to request for `units_consumed` simulated units
+    pub fn limit_for_units(&self, units_consumed: u64) -> u32 {
+        let limit = units_consumed * (100 + self.margin_pct) / 100 + BUDGET_HEADROOM_UNITS;
+        limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+    }
+
+    /// Cached limit for a shape, None if it was never simulated or went stale
+    pub fn cached_limit(&self, shape: &InstructionShape) -> Option<u32> {
+        let mut estimates = self.estimates.lock_or_recover();
+        let estimate = estimates.get_mut(shape).filter(|e| !e.stale)?;
+        estimate.uses += 1;
+        Some(estimate.unit_limit)
+    }
+
+    /// Store a fresh simulation result and return the limit to use
+    pub fn record_simulation(&self, shape: InstructionShape, units_consumed: u64) -> u32 {
+        let unit_limit = self.limit_for_units(units_consumed);
+        let mut estimates = self.estimates.lock_or_recover();
+        let compute_failures = estimates.get(&shape).map(|e| e.compute_failures).unwrap_or(0);
+        estimates.insert(
+            shape,
+            ShapeEstimate {
+                units_consumed,
+                unit_limit,
+                uses: 1,
+                compute_failures,
+                stale: false,
+            },
+        );
+        unit_limit
+    }
+
+    /// Simulate `instructions` at the maximum limit and cache the consumed units
+    pub async fn simulate_and_cache(
+        &self,
+        rpc_client: &RpcClient,
+        keypair: &Keypair,
+        instructions: &[Instruction],
+        recent_blockhash: Hash,
+        shape: InstructionShape,
+    ) -> Result<u32> {
+        let mut simulated = Vec::with_capacity(instructions.len() + 1);
+        simulated.push(ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT));
+        simulated.extend_from_slice(instructions);
+        let txn = Transaction::new_signed_with_payer(
+            &simulated,
+            Some(&keypair.pubkey()),
+            &vec![keypair],
+            recent_blockhash,
+        );
+
+        let sim_config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = rpc_client
+            .simulate_transaction_with_config(&txn, sim_config)
+            .await
+            .map_err(|e| anyhow!("Simulation failed for {}: {}", shape, e))?;
+        if let Some(err) = result.value.err {
+            return Err(anyhow!("Simulation of {} returned error: {}", shape, err));
+        }
+        let units_consumed = result
+            .value
+            .units_consumed
+            .ok_or_else(|| anyhow!("Simulation of {} reported no units consumed", shape))?;
+
+        Ok(self.record_simulation(shape, units_consumed))
+    }
+
+    /// Remember which shape a sent transaction had
+    pub fn note_sent(&self, signature: &str, shape: &InstructionShape) {
+        let mut pending = self.pending.lock_or_recover();
+        if pending.shapes.insert(signature.to_string(), shape.clone()).is_none() {
+            pending.order.push_back(signature.to_string());
+        }
+        while pending.order.len() > MAX_PENDING_SIGNATURES {
+            if let Some(oldest) = pending.order.pop_front() {
+                pending.shapes.remove(&oldest);
+            }
+        }
+    }
+
+    /// Report that a sent transaction failed; compute exhaustion marks its
+    /// shape stale so the next transaction of that shape is simulated again.
+    /// Returns true when the shape was invalidated.
+    pub fn report_failure(&self, signature: &str, error: &str) -> bool {
+        if !is_compute_exhaustion(error) {
+            return false;
+        }
+        let shape = {
+            let mut pending = self.pending.lock_or_recover();
+            pending.order.retain(|s| s != signature);
+            pending.shapes.remove(signature)
+        };
+        match shape {
+            Some(shape) => self.invalidate(&shape),
+            None => false,
+        }
+    }
+
+    /// Force the next transaction of `shape` to be simulated
+    pub fn invalidate(&self, shape: &InstructionShape) -> bool {
+        let mut estimates = self.estimates.lock_or_recover();
+        match estimates.get_mut(shape) {
+            Some(estimate) => {
+                estimate.compute_failures += 1;
+                estimate.stale = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Current cache contents keyed by the shape's display form, for inspection
+    pub fn snapshot(&self) -> Vec<(String, ShapeEstimate)> {
+        let estimates = self.estimates.lock_or_recover();
+        let mut entries = estimates
+            .iter()
+            .map(|(shape, estimate)| (shape.to_string(), estimate.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+}
+
+/// Whether a transaction error means it ran out of compute units
+pub fn is_compute_exhaustion(error: &str) -> bool {
+    error.contains("ComputationalBudgetExceeded")
+        || error.contains("exceeded CUs meter")
+        || error.contains("compute units exceeded")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_sdk::pubkey::Pubkey;
+
+    fn swap(program: Pubkey, discriminator: u8) -> Instruction {
+        Instruction::new_with_bytes(program, &[discriminator, 0, 0, 0, 0, 0, 0, 0, 42], vec![])
+    }
+
+    fn create_ata() -> Instruction {
+        Instruction::new_with_bytes(spl_associated_token_account::id(), &[1], vec![])
+    }
+
+    fn close_account() -> Instruction {
+        Instruction::new_with_bytes(spl_token::id(), &[9], vec![])
+    }
+
+    #[test]
+    fn shape_keys_on_program_direction_and_account_setup() {
+        let program = Pubkey::new_unique();
+        let buy = InstructionShape::from_instructions(&[create_ata(), swap(program, 1)]).unwrap();
+        let sell = InstructionShape::from_instructions(&[swap(program, 2), close_account()]).unwrap();
+        let plain_buy = InstructionShape::from_instructions(&[swap(program, 1)]).unwrap();
+        let other_venue = InstructionShape::from_instructions(&[create_ata(), swap(Pubkey::new_unique(), 1)]).unwrap();
+
+        assert_eq!(buy.program, program.to_string());
+        assert_eq!(buy.discriminator, "0100000000000000");
+        assert!(buy.creates_ata && !buy.closes_account);
+        assert!(sell.closes_account && !sell.creates_ata);
+        assert_ne!(buy, sell);
+        assert_ne!(buy, plain_buy);
+        assert_ne!(buy, other_venue);
+        // Only the first 8 bytes key the shape, the amounts after them don't
+        let mut other_amount = swap(program, 1);
+        other_amount.data[8] = 7;
+        assert_eq!(InstructionShape::from_instructions(&[create_ata(), other_amount]), Some(buy));
+        assert_eq!(InstructionShape::from_instructions(&[create_ata(), close_account()]), None);
+    }
+
+    #[test]
+    fn cached_limit_carries_the_margin() {
+        let estimator = ComputeEstimator::new(20);
+        let shape = InstructionShape::from_instructions(&[swap(Pubkey::new_unique(), 1)]).unwrap();
+        assert_eq!(estimator.cached_limit(&shape), None);
+
+        assert_eq!(estimator.record_simulation(shape.clone(), 100_000), 121_000);
+        assert_eq!(estimator.cached_limit(&shape), Some(121_000));
+        assert_eq!(estimator.limit_for_units(2_000_000), MAX_COMPUTE_UNIT_LIMIT);
+    }
+
+    #[test]
+    fn compute_failure_forces_a_new_simulation() {
+        let estimator = ComputeEstimator::new(20);
+        let shape = InstructionShape::from_instructions(&[swap(Pubkey::new_unique(), 1)]).unwrap();
+        estimator.record_simulation(shape.clone(), 100_000);
+        estimator.note_sent("sig", &shape);
+
+        // Other failures keep the cached limit
+        assert!(!estimator.report_failure("sig", "custom program error: 0x1771"));
+        assert!(estimator.cached_limit(&shape).is_some());
+
+        assert!(estimator.report_failure("sig", "Program failed: exceeded CUs meter at BPF instruction"));
+        assert_eq!(estimator.cached_limit(&shape), None);
+        assert_eq!(estimator.snapshot()[0].1.compute_failures, 1);
+        // The signature was consumed by the first report
+        assert!(!estimator.report_failure("sig", "ComputationalBudgetExceeded"));
+
+        assert_eq!(estimator.record_simulation(shape.clone(), 150_000), 181_000);
+        assert_eq!(estimator.cached_limit(&shape), Some(181_000));
+        assert_eq!(estimator.snapshot()[0].1.compute_failures, 1);
+    }
+}
@@ -0,0 +1,113 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use anchor_client::solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::engine::swap::SwapDirection;
+
+/// A pool as an adapter read it, reserves oriented token/quote. The quote
+/// is SOL unless the venue reads other pairs through `read_quoted_pool`.
+#[derive(Clone)]
+pub struct PoolInfo {
+    /// Registry name of the venue that read it
+    pub dex: &'static str,
+    pub pool_id: Pubkey,
+    pub mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub token_reserve: u64,
+    pub quote_reserve: u64,
+    /// The venue's own decoded pool; only the adapter that read it looks inside
+    venue: Arc<dyn Any + Send + Sync>,
+}
+
+impl PoolInfo {
+    /// A pool pairing `mint` with SOL
+    pub fn new<T: Any + Send + Sync>(
+        dex: &'static str,
+        pool_id: Pubkey,
+        mint: Pubkey,
+        token_reserve: u64,
+        sol_reserve: u64,
+        venue: T,
+    ) -> Self {
+        Self {
+            dex,
+            pool_id,
+            mint,
+            quote_mint: spl_token::native_mint::id(),
+            token_reserve,
+            quote_reserve: sol_reserve,
+            venue: Arc::new(venue),
+        }
+    }
+
+    /// The same pool, its quote side holding `quote_mint` instead of SOL
+    pub fn quoted_in(self, quote_mint: Pubkey) -> Self {
+        Self { quote_mint, ..self }
+    }
+
+    /// The decoded pool behind this one, an error when another venue read it
+    pub fn venue<T: Any>(&self) -> Result<&T> {
+        self.venue
+            .downcast_ref::<T>()
+            .ok_or_else(|| anyhow!("pool {} was read by {}, not this venue", self.pool_id, self.dex))
+    }
+}
+
+impl fmt::Debug for PoolInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolInfo")
+            .field("dex", &self.dex)
+            .field("pool_id", &self.pool_id)
+            .field("mint", &self.mint)
+            .field("quote_mint", &self.quote_mint)
+            .field("token_reserve", &self.token_reserve)
+            .field("quote_reserve", &self.quote_reserve)
+            .finish()
+    }
+}
+
+/// One venue the arbitrage engine can price and trade on without knowing which
+///
+/// Amounts are raw units: lamports on the SOL side, the mint's smallest unit
+/// on the token side and on a non-SOL quote side. A buy spends the quote, a
+/// sell spends tokens.
+#[async_trait]
+pub trait DexAdapter: Send + Sync {
+    /// Registry name of the venue, as used in the pool cache and price maps
+    fn name(&self) -> &'static str;
+
+    /// The venue's deepest pool pairing `mint` with SOL
+    async fn get_pool(&self, mint: &Pubkey) -> Result<PoolInfo>;
+
+    /// A pool whose address is already known, e.g. from the pool cache
+    async fn read_pool(&self, pool_id: &Pubkey, mint: &Pubkey) -> Result<PoolInfo>;
+
+    /// A known pool pairing `mint` with `quote_mint`; venues that only
+    /// trade against SOL refuse any other quote
+    async fn read_quoted_pool(&self, pool_id: &Pubkey, mint: &Pubkey, quote_mint: &Pubkey) -> Result<PoolInfo> {
+        if *quote_mint != spl_token::native_mint::id() {
+            return Err(anyhow!("{} only trades pairs with SOL, not {}", self.name(), quote_mint));
+        }
+        self.read_pool(pool_id, mint).await
+    }
+
+    /// Spot price of `mint` in lamports per raw unit on its deepest pool
+    async fn get_token_price(&self, mint: &Pubkey) -> Result<f64>;
+
+    /// What `amount_in` puts out through `pool` after the venue's fee
+    async fn quote(&self, pool: &PoolInfo, amount_in: u64, direction: SwapDirection) -> Result<u64>;
+
+    /// Instructions spending at most `amount_in` through `pool` for at least
+    /// `min_amount_out`, including any account setup the swap needs
+    async fn build_swap_instructions(
+        &self,
+        pool: &PoolInfo,
+        amount_in: u64,
+        min_amount_out: u64,
+        direction: SwapDirection,
+    ) -> Result<Vec<Instruction>>;
+}
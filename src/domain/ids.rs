@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::hmac::to_hex;
+
+/// Slots an allocator remembers counters for behind the newest one it saw
+const COUNTER_SLOT_HORIZON: u64 = 1_000;
+
+/// Names one detected opportunity across records, journal, logs and alerts
+///
+/// Derived from the token, the pool pair, the detection slot and how many
+/// times that same combination was already seen in the slot, so the same
+/// inputs give the same id in any process.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OpportunityId(String);
+
+impl OpportunityId {
+    pub const PREFIX: &'static str = "opp_";
+
+    pub fn derive(token: &str, buy_pool: &str, sell_pool: &str, slot: u64, counter: u32) -> Self {
+        let content = format!("{}|{}|{}|{}|{}", token, buy_pool, sell_pool, slot, counter);
+        let hash = anchor_client::solana_sdk::hash::hash(content.as_bytes());
+        Self(format!("{}{}", Self::PREFIX, &to_hex(&hash.to_bytes())[..16]))
+    }
+
+    /// Accepts an opportunity id or a trade id, which names its opportunity
+    pub fn parse(value: &str) -> Option<Self> {
+        let id = value.split_once(TradeId::SEPARATOR).map_or(value, |(opportunity, _)| opportunity);
+        let hex = id.strip_prefix(Self::PREFIX)?;
+        (hex.len() == 16 && hex.bytes().all(|b| b.is_ascii_hexdigit())).then(|| Self(id.to_string()))
+    }
+
+    /// The id of execution attempt `attempt` (from 1) on this opportunity
+    pub fn trade(&self, attempt: u32) -> TradeId {
+        TradeId(format!("{}{}{}", self.0, TradeId::SEPARATOR, attempt))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for OpportunityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// One execution attempt on an opportunity, `<opportunity id>-t<attempt>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TradeId(String);
+
+impl TradeId {
+    pub const SEPARATOR: &'static str = "-t";
+
+    pub fn opportunity(&self) -> OpportunityId {
+        OpportunityId::parse(&self.0).expect("trade ids are built from opportunity ids")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TradeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Hands out opportunity ids, counting repeats of the same token, pool pair
+/// and slot so each detection gets its own id
+#[derive(Debug, Default)]
+pub struct OpportunityIds {
+    counters: HashMap<(String, String, String, u64), u32>,
+    newest_slot: u64,
+}
+
+impl OpportunityIds {
+    pub fn next(&mut self, token: &str, buy_pool: &str, sell_pool: &str, slot: u64) -> OpportunityId {
+        if slot > self.newest_slot {
+            self.newest_slot = slot;
+            let horizon = slot.saturating_sub(COUNTER_SLOT_HORIZON);
+            self.counters.retain(|(_, _, _, seen), _| *seen >= horizon);
+        }
+        let counter = self
+            .counters
+            .entry((token.to_string(), buy_pool.to_string(), sell_pool.to_string(), slot))
+            .or_insert(0);
+        let id = OpportunityId::derive(token, buy_pool, sell_pool, slot, *counter);
+        *counter += 1;
+        id
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref OPPORTUNITY_IDS: Mutex<OpportunityIds> = Mutex::new(OpportunityIds::default());
+}
+
+/// Next id from the process-wide allocator
+pub fn next_opportunity_id(token: &str, buy_pool: &str, sell_pool: &str, slot: u64) -> OpportunityId {
+    match OPPORTUNITY_IDS.lock() {
+        Ok(mut ids) => ids.next(token, buy_pool, sell_pool, slot),
+        Err(poisoned) => poisoned.into_inner().next(token, buy_pool, sell_pool, slot),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_the_same_in_every_process_for_the_same_inputs() {
+        // Pinned, so a change to the derivation shows up as a broken trace history
+        assert_eq!(OpportunityId::derive("mint", "buy", "sell", 100, 0).as_str(), "opp_416a4354bd7b5cf3");
+        assert_eq!(OpportunityId::derive("mint", "buy", "sell", 100, 1).as_str(), "opp_c06ff8cdf5b2ffd7");
+
+        // Two allocators stand in for a process before and after a restart
+        let detections = [("mint", "buy", "sell", 100), ("mint", "buy", "sell", 100), ("other", "buy", "sell", 100), ("mint", "buy", "sell", 101)];
+        let run = || {
+            let mut ids = OpportunityIds::default();
+            detections.iter().map(|(token, buy, sell, slot)| ids.next(token, buy, sell, *slot)).collect::<Vec<_>>()
+        };
+        let (before, after) = (run(), run());
+        assert_eq!(before, after);
+        assert_eq!(before[0].as_str(), "opp_416a4354bd7b5cf3");
+        assert_eq!(before[1].as_str(), "opp_c06ff8cdf5b2ffd7");
+        assert_ne!(before[2], before[0]);
+        assert_ne!(before[3], before[0]);
+    }
+
+    #[test]
+    fn counters_older_than_the_horizon_are_forgotten() {
+        let mut ids = OpportunityIds::default();
+        let first = ids.next("mint", "buy", "sell", 100);
+        ids.next("mint", "buy", "sell", 100 + COUNTER_SLOT_HORIZON + 1);
+        // A late detection for the old slot starts its count over
+        assert_eq!(ids.next("mint", "buy", "sell", 100), first);
+    }
+
+    #[test]
+    fn trade_ids_name_their_opportunity() {
+        let opportunity = OpportunityId::derive("mint", "buy", "sell", 100, 0);
+        let trade = opportunity.trade(2);
+        assert_eq!(trade.as_str(), "opp_416a4354bd7b5cf3-t2");
+        assert_eq!(trade.opportunity(), opportunity);
+        assert_eq!(OpportunityId::parse(trade.as_str()), Some(opportunity.clone()));
+        assert_eq!(OpportunityId::parse(opportunity.as_str()), Some(opportunity));
+        assert_eq!(OpportunityId::parse("opp_416a4354bd7b5cf"), None);
+        assert_eq!(OpportunityId::parse("opp_416a4354bd7b5cfz"), None);
+        assert_eq!(OpportunityId::parse("416a4354bd7b5cf3"), None);
+    }
+}
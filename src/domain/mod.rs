@@ -1,2 +1,10 @@
 pub mod token;
 pub mod tx;
+pub mod tx_sender;
+pub mod compute_estimator;
+pub mod priority_fee;
+pub mod tx_pruning;
+pub mod price;
+pub mod ids;
+pub mod dex_adapter;
+pub mod nonce;
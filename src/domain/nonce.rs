@@ -0,0 +1,93 @@
+use anchor_client::solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+    system_instruction::{self, SystemInstruction},
+    system_program,
+};
+use anyhow::{anyhow, Result};
+
+/// An initialized nonce account as it is on-chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceAccount {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    /// The stored hash a transaction advancing this nonce signs against
+    pub blockhash: Hash,
+}
+
+impl NonceAccount {
+    /// Decode a nonce account's data; errors for anything uninitialized
+    pub fn parse(address: Pubkey, data: &[u8]) -> Result<Self> {
+        let versions: Versions = bincode::deserialize(data).map_err(|e| anyhow!("{} is not a nonce account: {}", address, e))?;
+        match versions.state() {
+            State::Initialized(data) => Ok(Self {
+                address,
+                authority: data.authority,
+                blockhash: data.blockhash(),
+            }),
+            State::Uninitialized => Err(anyhow!("Nonce account {} is not initialized", address)),
+        }
+    }
+
+    /// The instruction that has to lead a transaction signed against this nonce
+    pub fn advance_instruction(&self) -> Instruction {
+        system_instruction::advance_nonce_account(&self.address, &self.authority)
+    }
+
+    /// Put this nonce's advance first in `instructions`, replacing any an
+    /// earlier attempt left in them
+    pub fn lead(&self, instructions: &mut Vec<Instruction>) {
+        instructions.retain(|ix| !is_advance_nonce(ix));
+        instructions.insert(0, self.advance_instruction());
+    }
+}
+
+pub fn is_advance_nonce(instruction: &Instruction) -> bool {
+    instruction.program_id == system_program::id()
+        && matches!(bincode::deserialize::<SystemInstruction>(&instruction.data), Ok(SystemInstruction::AdvanceNonceAccount))
+}
+
+/// Move an advance-nonce instruction back to the front after tips and
+/// compute budget instructions were inserted ahead of it; the runtime only
+/// honours the nonce when it is the first instruction
+pub fn keep_advance_first(instructions: &mut Vec<Instruction>) {
+    if let Some(index) = instructions.iter().position(is_advance_nonce) {
+        if index > 0 {
+            let advance = instructions.remove(index);
+            instructions.insert(0, advance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+
+    #[test]
+    fn the_advance_nonce_instruction_comes_first() {
+        let nonce = NonceAccount {
+            address: Pubkey::new_from_array([7; 32]),
+            authority: Pubkey::new_from_array([8; 32]),
+            blockhash: Hash::new_unique(),
+        };
+        let swap = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+        // An earlier attempt's advance, on an account no longer configured
+        let stale = NonceAccount { address: Pubkey::new_unique(), ..nonce }.advance_instruction();
+        let mut instructions = vec![swap.clone(), stale];
+
+        nonce.lead(&mut instructions);
+        assert_eq!(instructions, vec![nonce.advance_instruction(), swap.clone()]);
+
+        // The senders put the compute budget in front afterwards
+        instructions.insert(0, ComputeBudgetInstruction::set_compute_unit_limit(200_000));
+        instructions.insert(0, ComputeBudgetInstruction::set_compute_unit_price(1_000));
+        keep_advance_first(&mut instructions);
+        assert_eq!(instructions[0], nonce.advance_instruction());
+        assert!(is_advance_nonce(&instructions[0]));
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(instructions[3], swap);
+    }
+}
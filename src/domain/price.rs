@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// A finite, positive price in quote per base. Every price that reaches a
+/// price map goes through `Price::new`, so NaN and inf can't get there.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+pub struct Price(f64);
+
+impl Price {
+    pub fn new(value: f64) -> Option<Self> {
+        (value.is_finite() && value > 0.0).then_some(Self(value))
+    }
+
+    /// quote / base from raw reserves, scaled by each side's decimals
+    pub fn from_reserves(
+        pool: &str,
+        base_reserve: u64,
+        quote_reserve: u64,
+        base_decimals: u8,
+        quote_decimals: u8,
+    ) -> Result<Self, PoolDataUnavailable> {
+        check_reserves(pool, base_reserve, quote_reserve)?;
+        let base = base_reserve as f64 / 10f64.powi(base_decimals as i32);
+        let quote = quote_reserve as f64 / 10f64.powi(quote_decimals as i32);
+        Self::new(quote / base).ok_or_else(|| PoolDataUnavailable::new(pool, ReserveProblem::NotFinite))
+    }
+
+    /// quote / base on raw units, for math that works in lamports per base unit
+    pub fn from_raw_reserves(pool: &str, base_reserve: u64, quote_reserve: u64) -> Result<Self, PoolDataUnavailable> {
+        Self::from_reserves(pool, base_reserve, quote_reserve, 0, 0)
+    }
+
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.9}", self.0)
+    }
+}
+
+/// Best bid and ask of an order book. Buys fill at the ask and sells at the
+/// bid, the mid is only a reference.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BookTouch {
+    pub bid: Price,
+    pub ask: Price,
+}
+
+impl BookTouch {
+    /// None for a crossed book, which only shows up mid-update
+    pub fn new(bid: Price, ask: Price) -> Option<Self> {
+        (bid <= ask).then_some(Self { bid, ask })
+    }
+
+    pub fn mid(&self) -> Price {
+        Price((self.bid.0 + self.ask.0) / 2.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReserveProblem {
+    /// The reserve couldn't be read, e.g. the RPC call failed
+    Missing,
+    Zero,
+    /// The reserves produced a NaN or infinite price
+    NotFinite,
+}
+
+/// The pool's reserves can't be turned into a price right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolDataUnavailable {
+    pub pool: String,
+    pub problem: ReserveProblem,
+}
+
+impl PoolDataUnavailable {
+    pub fn new(pool: impl ToString, problem: ReserveProblem) -> Self {
+        Self { pool: pool.to_string(), problem }
+    }
+}
+
+impl fmt::Display for PoolDataUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = match self.problem {
+            ReserveProblem::Missing => "reserves could not be read",
+            ReserveProblem::Zero => "a reserve is zero",
+            ReserveProblem::NotFinite => "reserves give a non-finite price",
+        };
+        write!(f, "Pool data unavailable for {}: {}", self.pool, problem)
+    }
+}
+
+impl std::error::Error for PoolDataUnavailable {}
+
+/// Reject zero reserves before they are divided by
+pub fn check_reserves(pool: &str, base_reserve: u64, quote_reserve: u64) -> Result<(), PoolDataUnavailable> {
+    if base_reserve == 0 || quote_reserve == 0 {
+        return Err(PoolDataUnavailable::new(pool, ReserveProblem::Zero));
+    }
+    Ok(())
+}
+
+/// What to do with a new price observation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceVerdict {
+    /// Within the band of the last known price, or the first price seen
+    Accept(Price),
+    /// Outside the band, held until a second observation agrees with it
+    Quarantined { price: Price, last_known: Price },
+    /// A second observation agreed with the quarantined price
+    Confirmed(Price),
+}
+
+impl PriceVerdict {
+    /// The price to store, None while quarantined
+    pub fn accepted(&self) -> Option<Price> {
+        match self {
+            Self::Accept(price) | Self::Confirmed(price) => Some(*price),
+            Self::Quarantined { .. } => None,
+        }
+    }
+}
+
+/// `PRICE_SANITY_MAX_JUMP`, `PRICE_CONFIRM_TOLERANCE_PCT` and `PRICE_QUARANTINE_SECS`
+#[derive(Debug, Clone, Copy)]
+pub struct PriceSanity {
+    /// Largest ratio between a new price and the last known one accepted outright
+    pub max_jump: f64,
+    /// How close a second observation must be to the quarantined one to confirm it
+    pub confirm_tolerance: f64,
+    /// A quarantined price older than this no longer counts as a first observation
+    pub quarantine_ttl: Duration,
+}
+
+impl PriceSanity {
+    pub fn from_env() -> Self {
+        let parse = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite() && *v > 0.0)
+                .unwrap_or(default)
+        };
+        Self {
+            max_jump: parse("PRICE_SANITY_MAX_JUMP", 10.0).max(1.0),
+            confirm_tolerance: parse("PRICE_CONFIRM_TOLERANCE_PCT", 5.0) / 100.0,
+            quarantine_ttl: Duration::from_secs_f64(parse("PRICE_QUARANTINE_SECS", 60.0)),
+        }
+    }
+
+    fn within_band(&self, price: Price, reference: Price) -> bool {
+        let ratio = price.value() / reference.value();
+        ratio <= self.max_jump && ratio >= 1.0 / self.max_jump
+    }
+
+    fn agrees(&self, price: Price, reference: Price) -> bool {
+        (price.value() / reference.value() - 1.0).abs() <= self.confirm_tolerance
+    }
+}
+
+/// Holds back prices that jump outside the sanity band until confirmed
+///
+/// Keyed by whatever identifies a price series (pool id, or mint and DEX).
+pub struct PriceGate {
+    sanity: PriceSanity,
+    quarantined: HashMap<String, (Price, Instant)>,
+}
+
+impl PriceGate {
+    pub fn new(sanity: PriceSanity) -> Self {
+        Self { sanity, quarantined: HashMap::new() }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(PriceSanity::from_env())
+    }
+
+    /// Judge `price` for `key` against the last price stored for it
+    pub fn observe(&mut self, key: &str, price: Price, last_known: Option<Price>, now: Instant) -> PriceVerdict {
+        let Some(last_known) = last_known else {
+            self.quarantined.remove(key);
+            return PriceVerdict::Accept(price);
+        };
+        if self.sanity.within_band(price, last_known) {
+            // Back in line with the known price, any pending outlier was noise
+            self.quarantined.remove(key);
+            return PriceVerdict::Accept(price);
+        }
+
+        if let Some((pending, at)) = self.quarantined.get(key) {
+            if now.saturating_duration_since(*at) <= self.sanity.quarantine_ttl && self.sanity.agrees(price, *pending) {
+                self.quarantined.remove(key);
+                return PriceVerdict::Confirmed(price);
+            }
+        }
+        self.quarantined.insert(key.to_string(), (price, now));
+        PriceVerdict::Quarantined { price, last_known }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(value: f64) -> Price {
+        Price::new(value).unwrap()
+    }
+
+    fn gate() -> PriceGate {
+        PriceGate::new(PriceSanity {
+            max_jump: 10.0,
+            confirm_tolerance: 0.05,
+            quarantine_ttl: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn only_finite_positive_prices_exist() {
+        assert!(Price::new(f64::NAN).is_none());
+        assert!(Price::new(f64::INFINITY).is_none());
+        assert!(Price::new(f64::NEG_INFINITY).is_none());
+        assert!(Price::new(0.0).is_none());
+        assert!(Price::new(-1.0).is_none());
+        assert_eq!(Price::new(1e-12).map(|p| p.value()), Some(1e-12));
+    }
+
+    #[test]
+    fn zero_reserves_are_unavailable_not_a_price() {
+        let zero = |base, quote| Price::from_reserves("pool", base, quote, 6, 9).unwrap_err();
+        assert_eq!(zero(0, 1_000), PoolDataUnavailable::new("pool", ReserveProblem::Zero));
+        assert_eq!(zero(1_000, 0), PoolDataUnavailable::new("pool", ReserveProblem::Zero));
+        assert_eq!(zero(0, 0).problem, ReserveProblem::Zero);
+
+        // 1_000 tokens of 6 decimals against 2 SOL
+        let priced = Price::from_reserves("pool", 1_000_000_000, 2_000_000_000, 6, 9).unwrap();
+        assert!((priced.value() - 0.002).abs() < 1e-15);
+        assert_eq!(Price::from_raw_reserves("pool", 4, 2).unwrap().value(), 0.5);
+    }
+
+    #[test]
+    fn one_sided_raw_reserves_name_the_pool() {
+        // What the stream pricer sees when a pool is drained on one side
+        let err = Price::from_raw_reserves("drained", 0, 40_000_000_000).unwrap_err();
+        assert_eq!(err, PoolDataUnavailable::new("drained", ReserveProblem::Zero));
+        assert!(err.to_string().contains("drained"));
+        assert_eq!(Price::from_raw_reserves("drained", 800_000_000_000_000, 0).unwrap_err().problem, ReserveProblem::Zero);
+    }
+
+    #[test]
+    fn first_price_and_prices_in_band_are_accepted() {
+        let mut gate = gate();
+        let now = Instant::now();
+        assert_eq!(gate.observe("pool", price(1.0), None, now), PriceVerdict::Accept(price(1.0)));
+        assert_eq!(gate.observe("pool", price(9.9), Some(price(1.0)), now), PriceVerdict::Accept(price(9.9)));
+        assert_eq!(gate.observe("pool", price(0.11), Some(price(1.0)), now), PriceVerdict::Accept(price(0.11)));
+    }
+
+    #[test]
+    fn a_hundredfold_jump_is_quarantined() {
+        let mut gate = gate();
+        let now = Instant::now();
+        let verdict = gate.observe("pool", price(100.0), Some(price(1.0)), now);
+        assert_eq!(verdict, PriceVerdict::Quarantined { price: price(100.0), last_known: price(1.0) });
+        assert_eq!(verdict.accepted(), None);
+
+        let crash = gate.observe("other", price(0.01), Some(price(1.0)), now);
+        assert!(matches!(crash, PriceVerdict::Quarantined { .. }));
+    }
+
+    #[test]
+    fn a_second_consistent_observation_confirms() {
+        let mut gate = gate();
+        let now = Instant::now();
+        gate.observe("pool", price(100.0), Some(price(1.0)), now);
+        let verdict = gate.observe("pool", price(102.0), Some(price(1.0)), now + Duration::from_secs(5));
+        assert_eq!(verdict, PriceVerdict::Confirmed(price(102.0)));
+        assert_eq!(verdict.accepted(), Some(price(102.0)));
+
+        // Confirmation is spent, the next outlier starts over
+        let verdict = gate.observe("pool", price(103.0), Some(price(1.0)), now + Duration::from_secs(6));
+        assert!(matches!(verdict, PriceVerdict::Quarantined { .. }));
+    }
+
+    #[test]
+    fn disagreeing_or_late_observations_do_not_confirm() {
+        let mut gate = gate();
+        let now = Instant::now();
+        gate.observe("pool", price(100.0), Some(price(1.0)), now);
+        // A different outlier replaces the pending one rather than confirming it
+        let verdict = gate.observe("pool", price(200.0), Some(price(1.0)), now + Duration::from_secs(1));
+        assert!(matches!(verdict, PriceVerdict::Quarantined { .. }));
+        let verdict = gate.observe("pool", price(201.0), Some(price(1.0)), now + Duration::from_secs(2));
+        assert_eq!(verdict, PriceVerdict::Confirmed(price(201.0)));
+
+        gate.observe("pool", price(100.0), Some(price(1.0)), now);
+        let late = gate.observe("pool", price(100.0), Some(price(1.0)), now + Duration::from_secs(61));
+        assert!(matches!(late, PriceVerdict::Quarantined { .. }));
+
+        // Another pool's pending outlier doesn't count
+        let verdict = gate.observe("other", price(100.0), Some(price(1.0)), now + Duration::from_secs(62));
+        assert!(matches!(verdict, PriceVerdict::Quarantined { .. }));
+    }
+
+    #[test]
+    fn a_price_back_in_band_clears_the_quarantine() {
+        let mut gate = gate();
+        let now = Instant::now();
+        gate.observe("pool", price(100.0), Some(price(1.0)), now);
+        assert_eq!(gate.observe("pool", price(1.1), Some(price(1.0)), now), PriceVerdict::Accept(price(1.1)));
+        let verdict = gate.observe("pool", price(100.0), Some(price(1.0)), now);
+        assert!(matches!(verdict, PriceVerdict::Quarantined { .. }));
+    }
+}
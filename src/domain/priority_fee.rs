@@ -0,0 +1,258 @@
+//! Compute unit price from what recent transactions paid for the same accounts
+//!
+//! `getRecentPrioritizationFees` reports, per recent slot, the lowest fee
+//! that landed a transaction writing the given accounts. A percentile of
+//! those bids enough to compete for the hot accounts without paying the
+//! outliers. Estimates are cached per account set for a few seconds so a
+//! burst of sends costs one query.
+//!
+//! Which price a swap bids is its `PriorityFeeStrategy`: that percentile,
+//! the fixed `UNIT_PRICE`, or a share of the profit the swap is expected to
+//! make.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::{compute_budget, instruction::Instruction, pubkey::Pubkey};
+use anyhow::Result;
+
+use crate::common::sync::LockExt;
+
+/// The RPC method accepts at most this many accounts
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+/// Account sets remembered at once; expired ones are dropped first
+const MAX_CACHED_SETS: usize = 256;
+
+lazy_static::lazy_static! {
+    /// Process-wide estimator shared by every sender
+    pub static ref PRIORITY_FEES: PriorityFeeEstimator = PriorityFeeEstimator::new(PriorityFeeConfig::from_env());
+}
+
+/// `PRIORITY_FEE_PERCENTILE`, `PRIORITY_FEE_CACHE_SECS` and
+/// `PRIORITY_FEE_MICRO_LAMPORTS`
+#[derive(Debug, Clone)]
+pub struct PriorityFeeConfig {
+    /// Percentile of the recent non-zero fees to bid, 1-100
+    pub percentile: u8,
+    pub cache_ttl: Duration,
+    /// Fixed unit price that skips the query, for RPCs without the method
+    pub override_price: Option<u64>,
+}
+
+impl PriorityFeeConfig {
+    pub fn from_env() -> Self {
+        let percentile = std::env::var("PRIORITY_FEE_PERCENTILE")
+            .ok()
+            .and_then(|v| u8::from_str(v.trim()).ok())
+            .unwrap_or(75)
+            .clamp(1, 100);
+        let cache_secs = std::env::var("PRIORITY_FEE_CACHE_SECS")
+            .ok()
+            .and_then(|v| u64::from_str(v.trim()).ok())
+            .unwrap_or(5);
+        let override_price = std::env::var("PRIORITY_FEE_MICRO_LAMPORTS")
+            .ok()
+            .and_then(|v| u64::from_str(v.trim()).ok());
+        Self { percentile, cache_ttl: Duration::from_secs(cache_secs), override_price }
+    }
+}
+
+/// How a transaction's compute unit price is chosen, `PRIORITY_FEE_STRATEGY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriorityFeeStrategy {
+    /// `UNIT_PRICE` on every transaction
+    Fixed,
+    /// `PRIORITY_FEE_PERCENTILE` of the recent fees on the accounts written
+    Percentile,
+    /// `bps` of the expected profit spread over the unit limit; the
+    /// percentile when a transaction has no expected profit
+    ProfitShare { bps: u64 },
+}
+
+impl PriorityFeeStrategy {
+    /// `fixed`, `percentile` (the default) or `profit`, which bids
+    /// `PRIORITY_FEE_PROFIT_BPS` (default 1000, a tenth) of the profit
+    pub fn from_env() -> Self {
+        let strategy = std::env::var("PRIORITY_FEE_STRATEGY").unwrap_or_default();
+        match strategy.trim().to_ascii_lowercase().as_str() {
+            "fixed" => Self::Fixed,
+            "profit" => Self::ProfitShare {
+                bps: std::env::var("PRIORITY_FEE_PROFIT_BPS")
+                    .ok()
+                    .and_then(|v| u64::from_str(v.trim()).ok())
+                    .unwrap_or(1_000)
+                    .min(10_000),
+            },
+            _ => Self::Percentile,
+        }
+    }
+
+    /// Unit price paying `bps` of `profit_lamports` over `unit_limit` units
+    pub fn profit_share_price(bps: u64, profit_lamports: u64, unit_limit: u32) -> u64 {
+        let fee_lamports = profit_lamports as u128 * bps as u128 / 10_000;
+        (fee_lamports * 1_000_000 / unit_limit.max(1) as u128).min(u64::MAX as u128) as u64
+    }
+}
+
+impl fmt::Display for PriorityFeeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed => write!(f, "fixed"),
+            Self::Percentile => write!(f, "percentile"),
+            Self::ProfitShare { bps } => write!(f, "profit {}bps", bps),
+        }
+    }
+}
+
+/// `percentile` of the non-zero `fees` by nearest rank; slots where nobody
+/// bid say nothing about the going price
+pub fn fee_percentile(fees: &[u64], percentile: u8) -> Option<u64> {
+    let mut bids: Vec<u64> = fees.iter().copied().filter(|fee| *fee > 0).collect();
+    if bids.is_empty() {
+        return None;
+    }
+    bids.sort_unstable();
+    let rank = (bids.len() * percentile.clamp(1, 100) as usize).div_ceil(100);
+    bids.get(rank.saturating_sub(1)).copied()
+}
+
+/// Accounts a transaction write-locks, other than the fee payer, which every
+/// transaction of ours shares and so tells nothing about the competition
+pub fn writable_accounts(payer: &Pubkey, instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = instructions
+        .iter()
+        .filter(|ix| ix.program_id != compute_budget::id())
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| meta.is_writable && meta.pubkey != *payer)
+        .map(|meta| meta.pubkey)
+        .collect();
+    accounts.sort_unstable();
+    accounts.dedup();
+    accounts.truncate(MAX_FEE_ACCOUNTS);
+    accounts
+}
+
+/// Caches a fee percentile per set of writable accounts
+pub struct PriorityFeeEstimator {
+    config: PriorityFeeConfig,
+    cache: Mutex<HashMap<Vec<Pubkey>, (Instant, Option<u64>)>>,
+}
+
+impl PriorityFeeEstimator {
+    pub fn new(config: PriorityFeeConfig) -> Self {
+        Self { config, cache: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn config(&self) -> &PriorityFeeConfig {
+        &self.config
+    }
+
+    /// Unit price for a transaction writing `accounts` (sorted and deduped,
+    /// as [`writable_accounts`] returns them). None when recent fees are all
+    /// zero, and the caller's configured price applies.
+    pub async fn unit_price(&self, rpc_client: &RpcClient, accounts: &[Pubkey]) -> Result<Option<u64>> {
+        if let Some(price) = self.config.override_price {
+            return Ok(Some(price));
+        }
+        if let Some((at, price)) = self.cache.lock_or_recover().get(accounts) {
+            if at.elapsed() < self.config.cache_ttl {
+                return Ok(*price);
+            }
+        }
+
+        let fees = rpc_client.get_recent_prioritization_fees(accounts).await?;
+        let fees: Vec<u64> = fees.iter().map(|fee| fee.prioritization_fee).collect();
+        let price = fee_percentile(&fees, self.config.percentile);
+
+        let mut cache = self.cache.lock_or_recover();
+        if cache.len() >= MAX_CACHED_SETS {
+            let ttl = self.config.cache_ttl;
+            cache.retain(|_, (at, _)| at.elapsed() < ttl);
+            if cache.len() >= MAX_CACHED_SETS {
+                cache.clear();
+            }
+        }
+        cache.insert(accounts.to_vec(), (Instant::now(), price));
+        Ok(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anchor_client::solana_client::rpc_request::RpcRequest;
+    use anchor_client::solana_sdk::instruction::AccountMeta;
+    use serde_json::json;
+
+    use super::*;
+
+    // Twelve slots, two of them without a bid: 100 to 1000 in steps of 100
+    const FEES: [u64; 12] = [0, 500, 100, 300, 0, 200, 400, 1_000, 600, 800, 700, 900];
+
+    #[test]
+    fn percentile_is_the_nearest_rank_of_the_nonzero_fees() {
+        assert_eq!(fee_percentile(&FEES, 75), Some(800));
+        assert_eq!(fee_percentile(&FEES, 50), Some(500));
+        assert_eq!(fee_percentile(&FEES, 100), Some(1_000));
+        assert_eq!(fee_percentile(&FEES, 1), Some(100));
+        // Out of range percentiles are clamped rather than indexing past the end
+        assert_eq!(fee_percentile(&FEES, 0), Some(100));
+        assert_eq!(fee_percentile(&FEES, 200), Some(1_000));
+        assert_eq!(fee_percentile(&[0, 0, 0], 75), None);
+        assert_eq!(fee_percentile(&[], 75), None);
+    }
+
+    #[test]
+    fn only_accounts_competed_for_are_asked_about() {
+        let payer = Pubkey::new_unique();
+        let (pool, vault, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let swap = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(pool, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(mint, false),
+            ],
+        );
+        let again = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![AccountMeta::new(pool, false)]);
+        let budget = compute_budget::ComputeBudgetInstruction::set_compute_unit_price(1);
+
+        let mut expected = vec![pool, vault];
+        expected.sort_unstable();
+        assert_eq!(writable_accounts(&payer, &[budget, swap, again]), expected);
+    }
+
+    #[tokio::test]
+    async fn estimator_bids_the_configured_percentile() {
+        let fees: Vec<_> = FEES.iter().enumerate().map(|(slot, fee)| json!({ "slot": slot, "prioritizationFee": fee })).collect();
+        let mocks = HashMap::from([(RpcRequest::GetRecentPrioritizationFees, json!(fees))]);
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+        let config = PriorityFeeConfig { percentile: 75, cache_ttl: Duration::from_secs(5), override_price: None };
+        let estimator = PriorityFeeEstimator::new(config);
+        assert_eq!(estimator.unit_price(&rpc_client, &[Pubkey::new_unique()]).await.unwrap(), Some(800));
+    }
+
+    #[tokio::test]
+    async fn override_skips_the_query() {
+        // Every call to this client fails, so a price can only come from the override
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        let config = PriorityFeeConfig { percentile: 75, cache_ttl: Duration::from_secs(5), override_price: Some(7_500) };
+        let estimator = PriorityFeeEstimator::new(config);
+        assert_eq!(estimator.unit_price(&rpc_client, &[Pubkey::new_unique()]).await.unwrap(), Some(7_500));
+    }
+
+    #[test]
+    fn profit_share_spreads_the_share_over_the_unit_limit() {
+        // A tenth of 1M lamports is 100k lamports, over 200k units 500k micro-lamports each
+        assert_eq!(PriorityFeeStrategy::profit_share_price(1_000, 1_000_000, 200_000), 500_000);
+        assert_eq!(PriorityFeeStrategy::profit_share_price(1_000, 0, 200_000), 0);
+    }
+}
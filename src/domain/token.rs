@@ -7,7 +7,7 @@ use spl_token_client::{
     client::{ProgramClient, ProgramRpcClient, ProgramRpcClientSendTransaction},
     token::{Token, TokenError, TokenResult},
 };
-use std::{collections::HashMap, sync::Arc, str::FromStr};
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, str::FromStr};
 use base64;
 use anyhow::{Result, anyhow};
 use solana_client;
@@ -15,6 +15,14 @@ use solana_client::rpc_request::TokenAccountsFilter;
 use spl_token::state::Account as TokenAccount;
 use spl_token::state::Mint as TokenMint;
 use anchor_client::solana_sdk::program_pack::Pack;
+use crate::common::format::{cached_decimals, remember_decimals};
+use crate::common::sync::LockExt;
+use crate::core::price::{check_reserves, PoolDataUnavailable, Price, ReserveProblem};
+
+lazy_static::lazy_static! {
+    // Mints with a decimals lookup in flight, so a burst of swaps reads once
+    static ref FETCHING_DECIMALS: Mutex<HashSet<Pubkey>> = Mutex::new(HashSet::new());
+}
 
 /// TokenPrice represents the price of a token on a specific DEX
 #[derive(Debug, Clone)]
@@ -224,6 +232,42 @@ pub async fn get_mint_info(
     mint_result
 }
 
+/// Decimals of `mint`, read from its account once and cached from then on;
+/// Token-2022 extensions follow the base layout, so only that is read
+pub async fn mint_decimals(
+    rpc_client: &anchor_client::solana_client::nonblocking::rpc_client::RpcClient,
+    mint: &Pubkey,
+) -> Result<u8> {
+    if let Some(decimals) = cached_decimals(&mint.to_string()) {
+        return Ok(decimals);
+    }
+    let data = rpc_client.get_account_data(mint).await?;
+    let base = data.get(..TokenMint::LEN).ok_or_else(|| anyhow!("Mint account {} is too short", mint))?;
+    let decimals = TokenMint::unpack_from_slice(base)
+        .map_err(|e| anyhow!("Failed to unpack mint {}: {}", mint, e))?
+        .decimals;
+    remember_decimals(&mint.to_string(), decimals);
+    crate::record::kv::remember_mint_decimals(&mint.to_string(), decimals);
+    Ok(decimals)
+}
+
+/// Look `mint`'s decimals up in the background, for callers that can't wait
+/// on RPC and skip the mint until [`cached_decimals`] has it
+pub fn prefetch_decimals(mint: Pubkey) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if cached_decimals(&mint.to_string()).is_some() || !FETCHING_DECIMALS.lock_or_recover().insert(mint) {
+        return;
+    }
+    runtime.spawn(async move {
+        if let Ok(rpc_client) = crate::common::config::create_nonblocking_rpc_client().await {
+            let _ = mint_decimals(&rpc_client, &mint).await;
+        }
+        FETCHING_DECIMALS.lock_or_recover().remove(&mint);
+    });
+}
+
 /// Find pools for a token on a specific DEX
 pub async fn find_pools_for_token(
     client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
@@ -298,38 +342,20 @@ pub async fn get_pumpswap_token_price(
         }
     }
     
-    // Calculate price (SOL per token)
-    if base_reserve == 0 || quote_reserve == 0 {
-        return Err(anyhow!("Zero reserves in pool"));
-    }
+    // Zero reserves mean the pool can't be priced, never a zero or infinite price
+    check_reserves(&pool_id.to_string(), base_reserve, quote_reserve)?;
     
-    // Get token decimals
-    let base_mint_info = match client.get_account(&base_mint).await {
-        Ok(info) => info,
-        Err(e) => return Err(anyhow!("Failed to get base mint account: {}", e)),
-    };
-    
-    let base_mint_data = match TokenMint::unpack(&base_mint_info.data) {
-        Ok(data) => data,
-        Err(e) => return Err(anyhow!("Failed to unpack base mint data: {}", e)),
-    };
-    
-    let base_decimals = base_mint_data.decimals;
+    // Token decimals, cached after the first read
+    let base_decimals = mint_decimals(&client, &base_mint)
+        .await
+        .map_err(|e| anyhow!("Failed to get base mint decimals: {}", e))?;
     
     // SOL has 9 decimals
     let quote_decimals = 9;
     
-    // Calculate price normalized by decimals
-    let base_amount_normalized = base_reserve as f64 / 10f64.powf(base_decimals as f64);
-    let quote_amount_normalized = quote_reserve as f64 / 10f64.powf(quote_decimals as f64);
-    
-    if base_amount_normalized == 0.0 {
-        return Err(anyhow!("Zero normalized base amount"));
-    }
-    
-    let price = quote_amount_normalized / base_amount_normalized;
+    let price = Price::from_reserves(&pool_id.to_string(), base_reserve, quote_reserve, base_decimals, quote_decimals)?;
     
-    Ok(price)
+    Ok(price.value())
 }
 
 /// Get the price of a token from a PumpFun bonding curve
@@ -387,7 +413,8 @@ pub async fn get_pumpfun_token_price(
         return Err(anyhow!("Zero normalized token amount"));
     }
     
-    let price = sol_amount_normalized / token_amount_normalized;
+    let price = Price::new(sol_amount_normalized / token_amount_normalized)
+        .ok_or_else(|| PoolDataUnavailable::new(mint, ReserveProblem::NotFinite))?;
     
-    Ok(price)
+    Ok(price.value())
 }
@@ -1,13 +1,18 @@
-use std::{sync::Arc, time::Duration};
+use std::collections::HashMap;
+use std::{sync::{Arc, Mutex}, time::Duration};
 use std::{str::FromStr, env};
 use anyhow::Result;
 use colored::Colorize;
 use std::hash;
 use anchor_client::solana_client::rpc_client::RpcClient;
 use anchor_client::solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
     commitment_config::CommitmentConfig,
     hash::Hash,
     instruction::Instruction,
+    message::{v0, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
     signature::Keypair,
     signer::Signer,
     system_instruction, system_transaction,
@@ -19,6 +24,12 @@ use jito_json_rpc_client::jsonrpc_client::rpc_client::RpcClient as JitoRpcClient
 use tokio::time::Instant;
 
 use crate::common::config::{create_nonblocking_rpc_client, Config};
+use crate::core::compute_estimator::{InstructionShape, COMPUTE_ESTIMATOR};
+use crate::core::priority_fee::{self, PriorityFeeStrategy, PRIORITY_FEES};
+use crate::common::sync::LockExt;
+use crate::core::nonce;
+use crate::core::tx_pruning::{self, PruneLimits};
+use crate::engine::leader;
 use crate::{
     common::logger::Logger,
     services::{
@@ -34,6 +45,9 @@ use lazy_static;
 const NOZOMI_TIP: f64 = 0.001;
 const JITO_TIP: f64 = 0.001;
 
+/// Most transactions the block engine accepts in one bundle, tip included
+const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
 // Cache these values to avoid repeated env lookups
 lazy_static::lazy_static! {
     static ref UNIT_PRICE: u64 = env::var("UNIT_PRICE")
@@ -45,6 +59,54 @@ lazy_static::lazy_static! {
         .ok()
         .and_then(|v| u32::from_str(&v).ok())
         .unwrap_or(200_000);
+
+    /// Fees of sent transactions not yet recorded, by signature
+    static ref SENT_FEES: Mutex<HashMap<String, SentFee>> = Mutex::new(HashMap::new());
+}
+
+/// Sent fees kept for recording at once; the cache starts over past this
+const MAX_SENT_FEES: usize = 1_024;
+
+/// Fee the runtime charges per signature; the bot's transactions have one
+pub const BASE_FEE_LAMPORTS: u64 = 5_000;
+
+/// Priority fee for one transaction at the configured unit price and limit
+pub fn priority_fee_lamports() -> u64 {
+    ComputeBudget::configured().fee_lamports()
+}
+
+/// Strategy and compute budget a transaction was sent with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SentFee {
+    pub strategy: PriorityFeeStrategy,
+    pub budget: ComputeBudget,
+}
+
+/// Compute unit limit and price a transaction is sent with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub unit_limit: u32,
+    /// Micro-lamports per compute unit
+    pub unit_price: u64,
+}
+
+impl ComputeBudget {
+    /// `UNIT_LIMIT` and `UNIT_PRICE`
+    pub fn configured() -> Self {
+        Self { unit_limit: *UNIT_LIMIT, unit_price: *UNIT_PRICE }
+    }
+
+    pub fn fee_lamports(&self) -> u64 {
+        self.unit_price * self.unit_limit as u64 / 1_000_000
+    }
+
+    /// Limit then price, to go ahead of everything else in the transaction
+    pub fn instructions(&self) -> [Instruction; 2] {
+        [
+            anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(self.unit_limit),
+            anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(self.unit_price),
+        ]
+    }
 }
 
 // Functions to get tip values at runtime
@@ -62,6 +124,57 @@ pub fn get_jito_tip() -> f64 {
         .unwrap_or(JITO_TIP)
 }
 
+/// Lamports tipped to Jito per transaction or bundle: `JITO_TIP_LAMPORTS`,
+/// else `JITO_TIP_VALUE` in SOL, else 0.001 SOL
+pub fn jito_tip_lamports() -> u64 {
+    std::env::var("JITO_TIP_LAMPORTS")
+        .ok()
+        .and_then(|v| u64::from_str(v.trim()).ok())
+        .unwrap_or_else(|| ui_amount_to_amount(get_jito_tip(), spl_token::native_mint::DECIMALS))
+}
+
+/// `JITO_TIP_ACCOUNT`: where bundle tips go, Jito's tip accounts in turn
+/// when unset
+fn bundle_tip_account() -> Result<Pubkey> {
+    match env::var("JITO_TIP_ACCOUNT") {
+        Ok(account) if !account.trim().is_empty() => Pubkey::from_str(account.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid JITO_TIP_ACCOUNT {}: {}", account, e)),
+        _ => Ok(jito::get_tip_account()?.0),
+    }
+}
+
+/// Send `transactions` to the Jito block engine as one bundle, followed by
+/// a `tip_lamports` transfer to the tip account, so they land together and
+/// in order or not at all. Returns the bundle id.
+pub async fn submit_jito_bundle(
+    keypair: &Keypair,
+    mut transactions: Vec<VersionedTransaction>,
+    tip_lamports: u64,
+    recent_blockhash: Hash,
+    logger: &Logger,
+) -> Result<String> {
+    if transactions.len() >= MAX_BUNDLE_TRANSACTIONS {
+        return Err(anyhow::anyhow!(
+            "{} transactions and the tip don't fit a {} transaction bundle",
+            transactions.len(),
+            MAX_BUNDLE_TRANSACTIONS
+        ));
+    }
+    let tip_account = bundle_tip_account()?;
+    logger.log(format!("[JITO TIP] => {} lamports to {}", tip_lamports, tip_account).cyan().to_string());
+    transactions.push(VersionedTransaction::from(system_transaction::transfer(
+        keypair,
+        &tip_account,
+        tip_lamports,
+        recent_blockhash,
+    )));
+    let jito_client = JitoRpcClient::new(format!("{}/api/v1/bundles", *jito::BLOCK_ENGINE_URL));
+    jito_client
+        .send_bundle(&transactions)
+        .await
+        .map_err(|e| anyhow::anyhow!("Jito rejected the bundle: {:?}", e))
+}
+
 pub async fn jito_confirm(
     client: &RpcClient,
     keypair: &Keypair,
@@ -144,17 +257,30 @@ pub async fn jito_confirm(
 }
 
 pub async fn new_signed_and_send_normal(
+    recent_blockhash: anchor_client::solana_sdk::hash::Hash,
+    keypair: &Keypair,
+    instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
+    logger: &Logger,
+) -> Result<Vec<String>> {
+    new_signed_and_send_versioned(recent_blockhash, keypair, instructions, &[], priority_fee, logger).await
+}
+
+/// The RPC send of [`new_signed_and_send_normal`], compiled as a v0 message
+/// over `lookup_tables` when any are given
+pub async fn new_signed_and_send_versioned(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    lookup_tables: &[AddressLookupTableAccount],
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, false, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
+    let ComputeBudget { unit_limit, unit_price } = fee.budget;
     
-    // ADD Priority fee
-    // -------------
-    let unit_limit = get_unit_limit();
-    let unit_price = get_unit_price();
 
     let modify_compute_units =
         anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(unit_limit);
@@ -166,12 +292,8 @@ pub async fn new_signed_and_send_normal(
     instructions.insert(1, add_priority_fee);
     
     // Create and sign transaction
-    let txn = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&keypair.pubkey()),
-        &vec![keypair],
-        recent_blockhash,
-    );
+    nonce::keep_advance_first(&mut instructions);
+    let txn = sign_message(keypair, &instructions, lookup_tables, recent_blockhash)?;
     
     // Log before sending
     logger.log("Attempting to send normal transaction...".to_string());
@@ -187,6 +309,7 @@ pub async fn new_signed_and_send_normal(
     };
     
     // Send transaction and directly handle the result
+    leader::ensure_leader()?;
     let result = rpc_client.send_transaction_with_config(&txn, tx_config).await;
     
     match result {
@@ -200,6 +323,8 @@ pub async fn new_signed_and_send_normal(
                     .to_string(),
             );
             
+            note_sent(std::slice::from_ref(&sig_str), &shape, fee);
+
             // Return the signature string in the expected Vec format
             Ok(vec![sig_str])
         },
@@ -215,28 +340,27 @@ pub async fn new_signed_and_send(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, true, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
+    let ComputeBudget { unit_limit, unit_price } = fee.budget;
 
     let mut txs = vec![];
     let (tip_account, tip1_account) = jito::get_tip_account()?;
 
-    // jito tip, the upper limit is 0.1
-    let tip = jito::get_tip_value().await?;
-    let fee = jito::get_priority_fee().await?;
-    let tip_lamports = ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS);
-    let fee_lamports = ui_amount_to_amount(fee, spl_token::native_mint::DECIMALS);
+    let jito_fee = jito::get_priority_fee().await?;
+    let tip_lamports = jito_tip_lamports();
+    logger.log(format!("[JITO TIP] => {} lamports to {}", tip_lamports, tip_account).cyan().to_string());
+    let fee_lamports = ui_amount_to_amount(jito_fee, spl_token::native_mint::DECIMALS);
 
     let jito_tip_instruction =
         system_instruction::transfer(&keypair.pubkey(), &tip_account, tip_lamports);
     let jito_tip2_instruction =
         system_instruction::transfer(&keypair.pubkey(), &tip1_account, fee_lamports);
 
-        // ADD Priority fee
-        // -------------
-        let unit_limit = get_unit_limit();
-        let unit_price = get_unit_price();
 
     let modify_compute_units =
         anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
@@ -253,6 +377,7 @@ pub async fn new_signed_and_send(
     // instructions.push(jito_tip2_instruction);
 
     // send init tx
+    nonce::keep_advance_first(&mut instructions);
     let txn = Transaction::new_signed_with_payer(
         &instructions,
         Some(&keypair.pubkey()),
@@ -274,6 +399,7 @@ pub async fn new_signed_and_send(
     let jito_client = Arc::new(JitoClient::new(
         format!("{}/api/v1/transactions", *jito::BLOCK_ENGINE_URL).as_str(),
     ));
+    leader::ensure_leader()?;
     let sig = match jito_client.send_transaction(&txn).await {
         Ok(signature) => signature,
         Err(_) => {
@@ -291,6 +417,7 @@ pub async fn new_signed_and_send(
             .to_string(),
     );
 
+    note_sent(&txs, &shape, fee);
     Ok(txs)
 }
 
@@ -298,9 +425,12 @@ pub async fn new_signed_and_send_zeroslot(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, true, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
 
     let mut txs = vec![];
     let tip_account = zeroslot::get_tip_account()?;
@@ -312,8 +442,12 @@ pub async fn new_signed_and_send_zeroslot(
     let zeroslot_tip_instruction =
         system_instruction::transfer(&keypair.pubkey(), &tip_account, tip_lamports);
     instructions.insert(0, zeroslot_tip_instruction);
+    let [modify_compute_units, add_priority_fee] = fee.budget.instructions();
+    instructions.insert(1, modify_compute_units);
+    instructions.insert(2, add_priority_fee);
 
     // send init tx
+    nonce::keep_advance_first(&mut instructions);
     let txn = Transaction::new_signed_with_payer(
         &instructions,
         Some(&keypair.pubkey()),
@@ -333,6 +467,7 @@ pub async fn new_signed_and_send_zeroslot(
     // };
 
     let zeroslot_client = Arc::new(ZeroSlotClient::new((*zeroslot::ZERO_SLOT_URL).as_str()));
+    leader::ensure_leader()?;
     let sig = match zeroslot_client.send_transaction(&txn).await {
         Ok(signature) => signature,
         Err(_) => {
@@ -349,6 +484,7 @@ pub async fn new_signed_and_send_zeroslot(
             .to_string(),
     );
 
+    note_sent(&txs, &shape, fee);
     Ok(txs)
 }
 
@@ -361,13 +497,232 @@ fn get_unit_limit() -> u32 {
     *UNIT_LIMIT
 }
 
+/// Both legs of an arbitrage, signed as one transaction
+#[derive(Debug, Clone)]
+pub struct AtomicArbitrageTx {
+    pub transaction: VersionedTransaction,
+    /// Serialized size in bytes, within the packet limit
+    pub size: usize,
+    /// True when the legs only fit as a v0 message over lookup tables
+    pub uses_lookup_tables: bool,
+}
+
+/// Sign the buy and sell legs of an arbitrage into one transaction, so a
+/// sell that misses its minimum reverts the buy with it
+///
+/// The order is `budget`, buy, sell. ATA creates the sell repeats from
+/// the buy are dropped, and the legs' own closes stay after the sell. A
+/// legacy message is used when it fits the packet, a v0 message over
+/// `lookup_tables` when it doesn't, and anything bigger is an error.
+pub fn build_atomic_arbitrage_tx(
+    keypair: &Keypair,
+    buy: Vec<Instruction>,
+    sell: Vec<Instruction>,
+    recent_blockhash: Hash,
+    lookup_tables: &[AddressLookupTableAccount],
+    budget: ComputeBudget,
+) -> Result<AtomicArbitrageTx> {
+    let mut instructions = budget.instructions().to_vec();
+    let repeated_create =
+        |ix: &Instruction| tx_pruning::OptionalKind::of(ix) == Some(tx_pruning::OptionalKind::AtaCreate) && buy.contains(ix);
+    let sell: Vec<Instruction> = sell.into_iter().filter(|ix| !repeated_create(ix)).collect();
+    instructions.extend(buy);
+    instructions.extend(sell);
+
+    let legacy = sign_message(keypair, &instructions, &[], recent_blockhash)?;
+    let size = serialized_size(&legacy);
+    if size <= PACKET_DATA_SIZE {
+        return Ok(AtomicArbitrageTx { transaction: legacy, size, uses_lookup_tables: false });
+    }
+    if lookup_tables.is_empty() {
+        return Err(anyhow::anyhow!(
+            "arbitrage transaction is {} bytes, over the {} byte limit, and no lookup table was given",
+            size,
+            PACKET_DATA_SIZE
+        ));
+    }
+
+    let transaction = sign_message(keypair, &instructions, lookup_tables, recent_blockhash)?;
+    let compressed = serialized_size(&transaction);
+    if compressed > PACKET_DATA_SIZE {
+        return Err(anyhow::anyhow!(
+            "arbitrage transaction is {} bytes even over {} lookup table(s), over the {} byte limit",
+            compressed,
+            lookup_tables.len(),
+            PACKET_DATA_SIZE
+        ));
+    }
+    Ok(AtomicArbitrageTx { transaction, size: compressed, uses_lookup_tables: true })
+}
+
+/// Sign `instructions` as a legacy message, or as a v0 message over
+/// `lookup_tables` when there are any
+pub fn sign_message(
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    if lookup_tables.is_empty() {
+        return Ok(VersionedTransaction::from(Transaction::new_signed_with_payer(
+            instructions,
+            Some(&keypair.pubkey()),
+            &[keypair],
+            recent_blockhash,
+        )));
+    }
+    let message = v0::Message::try_compile(&keypair.pubkey(), instructions, lookup_tables, recent_blockhash)?;
+    Ok(VersionedTransaction::try_new(VersionedMessage::V0(message), &[keypair])?)
+}
+
+fn serialized_size(transaction: &VersionedTransaction) -> usize {
+    bincode::serialized_size(transaction).map(|size| size as usize).unwrap_or(usize::MAX)
+}
+
+/// Unit price for `instructions`: `UNIT_PRICE` when fixed, otherwise the
+/// recent-fee percentile for the accounts they write, or `UNIT_PRICE` when
+/// no recent fee could be had. A swap's profit isn't known this far down, so
+/// a profit share bids the percentile too.
+async fn unit_price_for(keypair: &Keypair, instructions: &[Instruction], strategy: PriorityFeeStrategy, logger: &Logger) -> u64 {
+    if strategy == PriorityFeeStrategy::Fixed {
+        return get_unit_price();
+    }
+    let accounts = priority_fee::writable_accounts(&keypair.pubkey(), instructions);
+    let estimated = match create_nonblocking_rpc_client().await {
+        Ok(rpc_client) => PRIORITY_FEES.unit_price(&rpc_client, &accounts).await,
+        Err(e) => Err(e),
+    };
+    match estimated {
+        Ok(Some(unit_price)) => {
+            logger.debug_with(|| format!("[PRIORITY FEE] => {} micro-lamports per unit over {} accounts", unit_price, accounts.len()));
+            unit_price
+        }
+        Ok(None) => get_unit_price(),
+        Err(e) => {
+            logger.log(format!("[PRIORITY FEE] => Using UNIT_PRICE: {}", e).yellow().to_string());
+            get_unit_price()
+        }
+    }
+}
+
+/// Compute budget `instructions` go out with under `strategy`, logged with
+/// what it costs
+async fn price_transaction(
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    recent_blockhash: Hash,
+    strategy: PriorityFeeStrategy,
+    logger: &Logger,
+) -> (SentFee, Option<InstructionShape>) {
+    let (unit_limit, shape) = estimate_unit_limit(keypair, instructions, recent_blockhash, logger).await;
+    let unit_price = unit_price_for(keypair, instructions, strategy, logger).await;
+    let fee = SentFee { strategy, budget: ComputeBudget { unit_limit, unit_price } };
+    logger.log(format!(
+        "[PRIORITY FEE] => {}: {} micro-lamports x {} units = {} lamports",
+        strategy, unit_price, unit_limit, fee.budget.fee_lamports()
+    ).cyan().to_string());
+    (fee, shape)
+}
+
+/// Compute unit limit for a swap, simulated once per instruction shape and
+/// cached; falls back to UNIT_LIMIT when the swap can't be simulated
+async fn estimate_unit_limit(
+    keypair: &Keypair,
+    instructions: &[Instruction],
+    recent_blockhash: Hash,
+    logger: &Logger,
+) -> (u32, Option<InstructionShape>) {
+    let Some(shape) = InstructionShape::from_instructions(instructions) else {
+        return (get_unit_limit(), None);
+    };
+    if let Some(limit) = COMPUTE_ESTIMATOR.cached_limit(&shape) {
+        return (limit, Some(shape));
+    }
+
+    let simulated = match create_nonblocking_rpc_client().await {
+        Ok(rpc_client) => {
+            COMPUTE_ESTIMATOR
+                .simulate_and_cache(&rpc_client, keypair, instructions, recent_blockhash, shape.clone())
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match simulated {
+        Ok(limit) => {
+            logger.log(format!("[CU ESTIMATE] => {} -> {} units", shape, limit).cyan().to_string());
+            (limit, Some(shape))
+        }
+        Err(e) => {
+            logger.log(format!("[CU ESTIMATE] => Using UNIT_LIMIT: {}", e).yellow().to_string());
+            (get_unit_limit(), Some(shape))
+        }
+    }
+}
+
+/// Drop memo, ATA close and redundant ATA create instructions until the
+/// transaction fits the packet size and `MAX_TX_COST_LAMPORTS`
+fn prune_optional_instructions(keypair: &Keypair, instructions: &mut Vec<Instruction>, with_tip: bool, logger: &Logger) {
+    // Same shape as what the senders add: compute budget, and a tip transfer
+    let mut reserved = vec![
+        anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(get_unit_limit()),
+        anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(get_unit_price()),
+    ];
+    if with_tip {
+        reserved.push(system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1));
+    }
+
+    let limits = PruneLimits::from_env(priority_fee_lamports());
+    // No record of existing ATAs here yet, so pre-creates are always kept
+    let outcome = tx_pruning::prune_to_fit(&keypair.pubkey(), std::mem::take(instructions), &reserved, &limits, |_| false);
+    for pruned in &outcome.pruned {
+        logger.log(format!(
+            "[PRUNED] => {:?} ({:?}){}",
+            pruned.kind,
+            pruned.reason,
+            pruned.account.map(|a| format!(", deferred close of {}", a)).unwrap_or_default()
+        ).yellow().to_string());
+    }
+    if !outcome.fits {
+        logger.log(format!(
+            "[PRUNED] => Still over limits after pruning: {} bytes, {} lamports",
+            outcome.size, outcome.cost_lamports
+        ).red().to_string());
+    }
+    *instructions = outcome.instructions;
+}
+
+fn note_sent(signatures: &[String], shape: &Option<InstructionShape>, fee: SentFee) {
+    if let Some(shape) = shape {
+        for signature in signatures {
+            COMPUTE_ESTIMATOR.note_sent(signature, shape);
+        }
+    }
+    let mut sent = SENT_FEES.lock_or_recover();
+    if sent.len() >= MAX_SENT_FEES {
+        sent.clear();
+    }
+    for signature in signatures {
+        sent.insert(signature.clone(), fee);
+    }
+}
+
+/// The priority fee `signature` was sent with, for its trade record; handed
+/// out once
+pub fn take_sent_fee(signature: &str) -> Option<SentFee> {
+    SENT_FEES.lock_or_recover().remove(signature)
+}
+
 pub async fn new_signed_and_send_jito_tip(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, true, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
+    let ComputeBudget { unit_limit, unit_price } = fee.budget;
 
     let mut txs = vec![];
     let tip_account = match jito::get_tip_account() {
@@ -375,17 +730,13 @@ pub async fn new_signed_and_send_jito_tip(
         Err(e) => return Err(anyhow::anyhow!("Failed to get jito tip account: {}", e)),
     };
 
-    // jito tip, the upper limit is 0.1
-    let tip_lamports = ui_amount_to_amount(JITO_TIP, spl_token::native_mint::DECIMALS);
+    let tip_lamports = jito_tip_lamports();
+    logger.log(format!("[JITO TIP] => {} lamports to {}", tip_lamports, tip_account.0).cyan().to_string());
 
     let jito_tip_instruction =
         system_instruction::transfer(&keypair.pubkey(), &tip_account.0, tip_lamports);
     instructions.insert(0, jito_tip_instruction);
 
-    // ADD Priority fee
-    // -------------
-    let unit_limit = get_unit_limit();
-    let unit_price = get_unit_price();
 
     let modify_compute_units =
         anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
@@ -399,6 +750,7 @@ pub async fn new_signed_and_send_jito_tip(
     instructions.insert(2, add_priority_fee);
     
     // send init tx
+    nonce::keep_advance_first(&mut instructions);
     let txn = Transaction::new_signed_with_payer(
         &instructions,
         Some(&keypair.pubkey()),
@@ -418,6 +770,7 @@ pub async fn new_signed_and_send_jito_tip(
     };
     
     // Send transaction through normal RPC client
+    leader::ensure_leader()?;
     let tx_result = client.send_transaction_with_config(&txn, tx_config).await;
     
     match tx_result {
@@ -428,6 +781,7 @@ pub async fn new_signed_and_send_jito_tip(
                     .green()
                     .to_string(),
             );
+            note_sent(&txs, &shape, fee);
             Ok(txs)
         }
         Err(e) => {
@@ -442,9 +796,13 @@ pub async fn new_signed_and_send_nozomi(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, true, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
+    let ComputeBudget { unit_limit, unit_price } = fee.budget;
 
     let mut txs = vec![];
     let tip_account = nozomi::get_tip_account()?;
@@ -455,10 +813,6 @@ pub async fn new_signed_and_send_nozomi(
         system_instruction::transfer(&keypair.pubkey(), &tip_account, tip_lamports);
     instructions.insert(0, nozomi_tip_instruction);
 
-    // ADD Priority fee
-    // -------------
-    let unit_limit = 200000;
-    let unit_price = 20000;
 
     let modify_compute_units =
         anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
@@ -472,6 +826,7 @@ pub async fn new_signed_and_send_nozomi(
     instructions.insert(2, add_priority_fee);
 
     // send init tx
+    nonce::keep_advance_first(&mut instructions);
     let txn = Transaction::new_signed_with_payer(
         &instructions,
         Some(&keypair.pubkey()),
@@ -481,6 +836,7 @@ pub async fn new_signed_and_send_nozomi(
 
     let zeroslot_client = Arc::new(ZeroSlotClient::new((*nozomi::NOZOMI_URL).as_str()));
     // Store the result first to avoid capturing non-Send error types
+    leader::ensure_leader()?;
     let tx_result = zeroslot_client.send_transaction(&txn).await;
     
     match tx_result {
@@ -498,6 +854,7 @@ pub async fn new_signed_and_send_nozomi(
         }
     };
 
+    note_sent(&txs, &shape, fee);
     Ok(txs)
 }
 
@@ -505,6 +862,7 @@ pub async fn new_signed_and_send_spam(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &std::sync::Arc<Keypair>,
     instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     // Instead of spawning tasks in separate threads, run them concurrently in the same thread
@@ -517,6 +875,7 @@ pub async fn new_signed_and_send_spam(
         recent_blockhash,
         &keypair_clone,
         instructions.clone(),
+        priority_fee,
         &logger_clone,
     );
     
@@ -524,6 +883,7 @@ pub async fn new_signed_and_send_spam(
         recent_blockhash,
         &keypair_clone,
         instructions_clone.clone(),
+        priority_fee,
         &logger_clone,
     );
     
@@ -531,6 +891,7 @@ pub async fn new_signed_and_send_spam(
         recent_blockhash,
         &keypair_clone,
         instructions_clone,
+        priority_fee,
         &logger_clone,
     );
     
@@ -569,17 +930,19 @@ pub async fn new_signed_and_send_nozomi_tip(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, true, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
+    let ComputeBudget { unit_limit, unit_price } = fee.budget;
     let mut txs = vec![];
 
     // Get configuration - directly get the mutex guard
     let config = crate::common::config::Config::get().await;
 
     // Add compute budget instructions
-    let unit_limit = get_unit_limit();
-    let unit_price = get_unit_price();
 
     let modify_compute_units =
         anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(unit_limit);
@@ -606,6 +969,7 @@ pub async fn new_signed_and_send_nozomi_tip(
     instructions.push(tip_instruction);
 
     // Build and send transaction
+    nonce::keep_advance_first(&mut instructions);
     let txn = Transaction::new_signed_with_payer(
         &instructions,
         Some(&keypair.pubkey()),
@@ -615,6 +979,7 @@ pub async fn new_signed_and_send_nozomi_tip(
 
     // Use Nozomi client to send transaction
     let zeroslot_client = Arc::new(ZeroSlotClient::new((*nozomi::NOZOMI_URL).as_str()));
+    leader::ensure_leader()?;
     let nozomi_result = zeroslot_client.send_transaction(&txn).await;
     
     // Process result immediately to avoid capturing non-Send errors
@@ -626,6 +991,7 @@ pub async fn new_signed_and_send_nozomi_tip(
                     .green()
                     .to_string(),
             );
+            note_sent(&txs, &shape, fee);
             return Ok(txs);
         }
         Err(e) => {
@@ -646,6 +1012,7 @@ pub async fn new_signed_and_send_nozomi_tip(
     };
     
     // Handle potential error immediately
+    leader::ensure_leader()?;
     let fallback_result = client.send_transaction_with_config(&txn, tx_config).await;
     match fallback_result {
         Ok(signature) => {
@@ -655,6 +1022,7 @@ pub async fn new_signed_and_send_nozomi_tip(
                     .yellow()
                     .to_string(),
             );
+            note_sent(&txs, &shape, fee);
             Ok(txs)
         }
         Err(e) => {
@@ -668,14 +1036,16 @@ pub async fn new_signed_and_send_zeroslot_tip(
     recent_blockhash: anchor_client::solana_sdk::hash::Hash,
     keypair: &Keypair,
     mut instructions: Vec<Instruction>,
+    priority_fee: PriorityFeeStrategy,
     logger: &Logger,
 ) -> Result<Vec<String>> {
     let start_time = Instant::now();
+    prune_optional_instructions(keypair, &mut instructions, true, logger);
+    let (fee, shape) = price_transaction(keypair, &instructions, recent_blockhash, priority_fee, logger).await;
+    let ComputeBudget { unit_limit, unit_price } = fee.budget;
     let mut txs = vec![];
 
     // Add compute budget instructions
-    let unit_limit = get_unit_limit();
-    let unit_price = get_unit_price();
 
     let modify_compute_units =
         anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(unit_limit);
@@ -706,6 +1076,7 @@ pub async fn new_signed_and_send_zeroslot_tip(
     instructions.push(tip_instruction);
 
     // Build and send transaction
+    nonce::keep_advance_first(&mut instructions);
     let txn = Transaction::new_signed_with_payer(
         &instructions,
         Some(&keypair.pubkey()),
@@ -715,6 +1086,7 @@ pub async fn new_signed_and_send_zeroslot_tip(
 
     // Use ZeroSlot client to send transaction
     let zeroslot_client = Arc::new(ZeroSlotClient::new((*zeroslot::ZERO_SLOT_URL).as_str()));
+    leader::ensure_leader()?;
     let zeroslot_result = zeroslot_client.send_transaction(&txn).await;
     
     // Process the result immediately to avoid capturing non-Send errors
@@ -726,6 +1098,7 @@ pub async fn new_signed_and_send_zeroslot_tip(
                     .green()
                     .to_string(),
             );
+            note_sent(&txs, &shape, fee);
             return Ok(txs);
         }
         Err(e) => {
@@ -749,6 +1122,7 @@ pub async fn new_signed_and_send_zeroslot_tip(
     };
     
     // Handle potential error immediately to avoid capturing non-Send error
+    leader::ensure_leader()?;
     let fallback_result = client.send_transaction_with_config(&txn, tx_config).await;
     match fallback_result {
         Ok(signature) => {
@@ -758,6 +1132,7 @@ pub async fn new_signed_and_send_zeroslot_tip(
                     .yellow()
                     .to_string(),
             );
+            note_sent(&txs, &shape, fee);
             Ok(txs)
         }
         Err(e) => {
@@ -766,3 +1141,113 @@ pub async fn new_signed_and_send_zeroslot_tip(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_sdk::{compute_budget, instruction::AccountMeta};
+    use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+
+    fn leg(program_id: Pubkey, data_len: usize) -> Instruction {
+        Instruction::new_with_bytes(program_id, &vec![1; data_len], vec![])
+    }
+
+    #[test]
+    fn atomic_tx_orders_budget_then_buy_then_sell() {
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey();
+        let mint = Pubkey::new_unique();
+        let (buy_program, sell_program) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let create = create_associated_token_account_idempotent(&owner, &owner, &mint, &spl_token::id());
+        let ata = spl_associated_token_account::get_associated_token_address(&owner, &mint);
+        let close = spl_token::instruction::close_account(&spl_token::id(), &ata, &owner, &owner, &[&owner]).unwrap();
+
+        let built = build_atomic_arbitrage_tx(
+            &keypair,
+            vec![create.clone(), leg(buy_program, 8)],
+            // The sell leg repeats the create, which must not run twice
+            vec![create, leg(sell_program, 8), close],
+            Hash::new_unique(),
+            &[],
+            ComputeBudget { unit_limit: 300_000, unit_price: 1_000 },
+        )
+        .unwrap();
+
+        assert!(!built.uses_lookup_tables);
+        assert!(built.size <= PACKET_DATA_SIZE);
+        let message = &built.transaction.message;
+        let keys = message.static_account_keys();
+        let programs: Vec<Pubkey> = message.instructions().iter().map(|ix| *ix.program_id(keys)).collect();
+        assert_eq!(
+            programs,
+            vec![
+                compute_budget::id(),
+                compute_budget::id(),
+                spl_associated_token_account::id(),
+                buy_program,
+                sell_program,
+                spl_token::id(),
+            ]
+        );
+    }
+
+    #[test]
+    fn atomic_tx_over_the_packet_without_lookup_tables_is_an_error() {
+        let keypair = Keypair::new();
+        let err = build_atomic_arbitrage_tx(
+            &keypair,
+            vec![leg(Pubkey::new_unique(), 700)],
+            vec![leg(Pubkey::new_unique(), 700)],
+            Hash::new_unique(),
+            &[],
+            ComputeBudget { unit_limit: 300_000, unit_price: 1_000 },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("no lookup table"), "{}", err);
+    }
+
+    #[test]
+    fn atomic_tx_over_the_packet_compiles_to_v0_over_the_table() {
+        let keypair = Keypair::new();
+        // Two legs of 5 writable and 30 read-only accounts each, 70 keys a
+        // legacy message would list in full
+        let wide_leg = |program_id: Pubkey| {
+            let accounts = (0..35)
+                .map(|i| match i {
+                    0..=4 => AccountMeta::new(Pubkey::new_unique(), false),
+                    _ => AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                })
+                .collect();
+            Instruction::new_with_bytes(program_id, &[1; 8], accounts)
+        };
+        let (buy, sell) = (wide_leg(Pubkey::new_unique()), wide_leg(Pubkey::new_unique()));
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: buy.accounts.iter().chain(&sell.accounts).map(|meta| meta.pubkey).collect(),
+        };
+
+        let built = build_atomic_arbitrage_tx(
+            &keypair,
+            vec![buy.clone()],
+            vec![sell.clone()],
+            Hash::new_unique(),
+            std::slice::from_ref(&table),
+            ComputeBudget { unit_limit: 300_000, unit_price: 1_000 },
+        )
+        .unwrap();
+
+        assert!(built.uses_lookup_tables);
+        assert!(built.size <= PACKET_DATA_SIZE);
+        let VersionedMessage::V0(message) = &built.transaction.message else {
+            panic!("expected a v0 message");
+        };
+        assert_eq!(message.address_table_lookups.len(), 1);
+        let lookup = &message.address_table_lookups[0];
+        assert_eq!(lookup.account_key, table.key);
+        assert_eq!((lookup.writable_indexes.len(), lookup.readonly_indexes.len()), (10, 60));
+        // Only the payer and the invoked programs stay inline
+        assert_eq!(message.account_keys.len(), 4);
+        assert!(!message.account_keys.contains(&buy.accounts[0].pubkey));
+        assert!(built.transaction.verify_with_results().into_iter().all(|ok| ok));
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anchor_client::solana_sdk::{
+    instruction::Instruction, message::Message, packet::PACKET_DATA_SIZE, pubkey::Pubkey,
+    transaction::Transaction,
+};
+use serde::Serialize;
+
+/// Lamports locked as rent when an associated token account is created
+pub const ATA_RENT_LAMPORTS: u64 = 2_039_280;
+
+/// Base fee per signature
+const SIGNATURE_FEE_LAMPORTS: u64 = 5_000;
+
+/// SPL Token `CloseAccount` instruction tag
+const CLOSE_ACCOUNT_TAG: u8 = 9;
+
+lazy_static::lazy_static! {
+    static ref MEMO_PROGRAMS: [Pubkey; 2] = [
+        Pubkey::from_str("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr").unwrap(),
+        Pubkey::from_str("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo").unwrap(),
+    ];
+
+    /// Token accounts whose close was pruned, waiting for a later sweep
+    static ref DEFERRED_ATA_CLOSES: Mutex<HashSet<Pubkey>> = Mutex::new(HashSet::new());
+}
+
+/// Instructions a transaction still works without, in the order they are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionalKind {
+    Memo,
+    /// Closing a token account after the swap, reclaims rent but can wait
+    AtaClose,
+    /// Creating an ATA that already exists
+    AtaCreate,
+}
+
+impl OptionalKind {
+    pub const PRUNE_ORDER: [OptionalKind; 3] = [Self::Memo, Self::AtaClose, Self::AtaCreate];
+
+    /// Classify `ix`, None for core instructions (swap, tip, compute budget, ...)
+    pub fn of(ix: &Instruction) -> Option<Self> {
+        if MEMO_PROGRAMS.contains(&ix.program_id) {
+            return Some(Self::Memo);
+        }
+        if (ix.program_id == spl_token::id() || ix.program_id == spl_token_2022::id())
+            && ix.data.first() == Some(&CLOSE_ACCOUNT_TAG)
+        {
+            return Some(Self::AtaClose);
+        }
+        if ix.program_id == spl_associated_token_account::id() {
+            return Some(Self::AtaCreate);
+        }
+        None
+    }
+
+    /// Token account the instruction closes or creates
+    fn account(&self, ix: &Instruction) -> Option<Pubkey> {
+        let index = match self {
+            Self::Memo => return None,
+            Self::AtaClose => 0,
+            Self::AtaCreate => 1,
+        };
+        ix.accounts.get(index).map(|meta| meta.pubkey)
+    }
+}
+
+/// One instruction removed by `prune_to_fit`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrunedInstruction {
+    pub kind: OptionalKind,
+    pub account: Option<Pubkey>,
+    /// Which limit was exceeded when it was removed
+    pub reason: PruneReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    TooLarge,
+    OverFeeBudget,
+}
+
+/// Size and cost limits a transaction must fit
+#[derive(Debug, Clone, Copy)]
+pub struct PruneLimits {
+    /// Serialized transaction size in bytes
+    pub max_size: usize,
+    /// Signature fee + priority fee + ATA rent, None for no budget
+    pub max_cost_lamports: Option<u64>,
+    /// Priority fee the transaction will pay
+    pub priority_fee_lamports: u64,
+}
+
+impl PruneLimits {
+    /// Packet size limit, `MAX_TX_COST_LAMPORTS` as the optional cost budget
+    pub fn from_env(priority_fee_lamports: u64) -> Self {
+        Self {
+            max_size: PACKET_DATA_SIZE,
+            max_cost_lamports: std::env::var("MAX_TX_COST_LAMPORTS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok()),
+            priority_fee_lamports,
+        }
+    }
+}
+
+/// Result of pruning, kept with the transaction it was built for
+#[derive(Debug, Clone)]
+pub struct PruneOutcome {
+    pub instructions: Vec<Instruction>,
+    pub pruned: Vec<PrunedInstruction>,
+    /// Serialized size with the reserved instructions
+    pub size: usize,
+    pub cost_lamports: u64,
+    /// False if the limits are still exceeded after every optional instruction was tried
+    pub fits: bool,
+}
+
+/// Serialized size of an unsigned transaction carrying `instructions`
+pub fn transaction_size(payer: &Pubkey, instructions: &[Instruction]) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    bincode::serialized_size(&Transaction::new_unsigned(message))
+        .map(|size| size as usize)
+        .unwrap_or(usize::MAX)
+}
+
+/// Signature fee, priority fee and rent for every ATA the instructions create
+pub fn transaction_cost(instructions: &[Instruction], priority_fee_lamports: u64) -> u64 {
+    let creates = instructions
+        .iter()
+        .filter(|ix| OptionalKind::of(ix) == Some(OptionalKind::AtaCreate))
+        .count() as u64;
+    SIGNATURE_FEE_LAMPORTS + priority_fee_lamports + creates * ATA_RENT_LAMPORTS
+}
+
+/// Drop optional instructions until the transaction fits `limits`
+///
+/// `reserved` are instructions the sender adds afterwards (compute budget,
+/// tip); they count towards the size but are never candidates. Candidates go
+/// in `OptionalKind::PRUNE_ORDER`, last occurrence first, and an ATA create is
+/// only dropped when `ata_exists` says the account is already there. Over the
+/// cost budget alone, only removals that lower the cost are made.
+pub fn prune_to_fit(
+    payer: &Pubkey,
+    instructions: Vec<Instruction>,
+    reserved: &[Instruction],
+    limits: &PruneLimits,
+    ata_exists: impl Fn(&Pubkey) -> bool,
+) -> PruneOutcome {
+    let measure = |ixs: &[Instruction]| {
+        let all: Vec<Instruction> = reserved.iter().chain(ixs.iter()).cloned().collect();
+        (transaction_size(payer, &all), transaction_cost(ixs, limits.priority_fee_lamports))
+    };
+    let violation = |size: usize, cost: u64| {
+        if size > limits.max_size {
+            Some(PruneReason::TooLarge)
+        } else if limits.max_cost_lamports.is_some_and(|max| cost > max) {
+            Some(PruneReason::OverFeeBudget)
+        } else {
+            None
+        }
+    };
+
+    let mut instructions = instructions;
+    let mut pruned = Vec::new();
+    let (mut size, mut cost) = measure(&instructions);
+
+    for kind in OptionalKind::PRUNE_ORDER {
+        loop {
+            let Some(reason) = violation(size, cost) else {
+                return PruneOutcome { instructions, pruned, size, cost_lamports: cost, fits: true };
+            };
+            let candidate = instructions.iter().rposition(|ix| {
+                OptionalKind::of(ix) == Some(kind)
+                    && (kind != OptionalKind::AtaCreate
+                        || kind.account(ix).is_some_and(|account| ata_exists(&account)))
+            });
+            let Some(index) = candidate else { break };
+            if reason == PruneReason::OverFeeBudget && kind != OptionalKind::AtaCreate {
+                // Memos and closes don't cost anything to drop or keep
+                break;
+            }
+
+            let removed = instructions.remove(index);
+            let account = kind.account(&removed);
+            if kind == OptionalKind::AtaClose {
+                if let Some(account) = account {
+                    defer_ata_close(account);
+                }
+            }
+            pruned.push(PrunedInstruction { kind, account, reason });
+            (size, cost) = measure(&instructions);
+        }
+    }
+
+    let fits = violation(size, cost).is_none();
+    PruneOutcome { instructions, pruned, size, cost_lamports: cost, fits }
+}
+
+/// Queue a token account whose close was pruned so it can be swept later
+pub fn defer_ata_close(account: Pubkey) {
+    if let Ok(mut queue) = DEFERRED_ATA_CLOSES.lock() {
+        queue.insert(account);
+    }
+}
+
+/// Take every queued token account close
+pub fn take_deferred_ata_closes() -> Vec<Pubkey> {
+    DEFERRED_ATA_CLOSES
+        .lock()
+        .map(|mut queue| queue.drain().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_client::solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, instruction::AccountMeta, system_instruction,
+    };
+    use spl_associated_token_account::{
+        get_associated_token_address, instruction::create_associated_token_account_idempotent,
+    };
+
+    use super::*;
+
+    struct Built {
+        payer: Pubkey,
+        ata: Pubkey,
+        closed: Pubkey,
+        reserved: Vec<Instruction>,
+        swap: Instruction,
+        tip: Instruction,
+    }
+
+    impl Built {
+        fn new() -> Self {
+            let payer = Pubkey::new_unique();
+            let mint = Pubkey::new_unique();
+            let accounts = (0..16).map(|_| AccountMeta::new(Pubkey::new_unique(), false)).collect();
+            Self {
+                payer,
+                ata: get_associated_token_address(&payer, &mint),
+                closed: Pubkey::new_unique(),
+                reserved: vec![ComputeBudgetInstruction::set_compute_unit_limit(200_000)],
+                swap: Instruction::new_with_bytes(Pubkey::new_unique(), &[1; 24], accounts),
+                tip: system_instruction::transfer(&payer, &Pubkey::new_unique(), 10_000),
+            }
+        }
+
+        fn create(&self, mint: &Pubkey) -> Instruction {
+            create_associated_token_account_idempotent(&self.payer, &self.payer, mint, &spl_token::id())
+        }
+
+        /// ATA create, swap, close, memo and tip, the way a sell with cleanup is built
+        fn everything(&self, mint: &Pubkey, memo_bytes: usize) -> Vec<Instruction> {
+            vec![
+                self.create(mint),
+                self.swap.clone(),
+                spl_token::instruction::close_account(&spl_token::id(), &self.closed, &self.payer, &self.payer, &[&self.payer]).unwrap(),
+                Instruction::new_with_bytes(MEMO_PROGRAMS[0], &vec![b'x'; memo_bytes], vec![]),
+                self.tip.clone(),
+            ]
+        }
+
+        fn core(&self) -> Vec<Instruction> {
+            vec![self.swap.clone(), self.tip.clone()]
+        }
+    }
+
+    fn kinds(outcome: &PruneOutcome) -> Vec<OptionalKind> {
+        outcome.pruned.iter().map(|p| p.kind).collect()
+    }
+
+    #[test]
+    fn oversized_memo_is_dropped_first_to_fit_a_packet() {
+        let built = Built::new();
+        let mint = Pubkey::new_unique();
+        let instructions = built.everything(&mint, 1_000);
+        let all: Vec<_> = built.reserved.iter().chain(&instructions).cloned().collect();
+        assert!(transaction_size(&built.payer, &all) > PACKET_DATA_SIZE);
+
+        let limits = PruneLimits { max_size: PACKET_DATA_SIZE, max_cost_lamports: None, priority_fee_lamports: 0 };
+        let outcome = prune_to_fit(&built.payer, instructions, &built.reserved, &limits, |_| true);
+
+        assert!(outcome.fits);
+        assert!(outcome.size <= PACKET_DATA_SIZE);
+        assert_eq!(kinds(&outcome), [OptionalKind::Memo]);
+        assert_eq!(outcome.instructions.len(), 4);
+    }
+
+    #[test]
+    fn prunes_memo_then_close_then_existing_create_and_never_the_swap_or_tip() {
+        let built = Built::new();
+        let mint = Pubkey::new_unique();
+        let core: Vec<_> = built.reserved.iter().cloned().chain(built.core()).collect();
+        // Only the core instructions fit
+        let limits = PruneLimits {
+            max_size: transaction_size(&built.payer, &core),
+            max_cost_lamports: None,
+            priority_fee_lamports: 0,
+        };
+        let ata = get_associated_token_address(&built.payer, &mint);
+        let outcome = prune_to_fit(&built.payer, built.everything(&mint, 32), &built.reserved, &limits, |account| *account == ata);
+
+        assert!(outcome.fits);
+        assert_eq!(kinds(&outcome), [OptionalKind::Memo, OptionalKind::AtaClose, OptionalKind::AtaCreate]);
+        assert!(outcome.pruned.iter().all(|p| p.reason == PruneReason::TooLarge));
+        assert_eq!(outcome.pruned[1].account, Some(built.closed));
+        assert_eq!(outcome.pruned[2].account, Some(ata));
+        assert_eq!(outcome.instructions, built.core());
+        assert_eq!(outcome.size, limits.max_size);
+        // The skipped close waits for the sweeper
+        assert!(take_deferred_ata_closes().contains(&built.closed));
+    }
+
+    #[test]
+    fn create_of_a_missing_ata_is_kept_even_if_it_does_not_fit() {
+        let built = Built::new();
+        let mint = Pubkey::new_unique();
+        let core: Vec<_> = built.reserved.iter().cloned().chain(built.core()).collect();
+        let limits = PruneLimits {
+            max_size: transaction_size(&built.payer, &core),
+            max_cost_lamports: None,
+            priority_fee_lamports: 0,
+        };
+        let outcome = prune_to_fit(&built.payer, built.everything(&mint, 32), &built.reserved, &limits, |_| false);
+
+        assert!(!outcome.fits);
+        assert_eq!(kinds(&outcome), [OptionalKind::Memo, OptionalKind::AtaClose]);
+        assert_eq!(outcome.instructions[0], built.create(&mint));
+    }
+
+    #[test]
+    fn over_fee_budget_only_drops_what_costs_rent() {
+        let built = Built::new();
+        let mint = Pubkey::new_unique();
+        let limits = PruneLimits {
+            max_size: PACKET_DATA_SIZE,
+            max_cost_lamports: Some(SIGNATURE_FEE_LAMPORTS + 1_000),
+            priority_fee_lamports: 1_000,
+        };
+        let outcome = prune_to_fit(&built.payer, built.everything(&mint, 32), &built.reserved, &limits, |_| true);
+
+        assert!(outcome.fits);
+        assert_eq!(kinds(&outcome), [OptionalKind::AtaCreate]);
+        assert_eq!(outcome.pruned[0].reason, PruneReason::OverFeeBudget);
+        assert_eq!(outcome.cost_lamports, SIGNATURE_FEE_LAMPORTS + 1_000);
+        assert_eq!(outcome.instructions.len(), 4);
+    }
+}
@@ -0,0 +1,219 @@
+//! Where signed transactions are submitted
+//!
+//! The copy trader's buys and the liquidation sells used to call the
+//! zeroslot sender directly. They go through a `TxSender` now, chosen once
+//! from `TX_BACKEND`, so the same code sends over zeroslot, as a Jito
+//! bundle or to the plain RPC node. The exit ladder keeps its per-stage
+//! senders, choosing a different path per stage is its point.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anchor_client::solana_sdk::{hash::Hash, instruction::Instruction, signature::{Keypair, Signature}};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::common::logger::Logger;
+use crate::core::priority_fee::PriorityFeeStrategy;
+use crate::core::tx;
+
+/// Signs `instructions` with `keypair` and submits them, returning the signatures sent
+#[async_trait]
+pub trait TxSender: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(
+        &self,
+        recent_blockhash: Hash,
+        keypair: &Keypair,
+        instructions: Vec<Instruction>,
+        priority_fee: PriorityFeeStrategy,
+        logger: &Logger,
+    ) -> Result<Vec<String>>;
+}
+
+/// Through the zeroslot relay, with its tip
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ZeroslotSender;
+
+#[async_trait]
+impl TxSender for ZeroslotSender {
+    fn name(&self) -> &'static str {
+        "zeroslot"
+    }
+
+    async fn send(
+        &self,
+        recent_blockhash: Hash,
+        keypair: &Keypair,
+        instructions: Vec<Instruction>,
+        priority_fee: PriorityFeeStrategy,
+        logger: &Logger,
+    ) -> Result<Vec<String>> {
+        tx::new_signed_and_send_zeroslot(recent_blockhash, keypair, instructions, priority_fee, logger).await
+    }
+}
+
+/// As a Jito bundle with a tip transfer
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JitoSender;
+
+#[async_trait]
+impl TxSender for JitoSender {
+    fn name(&self) -> &'static str {
+        "jito"
+    }
+
+    async fn send(
+        &self,
+        recent_blockhash: Hash,
+        keypair: &Keypair,
+        instructions: Vec<Instruction>,
+        priority_fee: PriorityFeeStrategy,
+        logger: &Logger,
+    ) -> Result<Vec<String>> {
+        tx::new_signed_and_send(recent_blockhash, keypair, instructions, priority_fee, logger).await
+    }
+}
+
+/// Straight to the configured RPC node, no tip
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RpcSender;
+
+#[async_trait]
+impl TxSender for RpcSender {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    async fn send(
+        &self,
+        recent_blockhash: Hash,
+        keypair: &Keypair,
+        instructions: Vec<Instruction>,
+        priority_fee: PriorityFeeStrategy,
+        logger: &Logger,
+    ) -> Result<Vec<String>> {
+        tx::new_signed_and_send_normal(recent_blockhash, keypair, instructions, priority_fee, logger).await
+    }
+}
+
+/// Sends nothing and makes up a signature per call, for tests and mock app states
+#[derive(Debug, Default)]
+pub struct NoopSender {
+    sent: AtomicUsize,
+}
+
+impl NoopSender {
+    /// Transactions "sent" so far
+    pub fn sent(&self) -> usize {
+        self.sent.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl TxSender for NoopSender {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    async fn send(
+        &self,
+        _recent_blockhash: Hash,
+        _keypair: &Keypair,
+        _instructions: Vec<Instruction>,
+        _priority_fee: PriorityFeeStrategy,
+        _logger: &Logger,
+    ) -> Result<Vec<String>> {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        Ok(vec![Signature::new_unique().to_string()])
+    }
+}
+
+/// `TX_BACKEND`: zeroslot (default), jito or rpc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxBackend {
+    Zeroslot,
+    Jito,
+    Rpc,
+}
+
+impl FromStr for TxBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "zeroslot" => Ok(Self::Zeroslot),
+            "jito" => Ok(Self::Jito),
+            "rpc" | "normal" => Ok(Self::Rpc),
+            other => Err(anyhow!("Unknown TX_BACKEND: {}", other)),
+        }
+    }
+}
+
+impl TxBackend {
+    /// Zeroslot when unset, an error for anything unrecognised
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("TX_BACKEND") {
+            Ok(v) if !v.trim().is_empty() => v.parse(),
+            _ => Ok(Self::Zeroslot),
+        }
+    }
+
+    pub fn sender(self) -> Arc<dyn TxSender> {
+        match self {
+            Self::Zeroslot => Arc::new(ZeroslotSender),
+            Self::Jito => Arc::new(JitoSender),
+            Self::Rpc => Arc::new(RpcSender),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::config::AppState;
+
+    #[test]
+    fn backends_parse_by_name_and_build_their_sender() {
+        for (value, backend, name) in [
+            ("zeroslot", TxBackend::Zeroslot, "zeroslot"),
+            (" JITO ", TxBackend::Jito, "jito"),
+            ("rpc", TxBackend::Rpc, "rpc"),
+            ("normal", TxBackend::Rpc, "rpc"),
+        ] {
+            let parsed: TxBackend = value.parse().unwrap();
+            assert_eq!(parsed, backend);
+            assert_eq!(parsed.sender().name(), name);
+        }
+        assert!("bloxroute".parse::<TxBackend>().is_err());
+    }
+
+    #[tokio::test]
+    async fn noop_sender_counts_sends_and_signs_nothing_real() {
+        let noop = Arc::new(NoopSender::default());
+        let sender: Arc<dyn TxSender> = noop.clone();
+        let (keypair, logger) = (Keypair::new(), Logger::new("[TEST] => ".to_string()));
+
+        let first = sender
+            .send(Hash::default(), &keypair, Vec::new(), PriorityFeeStrategy::Fixed, &logger)
+            .await
+            .unwrap();
+        let second = sender
+            .send(Hash::default(), &keypair, Vec::new(), PriorityFeeStrategy::Fixed, &logger)
+            .await
+            .unwrap();
+
+        assert_eq!(noop.sent(), 2);
+        assert_eq!((first.len(), second.len()), (1, 1));
+        assert_ne!(first, second);
+        assert!(first[0].parse::<Signature>().is_ok());
+    }
+
+    #[test]
+    fn test_states_submit_through_the_noop_sender() {
+        assert_eq!(AppState::for_tests().tx_sender.name(), "noop");
+    }
+}
@@ -16,6 +16,7 @@
 //! - `InsufficientFunds`: Insufficient funds for a transaction.
 //! - `SimulationError`: Transaction simulation failed.
 //! - `RateLimitExceeded`: Rate limit exceeded.
+//! - `TooSmall`: Trade amount is below the venue minimum and would be dust.
 
 use serde_json::Error;
 use anchor_client::solana_client::{
@@ -92,6 +93,8 @@ pub enum ClientError {
     InvalidEventType,
 
     ChannelClosed,
+    /// Trade amount below the venue minimum (amount, minimum)
+    TooSmall(u64, u64),
 }
 
 impl std::fmt::Display for ClientError {
@@ -128,6 +131,9 @@ impl std::fmt::Display for ClientError {
             Self::Duplicate(msg) => write!(f, "Duplicate event: {}", msg),
             Self::InvalidEventType => write!(f, "Invalid event type"),
             Self::ChannelClosed => write!(f, "Channel closed"),
+            Self::TooSmall(amount, minimum) => {
+                write!(f, "Trade amount too small: {} < minimum {}", amount, minimum)
+            }
         }
     }
 }
@@ -153,6 +159,7 @@ impl std::error::Error for ClientError {
             Self::Duplicate(_) => None,
             Self::InvalidEventType => None,
             Self::ChannelClosed => None,
+            Self::TooSmall(_, _) => None,
             _ => None,
         }
     }
@@ -1,3 +1,3 @@
 pub use crate::dex::dex_registry::*;
 pub use crate::dex::pump_swap::*;
-
+pub use crate::dex::raydium_amm::*;
@@ -1,8 +1,55 @@
 use std::collections::HashMap;
-use anchor_client::solana_sdk::pubkey::Pubkey;
+use std::fmt;
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Keypair};
 use std::str::FromStr;
 use anyhow::Result;
 use std::sync::Arc;
+use colored::Colorize;
+
+use crate::common::logger::Logger;
+use crate::core::dex_adapter::DexAdapter;
+use crate::dex::{meteora_dlmm::MeteoraDlmm, pump_swap::PumpSwap, raydium_amm::RaydiumAmm};
+
+/// Highest fee override accepted from `DEX_FEES`, anything above is a typo
+pub const MAX_FEE_OVERRIDE_BPS: u64 = 1_000;
+
+lazy_static::lazy_static! {
+    // Per-DEX fee overrides from the DEX_FEES env var, parsed once
+    static ref DEX_FEE_OVERRIDES: HashMap<String, u64> = load_fee_overrides();
+    // Adapter-less registry for lookups off the stream, built on first use
+    static ref SHARED_REGISTRY: DEXRegistry = DEXRegistry::new();
+}
+
+/// Parse `DEX_FEES` (JSON object of dex name -> fee in bps), dropping out-of-range entries
+fn load_fee_overrides() -> HashMap<String, u64> {
+    let logger = Logger::new("[DEX-FEES] => ".yellow().to_string());
+    let raw = match std::env::var("DEX_FEES") {
+        Ok(raw) if !raw.trim().is_empty() => raw,
+        _ => return HashMap::new(),
+    };
+
+    let parsed: HashMap<String, u64> = match serde_json::from_str(&raw) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            logger.log(format!("Ignoring DEX_FEES, invalid JSON: {}", e).red().to_string());
+            return HashMap::new();
+        }
+    };
+
+    parsed
+        .into_iter()
+        .filter(|(dex, bps)| {
+            if *bps > MAX_FEE_OVERRIDE_BPS {
+                logger.log(format!(
+                    "Ignoring fee override for {}: {} bps exceeds {} bps",
+                    dex, bps, MAX_FEE_OVERRIDE_BPS
+                ).red().to_string());
+                return false;
+            }
+            true
+        })
+        .collect()
+}
 
 /// DEX represents a decentralized exchange on Solana
 #[derive(Debug, Clone)]
@@ -19,12 +66,36 @@ pub struct DEX {
     pub is_stable_curve: bool,
     /// Whether this DEX uses concentrated liquidity
     pub is_concentrated_liquidity: bool,
+    /// Whether this DEX is a central limit order book; its price is the
+    /// book's mid and trades fill at the touch
+    pub is_order_book: bool,
+    /// Smallest input (in lamports) the venue reliably fills; anything below is dust
+    pub min_trade_in_lamports: u64,
+    /// Swap fee in basis points used in profit math, `DEX_FEES` overrides the default
+    pub fee_bps: u64,
+}
+
+/// Fee override for a DEX from `DEX_FEES`, if one was given
+pub fn fee_override(name: &str) -> Option<u64> {
+    DEX_FEE_OVERRIDES.get(name).copied()
 }
 
 /// Registry of all supported DEXes
-#[derive(Debug)]
 pub struct DEXRegistry {
     dexes: HashMap<String, DEX>,
+    /// Name of the DEX owning each program id
+    by_program_id: HashMap<Pubkey, String>,
+    /// Venues that can be priced and traded through `DexAdapter`
+    adapters: HashMap<String, Arc<dyn DexAdapter>>,
+}
+
+impl fmt::Debug for DEXRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DEXRegistry")
+            .field("dexes", &self.dexes)
+            .field("adapters", &self.adapters.keys().collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 impl DEXRegistry {
@@ -32,6 +103,8 @@ impl DEXRegistry {
     pub fn new() -> Self {
         let mut registry = Self {
             dexes: HashMap::new(),
+            by_program_id: HashMap::new(),
+            adapters: HashMap::new(),
         };
         
         // Register known DEXes
@@ -42,10 +115,49 @@ impl DEXRegistry {
         registry.register_orca_whirlpool();
         registry.register_meteora_dlmm();
         registry.register_meteora_pools();
+        registry.register_phoenix();
+        registry.apply_fee_overrides();
         
         registry
     }
+
+    /// The default registry, built once; for transaction parsing and other
+    /// lookups that only need the venue table, not `AppState`'s adapters
+    pub fn shared() -> &'static DEXRegistry {
+        &SHARED_REGISTRY
+    }
     
+    /// The registry with adapters for every venue that has a swap builder,
+    /// all signing with `keypair`
+    pub fn with_default_adapters(
+        mut self,
+        keypair: Arc<Keypair>,
+        rpc_client: Arc<anchor_client::solana_client::rpc_client::RpcClient>,
+        rpc_nonblocking_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    ) -> Self {
+        let rpc_client = Some(rpc_client);
+        let rpc_nonblocking_client = Some(rpc_nonblocking_client);
+        self.register_adapter(Arc::new(PumpSwap::new(keypair.clone(), rpc_client.clone(), rpc_nonblocking_client.clone())));
+        self.register_adapter(Arc::new(RaydiumAmm::new(keypair.clone(), rpc_client.clone(), rpc_nonblocking_client.clone())));
+        self.register_adapter(Arc::new(MeteoraDlmm::new(keypair, rpc_client, rpc_nonblocking_client)));
+        self
+    }
+
+    /// Register the adapter of a DEX, replacing any it had
+    pub fn register_adapter(&mut self, adapter: Arc<dyn DexAdapter>) {
+        self.adapters.insert(adapter.name().to_string(), adapter);
+    }
+
+    /// Adapter of a DEX, None when the venue can't be traded through one
+    pub fn adapter(&self, name: &str) -> Option<Arc<dyn DexAdapter>> {
+        self.adapters.get(name).cloned()
+    }
+
+    /// Every registered adapter
+    pub fn adapters(&self) -> Vec<Arc<dyn DexAdapter>> {
+        self.adapters.values().cloned().collect()
+    }
+
     /// Register PumpSwap DEX
     fn register_pumpswap(&mut self) {
         let dex = DEX {
@@ -55,9 +167,12 @@ impl DEXRegistry {
             is_constant_product: true,
             is_stable_curve: false,
             is_concentrated_liquidity: false,
+            is_order_book: false,
+            min_trade_in_lamports: crate::dex::pump_swap::MIN_TRADE_IN_LAMPORTS,
+            fee_bps: crate::dex::pump_swap::PUMP_SWAP_FEE_BPS,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
     }
     
     /// Register Raydium AMM DEX
@@ -69,9 +184,12 @@ impl DEXRegistry {
             is_constant_product: true,
             is_stable_curve: false,
             is_concentrated_liquidity: false,
+            is_order_book: false,
+            min_trade_in_lamports: 100_000,
+            fee_bps: 25,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
     }
     
     /// Register Raydium CLMM DEX
@@ -83,9 +201,12 @@ impl DEXRegistry {
             is_constant_product: false,
             is_stable_curve: false,
             is_concentrated_liquidity: true,
+            is_order_book: false,
+            min_trade_in_lamports: 1_000_000, // below one tick of liquidity the swap reverts
+            fee_bps: 25,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
     }
     
     /// Register Raydium CPMM DEX
@@ -97,9 +218,12 @@ impl DEXRegistry {
             is_constant_product: true,
             is_stable_curve: false,
             is_concentrated_liquidity: false,
+            is_order_book: false,
+            min_trade_in_lamports: 100_000,
+            fee_bps: 25,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
     }
     
     /// Register Orca Whirlpool DEX
@@ -111,9 +235,12 @@ impl DEXRegistry {
             is_constant_product: false,
             is_stable_curve: false,
             is_concentrated_liquidity: true,
+            is_order_book: false,
+            min_trade_in_lamports: 1_000_000, // below one tick of liquidity the swap reverts
+            fee_bps: 30,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
     }
     
     /// Register Meteora DLMM DEX
@@ -125,9 +252,12 @@ impl DEXRegistry {
             is_constant_product: false,
             is_stable_curve: false,
             is_concentrated_liquidity: true,
+            is_order_book: false,
+            min_trade_in_lamports: 1_000_000,
+            fee_bps: 25,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
     }
     
     /// Register Meteora Pools DEX
@@ -139,13 +269,62 @@ impl DEXRegistry {
             is_constant_product: false,
             is_stable_curve: true,
             is_concentrated_liquidity: false,
+            is_order_book: false,
+            min_trade_in_lamports: 100_000,
+            fee_bps: 25,
         };
         
-        self.dexes.insert(dex.name.clone(), dex);
+        self.register_dex(dex);
+    }
+    
+    /// Register Phoenix order book DEX
+    fn register_phoenix(&mut self) {
+        let dex = DEX {
+            name: "phoenix".to_string(),
+            program_id: Pubkey::from_str(crate::dex::phoenix::PHOENIX_PROGRAM).unwrap(),
+            pool_account_size: 0, // market size depends on its book and seat capacity
+            is_constant_product: false,
+            is_stable_curve: false,
+            is_concentrated_liquidity: false,
+            is_order_book: true,
+            min_trade_in_lamports: 100_000,
+            fee_bps: 5, // taker fee of the major markets, makers pay nothing
+        };
+        
+        self.register_dex(dex);
+    }
+    
+    /// Replace registry default fees with the validated `DEX_FEES` overrides
+    fn apply_fee_overrides(&mut self) {
+        for (name, bps) in DEX_FEE_OVERRIDES.iter() {
+            if let Some(dex) = self.dexes.get_mut(name) {
+                dex.fee_bps = *bps;
+            }
+        }
+    }
+    
+    /// Whether the fee for a DEX comes from `DEX_FEES` rather than the default
+    pub fn has_fee_override(&self, name: &str) -> bool {
+        DEX_FEE_OVERRIDES.contains_key(name)
+    }
+    
+    /// Effective fee in bps for a DEX, zero when the DEX is unknown
+    pub fn fee_bps(&self, name: &str) -> u64 {
+        self.dexes.get(name).map(|dex| dex.fee_bps).unwrap_or(0)
+    }
+    
+    /// Fee of one pool of a DEX: what its account says when that was read,
+    /// unless `DEX_FEES` overrides the venue, and the DEX's fee otherwise
+    pub fn pool_fee_bps(&self, name: &str, pool_fee_bps: Option<u64>) -> u64 {
+        match pool_fee_bps {
+            Some(bps) if !self.has_fee_override(name) => bps,
+            _ => self.fee_bps(name),
+        }
     }
     
     /// Register a new DEX
     pub fn register_dex(&mut self, dex: DEX) {
+        self.by_program_id.insert(dex.program_id, dex.name.clone());
         self.dexes.insert(dex.name.clone(), dex);
     }
     
@@ -174,9 +353,24 @@ impl DEXRegistry {
         self.dexes.values().filter(|dex| dex.is_concentrated_liquidity).collect()
     }
     
+    /// Get all DEXes that are order books
+    pub fn get_order_book_dexes(&self) -> Vec<&DEX> {
+        self.dexes.values().filter(|dex| dex.is_order_book).collect()
+    }
+    
+    /// Whether a DEX is an order book, false when the DEX is unknown
+    pub fn is_order_book(&self, name: &str) -> bool {
+        self.dexes.get(name).map(|dex| dex.is_order_book).unwrap_or(false)
+    }
+    
+    /// Minimum trade size in lamports for a DEX, zero when the DEX is unknown
+    pub fn min_trade_in_lamports(&self, name: &str) -> u64 {
+        self.dexes.get(name).map(|dex| dex.min_trade_in_lamports).unwrap_or(0)
+    }
+    
     /// Find a DEX by program ID
     pub fn find_dex_by_program_id(&self, program_id: &Pubkey) -> Option<&DEX> {
-        self.dexes.values().find(|dex| dex.program_id == *program_id)
+        self.by_program_id.get(program_id).and_then(|name| self.dexes.get(name))
     }
 }
 
@@ -199,11 +393,8 @@ pub async fn identify_dex_from_pool(
         }
     };
     
-    // Create a registry to check against
-    let registry = DEXRegistry::new();
-    
     // Check if the pool's owner matches any known DEX program
-    if let Some(dex) = registry.find_dex_by_program_id(&account_info.owner) {
+    if let Some(dex) = DEXRegistry::shared().find_dex_by_program_id(&account_info.owner) {
         return Ok(Some(dex.name.clone()));
     }
     
@@ -0,0 +1,634 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anchor_client::solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::ui_amount_to_amount;
+use tokio::time::Instant;
+
+use crate::{
+    common::{
+        config::{SwapConfig, METEORA_DLMM_PROGRAM},
+        format::remember_decimals,
+    },
+    core::{
+        dex_adapter::{DexAdapter, PoolInfo as AdapterPool},
+        price::{check_reserves, BookTouch, Price},
+    },
+    dex::{
+        pump_swap::{SOL_MINT, TEN_THOUSAND, TOKEN_PROGRAM},
+        slippage::{get_expire_condition, min_amount_with_slippage},
+    },
+    engine::swap::{SwapDirection, SwapInType},
+    error::{ClientError, ClientResult},
+};
+
+pub const LB_PAIR_SIZE: usize = 904;
+
+// LbPair field offsets, after the 8 byte Anchor discriminator; the static
+// parameters run base_factor, filter/decay periods, reduction factor,
+// variable fee control, max volatility accumulator, min/max bin ids and
+// protocol share, then the power factor and 5 bytes of padding
+const BASE_FACTOR_OFFSET: usize = 8;
+const BASE_FEE_POWER_FACTOR_OFFSET: usize = 34;
+const ACTIVE_ID_OFFSET: usize = 76;
+const BIN_STEP_OFFSET: usize = 80;
+pub const TOKEN_X_MINT_OFFSET: usize = 88;
+pub const TOKEN_Y_MINT_OFFSET: usize = 120;
+const RESERVE_X_OFFSET: usize = 152;
+const RESERVE_Y_OFFSET: usize = 184;
+const ORACLE_OFFSET: usize = 552;
+
+// Offsets shared by SPL Token and Token-2022 accounts
+const MINT_DECIMALS_OFFSET: usize = 44;
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+/// Base fee is `base_factor * bin_step * 10 * 10^power` over this
+const FEE_PRECISION: f64 = 1_000_000_000.0;
+
+/// Bins per bin array account
+pub const MAX_BIN_PER_ARRAY: i32 = 70;
+/// Bin arrays passed to a swap: the active one and the next two it may cross into
+const BIN_ARRAYS_PER_SWAP: i64 = 3;
+
+/// sha256("global:swap")[..8]
+pub const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+/// The parts of a decoded LbPair account the bot needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeteoraDlmmPool {
+    pub pool_id: Pubkey,
+    pub base_factor: u16,
+    pub base_fee_power_factor: u8,
+    /// Bin holding the current price
+    pub active_id: i32,
+    /// Price step between neighbouring bins, in bps
+    pub bin_step: u16,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub reserve_x: Pubkey,
+    pub reserve_y: Pubkey,
+    pub oracle: Pubkey,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    i32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+/// Index of the bin array holding `bin_id`
+pub fn bin_array_index(bin_id: i32) -> i64 {
+    bin_id.div_euclid(MAX_BIN_PER_ARRAY) as i64
+}
+
+pub fn bin_array_address(lb_pair: &Pubkey, index: i64) -> Result<Pubkey> {
+    let program_id = Pubkey::from_str(METEORA_DLMM_PROGRAM)?;
+    let (address, _) =
+        Pubkey::find_program_address(&[b"bin_array", lb_pair.as_ref(), &index.to_le_bytes()], &program_id);
+    Ok(address)
+}
+
+impl MeteoraDlmmPool {
+    /// Decode an LbPair account
+    pub fn decode(pool_id: Pubkey, data: &[u8]) -> ClientResult<Self> {
+        if data.len() < LB_PAIR_SIZE {
+            return Err(ClientError::InvalidData(format!(
+                "LbPair account {} is {} bytes, expected {}",
+                pool_id,
+                data.len(),
+                LB_PAIR_SIZE
+            )));
+        }
+
+        Ok(Self {
+            pool_id,
+            base_factor: read_u16(data, BASE_FACTOR_OFFSET),
+            base_fee_power_factor: data[BASE_FEE_POWER_FACTOR_OFFSET],
+            active_id: read_i32(data, ACTIVE_ID_OFFSET),
+            bin_step: read_u16(data, BIN_STEP_OFFSET),
+            token_x_mint: read_pubkey(data, TOKEN_X_MINT_OFFSET),
+            token_y_mint: read_pubkey(data, TOKEN_Y_MINT_OFFSET),
+            reserve_x: read_pubkey(data, RESERVE_X_OFFSET),
+            reserve_y: read_pubkey(data, RESERVE_Y_OFFSET),
+            oracle: read_pubkey(data, ORACLE_OFFSET),
+        })
+    }
+
+    /// Price of token X in raw units of token Y at the active bin
+    pub fn spot_price_x_in_y(&self) -> f64 {
+        (1.0 + self.bin_step as f64 / TEN_THOUSAND as f64).powi(self.active_id)
+    }
+
+    /// Base fee as a fraction of the input; the volatility fee on top of it
+    /// isn't included
+    pub fn base_fee(&self) -> f64 {
+        self.base_factor as f64 * self.bin_step as f64 * 10.0 * 10f64.powi(self.base_fee_power_factor as i32)
+            / FEE_PRECISION
+    }
+
+    /// Bin arrays a swap walks, starting at the active one. Selling X for Y
+    /// moves the active bin down, buying X moves it up.
+    pub fn bin_arrays_for_swap(&self, swap_for_y: bool) -> Result<Vec<Pubkey>> {
+        let start = bin_array_index(self.active_id);
+        let step = if swap_for_y { -1 } else { 1 };
+        (0..BIN_ARRAYS_PER_SWAP)
+            .map(|i| bin_array_address(&self.pool_id, start + i * step))
+            .collect()
+    }
+}
+
+/// An LbPair with its reserves and the token programs of both mints
+#[derive(Debug, Clone)]
+pub struct MeteoraDlmmPoolInfo {
+    pub pool: MeteoraDlmmPool,
+    pub token_x_program: Pubkey,
+    pub token_y_program: Pubkey,
+    pub reserve_x_amount: u64,
+    pub reserve_y_amount: u64,
+    /// Slot the pair was read at
+    pub slot: u64,
+}
+
+impl MeteoraDlmmPoolInfo {
+    /// True when the token is X and SOL is Y
+    pub fn token_is_x(&self) -> bool {
+        Pubkey::from_str(SOL_MINT).map(|sol| self.pool.token_y_mint == sol).unwrap_or(false)
+    }
+
+    /// Token mint of the pair
+    pub fn token_mint(&self) -> Pubkey {
+        if self.token_is_x() { self.pool.token_x_mint } else { self.pool.token_y_mint }
+    }
+
+    /// (token reserve, SOL reserve) summed over every bin
+    pub fn reserves(&self) -> (u64, u64) {
+        if self.token_is_x() {
+            (self.reserve_x_amount, self.reserve_y_amount)
+        } else {
+            (self.reserve_y_amount, self.reserve_x_amount)
+        }
+    }
+
+    /// Active bin price of the token in lamports per raw unit
+    pub fn spot_price_in_sol(&self) -> ClientResult<Price> {
+        let x_in_y = self.pool.spot_price_x_in_y();
+        let price = if self.token_is_x() { x_in_y } else { 1.0 / x_in_y };
+        Price::new(price)
+            .ok_or_else(|| ClientError::InvalidData(format!("LbPair {} has no usable price", self.pool.pool_id)))
+    }
+
+    /// What the active bin fills a buy and a sell at, the base fee either
+    /// side of its price. Bins are constant-price, so this holds until a
+    /// trade empties the active bin.
+    pub fn touch(&self) -> ClientResult<BookTouch> {
+        let price = self.spot_price_in_sol()?.value();
+        let fee = self.pool.base_fee();
+        let invalid = || ClientError::InvalidData(format!("LbPair {} gives no usable quote", self.pool.pool_id));
+        let bid = Price::new(price * (1.0 - fee)).ok_or_else(invalid)?;
+        let ask = Price::new(price * (1.0 + fee)).ok_or_else(invalid)?;
+        BookTouch::new(bid, ask).ok_or_else(invalid)
+    }
+
+    /// Output of `amount_in` at the active bin price after the base fee.
+    /// Crossing into further bins moves the price against the trade, which
+    /// the caller's slippage has to cover.
+    pub fn quote(&self, amount_in: u64, direction: SwapDirection) -> ClientResult<u64> {
+        let price = self.spot_price_in_sol()?.value();
+        let after_fee = amount_in as f64 * (1.0 - self.pool.base_fee());
+        let out = match direction {
+            SwapDirection::Buy => after_fee / price,
+            SwapDirection::Sell => after_fee * price,
+        };
+        let (token_reserve, sol_reserve) = self.reserves();
+        let available = match direction {
+            SwapDirection::Buy => token_reserve,
+            SwapDirection::Sell => sol_reserve,
+        };
+        if !out.is_finite() || out >= available as f64 {
+            return Err(ClientError::InvalidData(format!(
+                "LbPair {} can't fill {} in",
+                self.pool.pool_id, amount_in
+            )));
+        }
+        Ok(out as u64)
+    }
+
+    /// Token program of `mint`'s side of the pair
+    fn token_program_of(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.pool.token_x_mint { self.token_x_program } else { self.token_y_program }
+    }
+}
+
+/// A DLMM swap: `amount_in` from `user_in` for at least `min_amount_out`
+/// into `user_out`, selling X for Y when `swap_for_y`
+pub fn build_swap_instruction(
+    pool_info: &MeteoraDlmmPoolInfo,
+    user_in: Pubkey,
+    user_out: Pubkey,
+    owner: Pubkey,
+    swap_for_y: bool,
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Result<Instruction> {
+    let program_id = Pubkey::from_str(METEORA_DLMM_PROGRAM)?;
+    let (event_authority, _) = Pubkey::find_program_address(&[b"__event_authority"], &program_id);
+    let pool = &pool_info.pool;
+
+    // Optional accounts are passed as the program id when absent; the bitmap
+    // extension is only needed for pairs whose bins sit far from zero
+    let mut accounts = vec![
+        AccountMeta::new(pool.pool_id, false),
+        AccountMeta::new_readonly(program_id, false),
+        AccountMeta::new(pool.reserve_x, false),
+        AccountMeta::new(pool.reserve_y, false),
+        AccountMeta::new(user_in, false),
+        AccountMeta::new(user_out, false),
+        AccountMeta::new_readonly(pool.token_x_mint, false),
+        AccountMeta::new_readonly(pool.token_y_mint, false),
+        AccountMeta::new(pool.oracle, false),
+        AccountMeta::new_readonly(program_id, false),
+        AccountMeta::new_readonly(owner, true),
+        AccountMeta::new_readonly(pool_info.token_x_program, false),
+        AccountMeta::new_readonly(pool_info.token_y_program, false),
+        AccountMeta::new_readonly(event_authority, false),
+        AccountMeta::new_readonly(program_id, false),
+    ];
+    accounts.extend(
+        pool.bin_arrays_for_swap(swap_for_y)?
+            .into_iter()
+            .map(|bin_array| AccountMeta::new(bin_array, false)),
+    );
+
+    let mut data = SWAP_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+    Ok(Instruction { program_id, accounts, data })
+}
+
+pub struct MeteoraDlmm {
+    pub keypair: Arc<Keypair>,
+    pub rpc_client: Option<Arc<anchor_client::solana_client::rpc_client::RpcClient>>,
+    pub rpc_nonblocking_client: Option<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>>,
+}
+
+impl MeteoraDlmm {
+    pub fn new(
+        keypair: Arc<Keypair>,
+        rpc_client: Option<Arc<anchor_client::solana_client::rpc_client::RpcClient>>,
+        rpc_nonblocking_client: Option<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>>,
+    ) -> Self {
+        Self {
+            keypair,
+            rpc_client,
+            rpc_nonblocking_client,
+        }
+    }
+
+    fn nonblocking_client(&self) -> Result<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>> {
+        self.rpc_nonblocking_client
+            .clone()
+            .ok_or_else(|| anyhow!("RPC nonblocking client not initialized"))
+    }
+
+    /// Swap instructions for `mint` against SOL, the same contract as
+    /// `RaydiumAmm::build_swap_ixn_by_mint`: a buy wraps `amount_in` into the
+    /// wallet's wrapped SOL account first, a sell unwraps it after.
+    pub async fn build_swap_ixn_by_mint(
+        &self,
+        mint_str: &str,
+        pool: Option<MeteoraDlmmPoolInfo>,
+        swap_config: SwapConfig,
+        start_time: Instant,
+    ) -> Result<(Arc<Keypair>, Vec<Instruction>, f64)> {
+        let owner = self.keypair.pubkey();
+        let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
+
+        let pool_info = match pool {
+            Some(pool) => pool,
+            None => get_pool_info(self.nonblocking_client()?, mint).await?,
+        };
+        if pool_info.token_mint() != mint {
+            return Err(anyhow!("LbPair {} doesn't pair {} with SOL", pool_info.pool.pool_id, mint));
+        }
+        let token_price = pool_info.spot_price_in_sol().map_err(|e| anyhow!("{}", e))?.value();
+
+        let token_ata = get_associated_token_address_with_program_id(&owner, &mint, &pool_info.token_program_of(&mint));
+        let mut instructions = vec![];
+
+        match swap_config.swap_direction {
+            SwapDirection::Buy => {
+                let amount_in = ui_amount_to_amount(swap_config.amount_in, 9);
+                let amount_out = pool_info.quote(amount_in, SwapDirection::Buy).map_err(|e| anyhow!("{}", e))?;
+                instructions.extend(self.build_buy_ixn_for_amount(
+                    &pool_info,
+                    amount_in,
+                    min_amount_with_slippage(amount_out, swap_config.slippage_bps),
+                )?);
+            }
+            SwapDirection::Sell => {
+                let balance = self
+                    .nonblocking_client()?
+                    .get_token_account_balance(&token_ata)
+                    .await
+                    .map_err(|_| anyhow!("Token ATA does not exist, cannot sell"))?;
+                let held = balance.amount.parse::<u64>().map_err(|_| anyhow!("Unreadable token balance"))?;
+                let amount = match swap_config.in_type {
+                    SwapInType::Qty => ui_amount_to_amount(swap_config.amount_in, balance.decimals),
+                    SwapInType::Pct => {
+                        let pct = swap_config.amount_in.min(1.0);
+                        (pct * 100.0) as u64 * held / 100
+                    }
+                };
+                if amount == 0 {
+                    return Err(anyhow!("Amount is zero, cannot sell"));
+                }
+                if amount > held {
+                    return Err(anyhow!("Sell amount exceeds account balance"));
+                }
+                let amount_out = pool_info.quote(amount, SwapDirection::Sell).map_err(|e| anyhow!("{}", e))?;
+                instructions.extend(self.build_sell_ixn_for_amount(
+                    &pool_info,
+                    amount,
+                    min_amount_with_slippage(amount_out, swap_config.slippage_bps),
+                )?);
+            }
+        }
+
+        if swap_config.swap_direction == SwapDirection::Buy
+            && start_time.elapsed() > Duration::from_millis(get_expire_condition())
+        {
+            return Err(anyhow!("RPC connection is too busy. Expire this txn."));
+        }
+
+        Ok((self.keypair.clone(), instructions, token_price))
+    }
+
+    /// Wrap `amount_in` lamports and spend them in `pool_info` for at least
+    /// `min_amount_out` tokens
+    pub fn build_buy_ixn_for_amount(
+        &self,
+        pool_info: &MeteoraDlmmPoolInfo,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let sol_mint = Pubkey::from_str(SOL_MINT)?;
+        let wsol_program = Pubkey::from_str(TOKEN_PROGRAM)?;
+        let mint = pool_info.token_mint();
+        let token_program = pool_info.token_program_of(&mint);
+        let token_ata = get_associated_token_address_with_program_id(&owner, &mint, &token_program);
+        let wsol_ata = get_associated_token_address_with_program_id(&owner, &sol_mint, &wsol_program);
+        Ok(vec![
+            create_associated_token_account_idempotent(&owner, &owner, &sol_mint, &wsol_program),
+            system_instruction::transfer(&owner, &wsol_ata, amount_in),
+            spl_token::instruction::sync_native(&wsol_program, &wsol_ata)?,
+            create_associated_token_account_idempotent(&owner, &owner, &mint, &token_program),
+            build_swap_instruction(
+                pool_info,
+                wsol_ata,
+                token_ata,
+                owner,
+                !pool_info.token_is_x(),
+                amount_in,
+                min_amount_out,
+            )?,
+        ])
+    }
+
+    /// Sell exactly `amount_in` tokens into `pool_info` for at least
+    /// `min_amount_out` lamports and unwrap them, without looking at the
+    /// wallet's token balance
+    pub fn build_sell_ixn_for_amount(
+        &self,
+        pool_info: &MeteoraDlmmPoolInfo,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let sol_mint = Pubkey::from_str(SOL_MINT)?;
+        let wsol_program = Pubkey::from_str(TOKEN_PROGRAM)?;
+        let mint = pool_info.token_mint();
+        let token_ata = get_associated_token_address_with_program_id(&owner, &mint, &pool_info.token_program_of(&mint));
+        let wsol_ata = get_associated_token_address_with_program_id(&owner, &sol_mint, &wsol_program);
+        Ok(vec![
+            create_associated_token_account_idempotent(&owner, &owner, &sol_mint, &wsol_program),
+            build_swap_instruction(
+                pool_info,
+                token_ata,
+                wsol_ata,
+                owner,
+                pool_info.token_is_x(),
+                amount_in,
+                min_amount_out,
+            )?,
+            spl_token::instruction::close_account(&wsol_program, &wsol_ata, &owner, &owner, &[&owner])?,
+        ])
+    }
+
+    /// The deepest SOL-paired LbPair of `mint`
+    pub async fn get_pool_info(&self, mint_str: &str) -> Result<MeteoraDlmmPoolInfo> {
+        let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
+        get_pool_info(self.nonblocking_client()?, mint).await
+    }
+
+    /// Active bin price in SOL per raw token unit, like `RaydiumAmm::get_token_price`
+    pub async fn get_token_price(&self, mint_str: &str) -> Result<f64> {
+        let pool_info = self.get_pool_info(mint_str).await?;
+        Ok(pool_info.spot_price_in_sol().map_err(|e| anyhow!("{}", e))?.value())
+    }
+}
+
+fn adapter_pool(pool_info: MeteoraDlmmPoolInfo) -> AdapterPool {
+    let (token_reserve, sol_reserve) = pool_info.reserves();
+    AdapterPool::new(
+        "meteora_dlmm",
+        pool_info.pool.pool_id,
+        pool_info.token_mint(),
+        token_reserve,
+        sol_reserve,
+        pool_info,
+    )
+}
+
+#[async_trait]
+impl DexAdapter for MeteoraDlmm {
+    fn name(&self) -> &'static str {
+        "meteora_dlmm"
+    }
+
+    async fn get_pool(&self, mint: &Pubkey) -> Result<AdapterPool> {
+        Ok(adapter_pool(get_pool_info(self.nonblocking_client()?, *mint).await?))
+    }
+
+    async fn read_pool(&self, pool_id: &Pubkey, mint: &Pubkey) -> Result<AdapterPool> {
+        let pool_info = read_lb_pair(self.nonblocking_client()?, *pool_id).await?;
+        if pool_info.token_mint() != *mint {
+            return Err(anyhow!("LbPair {} doesn't pair {} with SOL", pool_id, mint));
+        }
+        Ok(adapter_pool(pool_info))
+    }
+
+    async fn get_token_price(&self, mint: &Pubkey) -> Result<f64> {
+        MeteoraDlmm::get_token_price(self, &mint.to_string()).await
+    }
+
+    async fn quote(&self, pool: &AdapterPool, amount_in: u64, direction: SwapDirection) -> Result<u64> {
+        pool.venue::<MeteoraDlmmPoolInfo>()?
+            .quote(amount_in, direction)
+            .map_err(|e| anyhow!("{}", e))
+    }
+
+    async fn build_swap_instructions(
+        &self,
+        pool: &AdapterPool,
+        amount_in: u64,
+        min_amount_out: u64,
+        direction: SwapDirection,
+    ) -> Result<Vec<Instruction>> {
+        let pool_info = pool.venue::<MeteoraDlmmPoolInfo>()?;
+        match direction {
+            SwapDirection::Buy => self.build_buy_ixn_for_amount(pool_info, amount_in, min_amount_out),
+            SwapDirection::Sell => self.build_sell_ixn_for_amount(pool_info, amount_in, min_amount_out),
+        }
+    }
+}
+
+/// Find the LbPair pairing `mint` with SOL that holds the most SOL, on either side
+pub async fn get_pool_info(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    mint: Pubkey,
+) -> Result<MeteoraDlmmPoolInfo> {
+    let program_id = Pubkey::from_str(METEORA_DLMM_PROGRAM)?;
+    let sol_mint = Pubkey::from_str(SOL_MINT)?;
+
+    let mut candidates = Vec::new();
+    for (token_offset, sol_offset) in [(TOKEN_X_MINT_OFFSET, TOKEN_Y_MINT_OFFSET), (TOKEN_Y_MINT_OFFSET, TOKEN_X_MINT_OFFSET)] {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(LB_PAIR_SIZE as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(token_offset, &mint.to_bytes())),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(sol_offset, &sol_mint.to_bytes())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: Some(false),
+        };
+        let accounts = rpc_client.get_program_accounts_with_config(&program_id, config).await?;
+        candidates.extend(accounts.into_iter().map(|(pubkey, _)| pubkey));
+    }
+
+    let mut deepest: Option<MeteoraDlmmPoolInfo> = None;
+    for pool_id in candidates {
+        let Ok(pool_info) = read_lb_pair(rpc_client.clone(), pool_id).await else { continue };
+        if deepest.as_ref().map_or(true, |best| pool_info.reserves().1 > best.reserves().1) {
+            deepest = Some(pool_info);
+        }
+    }
+    deepest.ok_or_else(|| anyhow!("No Meteora DLMM pair of {} with SOL", mint))
+}
+
+/// Read an LbPair whose address is already known, with its reserve balances
+/// and the token program owning each mint
+pub async fn read_lb_pair(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    pool_id: Pubkey,
+) -> Result<MeteoraDlmmPoolInfo> {
+    let response = rpc_client.get_account_with_commitment(&pool_id, rpc_client.commitment()).await?;
+    let account = response.value.ok_or_else(|| anyhow!("LbPair {} not found", pool_id))?;
+    let pool = MeteoraDlmmPool::decode(pool_id, &account.data).map_err(|e| anyhow!("{}", e))?;
+
+    let keys = [pool.token_x_mint, pool.token_y_mint, pool.reserve_x, pool.reserve_y];
+    let accounts = rpc_client.get_multiple_accounts(&keys).await?;
+    let [Some(mint_x), Some(mint_y), Some(reserve_x), Some(reserve_y)] = <[_; 4]>::try_from(accounts)
+        .map_err(|_| anyhow!("LbPair {} accounts came back incomplete", pool_id))?
+    else {
+        return Err(anyhow!("LbPair {} is missing a mint or reserve account", pool_id));
+    };
+    for (mint, data) in [(&pool.token_x_mint, &mint_x.data), (&pool.token_y_mint, &mint_y.data)] {
+        if let Some(decimals) = data.get(MINT_DECIMALS_OFFSET) {
+            remember_decimals(&mint.to_string(), *decimals);
+        }
+    }
+    let amount = |data: &[u8]| {
+        (data.len() >= TOKEN_ACCOUNT_AMOUNT_OFFSET + 8)
+            .then(|| read_u64(data, TOKEN_ACCOUNT_AMOUNT_OFFSET))
+            .ok_or_else(|| anyhow!("Unreadable reserve of LbPair {}", pool_id))
+    };
+    let reserve_x_amount = amount(&reserve_x.data)?;
+    let reserve_y_amount = amount(&reserve_y.data)?;
+    check_reserves(&pool_id.to_string(), reserve_x_amount, reserve_y_amount)?;
+
+    Ok(MeteoraDlmmPoolInfo {
+        pool,
+        token_x_program: mint_x.owner,
+        token_y_program: mint_y.owner,
+        reserve_x_amount,
+        reserve_y_amount,
+        slot: response.context.slot,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An LbPair with base factor 10_000, protocol share 2_000, power factor
+    /// 1, active bin 100 and a 1 bps bin step; the padding after the power
+    /// factor stays zeroed as on-chain
+    fn lb_pair_fixture() -> Vec<u8> {
+        let mut data = vec![0u8; LB_PAIR_SIZE];
+        data[8..10].copy_from_slice(&10_000u16.to_le_bytes());
+        data[32..34].copy_from_slice(&2_000u16.to_le_bytes());
+        data[34] = 1;
+        data[ACTIVE_ID_OFFSET..ACTIVE_ID_OFFSET + 4].copy_from_slice(&100i32.to_le_bytes());
+        data[BIN_STEP_OFFSET..BIN_STEP_OFFSET + 2].copy_from_slice(&1u16.to_le_bytes());
+        data[TOKEN_X_MINT_OFFSET..TOKEN_X_MINT_OFFSET + 32].copy_from_slice(&[3; 32]);
+        data[TOKEN_Y_MINT_OFFSET..TOKEN_Y_MINT_OFFSET + 32].copy_from_slice(&[4; 32]);
+        data
+    }
+
+    #[test]
+    fn power_factor_is_read_after_the_protocol_share() {
+        let pool = MeteoraDlmmPool::decode(Pubkey::new_unique(), &lb_pair_fixture()).unwrap();
+        assert_eq!(pool.base_factor, 10_000);
+        assert_eq!(pool.base_fee_power_factor, 1);
+        assert_eq!((pool.active_id, pool.bin_step), (100, 1));
+        assert_eq!(pool.token_x_mint, Pubkey::new_from_array([3; 32]));
+        // 10_000 * 1 * 10 * 10^1 / 1e9: 10 bps, not the 1 bps a zero power gives
+        assert!((pool.base_fee() - 0.001).abs() < 1e-12, "{}", pool.base_fee());
+    }
+}
@@ -1,2 +1,8 @@
 pub mod pump_swap;
 pub mod dex_registry;
+pub mod raydium_amm;
+pub mod phoenix;
+pub mod whirlpool;
+pub mod meteora_dlmm;
+pub mod swap_accounts;
+pub mod slippage;
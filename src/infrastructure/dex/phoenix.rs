@@ -0,0 +1,496 @@
+use std::str::FromStr;
+
+use anchor_client::solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use anyhow::Result;
+
+use crate::core::price::{BookTouch, Price};
+use crate::error::{ClientError, ClientResult};
+
+pub const PHOENIX_PROGRAM: &str = "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY";
+
+/// Instruction tag of Swap, an IOC order that settles straight to token accounts
+const SWAP: u8 = 0;
+/// OrderPacket variant index of ImmediateOrCancel
+const IMMEDIATE_OR_CANCEL: u8 = 2;
+/// SelfTradeBehavior::DecrementTake, the taker's own resting orders shrink instead of failing the swap
+const DECREMENT_TAKE: u8 = 2;
+
+// MarketHeader field offsets
+const MARKET_HEADER_SIZE: usize = 576;
+const BIDS_SIZE_OFFSET: usize = 16;
+const ASKS_SIZE_OFFSET: usize = 24;
+const BASE_DECIMALS_OFFSET: usize = 40;
+pub const BASE_MINT_OFFSET: usize = 48;
+const BASE_VAULT_OFFSET: usize = 80;
+const BASE_LOT_SIZE_OFFSET: usize = 112;
+const QUOTE_DECIMALS_OFFSET: usize = 120;
+pub const QUOTE_MINT_OFFSET: usize = 128;
+const QUOTE_VAULT_OFFSET: usize = 160;
+const QUOTE_LOT_SIZE_OFFSET: usize = 192;
+const TICK_SIZE_OFFSET: usize = 200;
+const RAW_BASE_UNITS_PER_BASE_UNIT_OFFSET: usize = 312;
+
+// FIFOMarket field offsets, it follows the header after 256 bytes of padding
+const TAKER_FEE_BPS_OFFSET: usize = 856;
+const BIDS_TREE_OFFSET: usize = 880;
+
+// Order trees are sokoban red-black trees: a 32 byte header (root index,
+// padding, allocator size/bump/free list) then fixed 64 byte nodes of
+// [left, right, parent, color] registers, FIFOOrderId key and FIFORestingOrder value.
+// Node indices are 1-based, 0 is the nil node.
+const TREE_HEADER_SIZE: usize = 32;
+const NODE_SIZE: usize = 64;
+const NODE_LEFT: usize = 0;
+const NODE_RIGHT: usize = 4;
+const NODE_PRICE_IN_TICKS: usize = 16;
+const NODE_BASE_LOTS: usize = 40;
+
+/// Side of a taker order, `Bid` buys base with quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Resting size at one price, summed over orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookLevel {
+    pub price_in_ticks: u64,
+    pub base_lots: u64,
+}
+
+/// A decoded Phoenix market with its book aggregated into levels
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhoenixMarket {
+    pub market: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_decimals: u32,
+    pub quote_decimals: u32,
+    /// Base atoms per base lot
+    pub base_lot_size: u64,
+    /// Quote atoms per quote lot
+    pub quote_lot_size: u64,
+    pub tick_size_in_quote_atoms_per_base_unit: u64,
+    /// Whole base tokens per base unit, 1 for most markets
+    pub raw_base_units_per_base_unit: u32,
+    pub taker_fee_bps: u64,
+    /// Best (highest) first
+    pub bids: Vec<BookLevel>,
+    /// Best (lowest) first
+    pub asks: Vec<BookLevel>,
+}
+
+/// The trader's side of a swap
+#[derive(Debug, Clone, Copy)]
+pub struct PhoenixTrader {
+    pub owner: Pubkey,
+    pub base_account: Pubkey,
+    pub quote_account: Pubkey,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+/// Walk one order tree and aggregate its orders by price.
+/// Visits at most `capacity` nodes so a corrupt tree can't loop forever.
+fn read_levels(market: &Pubkey, tree: &[u8], capacity: usize) -> ClientResult<Vec<BookLevel>> {
+    let root = read_u32(tree, 0) as usize;
+    let mut orders: Vec<BookLevel> = Vec::new();
+    let mut stack = vec![root];
+    while let Some(index) = stack.pop() {
+        if index == 0 {
+            continue;
+        }
+        if index > capacity || orders.len() >= capacity {
+            return Err(ClientError::InvalidData(format!("Phoenix market {} has a corrupt order tree", market)));
+        }
+        let node = &tree[TREE_HEADER_SIZE + (index - 1) * NODE_SIZE..][..NODE_SIZE];
+        orders.push(BookLevel {
+            price_in_ticks: read_u64(node, NODE_PRICE_IN_TICKS),
+            base_lots: read_u64(node, NODE_BASE_LOTS),
+        });
+        stack.push(read_u32(node, NODE_LEFT) as usize);
+        stack.push(read_u32(node, NODE_RIGHT) as usize);
+    }
+
+    orders.sort_by_key(|o| o.price_in_ticks);
+    let mut levels: Vec<BookLevel> = Vec::with_capacity(orders.len());
+    for order in orders {
+        match levels.last_mut() {
+            Some(level) if level.price_in_ticks == order.price_in_ticks => level.base_lots += order.base_lots,
+            _ => levels.push(order),
+        }
+    }
+    Ok(levels)
+}
+
+impl PhoenixMarket {
+    /// Decode a market account, header and both sides of the book
+    pub fn decode(market: Pubkey, data: &[u8]) -> ClientResult<Self> {
+        if data.len() < BIDS_TREE_OFFSET {
+            return Err(ClientError::InvalidData(format!(
+                "Phoenix market {} is {} bytes, expected at least {}",
+                market,
+                data.len(),
+                BIDS_TREE_OFFSET
+            )));
+        }
+        let bids_size = read_u64(data, BIDS_SIZE_OFFSET) as usize;
+        let asks_size = read_u64(data, ASKS_SIZE_OFFSET) as usize;
+        let bids_len = TREE_HEADER_SIZE + bids_size * NODE_SIZE;
+        let asks_offset = BIDS_TREE_OFFSET + bids_len;
+        let asks_len = TREE_HEADER_SIZE + asks_size * NODE_SIZE;
+        if data.len() < asks_offset + asks_len {
+            return Err(ClientError::InvalidData(format!(
+                "Phoenix market {} is {} bytes, too short for {} bids and {} asks",
+                market,
+                data.len(),
+                bids_size,
+                asks_size
+            )));
+        }
+
+        let mut bids = read_levels(&market, &data[BIDS_TREE_OFFSET..][..bids_len], bids_size)?;
+        bids.reverse();
+        let asks = read_levels(&market, &data[asks_offset..][..asks_len], asks_size)?;
+
+        Ok(Self {
+            market,
+            base_mint: read_pubkey(data, BASE_MINT_OFFSET),
+            quote_mint: read_pubkey(data, QUOTE_MINT_OFFSET),
+            base_vault: read_pubkey(data, BASE_VAULT_OFFSET),
+            quote_vault: read_pubkey(data, QUOTE_VAULT_OFFSET),
+            base_decimals: read_u32(data, BASE_DECIMALS_OFFSET),
+            quote_decimals: read_u32(data, QUOTE_DECIMALS_OFFSET),
+            base_lot_size: read_u64(data, BASE_LOT_SIZE_OFFSET),
+            quote_lot_size: read_u64(data, QUOTE_LOT_SIZE_OFFSET),
+            tick_size_in_quote_atoms_per_base_unit: read_u64(data, TICK_SIZE_OFFSET),
+            raw_base_units_per_base_unit: read_u32(data, RAW_BASE_UNITS_PER_BASE_UNIT_OFFSET).max(1),
+            taker_fee_bps: read_u64(data, TAKER_FEE_BPS_OFFSET),
+            bids,
+            asks,
+        })
+    }
+
+    /// Quote atoms per base atom at `price_in_ticks`
+    fn atoms_price(&self, price_in_ticks: u64) -> f64 {
+        let base_atoms_per_unit = self.raw_base_units_per_base_unit as f64 * 10f64.powi(self.base_decimals as i32);
+        price_in_ticks as f64 * self.tick_size_in_quote_atoms_per_base_unit as f64 / base_atoms_per_unit
+    }
+
+    /// Whole quote tokens per whole base token, the unit pool prices use
+    pub fn price(&self, price_in_ticks: u64) -> Option<Price> {
+        let scale = 10f64.powi(self.base_decimals as i32 - self.quote_decimals as i32);
+        Price::new(self.atoms_price(price_in_ticks) * scale)
+    }
+
+    /// Best bid and ask, None when either side is empty or the book is crossed
+    pub fn touch(&self) -> Option<BookTouch> {
+        let bid = self.price(self.bids.first()?.price_in_ticks)?;
+        let ask = self.price(self.asks.first()?.price_in_ticks)?;
+        BookTouch::new(bid, ask)
+    }
+
+    /// Quote atoms resting on `side` within `bps` of that side's best price,
+    /// what a taker can fill there before the price walks further away
+    pub fn depth_within(&self, side: Side, bps: u64) -> u64 {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        let Some(best) = levels.first() else { return 0 };
+        let band = best.price_in_ticks as u128 * bps as u128 / 10_000;
+        levels
+            .iter()
+            .take_while(|level| (level.price_in_ticks as i128 - best.price_in_ticks as i128).unsigned_abs() <= band)
+            .map(|level| {
+                let base_atoms = level.base_lots.saturating_mul(self.base_lot_size);
+                (base_atoms as f64 * self.atoms_price(level.price_in_ticks)) as u64
+            })
+            .sum()
+    }
+
+    /// Depth a round trip can use: the thinner of the two sides
+    pub fn liquidity_within(&self, bps: u64) -> u64 {
+        self.depth_within(Side::Bid, bps).min(self.depth_within(Side::Ask, bps))
+    }
+}
+
+/// Build an IOC taker swap. A `Bid` spends `amount_in` quote atoms for at
+/// least `min_out` base atoms, an `Ask` spends base atoms for quote. Amounts
+/// are rounded down to whole lots and the minimum fill up, so the program
+/// never fills worse than `min_out`.
+pub fn build_ioc_swap_instruction(
+    market: &PhoenixMarket,
+    trader: PhoenixTrader,
+    side: Side,
+    amount_in: u64,
+    min_out: u64,
+) -> Result<Instruction> {
+    let program_id = Pubkey::from_str(PHOENIX_PROGRAM)?;
+    let (log_authority, _) = Pubkey::find_program_address(&[b"log"], &program_id);
+    if market.base_lot_size == 0 || market.quote_lot_size == 0 {
+        return Err(ClientError::InvalidData(format!("Phoenix market {} has a zero lot size", market.market)).into());
+    }
+
+    let (side_tag, num_base_lots, num_quote_lots, min_base_lots, min_quote_lots) = match side {
+        Side::Bid => (0u8, 0, amount_in / market.quote_lot_size, min_out.div_ceil(market.base_lot_size), 0),
+        Side::Ask => (1u8, amount_in / market.base_lot_size, 0, 0, min_out.div_ceil(market.quote_lot_size)),
+    };
+    if num_base_lots == 0 && num_quote_lots == 0 {
+        let lot = if side == Side::Bid { market.quote_lot_size } else { market.base_lot_size };
+        return Err(ClientError::TooSmall(amount_in, lot).into());
+    }
+
+    let mut data = Vec::with_capacity(72);
+    data.push(SWAP);
+    data.push(IMMEDIATE_OR_CANCEL);
+    data.push(side_tag);
+    data.push(0); // price_in_ticks: None, take whatever the book offers down to the minimum fill
+    data.extend_from_slice(&num_base_lots.to_le_bytes());
+    data.extend_from_slice(&num_quote_lots.to_le_bytes());
+    data.extend_from_slice(&min_base_lots.to_le_bytes());
+    data.extend_from_slice(&min_quote_lots.to_le_bytes());
+    data.push(DECREMENT_TAKE);
+    data.push(0); // match_limit: None
+    data.extend_from_slice(&0u128.to_le_bytes()); // client_order_id
+    data.push(0); // use_only_deposited_funds: false
+    data.push(0); // last_valid_slot: None
+    data.push(0); // last_valid_unix_timestamp_in_seconds: None
+
+    let accounts = vec![
+        AccountMeta::new_readonly(program_id, false),
+        AccountMeta::new_readonly(log_authority, false),
+        AccountMeta::new(market.market, false),
+        AccountMeta::new_readonly(trader.owner, true),
+        AccountMeta::new(trader.base_account, false),
+        AccountMeta::new(trader.quote_account, false),
+        AccountMeta::new(market.base_vault, false),
+        AccountMeta::new(market.quote_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id,
+        accounts,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_LOT: u64 = 10;
+
+    fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A tree of `(left, right, price_in_ticks, base_lots)` nodes rooted at node 1
+    fn tree(nodes: &[(u32, u32, u64, u64)]) -> Vec<u8> {
+        let mut data = vec![0u8; TREE_HEADER_SIZE + nodes.len() * NODE_SIZE];
+        put_u32(&mut data, 0, if nodes.is_empty() { 0 } else { 1 });
+        for (i, (left, right, price, lots)) in nodes.iter().enumerate() {
+            let node = TREE_HEADER_SIZE + i * NODE_SIZE;
+            put_u32(&mut data, node + NODE_LEFT, *left);
+            put_u32(&mut data, node + NODE_RIGHT, *right);
+            put_u64(&mut data, node + NODE_PRICE_IN_TICKS, *price);
+            put_u64(&mut data, node + NODE_BASE_LOTS, *lots);
+        }
+        data
+    }
+
+    /// Base of 3 decimals against SOL, with a tick of one lamport per base atom
+    fn market_account(
+        base_mint: Pubkey,
+        quote_mint: Pubkey,
+        bids: &[(u32, u32, u64, u64)],
+        asks: &[(u32, u32, u64, u64)],
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; BIDS_TREE_OFFSET];
+        put_u64(&mut data, BIDS_SIZE_OFFSET, bids.len() as u64);
+        put_u64(&mut data, ASKS_SIZE_OFFSET, asks.len() as u64);
+        put_u32(&mut data, BASE_DECIMALS_OFFSET, 3);
+        data[BASE_MINT_OFFSET..BASE_MINT_OFFSET + 32].copy_from_slice(base_mint.as_ref());
+        data[BASE_VAULT_OFFSET..BASE_VAULT_OFFSET + 32].copy_from_slice(Pubkey::new_from_array([7; 32]).as_ref());
+        put_u64(&mut data, BASE_LOT_SIZE_OFFSET, BASE_LOT);
+        put_u32(&mut data, QUOTE_DECIMALS_OFFSET, 9);
+        data[QUOTE_MINT_OFFSET..QUOTE_MINT_OFFSET + 32].copy_from_slice(quote_mint.as_ref());
+        data[QUOTE_VAULT_OFFSET..QUOTE_VAULT_OFFSET + 32].copy_from_slice(Pubkey::new_from_array([8; 32]).as_ref());
+        put_u64(&mut data, QUOTE_LOT_SIZE_OFFSET, 1);
+        put_u64(&mut data, TICK_SIZE_OFFSET, 1_000);
+        put_u32(&mut data, RAW_BASE_UNITS_PER_BASE_UNIT_OFFSET, 1);
+        put_u64(&mut data, TAKER_FEE_BPS_OFFSET, 5);
+        data.extend(tree(bids));
+        data.extend(tree(asks));
+        data
+    }
+
+    fn fixture() -> (Pubkey, Pubkey, Pubkey, Vec<u8>) {
+        let market = Pubkey::new_unique();
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        // Two bids at 995 add up to one level, 990 sits just outside 50 bps of it
+        let bids = [(2, 3, 995, 3), (0, 0, 990, 5), (0, 0, 995, 2)];
+        let asks = [(0, 2, 1_005, 4), (0, 0, 1_100, 10)];
+        (market, base_mint, quote_mint, market_account(base_mint, quote_mint, &bids, &asks))
+    }
+
+    #[test]
+    fn decodes_header_and_aggregates_levels() {
+        let (market_id, base_mint, quote_mint, data) = fixture();
+        let market = PhoenixMarket::decode(market_id, &data).unwrap();
+        assert_eq!(market.market, market_id);
+        assert_eq!(market.base_mint, base_mint);
+        assert_eq!(market.quote_mint, quote_mint);
+        assert_eq!(market.base_vault, Pubkey::new_from_array([7; 32]));
+        assert_eq!(market.quote_vault, Pubkey::new_from_array([8; 32]));
+        assert_eq!((market.base_decimals, market.quote_decimals), (3, 9));
+        assert_eq!((market.base_lot_size, market.quote_lot_size), (BASE_LOT, 1));
+        assert_eq!(market.taker_fee_bps, 5);
+        assert_eq!(
+            market.bids,
+            vec![
+                BookLevel { price_in_ticks: 995, base_lots: 5 },
+                BookLevel { price_in_ticks: 990, base_lots: 5 },
+            ]
+        );
+        assert_eq!(
+            market.asks,
+            vec![
+                BookLevel { price_in_ticks: 1_005, base_lots: 4 },
+                BookLevel { price_in_ticks: 1_100, base_lots: 10 },
+            ]
+        );
+    }
+
+    #[test]
+    fn touch_is_the_executable_side_in_whole_tokens() {
+        let (market_id, _, _, data) = fixture();
+        let market = PhoenixMarket::decode(market_id, &data).unwrap();
+        let touch = market.touch().unwrap();
+        // One lamport per base atom is 1e-6 SOL per token of 3 decimals
+        assert!((touch.bid.value() - 995e-6).abs() < 1e-15);
+        assert!((touch.ask.value() - 1_005e-6).abs() < 1e-15);
+        assert!((touch.mid().value() - 1_000e-6).abs() < 1e-15);
+    }
+
+    #[test]
+    fn depth_counts_levels_within_the_band() {
+        let (market_id, _, _, data) = fixture();
+        let market = PhoenixMarket::decode(market_id, &data).unwrap();
+        assert_eq!(market.depth_within(Side::Bid, 50), 5 * BASE_LOT * 995);
+        assert_eq!(market.depth_within(Side::Bid, 100), 5 * BASE_LOT * 995 + 5 * BASE_LOT * 990);
+        assert_eq!(market.depth_within(Side::Ask, 50), 4 * BASE_LOT * 1_005);
+        assert_eq!(market.liquidity_within(50), 4 * BASE_LOT * 1_005);
+    }
+
+    #[test]
+    fn one_sided_or_corrupt_books() {
+        let market_id = Pubkey::new_unique();
+        let data = market_account(Pubkey::new_unique(), Pubkey::new_unique(), &[(0, 0, 995, 1)], &[]);
+        let market = PhoenixMarket::decode(market_id, &data).unwrap();
+        assert!(market.asks.is_empty());
+        assert_eq!(market.touch(), None);
+        assert_eq!(market.liquidity_within(50), 0);
+
+        // A child index past the tree's capacity
+        let data = market_account(Pubkey::new_unique(), Pubkey::new_unique(), &[(9, 0, 995, 1)], &[]);
+        assert!(PhoenixMarket::decode(market_id, &data).is_err());
+        // A cycle back to the root
+        let data = market_account(Pubkey::new_unique(), Pubkey::new_unique(), &[(1, 0, 995, 1)], &[]);
+        assert!(PhoenixMarket::decode(market_id, &data).is_err());
+
+        let (_, _, _, data) = fixture();
+        assert!(PhoenixMarket::decode(market_id, &data[..data.len() - 1]).is_err());
+        assert!(PhoenixMarket::decode(market_id, &data[..BIDS_TREE_OFFSET - 1]).is_err());
+    }
+
+    fn trader() -> PhoenixTrader {
+        PhoenixTrader {
+            owner: Pubkey::new_unique(),
+            base_account: Pubkey::new_unique(),
+            quote_account: Pubkey::new_unique(),
+        }
+    }
+
+    fn expected_data(side: u8, base_lots: u64, quote_lots: u64, min_base_lots: u64, min_quote_lots: u64) -> Vec<u8> {
+        let mut data = vec![SWAP, IMMEDIATE_OR_CANCEL, side, 0];
+        for value in [base_lots, quote_lots, min_base_lots, min_quote_lots] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[DECREMENT_TAKE, 0]);
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&[0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn ioc_bid_spends_quote_lots_for_a_minimum_of_base_lots() {
+        let (market_id, _, _, data) = fixture();
+        let market = PhoenixMarket::decode(market_id, &data).unwrap();
+        let trader = trader();
+        // 995 base atoms rounds up to 100 lots, so the fill is never short
+        let ix = build_ioc_swap_instruction(&market, trader, Side::Bid, 1_000_000, 995).unwrap();
+        assert_eq!(ix.program_id, Pubkey::from_str(PHOENIX_PROGRAM).unwrap());
+        assert_eq!(ix.data, expected_data(0, 0, 1_000_000, 100, 0));
+        assert_eq!(ix.data.len(), 57);
+
+        let (log_authority, _) = Pubkey::find_program_address(&[b"log"], &ix.program_id);
+        let keys: Vec<Pubkey> = ix.accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(
+            keys,
+            vec![
+                ix.program_id,
+                log_authority,
+                market_id,
+                trader.owner,
+                trader.base_account,
+                trader.quote_account,
+                market.base_vault,
+                market.quote_vault,
+                spl_token::id(),
+            ]
+        );
+        let signers: Vec<usize> = ix.accounts.iter().enumerate().filter(|(_, a)| a.is_signer).map(|(i, _)| i).collect();
+        assert_eq!(signers, vec![3]);
+        assert!(ix.accounts[2].is_writable && ix.accounts[6].is_writable && ix.accounts[7].is_writable);
+    }
+
+    #[test]
+    fn ioc_ask_rounds_base_down_to_whole_lots() {
+        let (market_id, _, _, data) = fixture();
+        let market = PhoenixMarket::decode(market_id, &data).unwrap();
+        let ix = build_ioc_swap_instruction(&market, trader(), Side::Ask, 55, 4_000).unwrap();
+        assert_eq!(ix.data, expected_data(1, 5, 0, 0, 4_000));
+
+        let err = build_ioc_swap_instruction(&market, trader(), Side::Ask, BASE_LOT - 1, 1).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ClientError>(), Some(ClientError::TooSmall(9, 10))));
+    }
+}
@@ -1,8 +1,9 @@
 use std::{str::FromStr, sync::Arc, time::Duration};
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use borsh::BorshDeserialize;
 use colored::Colorize;
 use std::cmp;
-use std::env;
 
 use anchor_client::solana_sdk::{
     instruction::{AccountMeta, Instruction},
@@ -11,6 +12,11 @@ use anchor_client::solana_sdk::{
     signer::Signer,
     system_program,
 };
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
 use spl_associated_token_account::{
     get_associated_token_address, instruction::create_associated_token_account_idempotent,
 };
@@ -20,8 +26,17 @@ use tokio::time::{Instant, sleep};
 
 use crate::{
     common::{config::SwapConfig, logger::Logger},
-    core::token,
+    core::{
+        dex_adapter::{DexAdapter, PoolInfo as AdapterPool},
+        price::{check_reserves, PoolDataUnavailable, Price, ReserveProblem},
+        token,
+    },
+    dex::{
+        dex_registry::fee_override,
+        slippage::{get_expire_condition, max_amount_with_slippage, min_amount_with_slippage},
+    },
     engine::swap::{SwapDirection, SwapInType},
+    error::{ClientError, ClientResult},
 };
 
 // PumpSwap Constants
@@ -36,6 +51,120 @@ pub const PUMP_EVENT_AUTHORITY: &str = "GS4CU59F31iL7aR2Q8zVS8DRrcRnXX1yjQ66TqNV
 pub const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
 pub const SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
 pub const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+/// LP plus protocol fee PumpSwap takes off every swap input
+pub const PUMP_SWAP_FEE_BPS: u64 = 25;
+// Pool field offsets, after the discriminator, bump, index and creator
+pub const POOL_BASE_MINT_OFFSET: usize = 43;
+pub const POOL_QUOTE_MINT_OFFSET: usize = 75;
+/// Buys below ~0.001 SOL round to zero tokens once pool fees are taken
+pub const MIN_TRADE_IN_LAMPORTS: u64 = 1_000_000;
+/// Anchor event discriminators, sha256("event:BuyEvent") / ("event:SellEvent")
+pub const BUY_EVENT_DISCRIMINATOR: [u8; 8] = [103, 244, 82, 31, 44, 245, 119, 119];
+pub const SELL_EVENT_DISCRIMINATOR: [u8; 8] = [62, 47, 55, 10, 165, 3, 220, 42];
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// PumpSwap's `BuyEvent` up to the fee recipient accounts; fields later
+/// program versions append (coin creator fees) are left unread
+#[derive(Debug, Clone, BorshDeserialize)]
+pub struct BuyEvent {
+    pub timestamp: i64,
+    pub base_amount_out: u64,
+    pub max_quote_amount_in: u64,
+    pub user_base_token_reserves: u64,
+    pub user_quote_token_reserves: u64,
+    pub pool_base_token_reserves: u64,
+    pub pool_quote_token_reserves: u64,
+    pub quote_amount_in: u64,
+    pub lp_fee_basis_points: u64,
+    pub lp_fee: u64,
+    pub protocol_fee_basis_points: u64,
+    pub protocol_fee: u64,
+    pub quote_amount_in_with_lp_fee: u64,
+    pub user_quote_amount_in: u64,
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+    pub protocol_fee_recipient: Pubkey,
+    pub protocol_fee_recipient_token_account: Pubkey,
+}
+
+/// PumpSwap's `SellEvent`, read to the same point as [`BuyEvent`]
+#[derive(Debug, Clone, BorshDeserialize)]
+pub struct SellEvent {
+    pub timestamp: i64,
+    pub base_amount_in: u64,
+    pub min_quote_amount_out: u64,
+    pub user_base_token_reserves: u64,
+    pub user_quote_token_reserves: u64,
+    pub pool_base_token_reserves: u64,
+    pub pool_quote_token_reserves: u64,
+    pub quote_amount_out: u64,
+    pub lp_fee_basis_points: u64,
+    pub lp_fee: u64,
+    pub protocol_fee_basis_points: u64,
+    pub protocol_fee: u64,
+    pub quote_amount_out_without_lp_fee: u64,
+    pub user_quote_amount_out: u64,
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+    pub protocol_fee_recipient: Pubkey,
+    pub protocol_fee_recipient_token_account: Pubkey,
+}
+
+/// A swap event PumpSwap emitted through `Program data:`
+#[derive(Debug, Clone)]
+pub enum PumpSwapEvent {
+    Buy(BuyEvent),
+    Sell(SellEvent),
+}
+
+impl PumpSwapEvent {
+    /// Decode one log line, None unless it is a PumpSwap buy or sell event
+    pub fn from_log(log: &str) -> Option<Self> {
+        let encoded = log.strip_prefix(PROGRAM_DATA_PREFIX)?;
+        let data = base64::decode(encoded.trim()).ok()?;
+        let (discriminator, mut body) = (data.get(..8)?, data.get(8..)?);
+        // Newer program versions append fields, so trailing bytes are fine
+        if discriminator == BUY_EVENT_DISCRIMINATOR {
+            BuyEvent::deserialize(&mut body).ok().map(Self::Buy)
+        } else if discriminator == SELL_EVENT_DISCRIMINATOR {
+            SellEvent::deserialize(&mut body).ok().map(Self::Sell)
+        } else {
+            None
+        }
+    }
+
+    /// First buy or sell event among a transaction's logs
+    pub fn find(log_messages: &[String]) -> Option<Self> {
+        log_messages.iter().find_map(|log| Self::from_log(log))
+    }
+
+    /// Wallet that traded, which need not be the fee payer
+    pub fn user(&self) -> Pubkey {
+        match self {
+            Self::Buy(event) => event.user,
+            Self::Sell(event) => event.user,
+        }
+    }
+
+    pub fn pool(&self) -> Pubkey {
+        match self {
+            Self::Buy(event) => event.pool,
+            Self::Sell(event) => event.pool,
+        }
+    }
+
+    /// Pool base and quote reserves as the event reported them
+    pub fn pool_reserves(&self) -> (u64, u64) {
+        match self {
+            Self::Buy(event) => (event.pool_base_token_reserves, event.pool_quote_token_reserves),
+            Self::Sell(event) => (event.pool_base_token_reserves, event.pool_quote_token_reserves),
+        }
+    }
+}
 
 /// A struct to represent the PumpSwap pool which uses constant product AMM
 #[derive(Debug, Clone)]
@@ -77,7 +206,7 @@ impl PumpSwap {
         start_time: Instant,
     ) -> Result<(Arc<Keypair>, Vec<Instruction>, f64)> {
         let logger = Logger::new("[PUMPSWAP-SWAP-BY-MINT] => ".blue().to_string());
-        let slippage_bps = swap_config.slippage * 100;
+        let slippage_bps = swap_config.slippage_bps;
         let owner = self.keypair.pubkey();
         let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
         let sol_mint = Pubkey::from_str(SOL_MINT)?;
@@ -232,13 +361,14 @@ impl PumpSwap {
         };
 
         // Calculate token price from reserves
-        let token_price: f64 = (quote_reserve as f64) / (base_reserve as f64);
+        let token_price = Price::from_raw_reserves(&pool_info.pool_id.to_string(), base_reserve, quote_reserve)?.value();
 
         // Prepare swap instruction parameters based on direction
         let (base_amount, quote_amount, accounts) = match swap_config.swap_direction {
             SwapDirection::Buy => {
                 // For buy: base_amount_out and max_quote_amount_in
-                let base_amount_out = calculate_buy_base_amount(amount_specified, quote_reserve, base_reserve);
+                let base_amount_out = calculate_buy_base_amount(amount_specified, quote_reserve, base_reserve, PUMP_SWAP_FEE_BPS)
+                    .map_err(|e| anyhow!("{}", e))?;
                 let max_quote_amount_in = max_amount_with_slippage(amount_specified, slippage_bps);
                 
                 // Check if buy amount exceeds pool reserves
@@ -265,7 +395,8 @@ impl PumpSwap {
             SwapDirection::Sell => {
                 // For sell: base_amount_in and min_quote_amount_out
                 let base_amount_in = amount_specified;
-                let quote_amount_out = calculate_sell_quote_amount(base_amount_in, base_reserve, quote_reserve);
+                let quote_amount_out = calculate_sell_quote_amount(base_amount_in, base_reserve, quote_reserve, PUMP_SWAP_FEE_BPS)
+                    .map_err(|e| anyhow!("{}", e))?;
                 let min_quote_amount_out = min_amount_with_slippage(quote_amount_out, slippage_bps);
                 
                 // Create sell accounts vector
@@ -329,6 +460,67 @@ impl PumpSwap {
         Ok((self.keypair.clone(), instructions, token_price))
     }
 
+    /// Buy exactly `base_amount_out` tokens from `pool` for at most
+    /// `max_quote_amount_in` wrapped SOL, creating the token account if needed
+    pub fn build_buy_ixn_for_amount(
+        &self,
+        pool: &PumpSwapPool,
+        base_amount_out: u64,
+        max_quote_amount_in: u64,
+    ) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let token_program = Pubkey::from_str(TOKEN_PROGRAM)?;
+        let accounts = create_buy_accounts(
+            pool.pool_id,
+            owner,
+            pool.base_mint,
+            pool.quote_mint,
+            get_associated_token_address(&owner, &pool.base_mint),
+            get_associated_token_address(&owner, &pool.quote_mint),
+            pool.pool_base_account,
+            pool.pool_quote_account,
+        )?;
+        Ok(vec![
+            create_associated_token_account_idempotent(&owner, &owner, &pool.base_mint, &token_program),
+            create_swap_instruction(
+                Pubkey::from_str(PUMP_PROGRAM)?,
+                BUY_DISCRIMINATOR,
+                base_amount_out,
+                max_quote_amount_in,
+                accounts,
+            ),
+        ])
+    }
+
+    /// Sell exactly `base_amount_in` tokens into `pool` for at least
+    /// `min_quote_amount_out` lamports, without looking at the wallet. Used
+    /// when an earlier instruction in the same transaction provides the tokens.
+    pub fn build_sell_ixn_for_amount(
+        &self,
+        pool: &PumpSwapPool,
+        base_amount_in: u64,
+        min_quote_amount_out: u64,
+    ) -> Result<Instruction> {
+        let owner = self.keypair.pubkey();
+        let accounts = create_sell_accounts(
+            pool.pool_id,
+            owner,
+            pool.base_mint,
+            pool.quote_mint,
+            get_associated_token_address(&owner, &pool.base_mint),
+            get_associated_token_address(&owner, &pool.quote_mint),
+            pool.pool_base_account,
+            pool.pool_quote_account,
+        )?;
+        Ok(create_swap_instruction(
+            Pubkey::from_str(PUMP_PROGRAM)?,
+            SELL_DISCRIMINATOR,
+            base_amount_in,
+            min_quote_amount_out,
+            accounts,
+        ))
+    }
+
     pub async fn get_token_price(&self, mint_str: &str) -> Result<f64> {
         let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
         
@@ -336,9 +528,68 @@ impl PumpSwap {
         let pool_info = get_pool_info(self.rpc_client.clone().unwrap(), mint).await?;
         
         // Calculate price from reserves (quote/base)
-        let price = pool_info.quote_reserve as f64 / pool_info.base_reserve as f64;
+        let price = Price::from_raw_reserves(
+            &pool_info.pool_id.to_string(),
+            pool_info.base_reserve,
+            pool_info.quote_reserve,
+        )?;
         
-        Ok(price)
+        Ok(price.value())
+    }
+}
+
+/// Effective PumpSwap fee, `DEX_FEES` overriding the default
+fn fee_bps() -> u64 {
+    fee_override("pumpswap").unwrap_or(PUMP_SWAP_FEE_BPS)
+}
+
+fn adapter_pool(pool: PumpSwapPool) -> AdapterPool {
+    AdapterPool::new("pumpswap", pool.pool_id, pool.base_mint, pool.base_reserve, pool.quote_reserve, pool)
+}
+
+#[async_trait]
+impl DexAdapter for PumpSwap {
+    fn name(&self) -> &'static str {
+        "pumpswap"
+    }
+
+    async fn get_pool(&self, mint: &Pubkey) -> Result<AdapterPool> {
+        let rpc_client = self.rpc_client.clone().ok_or_else(|| anyhow!("RPC client not initialized"))?;
+        Ok(adapter_pool(get_pool_info(rpc_client, *mint).await?))
+    }
+
+    async fn read_pool(&self, pool_id: &Pubkey, mint: &Pubkey) -> Result<AdapterPool> {
+        let rpc_client = self.rpc_client.clone().ok_or_else(|| anyhow!("RPC client not initialized"))?;
+        Ok(adapter_pool(read_pool(rpc_client, *pool_id, *mint).await?))
+    }
+
+    async fn get_token_price(&self, mint: &Pubkey) -> Result<f64> {
+        PumpSwap::get_token_price(self, &mint.to_string()).await
+    }
+
+    async fn quote(&self, pool: &AdapterPool, amount_in: u64, direction: SwapDirection) -> Result<u64> {
+        let pool = pool.venue::<PumpSwapPool>()?;
+        let out = match direction {
+            SwapDirection::Buy => calculate_buy_base_amount(amount_in, pool.quote_reserve, pool.base_reserve, fee_bps()),
+            SwapDirection::Sell => calculate_sell_quote_amount(amount_in, pool.base_reserve, pool.quote_reserve, fee_bps()),
+        };
+        out.map_err(|e| anyhow!("{}", e))
+    }
+
+    /// Buys are exact-out on PumpSwap: `min_amount_out` tokens for at most
+    /// `amount_in` of the wallet's wrapped SOL
+    async fn build_swap_instructions(
+        &self,
+        pool: &AdapterPool,
+        amount_in: u64,
+        min_amount_out: u64,
+        direction: SwapDirection,
+    ) -> Result<Vec<Instruction>> {
+        let pool = pool.venue::<PumpSwapPool>()?;
+        match direction {
+            SwapDirection::Buy => self.build_buy_ixn_for_amount(pool, min_amount_out, amount_in),
+            SwapDirection::Sell => Ok(vec![self.build_sell_ixn_for_amount(pool, amount_in, min_amount_out)?]),
+        }
     }
 }
 
@@ -348,20 +599,66 @@ async fn get_pool_info(
     mint: Pubkey,
 ) -> Result<PumpSwapPool> {
     let sol_mint = Pubkey::from_str(SOL_MINT)?;
+    let pool_id = find_pool_by_mints(rpc_client.clone(), mint, sol_mint).await?;
+    read_pool(rpc_client, pool_id, mint).await
+}
+
+/// Find the pool pairing `base_mint` with `quote_mint`. The pool PDA is
+/// seeded with its creator, which isn't known up front, so pools are looked
+/// up by their mints instead; with several, the one holding the most quote wins.
+pub async fn find_pool_by_mints(
+    rpc_client: Arc<anchor_client::solana_client::rpc_client::RpcClient>,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+) -> Result<Pubkey> {
+    let logger = Logger::new("[PUMPSWAP-FIND-POOL] => ".blue().to_string());
     let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
-    
-    // Find the pool address for the base mint and quote mint (SOL)
-    let (pool_id, _) = Pubkey::find_program_address(
-        &[
-            b"pool",
-            &[0u8, 0u8], // index as u16 (little-endian)
-            &Pubkey::default().to_bytes(), // creator (we don't know this, using default)
-            &mint.to_bytes(),
-            &sol_mint.to_bytes(),
-        ],
-        &pump_program,
-    );
-    
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(POOL_BASE_MINT_OFFSET, &base_mint.to_bytes())),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(POOL_QUOTE_MINT_OFFSET, &quote_mint.to_bytes())),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig { offset: 0, length: 0 }),
+            ..RpcAccountInfoConfig::default()
+        },
+        with_context: Some(false),
+    };
+    let candidates: Vec<Pubkey> = rpc_client
+        .get_program_accounts_with_config(&pump_program, config)?
+        .into_iter()
+        .map(|(pubkey, _)| pubkey)
+        .collect();
+
+    let deepest = match candidates.as_slice() {
+        [] => None,
+        [only] => Some(*only),
+        _ => candidates
+            .iter()
+            .filter_map(|pool_id| {
+                let quote_account = get_associated_token_address(pool_id, &quote_mint);
+                let balance = rpc_client.get_token_account_balance(&quote_account).ok()?;
+                Some((*pool_id, balance.amount.parse::<u64>().ok()?))
+            })
+            .max_by_key(|(_, quote_reserve)| *quote_reserve)
+            .map(|(pool_id, _)| pool_id),
+    };
+    deepest.ok_or_else(|| {
+        logger.log(format!("No PumpSwap pool pairs {} with {}", base_mint, quote_mint).red().to_string());
+        anyhow!("No PumpSwap pool pairs {} with {}", base_mint, quote_mint)
+    })
+}
+
+/// Read the reserves of a PumpSwap pool whose address is already known
+pub async fn read_pool(
+    rpc_client: Arc<anchor_client::solana_client::rpc_client::RpcClient>,
+    pool_id: Pubkey,
+    mint: Pubkey,
+) -> Result<PumpSwapPool> {
+    let sol_mint = Pubkey::from_str(SOL_MINT)?;
+    let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+
     // Find the LP mint address
     let (lp_mint, _) = Pubkey::find_program_address(
         &[
@@ -375,26 +672,17 @@ async fn get_pool_info(
     let pool_base_account = get_associated_token_address(&pool_id, &mint);
     let pool_quote_account = get_associated_token_address(&pool_id, &sol_mint);
     
-    // Get token balances (reserves)
-    let base_balance = match rpc_client.get_token_account_balance(&pool_base_account) {
-        Ok(balance) => {
-            match balance.ui_amount {
-                Some(amount) => (amount * (10f64.powf(balance.decimals as f64))) as u64,
-                None => 0,
-            }
-        },
-        Err(_) => 0,
-    };
-    
-    let quote_balance = match rpc_client.get_token_account_balance(&pool_quote_account) {
-        Ok(balance) => {
-            match balance.ui_amount {
-                Some(amount) => (amount * (10f64.powf(balance.decimals as f64))) as u64,
-                None => 0,
-            }
-        },
-        Err(_) => 0,
+    // Get token balances (reserves). A failed read is an error, never a zero reserve
+    let read_reserve = |account: &Pubkey| {
+        rpc_client
+            .get_token_account_balance(account)
+            .ok()
+            .and_then(|balance| balance.amount.parse::<u64>().ok())
+            .ok_or_else(|| PoolDataUnavailable::new(pool_id, ReserveProblem::Missing))
     };
+    let base_balance = read_reserve(&pool_base_account)?;
+    let quote_balance = read_reserve(&pool_quote_account)?;
+    check_reserves(&pool_id.to_string(), base_balance, quote_balance)?;
     
     // Return the pool info
     Ok(PumpSwapPool {
@@ -409,72 +697,73 @@ async fn get_pool_info(
     })
 }
 
-/// Calculate the amount of base tokens received for a given quote amount in buy operation
-fn calculate_buy_base_amount(quote_amount_in: u64, quote_reserve: u64, base_reserve: u64) -> u64 {
-    // For buys in constant product AMM:
-    // quote_reserve * base_reserve = (quote_reserve + quote_amount_in) * (base_reserve - base_amount_out)
+/// Calculate the amount of base tokens received for a given quote amount in buy operation,
+/// after `fee_bps` is taken off the input
+///
+/// Returns `ClientError::TooSmall` when the input is below the venue minimum or the
+/// output would round down to zero tokens.
+pub fn calculate_buy_base_amount(quote_amount_in: u64, quote_reserve: u64, base_reserve: u64, fee_bps: u64) -> ClientResult<u64> {
+    // For buys in constant product AMM, with the fee kept out of the pool:
+    // quote_reserve * base_reserve = (quote_reserve + effective_in) * (base_reserve - base_amount_out)
     // Solving for base_amount_out:
-    // base_amount_out = base_reserve - (quote_reserve * base_reserve) / (quote_reserve + quote_amount_in)
+    // base_amount_out = base_reserve - (quote_reserve * base_reserve) / (quote_reserve + effective_in)
     
-    if quote_amount_in == 0 || base_reserve == 0 || quote_reserve == 0 {
-        return 0;
+    if quote_amount_in < MIN_TRADE_IN_LAMPORTS {
+        return Err(ClientError::TooSmall(quote_amount_in, MIN_TRADE_IN_LAMPORTS));
     }
     
+    if base_reserve == 0 || quote_reserve == 0 {
+        return Err(ClientError::InvalidData("PumpSwap pool has empty reserves".to_string()));
+    }
+    
+    let quote_amount_in = amount_after_fee(quote_amount_in, fee_bps);
     let quote_reserve_after = quote_reserve.checked_add(quote_amount_in).unwrap_or(quote_reserve);
     let numerator = (quote_reserve as u128).checked_mul(base_reserve as u128).unwrap_or(0);
     let denominator = quote_reserve_after as u128;
     
-    if denominator == 0 {
-        return 0;
-    }
-    
     let base_reserve_after = numerator.checked_div(denominator).unwrap_or(0);
     let base_amount_out = base_reserve.checked_sub(base_reserve_after as u64).unwrap_or(0);
     
-    base_amount_out
+    if base_amount_out == 0 {
+        return Err(ClientError::TooSmall(quote_amount_in, MIN_TRADE_IN_LAMPORTS));
+    }
+    
+    Ok(base_amount_out)
 }
 
-/// Calculate the amount of quote tokens received for a given base amount in sell operation
-fn calculate_sell_quote_amount(base_amount_in: u64, base_reserve: u64, quote_reserve: u64) -> u64 {
-    // For sells in constant product AMM:
-    // quote_reserve * base_reserve = (quote_reserve - quote_amount_out) * (base_reserve + base_amount_in)
+/// Calculate the amount of quote tokens received for a given base amount in sell operation,
+/// after `fee_bps` is taken off the input
+///
+/// Returns `ClientError::TooSmall` when the quote output is below the venue minimum.
+pub fn calculate_sell_quote_amount(base_amount_in: u64, base_reserve: u64, quote_reserve: u64, fee_bps: u64) -> ClientResult<u64> {
+    // For sells in constant product AMM, with the fee kept out of the pool:
+    // quote_reserve * base_reserve = (quote_reserve - quote_amount_out) * (base_reserve + effective_in)
     // Solving for quote_amount_out:
-    // quote_amount_out = quote_reserve - (quote_reserve * base_reserve) / (base_reserve + base_amount_in)
+    // quote_amount_out = quote_reserve - (quote_reserve * base_reserve) / (base_reserve + effective_in)
     
-    if base_amount_in == 0 || base_reserve == 0 || quote_reserve == 0 {
-        return 0;
+    if base_reserve == 0 || quote_reserve == 0 {
+        return Err(ClientError::InvalidData("PumpSwap pool has empty reserves".to_string()));
     }
     
+    let base_amount_in = amount_after_fee(base_amount_in, fee_bps);
     let base_reserve_after = base_reserve.checked_add(base_amount_in).unwrap_or(base_reserve);
     let numerator = (quote_reserve as u128).checked_mul(base_reserve as u128).unwrap_or(0);
     let denominator = base_reserve_after as u128;
     
-    if denominator == 0 {
-        return 0;
-    }
-    
     let quote_reserve_after = numerator.checked_div(denominator).unwrap_or(0);
     let quote_amount_out = quote_reserve.checked_sub(quote_reserve_after as u64).unwrap_or(0);
     
-    quote_amount_out
-}
-
-/// Calculate the minimum amount with slippage tolerance
-fn min_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    input_amount
-        .checked_mul(TEN_THOUSAND.checked_sub(slippage_bps).unwrap_or(TEN_THOUSAND))
-        .unwrap_or(input_amount)
-        .checked_div(TEN_THOUSAND)
-        .unwrap_or(input_amount)
+    if quote_amount_out < MIN_TRADE_IN_LAMPORTS {
+        return Err(ClientError::TooSmall(quote_amount_out, MIN_TRADE_IN_LAMPORTS));
+    }
+    
+    Ok(quote_amount_out)
 }
 
-/// Calculate the maximum amount with slippage tolerance
-fn max_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    input_amount
-        .checked_mul(slippage_bps.checked_add(TEN_THOUSAND).unwrap_or(TEN_THOUSAND))
-        .unwrap_or(input_amount)
-        .checked_div(TEN_THOUSAND)
-        .unwrap_or(input_amount)
+/// `amount` less a `fee_bps` fee; a zero fee leaves it untouched
+fn amount_after_fee(amount: u64, fee_bps: u64) -> u64 {
+    let keep = TEN_THOUSAND.saturating_sub(fee_bps) as u128;
+    (amount as u128 * keep / TEN_THOUSAND as u128) as u64
 }
 
 /// Create accounts for buy operation
@@ -578,10 +867,98 @@ fn create_swap_instruction(
     }
 }
 
-/// Get expiration time for transaction
-fn get_expire_condition() -> u64 {
-    env::var("EXPIRE_CONDITION")
-        .ok()
-        .and_then(|v| u64::from_str(&v).ok())
-        .unwrap_or(10000) // Default 10 seconds
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_reserve_reads_are_unavailable_not_zero() {
+        let rpc_client = Arc::new(anchor_client::solana_client::rpc_client::RpcClient::new_mock("fails".to_string()));
+        let pool_id = Pubkey::new_unique();
+        let err = read_pool(rpc_client, pool_id, Pubkey::new_unique()).await.unwrap_err();
+        let unavailable = err.downcast_ref::<PoolDataUnavailable>().expect("typed pool data error");
+        assert_eq!(*unavailable, PoolDataUnavailable::new(pool_id, ReserveProblem::Missing));
+    }
+
+    /// A buy event in the layout PumpSwap emits: 1 SOL in for 35k tokens
+    /// (6 decimals), pool 800M tokens / 40 SOL, pool `[1; 32]`, user `[2; 32]`
+    const BUY_EVENT_LOG: &str = "Program data: Z/RSHyz1d3cAeOdoAAAAAACeKSYIAAAAgGAzPAAAAAAAAAAAAAAAAADyBSoBAAAAAADSg5jXAgAAkC9QCQAAAADKmjsAAAAAFAAAAAAAAACAhB4AAAAAAAUAAAAAAAAAIKEHAAAAAACATrk7AAAAAKDvwDsAAAAAAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQECAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYG";
+    /// The matching sell of the same 35k tokens for a 0.99 SOL minimum
+    const SELL_EVENT_LOG: &str = "Program data: Pi83CqUD3CoAeOdoAAAAAACeKSYIAAAAgDMCOwAAAAAAnikmCAAAAAAAAAAAAAAAAADSg5jXAgAAkC9QCQAAAADKmjsAAAAAFAAAAAAAAACAhB4AAAAAAAUAAAAAAAAAIKEHAAAAAACARXw7AAAAAGCkdDsAAAAAAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQECAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQUFBQYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYGBgYG";
+
+    #[test]
+    fn program_data_events_decode_by_discriminator() {
+        let Some(PumpSwapEvent::Buy(buy)) = PumpSwapEvent::from_log(BUY_EVENT_LOG) else {
+            panic!("buy event not decoded");
+        };
+        assert_eq!((buy.base_amount_out, buy.max_quote_amount_in), (35_000_000_000, 1_010_000_000));
+        assert_eq!(buy.quote_amount_in, 1_000_000_000);
+        assert_eq!(buy.pool, Pubkey::new_from_array([1; 32]));
+        assert_eq!(buy.user, Pubkey::new_from_array([2; 32]));
+
+        let Some(sell @ PumpSwapEvent::Sell(_)) = PumpSwapEvent::from_log(SELL_EVENT_LOG) else {
+            panic!("sell event not decoded");
+        };
+        assert_eq!(sell.pool_reserves(), (800_000_000_000_000, 40_000_000_000));
+        let PumpSwapEvent::Sell(sell) = sell else { unreachable!() };
+        assert_eq!((sell.base_amount_in, sell.min_quote_amount_out), (35_000_000_000, 990_000_000));
+    }
+
+    #[test]
+    fn only_whole_pumpswap_events_decode() {
+        let data = base64::decode(BUY_EVENT_LOG.strip_prefix(PROGRAM_DATA_PREFIX).unwrap()).unwrap();
+        // Fields a later program version appends are skipped
+        let mut extended = data.clone();
+        extended.extend_from_slice(&[9; 40]);
+        assert!(PumpSwapEvent::from_log(&format!("{}{}", PROGRAM_DATA_PREFIX, base64::encode(&extended))).is_some());
+
+        let truncated = format!("{}{}", PROGRAM_DATA_PREFIX, base64::encode(&data[..200]));
+        assert!(PumpSwapEvent::from_log(&truncated).is_none());
+        let mut other = data;
+        other[..8].copy_from_slice(&[0; 8]);
+        assert!(PumpSwapEvent::from_log(&format!("{}{}", PROGRAM_DATA_PREFIX, base64::encode(&other))).is_none());
+        assert!(PumpSwapEvent::from_log("Program log: Instruction: Buy").is_none());
+        assert!(PumpSwapEvent::from_log("Program data: not base64!").is_none());
+
+        let logs = vec![
+            "Program pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA invoke [1]".to_string(),
+            "Program log: Instruction: Sell".to_string(),
+            SELL_EVENT_LOG.to_string(),
+            BUY_EVENT_LOG.to_string(),
+        ];
+        assert!(matches!(PumpSwapEvent::find(&logs), Some(PumpSwapEvent::Sell(_))));
+    }
+
+    #[test]
+    fn fee_comes_off_the_input_before_the_invariant() {
+        assert_eq!(amount_after_fee(1_000_000, 25), 997_500);
+        assert_eq!(amount_after_fee(1_000_000, 0), 1_000_000);
+
+        // 1 SOL into 9 SOL / 1e12 base at 25 bps: 0.9975 SOL goes in, the
+        // base side falls to 9e21 / 9_997_500_000 = 900_225_056_264
+        let base_out = calculate_buy_base_amount(1_000_000_000, 9_000_000_000, 1_000_000_000_000, 25).unwrap();
+        assert_eq!(base_out, 99_774_943_736);
+
+        // 1e11 base into 9e11 base / 10 SOL: 9.975e10 goes in, the quote
+        // side falls to 9e21 / 999_750_000_000 = 9_002_250_562
+        let quote_out = calculate_sell_quote_amount(100_000_000_000, 900_000_000_000, 10_000_000_000, 25).unwrap();
+        assert_eq!(quote_out, 997_749_438);
+    }
+
+    #[test]
+    fn zero_fee_reproduces_the_plain_constant_product() {
+        // Same trades as above, x*y=k with nothing held back
+        assert_eq!(calculate_buy_base_amount(1_000_000_000, 9_000_000_000, 1_000_000_000_000, 0).unwrap(), 100_000_000_000);
+        assert_eq!(calculate_sell_quote_amount(100_000_000_000, 900_000_000_000, 10_000_000_000, 0).unwrap(), 1_000_000_000);
+    }
+
+    #[test]
+    fn empty_or_one_sided_pools_quote_nothing() {
+        for (quote_reserve, base_reserve) in [(0, 0), (0, 1_000_000_000_000), (9_000_000_000, 0)] {
+            let buy = calculate_buy_base_amount(1_000_000_000, quote_reserve, base_reserve, 25);
+            assert!(matches!(buy, Err(ClientError::InvalidData(_))), "buy against {}/{}", quote_reserve, base_reserve);
+            let sell = calculate_sell_quote_amount(100_000_000_000, base_reserve, quote_reserve, 25);
+            assert!(matches!(sell, Err(ClientError::InvalidData(_))), "sell against {}/{}", base_reserve, quote_reserve);
+        }
+    }
 }
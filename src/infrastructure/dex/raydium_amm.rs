@@ -0,0 +1,879 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anchor_client::solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::ui_amount_to_amount;
+use tokio::time::Instant;
+
+use crate::{
+    common::config::SwapConfig,
+    core::{
+        dex_adapter::{DexAdapter, PoolInfo as AdapterPool},
+        price::{check_reserves, Price},
+    },
+    dex::{
+        pump_swap::{SOL_MINT, TOKEN_PROGRAM},
+        slippage::{get_expire_condition, min_amount_with_slippage},
+    },
+    engine::{
+        quote_currency::QuoteAsset,
+        swap::{SwapDirection, SwapInType},
+    },
+    error::{ClientError, ClientResult},
+};
+
+// Raydium AMM v4 Constants
+pub const RAYDIUM_AMM_PROGRAM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+pub const RAYDIUM_AMM_AUTHORITY: &str = "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1";
+pub const OPENBOOK_PROGRAM: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+pub const SERUM_V3_PROGRAM: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+pub const AMM_INFO_SIZE: usize = 752;
+
+/// Instruction tags of the AMM program
+pub const SWAP_BASE_IN: u8 = 9;
+pub const SWAP_BASE_OUT: u8 = 11;
+/// Variants without the OpenBook accounts, for pools with a placeholder market
+pub const SWAP_BASE_IN_V2: u8 = 16;
+pub const SWAP_BASE_OUT_V2: u8 = 17;
+
+// AmmInfo field offsets
+const COIN_DECIMALS_OFFSET: usize = 32;
+const PC_DECIMALS_OFFSET: usize = 40;
+const SWAP_FEE_NUMERATOR_OFFSET: usize = 176;
+const SWAP_FEE_DENOMINATOR_OFFSET: usize = 184;
+const NEED_TAKE_PNL_COIN_OFFSET: usize = 192;
+const NEED_TAKE_PNL_PC_OFFSET: usize = 200;
+const COIN_VAULT_OFFSET: usize = 336;
+const PC_VAULT_OFFSET: usize = 368;
+pub const COIN_MINT_OFFSET: usize = 400;
+pub const PC_MINT_OFFSET: usize = 432;
+const LP_MINT_OFFSET: usize = 464;
+const OPEN_ORDERS_OFFSET: usize = 496;
+const MARKET_OFFSET: usize = 528;
+const MARKET_PROGRAM_OFFSET: usize = 560;
+const TARGET_ORDERS_OFFSET: usize = 592;
+
+// OpenBook market field offsets (after the 5 byte "serum" header)
+const MARKET_VAULT_SIGNER_NONCE_OFFSET: usize = 45;
+const MARKET_COIN_VAULT_OFFSET: usize = 117;
+const MARKET_PC_VAULT_OFFSET: usize = 165;
+const MARKET_EVENT_QUEUE_OFFSET: usize = 253;
+const MARKET_BIDS_OFFSET: usize = 285;
+const MARKET_ASKS_OFFSET: usize = 317;
+
+/// Which account layout a v4 pool's swaps need
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmmSubtype {
+    /// Backed by a live OpenBook/Serum market, swaps pass the market accounts
+    OpenBookMarket,
+    /// Created with a placeholder market; swaps must use the V2 instructions
+    PlaceholderMarket,
+}
+
+impl AmmSubtype {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OpenBookMarket => "amm_v4",
+            Self::PlaceholderMarket => "amm_v4_no_market",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "amm_v4" => Some(Self::OpenBookMarket),
+            "amm_v4_no_market" => Some(Self::PlaceholderMarket),
+            _ => None,
+        }
+    }
+}
+
+/// The parts of a decoded AmmInfo account the bot needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaydiumAmmPool {
+    pub pool_id: Pubkey,
+    pub coin_decimals: u64,
+    pub pc_decimals: u64,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+    pub need_take_pnl_coin: u64,
+    pub need_take_pnl_pc: u64,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub open_orders: Pubkey,
+    pub market: Pubkey,
+    pub market_program: Pubkey,
+    pub target_orders: Pubkey,
+}
+
+/// OpenBook market accounts a v4 swap passes through
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketKeys {
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub event_queue: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub vault_signer: Pubkey,
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+impl RaydiumAmmPool {
+    /// Decode an AmmInfo account
+    pub fn decode(pool_id: Pubkey, data: &[u8]) -> ClientResult<Self> {
+        if data.len() < AMM_INFO_SIZE {
+            return Err(ClientError::InvalidData(format!(
+                "Raydium AMM account {} is {} bytes, expected {}",
+                pool_id,
+                data.len(),
+                AMM_INFO_SIZE
+            )));
+        }
+
+        Ok(Self {
+            pool_id,
+            coin_decimals: read_u64(data, COIN_DECIMALS_OFFSET),
+            pc_decimals: read_u64(data, PC_DECIMALS_OFFSET),
+            swap_fee_numerator: read_u64(data, SWAP_FEE_NUMERATOR_OFFSET),
+            swap_fee_denominator: read_u64(data, SWAP_FEE_DENOMINATOR_OFFSET),
+            need_take_pnl_coin: read_u64(data, NEED_TAKE_PNL_COIN_OFFSET),
+            need_take_pnl_pc: read_u64(data, NEED_TAKE_PNL_PC_OFFSET),
+            coin_vault: read_pubkey(data, COIN_VAULT_OFFSET),
+            pc_vault: read_pubkey(data, PC_VAULT_OFFSET),
+            coin_mint: read_pubkey(data, COIN_MINT_OFFSET),
+            pc_mint: read_pubkey(data, PC_MINT_OFFSET),
+            lp_mint: read_pubkey(data, LP_MINT_OFFSET),
+            open_orders: read_pubkey(data, OPEN_ORDERS_OFFSET),
+            market: read_pubkey(data, MARKET_OFFSET),
+            market_program: read_pubkey(data, MARKET_PROGRAM_OFFSET),
+            target_orders: read_pubkey(data, TARGET_ORDERS_OFFSET),
+        })
+    }
+
+    /// A market is only real if it lives under a known order book program;
+    /// placeholder pools point at the default key or an unrelated program
+    pub fn subtype(&self) -> AmmSubtype {
+        let known_market_program = [OPENBOOK_PROGRAM, SERUM_V3_PROGRAM]
+            .iter()
+            .any(|p| Pubkey::from_str(p).map(|p| p == self.market_program).unwrap_or(false));
+        if known_market_program && self.market != Pubkey::default() {
+            AmmSubtype::OpenBookMarket
+        } else {
+            AmmSubtype::PlaceholderMarket
+        }
+    }
+
+    /// Output for `amount_in` against vault balances, after the pool's swap fee.
+    /// Vault balances include PnL the pool hasn't taken yet, which isn't tradable.
+    pub fn quote_base_in(
+        &self,
+        amount_in: u64,
+        coin_to_pc: bool,
+        coin_vault_balance: u64,
+        pc_vault_balance: u64,
+    ) -> ClientResult<u64> {
+        let coin_reserve = coin_vault_balance.saturating_sub(self.need_take_pnl_coin);
+        let pc_reserve = pc_vault_balance.saturating_sub(self.need_take_pnl_pc);
+        let (reserve_in, reserve_out) = if coin_to_pc {
+            (coin_reserve, pc_reserve)
+        } else {
+            (pc_reserve, coin_reserve)
+        };
+        if reserve_in == 0 || reserve_out == 0 || self.swap_fee_denominator == 0 {
+            return Err(ClientError::InvalidData(format!("Raydium AMM {} has empty reserves", self.pool_id)));
+        }
+
+        let fee = (amount_in as u128 * self.swap_fee_numerator as u128)
+            .div_ceil(self.swap_fee_denominator as u128);
+        let amount_in_after_fee = amount_in as u128 - fee.min(amount_in as u128);
+        let amount_out = amount_in_after_fee * reserve_out as u128 / (reserve_in as u128 + amount_in_after_fee);
+        if amount_out == 0 {
+            return Err(ClientError::TooSmall(amount_in, 0));
+        }
+        Ok(amount_out as u64)
+    }
+}
+
+/// Read bids/asks/event queue/vaults from an OpenBook market account
+pub fn decode_market_keys(market: &Pubkey, market_program: &Pubkey, data: &[u8]) -> Result<MarketKeys> {
+    if data.len() < MARKET_ASKS_OFFSET + 32 {
+        return Err(anyhow!("Market account {} too short: {} bytes", market, data.len()));
+    }
+    let nonce = read_u64(data, MARKET_VAULT_SIGNER_NONCE_OFFSET);
+    let vault_signer = Pubkey::create_program_address(&[market.as_ref(), &nonce.to_le_bytes()], market_program)
+        .map_err(|e| anyhow!("Invalid vault signer for market {}: {}", market, e))?;
+
+    Ok(MarketKeys {
+        bids: read_pubkey(data, MARKET_BIDS_OFFSET),
+        asks: read_pubkey(data, MARKET_ASKS_OFFSET),
+        event_queue: read_pubkey(data, MARKET_EVENT_QUEUE_OFFSET),
+        coin_vault: read_pubkey(data, MARKET_COIN_VAULT_OFFSET),
+        pc_vault: read_pubkey(data, MARKET_PC_VAULT_OFFSET),
+        vault_signer,
+    })
+}
+
+/// The user's side of a swap
+#[derive(Debug, Clone, Copy)]
+pub struct UserSwapAccounts {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub owner: Pubkey,
+}
+
+/// Build a swap for either subtype.
+///
+/// `market_keys` is required for `OpenBookMarket` pools and ignored for
+/// placeholder ones. With `base_in` the amounts are (amount_in, min_out),
+/// otherwise (max_in, amount_out).
+pub fn build_swap_instruction(
+    pool: &RaydiumAmmPool,
+    market_keys: Option<&MarketKeys>,
+    user: UserSwapAccounts,
+    base_in: bool,
+    amount: u64,
+    other_amount_threshold: u64,
+) -> Result<Instruction> {
+    let program_id = Pubkey::from_str(RAYDIUM_AMM_PROGRAM)?;
+    let authority = Pubkey::from_str(RAYDIUM_AMM_AUTHORITY)?;
+
+    let (tag, accounts) = match pool.subtype() {
+        AmmSubtype::PlaceholderMarket => {
+            let tag = if base_in { SWAP_BASE_IN_V2 } else { SWAP_BASE_OUT_V2 };
+            let accounts = vec![
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new(pool.pool_id, false),
+                AccountMeta::new_readonly(authority, false),
+                AccountMeta::new(pool.coin_vault, false),
+                AccountMeta::new(pool.pc_vault, false),
+                AccountMeta::new(user.source, false),
+                AccountMeta::new(user.destination, false),
+                AccountMeta::new_readonly(user.owner, true),
+            ];
+            (tag, accounts)
+        }
+        AmmSubtype::OpenBookMarket => {
+            let market_keys = market_keys
+                .ok_or_else(|| anyhow!("Raydium AMM {} needs its market accounts to swap", pool.pool_id))?;
+            let tag = if base_in { SWAP_BASE_IN } else { SWAP_BASE_OUT };
+            let accounts = vec![
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new(pool.pool_id, false),
+                AccountMeta::new_readonly(authority, false),
+                AccountMeta::new(pool.open_orders, false),
+                AccountMeta::new(pool.target_orders, false),
+                AccountMeta::new(pool.coin_vault, false),
+                AccountMeta::new(pool.pc_vault, false),
+                AccountMeta::new_readonly(pool.market_program, false),
+                AccountMeta::new(pool.market, false),
+                AccountMeta::new(market_keys.bids, false),
+                AccountMeta::new(market_keys.asks, false),
+                AccountMeta::new(market_keys.event_queue, false),
+                AccountMeta::new(market_keys.coin_vault, false),
+                AccountMeta::new(market_keys.pc_vault, false),
+                AccountMeta::new_readonly(market_keys.vault_signer, false),
+                AccountMeta::new(user.source, false),
+                AccountMeta::new(user.destination, false),
+                AccountMeta::new_readonly(user.owner, true),
+            ];
+            (tag, accounts)
+        }
+    };
+
+    let mut data = Vec::with_capacity(17);
+    data.push(tag);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+
+    Ok(Instruction {
+        program_id,
+        accounts,
+        data,
+    })
+}
+
+/// A v4 pool quoted in SOL or USDC with what a swap against it needs: the
+/// market accounts when it has a live market, and tradable reserves
+#[derive(Debug, Clone)]
+pub struct RaydiumAmmPoolInfo {
+    pub pool: RaydiumAmmPool,
+    pub market_keys: Option<MarketKeys>,
+    /// Vault balances less the PnL the pool hasn't taken
+    pub coin_reserve: u64,
+    pub pc_reserve: u64,
+}
+
+impl RaydiumAmmPoolInfo {
+    /// True when the token is the coin side and the quote the pc side. SOL
+    /// quotes whenever it is in the pair, so the SOL/USDC pool trades USDC.
+    pub fn token_is_coin(&self) -> bool {
+        QuoteAsset::orient(&self.pool.coin_mint.to_string(), &self.pool.pc_mint.to_string())
+            .is_some_and(|(token_is_coin, _)| token_is_coin)
+    }
+
+    /// Token mint of the pair
+    pub fn token_mint(&self) -> Pubkey {
+        if self.token_is_coin() { self.pool.coin_mint } else { self.pool.pc_mint }
+    }
+
+    /// Mint the token is quoted in
+    pub fn quote_mint(&self) -> Pubkey {
+        if self.token_is_coin() { self.pool.pc_mint } else { self.pool.coin_mint }
+    }
+
+    fn quoted_in_sol(&self) -> bool {
+        self.quote_mint().to_string() == SOL_MINT
+    }
+
+    /// (token reserve, quote reserve)
+    pub fn reserves(&self) -> (u64, u64) {
+        if self.token_is_coin() {
+            (self.coin_reserve, self.pc_reserve)
+        } else {
+            (self.pc_reserve, self.coin_reserve)
+        }
+    }
+
+    /// Output of `amount_in` sold into the pool, after its fee
+    pub fn quote(&self, amount_in: u64, direction: SwapDirection) -> ClientResult<u64> {
+        // Buying spends the quote, so the input is the token side's opposite
+        let coin_to_pc = match direction {
+            SwapDirection::Buy => !self.token_is_coin(),
+            SwapDirection::Sell => self.token_is_coin(),
+        };
+        let pool = &self.pool;
+        pool.quote_base_in(
+            amount_in,
+            coin_to_pc,
+            self.coin_reserve + pool.need_take_pnl_coin,
+            self.pc_reserve + pool.need_take_pnl_pc,
+        )
+    }
+}
+
+pub struct RaydiumAmm {
+    pub keypair: Arc<Keypair>,
+    pub rpc_client: Option<Arc<anchor_client::solana_client::rpc_client::RpcClient>>,
+    pub rpc_nonblocking_client: Option<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>>,
+}
+
+impl RaydiumAmm {
+    pub fn new(
+        keypair: Arc<Keypair>,
+        rpc_client: Option<Arc<anchor_client::solana_client::rpc_client::RpcClient>>,
+        rpc_nonblocking_client: Option<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>>,
+    ) -> Self {
+        Self {
+            keypair,
+            rpc_client,
+            rpc_nonblocking_client,
+        }
+    }
+
+    fn nonblocking_client(&self) -> Result<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>> {
+        self.rpc_nonblocking_client
+            .clone()
+            .ok_or_else(|| anyhow!("RPC nonblocking client not initialized"))
+    }
+
+    /// Swap instructions for `mint` against SOL, the same contract as
+    /// `PumpSwap::build_swap_ixn_by_mint`. SOL goes in and out through the
+    /// wallet's wrapped SOL account: a buy wraps `amount_in` first, a sell
+    /// unwraps everything after.
+    pub async fn build_swap_ixn_by_mint(
+        &self,
+        mint_str: &str,
+        pool: Option<RaydiumAmmPoolInfo>,
+        swap_config: SwapConfig,
+        start_time: Instant,
+    ) -> Result<(Arc<Keypair>, Vec<Instruction>, f64)> {
+        let owner = self.keypair.pubkey();
+        let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
+
+        let pool_info = match pool {
+            Some(pool) => pool,
+            None => get_pool_info(self.nonblocking_client()?, mint).await?,
+        };
+        if pool_info.token_mint() != mint || !pool_info.quoted_in_sol() {
+            return Err(anyhow!("Raydium AMM {} doesn't pair {} with SOL", pool_info.pool.pool_id, mint));
+        }
+        let (token_reserve, sol_reserve) = pool_info.reserves();
+        let token_price = Price::from_raw_reserves(&pool_info.pool.pool_id.to_string(), token_reserve, sol_reserve)?.value();
+
+        let token_ata = get_associated_token_address(&owner, &mint);
+        let mut instructions = vec![];
+
+        match swap_config.swap_direction {
+            SwapDirection::Buy => {
+                let amount_in = ui_amount_to_amount(swap_config.amount_in, 9);
+                let amount_out = pool_info.quote(amount_in, SwapDirection::Buy).map_err(|e| anyhow!("{}", e))?;
+                instructions.extend(self.build_buy_ixn_for_amount(
+                    &pool_info,
+                    amount_in,
+                    min_amount_with_slippage(amount_out, swap_config.slippage_bps),
+                )?);
+            }
+            SwapDirection::Sell => {
+                let balance = self
+                    .nonblocking_client()?
+                    .get_token_account_balance(&token_ata)
+                    .await
+                    .map_err(|_| anyhow!("Token ATA does not exist, cannot sell"))?;
+                let held = balance.amount.parse::<u64>().map_err(|_| anyhow!("Unreadable token balance"))?;
+                let amount = match swap_config.in_type {
+                    SwapInType::Qty => ui_amount_to_amount(swap_config.amount_in, balance.decimals),
+                    SwapInType::Pct => {
+                        let pct = swap_config.amount_in.min(1.0);
+                        (pct * 100.0) as u64 * held / 100
+                    }
+                };
+                if amount == 0 {
+                    return Err(anyhow!("Amount is zero, cannot sell"));
+                }
+                if amount > held {
+                    return Err(anyhow!("Sell amount exceeds account balance"));
+                }
+                let amount_out = pool_info.quote(amount, SwapDirection::Sell).map_err(|e| anyhow!("{}", e))?;
+                instructions.extend(self.build_sell_ixn_for_amount(
+                    &pool_info,
+                    amount,
+                    min_amount_with_slippage(amount_out, swap_config.slippage_bps),
+                )?);
+            }
+        }
+
+        if swap_config.swap_direction == SwapDirection::Buy
+            && start_time.elapsed() > Duration::from_millis(get_expire_condition())
+        {
+            return Err(anyhow!("RPC connection is too busy. Expire this txn."));
+        }
+
+        Ok((self.keypair.clone(), instructions, token_price))
+    }
+
+    /// Spend `amount_in` of the quote in `pool_info` for at least
+    /// `min_amount_out` tokens. SOL is wrapped first; any other quote is
+    /// spent from the wallet's own account.
+    pub fn build_buy_ixn_for_amount(
+        &self,
+        pool_info: &RaydiumAmmPoolInfo,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let quote_mint = pool_info.quote_mint();
+        let token_program = Pubkey::from_str(TOKEN_PROGRAM)?;
+        let mint = pool_info.token_mint();
+        let token_ata = get_associated_token_address(&owner, &mint);
+        let quote_ata = get_associated_token_address(&owner, &quote_mint);
+        let mut instructions = vec![create_associated_token_account_idempotent(&owner, &owner, &quote_mint, &token_program)];
+        if pool_info.quoted_in_sol() {
+            instructions.push(system_instruction::transfer(&owner, &quote_ata, amount_in));
+            instructions.push(spl_token::instruction::sync_native(&token_program, &quote_ata)?);
+        }
+        instructions.push(create_associated_token_account_idempotent(&owner, &owner, &mint, &token_program));
+        instructions.push(build_swap_instruction(
+            &pool_info.pool,
+            pool_info.market_keys.as_ref(),
+            UserSwapAccounts { source: quote_ata, destination: token_ata, owner },
+            true,
+            amount_in,
+            min_amount_out,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Sell exactly `amount_in` tokens into `pool_info` for at least
+    /// `min_amount_out` of the quote, without looking at the wallet's token
+    /// balance. SOL is unwrapped after; any other quote stays in its account.
+    pub fn build_sell_ixn_for_amount(
+        &self,
+        pool_info: &RaydiumAmmPoolInfo,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<Vec<Instruction>> {
+        let owner = self.keypair.pubkey();
+        let quote_mint = pool_info.quote_mint();
+        let token_program = Pubkey::from_str(TOKEN_PROGRAM)?;
+        let token_ata = get_associated_token_address(&owner, &pool_info.token_mint());
+        let quote_ata = get_associated_token_address(&owner, &quote_mint);
+        let mut instructions = vec![
+            create_associated_token_account_idempotent(&owner, &owner, &quote_mint, &token_program),
+            build_swap_instruction(
+                &pool_info.pool,
+                pool_info.market_keys.as_ref(),
+                UserSwapAccounts { source: token_ata, destination: quote_ata, owner },
+                true,
+                amount_in,
+                min_amount_out,
+            )?,
+        ];
+        if pool_info.quoted_in_sol() {
+            instructions.push(spl_token::instruction::close_account(&token_program, &quote_ata, &owner, &owner, &[&owner])?);
+        }
+        Ok(instructions)
+    }
+
+    /// The deepest SOL-paired pool of `mint`
+    pub async fn get_pool_info(&self, mint_str: &str) -> Result<RaydiumAmmPoolInfo> {
+        let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
+        get_pool_info(self.nonblocking_client()?, mint).await
+    }
+
+    /// Spot price in SOL per raw token unit, like `PumpSwap::get_token_price`
+    pub async fn get_token_price(&self, mint_str: &str) -> Result<f64> {
+        let pool_info = self.get_pool_info(mint_str).await?;
+        let (token_reserve, sol_reserve) = pool_info.reserves();
+        Ok(Price::from_raw_reserves(&pool_info.pool.pool_id.to_string(), token_reserve, sol_reserve)?.value())
+    }
+}
+
+fn adapter_pool(pool_info: RaydiumAmmPoolInfo) -> AdapterPool {
+    let (token_reserve, quote_reserve) = pool_info.reserves();
+    let quote_mint = pool_info.quote_mint();
+    AdapterPool::new(
+        "raydium_amm",
+        pool_info.pool.pool_id,
+        pool_info.token_mint(),
+        token_reserve,
+        quote_reserve,
+        pool_info,
+    )
+    .quoted_in(quote_mint)
+}
+
+#[async_trait]
+impl DexAdapter for RaydiumAmm {
+    fn name(&self) -> &'static str {
+        "raydium_amm"
+    }
+
+    async fn get_pool(&self, mint: &Pubkey) -> Result<AdapterPool> {
+        Ok(adapter_pool(get_pool_info(self.nonblocking_client()?, *mint).await?))
+    }
+
+    async fn read_pool(&self, pool_id: &Pubkey, mint: &Pubkey) -> Result<AdapterPool> {
+        self.read_quoted_pool(pool_id, mint, &Pubkey::from_str(SOL_MINT)?).await
+    }
+
+    async fn read_quoted_pool(&self, pool_id: &Pubkey, mint: &Pubkey, quote_mint: &Pubkey) -> Result<AdapterPool> {
+        let pool_info = read_amm_pool(self.nonblocking_client()?, *pool_id).await?;
+        if pool_info.token_mint() != *mint || pool_info.quote_mint() != *quote_mint {
+            return Err(anyhow!("Raydium AMM {} doesn't pair {} with {}", pool_id, mint, quote_mint));
+        }
+        Ok(adapter_pool(pool_info))
+    }
+
+    async fn get_token_price(&self, mint: &Pubkey) -> Result<f64> {
+        RaydiumAmm::get_token_price(self, &mint.to_string()).await
+    }
+
+    async fn quote(&self, pool: &AdapterPool, amount_in: u64, direction: SwapDirection) -> Result<u64> {
+        pool.venue::<RaydiumAmmPoolInfo>()?
+            .quote(amount_in, direction)
+            .map_err(|e| anyhow!("{}", e))
+    }
+
+    async fn build_swap_instructions(
+        &self,
+        pool: &AdapterPool,
+        amount_in: u64,
+        min_amount_out: u64,
+        direction: SwapDirection,
+    ) -> Result<Vec<Instruction>> {
+        let pool_info = pool.venue::<RaydiumAmmPoolInfo>()?;
+        match direction {
+            SwapDirection::Buy => self.build_buy_ixn_for_amount(pool_info, amount_in, min_amount_out),
+            SwapDirection::Sell => self.build_sell_ixn_for_amount(pool_info, amount_in, min_amount_out),
+        }
+    }
+}
+
+/// Find the deepest v4 pool pairing `mint` with SOL, on either side
+pub async fn get_pool_info(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    mint: Pubkey,
+) -> Result<RaydiumAmmPoolInfo> {
+    let program_id = Pubkey::from_str(RAYDIUM_AMM_PROGRAM)?;
+    let sol_mint = Pubkey::from_str(SOL_MINT)?;
+
+    let mut candidates = Vec::new();
+    for (token_offset, sol_offset) in [(COIN_MINT_OFFSET, PC_MINT_OFFSET), (PC_MINT_OFFSET, COIN_MINT_OFFSET)] {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(AMM_INFO_SIZE as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(token_offset, &mint.to_bytes())),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(sol_offset, &sol_mint.to_bytes())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: Some(false),
+        };
+        let accounts = rpc_client.get_program_accounts_with_config(&program_id, config).await?;
+        candidates.extend(accounts.into_iter().map(|(pubkey, _)| pubkey));
+    }
+
+    let mut deepest: Option<RaydiumAmmPoolInfo> = None;
+    for pool_id in candidates {
+        let Ok(pool_info) = read_amm_pool(rpc_client.clone(), pool_id).await else { continue };
+        if deepest.as_ref().map_or(true, |best| pool_info.reserves().1 > best.reserves().1) {
+            deepest = Some(pool_info);
+        }
+    }
+    deepest.ok_or_else(|| anyhow!("No Raydium AMM pool pairs {} with SOL", mint))
+}
+
+/// Read a v4 pool whose address is already known: its account, its market's
+/// accounts if it has a live one, and its vault balances
+pub async fn read_amm_pool(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    pool_id: Pubkey,
+) -> Result<RaydiumAmmPoolInfo> {
+    let data = rpc_client.get_account_data(&pool_id).await?;
+    let pool = RaydiumAmmPool::decode(pool_id, &data).map_err(|e| anyhow!("{}", e))?;
+
+    let market_keys = match pool.subtype() {
+        AmmSubtype::OpenBookMarket => {
+            let data = rpc_client.get_account_data(&pool.market).await?;
+            Some(decode_market_keys(&pool.market, &pool.market_program, &data)?)
+        }
+        AmmSubtype::PlaceholderMarket => None,
+    };
+
+    let coin_balance = vault_balance(&rpc_client, &pool.coin_vault).await?;
+    let pc_balance = vault_balance(&rpc_client, &pool.pc_vault).await?;
+    let coin_reserve = coin_balance.saturating_sub(pool.need_take_pnl_coin);
+    let pc_reserve = pc_balance.saturating_sub(pool.need_take_pnl_pc);
+    check_reserves(&pool_id.to_string(), coin_reserve, pc_reserve)?;
+
+    Ok(RaydiumAmmPoolInfo {
+        pool,
+        market_keys,
+        coin_reserve,
+        pc_reserve,
+    })
+}
+
+async fn vault_balance(
+    rpc_client: &anchor_client::solana_client::nonblocking::rpc_client::RpcClient,
+    vault: &Pubkey,
+) -> Result<u64> {
+    rpc_client
+        .get_token_account_balance(vault)
+        .await?
+        .amount
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Unreadable balance of vault {}", vault))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed keys so the fixtures and golden instructions stay the same run to run
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    fn write_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_pubkey(data: &mut [u8], offset: usize, key: &Pubkey) {
+        data[offset..offset + 32].copy_from_slice(key.as_ref());
+    }
+
+    /// An AmmInfo account as the program lays it out, with `market` under `market_program`
+    fn amm_info_fixture(market: Pubkey, market_program: Pubkey) -> Vec<u8> {
+        let mut data = vec![0u8; AMM_INFO_SIZE];
+        write_u64(&mut data, COIN_DECIMALS_OFFSET, 6);
+        write_u64(&mut data, PC_DECIMALS_OFFSET, 9);
+        write_u64(&mut data, SWAP_FEE_NUMERATOR_OFFSET, 25);
+        write_u64(&mut data, SWAP_FEE_DENOMINATOR_OFFSET, 10_000);
+        write_u64(&mut data, NEED_TAKE_PNL_COIN_OFFSET, 1_000);
+        write_u64(&mut data, NEED_TAKE_PNL_PC_OFFSET, 2_000);
+        write_pubkey(&mut data, COIN_VAULT_OFFSET, &key(1));
+        write_pubkey(&mut data, PC_VAULT_OFFSET, &key(2));
+        write_pubkey(&mut data, COIN_MINT_OFFSET, &key(3));
+        write_pubkey(&mut data, PC_MINT_OFFSET, &Pubkey::from_str(SOL_MINT).unwrap());
+        write_pubkey(&mut data, LP_MINT_OFFSET, &key(4));
+        write_pubkey(&mut data, OPEN_ORDERS_OFFSET, &key(5));
+        write_pubkey(&mut data, MARKET_OFFSET, &market);
+        write_pubkey(&mut data, MARKET_PROGRAM_OFFSET, &market_program);
+        write_pubkey(&mut data, TARGET_ORDERS_OFFSET, &key(6));
+        data
+    }
+
+    fn openbook_pool() -> RaydiumAmmPool {
+        let data = amm_info_fixture(key(7), Pubkey::from_str(OPENBOOK_PROGRAM).unwrap());
+        RaydiumAmmPool::decode(key(9), &data).unwrap()
+    }
+
+    fn placeholder_pool() -> RaydiumAmmPool {
+        // Newer pools point their market at a throwaway account outside any order book program
+        let data = amm_info_fixture(key(7), key(8));
+        RaydiumAmmPool::decode(key(9), &data).unwrap()
+    }
+
+    fn market_keys() -> MarketKeys {
+        MarketKeys {
+            bids: key(10),
+            asks: key(11),
+            event_queue: key(12),
+            coin_vault: key(13),
+            pc_vault: key(14),
+            vault_signer: key(15),
+        }
+    }
+
+    fn user() -> UserSwapAccounts {
+        UserSwapAccounts { source: key(20), destination: key(21), owner: key(22) }
+    }
+
+    fn keys(instruction: &Instruction) -> Vec<(Pubkey, bool, bool)> {
+        instruction.accounts.iter().map(|a| (a.pubkey, a.is_writable, a.is_signer)).collect()
+    }
+
+    #[test]
+    fn decodes_both_subtypes() {
+        let pool = openbook_pool();
+        assert_eq!(pool.coin_decimals, 6);
+        assert_eq!(pool.pc_decimals, 9);
+        assert_eq!((pool.swap_fee_numerator, pool.swap_fee_denominator), (25, 10_000));
+        assert_eq!((pool.need_take_pnl_coin, pool.need_take_pnl_pc), (1_000, 2_000));
+        assert_eq!((pool.coin_vault, pool.pc_vault), (key(1), key(2)));
+        assert_eq!(pool.coin_mint, key(3));
+        assert_eq!((pool.open_orders, pool.market, pool.target_orders), (key(5), key(7), key(6)));
+        assert_eq!(pool.subtype(), AmmSubtype::OpenBookMarket);
+
+        assert_eq!(placeholder_pool().subtype(), AmmSubtype::PlaceholderMarket);
+        let unset = amm_info_fixture(Pubkey::default(), Pubkey::from_str(OPENBOOK_PROGRAM).unwrap());
+        assert_eq!(RaydiumAmmPool::decode(key(9), &unset).unwrap().subtype(), AmmSubtype::PlaceholderMarket);
+
+        assert!(RaydiumAmmPool::decode(key(9), &[0u8; AMM_INFO_SIZE - 1]).is_err());
+        for subtype in [AmmSubtype::OpenBookMarket, AmmSubtype::PlaceholderMarket] {
+            assert_eq!(AmmSubtype::from_name(subtype.as_str()), Some(subtype));
+        }
+    }
+
+    #[test]
+    fn quoting_is_the_same_for_both_subtypes() {
+        // 0.25% fee, rounded up, taken before the constant product
+        let expected = 996_505;
+        for pool in [openbook_pool(), placeholder_pool()] {
+            let out = pool.quote_base_in(1_000_000, true, 1_000_000_000 + 1_000, 1_000_000_000 + 2_000).unwrap();
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn golden_swap_with_market_accounts() {
+        let pool = openbook_pool();
+        let ix = build_swap_instruction(&pool, Some(&market_keys()), user(), true, 1_000, 900).unwrap();
+
+        assert_eq!(ix.program_id, Pubkey::from_str(RAYDIUM_AMM_PROGRAM).unwrap());
+        assert_eq!(ix.data, [9, 232, 3, 0, 0, 0, 0, 0, 0, 132, 3, 0, 0, 0, 0, 0, 0]);
+        let authority = Pubkey::from_str(RAYDIUM_AMM_AUTHORITY).unwrap();
+        assert_eq!(
+            keys(&ix),
+            vec![
+                (spl_token::id(), false, false),
+                (key(9), true, false),
+                (authority, false, false),
+                (key(5), true, false),
+                (key(6), true, false),
+                (key(1), true, false),
+                (key(2), true, false),
+                (Pubkey::from_str(OPENBOOK_PROGRAM).unwrap(), false, false),
+                (key(7), true, false),
+                (key(10), true, false),
+                (key(11), true, false),
+                (key(12), true, false),
+                (key(13), true, false),
+                (key(14), true, false),
+                (key(15), false, false),
+                (key(20), true, false),
+                (key(21), true, false),
+                (key(22), false, true),
+            ]
+        );
+        // A live market can't be swapped through without its accounts
+        assert!(build_swap_instruction(&pool, None, user(), true, 1_000, 900).is_err());
+    }
+
+    #[test]
+    fn golden_swap_without_market() {
+        let pool = placeholder_pool();
+        // Market keys are ignored for placeholder pools
+        let ix = build_swap_instruction(&pool, Some(&market_keys()), user(), false, 1_000, 900).unwrap();
+
+        assert_eq!(ix.data, [SWAP_BASE_OUT_V2, 232, 3, 0, 0, 0, 0, 0, 0, 132, 3, 0, 0, 0, 0, 0, 0]);
+        let authority = Pubkey::from_str(RAYDIUM_AMM_AUTHORITY).unwrap();
+        assert_eq!(
+            keys(&ix),
+            vec![
+                (spl_token::id(), false, false),
+                (key(9), true, false),
+                (authority, false, false),
+                (key(1), true, false),
+                (key(2), true, false),
+                (key(20), true, false),
+                (key(21), true, false),
+                (key(22), false, true),
+            ]
+        );
+        let base_in = build_swap_instruction(&pool, None, user(), true, 1_000, 900).unwrap();
+        assert_eq!(base_in.data[0], SWAP_BASE_IN_V2);
+    }
+
+    #[test]
+    fn market_keys_decode_with_their_vault_signer() {
+        let market = key(7);
+        let program = Pubkey::from_str(OPENBOOK_PROGRAM).unwrap();
+        let nonce = (0u64..256)
+            .find(|nonce| Pubkey::create_program_address(&[market.as_ref(), &nonce.to_le_bytes()], &program).is_ok())
+            .unwrap();
+        let mut data = vec![0u8; MARKET_ASKS_OFFSET + 32];
+        write_u64(&mut data, MARKET_VAULT_SIGNER_NONCE_OFFSET, nonce);
+        write_pubkey(&mut data, MARKET_COIN_VAULT_OFFSET, &key(13));
+        write_pubkey(&mut data, MARKET_PC_VAULT_OFFSET, &key(14));
+        write_pubkey(&mut data, MARKET_EVENT_QUEUE_OFFSET, &key(12));
+        write_pubkey(&mut data, MARKET_BIDS_OFFSET, &key(10));
+        write_pubkey(&mut data, MARKET_ASKS_OFFSET, &key(11));
+
+        let decoded = decode_market_keys(&market, &program, &data).unwrap();
+        let signer = Pubkey::create_program_address(&[market.as_ref(), &nonce.to_le_bytes()], &program).unwrap();
+        assert_eq!(decoded, MarketKeys { vault_signer: signer, ..market_keys() });
+        assert!(decode_market_keys(&market, &program, &data[..MARKET_ASKS_OFFSET]).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Slippage bounds and the swap retry deadline shared by every venue adapter.
+use std::{env, str::FromStr};
+
+use crate::dex::pump_swap::TEN_THOUSAND;
+
+/// Calculate the minimum amount with slippage tolerance, in bps:
+/// (1_000_000, 50) -> 995_000. `swap_config.slippage_bps` is passed as is;
+/// the percent-to-bps conversion happens once, when the config is loaded.
+pub(crate) fn min_amount_with_slippage(amount: u64, slippage_bps: u64) -> u64 {
+    let keep = TEN_THOUSAND.saturating_sub(slippage_bps) as u128;
+    (amount as u128 * keep / TEN_THOUSAND as u128) as u64
+}
+
+/// Calculate the maximum amount with slippage tolerance, in bps:
+/// (1_000_000, 50) -> 1_005_000
+pub(crate) fn max_amount_with_slippage(amount: u64, slippage_bps: u64) -> u64 {
+    let allow = TEN_THOUSAND.saturating_add(slippage_bps) as u128;
+    u64::try_from(amount as u128 * allow / TEN_THOUSAND as u128).unwrap_or(u64::MAX)
+}
+
+/// Get expiration time for transaction
+pub(crate) fn get_expire_condition() -> u64 {
+    env::var("EXPIRE_CONDITION")
+        .ok()
+        .and_then(|v| u64::from_str(&v).ok())
+        .unwrap_or(10000) // Default 10 seconds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slippage_is_read_as_basis_points() {
+        // 50 is half a percent, not 50%
+        assert_eq!(min_amount_with_slippage(1_000_000, 50), 995_000);
+        assert_eq!(max_amount_with_slippage(1_000_000, 50), 1_005_000);
+        assert_eq!(min_amount_with_slippage(1_000_000, 0), 1_000_000);
+        assert_eq!(min_amount_with_slippage(1_000_000, 10_000), 0);
+        assert_eq!(min_amount_with_slippage(1_000, 20_000), 0);
+        assert_eq!(min_amount_with_slippage(u64::MAX, 0), u64::MAX);
+        assert_eq!(max_amount_with_slippage(u64::MAX, 50), u64::MAX);
+    }
+
+    #[test]
+    fn fifty_bps_bounds_stay_within_half_a_percent_of_the_quote() {
+        for quote in [1_000, 123_456_789, 5_000_000_000_000] {
+            let min_out = min_amount_with_slippage(quote, 50);
+            assert!(min_out < quote && min_out as f64 >= quote as f64 * 0.995 - 1.0, "quote {}", quote);
+            let max_in = max_amount_with_slippage(quote, 50);
+            assert!(max_in > quote && max_in as f64 <= quote as f64 * 1.005, "quote {}", quote);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Pool accounts named by a swap instruction, per DEX
+//!
+//! Each venue orders its swap accounts differently, and a program's other
+//! instructions (config, admin, liquidity) name different accounts again.
+//! The instruction data is checked against the venue's swap discriminators
+//! or tags before any position is read, so nothing but a swap is decoded.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+
+use crate::dex::dex_registry::DEX;
+use crate::dex::{meteora_dlmm, pump_swap, raydium_amm};
+
+/// sha256("global:swap_base_input")[..8] / ("global:swap_base_output")
+const CPMM_SWAP_BASE_INPUT: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+const CPMM_SWAP_BASE_OUTPUT: [u8; 8] = [55, 217, 98, 86, 163, 74, 180, 173];
+/// sha256("global:swap")[..8] / ("global:swap_v2")
+const WHIRLPOOL_SWAP: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+const WHIRLPOOL_SWAP_V2: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+/// sha256("global:swap_exact_out")[..8] / ("global:swap_with_price_impact")
+const DLMM_SWAP_EXACT_OUT: [u8; 8] = [250, 73, 101, 33, 38, 207, 75, 184];
+const DLMM_SWAP_WITH_PRICE_IMPACT: [u8; 8] = [56, 173, 230, 208, 173, 228, 156, 205];
+
+/// The pool a swap went through; mints are None for venues whose swap
+/// doesn't pass them, and are then read off the vaults' token balances
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapPoolAccounts {
+    pub pool_id: Pubkey,
+    pub base_mint: Option<Pubkey>,
+    pub quote_mint: Option<Pubkey>,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+}
+
+/// Decode the pool of a `dex` swap from its instruction `data` and its
+/// `accounts` in instruction order; None for anything but a swap of a
+/// venue with a mapping (PumpSwap, Raydium AMM and CPMM, Whirlpool,
+/// Meteora DLMM)
+pub fn decode_pool_accounts(dex: &DEX, data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+    match dex.name.as_str() {
+        "pumpswap" => decode_pump_swap(data, accounts),
+        "raydium_amm" => decode_raydium_amm(data, accounts),
+        "raydium_cpmm" => decode_raydium_cpmm(data, accounts),
+        "whirlpool" => decode_whirlpool(data, accounts),
+        "meteora_dlmm" => decode_meteora_dlmm(data, accounts),
+        _ => None,
+    }
+}
+
+fn discriminator(data: &[u8]) -> Option<[u8; 8]> {
+    data.get(..8)?.try_into().ok()
+}
+
+/// pool, user, global config, base mint, quote mint, user base and quote
+/// accounts, pool base and quote vaults, ...
+fn decode_pump_swap(data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+    let discriminator = discriminator(data)?;
+    if discriminator != pump_swap::BUY_DISCRIMINATOR && discriminator != pump_swap::SELL_DISCRIMINATOR {
+        return None;
+    }
+    Some(SwapPoolAccounts {
+        pool_id: *accounts.first()?,
+        base_mint: Some(*accounts.get(3)?),
+        quote_mint: Some(*accounts.get(4)?),
+        base_vault: *accounts.get(7)?,
+        quote_vault: *accounts.get(8)?,
+    })
+}
+
+/// token program, amm, authority, then either the coin and pc vaults (the
+/// placeholder-market tags), or open orders, target orders and the vaults;
+/// some older clients leave target orders out
+fn decode_raydium_amm(data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+    let vaults_at = match *data.first()? {
+        raydium_amm::SWAP_BASE_IN_V2 | raydium_amm::SWAP_BASE_OUT_V2 => 3,
+        raydium_amm::SWAP_BASE_IN | raydium_amm::SWAP_BASE_OUT if accounts.len() >= 18 => 5,
+        raydium_amm::SWAP_BASE_IN | raydium_amm::SWAP_BASE_OUT => 4,
+        _ => return None,
+    };
+    Some(SwapPoolAccounts {
+        pool_id: *accounts.get(1)?,
+        base_mint: None,
+        quote_mint: None,
+        base_vault: *accounts.get(vaults_at)?,
+        quote_vault: *accounts.get(vaults_at + 1)?,
+    })
+}
+
+/// payer, authority, amm config, pool state, user input and output
+/// accounts, input and output vaults, both token programs, input and
+/// output mints, observation. The sides follow the trade direction, so SOL
+/// is put on the quote side.
+fn decode_raydium_cpmm(data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+    let discriminator = discriminator(data)?;
+    if discriminator != CPMM_SWAP_BASE_INPUT && discriminator != CPMM_SWAP_BASE_OUTPUT {
+        return None;
+    }
+    let (input_mint, output_mint) = (*accounts.get(10)?, *accounts.get(11)?);
+    let (input_vault, output_vault) = (*accounts.get(6)?, *accounts.get(7)?);
+    let ((base_mint, base_vault), (quote_mint, quote_vault)) = if input_mint == spl_token::native_mint::id() {
+        ((output_mint, output_vault), (input_mint, input_vault))
+    } else {
+        ((input_mint, input_vault), (output_mint, output_vault))
+    };
+    Some(SwapPoolAccounts {
+        pool_id: *accounts.get(3)?,
+        base_mint: Some(base_mint),
+        quote_mint: Some(quote_mint),
+        base_vault,
+        quote_vault,
+    })
+}
+
+/// `swap`: token program, authority, whirlpool, owner account A, vault A,
+/// owner account B, vault B, ... `swap_v2` adds the mints: two token
+/// programs, memo program, authority, whirlpool, mint A, mint B, owner
+/// account A, vault A, owner account B, vault B, ...
+fn decode_whirlpool(data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+    match discriminator(data)? {
+        WHIRLPOOL_SWAP => Some(SwapPoolAccounts {
+            pool_id: *accounts.get(2)?,
+            base_mint: None,
+            quote_mint: None,
+            base_vault: *accounts.get(4)?,
+            quote_vault: *accounts.get(6)?,
+        }),
+        WHIRLPOOL_SWAP_V2 => Some(SwapPoolAccounts {
+            pool_id: *accounts.get(4)?,
+            base_mint: Some(*accounts.get(5)?),
+            quote_mint: Some(*accounts.get(6)?),
+            base_vault: *accounts.get(8)?,
+            quote_vault: *accounts.get(10)?,
+        }),
+        _ => None,
+    }
+}
+
+/// lb pair, bitmap extension, reserve X, reserve Y, user in and out
+/// accounts, mint X, mint Y, ...; the same for all three swap variants
+fn decode_meteora_dlmm(data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+    let discriminator = discriminator(data)?;
+    if ![meteora_dlmm::SWAP_DISCRIMINATOR, DLMM_SWAP_EXACT_OUT, DLMM_SWAP_WITH_PRICE_IMPACT].contains(&discriminator) {
+        return None;
+    }
+    Some(SwapPoolAccounts {
+        pool_id: *accounts.first()?,
+        base_mint: Some(*accounts.get(6)?),
+        quote_mint: Some(*accounts.get(7)?),
+        base_vault: *accounts.get(2)?,
+        quote_vault: *accounts.get(3)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::dex::dex_registry::DEXRegistry;
+
+    fn decode(dex: &str, data: &[u8], accounts: &[Pubkey]) -> Option<SwapPoolAccounts> {
+        decode_pool_accounts(DEXRegistry::shared().get_dex(dex).unwrap(), data, accounts)
+    }
+
+    fn keys(n: usize) -> Vec<Pubkey> {
+        (0..n).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    fn with_data(discriminator: [u8; 8]) -> Vec<u8> {
+        // Amount and limit after the discriminator, as every swap carries them
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&[0; 16]);
+        data
+    }
+
+    #[test]
+    fn pump_swap_buy_from_the_bench_fixture() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../../../benches/fixtures/pumpswap_buy_small.json")).unwrap();
+        let account_keys: Vec<Pubkey> = fixture["account_keys"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| Pubkey::from_str(key.as_str().unwrap()).unwrap())
+            .collect();
+        let swap = &fixture["instructions"][1];
+        let accounts: Vec<Pubkey> =
+            swap["accounts"].as_array().unwrap().iter().map(|i| account_keys[i.as_u64().unwrap() as usize]).collect();
+        let data = base64::decode(swap["data_base64"].as_str().unwrap()).unwrap();
+
+        let key = |s: &str| Pubkey::from_str(s).unwrap();
+        assert_eq!(
+            decode("pumpswap", &data, &accounts),
+            Some(SwapPoolAccounts {
+                pool_id: key("CLEND1eVcHj4Z9XCvJo3j5wPiiKdYtXP8C6xarnpaWZ2"),
+                base_mint: Some(key("AiUtVjX45yJ3uELetZYseyTFV84MAqFN6GSFfhGtQdJ9")),
+                quote_mint: Some(spl_token::native_mint::id()),
+                base_vault: key("4hYPPrCBhJN9ci8fTN5s5TWCG6si76YD94gUcdo36erC"),
+                quote_vault: key("3C1gTRzeYLW9XpZJNiDLfMHavbC9H2Upe4cbVcMS3zW8"),
+            })
+        );
+        // The compute budget instruction before it is nothing of PumpSwap's
+        let budget = base64::decode(fixture["instructions"][0]["data_base64"].as_str().unwrap()).unwrap();
+        assert_eq!(decode("pumpswap", &budget, &accounts), None);
+    }
+
+    #[test]
+    fn raydium_amm_vaults_move_with_the_account_layout() {
+        let accounts = keys(18);
+        let swap = decode("raydium_amm", &[raydium_amm::SWAP_BASE_IN, 0, 0], &accounts).unwrap();
+        assert_eq!((swap.pool_id, swap.base_vault, swap.quote_vault), (accounts[1], accounts[5], accounts[6]));
+        assert_eq!((swap.base_mint, swap.quote_mint), (None, None));
+
+        // Without target orders the vaults come one earlier
+        let swap = decode("raydium_amm", &[raydium_amm::SWAP_BASE_OUT], &accounts[..17]).unwrap();
+        assert_eq!((swap.base_vault, swap.quote_vault), (accounts[4], accounts[5]));
+
+        let swap = decode("raydium_amm", &[raydium_amm::SWAP_BASE_IN_V2], &accounts[..8]).unwrap();
+        assert_eq!((swap.pool_id, swap.base_vault, swap.quote_vault), (accounts[1], accounts[3], accounts[4]));
+
+        // Deposit, tag 3, names the pool too but isn't a swap
+        assert_eq!(decode("raydium_amm", &[3], &accounts), None);
+    }
+
+    #[test]
+    fn raydium_cpmm_puts_sol_on_the_quote_side_either_way() {
+        let mut accounts = keys(13);
+        let token = accounts[11];
+        accounts[10] = spl_token::native_mint::id();
+        let buy = decode("raydium_cpmm", &with_data(CPMM_SWAP_BASE_INPUT), &accounts).unwrap();
+        assert_eq!(buy.pool_id, accounts[3]);
+        assert_eq!((buy.base_mint, buy.base_vault), (Some(token), accounts[7]));
+        assert_eq!((buy.quote_mint, buy.quote_vault), (Some(spl_token::native_mint::id()), accounts[6]));
+
+        accounts.swap(10, 11);
+        accounts.swap(6, 7);
+        let sell = decode("raydium_cpmm", &with_data(CPMM_SWAP_BASE_OUTPUT), &accounts).unwrap();
+        assert_eq!(sell, buy);
+
+        assert_eq!(decode("raydium_cpmm", &with_data(WHIRLPOOL_SWAP), &accounts), None);
+    }
+
+    #[test]
+    fn whirlpool_swap_and_swap_v2() {
+        let accounts = keys(11);
+        let swap = decode("whirlpool", &with_data(WHIRLPOOL_SWAP), &accounts).unwrap();
+        assert_eq!((swap.pool_id, swap.base_vault, swap.quote_vault), (accounts[2], accounts[4], accounts[6]));
+        assert_eq!(swap.base_mint, None);
+
+        let swap = decode("whirlpool", &with_data(WHIRLPOOL_SWAP_V2), &accounts).unwrap();
+        assert_eq!(
+            swap,
+            SwapPoolAccounts {
+                pool_id: accounts[4],
+                base_mint: Some(accounts[5]),
+                quote_mint: Some(accounts[6]),
+                base_vault: accounts[8],
+                quote_vault: accounts[10],
+            }
+        );
+        // A v2 swap cut short of its vaults is not read past the end
+        assert_eq!(decode("whirlpool", &with_data(WHIRLPOOL_SWAP_V2), &accounts[..9]), None);
+    }
+
+    #[test]
+    fn meteora_dlmm_swaps_share_one_layout() {
+        let accounts = keys(8);
+        for discriminator in [meteora_dlmm::SWAP_DISCRIMINATOR, DLMM_SWAP_EXACT_OUT, DLMM_SWAP_WITH_PRICE_IMPACT] {
+            let swap = decode("meteora_dlmm", &with_data(discriminator), &accounts).unwrap();
+            assert_eq!((swap.pool_id, swap.base_vault, swap.quote_vault), (accounts[0], accounts[2], accounts[3]));
+            assert_eq!((swap.base_mint, swap.quote_mint), (Some(accounts[6]), Some(accounts[7])));
+        }
+        assert_eq!(decode("meteora_dlmm", &with_data(CPMM_SWAP_BASE_INPUT), &accounts), None);
+    }
+
+    #[test]
+    fn venues_without_a_mapping_decode_nothing() {
+        let accounts = keys(20);
+        assert_eq!(decode("raydium_clmm", &with_data(WHIRLPOOL_SWAP), &accounts), None);
+        assert_eq!(decode("pumpswap", &[], &accounts), None);
+    }
+}
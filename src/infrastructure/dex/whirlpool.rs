@@ -0,0 +1,411 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::{anyhow, Result};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+
+use crate::core::price::{BookTouch, Price};
+use crate::core::token::mint_decimals;
+use crate::dex::pump_swap::SOL_MINT;
+use crate::engine::swap::SwapDirection;
+use crate::error::{ClientError, ClientResult};
+
+pub const WHIRLPOOL_PROGRAM: &str = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+pub const WHIRLPOOL_SIZE: usize = 653;
+
+// Whirlpool field offsets, after the 8 byte Anchor discriminator
+const TICK_SPACING_OFFSET: usize = 41;
+const FEE_RATE_OFFSET: usize = 45;
+const LIQUIDITY_OFFSET: usize = 49;
+const SQRT_PRICE_OFFSET: usize = 65;
+const TICK_CURRENT_INDEX_OFFSET: usize = 81;
+pub const TOKEN_MINT_A_OFFSET: usize = 101;
+const TOKEN_VAULT_A_OFFSET: usize = 133;
+pub const TOKEN_MINT_B_OFFSET: usize = 181;
+const TOKEN_VAULT_B_OFFSET: usize = 213;
+
+/// Fee rate is in hundredths of a basis point
+const FEE_RATE_DENOMINATOR: f64 = 1_000_000.0;
+
+// TickArray layout: discriminator, start tick index, 88 ticks, whirlpool
+pub const TICK_ARRAY_SIZE: i32 = 88;
+const TICK_ARRAY_START_OFFSET: usize = 8;
+const TICKS_OFFSET: usize = 12;
+/// initialized (1), liquidity_net (16), liquidity_gross (16), two fee
+/// growths (32) and three reward growths (48)
+const TICK_SIZE: usize = 113;
+const TICK_LIQUIDITY_NET_OFFSET: usize = 1;
+
+/// Tick arrays read on each side of the current one. A swap instruction
+/// passes at most three in its direction, so a quote never needs more.
+const TICK_ARRAYS_PER_SIDE: i32 = 2;
+
+/// The parts of a decoded Whirlpool account pricing needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhirlpoolState {
+    pub pool_id: Pubkey,
+    pub tick_spacing: u16,
+    pub fee_rate: u16,
+    /// Liquidity active at the current tick
+    pub liquidity: u128,
+    /// sqrt(price of A in B), Q64.64
+    pub sqrt_price: u128,
+    pub tick_current_index: i32,
+    pub token_mint_a: Pubkey,
+    pub token_vault_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_vault_b: Pubkey,
+}
+
+/// An initialized tick: crossing it changes the active liquidity by `liquidity_net`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitializedTick {
+    pub index: i32,
+    pub liquidity_net: i128,
+}
+
+/// The initialized ticks of one tick array
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickArray {
+    pub start_tick_index: i32,
+    pub ticks: Vec<InitializedTick>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    i32::from_le_bytes(bytes)
+}
+
+fn read_u128(data: &[u8], offset: usize) -> u128 {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&data[offset..offset + 16]);
+    u128::from_le_bytes(bytes)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+/// Q64.64 to a plain number
+fn sqrt_price_from_x64(sqrt_price: u128) -> f64 {
+    sqrt_price as f64 / 2f64.powi(64)
+}
+
+/// sqrt(1.0001^tick)
+fn sqrt_price_at_tick(tick: i32) -> f64 {
+    1.0001f64.powf(tick as f64 / 2.0)
+}
+
+/// First tick of the array holding `tick`
+pub fn tick_array_start(tick: i32, tick_spacing: u16) -> i32 {
+    let ticks_per_array = tick_spacing as i32 * TICK_ARRAY_SIZE;
+    tick.div_euclid(ticks_per_array) * ticks_per_array
+}
+
+pub fn tick_array_address(whirlpool: &Pubkey, start_tick_index: i32) -> Result<Pubkey> {
+    let program_id = Pubkey::from_str(WHIRLPOOL_PROGRAM)?;
+    let (address, _) = Pubkey::find_program_address(
+        &[b"tick_array", whirlpool.as_ref(), start_tick_index.to_string().as_bytes()],
+        &program_id,
+    );
+    Ok(address)
+}
+
+impl WhirlpoolState {
+    /// Decode a Whirlpool account
+    pub fn decode(pool_id: Pubkey, data: &[u8]) -> ClientResult<Self> {
+        if data.len() < WHIRLPOOL_SIZE {
+            return Err(ClientError::InvalidData(format!(
+                "Whirlpool account {} is {} bytes, expected {}",
+                pool_id,
+                data.len(),
+                WHIRLPOOL_SIZE
+            )));
+        }
+
+        Ok(Self {
+            pool_id,
+            tick_spacing: read_u16(data, TICK_SPACING_OFFSET),
+            fee_rate: read_u16(data, FEE_RATE_OFFSET),
+            liquidity: read_u128(data, LIQUIDITY_OFFSET),
+            sqrt_price: read_u128(data, SQRT_PRICE_OFFSET),
+            tick_current_index: read_i32(data, TICK_CURRENT_INDEX_OFFSET),
+            token_mint_a: read_pubkey(data, TOKEN_MINT_A_OFFSET),
+            token_vault_a: read_pubkey(data, TOKEN_VAULT_A_OFFSET),
+            token_mint_b: read_pubkey(data, TOKEN_MINT_B_OFFSET),
+            token_vault_b: read_pubkey(data, TOKEN_VAULT_B_OFFSET),
+        })
+    }
+
+    /// The mint paired with `mint`, None if `mint` isn't in the pool
+    pub fn other_mint(&self, mint: &Pubkey) -> Option<Pubkey> {
+        if *mint == self.token_mint_a {
+            Some(self.token_mint_b)
+        } else if *mint == self.token_mint_b {
+            Some(self.token_mint_a)
+        } else {
+            None
+        }
+    }
+
+    /// Start indexes of the current tick array and its neighbours, lowest first
+    pub fn tick_array_starts(&self) -> Vec<i32> {
+        let ticks_per_array = self.tick_spacing as i32 * TICK_ARRAY_SIZE;
+        let current = tick_array_start(self.tick_current_index, self.tick_spacing);
+        (-TICK_ARRAYS_PER_SIDE..=TICK_ARRAYS_PER_SIDE)
+            .map(|offset| current + offset * ticks_per_array)
+            .collect()
+    }
+
+    /// Spot price of token A in raw units of token B; at CLMM depths only
+    /// meaningful for tiny trades, see `quote_exact_in`
+    pub fn spot_price_a_in_b(&self) -> f64 {
+        let sqrt_price = sqrt_price_from_x64(self.sqrt_price);
+        sqrt_price * sqrt_price
+    }
+
+    /// Spot price of one whole token A in whole tokens B. The sqrt price is
+    /// over raw units, so the decimals difference is scaled back in.
+    pub fn ui_price_a_in_b(&self, decimals_a: u8, decimals_b: u8) -> f64 {
+        self.spot_price_a_in_b() * 10f64.powi(decimals_a as i32 - decimals_b as i32)
+    }
+
+    /// Spot price of `mint` in lamports per raw unit, for a pool pairing it with SOL
+    pub fn spot_price_in_sol(&self, mint: &Pubkey) -> ClientResult<Price> {
+        let sol_mint = Pubkey::from_str(SOL_MINT).map_err(|e| ClientError::InvalidData(e.to_string()))?;
+        let a_in_b = self.spot_price_a_in_b();
+        let price = if *mint == self.token_mint_a && self.token_mint_b == sol_mint {
+            a_in_b
+        } else if *mint == self.token_mint_b && self.token_mint_a == sol_mint {
+            1.0 / a_in_b
+        } else {
+            return Err(ClientError::InvalidData(format!("Whirlpool {} doesn't pair {} with SOL", self.pool_id, mint)));
+        };
+        Price::new(price).ok_or_else(|| ClientError::InvalidData(format!("Whirlpool {} has no usable price", self.pool_id)))
+    }
+
+    /// Output of swapping `amount_in` through the pool, crossing every
+    /// initialized tick in `tick_arrays` on the way and taking the pool fee
+    /// off the input. Fails when the input runs past the loaded arrays.
+    pub fn quote_exact_in(&self, tick_arrays: &[TickArray], amount_in: u64, a_to_b: bool) -> ClientResult<u64> {
+        let mut ticks: Vec<InitializedTick> = tick_arrays.iter().flat_map(|array| array.ticks.iter().copied()).collect();
+        let ticks_per_array = self.tick_spacing as i32 * TICK_ARRAY_SIZE;
+        // The loaded range ends at the edge of the furthest array in the swap direction
+        let boundary = if a_to_b {
+            ticks.retain(|tick| tick.index <= self.tick_current_index);
+            ticks.sort_by_key(|tick| std::cmp::Reverse(tick.index));
+            tick_arrays.iter().map(|array| array.start_tick_index).min()
+        } else {
+            ticks.retain(|tick| tick.index > self.tick_current_index);
+            ticks.sort_by_key(|tick| tick.index);
+            tick_arrays.iter().map(|array| array.start_tick_index + ticks_per_array).max()
+        }
+        .ok_or_else(|| ClientError::InvalidData(format!("No tick arrays loaded for Whirlpool {}", self.pool_id)))?;
+
+        let mut remaining = amount_in as f64 * (1.0 - self.fee_rate as f64 / FEE_RATE_DENOMINATOR);
+        let mut sqrt_price = sqrt_price_from_x64(self.sqrt_price);
+        let mut liquidity = self.liquidity as f64;
+        let mut amount_out = 0.0;
+
+        let targets = ticks
+            .iter()
+            .map(|tick| (tick.index, Some(tick.liquidity_net)))
+            .chain(std::iter::once((boundary, None)));
+        for (target_tick, liquidity_net) in targets {
+            let target = sqrt_price_at_tick(target_tick);
+            if liquidity > 0.0 {
+                if a_to_b {
+                    // Token A in moves 1/sqrt(P) up by in/L, token B out is L * the drop in sqrt(P)
+                    let max_in = liquidity * (1.0 / target - 1.0 / sqrt_price);
+                    if remaining < max_in {
+                        let next = liquidity * sqrt_price / (liquidity + remaining * sqrt_price);
+                        amount_out += liquidity * (sqrt_price - next);
+                        remaining = 0.0;
+                        break;
+                    }
+                    amount_out += liquidity * (sqrt_price - target);
+                    remaining -= max_in;
+                } else {
+                    // Token B in moves sqrt(P) up by in/L, token A out is L * the drop in 1/sqrt(P)
+                    let max_in = liquidity * (target - sqrt_price);
+                    if remaining < max_in {
+                        let next = sqrt_price + remaining / liquidity;
+                        amount_out += liquidity * (1.0 / sqrt_price - 1.0 / next);
+                        remaining = 0.0;
+                        break;
+                    }
+                    amount_out += liquidity * (1.0 / sqrt_price - 1.0 / target);
+                    remaining -= max_in;
+                }
+            }
+            sqrt_price = target;
+            match liquidity_net {
+                // Moving down a tick removes the positions that start there
+                Some(net) if a_to_b => liquidity -= net as f64,
+                Some(net) => liquidity += net as f64,
+                None => break,
+            }
+            liquidity = liquidity.max(0.0);
+        }
+
+        if remaining > 0.0 {
+            return Err(ClientError::InvalidData(format!(
+                "Whirlpool {} can't fill {} within its loaded tick arrays",
+                self.pool_id, amount_in
+            )));
+        }
+        let amount_out = amount_out.floor();
+        if !amount_out.is_finite() || amount_out < 1.0 {
+            return Err(ClientError::TooSmall(amount_in, 0));
+        }
+        Ok(amount_out as u64)
+    }
+}
+
+impl TickArray {
+    /// Decode a TickArray account, keeping only its initialized ticks
+    pub fn decode(data: &[u8], tick_spacing: u16) -> ClientResult<Self> {
+        let expected = TICKS_OFFSET + TICK_ARRAY_SIZE as usize * TICK_SIZE;
+        if data.len() < expected {
+            return Err(ClientError::InvalidData(format!(
+                "Tick array is {} bytes, expected at least {}",
+                data.len(),
+                expected
+            )));
+        }
+        let start_tick_index = read_i32(data, TICK_ARRAY_START_OFFSET);
+        let ticks = (0..TICK_ARRAY_SIZE as usize)
+            .filter_map(|i| {
+                let offset = TICKS_OFFSET + i * TICK_SIZE;
+                (data[offset] != 0).then(|| InitializedTick {
+                    index: start_tick_index + i as i32 * tick_spacing as i32,
+                    liquidity_net: read_u128(data, offset + TICK_LIQUIDITY_NET_OFFSET) as i128,
+                })
+            })
+            .collect();
+        Ok(Self { start_tick_index, ticks })
+    }
+}
+
+/// A Whirlpool with the tick arrays around its current price
+#[derive(Debug, Clone)]
+pub struct LoadedWhirlpool {
+    pub state: WhirlpoolState,
+    pub tick_arrays: Vec<TickArray>,
+    /// Slot the pool account was read at
+    pub slot: u64,
+}
+
+impl LoadedWhirlpool {
+    /// Output of `amount_in` of a SOL-paired pool: lamports in for a buy of
+    /// `mint`, raw `mint` units in for a sell
+    pub fn quote_exact_in(&self, mint: &Pubkey, amount_in: u64, direction: SwapDirection) -> ClientResult<u64> {
+        let token_is_a = *mint == self.state.token_mint_a;
+        // Selling A, or buying B with A, moves the price down
+        let a_to_b = match direction {
+            SwapDirection::Buy => !token_is_a,
+            SwapDirection::Sell => token_is_a,
+        };
+        self.state.quote_exact_in(&self.tick_arrays, amount_in, a_to_b)
+    }
+
+    /// What buying and selling `mint` for `size_lamports` fills at, as a
+    /// bid/ask pair in lamports per raw unit. The spread between the two is
+    /// the fee and price impact at that size, which the spot price hides.
+    pub fn touch_at_size(&self, mint: &Pubkey, size_lamports: u64) -> ClientResult<BookTouch> {
+        let bought = self.quote_exact_in(mint, size_lamports, SwapDirection::Buy)?;
+        let ask = size_lamports as f64 / bought as f64;
+        let sold_for = self.quote_exact_in(mint, bought, SwapDirection::Sell)?;
+        let bid = sold_for as f64 / bought as f64;
+        let invalid = || ClientError::InvalidData(format!("Whirlpool {} gives no usable quote", self.state.pool_id));
+        BookTouch::new(Price::new(bid).ok_or_else(invalid)?, Price::new(ask).ok_or_else(invalid)?).ok_or_else(invalid)
+    }
+}
+
+/// Read a Whirlpool and the tick arrays around its current tick; arrays
+/// that were never initialized are skipped
+pub async fn read_whirlpool(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    pool_id: Pubkey,
+) -> Result<LoadedWhirlpool> {
+    let response = rpc_client.get_account_with_commitment(&pool_id, rpc_client.commitment()).await?;
+    let account = response.value.ok_or_else(|| anyhow!("Whirlpool {} not found", pool_id))?;
+    let state = WhirlpoolState::decode(pool_id, &account.data).map_err(|e| anyhow!("{}", e))?;
+
+    let addresses = state
+        .tick_array_starts()
+        .into_iter()
+        .map(|start| tick_array_address(&pool_id, start))
+        .collect::<Result<Vec<_>>>()?;
+    let tick_arrays = rpc_client
+        .get_multiple_accounts(&addresses)
+        .await?
+        .into_iter()
+        .flatten()
+        .filter_map(|account| TickArray::decode(&account.data, state.tick_spacing).ok())
+        .collect();
+    Ok(LoadedWhirlpool { state, tick_arrays, slot: response.context.slot })
+}
+
+/// The SOL-paired Whirlpool of `mint` with the most active liquidity
+pub async fn find_sol_whirlpool(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    mint: Pubkey,
+) -> Result<WhirlpoolState> {
+    let program_id = Pubkey::from_str(WHIRLPOOL_PROGRAM)?;
+    let sol_mint = Pubkey::from_str(SOL_MINT)?;
+
+    let mut deepest: Option<WhirlpoolState> = None;
+    // Whirlpools order their mints, so SOL may be either side
+    for (token_offset, sol_offset) in [(TOKEN_MINT_A_OFFSET, TOKEN_MINT_B_OFFSET), (TOKEN_MINT_B_OFFSET, TOKEN_MINT_A_OFFSET)] {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(WHIRLPOOL_SIZE as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(token_offset, &mint.to_bytes())),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(sol_offset, &sol_mint.to_bytes())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: Some(false),
+        };
+        for (pubkey, account) in rpc_client.get_program_accounts_with_config(&program_id, config).await? {
+            let Ok(state) = WhirlpoolState::decode(pubkey, &account.data) else { continue };
+            if deepest.as_ref().map_or(true, |best| state.liquidity > best.liquidity) {
+                deepest = Some(state);
+            }
+        }
+    }
+    deepest.ok_or_else(|| anyhow!("No Whirlpool pairs {} with SOL", mint))
+}
+
+/// Price of one whole `mint` token in SOL, from the sqrt price of its
+/// deepest SOL Whirlpool. Concentrated liquidity has no reserves to divide,
+/// and this is the spot price, not what a trade fills at.
+pub async fn get_token_price(
+    rpc_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    mint_str: &str,
+) -> Result<f64> {
+    let mint = Pubkey::from_str(mint_str).map_err(|_| anyhow!("Invalid mint address"))?;
+    let state = find_sol_whirlpool(Arc::clone(&rpc_client), mint).await?;
+    let decimals_a = mint_decimals(&rpc_client, &state.token_mint_a).await?;
+    let decimals_b = mint_decimals(&rpc_client, &state.token_mint_b).await?;
+    let a_in_b = state.ui_price_a_in_b(decimals_a, decimals_b);
+    let price = if mint == state.token_mint_a { a_in_b } else { 1.0 / a_in_b };
+    Price::new(price)
+        .map(|price| price.value())
+        .ok_or_else(|| anyhow!("Whirlpool {} has no usable price", state.pool_id))
+}
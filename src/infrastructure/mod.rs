@@ -5,6 +5,7 @@ pub mod services;
 pub mod record {
     pub use crate::record::transaction_logger::*;
     pub use crate::record::transaction_streamer::*;
+    pub use crate::record::snapshot_recorder::*;
 }
 
 
@@ -2,3 +2,5 @@ pub use crate::services::jito::*;
 pub use crate::services::nozomi::*;
 pub use crate::services::zeroslot::*;
 
+pub use crate::services::notifier::*;
+pub use crate::services::rpc_pool::*;
@@ -1,10 +1,17 @@
 use anyhow::{anyhow, Result};
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::{seq::IteratorRandom, thread_rng};
 use serde::Deserialize;
 use serde_json::Value;
 use anchor_client::solana_sdk::pubkey::Pubkey;
-use std::{future::Future, str::FromStr, sync::LazyLock, time::Duration};
+use std::{
+    future::Future,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LazyLock,
+    },
+    time::Duration,
+};
 use tokio::time::{sleep, Instant};
 
 use crate::common::config::import_env_var;
@@ -12,29 +19,60 @@ use crate::common::config::import_env_var;
 pub static BLOCK_ENGINE_URL: LazyLock<String> =
     LazyLock::new(|| import_env_var("JITO_BLOCK_ENGINE_URL"));
 
+/// Jito's tip accounts
+pub const TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+];
+
+static TIP_ROTATION: LazyLock<TipRotation> = LazyLock::new(TipRotation::jito);
+
+/// Hands out tip accounts in turn, so back-to-back tips don't all
+/// write-lock the same account and queue behind each other
+#[derive(Debug)]
+pub struct TipRotation {
+    accounts: Vec<Pubkey>,
+    next: AtomicUsize,
+}
+
+impl TipRotation {
+    pub fn new(accounts: Vec<Pubkey>) -> Self {
+        Self { accounts, next: AtomicUsize::new(0) }
+    }
+
+    /// Over `TIP_ACCOUNTS`, starting with the first
+    pub fn jito() -> Self {
+        Self::new(
+            TIP_ACCOUNTS
+                .iter()
+                .map(|account| Pubkey::from_str(account).expect("valid Jito tip account"))
+                .collect(),
+        )
+    }
+
+    /// The account after the one handed out last, None when there are none
+    pub fn next(&self) -> Option<Pubkey> {
+        if self.accounts.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.accounts.len();
+        Some(self.accounts[index])
+    }
+}
+
+/// The next tip account in rotation, with the account `JITO_PRIORITY_FEE` is paid to
 pub fn get_tip_account() -> Result<(Pubkey, Pubkey)> {
-    let accounts = [
-        "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5".to_string(),
-        "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt".to_string(),
-        "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL".to_string(),
-        "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT".to_string(),
-        "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe".to_string(),
-        "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh".to_string(),
-        "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49".to_string(),
-        "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY".to_string(),
-    ];
-    let mut rng = thread_rng();
-    let tip_account = match accounts.iter().choose(&mut rng) {
-        Some(acc) => Ok(Pubkey::from_str(acc).inspect_err(|err| {
-            println!("jito: failed to parse Pubkey: {:?}", err);
-        })?),
-        None => Err(anyhow!("jito: no tip accounts available")),
-    };
+    let tip_account = TIP_ROTATION.next().ok_or_else(|| anyhow!("jito: no tip accounts available"))?;
     let tip1_account = Pubkey::from_str("JitoFSvbiCrygnx4HZzau4LdeyBU6VUeyf9jt8F8bMk")
         .inspect_err(|err| {
             println!("jito: failed to parse Pubkey: {:?}", err);
         })?;
-    let tip_account = tip_account?;
     Ok((tip_account, tip1_account))
 }
 // unit sol
@@ -297,3 +335,49 @@ impl JitoClient {
         Ok(response_data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn consecutive_tips_rotate_through_the_accounts_in_order() {
+        let accounts: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let rotation = TipRotation::new(accounts.clone());
+        let handed_out: Vec<Pubkey> = (0..7).map(|_| rotation.next().unwrap()).collect();
+        assert_eq!(
+            handed_out,
+            [accounts[0], accounts[1], accounts[2], accounts[0], accounts[1], accounts[2], accounts[0]]
+        );
+        assert_eq!(TipRotation::new(Vec::new()).next(), None);
+    }
+
+    #[test]
+    fn jito_rotation_starts_at_the_first_tip_account_and_wraps() {
+        let rotation = TipRotation::jito();
+        let round: Vec<String> = (0..TIP_ACCOUNTS.len()).map(|_| rotation.next().unwrap().to_string()).collect();
+        assert_eq!(round, TIP_ACCOUNTS);
+        assert_eq!(rotation.next().unwrap().to_string(), TIP_ACCOUNTS[0]);
+    }
+
+    #[test]
+    fn concurrent_senders_spread_evenly_over_the_accounts() {
+        let rotation = Arc::new(TipRotation::jito());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let rotation = Arc::clone(&rotation);
+                std::thread::spawn(move || (0..200).map(|_| rotation.next().unwrap()).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut uses: HashMap<Pubkey, usize> = HashMap::new();
+        for thread in threads {
+            for account in thread.join().unwrap() {
+                *uses.entry(account).or_default() += 1;
+            }
+        }
+        assert_eq!(uses.len(), TIP_ACCOUNTS.len());
+        assert!(uses.values().all(|count| *count == 100), "{:?}", uses);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod jito;
 pub mod nozomi;
 pub mod zeroslot;
+pub mod notifier;
+pub mod rpc_pool;
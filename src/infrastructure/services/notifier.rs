@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+use colored::Colorize;
+use serde_json::json;
+
+use crate::common::logger::Logger;
+use crate::common::sync::LockExt;
+
+/// Alerts a recording notifier has sent, as (title, body)
+pub type SentAlerts = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Operator alerts, logged locally and posted to `NOTIFY_WEBHOOK_URL` when set
+///
+/// The webhook receives `{"text": ...}`, which Slack and most chat bridges accept.
+#[derive(Clone)]
+pub struct Notifier {
+    webhook_url: Option<String>,
+    http: reqwest::Client,
+    logger: Logger,
+    sent: Option<SentAlerts>,
+}
+
+impl Notifier {
+    pub fn new(webhook_url: Option<String>) -> Self {
+        Self {
+            webhook_url,
+            http: reqwest::Client::new(),
+            logger: Logger::new("[NOTIFIER] => ".magenta().bold().to_string()),
+            sent: None,
+        }
+    }
+
+    /// A notifier that posts nowhere and keeps every alert, for tests
+    pub fn recording() -> (Self, SentAlerts) {
+        let sent = SentAlerts::default();
+        (Self { sent: Some(Arc::clone(&sent)), ..Self::new(None) }, sent)
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("NOTIFY_WEBHOOK_URL").ok().filter(|v| !v.trim().is_empty()))
+    }
+
+    /// Send an alert; delivery failures are logged and never propagated
+    pub async fn alert(&self, title: &str, body: &str) {
+        self.logger.error(format!("[ALERT] {} => {}", title, body).red().bold().to_string());
+        if let Some(sent) = &self.sent {
+            sent.lock_or_recover().push((title.to_string(), body.to_string()));
+        }
+
+        let Some(url) = &self.webhook_url else {
+            return;
+        };
+
+        let payload = json!({ "text": format!("*{}*\n{}", title, body) });
+        if let Err(e) = self.http.post(url).json(&payload).send().await {
+            self.logger.log(format!("Failed to deliver alert: {}", e).red().to_string());
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anyhow::{anyhow, Result};
+use tokio::time::Instant;
+
+use crate::common::clock::Clock;
+use crate::common::metrics::{RPC_PACING_RATE, RPC_THROTTLED_TOTAL};
+use crate::common::sync::LockExt;
+
+/// What an RPC call is for, which decides who goes first when capacity is short
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallClass {
+    /// Selling a position
+    Exit,
+    /// Confirming or re-verifying a sent transaction
+    Confirm,
+    /// Quotes and blockhashes for a trade about to be sent
+    Quote,
+    /// Periodic pool/price refresh
+    Refresh,
+    /// Pool discovery scans
+    Discovery,
+}
+
+impl CallClass {
+    /// Background work that should slow down rather than compete with trading
+    pub fn is_background(&self) -> bool {
+        matches!(self, Self::Refresh | Self::Discovery)
+    }
+}
+
+/// Token bucket limits for one endpoint
+#[derive(Debug, Clone)]
+pub struct PacerConfig {
+    /// Requests per second when the endpoint is healthy
+    pub max_rate: f64,
+    /// Floor the rate never shrinks below
+    pub min_rate: f64,
+    /// Bucket size, the burst allowed after an idle period
+    pub burst: f64,
+    /// Rate multiplier applied on each 429
+    pub decrease_factor: f64,
+    /// Rate added back per successful call
+    pub increase_step: f64,
+    /// Share of the bucket background calls must leave for exit/confirm calls
+    pub background_reserve: f64,
+}
+
+impl PacerConfig {
+    /// `RPC_MAX_RPS` caps the rate, everything else uses defaults
+    pub fn from_env() -> Self {
+        let max_rate = std::env::var("RPC_MAX_RPS")
+            .ok()
+            .and_then(|v| f64::from_str(&v).ok())
+            .filter(|v| *v > 0.0)
+            .unwrap_or(40.0);
+        Self {
+            max_rate,
+            min_rate: (max_rate / 40.0).max(0.5),
+            burst: max_rate.max(1.0),
+            decrease_factor: 0.5,
+            increase_step: (max_rate / 100.0).max(0.1),
+            background_reserve: 0.5,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PacerState {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket whose refill rate shrinks multiplicatively on 429s and timeouts
+/// and grows back additively on success (AIMD)
+#[derive(Debug)]
+pub struct AdaptivePacer {
+    config: PacerConfig,
+    state: Mutex<PacerState>,
+}
+
+impl AdaptivePacer {
+    pub fn new(config: PacerConfig, now: Instant) -> Self {
+        let state = PacerState {
+            rate: config.max_rate,
+            tokens: config.burst,
+            last_refill: now,
+        };
+        Self { config, state: Mutex::new(state) }
+    }
+
+    fn refill(&self, state: &mut PacerState, now: Instant) {
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * state.rate).min(self.config.burst);
+        state.last_refill = now;
+    }
+
+    /// Take a token for `class`, or return how long to wait before trying again.
+    /// While the rate is cut, background calls must leave part of the bucket
+    /// for exit and confirmation calls.
+    pub fn try_acquire(&self, class: CallClass, now: Instant) -> std::result::Result<(), Duration> {
+        let mut state = self.state.lock_or_recover();
+        self.refill(&mut state, now);
+
+        let constrained = state.rate < self.config.max_rate;
+        let needed = if class.is_background() && constrained {
+            (1.0 + self.config.burst * self.config.background_reserve).min(self.config.burst)
+        } else {
+            1.0
+        };
+
+        if state.tokens >= needed {
+            state.tokens -= 1.0;
+            return Ok(());
+        }
+        let missing = needed - state.tokens;
+        Err(Duration::from_secs_f64(missing / state.rate))
+    }
+
+    pub fn on_success(&self) {
+        let mut state = self.state.lock_or_recover();
+        state.rate = (state.rate + self.config.increase_step).min(self.config.max_rate);
+    }
+
+    pub fn on_throttled(&self) {
+        let mut state = self.state.lock_or_recover();
+        state.rate = (state.rate * self.config.decrease_factor).max(self.config.min_rate);
+        // Drop the burst too, the provider is already unhappy
+        state.tokens = state.tokens.min(1.0);
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.state.lock_or_recover().rate
+    }
+
+    /// How much slower than healthy this endpoint currently runs, >= 1.0
+    pub fn slowdown(&self) -> f64 {
+        self.config.max_rate / self.rate()
+    }
+}
+
+/// How a failed call should be counted against the endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    RateLimited,
+    Timeout,
+    Other,
+}
+
+pub fn classify_error(error: &str) -> FailureKind {
+    let lower = error.to_ascii_lowercase();
+    if lower.contains("429") || lower.contains("too many requests") || lower.contains("rate limit") {
+        FailureKind::RateLimited
+    } else if lower.contains("timed out") || lower.contains("timeout") {
+        FailureKind::Timeout
+    } else {
+        FailureKind::Other
+    }
+}
+
+/// One RPC endpoint and its pacer
+pub struct RpcEndpoint<C> {
+    pub url: String,
+    pub client: Arc<C>,
+    pub pacer: AdaptivePacer,
+}
+
+/// RPC endpoints with per-endpoint adaptive pacing
+///
+/// Generic over the client so tests can drive it with a mock transport.
+pub struct RpcPool<C = RpcClient> {
+    endpoints: Vec<RpcEndpoint<C>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RpcPool<RpcClient> {
+    /// `RPC_HTTP` plus any comma-separated `RPC_POOL_URLS`
+    pub fn from_env(clock: Arc<dyn Clock>) -> Self {
+        let mut urls = vec![std::env::var("RPC_HTTP").unwrap_or_default()];
+        if let Ok(extra) = std::env::var("RPC_POOL_URLS") {
+            urls.extend(extra.split(',').map(|u| u.trim().to_string()).filter(|u| !u.is_empty()));
+        }
+        urls.retain(|u| !u.is_empty());
+        urls.dedup();
+
+        let config = PacerConfig::from_env();
+        let clients = urls
+            .into_iter()
+            .map(|url| {
+                let client = RpcClient::new_with_commitment(url.clone(), CommitmentConfig::processed());
+                (url, Arc::new(client))
+            })
+            .collect();
+        Self::new(clients, config, clock)
+    }
+}
+
+impl<C> RpcPool<C> {
+    pub fn new(clients: Vec<(String, Arc<C>)>, config: PacerConfig, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        let endpoints = clients
+            .into_iter()
+            .map(|(url, client)| {
+                RPC_PACING_RATE.with_label_values(&[&url]).set(config.max_rate);
+                RpcEndpoint {
+                    url,
+                    client,
+                    pacer: AdaptivePacer::new(config.clone(), now),
+                }
+            })
+            .collect();
+        Self { endpoints, clock }
+    }
+
+    pub fn endpoints(&self) -> &[RpcEndpoint<C>] {
+        &self.endpoints
+    }
+
+    /// Current pacing rate per endpoint, in requests per second
+    pub fn pacing_rates(&self) -> Vec<(String, f64)> {
+        self.endpoints.iter().map(|e| (e.url.clone(), e.pacer.rate())).collect()
+    }
+
+    /// Interval a background loop should use instead of `base`, stretched by
+    /// how far the healthiest endpoint is throttled
+    pub fn background_interval(&self, base: Duration) -> Duration {
+        let slowdown = self
+            .endpoints
+            .iter()
+            .map(|e| e.pacer.slowdown())
+            .fold(f64::INFINITY, f64::min);
+        if !slowdown.is_finite() {
+            return base;
+        }
+        base.mul_f64(slowdown.max(1.0))
+    }
+
+    /// Wait for a token on any endpoint, preferring the one with the highest rate
+    async fn acquire(&self, class: CallClass, skip: &[usize]) -> Option<usize> {
+        loop {
+            let now = self.clock.now();
+            let mut order: Vec<usize> = (0..self.endpoints.len()).filter(|i| !skip.contains(i)).collect();
+            if order.is_empty() {
+                return None;
+            }
+            order.sort_by(|a, b| {
+                self.endpoints[*b]
+                    .pacer
+                    .rate()
+                    .partial_cmp(&self.endpoints[*a].pacer.rate())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            let mut shortest_wait = Duration::MAX;
+            for index in order {
+                match self.endpoints[index].pacer.try_acquire(class, now) {
+                    Ok(()) => return Some(index),
+                    Err(wait) => shortest_wait = shortest_wait.min(wait),
+                }
+            }
+            self.clock.sleep(shortest_wait.max(Duration::from_millis(1))).await;
+        }
+    }
+
+    /// Run `call` against a paced endpoint.
+    ///
+    /// Pacing never fails a call, it only delays it. A 429 or timeout shrinks
+    /// that endpoint's rate and the call moves on to the next endpoint. Once
+    /// every endpoint has throttled, exit/confirm calls go around once more and
+    /// background calls a few more times, each round waiting on the slower bucket.
+    pub async fn call<T, E, F, Fut>(&self, class: CallClass, call: F) -> Result<T>
+    where
+        E: Display,
+        F: Fn(Arc<C>) -> Fut,
+        Fut: Future<Output = std::result::Result<T, E>>,
+    {
+        let mut tried = Vec::new();
+        let mut rounds = match class {
+            CallClass::Exit | CallClass::Confirm => 2,
+            CallClass::Refresh | CallClass::Discovery => 3,
+            CallClass::Quote => 1,
+        };
+        let mut last_error = None;
+
+        loop {
+            let Some(index) = self.acquire(class, &tried).await else {
+                rounds -= 1;
+                if rounds == 0 {
+                    break;
+                }
+                tried.clear();
+                continue;
+            };
+            let endpoint = &self.endpoints[index];
+            match call(Arc::clone(&endpoint.client)).await {
+                Ok(value) => {
+                    endpoint.pacer.on_success();
+                    RPC_PACING_RATE.with_label_values(&[&endpoint.url]).set(endpoint.pacer.rate());
+                    return Ok(value);
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    let label = match classify_error(&message) {
+                        FailureKind::RateLimited => "429",
+                        FailureKind::Timeout => "timeout",
+                        // Not a capacity problem, another endpoint won't do better
+                        FailureKind::Other => return Err(anyhow!(message)),
+                    };
+                    endpoint.pacer.on_throttled();
+                    RPC_THROTTLED_TOTAL.with_label_values(&[&endpoint.url, label]).inc();
+                    RPC_PACING_RATE.with_label_values(&[&endpoint.url]).set(endpoint.pacer.rate());
+                    tried.push(index);
+                    last_error = Some(message);
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "All RPC endpoints throttled: {}",
+            last_error.unwrap_or_else(|| "no endpoints configured".to_string())
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::common::clock::MockClock;
+
+    fn config() -> PacerConfig {
+        PacerConfig {
+            max_rate: 10.0,
+            min_rate: 0.5,
+            burst: 10.0,
+            decrease_factor: 0.5,
+            increase_step: 1.0,
+            background_reserve: 0.5,
+        }
+    }
+
+    /// Answers 429 to the next `throttled` requests, then serves
+    #[derive(Default)]
+    struct MockTransport {
+        throttled: AtomicUsize,
+        served: AtomicUsize,
+    }
+
+    impl MockTransport {
+        fn throttling(requests: usize) -> Arc<Self> {
+            let transport = Self::default();
+            transport.throttled.store(requests, Ordering::SeqCst);
+            Arc::new(transport)
+        }
+
+        fn request(&self) -> std::result::Result<(), String> {
+            let throttle = self
+                .throttled
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1))
+                .is_ok();
+            if throttle {
+                return Err("HTTP status client error (429 Too Many Requests)".to_string());
+            }
+            self.served.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn pool(transports: &[(&str, Arc<MockTransport>)]) -> RpcPool<MockTransport> {
+        let clients = transports.iter().map(|(url, t)| (url.to_string(), Arc::clone(t))).collect();
+        RpcPool::new(clients, config(), Arc::new(MockClock::new()))
+    }
+
+    async fn request(pool: &RpcPool<MockTransport>, class: CallClass) -> Result<()> {
+        pool.call(class, |client| async move { client.request() }).await
+    }
+
+    #[test]
+    fn rate_halves_on_each_429_and_grows_back_one_step_per_success() {
+        let pacer = AdaptivePacer::new(config(), Instant::now());
+        for expected in [5.0, 2.5, 1.25, 0.625, 0.5, 0.5] {
+            pacer.on_throttled();
+            assert_eq!(pacer.rate(), expected);
+        }
+        assert_eq!(pacer.slowdown(), 20.0);
+        for expected in [1.5, 2.5, 3.5] {
+            pacer.on_success();
+            assert_eq!(pacer.rate(), expected);
+        }
+        (0..20).for_each(|_| pacer.on_success());
+        assert_eq!(pacer.rate(), 10.0);
+    }
+
+    #[test]
+    fn throttled_bucket_keeps_room_for_exits() {
+        let now = Instant::now();
+        let pacer = AdaptivePacer::new(config(), now);
+        // Healthy: background calls take tokens like anything else
+        assert!(pacer.try_acquire(CallClass::Discovery, now).is_ok());
+
+        pacer.on_throttled();
+        let wait = pacer.try_acquire(CallClass::Discovery, now).unwrap_err();
+        // Six tokens needed, one left, refilling at 5/s
+        assert_eq!(wait, Duration::from_secs(1));
+        assert!(pacer.try_acquire(CallClass::Exit, now).is_ok());
+        assert!(pacer.try_acquire(CallClass::Confirm, now).is_err());
+    }
+
+    #[tokio::test]
+    async fn exit_call_moves_past_a_throttled_endpoint() {
+        let busy = MockTransport::throttling(usize::MAX);
+        let healthy = MockTransport::throttling(0);
+        let pool = pool(&[("busy", Arc::clone(&busy)), ("healthy", Arc::clone(&healthy))]);
+
+        request(&pool, CallClass::Exit).await.unwrap();
+        request(&pool, CallClass::Confirm).await.unwrap();
+
+        assert_eq!(healthy.served.load(Ordering::SeqCst), 2);
+        let rates: Vec<_> = pool.pacing_rates();
+        // Tried first while both were at full rate, then ordered behind the healthy one
+        assert_eq!(rates, [("busy".to_string(), 5.0), ("healthy".to_string(), 10.0)]);
+    }
+
+    #[tokio::test]
+    async fn background_calls_wait_out_a_burst_instead_of_failing() {
+        let transport = MockTransport::throttling(2);
+        let pool = pool(&[("only", Arc::clone(&transport))]);
+
+        request(&pool, CallClass::Refresh).await.unwrap();
+        // Two 429s took it to 2.5, the success added a step back
+        assert_eq!(pool.pacing_rates()[0].1, 3.5);
+        assert!(pool.background_interval(Duration::from_secs(7)) > Duration::from_secs(7));
+
+        for _ in 0..10 {
+            request(&pool, CallClass::Exit).await.unwrap();
+        }
+        assert_eq!(pool.pacing_rates()[0].1, 10.0);
+        assert_eq!(pool.background_interval(Duration::from_secs(7)), Duration::from_secs(7));
+    }
+
+    #[tokio::test]
+    async fn quote_gives_up_once_every_endpoint_throttled() {
+        let pool = pool(&[("only", MockTransport::throttling(usize::MAX))]);
+        let error = request(&pool, CallClass::Quote).await.unwrap_err();
+        assert!(error.to_string().starts_with("All RPC endpoints throttled"));
+    }
+}
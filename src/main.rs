@@ -1,68 +1,747 @@
 use solana_vntr_sniper::{
-    shared::{config::Config, constants::RUN_MSG},
-    domain::token::{TokenModel, TokenMetadata, find_pools_for_token},
+    shared::{config::{slippage_to_bps, ArbitrageSettings, Config, CURRENT_CONFIG_VERSION, POOL_CACHE_PATH}, constants::RUN_MSG, format::{fmt_lamports_exact, fmt_pct}, profiling},
+    domain::{priority_fee::PriorityFeeStrategy, token::{TokenModel, TokenMetadata, find_pools_for_token}},
     infrastructure::dex::{DEXRegistry, identify_dex_from_pool},
-    application::monitoring::arbitrage_monitor,
+    application::monitoring::{arbitrage_monitor, copy_trader_pumpfun, new_token_trader_pumpfun},
+    application::{admin, analyze, arbitrage_alt, blockhash_cache, capital, doctor, dormancy, inventory, leader, liquidation, math_verifier, quote_currency, refresh_scheduler, sweep, tui},
+    application::supervisor::{supervise, SupervisorConfig},
+    application::pool_discovery::{initialize_pool_cache, PoolCache},
+    application::pool_health::HealthConfig,
+    record::{
+        journal::{JournalScope, JournalSource},
+        kv::{self, KvStore},
+        opportunities::{self as legacy_opportunities, AfterMigration},
+        price_board::PriceBoardReader,
+        redact::{self, RedactionConfig, Redactor},
+        store::RecordStore,
+        tax_export::{self, LotMethod},
+    },
 };
-use anchor_client::solana_sdk::pubkey::Pubkey;
-use std::{str::FromStr, sync::Arc};
+use anchor_client::solana_sdk::{pubkey::Pubkey, signer::Signer};
+use std::{collections::BTreeMap, io::IsTerminal, str::FromStr, sync::Arc};
 use chrono::Utc;
 use tokio::time::{sleep, Duration};
 use solana_vntr_sniper::shared::config::SwapConfig;
 use solana_vntr_sniper::application::swapping::SwapDirection;
 use solana_vntr_sniper::application::swapping::SwapInType;
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(name = "solana-vntr-sniper", about = "Multi-DEX arbitrage bot for Solana")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Show a live status screen instead of scrolling logs; ignored when stdout is not a terminal
+    #[arg(long, global = true)]
+    tui: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the arbitrage monitor (default)
+    #[command(alias = "run")]
+    Arbitrage {
+        /// Spread in percent worth acting on, net of fees; overrides ARBITRAGE_THRESHOLD
+        #[arg(long)]
+        threshold: Option<f64>,
+        /// SOL liquidity in lamports a pool needs; overrides MIN_LIQUIDITY
+        #[arg(long)]
+        min_liquidity: Option<u64>,
+        /// Token mints to scan, comma separated; overrides MONITOR_TOKEN_MINTS
+        #[arg(long, value_delimiter = ',')]
+        mints: Vec<String>,
+        /// SOL per trade before sizing from pool reserves
+        #[arg(long)]
+        amount: Option<f64>,
+        /// Slippage in basis points
+        #[arg(long)]
+        slippage: Option<u64>,
+    },
+    /// Copy the buys and sells of the target wallets
+    CopyTrade {
+        /// Wallets to copy, comma separated; overrides COPY_TRADING_TARGET_ADDRESS
+        #[arg(long, value_delimiter = ',')]
+        targets: Vec<String>,
+        /// Overrides TOKEN_AMOUNT
+        #[arg(long)]
+        amount: Option<f64>,
+        /// Slippage in basis points; overrides SLIPPAGE
+        #[arg(long)]
+        slippage: Option<u64>,
+    },
+    /// Buy new PumpFun tokens whose dev buy is within MIN_DEV_BUY..MAX_DEV_BUY
+    Snipe {
+        /// Overrides TOKEN_AMOUNT
+        #[arg(long)]
+        amount: Option<f64>,
+        /// Slippage in basis points; overrides SLIPPAGE
+        #[arg(long)]
+        slippage: Option<u64>,
+    },
+    /// Find the pools of each mint on every supported DEX, add them to the pool cache, print them and exit
+    DiscoverPools {
+        #[arg(required = true)]
+        mints: Vec<String>,
+    },
+    /// Force-sell every open position at EMERGENCY_SLIPPAGE, stop buying and exit
+    Liquidate,
+    /// Check config, gRPC and RPC connectivity, the wallet and the pool cache, then exit
+    Doctor,
+    /// Manage the address lookup table arbitrage transactions use (ARBITRAGE_ALT)
+    Alt {
+        #[command(subcommand)]
+        action: AltAction,
+    },
+    /// Manage the trade journal
+    Journal {
+        #[command(subcommand)]
+        action: JournalAction,
+    },
+    /// Print recorded trades with their journal notes in time order
+    Analyze {
+        /// Only show this token
+        #[arg(long)]
+        mint: Option<String>,
+        /// Show why detected opportunities were rejected instead of the trade journal
+        #[arg(long)]
+        rejections: bool,
+        /// Show the daily distribution of best cross-DEX spreads per token
+        #[arg(long, conflicts_with = "rejections")]
+        spreads: bool,
+        /// List programs copy targets used that have no DEX adapter, with how often each was seen and skipped
+        #[arg(long, conflicts_with_all = ["rejections", "spreads"])]
+        unknown_programs: bool,
+        /// Show how far local swap math was from RPC simulation per venue and day
+        #[arg(long, conflicts_with_all = ["rejections", "spreads", "unknown_programs"])]
+        math: bool,
+        /// Show everything recorded about one opportunity, by opportunity or trade id
+        #[arg(long, conflicts_with_all = ["mint", "rejections", "spreads", "unknown_programs", "math"])]
+        trace: Option<String>,
+    },
+    /// Write per-disposal records with cost basis to CSV
+    ExportTax {
+        /// Lot matching: fifo or average
+        #[arg(long, default_value = "fifo")]
+        method: String,
+        /// Only disposals in this calendar year (UTC)
+        #[arg(long)]
+        year: Option<i32>,
+        #[arg(long, default_value = "tax_export.csv")]
+        out: String,
+    },
+    /// Copy recorded trades, journal, rejections and record files with addresses pseudonymized
+    ExportRedacted {
+        /// Redaction config listing the addresses to pseudonymize
+        #[arg(long, default_value = "redaction.json")]
+        config: String,
+        #[arg(long, default_value = "redacted_export")]
+        out: String,
+        /// Pseudonym to address mapping; keep it local, it must be outside --out
+        #[arg(long, default_value = "redaction_mapping.csv")]
+        mapping: String,
+    },
+    /// List cached pools with their health score, healthiest first
+    Pools {
+        /// Only show pools of this token
+        #[arg(long)]
+        mint: Option<String>,
+    },
+    /// Import the legacy arbitrage_opportunities/*.json files into the record store
+    MigrateOpportunities {
+        #[arg(long, default_value = "arbitrage_opportunities")]
+        dir: String,
+        /// Delete files once they are in the store
+        #[arg(long)]
+        delete: bool,
+        /// Move files into this directory once they are in the store
+        #[arg(long, conflicts_with = "delete")]
+        archive: Option<String>,
+    },
+    /// Dump or load the embedded state store as JSON; the bot must be stopped
+    Kv {
+        #[command(subcommand)]
+        action: KvAction,
+    },
+    /// Read the shared-memory price board
+    Priceboard {
+        #[command(subcommand)]
+        action: PriceboardAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum PriceboardAction {
+    /// Print every pool currently on the board
+    Dump {
+        /// Board file, defaults to PRICE_BOARD_PATH
+        #[arg(long)]
+        path: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AltAction {
+    /// Create a lookup table owned by the wallet and print its address
+    Create,
+    /// Add addresses to a table the wallet owns; cached pools of --mint when none are given
+    Extend {
+        /// Table to extend, defaults to ARBITRAGE_ALT
+        #[arg(long)]
+        table: Option<String>,
+        /// Add every cached pool of this token
+        #[arg(long)]
+        mint: Option<String>,
+        addresses: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KvAction {
+    /// Write every namespace to a JSON file
+    Export {
+        #[arg(long, default_value = "kv_export.json")]
+        out: String,
+    },
+    /// Load a JSON file written by export, overwriting the keys it contains
+    Import {
+        file: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum JournalAction {
+    /// Attach a note to the session, a mint (--mint) or a trade (--trade)
+    Add {
+        #[arg(long)]
+        mint: Option<String>,
+        #[arg(long)]
+        trade: Option<String>,
+        /// key=value, repeatable
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        note: String,
+    },
+}
+
+/// Flags that stand in for env vars the modes read deep inside; set before the
+/// config loads, which leaves variables that are already set alone
+fn apply_env_overrides(command: &Command) {
+    match command {
+        Command::Arbitrage { threshold, min_liquidity, mints, .. } => {
+            if let Some(threshold) = threshold {
+                std::env::set_var("ARBITRAGE_THRESHOLD", threshold.to_string());
+            }
+            if let Some(min_liquidity) = min_liquidity {
+                std::env::set_var("MIN_LIQUIDITY", min_liquidity.to_string());
+            }
+            if !mints.is_empty() {
+                std::env::set_var("MONITOR_TOKEN_MINTS", mints.join(","));
+            }
+        }
+        Command::CopyTrade { targets, .. } if !targets.is_empty() => {
+            std::env::set_var("COPY_TRADING_TARGET_ADDRESS", targets.join(","));
+            std::env::set_var("IS_MULTI_COPY_TRADING", (targets.len() > 1).to_string());
+        }
+        _ => {}
+    }
+}
+
+/// `--amount` and `--slippage` over a mode's swap config
+fn override_swap_config(mut swap_config: SwapConfig, amount: Option<f64>, slippage: Option<u64>) -> anyhow::Result<SwapConfig> {
+    if let Some(amount) = amount {
+        if amount.is_nan() || amount <= 0.0 {
+            return Err(anyhow::anyhow!("--amount must be above zero, got {}", amount));
+        }
+        swap_config.amount_in = amount;
+    }
+    if let Some(slippage) = slippage {
+        swap_config.slippage_bps = slippage_to_bps("--slippage", slippage, CURRENT_CONFIG_VERSION)?;
+    }
+    Ok(swap_config)
+}
+
+/// Journal, analyze, exports, pool listing, migrations, kv dumps, price board reads and the doctor don't need the full bot config
+async fn run_offline_command(command: &Command) -> anyhow::Result<bool> {
+    match command {
+        Command::Doctor => {
+            let report = doctor::run_doctor(true).await;
+            report.print();
+            if !report.ok() {
+                std::process::exit(1);
+            }
+            Ok(true)
+        }
+        Command::Journal { action: JournalAction::Add { mint, trade, tags, note } } => {
+            let store = RecordStore::open_from_env()?;
+            let scope = match (trade, mint) {
+                (Some(trade_id), _) => JournalScope::Trade { trade_id: trade_id.clone() },
+                (None, Some(mint)) => JournalScope::Mint { mint: mint.clone() },
+                (None, None) => JournalScope::Session,
+            };
+            let mut tag_map = BTreeMap::new();
+            for tag in tags {
+                let (key, value) = tag
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("Tag must be key=value: {}", tag))?;
+                tag_map.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            let id = store.add_journal_entry(&scope, note, &tag_map, JournalSource::User)?;
+            println!("Journal entry {} added", id);
+            Ok(true)
+        }
+        Command::Analyze { mint, rejections, spreads, unknown_programs, math, trace } => {
+            let store = RecordStore::open_from_env()?;
+            if let Some(id) = trace {
+                analyze::print_trace(&store, id, std::path::Path::new("arbitrage_opportunities"))?;
+            } else if *math {
+                analyze::print_math_report(&store)?;
+            } else if *unknown_programs {
+                analyze::print_unknown_programs(&store)?;
+            } else if *spreads {
+                analyze::print_spread_report(&store, mint.as_deref())?;
+            } else if *rejections {
+                analyze::print_rejection_report(&store, mint.as_deref())?;
+            } else {
+                analyze::print_trade_journal(&store, mint.as_deref())?;
+            }
+            Ok(true)
+        }
+        Command::ExportTax { method, year, out } => {
+            let store = RecordStore::open_from_env()?;
+            let method = LotMethod::from_str(method)?;
+            let output = tax_export::export_tax_csv(&store, method, *year, out).await?;
+            println!("Wrote {} disposals to {}", output.disposals.len(), out);
+            if !output.skipped.is_empty() {
+                println!("Skipped {} trades without both SOL and token amounts", output.skipped.len());
+            }
+            Ok(true)
+        }
+        Command::ExportRedacted { config, out, mapping } => {
+            let store = RecordStore::open_from_env()?;
+            let redactor = Redactor::new(redact::key_from_env()?, RedactionConfig::load(config)?);
+            let export = redact::export_redacted(&store, redactor, out, mapping)?;
+            println!(
+                "Wrote {} trades, {} journal entries, {} rejections and {} record files to {}",
+                export.trades, export.journal, export.rejections, export.files, out
+            );
+            println!("{} pseudonyms mapped in {}, do not share it", export.pseudonyms, mapping);
+            Ok(true)
+        }
+        Command::Priceboard { action: PriceboardAction::Dump { path } } => {
+            let path = path
+                .clone()
+                .or_else(|| std::env::var("PRICE_BOARD_PATH").ok())
+                .ok_or_else(|| anyhow::anyhow!("Pass --path or set PRICE_BOARD_PATH"))?;
+            let board = PriceBoardReader::open(&path)?;
+            let now = Utc::now().timestamp_millis();
+            println!("{:>18}  {:>16}  {:>16}  {:>12}  {:>8}", "key", "price", "liquidity", "slot", "age ms");
+            for entry in board.entries() {
+                println!(
+                    "{:>18x}  {:>16.9}  {:>16}  {:>12}  {:>8}",
+                    entry.key, entry.price, entry.liquidity, entry.chain_slot, now - entry.updated_ms
+                );
+            }
+            Ok(true)
+        }
+        Command::Pools { mint } => {
+            let cache = PoolCache::load(POOL_CACHE_PATH)?;
+            let health = HealthConfig::from_env();
+            let mut pools: Vec<_> = cache
+                .pools
+                .iter()
+                .filter(|(token, _)| mint.as_ref().map_or(true, |mint| mint == *token))
+                .flat_map(|(_, pools)| pools.iter())
+                .collect();
+            pools.sort_by(|a, b| b.health_score().unwrap_or(-1.0).total_cmp(&a.health_score().unwrap_or(-1.0)));
+            println!(
+                "{:<44}  {:<14}  {:>6}  {:>5}  {:>5}  {:>5}  {:>5}  {}",
+                "pool", "dex", "health", "liq", "vol", "stab", "rel", "token"
+            );
+            for pool in pools {
+                let (score, components) = match &pool.health {
+                    Some(h) => {
+                        let c = h.components(&health);
+                        (
+                            format!("{:.0}", h.score),
+                            format!("{:>5.2}  {:>5.2}  {:>5.2}  {:>5.2}", c.liquidity, c.volume, c.stability, c.reliability),
+                        )
+                    }
+                    None => ("-".to_string(), format!("{:>5}  {:>5}  {:>5}  {:>5}", "-", "-", "-", "-")),
+                };
+                let dormant = if cache.dormant.contains(&pool.base_mint) { " (dormant)" } else { "" };
+                println!(
+                    "{:<44}  {:<14}  {:>6}  {}  {}{}",
+                    pool.pool_id, pool.dex_name, score, components, pool.base_mint, dormant
+                );
+            }
+            if health.min_score > 0.0 {
+                println!("\nLegs need a health of at least {:.0} (POOL_MIN_HEALTH)", health.min_score);
+            }
+            Ok(true)
+        }
+        Command::Kv { action } => {
+            let store = KvStore::open_from_env()?;
+            match action {
+                KvAction::Export { out } => {
+                    std::fs::write(out, serde_json::to_string_pretty(&store.export_json()?)?)?;
+                    println!("Wrote {}", out);
+                }
+                KvAction::Import { file } => {
+                    let dump: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+                    println!("Imported {} entries from {}", store.import_json(&dump)?, file);
+                }
+            }
+            Ok(true)
+        }
+        Command::MigrateOpportunities { dir, delete, archive } => {
+            let store = RecordStore::open_from_env()?;
+            // Cached mints complete the 8 character prefixes old files were named with
+            let known_mints: Vec<String> = PoolCache::load(POOL_CACHE_PATH)
+                .map(|cache| cache.pools.keys().cloned().collect())
+                .unwrap_or_default();
+            let after = match (archive, delete) {
+                (Some(archive), _) => AfterMigration::Archive(archive.into()),
+                (None, true) => AfterMigration::Delete,
+                (None, false) => AfterMigration::Keep,
+            };
+            let report = legacy_opportunities::migrate_legacy(&store, std::path::Path::new(dir), &known_mints, &after)?;
+            println!(
+                "Migrated {}, {} already stored, {} unparseable",
+                report.migrated, report.duplicates, report.unparseable.len()
+            );
+            if report.prefix_only > 0 {
+                println!("{} migrated with only the mint prefix known", report.prefix_only);
+            }
+            for (file, error) in &report.unparseable {
+                println!("  {}: {}", file, error);
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+
+    if let Some(command) = &cli.command {
+        match run_offline_command(command).await {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(command) = &cli.command {
+        apply_env_overrides(command);
+    }
+
+    /* Self-test before trading, SKIP_PREFLIGHT=true starts without it */
+    let trading = match &cli.command {
+        None | Some(Command::Arbitrage { .. }) => Some(true),
+        Some(Command::CopyTrade { .. }) => Some(false),
+        _ => None,
+    };
+    if let Some(check_mints) = trading.filter(|_| doctor::preflight_enabled()) {
+        let report = doctor::run_doctor(check_mints).await;
+        report.print();
+        if !report.ok() {
+            eprintln!("Preflight failed; fix the checks above or set SKIP_PREFLIGHT=true");
+            std::process::exit(1);
+        }
+    }
+
     /* Initial Settings */
     let config = Config::new().await;
     let config = config.lock().await;
 
+    /* State kept across restarts: mint decimals feed the amount formatting, positions are reported */
+    kv::install(config.app_state.kv.clone());
+    for (mint, info) in config.app_state.kv.all(&kv::MINT_INFO).unwrap_or_default() {
+        solana_vntr_sniper::shared::format::remember_decimals(&mint, info.decimals);
+    }
+    let open_positions = config.app_state.kv.all(&kv::POSITIONS).unwrap_or_default();
+    if !open_positions.is_empty() {
+        println!("{} positions open from the previous run:", open_positions.len());
+        for (mint, position) in &open_positions {
+            println!("  {} ({}, opened by {})", mint, position.strategy, position.signature);
+        }
+    }
+    if let Ok(Some(slot)) = config.app_state.kv.get(&kv::STREAM, kv::LAST_SLOT_KEY) {
+        println!("Stream last processed slot {}", slot);
+    }
+
+    if let Some(Command::Liquidate) = cli.command {
+        match liquidation::liquidate_all(&config.app_state).await {
+            Ok(summary) => {
+                println!(
+                    "Liquidation finished: {} attempted, {} sold, {} failed",
+                    summary.attempted, summary.sold.len(), summary.failed.len()
+                );
+                std::process::exit(if summary.failed.is_empty() { 0 } else { 1 });
+            }
+            Err(e) => {
+                eprintln!("Liquidation error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(Command::Alt { action }) = &cli.command {
+        let logger = solana_vntr_sniper::shared::logger::Logger::new("[ALT] => ".to_string());
+        let app_state = &config.app_state;
+        let result = match action {
+            AltAction::Create => arbitrage_alt::create_table(app_state, &logger).await.map(|table| {
+                println!("{}", table);
+                println!("Set ARBITRAGE_ALT={} to use it", table);
+            }),
+            AltAction::Extend { table, mint, addresses } => async {
+                let table = match table {
+                    Some(table) => Pubkey::from_str(table).map_err(|_| anyhow::anyhow!("invalid table {}", table))?,
+                    None => arbitrage_alt::configured_table()?.ok_or_else(|| anyhow::anyhow!("pass --table or set ARBITRAGE_ALT"))?,
+                };
+                let mut keys = addresses
+                    .iter()
+                    .map(|address| Pubkey::from_str(address).map_err(|_| anyhow::anyhow!("invalid address {}", address)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                if let Some(mint) = mint {
+                    let cache = app_state.pool_cache_manager.get_cache()?;
+                    keys.extend(
+                        cache.get_pools_for_token(mint).into_iter().flatten().filter_map(|pool| Pubkey::from_str(&pool.pool_id).ok()),
+                    );
+                }
+                let signatures = arbitrage_alt::extend_table(app_state, table, &keys, &logger).await?;
+                println!("{} extend transactions sent", signatures.len());
+                anyhow::Ok(())
+            }.await,
+        };
+        if let Err(e) = result {
+            eprintln!("ALT error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::DiscoverPools { mints }) = &cli.command {
+        let mints: Vec<Pubkey> = mints
+            .iter()
+            .map(|mint| Pubkey::from_str(mint).map_err(|_| format!("invalid mint {}", mint)))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+        let app_state = &config.app_state;
+        if let Err(e) = initialize_pool_cache(&app_state.rpc_pool, &mints, &app_state.pool_cache_manager).await {
+            eprintln!("Pool discovery error: {}", e);
+            std::process::exit(1);
+        }
+        let cache = app_state.pool_cache_manager.get_cache().unwrap_or_default();
+        for mint in &mints {
+            let mut by_dex: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+            for pool in cache.get_pools_for_token(&mint.to_string()).into_iter().flatten() {
+                by_dex.entry(pool.dex_name.as_str()).or_default().push(pool);
+            }
+            println!("\n{} ({} pools)", mint, by_dex.values().map(Vec::len).sum::<usize>());
+            println!("{:<14}  {:<44}  {:<44}  {:>16}", "dex", "pool", "quote mint", "liquidity");
+            for (dex, pools) in by_dex {
+                for pool in pools {
+                    let liquidity = pool.liquidity.map_or("-".to_string(), fmt_lamports_exact);
+                    println!("{:<14}  {:<44}  {:<44}  {:>16}", dex, pool.pool_id, pool.quote_mint, liquidity);
+                }
+            }
+        }
+        return;
+    }
+
+    /* Stage timing, only with --features profiling; the folded stacks are written on Ctrl-C or exit */
+    profiling::init();
+    if cfg!(feature = "profiling") {
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                profiling::flush();
+                std::process::exit(130);
+            }
+        });
+    }
+
+    /* Background tasks are restarted on panic, see supervisor */
+    let supervisor_config = SupervisorConfig::from_env();
+
+    /* Leader lease for multi-region runs; followers ingest and record but never send */
+    if let Some(leader_config) = leader::LeaderConfig::from_env() {
+        leader::enable();
+        supervise(config.app_state.clone(), "leader_election", supervisor_config, move |app_state| {
+            leader::run_leader_election(app_state, leader_config.clone())
+        });
+    }
+
+    /* Sampled check of local swap math against RPC simulation, off the hot path */
+    if let Some(verifier_config) = math_verifier::VerifierConfig::from_env() {
+        math_verifier::enable(&verifier_config);
+        supervise(config.app_state.clone(), "math_verifier", supervisor_config, move |app_state| {
+            math_verifier::run_math_verifier(app_state, verifier_config)
+        });
+    }
+
+    /* Standing inventory: re-mark, flatten on poor health, rebalance when calm */
+    if let Ok(Some(inventory_config)) = inventory::InventoryConfig::from_env() {
+        supervise(config.app_state.clone(), "inventory_rebalancer", supervisor_config, move |app_state| {
+            inventory::run_rebalancer(app_state, inventory_config.clone())
+        });
+    }
+
+    /* Admin API */
+    if let Some(bind) = admin::get_admin_bind() {
+        supervise(config.app_state.clone(), "admin", supervisor_config, move |app_state| {
+            let bind = bind.clone();
+            async move {
+                if let Err(e) = admin::serve_admin(bind, app_state).await {
+                    eprintln!("Admin API error: {}", e);
+                }
+            }
+        });
+    }
+
+    /* Capital allocation across strategies */
+    supervise(config.app_state.clone(), "balance_refresh", supervisor_config, |app_state| {
+        capital::run_balance_refresh(app_state, Duration::from_secs(30))
+    });
+
+    /* Blockhash from RPC whenever the stream hasn't brought a recent one */
+    supervise(config.app_state.clone(), "blockhash_refresh", supervisor_config, blockhash_cache::run_refresh);
+
+    /* Pool cache refresh, most relevant tokens first */
+    supervise(config.app_state.clone(), "pool_refresh", supervisor_config, refresh_scheduler::run_pool_refresh);
+
+    /* Demote dead tokens out of scanning and refresh */
+    supervise(config.app_state.clone(), "dormancy", supervisor_config, dormancy::run_dormancy);
+
+    /* Cold-storage sweep of profits above the operating balance */
+    match sweep::SweepConfig::from_env(&config.app_state.wallet.pubkey()) {
+        Ok(sweep_config) => {
+            supervise(config.app_state.clone(), "sweep", supervisor_config, move |app_state| {
+                sweep::run_sweep(app_state, sweep_config.clone())
+            });
+        }
+        Err(sweep::SweepRefusal::Disabled) => {}
+        Err(refusal) => eprintln!("Cold sweep not started: {}", refusal),
+    }
+
     /* Running Bot */
     let run_msg = RUN_MSG;
     println!("{}", run_msg);
-    println!("ARBITRAGE BOT: Monitoring token prices across multiple DEXes");
-    
-    /* Display supported DEXes */
-    let dex_registry = DEXRegistry::new();
-    println!("Tracking DEXes:");
-    for dex in dex_registry.get_all_dexes() {
-        println!("  - {} ({})", dex.name, dex.program_id);
-    }
-
-    /* Get arbitrage settings from environment */
-    let arbitrage_threshold = std::env::var("ARBITRAGE_THRESHOLD")
-        .ok()
-        .and_then(|v| v.parse::<f64>().ok())
-        .unwrap_or(1.5); // Default to 1.5% if not specified
-    
-    let min_liquidity = std::env::var("MIN_LIQUIDITY")
-        .ok()
-        .and_then(|v| v.parse::<u64>().ok())
-        .unwrap_or(10_000_000_000); // Default to 10 SOL if not specified
-    
-    /* Setup swap config for arbitrage */
-    let swap_config = SwapConfig {
-        swap_direction: SwapDirection::Buy,
-        in_type: SwapInType::Sol,
-        amount_in: 0.1, // Default to 0.1 SOL per trade
-        slippage: 50, // 0.5% slippage
-        use_jito: false, // Don't use Jito MEV protection by default
-    };
-    
-    /* Start arbitrage monitor */
-    println!("Starting arbitrage monitor with threshold: {}%, min liquidity: {} SOL", 
-        arbitrage_threshold, min_liquidity as f64 / 1_000_000_000.0);
-    
-    match arbitrage_monitor(
-        config.yellowstone_grpc_http.clone(),
-        config.yellowstone_grpc_token.clone(),
-        config.app_state.clone(),
-        swap_config,
-        arbitrage_threshold,
-        min_liquidity,
-    ).await {
-        Ok(_) => println!("Arbitrage monitor completed successfully"),
-        Err(e) => eprintln!("Arbitrage monitor error: {}", e),
+
+    /* Status screen, plain logging when there is no terminal to draw on */
+    if cli.tui {
+        if std::io::stdout().is_terminal() {
+            let app_state = config.app_state.clone();
+            tokio::spawn(async move {
+                let result = tui::run_tui(app_state).await;
+                if let Err(e) = &result {
+                    eprintln!("TUI error: {}", e);
+                }
+                std::process::exit(if result.is_ok() { 0 } else { 1 });
+            });
+        } else {
+            eprintln!("--tui needs a terminal on stdout, falling back to plain logging");
+        }
     }
+
+    let grpc_http = config.yellowstone_grpc_http.clone();
+    let grpc_token = config.yellowstone_grpc_token.clone();
+    let (time_exceed, counter_limit) = (config.time_exceed, config.counter_limit as u64);
+    let (min_dev_buy, max_dev_buy) = (config.min_dev_buy as u64, config.max_dev_buy as u64);
+    let monitor = match &cli.command {
+        Some(Command::CopyTrade { amount, slippage, .. }) | Some(Command::Snipe { amount, slippage }) => {
+            let snipe = matches!(cli.command, Some(Command::Snipe { .. }));
+            let swap_config = override_swap_config(config.swap_config.clone(), *amount, *slippage).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            println!(
+                "{}: {} SOL per buy, {} bps slippage",
+                if snipe { "SNIPER" } else { "COPY TRADER" },
+                swap_config.amount_in,
+                swap_config.slippage_bps
+            );
+            let name = if snipe { "new_token_trader" } else { "copy_trader" };
+            supervise(config.app_state.clone(), name, supervisor_config, move |app_state| {
+                let (grpc_http, grpc_token, swap_config) = (grpc_http.clone(), grpc_token.clone(), swap_config.clone());
+                async move {
+                    let result = if snipe {
+                        new_token_trader_pumpfun(
+                            grpc_http, grpc_token, app_state, swap_config, time_exceed, counter_limit, min_dev_buy, max_dev_buy,
+                        ).await
+                    } else {
+                        copy_trader_pumpfun(
+                            grpc_http, grpc_token, app_state, swap_config, time_exceed, counter_limit, min_dev_buy, max_dev_buy,
+                        ).await
+                    };
+                    if let Err(e) = result {
+                        eprintln!("{} error: {}", name, e);
+                    }
+                }
+            })
+        }
+        _ => {
+            println!("ARBITRAGE BOT: Monitoring token prices across multiple DEXes");
+
+            /* Display supported DEXes */
+            let dex_registry = config.app_state.dex_registry.clone();
+            println!("Tracking DEXes:");
+            for dex in dex_registry.get_all_dexes() {
+                let fee_source = if dex_registry.has_fee_override(&dex.name) { "override" } else { "default" };
+                println!("  - {} ({}) fee: {} bps ({})", dex.name, dex.program_id, dex.fee_bps, fee_source);
+            }
+
+            /* Get arbitrage settings from environment, refusing to start on bad values */
+            let settings = ArbitrageSettings::from_env().unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+
+            /* Setup swap config for arbitrage, 0.1 SOL at 0.5% slippage without Jito unless flagged */
+            let (amount, slippage) = match &cli.command {
+                Some(Command::Arbitrage { amount, slippage, .. }) => (*amount, *slippage),
+                _ => (None, None),
+            };
+            let swap_config = SwapConfig {
+                swap_direction: SwapDirection::Buy,
+                in_type: SwapInType::Sol,
+                amount_in: 0.1,
+                slippage_bps: 50,
+                use_jito: false,
+                priority_fee: PriorityFeeStrategy::from_env(),
+            };
+            let swap_config = override_swap_config(swap_config, amount, slippage).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+
+            /* SOL/USDC rate that USDC-quoted pools are priced at */
+            supervise(config.app_state.clone(), "reference_rate", supervisor_config, quote_currency::run_reference_refresh);
+
+            /* Start arbitrage monitor */
+            println!("Starting arbitrage monitor with threshold: {}, min liquidity: {}",
+                fmt_pct(settings.threshold_pct), fmt_lamports_exact(settings.min_liquidity));
+
+            supervise(config.app_state.clone(), "arbitrage_monitor", supervisor_config, move |app_state| {
+                let (grpc_http, grpc_token, swap_config, settings) =
+                    (grpc_http.clone(), grpc_token.clone(), swap_config.clone(), settings.clone());
+                async move {
+                    match arbitrage_monitor(grpc_http, grpc_token, app_state, swap_config, settings).await {
+                        Ok(_) => println!("Arbitrage monitor completed successfully"),
+                        Err(e) => eprintln!("Arbitrage monitor error: {}", e),
+                    }
+                }
+            })
+        }
+    };
+    let _ = monitor.await;
+    profiling::flush();
 }
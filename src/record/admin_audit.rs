@@ -0,0 +1,121 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use super::store::RecordStore;
+
+/// One admin mutation; rows are only ever inserted
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminAuditEntry {
+    pub id: i64,
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    /// Signed `X-Admin-Actor`, or "unsigned" when request signing is off
+    pub actor: String,
+    pub method: String,
+    pub path: String,
+    /// e.g. "liquidate", "journal_add", "entries_paused"
+    pub action: String,
+    /// JSON before and after, None where there is no prior state
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// Config hash after the change
+    pub config_hash: String,
+}
+
+impl RecordStore {
+    /// Append an audit entry; `id` and `created_at` of `entry` are ignored
+    pub fn append_admin_audit(&self, entry: &AdminAuditEntry) -> Result<i64> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO admin_audit (created_at, actor, method, path, action, old_value, new_value, config_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                chrono::Utc::now().timestamp_millis(),
+                entry.actor,
+                entry.method,
+                entry.path,
+                entry.action,
+                entry.old_value,
+                entry.new_value,
+                entry.config_hash,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// The most recent `limit` entries, newest first
+    pub fn admin_audit(&self, limit: usize) -> Result<Vec<AdminAuditEntry>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, actor, method, path, action, old_value, new_value, config_hash
+             FROM admin_audit ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok(AdminAuditEntry {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    actor: row.get(2)?,
+                    method: row.get(3)?,
+                    path: row.get(4)?,
+                    action: row.get(5)?,
+                    old_value: row.get(6)?,
+                    new_value: row.get(7)?,
+                    config_hash: row.get(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action: &str, old_value: Option<&str>, new_value: Option<&str>) -> AdminAuditEntry {
+        AdminAuditEntry {
+            id: 0,
+            created_at: 0,
+            actor: "ops".to_string(),
+            method: "POST".to_string(),
+            path: format!("/{}", action),
+            action: action.to_string(),
+            old_value: old_value.map(str::to_string),
+            new_value: new_value.map(str::to_string),
+            config_hash: "0123456789abcdef".to_string(),
+        }
+    }
+
+    #[test]
+    fn entries_read_back_newest_first() {
+        let store = RecordStore::open_in_memory().unwrap();
+        let first = store.append_admin_audit(&entry("entries_paused", Some("false"), Some("true"))).unwrap();
+        let second = store.append_admin_audit(&entry("liquidate", None, Some("{}"))).unwrap();
+        assert!(second > first);
+
+        let entries = store.admin_audit(10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].id, entries[0].action.as_str()), (second, "liquidate"));
+        assert_eq!(entries[0].old_value, None);
+        let paused = &entries[1];
+        assert_eq!((paused.actor.as_str(), paused.method.as_str(), paused.path.as_str()), ("ops", "POST", "/entries_paused"));
+        assert_eq!((paused.old_value.as_deref(), paused.new_value.as_deref()), (Some("false"), Some("true")));
+        assert_eq!(paused.config_hash, "0123456789abcdef");
+        assert!(paused.created_at > 0);
+
+        assert_eq!(store.admin_audit(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn entries_cannot_be_changed_or_removed() {
+        let store = RecordStore::open_in_memory().unwrap();
+        store.append_admin_audit(&entry("entries_paused", Some("false"), Some("true"))).unwrap();
+        let conn = store.lock().unwrap();
+        assert!(conn.execute("UPDATE admin_audit SET actor = 'someone else'", []).is_err());
+        assert!(conn.execute("DELETE FROM admin_audit", []).is_err());
+        drop(conn);
+        assert_eq!(store.admin_audit(10).unwrap()[0].actor, "ops");
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::store::RecordStore;
+
+/// What a journal entry is attached to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum JournalScope {
+    Session,
+    Mint { mint: String },
+    Trade { trade_id: String },
+}
+
+impl JournalScope {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Session => "session",
+            Self::Mint { .. } => "mint",
+            Self::Trade { .. } => "trade",
+        }
+    }
+}
+
+/// Who wrote a journal entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalSource {
+    /// Operator via admin endpoint or CLI
+    User,
+    /// Written by the bot itself, e.g. on a panic liquidation
+    System,
+}
+
+impl JournalSource {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::System => "system",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEntry {
+    pub id: i64,
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    pub scope: String,
+    pub mint: Option<String>,
+    pub trade_id: Option<String>,
+    pub note: String,
+    pub source: String,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl RecordStore {
+    /// Attach a note with optional key/value tags to the session, a mint or a trade
+    pub fn add_journal_entry(
+        &self,
+        scope: &JournalScope,
+        note: &str,
+        tags: &BTreeMap<String, String>,
+        source: JournalSource,
+    ) -> Result<i64> {
+        let (mint, trade_id) = match scope {
+            JournalScope::Session => (None, None),
+            JournalScope::Mint { mint } => (Some(mint.clone()), None),
+            JournalScope::Trade { trade_id } => {
+                // Keep the mint alongside so mint queries also find trade notes
+                let mint = self.get_trade(trade_id)?.map(|trade| trade.mint);
+                (mint, Some(trade_id.clone()))
+            }
+        };
+
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO journal (created_at, scope, mint, trade_id, note, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                chrono::Utc::now().timestamp_millis(),
+                scope.name(),
+                mint,
+                trade_id,
+                note,
+                source.name(),
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        for (key, value) in tags {
+            tx.execute(
+                "INSERT INTO journal_tags (journal_id, key, value) VALUES (?1, ?2, ?3)",
+                params![id, key, value],
+            )?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Journal entries for a mint (including notes on its trades), oldest first
+    pub fn journal_by_mint(&self, mint: &str) -> Result<Vec<JournalEntry>> {
+        self.query_journal(
+            "SELECT id, created_at, scope, mint, trade_id, note, source FROM journal
+             WHERE mint = ?1 ORDER BY created_at, id",
+            params![mint],
+        )
+    }
+
+    /// Journal entries carrying tag `key`, optionally with a specific value
+    pub fn journal_by_tag(&self, key: &str, value: Option<&str>) -> Result<Vec<JournalEntry>> {
+        self.query_journal(
+            "SELECT DISTINCT j.id, j.created_at, j.scope, j.mint, j.trade_id, j.note, j.source
+             FROM journal j JOIN journal_tags t ON t.journal_id = j.id
+             WHERE t.key = ?1 AND (?2 IS NULL OR t.value = ?2)
+             ORDER BY j.created_at, j.id",
+            params![key, value],
+        )
+    }
+
+    /// Session-wide entries, oldest first
+    pub fn journal_for_session(&self) -> Result<Vec<JournalEntry>> {
+        self.query_journal(
+            "SELECT id, created_at, scope, mint, trade_id, note, source FROM journal
+             WHERE scope = 'session' ORDER BY created_at, id",
+            params![],
+        )
+    }
+
+    /// Every entry, oldest first
+    pub fn journal(&self) -> Result<Vec<JournalEntry>> {
+        self.query_journal(
+            "SELECT id, created_at, scope, mint, trade_id, note, source FROM journal
+             ORDER BY created_at, id",
+            params![],
+        )
+    }
+
+    fn query_journal(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<JournalEntry>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(sql)?;
+        let mut entries = stmt
+            .query_map(params, |row| {
+                Ok(JournalEntry {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    scope: row.get(2)?,
+                    mint: row.get(3)?,
+                    trade_id: row.get(4)?,
+                    note: row.get(5)?,
+                    source: row.get(6)?,
+                    tags: BTreeMap::new(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tag_stmt = conn.prepare("SELECT key, value FROM journal_tags WHERE journal_id = ?1")?;
+        for entry in entries.iter_mut() {
+            entry.tags = tag_stmt
+                .query_map(params![entry.id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<BTreeMap<_, _>, _>>()?;
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::store::TradeRecord;
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn buy(trade_id: &str, mint: &str) -> TradeRecord {
+        TradeRecord {
+            trade_id: trade_id.to_string(),
+            created_at: 0,
+            mint: mint.to_string(),
+            side: "buy".to_string(),
+            sol_amount: Some(1_000_000),
+            token_amount: Some(500),
+            price: None,
+            signature: None,
+            priority_fee_strategy: None,
+            priority_fee_lamports: None,
+        }
+    }
+
+    #[test]
+    fn mint_query_finds_notes_on_the_mint_and_its_trades() {
+        let store = RecordStore::open_in_memory().unwrap();
+        store.record_trade(&buy("t1", "mintA")).unwrap();
+        let on_mint = JournalScope::Mint { mint: "mintA".to_string() };
+        let on_trade = JournalScope::Trade { trade_id: "t1".to_string() };
+        let elsewhere = JournalScope::Mint { mint: "mintB".to_string() };
+        store.add_journal_entry(&on_mint, "dev wallet looks sketchy", &tags(&[]), JournalSource::User).unwrap();
+        store.add_journal_entry(&on_trade, "filled late", &tags(&[]), JournalSource::User).unwrap();
+        store.add_journal_entry(&elsewhere, "unrelated", &tags(&[]), JournalSource::User).unwrap();
+        store.add_journal_entry(&JournalScope::Session, "session note", &tags(&[]), JournalSource::System).unwrap();
+
+        let notes: Vec<_> = store.journal_by_mint("mintA").unwrap().into_iter().map(|e| e.note).collect();
+        assert_eq!(notes, ["dev wallet looks sketchy", "filled late"]);
+        let trade_note = &store.journal_by_mint("mintA").unwrap()[1];
+        assert_eq!(trade_note.scope, "trade");
+        assert_eq!(trade_note.trade_id.as_deref(), Some("t1"));
+        assert_eq!(store.journal_for_session().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tag_query_matches_key_and_optionally_value() {
+        let store = RecordStore::open_in_memory().unwrap();
+        let session = JournalScope::Session;
+        store.add_journal_entry(&session, "paused target", &tags(&[("event", "pause"), ("target", "abc")]), JournalSource::User).unwrap();
+        store.add_journal_entry(&session, "liquidated", &tags(&[("event", "panic_liquidation")]), JournalSource::System).unwrap();
+        store.add_journal_entry(&session, "untagged", &tags(&[]), JournalSource::User).unwrap();
+
+        assert_eq!(store.journal_by_tag("event", None).unwrap().len(), 2);
+        let liquidations = store.journal_by_tag("event", Some("panic_liquidation")).unwrap();
+        assert_eq!(liquidations.len(), 1);
+        assert_eq!(liquidations[0].source, "system");
+        let paused = store.journal_by_tag("target", Some("abc")).unwrap();
+        assert_eq!(paused[0].tags, tags(&[("event", "pause"), ("target", "abc")]));
+        assert!(store.journal_by_tag("target", Some("xyz")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,361 @@
+//! Embedded key-value store for state the bot needs back after a restart
+//!
+//! Open positions, the last streamed slot, mint info and per-target stats live
+//! here instead of each in its own JSON file. Every namespace is its own redb
+//! table holding JSON values, so one namespace can never read another's keys.
+//! A write either commits whole or not at all; namespaces marked non-durable
+//! skip the fsync and may lose their latest commits on a crash, never their
+//! consistency.
+
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Result};
+use redb::{backends::InMemoryBackend, Database, Durability, ReadableTable, TableDefinition, TableError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub const DEFAULT_KV_PATH: &str = "./record/state.redb";
+
+/// A typed namespace: values in it are always `V`
+pub struct Namespace<V> {
+    pub name: &'static str,
+    /// Fsync on commit; off for markers rewritten every few hundred ms
+    pub durable: bool,
+    _value: PhantomData<fn() -> V>,
+}
+
+impl<V> Namespace<V> {
+    pub const fn new(name: &'static str, durable: bool) -> Self {
+        Self { name, durable, _value: PhantomData }
+    }
+}
+
+/// A position opened by the bot and not yet closed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionState {
+    pub mint: String,
+    pub signature: String,
+    pub strategy: String,
+    /// Slot of the transaction the position was opened on
+    pub slot: u64,
+    pub entry_price: f64,
+    pub lamports: u64,
+    pub opened_at_ms: i64,
+    /// Venue the position was bought on, liquidation sells it back there;
+    /// None for positions stored before it was recorded
+    #[serde(default)]
+    pub dex: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MintInfo {
+    pub decimals: u8,
+}
+
+/// How copying one target has gone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetStats {
+    pub copied: u64,
+    pub failed: u64,
+    pub last_seen_ms: i64,
+}
+
+/// Keyed by mint
+pub const POSITIONS: Namespace<PositionState> = Namespace::new("positions", true);
+/// `last_slot` is the newest slot the stream processed
+pub const STREAM: Namespace<u64> = Namespace::new("stream", false);
+pub const LAST_SLOT_KEY: &str = "last_slot";
+/// Keyed by mint
+pub const MINT_INFO: Namespace<MintInfo> = Namespace::new("mint_info", true);
+/// Keyed by target wallet
+pub const TARGET_STATS: Namespace<TargetStats> = Namespace::new("target_stats", true);
+
+/// Every namespace export and import know about
+pub const NAMESPACES: [&str; 5] = [
+    POSITIONS.name,
+    STREAM.name,
+    MINT_INFO.name,
+    TARGET_STATS.name,
+    crate::engine::inventory::INVENTORY.name,
+];
+
+lazy_static::lazy_static! {
+    /// Store shared with code that has no `AppState` at hand
+    static ref INSTALLED: RwLock<Option<Arc<KvStore>>> = RwLock::new(None);
+}
+
+/// Make `store` the one `installed()` returns
+pub fn install(store: Arc<KvStore>) {
+    if let Ok(mut installed) = INSTALLED.write() {
+        *installed = Some(store);
+    }
+}
+
+pub fn installed() -> Option<Arc<KvStore>> {
+    INSTALLED.read().ok()?.clone()
+}
+
+/// Persist a mint's decimals to the installed store, if any
+pub fn remember_mint_decimals(mint: &str, decimals: u8) {
+    if let Some(store) = installed() {
+        let _ = store.put(&MINT_INFO, mint, &MintInfo { decimals });
+    }
+}
+
+fn table(name: &str) -> TableDefinition<'_, &'static str, &'static [u8]> {
+    TableDefinition::new(name)
+}
+
+pub struct KvStore {
+    db: Database,
+}
+
+impl KvStore {
+    pub fn open(path: &str) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        Ok(Self { db: Database::create(path)? })
+    }
+
+    /// In-memory store, used by tests and dry tooling
+    pub fn open_in_memory() -> Result<Self> {
+        Ok(Self { db: Database::builder().create_with_backend(InMemoryBackend::new())? })
+    }
+
+    /// Open the store at `KV_PATH`, falling back to the default path
+    pub fn open_from_env() -> Result<Self> {
+        let path = std::env::var("KV_PATH").unwrap_or_else(|_| DEFAULT_KV_PATH.to_string());
+        Self::open(&path)
+    }
+
+    pub fn get<V: DeserializeOwned>(&self, namespace: &Namespace<V>, key: &str) -> Result<Option<V>> {
+        self.get_raw(namespace.name, key)?
+            .map(|raw| serde_json::from_slice(&raw).map_err(Into::into))
+            .transpose()
+    }
+
+    pub fn put<V: Serialize>(&self, namespace: &Namespace<V>, key: &str, value: &V) -> Result<()> {
+        let mut batch = self.batch();
+        batch.put(namespace, key, value)?;
+        batch.commit()
+    }
+
+    pub fn remove<V>(&self, namespace: &Namespace<V>, key: &str) -> Result<()> {
+        let mut batch = self.batch();
+        batch.remove(namespace, key);
+        batch.commit()
+    }
+
+    /// Every entry of a namespace in key order; entries that no longer
+    /// decode as `V` are skipped
+    pub fn all<V: DeserializeOwned>(&self, namespace: &Namespace<V>) -> Result<Vec<(String, V)>> {
+        Ok(self
+            .all_raw(namespace.name)?
+            .into_iter()
+            .filter_map(|(key, raw)| serde_json::from_slice(&raw).ok().map(|value| (key, value)))
+            .collect())
+    }
+
+    /// Writes that commit together in one transaction
+    pub fn batch(&self) -> KvBatch<'_> {
+        KvBatch { store: self, writes: Vec::new(), durable: false }
+    }
+
+    fn get_raw(&self, name: &str, key: &str) -> Result<Option<Vec<u8>>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(table(name)) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let value = table.get(key)?.map(|value| value.value().to_vec());
+        Ok(value)
+    }
+
+    fn all_raw(&self, name: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(table(name)) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            entries.push((key.value().to_string(), value.value().to_vec()));
+        }
+        Ok(entries)
+    }
+
+    /// Every namespace as `{namespace: {key: value}}`
+    pub fn export_json(&self) -> Result<serde_json::Value> {
+        let mut export = serde_json::Map::new();
+        for name in NAMESPACES {
+            let mut entries = serde_json::Map::new();
+            for (key, raw) in self.all_raw(name)? {
+                entries.insert(key, serde_json::from_slice(&raw)?);
+            }
+            export.insert(name.to_string(), serde_json::Value::Object(entries));
+        }
+        Ok(serde_json::Value::Object(export))
+    }
+
+    /// Load an `export_json` dump in one transaction, overwriting keys it
+    /// contains; returns the number of entries written
+    pub fn import_json(&self, dump: &serde_json::Value) -> Result<usize> {
+        let namespaces: BTreeMap<String, BTreeMap<String, serde_json::Value>> = serde_json::from_value(dump.clone())?;
+        let txn = self.db.begin_write()?;
+        let mut written = 0;
+        for (name, entries) in &namespaces {
+            let name = NAMESPACES
+                .iter()
+                .find(|known| *known == name)
+                .ok_or_else(|| anyhow!("Unknown namespace in dump: {}", name))?;
+            let mut table = txn.open_table(table(name))?;
+            for (key, value) in entries {
+                table.insert(key.as_str(), serde_json::to_vec(value)?.as_slice())?;
+                written += 1;
+            }
+        }
+        txn.commit()?;
+        Ok(written)
+    }
+}
+
+pub struct KvBatch<'a> {
+    store: &'a KvStore,
+    /// (namespace, key, value or None to remove)
+    writes: Vec<(&'static str, String, Option<Vec<u8>>)>,
+    durable: bool,
+}
+
+impl KvBatch<'_> {
+    pub fn put<V: Serialize>(&mut self, namespace: &Namespace<V>, key: &str, value: &V) -> Result<()> {
+        self.writes.push((namespace.name, key.to_string(), Some(serde_json::to_vec(value)?)));
+        self.durable |= namespace.durable;
+        Ok(())
+    }
+
+    pub fn remove<V>(&mut self, namespace: &Namespace<V>, key: &str) {
+        self.writes.push((namespace.name, key.to_string(), None));
+        self.durable |= namespace.durable;
+    }
+
+    /// Commit every write or none; fsyncs when any namespace touched is durable
+    pub fn commit(self) -> Result<()> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+        let mut txn = self.store.db.begin_write()?;
+        if !self.durable {
+            txn.set_durability(Durability::Eventual);
+        }
+        for (name, key, value) in &self.writes {
+            let mut table = txn.open_table(table(name))?;
+            match value {
+                Some(value) => {
+                    table.insert(key.as_str(), value.as_slice())?;
+                }
+                None => {
+                    table.remove(key.as_str())?;
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kv_test_{}_{}.redb", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn position(mint: &str) -> PositionState {
+        PositionState {
+            mint: mint.to_string(),
+            signature: format!("sig-{}", mint),
+            strategy: "copy".to_string(),
+            slot: 42,
+            entry_price: 0.0001,
+            lamports: 1_000_000,
+            opened_at_ms: 1_700_000_000_000,
+            dex: Some("pumpswap".to_string()),
+        }
+    }
+
+    #[test]
+    fn committed_state_survives_an_abrupt_drop_and_unfinished_writes_do_not() {
+        let path = temp_path("crash");
+        {
+            let store = KvStore::open(&path).unwrap();
+            let mut batch = store.batch();
+            batch.put(&POSITIONS, "mint_a", &position("mint_a")).unwrap();
+            batch.put(&STREAM, LAST_SLOT_KEY, &1_234).unwrap();
+            batch.commit().unwrap();
+
+            // A write cut off mid-transaction: opened, written, never committed
+            let txn = store.db.begin_write().unwrap();
+            {
+                let mut table = txn.open_table(table(POSITIONS.name)).unwrap();
+                table.insert("mint_b", serde_json::to_vec(&position("mint_b")).unwrap().as_slice()).unwrap();
+            }
+            drop(txn);
+            // A batch built but dropped before commit
+            let mut batch = store.batch();
+            batch.remove(&POSITIONS, "mint_a");
+            drop(batch);
+        }
+
+        let store = KvStore::open(&path).unwrap();
+        let positions = store.all(&POSITIONS).unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].0, "mint_a");
+        assert_eq!(positions[0].1.signature, "sig-mint_a");
+        assert_eq!(store.get(&STREAM, LAST_SLOT_KEY).unwrap(), Some(1_234));
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn namespaces_do_not_see_each_others_keys() {
+        let store = KvStore::open_in_memory().unwrap();
+        store.put(&POSITIONS, "shared", &position("shared")).unwrap();
+        store.put(&MINT_INFO, "shared", &MintInfo { decimals: 6 }).unwrap();
+        store.put(&TARGET_STATS, "wallet", &TargetStats { copied: 3, failed: 1, last_seen_ms: 9 }).unwrap();
+
+        assert_eq!(store.get(&MINT_INFO, "shared").unwrap().map(|info| info.decimals), Some(6));
+        assert!(store.get(&TARGET_STATS, "shared").unwrap().is_none());
+        assert!(store.get(&POSITIONS, "wallet").unwrap().is_none());
+
+        store.remove(&POSITIONS, "shared").unwrap();
+        assert!(store.get(&POSITIONS, "shared").unwrap().is_none());
+        assert!(store.get(&MINT_INFO, "shared").unwrap().is_some());
+        assert_eq!(store.all(&TARGET_STATS).unwrap().len(), 1);
+        assert!(store.all(&STREAM).unwrap().is_empty());
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let store = KvStore::open_in_memory().unwrap();
+        store.put(&POSITIONS, "mint_a", &position("mint_a")).unwrap();
+        store.put(&STREAM, LAST_SLOT_KEY, &99).unwrap();
+        let dump = store.export_json().unwrap();
+        assert_eq!(dump["stream"][LAST_SLOT_KEY], 99);
+
+        let restored = KvStore::open_in_memory().unwrap();
+        assert_eq!(restored.import_json(&dump).unwrap(), 2);
+        assert_eq!(restored.export_json().unwrap(), dump);
+        assert!(restored.import_json(&serde_json::json!({ "unknown": { "k": 1 } })).is_err());
+    }
+}
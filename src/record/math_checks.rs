@@ -0,0 +1,64 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use super::store::RecordStore;
+
+/// One sampled swap priced by the bot's own curve math and by an RPC simulation
+#[derive(Debug, Clone, Serialize)]
+pub struct MathCheck {
+    /// Unix time in milliseconds
+    pub checked_at: i64,
+    pub venue: String,
+    pub mint: String,
+    pub amount_in: u64,
+    /// What the local math says, in the venue's comparison unit
+    pub expected: u64,
+    /// What the simulation did, in the same unit
+    pub simulated: u64,
+    /// simulated / expected - 1
+    pub rel_error: f64,
+}
+
+impl RecordStore {
+    pub fn record_math_check(&self, check: &MathCheck) -> Result<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO math_checks (checked_at, venue, mint, amount_in, expected, simulated, rel_error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                check.checked_at,
+                check.venue,
+                check.mint,
+                check.amount_in as i64,
+                check.expected as i64,
+                check.simulated as i64,
+                check.rel_error,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Comparisons for a venue, or every venue when `venue` is None, oldest first
+    pub fn math_checks(&self, venue: Option<&str>) -> Result<Vec<MathCheck>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT checked_at, venue, mint, amount_in, expected, simulated, rel_error
+             FROM math_checks WHERE (?1 IS NULL OR venue = ?1) ORDER BY checked_at",
+        )?;
+        let rows = stmt
+            .query_map(params![venue], |row| {
+                Ok(MathCheck {
+                    checked_at: row.get(0)?,
+                    venue: row.get(1)?,
+                    mint: row.get(2)?,
+                    amount_in: row.get::<_, i64>(3)? as u64,
+                    expected: row.get::<_, i64>(4)? as u64,
+                    simulated: row.get::<_, i64>(5)? as u64,
+                    rel_error: row.get(6)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
@@ -1,2 +1,16 @@
 pub mod transaction_logger;
-pub mod transaction_streamer; 
\ No newline at end of file
+pub mod transaction_streamer;
+pub mod snapshot_recorder;
+pub mod store;
+pub mod journal;
+pub mod tax_export;
+pub mod rejections;
+pub mod price_board;
+pub mod spreads;
+pub mod unknown_programs;
+pub mod redact;
+pub mod admin_audit;
+pub mod math_checks;
+pub mod opportunities;
+pub mod kv;
+pub mod trace;
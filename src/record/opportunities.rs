@@ -0,0 +1,407 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use super::store::RecordStore;
+use crate::common::hmac::to_hex;
+
+/// Opportunities recorded by the scanner itself
+pub const SOURCE_LIVE: &str = "live";
+/// Opportunities imported from the old per-file JSON records
+pub const SOURCE_LEGACY: &str = "legacy";
+
+/// An arbitrage opportunity the scanner would have executed
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitrageOpportunity {
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    pub token: String,
+    /// Only the start of the mint is known, as in legacy records cut to 8 characters
+    pub token_is_prefix: bool,
+    pub buy_dex: String,
+    pub buy_price: f64,
+    pub buy_pool: String,
+    pub sell_dex: String,
+    pub sell_price: f64,
+    pub sell_pool: String,
+    /// Net of fees for new records, the raw price difference for the oldest ones
+    pub profit_pct: Option<f64>,
+    /// `live` or `legacy`
+    pub source: String,
+    /// None for records written before opportunities had ids
+    pub opportunity_id: Option<String>,
+}
+
+impl ArbitrageOpportunity {
+    /// Hash of what the opportunity was, not where it came from, so a legacy
+    /// copy of a live record is a duplicate
+    pub fn content_hash(&self) -> String {
+        let content = format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{:?}",
+            self.created_at,
+            self.token,
+            self.buy_dex,
+            self.buy_price,
+            self.buy_pool,
+            self.sell_dex,
+            self.sell_price,
+            self.sell_pool,
+            self.profit_pct,
+        );
+        to_hex(&anchor_client::solana_sdk::hash::hash(content.as_bytes()).to_bytes())
+    }
+}
+
+impl RecordStore {
+    /// Store an opportunity unless one with the same content is already there;
+    /// returns whether it was new
+    pub fn record_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        let conn = self.lock()?;
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO opportunities
+                (content_hash, created_at, token, token_is_prefix, buy_dex, buy_price, buy_pool,
+                 sell_dex, sell_price, sell_pool, profit_pct, source, opportunity_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                opportunity.content_hash(),
+                opportunity.created_at,
+                opportunity.token,
+                opportunity.token_is_prefix,
+                opportunity.buy_dex,
+                opportunity.buy_price,
+                opportunity.buy_pool,
+                opportunity.sell_dex,
+                opportunity.sell_price,
+                opportunity.sell_pool,
+                opportunity.profit_pct,
+                opportunity.source,
+                opportunity.opportunity_id,
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Stored opportunities carrying `opportunity_id`
+    pub fn opportunities_by_id(&self, opportunity_id: &str) -> Result<Vec<ArbitrageOpportunity>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT created_at, token, token_is_prefix, buy_dex, buy_price, buy_pool,
+                    sell_dex, sell_price, sell_pool, profit_pct, source, opportunity_id
+             FROM opportunities WHERE opportunity_id = ?1 ORDER BY created_at, id",
+        )?;
+        let opportunities = stmt
+            .query_map(params![opportunity_id], |row| {
+                Ok(ArbitrageOpportunity {
+                    created_at: row.get(0)?,
+                    token: row.get(1)?,
+                    token_is_prefix: row.get(2)?,
+                    buy_dex: row.get(3)?,
+                    buy_price: row.get(4)?,
+                    buy_pool: row.get(5)?,
+                    sell_dex: row.get(6)?,
+                    sell_price: row.get(7)?,
+                    sell_pool: row.get(8)?,
+                    profit_pct: row.get(9)?,
+                    source: row.get(10)?,
+                    opportunity_id: row.get(11)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(opportunities)
+    }
+}
+
+/// A triangular cycle the scanner found, beside the two-leg opportunities
+#[derive(Debug, Clone, Serialize)]
+pub struct TriangleRecord {
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    pub opportunity_id: String,
+    /// Pools in trade order, as `dex:pool`
+    pub route: String,
+    /// Every hop with its expected input and output, as JSON
+    pub hops: String,
+    pub input_lamports: u64,
+    pub output_lamports: u64,
+    pub net_profit_lamports: i64,
+    pub profit_pct: f64,
+}
+
+impl RecordStore {
+    pub fn record_triangle(&self, triangle: &TriangleRecord) -> Result<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO triangles
+                (created_at, opportunity_id, route, hops, input_lamports, output_lamports,
+                 net_profit_lamports, profit_pct)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                triangle.created_at,
+                triangle.opportunity_id,
+                triangle.route,
+                triangle.hops,
+                triangle.input_lamports as i64,
+                triangle.output_lamports as i64,
+                triangle.net_profit_lamports,
+                triangle.profit_pct,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+/// One `arbitrage_opportunities/*.json` file, in any of the shapes it was written in
+#[derive(Debug, Deserialize)]
+struct LegacyRecord {
+    /// `%Y%m%d%H%M%S`, UTC
+    timestamp: Option<String>,
+    token_mint: Option<String>,
+    buy_dex: String,
+    buy_price: f64,
+    #[serde(default)]
+    buy_pool: Option<String>,
+    sell_dex: String,
+    sell_price: f64,
+    #[serde(default)]
+    sell_pool: Option<String>,
+    net_profit_pct: Option<f64>,
+    /// Written before fees were taken into account
+    price_difference_pct: Option<f64>,
+    #[serde(default)]
+    opportunity_id: Option<String>,
+}
+
+/// Legacy file names are `arb_<first 8 chars of mint>_<timestamp>.json`
+fn file_name_parts(file_name: &str) -> Option<(&str, &str)> {
+    file_name.strip_prefix("arb_")?.strip_suffix(".json")?.rsplit_once('_')
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S")
+        .ok()
+        .map(|t| Utc.from_utc_datetime(&t).timestamp_millis())
+}
+
+/// Parse one legacy file; `known_mints` completes a mint that only survived as a prefix
+pub fn parse_legacy(raw: &str, file_name: &str, known_mints: &[String]) -> Result<ArbitrageOpportunity> {
+    let record: LegacyRecord = serde_json::from_str(raw)?;
+    let name_parts = file_name_parts(file_name);
+
+    let created_at = record
+        .timestamp
+        .as_deref()
+        .or(name_parts.map(|(_, timestamp)| timestamp))
+        .and_then(parse_timestamp)
+        .ok_or_else(|| anyhow!("no usable timestamp"))?;
+
+    let token = record
+        .token_mint
+        .filter(|mint| !mint.is_empty())
+        .or(name_parts.map(|(prefix, _)| prefix.to_string()))
+        .ok_or_else(|| anyhow!("no token"))?;
+    // Base58 mints are 32 to 44 characters, anything shorter was cut
+    let (token, token_is_prefix) = if token.len() >= 32 {
+        (token, false)
+    } else {
+        let mut matches = known_mints.iter().filter(|mint| mint.starts_with(&token));
+        match (matches.next(), matches.next()) {
+            (Some(mint), None) => (mint.clone(), false),
+            _ => (token, true),
+        }
+    };
+
+    Ok(ArbitrageOpportunity {
+        created_at,
+        token,
+        token_is_prefix,
+        buy_dex: record.buy_dex,
+        buy_price: record.buy_price,
+        buy_pool: record.buy_pool.unwrap_or_else(|| "unknown".to_string()),
+        sell_dex: record.sell_dex,
+        sell_price: record.sell_price,
+        sell_pool: record.sell_pool.unwrap_or_else(|| "unknown".to_string()),
+        profit_pct: record.net_profit_pct.or(record.price_difference_pct),
+        source: SOURCE_LEGACY.to_string(),
+        opportunity_id: record.opportunity_id,
+    })
+}
+
+/// What happens to legacy files once they are safely in the store
+#[derive(Debug, Clone)]
+pub enum AfterMigration {
+    Keep,
+    Delete,
+    /// Move into this directory
+    Archive(PathBuf),
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MigrationReport {
+    pub migrated: usize,
+    /// Already in the store with the same content
+    pub duplicates: usize,
+    pub unparseable: Vec<(String, String)>,
+    /// Migrated rows whose mint is only known as a prefix
+    pub prefix_only: usize,
+}
+
+/// Import every legacy opportunity file in `dir`
+///
+/// Unparseable files are always left where they are; the others are kept,
+/// deleted or archived per `after` once the whole directory went in.
+pub fn migrate_legacy(
+    store: &RecordStore,
+    dir: &Path,
+    known_mints: &[String],
+    after: &AfterMigration,
+) -> Result<MigrationReport> {
+    let mut report = MigrationReport::default();
+    let mut stored = Vec::new();
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        let parsed = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|raw| parse_legacy(&raw, &file_name, known_mints));
+        let opportunity = match parsed {
+            Ok(opportunity) => opportunity,
+            Err(e) => {
+                report.unparseable.push((file_name, e.to_string()));
+                continue;
+            }
+        };
+        if store.record_opportunity(&opportunity)? {
+            report.migrated += 1;
+            if opportunity.token_is_prefix {
+                report.prefix_only += 1;
+            }
+        } else {
+            report.duplicates += 1;
+        }
+        stored.push(path);
+    }
+
+    match after {
+        AfterMigration::Keep => {}
+        AfterMigration::Delete => {
+            for path in &stored {
+                fs::remove_file(path)?;
+            }
+        }
+        AfterMigration::Archive(archive) => {
+            fs::create_dir_all(archive)?;
+            for path in &stored {
+                if let Some(name) = path.file_name() {
+                    fs::rename(path, archive.join(name))?;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINT: &str = "7GCihgDB8fe6KNjn2MYtkzZcRjQy3t9GHdC8uHYmW2hr";
+
+    fn legacy_json(token_mint: Option<&str>) -> String {
+        let mut record = serde_json::json!({
+            "timestamp": "20240301120000",
+            "buy_dex": "pumpswap",
+            "buy_price": 0.000123,
+            "buy_pool": "BuyPool1",
+            "sell_dex": "raydium_amm",
+            "sell_price": 0.000131,
+            "sell_pool": "SellPool1",
+            "price_difference_pct": 6.5,
+            "min_liquidity": 12.5
+        });
+        if let Some(mint) = token_mint {
+            record["token_mint"] = serde_json::json!(mint);
+        }
+        serde_json::to_string_pretty(&record).unwrap()
+    }
+
+    fn legacy_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("legacy_opportunities_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn normal_record_parses_with_the_legacy_tag() {
+        let opportunity = parse_legacy(&legacy_json(Some(MINT)), "arb_7GCihgDB_20240301120000.json", &[]).unwrap();
+
+        assert_eq!(opportunity.token, MINT);
+        assert!(!opportunity.token_is_prefix);
+        assert_eq!(opportunity.created_at, 1_709_294_400_000);
+        assert_eq!(opportunity.buy_pool, "BuyPool1");
+        assert_eq!(opportunity.sell_dex, "raydium_amm");
+        assert_eq!(opportunity.profit_pct, Some(6.5));
+        assert_eq!(opportunity.source, SOURCE_LEGACY);
+    }
+
+    #[test]
+    fn truncated_token_is_completed_from_known_mints_or_kept_as_prefix() {
+        let file_name = "arb_7GCihgDB_20240301120000.json";
+
+        let completed = parse_legacy(&legacy_json(None), file_name, &[MINT.to_string()]).unwrap();
+        assert_eq!(completed.token, MINT);
+        assert!(!completed.token_is_prefix);
+
+        let ambiguous = [MINT.to_string(), "7GCihgDBzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz".to_string()];
+        let prefix = parse_legacy(&legacy_json(None), file_name, &ambiguous).unwrap();
+        assert_eq!(prefix.token, "7GCihgDB");
+        assert!(prefix.token_is_prefix);
+    }
+
+    #[test]
+    fn corrupt_and_timestampless_files_are_rejected() {
+        assert!(parse_legacy("{\"buy_dex\": \"pumpswap\", \"buy_pr", "arb_7GCihgDB_20240301120000.json", &[]).is_err());
+
+        let no_timestamp = legacy_json(Some(MINT)).replace("\"20240301120000\"", "null");
+        assert!(parse_legacy(&no_timestamp, "renamed.json", &[]).is_err());
+        // The file name still carries it
+        assert!(parse_legacy(&no_timestamp, "arb_7GCihgDB_20240301120000.json", &[]).is_ok());
+    }
+
+    #[test]
+    fn migration_counts_duplicates_and_leaves_corrupt_files_behind() {
+        let dir = legacy_dir("migrate");
+        fs::write(dir.join("arb_7GCihgDB_20240301120000.json"), legacy_json(Some(MINT))).unwrap();
+        // Same opportunity written twice under different names
+        fs::write(dir.join("arb_7GCihgDB_20240301120000_copy.json"), legacy_json(Some(MINT))).unwrap();
+        fs::write(dir.join("arb_broken_20240301120001.json"), "{ not json").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let store = RecordStore::open_in_memory().unwrap();
+        let archive = dir.join("archive");
+        let report = migrate_legacy(&store, &dir, &[], &AfterMigration::Archive(archive.clone())).unwrap();
+
+        assert_eq!(report.migrated, 1);
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.prefix_only, 0);
+        assert_eq!(report.unparseable.len(), 1);
+        assert_eq!(report.unparseable[0].0, "arb_broken_20240301120001.json");
+        assert!(dir.join("arb_broken_20240301120001.json").exists());
+        assert!(!dir.join("arb_7GCihgDB_20240301120000.json").exists());
+        assert_eq!(fs::read_dir(&archive).unwrap().count(), 2);
+
+        // Running it again over the archive finds nothing new
+        let again = migrate_legacy(&store, &archive, &[], &AfterMigration::Keep).unwrap();
+        assert_eq!((again.migrated, again.duplicates), (0, 2));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,375 @@
+//! Memory-mapped board of live per-pool prices for other processes on the host
+//!
+//! File layout, all integers little-endian:
+//!
+//! ```text
+//! header (64 bytes)
+//!   0  magic       [u8; 8]  "SOLPXBRD"
+//!   8  version     u32      FORMAT_VERSION
+//!   12 slot_size   u32      64
+//!   16 slot_count  u32
+//!   20 reserved    u32
+//!   24 created_ms  i64      unix millis the file was created
+//!
+//! slot i at offset 64 + i * 64
+//!   0  seq         u64      odd while the writer is mid-update
+//!   8  key         u64      FNV-1a 64 of the pool id, 0 = empty slot
+//!   16 price       f64
+//!   24 liquidity   u64      lamports
+//!   32 updated_ms  i64      unix millis of the last update
+//!   40 chain_slot  u64      slot the price was observed at
+//! ```
+//!
+//! There is a single writer. To read a slot: load `seq`, retry if odd, copy
+//! the fields, load `seq` again and retry if it changed. The file is replaced
+//! (never rewritten in place) when the bot starts or the layout changes, so a
+//! reader that sees a different magic, version or slot count must reopen it.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use anyhow::{anyhow, Result};
+use memmap2::{Mmap, MmapMut};
+use serde::Serialize;
+
+pub const MAGIC: &[u8; 8] = b"SOLPXBRD";
+pub const FORMAT_VERSION: u32 = 1;
+pub const HEADER_SIZE: usize = 64;
+pub const SLOT_SIZE: usize = 64;
+pub const DEFAULT_SLOT_COUNT: u32 = 4096;
+
+/// How many times a reader retries a slot the writer keeps changing
+const READ_RETRIES: usize = 64;
+
+/// FNV-1a 64 of the pool id, the key external readers match on
+pub fn pool_key(pool_id: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in pool_id.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    // 0 marks an empty slot
+    hash.max(1)
+}
+
+/// One pool's price as read from the board
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BoardEntry {
+    pub key: u64,
+    pub price: f64,
+    pub liquidity: u64,
+    pub updated_ms: i64,
+    pub chain_slot: u64,
+}
+
+/// A mapping seen as 64-bit words, the only way slots are read or written
+///
+/// Another process maps the same memory, so slot fields are only ever touched
+/// as atomics. The pointer is taken from the mapping once, when it is opened,
+/// and nothing else borrows the mapped bytes after that.
+struct Words {
+    ptr: NonNull<AtomicU64>,
+    len: usize,
+}
+
+impl Words {
+    /// SAFETY: `ptr` must be 8-byte aligned and stay mapped for `len` bytes
+    /// for as long as the `Words` lives
+    unsafe fn new(ptr: *const u8, len: usize) -> Self {
+        debug_assert_eq!(ptr as usize % std::mem::align_of::<AtomicU64>(), 0);
+        Self {
+            ptr: NonNull::new(ptr as *mut AtomicU64).expect("mapping is never null"),
+            len: len / 8,
+        }
+    }
+
+    fn as_slice(&self) -> &[AtomicU64] {
+        // SAFETY: guaranteed by `new`; AtomicU64 allows shared mutation
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// The word at byte `offset`, which is a multiple of 8
+    fn at(&self, offset: usize) -> &AtomicU64 {
+        &self.as_slice()[offset / 8]
+    }
+}
+
+fn slot_offset(index: usize) -> usize {
+    HEADER_SIZE + index * SLOT_SIZE
+}
+
+/// `PRICE_BOARD_PATH` enables the board, `PRICE_BOARD_SLOTS` sizes it
+#[derive(Debug, Clone)]
+pub struct PriceBoardConfig {
+    pub path: String,
+    pub slot_count: u32,
+}
+
+impl PriceBoardConfig {
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("PRICE_BOARD_PATH").ok().filter(|p| !p.is_empty())?;
+        let slot_count = std::env::var("PRICE_BOARD_SLOTS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_SLOT_COUNT);
+        Some(Self { path, slot_count })
+    }
+}
+
+/// The bot's side of the board
+///
+/// Owned by the one task that applies price updates; `update` never blocks
+/// and never allocates once a pool has its slot.
+pub struct PriceBoardWriter {
+    /// Kept only to hold the mapping open; all access goes through `words`
+    _map: MmapMut,
+    words: Words,
+    slot_count: usize,
+    slots: HashMap<u64, usize>,
+    full: bool,
+}
+
+impl PriceBoardWriter {
+    /// Create a fresh board at `config.path`, replacing any existing file
+    pub fn create(config: &PriceBoardConfig) -> Result<Self> {
+        let path = Path::new(&config.path);
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        // Build the new file next to the old one and swap it in, so readers
+        // still mapping the old file never see a half-written header
+        let tmp_path = path.with_extension("tmp");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        let len = HEADER_SIZE + config.slot_count as usize * SLOT_SIZE;
+        file.set_len(len as u64)?;
+
+        // SAFETY: the file was just created by us and is only resized here
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[0..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        map[12..16].copy_from_slice(&(SLOT_SIZE as u32).to_le_bytes());
+        map[16..20].copy_from_slice(&config.slot_count.to_le_bytes());
+        map[24..32].copy_from_slice(&chrono::Utc::now().timestamp_millis().to_le_bytes());
+        map.flush()?;
+        std::fs::rename(&tmp_path, path)?;
+
+        // SAFETY: the mapping is page aligned and `map` moves into `Self`
+        // with it, a move doesn't change where the pages are mapped
+        let words = unsafe { Words::new(map.as_mut_ptr(), len) };
+        Ok(Self {
+            _map: map,
+            words,
+            slot_count: config.slot_count as usize,
+            slots: HashMap::new(),
+            full: false,
+        })
+    }
+
+    /// Find or claim the slot for `key`, linear probing from `key % slot_count`
+    fn slot_for(&mut self, key: u64) -> Option<usize> {
+        if let Some(index) = self.slots.get(&key) {
+            return Some(*index);
+        }
+        let start = (key % self.slot_count as u64) as usize;
+        let index = (0..self.slot_count)
+            .map(|step| (start + step) % self.slot_count)
+            .find(|index| self.words.at(slot_offset(*index) + 8).load(Ordering::Relaxed) == 0)?;
+        self.slots.insert(key, index);
+        Some(index)
+    }
+
+    /// Publish a pool's latest price. Returns false if the board is full.
+    pub fn update(&mut self, pool_id: &str, price: f64, liquidity: u64, chain_slot: u64) -> bool {
+        let key = pool_key(pool_id);
+        let Some(index) = self.slot_for(key) else {
+            self.full = true;
+            return false;
+        };
+
+        let word = |field: usize| self.words.at(slot_offset(index) + field);
+        let seq = word(0);
+        let start = seq.load(Ordering::Relaxed);
+
+        seq.store(start.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        word(8).store(key, Ordering::Relaxed);
+        word(16).store(price.to_bits(), Ordering::Relaxed);
+        word(24).store(liquidity, Ordering::Relaxed);
+        word(32).store(chrono::Utc::now().timestamp_millis() as u64, Ordering::Relaxed);
+        word(40).store(chain_slot, Ordering::Relaxed);
+        seq.store(start.wrapping_add(2), Ordering::Release);
+        true
+    }
+
+    /// Whether an update has been dropped because every slot is taken
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+}
+
+// SAFETY: `words` points into the mapping the writer owns, which moves with it
+unsafe impl Send for PriceBoardWriter {}
+
+/// Read-only view of a board, used by `priceboard dump`
+pub struct PriceBoardReader {
+    _map: Mmap,
+    words: Words,
+    slot_count: usize,
+}
+
+// SAFETY: the reader only loads through atomics from a mapping it owns
+unsafe impl Send for PriceBoardReader {}
+unsafe impl Sync for PriceBoardReader {}
+
+impl PriceBoardReader {
+    pub fn open(path: &str) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        // SAFETY: the writer only changes slot contents through the seqlock,
+        // the header is fixed for the life of the file
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < HEADER_SIZE || &map[0..8] != MAGIC {
+            return Err(anyhow!("{} is not a price board", path));
+        }
+        let version = u32::from_le_bytes(map[8..12].try_into()?);
+        let slot_size = u32::from_le_bytes(map[12..16].try_into()?) as usize;
+        if version != FORMAT_VERSION || slot_size != SLOT_SIZE {
+            return Err(anyhow!(
+                "Unsupported price board layout: version {}, slot size {}",
+                version,
+                slot_size
+            ));
+        }
+        let slot_count = u32::from_le_bytes(map[16..20].try_into()?) as usize;
+        if map.len() < HEADER_SIZE + slot_count * SLOT_SIZE {
+            return Err(anyhow!("Price board {} is truncated", path));
+        }
+        // SAFETY: page aligned and owned by `Self` with the words; only ever loaded from
+        let words = unsafe { Words::new(map.as_ptr(), map.len()) };
+        Ok(Self { _map: map, words, slot_count })
+    }
+
+    /// Consistent copy of slot `index`, None if empty or the writer kept
+    /// changing it for every retry
+    pub fn read_slot(&self, index: usize) -> Option<BoardEntry> {
+        let word = |field: usize| self.words.at(slot_offset(index) + field);
+        let seq = word(0);
+        for _ in 0..READ_RETRIES {
+            let before = seq.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let entry = BoardEntry {
+                key: word(8).load(Ordering::Relaxed),
+                price: f64::from_bits(word(16).load(Ordering::Relaxed)),
+                liquidity: word(24).load(Ordering::Relaxed),
+                updated_ms: word(32).load(Ordering::Relaxed) as i64,
+                chain_slot: word(40).load(Ordering::Relaxed),
+            };
+            fence(Ordering::Acquire);
+            if seq.load(Ordering::Relaxed) == before {
+                return (entry.key != 0).then_some(entry);
+            }
+        }
+        None
+    }
+
+    /// Every occupied slot
+    pub fn entries(&self) -> Vec<BoardEntry> {
+        (0..self.slot_count).filter_map(|index| self.read_slot(index)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn board_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("price_board_{}_{}.bin", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn updates_are_read_back_by_pool_key() {
+        let config = PriceBoardConfig { path: board_path("roundtrip"), slot_count: 8 };
+        let mut writer = PriceBoardWriter::create(&config).unwrap();
+        assert!(writer.update("poolA", 0.5, 1_000, 7));
+        assert!(writer.update("poolB", 2.0, 3_000, 8));
+        assert!(writer.update("poolA", 0.75, 1_500, 9));
+
+        let reader = PriceBoardReader::open(&config.path).unwrap();
+        let mut entries = reader.entries();
+        entries.sort_by_key(|entry| entry.chain_slot);
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].key, entries[0].price, entries[0].liquidity), (pool_key("poolB"), 2.0, 3_000));
+        assert_eq!((entries[1].key, entries[1].price, entries[1].liquidity), (pool_key("poolA"), 0.75, 1_500));
+        std::fs::remove_file(&config.path).ok();
+    }
+
+    #[test]
+    fn full_board_drops_new_pools() {
+        let config = PriceBoardConfig { path: board_path("full"), slot_count: 2 };
+        let mut writer = PriceBoardWriter::create(&config).unwrap();
+        assert!(writer.update("a", 1.0, 1, 1));
+        assert!(writer.update("b", 1.0, 1, 1));
+        assert!(!writer.update("c", 1.0, 1, 1));
+        assert!(writer.is_full());
+        // Pools that already have a slot keep updating
+        assert!(writer.update("a", 2.0, 2, 2));
+        std::fs::remove_file(&config.path).ok();
+    }
+
+    #[test]
+    fn reader_never_sees_a_torn_slot() {
+        let config = PriceBoardConfig { path: board_path("torn"), slot_count: 4 };
+        let mut writer = PriceBoardWriter::create(&config).unwrap();
+        writer.update("pool", 0.0, 0, 0);
+        let reader = Arc::new(PriceBoardReader::open(&config.path).unwrap());
+        let index = (0..4).find(|index| reader.read_slot(*index).is_some()).unwrap();
+        let done = Arc::new(AtomicBool::new(false));
+        let reads = Arc::new(AtomicU64::new(0));
+
+        let reading = {
+            let reader = Arc::clone(&reader);
+            let done = Arc::clone(&done);
+            let reads = Arc::clone(&reads);
+            std::thread::spawn(move || {
+                let mut last = 0u64;
+                while !done.load(Ordering::Relaxed) {
+                    let Some(entry) = reader.read_slot(index) else { continue };
+                    // Every update writes the same n into all three fields
+                    assert_eq!(entry.price, entry.liquidity as f64, "torn read: {:?}", entry);
+                    assert_eq!(entry.chain_slot, entry.liquidity, "torn read: {:?}", entry);
+                    assert!(entry.chain_slot >= last, "went back from {} to {}", last, entry.chain_slot);
+                    last = entry.chain_slot;
+                    reads.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+
+        // Keep writing until the reader has raced a good number of updates
+        let mut n = 0u64;
+        while n < 1_000_000 || reads.load(Ordering::Relaxed) < 100_000 {
+            n += 1;
+            writer.update("pool", n as f64, n, n);
+        }
+        done.store(true, Ordering::Relaxed);
+        reading.join().unwrap();
+        std::fs::remove_file(&config.path).ok();
+    }
+}
@@ -0,0 +1,500 @@
+//! Redacted export of recorded data for sharing with collaborators
+//!
+//! Configured addresses and every recorded trade signature are replaced with
+//! a keyed pseudonym, so the same address maps to the same pseudonym in every
+//! exported file (and across exports made with the same key). The mapping back
+//! to the originals is written to a separate file that must stay local.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::store::RecordStore;
+use crate::common::hmac::{hmac_sha256, to_hex};
+
+/// Record locations copied into the export besides the store tables
+pub const RECORD_FILE_DIRS: [&str; 2] = ["./record", "./arbitrage_opportunities"];
+
+/// Only text records are copied; the store database and anything binary are skipped
+const TEXT_EXTENSIONS: [&str; 5] = ["json", "jsonl", "log", "txt", "csv"];
+
+/// Key must be at least this long, shorter keys make the pseudonyms guessable
+const MIN_KEY_LEN: usize = 16;
+
+/// Redaction config file, e.g. `redaction.json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedactionConfig {
+    /// Addresses to pseudonymize: wallets, token accounts, copy targets
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Replace journal notes and memo program logs with a placeholder
+    #[serde(default = "default_true")]
+    pub strip_memos: bool,
+    /// Round store timestamps down to the minute
+    #[serde(default)]
+    pub minute_timestamps: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl RedactionConfig {
+    pub fn load(path: &str) -> Result<Self> {
+        let raw = fs::read_to_string(path).with_context(|| format!("Failed to read redaction config {}", path))?;
+        serde_json::from_str(&raw).with_context(|| format!("Invalid redaction config {}", path))
+    }
+}
+
+/// Read `REDACTION_KEY`; it is kept out of the config file so the config can be shared
+pub fn key_from_env() -> Result<Vec<u8>> {
+    let key = std::env::var("REDACTION_KEY").map_err(|_| anyhow!("Set REDACTION_KEY to export redacted records"))?;
+    if key.len() < MIN_KEY_LEN {
+        return Err(anyhow!("REDACTION_KEY must be at least {} characters", MIN_KEY_LEN));
+    }
+    Ok(key.into_bytes())
+}
+
+/// Applies one redaction config and remembers every pseudonym it handed out
+pub struct Redactor {
+    key: Vec<u8>,
+    config: RedactionConfig,
+    /// Original -> pseudonym
+    mapping: BTreeMap<String, String>,
+}
+
+impl Redactor {
+    pub fn new(key: Vec<u8>, config: RedactionConfig) -> Self {
+        let mut redactor = Self { key, config: config.clone(), mapping: BTreeMap::new() };
+        for address in config.addresses.iter().filter(|a| !a.is_empty()) {
+            redactor.pseudonym(address);
+        }
+        redactor
+    }
+
+    /// Stable pseudonym for `value`, the same for every run with the same key
+    pub fn pseudonym(&mut self, value: &str) -> String {
+        if let Some(pseudonym) = self.mapping.get(value) {
+            return pseudonym.clone();
+        }
+        let mac = hmac_sha256(&self.key, value.as_bytes());
+        let pseudonym = format!("anon_{}", to_hex(&mac[..8]));
+        self.mapping.insert(value.to_string(), pseudonym.clone());
+        pseudonym
+    }
+
+    /// Replace every known original in free text, and strip memo log contents
+    pub fn redact_text(&self, text: &str) -> String {
+        // Longest first so an original containing another is replaced whole
+        let mut originals: Vec<&String> = self.mapping.keys().collect();
+        originals.sort_by_key(|o| std::cmp::Reverse(o.len()));
+        let mut redacted = text.to_string();
+        for original in originals {
+            if redacted.contains(original.as_str()) {
+                redacted = redacted.replace(original.as_str(), &self.mapping[original]);
+            }
+        }
+        if self.config.strip_memos {
+            redacted = redacted.lines().map(strip_memo_log).collect::<Vec<_>>().join("\n");
+            if text.ends_with('\n') {
+                redacted.push('\n');
+            }
+        }
+        redacted
+    }
+
+    /// Journal notes are free text and may say anything about the operator
+    pub fn note(&self, note: &str) -> String {
+        if self.config.strip_memos {
+            "[stripped]".to_string()
+        } else {
+            self.redact_text(note)
+        }
+    }
+
+    pub fn timestamp(&self, millis: i64) -> i64 {
+        if self.config.minute_timestamps {
+            millis - millis.rem_euclid(60_000)
+        } else {
+            millis
+        }
+    }
+
+    /// Originals that must not appear anywhere in the export
+    pub fn originals(&self) -> impl Iterator<Item = &String> {
+        self.mapping.keys()
+    }
+
+    /// Write `pseudonym,original` lines; this file links the export back to the wallet
+    pub fn write_mapping(&self, path: &Path) -> Result<()> {
+        let mut by_pseudonym: Vec<(&String, &String)> = self.mapping.iter().map(|(o, p)| (p, o)).collect();
+        by_pseudonym.sort();
+        let mut file = fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        writeln!(file, "pseudonym,original")?;
+        for (pseudonym, original) in by_pseudonym {
+            writeln!(file, "{},{}", pseudonym, original)?;
+        }
+        Ok(())
+    }
+}
+
+/// `Program log: Memo (len 5): "hello"` keeps the prefix, loses the text
+fn strip_memo_log(line: &str) -> String {
+    match line.find("Memo (len") {
+        Some(at) => format!("{}Memo [stripped]", &line[..at]),
+        None => line.to_string(),
+    }
+}
+
+/// What an export wrote
+#[derive(Debug, Default)]
+pub struct RedactedExport {
+    pub trades: usize,
+    pub journal: usize,
+    pub rejections: usize,
+    pub files: usize,
+    pub pseudonyms: usize,
+}
+
+fn write_jsonl<T: Serialize>(path: &Path, rows: &[T]) -> Result<()> {
+    let mut file = fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    for row in rows {
+        writeln!(file, "{}", serde_json::to_string(row)?)?;
+    }
+    Ok(())
+}
+
+fn is_text_record(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| TEXT_EXTENSIONS.contains(&e))
+}
+
+/// Text record files under `dir`, recursively
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if is_text_record(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The mapping must not end up in the directory that gets shared
+fn ensure_outside(out_dir: &Path, mapping: &Path) -> Result<()> {
+    let out = out_dir.canonicalize()?;
+    let parent = match mapping.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.canonicalize()?,
+        _ => std::env::current_dir()?,
+    };
+    if parent.starts_with(&out) {
+        return Err(anyhow!("Mapping file {} must be outside the export directory", mapping.display()));
+    }
+    Ok(())
+}
+
+/// Fail if any original survived into the export
+fn audit(redactor: &Redactor, written: &[PathBuf]) -> Result<()> {
+    for path in written {
+        let content = fs::read_to_string(path)?;
+        let name = path.to_string_lossy();
+        if let Some(leak) = redactor.originals().find(|o| content.contains(o.as_str()) || name.contains(o.as_str())) {
+            return Err(anyhow!("Redaction audit failed: {} still contains {}", path.display(), leak));
+        }
+    }
+    Ok(())
+}
+
+/// Export the store tables as JSON lines plus the text record files, redacted,
+/// into `out_dir`, and write the pseudonym mapping to `mapping_path`
+pub fn export_redacted(
+    store: &RecordStore,
+    redactor: Redactor,
+    out_dir: &str,
+    mapping_path: &str,
+) -> Result<RedactedExport> {
+    export_redacted_from(store, redactor, &RECORD_FILE_DIRS, out_dir, mapping_path)
+}
+
+/// `export_redacted` copying text records from `record_dirs`
+fn export_redacted_from(
+    store: &RecordStore,
+    mut redactor: Redactor,
+    record_dirs: &[&str],
+    out_dir: &str,
+    mapping_path: &str,
+) -> Result<RedactedExport> {
+    let out_dir = Path::new(out_dir);
+    let mapping_path = Path::new(mapping_path);
+    fs::create_dir_all(out_dir)?;
+    ensure_outside(out_dir, mapping_path)?;
+
+    let mut trades = store.trades(None)?;
+    // Trade ids are signatures, both link straight to the wallet
+    for trade in &trades {
+        redactor.pseudonym(&trade.trade_id);
+        if let Some(signature) = &trade.signature {
+            redactor.pseudonym(signature);
+        }
+    }
+    for trade in trades.iter_mut() {
+        trade.trade_id = redactor.pseudonym(&trade.trade_id);
+        trade.signature = trade.signature.as_deref().map(|s| redactor.pseudonym(s));
+        trade.mint = redactor.redact_text(&trade.mint);
+        trade.created_at = redactor.timestamp(trade.created_at);
+    }
+
+    let mut journal = store.journal()?;
+    for entry in journal.iter_mut() {
+        entry.created_at = redactor.timestamp(entry.created_at);
+        entry.mint = entry.mint.as_deref().map(|m| redactor.redact_text(m));
+        entry.trade_id = entry.trade_id.as_deref().map(|t| redactor.pseudonym(t));
+        entry.note = redactor.note(&entry.note);
+        entry.tags = entry
+            .tags
+            .iter()
+            .map(|(k, v)| (k.clone(), redactor.redact_text(v)))
+            .collect();
+    }
+
+    let mut rejections = store.rejections(None)?;
+    for rejection in rejections.iter_mut() {
+        rejection.created_at = redactor.timestamp(rejection.created_at);
+        rejection.token = redactor.redact_text(&rejection.token);
+    }
+
+    let written_tables = [out_dir.join("trades.jsonl"), out_dir.join("journal.jsonl"), out_dir.join("rejections.jsonl")];
+    write_jsonl(&written_tables[0], &trades)?;
+    write_jsonl(&written_tables[1], &journal)?;
+    write_jsonl(&written_tables[2], &rejections)?;
+    let mut written = written_tables.to_vec();
+
+    let mut sources = Vec::new();
+    for dir in record_dirs {
+        collect_files(Path::new(dir), &mut sources)?;
+    }
+    let out_canonical = out_dir.canonicalize()?;
+    let mut seen_targets = BTreeSet::new();
+    for source in &sources {
+        // An export directory placed under ./record must not copy itself
+        if source.canonicalize().is_ok_and(|p| p.starts_with(&out_canonical)) {
+            continue;
+        }
+        // Only the normal components, an absolute source must not escape `files`
+        let relative: PathBuf = source.components().filter(|c| matches!(c, Component::Normal(_))).collect();
+        let target = out_dir.join("files").join(redactor.redact_text(&relative.to_string_lossy()));
+        if !seen_targets.insert(target.clone()) {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = fs::read_to_string(source).with_context(|| format!("Failed to read {}", source.display()))?;
+        fs::write(&target, redactor.redact_text(&content))?;
+        written.push(target);
+    }
+
+    audit(&redactor, &written)?;
+    redactor.write_mapping(mapping_path)?;
+
+    Ok(RedactedExport {
+        trades: trades.len(),
+        journal: journal.len(),
+        rejections: rejections.len(),
+        files: written.len() - written_tables.len(),
+        pseudonyms: redactor.mapping.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_client::solana_sdk::{signature::Keypair, signer::Signer};
+
+    use super::*;
+    use crate::record::journal::{JournalScope, JournalSource};
+    use crate::record::store::TradeRecord;
+
+    const KEY: &[u8] = b"a shared redaction key";
+
+    fn config(addresses: &[&str], minute_timestamps: bool) -> RedactionConfig {
+        RedactionConfig {
+            addresses: addresses.iter().map(|a| a.to_string()).collect(),
+            strip_memos: true,
+            minute_timestamps,
+        }
+    }
+
+    /// A fresh directory under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("redact_test_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files_under(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(files_under(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn pseudonyms_are_stable_per_key() {
+        let wallet = Keypair::new().pubkey().to_string();
+        let mut first = Redactor::new(KEY.to_vec(), config(&[&wallet], false));
+        let mut second = Redactor::new(KEY.to_vec(), config(&[], false));
+        let mut other_key = Redactor::new(b"a different redaction key".to_vec(), config(&[], false));
+
+        let pseudonym = first.pseudonym(&wallet);
+        assert!(pseudonym.starts_with("anon_") && pseudonym.len() == "anon_".len() + 16, "{}", pseudonym);
+        assert_eq!(second.pseudonym(&wallet), pseudonym);
+        assert_ne!(other_key.pseudonym(&wallet), pseudonym);
+        assert_ne!(first.pseudonym("another"), pseudonym);
+    }
+
+    #[test]
+    fn text_loses_addresses_and_memo_contents() {
+        let wallet = Keypair::new().pubkey().to_string();
+        let mut redactor = Redactor::new(KEY.to_vec(), config(&[&wallet], true));
+        let pseudonym = redactor.pseudonym(&wallet);
+        let text = format!("signer {}\nProgram log: Memo (len 8): \"gm frens\"\n", wallet);
+        assert_eq!(
+            redactor.redact_text(&text),
+            format!("signer {}\nProgram log: Memo [stripped]\n", pseudonym)
+        );
+        assert_eq!(redactor.note(&format!("bought with {}", wallet)), "[stripped]");
+        assert_eq!(redactor.timestamp(1_700_000_123_456), 1_700_000_100_000);
+        assert_eq!(Redactor::new(KEY.to_vec(), config(&[], false)).timestamp(1_700_000_123_456), 1_700_000_123_456);
+    }
+
+    /// A store and a record directory that mention `wallet`, `target` and `signature` everywhere
+    fn recorded(wallet: &str, target: &str, signature: &str, dir: &Path) -> RecordStore {
+        let store = RecordStore::open_in_memory().unwrap();
+        store
+            .record_trade(&TradeRecord {
+                trade_id: signature.to_string(),
+                created_at: 1_700_000_123_456,
+                mint: "MintX".to_string(),
+                side: "buy".to_string(),
+                sol_amount: Some(1_000_000),
+                token_amount: Some(500),
+                price: None,
+                signature: Some(signature.to_string()),
+                priority_fee_strategy: None,
+                priority_fee_lamports: None,
+            })
+            .unwrap();
+        let tags = BTreeMap::from([("copied".to_string(), target.to_string())]);
+        let scope = JournalScope::Trade { trade_id: signature.to_string() };
+        store
+            .add_journal_entry(&scope, &format!("{} copied {}", wallet, target), &tags, JournalSource::User)
+            .unwrap();
+
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(
+            dir.join("sub").join(format!("{}.log", wallet)),
+            format!("{} bought MintX\nProgram log: Memo (len 5): \"hello\"\nTX_HASH: {}\n", wallet, signature),
+        )
+        .unwrap();
+        fs::write(dir.join("copy.jsonl"), format!("{{\"target\":\"{}\"}}\n", target)).unwrap();
+        fs::write(dir.join("store.db"), wallet).unwrap();
+        store
+    }
+
+    #[test]
+    fn export_contains_no_configured_address_or_signature() {
+        let scratch = scratch("export");
+        let (wallet, target) = (Keypair::new().pubkey().to_string(), Keypair::new().pubkey().to_string());
+        let signature = bs58::encode([7u8; 64]).into_string();
+        let records = scratch.join("record");
+        let store = recorded(&wallet, &target, &signature, &records);
+
+        let out_dir = scratch.join("out");
+        let mapping = scratch.join("mapping.csv");
+        let redactor = Redactor::new(KEY.to_vec(), config(&[&wallet, &target], true));
+        let records_dir = records.to_string_lossy().to_string();
+        let export = export_redacted_from(
+            &store,
+            redactor,
+            &[records_dir.as_str()],
+            &out_dir.to_string_lossy(),
+            &mapping.to_string_lossy(),
+        )
+        .unwrap();
+        assert_eq!((export.trades, export.journal, export.rejections, export.files), (1, 1, 0, 2));
+        assert_eq!(export.pseudonyms, 3);
+
+        let written = files_under(&out_dir);
+        assert_eq!(written.len(), 5);
+        for path in &written {
+            let content = fs::read_to_string(path).unwrap();
+            for original in [&wallet, &target, &signature] {
+                assert!(!content.contains(original.as_str()), "{} leaks {}", path.display(), original);
+                assert!(!path.to_string_lossy().contains(original.as_str()), "{} leaks {}", path.display(), original);
+            }
+            assert!(!content.contains("hello"), "{} keeps the memo", path.display());
+        }
+        let trades = fs::read_to_string(out_dir.join("trades.jsonl")).unwrap();
+        assert!(trades.contains("1700000100000"), "{}", trades);
+        assert!(trades.contains("MintX"));
+
+        // The sources are untouched, only the mapping links back to them
+        let source = fs::read_to_string(records.join("sub").join(format!("{}.log", wallet))).unwrap();
+        assert!(source.contains(&wallet) && source.contains("hello"));
+        let mapping = fs::read_to_string(&mapping).unwrap();
+        assert!(mapping.starts_with("pseudonym,original\n"));
+        assert!(mapping.contains(&wallet) && mapping.contains(&target) && mapping.contains(&signature));
+        fs::remove_dir_all(&scratch).unwrap();
+    }
+
+    #[test]
+    fn exports_with_the_same_key_join() {
+        let scratch = scratch("stable");
+        let (wallet, target) = (Keypair::new().pubkey().to_string(), Keypair::new().pubkey().to_string());
+        let signature = bs58::encode([9u8; 64]).into_string();
+        let store = recorded(&wallet, &target, &signature, &scratch.join("record"));
+
+        let export = |name: &str| {
+            let redactor = Redactor::new(KEY.to_vec(), config(&[&wallet, &target], true));
+            let out_dir = scratch.join(name);
+            let mapping = scratch.join(format!("{}.csv", name));
+            export_redacted_from(&store, redactor, &[], &out_dir.to_string_lossy(), &mapping.to_string_lossy()).unwrap();
+            (fs::read_to_string(out_dir.join("trades.jsonl")).unwrap(), fs::read_to_string(mapping).unwrap())
+        };
+        let (first_trades, first_mapping) = export("first");
+        let (second_trades, second_mapping) = export("second");
+        assert_eq!(first_trades, second_trades);
+        assert_eq!(first_mapping, second_mapping);
+        fs::remove_dir_all(&scratch).unwrap();
+    }
+
+    #[test]
+    fn mapping_inside_the_export_is_refused() {
+        let scratch = scratch("inside");
+        let out_dir = scratch.join("out");
+        let store = RecordStore::open_in_memory().unwrap();
+        let redactor = Redactor::new(KEY.to_vec(), config(&[], false));
+        let mapping = out_dir.join("mapping.csv");
+        let err = export_redacted_from(&store, redactor, &[], &out_dir.to_string_lossy(), &mapping.to_string_lossy());
+        assert!(err.is_err());
+        assert!(!mapping.exists());
+        fs::remove_dir_all(&scratch).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use super::store::RecordStore;
+
+/// Why a detected opportunity was not executed, with its profit breakdown
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectionRecord {
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    pub token: String,
+    pub buy_dex: String,
+    pub sell_dex: String,
+    /// Skip reason code, e.g. "below_net_profit"
+    pub reason: String,
+    pub gross_spread_bps: f64,
+    pub dex_fee_bps: f64,
+    pub priority_fee_bps: f64,
+    pub tip_bps: f64,
+    pub conversion_bps: f64,
+    pub net_bps: f64,
+    /// Distance from break-even in bps, zero if it was profitable
+    pub miss_bps: f64,
+    pub liquidity_shortfall: Option<u64>,
+}
+
+impl RecordStore {
+    /// Store a batch of rejections from one scan in a single transaction
+    pub fn record_rejections(&self, rejections: &[RejectionRecord]) -> Result<()> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO rejections
+                    (created_at, token, buy_dex, sell_dex, reason, gross_spread_bps, dex_fee_bps,
+                     priority_fee_bps, tip_bps, conversion_bps, net_bps, miss_bps, liquidity_shortfall)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )?;
+            for r in rejections {
+                stmt.execute(params![
+                    r.created_at,
+                    r.token,
+                    r.buy_dex,
+                    r.sell_dex,
+                    r.reason,
+                    r.gross_spread_bps,
+                    r.dex_fee_bps,
+                    r.priority_fee_bps,
+                    r.tip_bps,
+                    r.conversion_bps,
+                    r.net_bps,
+                    r.miss_bps,
+                    r.liquidity_shortfall.map(|v| v as i64),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rejections for a token, or all of them when `token` is None, oldest first
+    pub fn rejections(&self, token: Option<&str>) -> Result<Vec<RejectionRecord>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT created_at, token, buy_dex, sell_dex, reason, gross_spread_bps, dex_fee_bps,
+                    priority_fee_bps, tip_bps, conversion_bps, net_bps, miss_bps, liquidity_shortfall
+             FROM rejections WHERE (?1 IS NULL OR token = ?1) ORDER BY created_at, id",
+        )?;
+        let rows = stmt
+            .query_map(params![token], |row| {
+                Ok(RejectionRecord {
+                    created_at: row.get(0)?,
+                    token: row.get(1)?,
+                    buy_dex: row.get(2)?,
+                    sell_dex: row.get(3)?,
+                    reason: row.get(4)?,
+                    gross_spread_bps: row.get(5)?,
+                    dex_fee_bps: row.get(6)?,
+                    priority_fee_bps: row.get(7)?,
+                    tip_bps: row.get(8)?,
+                    conversion_bps: row.get(9)?,
+                    net_bps: row.get(10)?,
+                    miss_bps: row.get(11)?,
+                    liquidity_shortfall: row.get::<_, Option<i64>>(12)?.map(|v| v as u64),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
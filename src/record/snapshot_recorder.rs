@@ -0,0 +1,79 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// One DEX price observation for a token, one line in the snapshot file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceSnapshot {
+    pub timestamp_ms: i64,
+    pub token_mint: String,
+    pub dex: String,
+    pub price: f64,
+    pub liquidity: u64,
+    pub slot: u64,
+}
+
+/// Settings for the periodic price snapshot recorder
+#[derive(Debug, Clone)]
+pub struct SnapshotRecorderConfig {
+    /// How often every (token, dex) price is written
+    pub interval: Duration,
+    /// Newline-delimited JSON file the snapshots are appended to
+    pub path: String,
+}
+
+impl SnapshotRecorderConfig {
+    pub fn from_env() -> Self {
+        let interval_ms = std::env::var("SNAPSHOT_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1000);
+        let path = std::env::var("SNAPSHOT_PATH")
+            .unwrap_or_else(|_| "./record/snapshots.ndjson".to_string());
+
+        Self {
+            interval: Duration::from_millis(interval_ms.max(100)),
+            path,
+        }
+    }
+}
+
+/// Observe-only mode records snapshots and skips opportunity detection entirely
+pub fn is_observe_only() -> bool {
+    std::env::var("OBSERVE_ONLY")
+        .ok()
+        .and_then(|v| v.parse::<bool>().ok())
+        .unwrap_or(false)
+}
+
+/// Append snapshots to the NDJSON file, creating it and its directory if needed
+pub fn append_snapshots(path: &str, snapshots: &[PriceSnapshot]) -> Result<(), String> {
+    if snapshots.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(parent) = Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+        }
+    }
+
+    let mut buffer = String::new();
+    for snapshot in snapshots {
+        let line = serde_json::to_string(snapshot).map_err(|e| format!("Failed to encode snapshot: {}", e))?;
+        buffer.push_str(&line);
+        buffer.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    file.write_all(buffer.as_bytes())
+        .map_err(|e| format!("Failed to write to {}: {}", path, e))
+}
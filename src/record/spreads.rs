@@ -0,0 +1,60 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use super::store::RecordStore;
+
+/// Distribution of one token's best cross-DEX spread over one hour of scans,
+/// including scans that stayed below the arbitrage threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct SpreadSummary {
+    /// Unix time in milliseconds of the start of the hour
+    pub hour_start: i64,
+    pub token: String,
+    /// Scans that saw the token on at least two DEXes
+    pub count: u64,
+    pub p50_bps: f64,
+    pub p95_bps: f64,
+    pub max_bps: f64,
+}
+
+impl RecordStore {
+    /// Store an hour's summaries, replacing rows already written for that hour
+    pub fn record_spread_summaries(&self, summaries: &[SpreadSummary]) -> Result<()> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO spread_hourly (hour_start, token, count, p50_bps, p95_bps, max_bps)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for s in summaries {
+                stmt.execute(params![s.hour_start, s.token, s.count as i64, s.p50_bps, s.p95_bps, s.max_bps])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Hourly summaries for a token, or every token when `token` is None, oldest first
+    pub fn spread_summaries(&self, token: Option<&str>) -> Result<Vec<SpreadSummary>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT hour_start, token, count, p50_bps, p95_bps, max_bps
+             FROM spread_hourly WHERE (?1 IS NULL OR token = ?1) ORDER BY hour_start, token",
+        )?;
+        let rows = stmt
+            .query_map(params![token], |row| {
+                Ok(SpreadSummary {
+                    hour_start: row.get(0)?,
+                    token: row.get(1)?,
+                    count: row.get::<_, i64>(2)? as u64,
+                    p50_bps: row.get(3)?,
+                    p95_bps: row.get(4)?,
+                    max_bps: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
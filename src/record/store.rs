@@ -0,0 +1,287 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+/// Default location of the SQLite record store
+pub const DEFAULT_RECORD_DB_PATH: &str = "./record/bot.db";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS trades (
+    trade_id     TEXT PRIMARY KEY,
+    created_at   INTEGER NOT NULL,
+    mint         TEXT NOT NULL,
+    side         TEXT NOT NULL,
+    sol_amount   INTEGER,
+    token_amount INTEGER,
+    price        REAL,
+    signature    TEXT,
+    priority_fee_strategy TEXT,
+    priority_fee_lamports INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_trades_mint ON trades (mint, created_at);
+
+CREATE TABLE IF NOT EXISTS journal (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at INTEGER NOT NULL,
+    scope      TEXT NOT NULL,
+    mint       TEXT,
+    trade_id   TEXT,
+    note       TEXT NOT NULL,
+    source     TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_journal_mint ON journal (mint, created_at);
+CREATE INDEX IF NOT EXISTS idx_journal_trade ON journal (trade_id);
+
+CREATE TABLE IF NOT EXISTS journal_tags (
+    journal_id INTEGER NOT NULL REFERENCES journal (id),
+    key        TEXT NOT NULL,
+    value      TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_journal_tags ON journal_tags (key, value);
+
+CREATE TABLE IF NOT EXISTS rejections (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at          INTEGER NOT NULL,
+    token               TEXT NOT NULL,
+    buy_dex             TEXT NOT NULL,
+    sell_dex            TEXT NOT NULL,
+    reason              TEXT NOT NULL,
+    gross_spread_bps    REAL NOT NULL,
+    dex_fee_bps         REAL NOT NULL,
+    priority_fee_bps    REAL NOT NULL,
+    tip_bps             REAL NOT NULL,
+    conversion_bps      REAL NOT NULL,
+    net_bps             REAL NOT NULL,
+    miss_bps            REAL NOT NULL,
+    liquidity_shortfall INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_rejections_token ON rejections (token, created_at);
+
+CREATE TABLE IF NOT EXISTS spread_hourly (
+    hour_start INTEGER NOT NULL,
+    token      TEXT NOT NULL,
+    count      INTEGER NOT NULL,
+    p50_bps    REAL NOT NULL,
+    p95_bps    REAL NOT NULL,
+    max_bps    REAL NOT NULL,
+    PRIMARY KEY (hour_start, token)
+);
+
+CREATE TABLE IF NOT EXISTS unknown_programs (
+    program_id    TEXT PRIMARY KEY,
+    first_seen    INTEGER NOT NULL,
+    last_seen     INTEGER NOT NULL,
+    seen_count    INTEGER NOT NULL,
+    skipped_count INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS admin_audit (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at  INTEGER NOT NULL,
+    actor       TEXT NOT NULL,
+    method      TEXT NOT NULL,
+    path        TEXT NOT NULL,
+    action      TEXT NOT NULL,
+    old_value   TEXT,
+    new_value   TEXT,
+    config_hash TEXT NOT NULL
+);
+CREATE TRIGGER IF NOT EXISTS admin_audit_no_update BEFORE UPDATE ON admin_audit
+BEGIN SELECT RAISE(ABORT, 'admin_audit is append-only'); END;
+CREATE TRIGGER IF NOT EXISTS admin_audit_no_delete BEFORE DELETE ON admin_audit
+BEGIN SELECT RAISE(ABORT, 'admin_audit is append-only'); END;
+
+CREATE TABLE IF NOT EXISTS opportunities (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    content_hash    TEXT NOT NULL UNIQUE,
+    created_at      INTEGER NOT NULL,
+    token           TEXT NOT NULL,
+    token_is_prefix INTEGER NOT NULL,
+    buy_dex         TEXT NOT NULL,
+    buy_price       REAL NOT NULL,
+    buy_pool        TEXT NOT NULL,
+    sell_dex        TEXT NOT NULL,
+    sell_price      REAL NOT NULL,
+    sell_pool       TEXT NOT NULL,
+    profit_pct      REAL,
+    source          TEXT NOT NULL,
+    opportunity_id  TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_opportunities_token ON opportunities (token, created_at);
+
+CREATE TABLE IF NOT EXISTS triangles (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at          INTEGER NOT NULL,
+    opportunity_id      TEXT NOT NULL,
+    route               TEXT NOT NULL,
+    hops                TEXT NOT NULL,
+    input_lamports      INTEGER NOT NULL,
+    output_lamports     INTEGER NOT NULL,
+    net_profit_lamports INTEGER NOT NULL,
+    profit_pct          REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_triangles_created ON triangles (created_at);
+
+CREATE TABLE IF NOT EXISTS math_checks (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    checked_at INTEGER NOT NULL,
+    venue      TEXT NOT NULL,
+    mint       TEXT NOT NULL,
+    amount_in  INTEGER NOT NULL,
+    expected   INTEGER NOT NULL,
+    simulated  INTEGER NOT NULL,
+    rel_error  REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_math_checks_venue ON math_checks (venue, checked_at);
+
+CREATE TABLE IF NOT EXISTS sol_usd_daily (
+    date  TEXT PRIMARY KEY,
+    price REAL NOT NULL
+);
+";
+
+/// A buy or sell the bot sent, as recorded in the store
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeRecord {
+    pub trade_id: String,
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    pub mint: String,
+    /// "buy" or "sell"
+    pub side: String,
+    pub sol_amount: Option<u64>,
+    pub token_amount: Option<u64>,
+    pub price: Option<f64>,
+    pub signature: Option<String>,
+    /// How the compute unit price was bid, and what it came to
+    pub priority_fee_strategy: Option<String>,
+    pub priority_fee_lamports: Option<u64>,
+}
+
+/// SQLite-backed store for trades, journal entries and rejected opportunities
+///
+/// A single connection behind a mutex is plenty for the bot's write rate.
+pub struct RecordStore {
+    pub(crate) conn: Mutex<Connection>,
+}
+
+impl RecordStore {
+    /// Open (or create) the store at `path` and apply the schema
+    pub fn open(path: &str) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        Self::init(Connection::open(path)?)
+    }
+
+    /// In-memory store, used by tests and dry tooling
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// Open the store at `RECORD_DB_PATH`, falling back to the default path
+    pub fn open_from_env() -> Result<Self> {
+        let path = std::env::var("RECORD_DB_PATH").unwrap_or_else(|_| DEFAULT_RECORD_DB_PATH.to_string());
+        Self::open(&path)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        // Columns added after a table first shipped; CREATE TABLE IF NOT EXISTS
+        // leaves stores created before them without
+        ensure_column(&conn, "opportunities", "opportunity_id", "TEXT")?;
+        ensure_column(&conn, "trades", "priority_fee_strategy", "TEXT")?;
+        ensure_column(&conn, "trades", "priority_fee_lamports", "INTEGER")?;
+        conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_opportunities_id ON opportunities (opportunity_id);")?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    pub(crate) fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("Record store lock poisoned"))
+    }
+
+    /// Insert or replace a trade
+    pub fn record_trade(&self, trade: &TradeRecord) -> Result<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO trades
+                (trade_id, created_at, mint, side, sol_amount, token_amount, price, signature,
+                 priority_fee_strategy, priority_fee_lamports)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                trade.trade_id,
+                trade.created_at,
+                trade.mint,
+                trade.side,
+                trade.sol_amount.map(|v| v as i64),
+                trade.token_amount.map(|v| v as i64),
+                trade.price,
+                trade.signature,
+                trade.priority_fee_strategy,
+                trade.priority_fee_lamports.map(|v| v as i64),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_trade(&self, trade_id: &str) -> Result<Option<TradeRecord>> {
+        let conn = self.lock()?;
+        let trade = conn
+            .query_row(
+                "SELECT trade_id, created_at, mint, side, sol_amount, token_amount, price, signature,
+                 priority_fee_strategy, priority_fee_lamports
+                 FROM trades WHERE trade_id = ?1",
+                params![trade_id],
+                trade_from_row,
+            )
+            .optional()?;
+        Ok(trade)
+    }
+
+    /// Trades for a mint, or every trade when `mint` is None, oldest first
+    pub fn trades(&self, mint: Option<&str>) -> Result<Vec<TradeRecord>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT trade_id, created_at, mint, side, sol_amount, token_amount, price, signature,
+             priority_fee_strategy, priority_fee_lamports
+             FROM trades WHERE (?1 IS NULL OR mint = ?1) ORDER BY created_at, trade_id",
+        )?;
+        let trades = stmt
+            .query_map(params![mint], trade_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(trades)
+    }
+}
+
+fn ensure_column(conn: &Connection, table: &str, column: &str, declaration: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, declaration))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn trade_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TradeRecord> {
+    Ok(TradeRecord {
+        trade_id: row.get(0)?,
+        created_at: row.get(1)?,
+        mint: row.get(2)?,
+        side: row.get(3)?,
+        sol_amount: row.get::<_, Option<i64>>(4)?.map(|v| v as u64),
+        token_amount: row.get::<_, Option<i64>>(5)?.map(|v| v as u64),
+        price: row.get(6)?,
+        signature: row.get(7)?,
+        priority_fee_strategy: row.get(8)?,
+        priority_fee_lamports: row.get::<_, Option<i64>>(9)?.map(|v| v as u64),
+    })
+}
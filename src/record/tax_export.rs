@@ -0,0 +1,511 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use rusqlite::params;
+use serde::Deserialize;
+
+use super::store::{RecordStore, TradeRecord};
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// How sells are matched against earlier buys of the same mint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LotMethod {
+    Fifo,
+    AverageCost,
+}
+
+impl FromStr for LotMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fifo" => Ok(Self::Fifo),
+            "average" | "avg" | "average_cost" => Ok(Self::AverageCost),
+            other => Err(anyhow!("Unknown lot method '{}', expected fifo or average", other)),
+        }
+    }
+}
+
+/// When the disposed quantity was acquired
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Acquired {
+    At(i64),
+    /// Average cost pools several buys together
+    Various,
+    /// Sold more than the recorded buys cover
+    Unknown,
+}
+
+/// One taxable disposal, a sell split per matched lot
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposal {
+    pub acquired: Acquired,
+    pub disposed_at: i64,
+    pub asset: String,
+    pub quantity: u64,
+    pub proceeds_lamports: u64,
+    pub proceeds_usd: Option<f64>,
+    pub cost_basis_lamports: u64,
+    pub cost_basis_usd: Option<f64>,
+}
+
+impl Disposal {
+    pub fn gain_lamports(&self) -> i64 {
+        self.proceeds_lamports as i64 - self.cost_basis_lamports as i64
+    }
+
+    pub fn gain_usd(&self) -> Option<f64> {
+        Some(self.proceeds_usd? - self.cost_basis_usd?)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Lot {
+    acquired_at: i64,
+    quantity: u64,
+    cost_lamports: u64,
+    cost_usd: Option<f64>,
+}
+
+/// Result of matching the trade history
+#[derive(Debug, Default)]
+pub struct MatchOutput {
+    pub disposals: Vec<Disposal>,
+    /// Trades missing a SOL or token amount, they can't be matched
+    pub skipped: Vec<String>,
+}
+
+/// `part` of `total` scaled to `value`, rounded down
+fn pro_rata(value: u64, part: u64, total: u64) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+    (value as u128 * part as u128 / total as u128) as u64
+}
+
+fn pro_rata_usd(value: Option<f64>, part: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        return value.map(|_| 0.0);
+    }
+    value.map(|v| v * part as f64 / total as f64)
+}
+
+fn date_of(millis: i64) -> NaiveDate {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .map(|t| t.date_naive())
+        .unwrap_or_default()
+}
+
+fn usd_value(lamports: u64, at: i64, prices: &BTreeMap<NaiveDate, f64>) -> Option<f64> {
+    prices.get(&date_of(at)).map(|price| lamports as f64 / LAMPORTS_PER_SOL * price)
+}
+
+/// Match sells against buys per mint, in (created_at, trade_id) order.
+///
+/// A sell that consumes several lots yields one disposal per lot; proceeds are
+/// split pro rata and the last piece takes the rounding remainder so the
+/// pieces always sum to the recorded proceeds.
+pub fn match_disposals(
+    trades: &[TradeRecord],
+    method: LotMethod,
+    prices: &BTreeMap<NaiveDate, f64>,
+) -> MatchOutput {
+    let mut ordered: Vec<&TradeRecord> = trades.iter().collect();
+    ordered.sort_by(|a, b| (a.created_at, &a.trade_id).cmp(&(b.created_at, &b.trade_id)));
+
+    let mut output = MatchOutput::default();
+    let mut lots: HashMap<&str, VecDeque<Lot>> = HashMap::new();
+
+    for trade in ordered {
+        let (Some(sol_amount), Some(token_amount)) = (trade.sol_amount, trade.token_amount) else {
+            output.skipped.push(trade.trade_id.clone());
+            continue;
+        };
+        if token_amount == 0 {
+            output.skipped.push(trade.trade_id.clone());
+            continue;
+        }
+
+        let held = lots.entry(trade.mint.as_str()).or_default();
+        match trade.side.as_str() {
+            "buy" => {
+                held.push_back(Lot {
+                    acquired_at: trade.created_at,
+                    quantity: token_amount,
+                    cost_lamports: sol_amount,
+                    cost_usd: usd_value(sol_amount, trade.created_at, prices),
+                });
+                if method == LotMethod::AverageCost {
+                    merge_into_average(held);
+                }
+            }
+            "sell" => {
+                let proceeds_usd = usd_value(sol_amount, trade.created_at, prices);
+                let mut pieces = match method {
+                    LotMethod::Fifo => consume_fifo(held, token_amount),
+                    LotMethod::AverageCost => consume_average(held, token_amount),
+                };
+
+                let mut proceeds_left = sol_amount;
+                let last = pieces.len().saturating_sub(1);
+                for (i, piece) in pieces.iter_mut().enumerate() {
+                    piece.disposed_at = trade.created_at;
+                    piece.asset = trade.mint.clone();
+                    piece.proceeds_lamports = if i == last {
+                        proceeds_left
+                    } else {
+                        pro_rata(sol_amount, piece.quantity, token_amount)
+                    };
+                    proceeds_left -= piece.proceeds_lamports;
+                    piece.proceeds_usd = pro_rata_usd(proceeds_usd, piece.quantity, token_amount);
+                }
+                output.disposals.extend(pieces);
+            }
+            _ => output.skipped.push(trade.trade_id.clone()),
+        }
+    }
+
+    output
+}
+
+/// Collapse every open lot of a mint into one pooled lot
+fn merge_into_average(held: &mut VecDeque<Lot>) {
+    if held.len() < 2 {
+        return;
+    }
+    let merged = held.drain(..).fold(None::<Lot>, |acc, lot| match acc {
+        None => Some(lot),
+        Some(mut pool) => {
+            pool.acquired_at = pool.acquired_at.min(lot.acquired_at);
+            pool.quantity += lot.quantity;
+            pool.cost_lamports += lot.cost_lamports;
+            pool.cost_usd = pool.cost_usd.zip(lot.cost_usd).map(|(a, b)| a + b);
+            Some(pool)
+        }
+    });
+    held.extend(merged);
+}
+
+fn empty_disposal(acquired: Acquired, quantity: u64) -> Disposal {
+    Disposal {
+        acquired,
+        disposed_at: 0,
+        asset: String::new(),
+        quantity,
+        proceeds_lamports: 0,
+        proceeds_usd: None,
+        cost_basis_lamports: 0,
+        cost_basis_usd: None,
+    }
+}
+
+/// Take `quantity` from one lot, shrinking it in place
+fn take_from_lot(lot: &mut Lot, quantity: u64) -> (u64, Option<f64>) {
+    if quantity >= lot.quantity {
+        let cost = (lot.cost_lamports, lot.cost_usd);
+        lot.quantity = 0;
+        lot.cost_lamports = 0;
+        lot.cost_usd = lot.cost_usd.map(|_| 0.0);
+        return cost;
+    }
+    let cost_lamports = pro_rata(lot.cost_lamports, quantity, lot.quantity);
+    let cost_usd = pro_rata_usd(lot.cost_usd, quantity, lot.quantity);
+    lot.quantity -= quantity;
+    lot.cost_lamports -= cost_lamports;
+    lot.cost_usd = lot.cost_usd.zip(cost_usd).map(|(total, taken)| total - taken);
+    (cost_lamports, cost_usd)
+}
+
+fn consume_fifo(held: &mut VecDeque<Lot>, mut quantity: u64) -> Vec<Disposal> {
+    let mut pieces = Vec::new();
+    while quantity > 0 {
+        let Some(lot) = held.front_mut() else { break };
+        let taken = quantity.min(lot.quantity);
+        let acquired_at = lot.acquired_at;
+        let (cost_lamports, cost_usd) = take_from_lot(lot, taken);
+        if lot.quantity == 0 {
+            held.pop_front();
+        }
+
+        let mut piece = empty_disposal(Acquired::At(acquired_at), taken);
+        piece.cost_basis_lamports = cost_lamports;
+        piece.cost_basis_usd = cost_usd;
+        pieces.push(piece);
+        quantity -= taken;
+    }
+    if quantity > 0 {
+        pieces.push(empty_disposal(Acquired::Unknown, quantity));
+    }
+    pieces
+}
+
+fn consume_average(held: &mut VecDeque<Lot>, quantity: u64) -> Vec<Disposal> {
+    let mut pieces = Vec::new();
+    let mut remaining = quantity;
+    if let Some(pool) = held.front_mut() {
+        let taken = remaining.min(pool.quantity);
+        let (cost_lamports, cost_usd) = take_from_lot(pool, taken);
+        if pool.quantity == 0 {
+            held.pop_front();
+        }
+
+        let mut piece = empty_disposal(Acquired::Various, taken);
+        piece.cost_basis_lamports = cost_lamports;
+        piece.cost_basis_usd = cost_usd;
+        pieces.push(piece);
+        remaining -= taken;
+    }
+    if remaining > 0 {
+        pieces.push(empty_disposal(Acquired::Unknown, remaining));
+    }
+    pieces
+}
+
+fn format_sol(lamports: i64) -> String {
+    let sign = if lamports < 0 { "-" } else { "" };
+    let abs = lamports.unsigned_abs();
+    format!("{}{}.{:09}", sign, abs / 1_000_000_000, abs % 1_000_000_000)
+}
+
+fn format_usd(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.2}", v)).unwrap_or_default()
+}
+
+fn format_date(millis: i64) -> String {
+    date_of(millis).format("%Y-%m-%d").to_string()
+}
+
+/// Render disposals as CSV, one row per matched lot
+pub fn disposals_to_csv(disposals: &[Disposal]) -> String {
+    let mut csv = String::from(
+        "acquired,disposed,asset,quantity,proceeds_sol,proceeds_usd,cost_basis_sol,cost_basis_usd,gain_sol,gain_usd\n",
+    );
+    for d in disposals {
+        let acquired = match d.acquired {
+            Acquired::At(at) => format_date(at),
+            Acquired::Various => "various".to_string(),
+            Acquired::Unknown => "unknown".to_string(),
+        };
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{}",
+            acquired,
+            format_date(d.disposed_at),
+            d.asset,
+            d.quantity,
+            format_sol(d.proceeds_lamports as i64),
+            format_usd(d.proceeds_usd),
+            format_sol(d.cost_basis_lamports as i64),
+            format_usd(d.cost_basis_usd),
+            format_sol(d.gain_lamports()),
+            format_usd(d.gain_usd()),
+        );
+    }
+    csv
+}
+
+#[derive(Deserialize)]
+struct MarketChartRange {
+    prices: Vec<(i64, f64)>,
+}
+
+/// Daily SOL/USD closes between two dates from CoinGecko, first sample of each day
+async fn fetch_sol_usd_daily(from: NaiveDate, to: NaiveDate) -> Result<BTreeMap<NaiveDate, f64>> {
+    let from_ts = from.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp();
+    let to_ts = to.and_hms_opt(23, 59, 59).unwrap_or_default().and_utc().timestamp();
+    let url = format!(
+        "https://api.coingecko.com/api/v3/coins/solana/market_chart/range?vs_currency=usd&from={}&to={}",
+        from_ts, to_ts
+    );
+    let chart = reqwest::get(&url).await?.json::<MarketChartRange>().await?;
+
+    let mut daily = BTreeMap::new();
+    for (millis, price) in chart.prices {
+        daily.entry(date_of(millis)).or_insert(price);
+    }
+    Ok(daily)
+}
+
+impl RecordStore {
+    /// Cached daily SOL/USD prices in `[from, to]`
+    pub fn sol_usd_prices(&self, from: NaiveDate, to: NaiveDate) -> Result<BTreeMap<NaiveDate, f64>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare("SELECT date, price FROM sol_usd_daily WHERE date BETWEEN ?1 AND ?2")?;
+        let rows = stmt
+            .query_map(params![from.to_string(), to.to_string()], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(date, price)| NaiveDate::from_str(&date).ok().map(|d| (d, price)))
+            .collect())
+    }
+
+    pub fn cache_sol_usd_prices(&self, prices: &BTreeMap<NaiveDate, f64>) -> Result<()> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        for (date, price) in prices {
+            tx.execute(
+                "INSERT OR REPLACE INTO sol_usd_daily (date, price) VALUES (?1, ?2)",
+                params![date.to_string(), price],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Daily prices covering `[from, to]`, fetched once and served from the store afterwards
+    pub async fn load_sol_usd_prices(&self, from: NaiveDate, to: NaiveDate) -> Result<BTreeMap<NaiveDate, f64>> {
+        let cached = self.sol_usd_prices(from, to)?;
+        let expected = (to - from).num_days() + 1;
+        if cached.len() as i64 >= expected {
+            return Ok(cached);
+        }
+
+        // Today's close isn't known yet, so never cache it
+        let today = Utc::now().date_naive();
+        let fetched = fetch_sol_usd_daily(from, to).await?;
+        let settled = fetched
+            .iter()
+            .filter(|(date, _)| **date < today)
+            .map(|(date, price)| (*date, *price))
+            .collect::<BTreeMap<_, _>>();
+        self.cache_sol_usd_prices(&settled)?;
+
+        let mut prices = cached;
+        prices.extend(fetched);
+        Ok(prices)
+    }
+}
+
+/// Match every recorded trade and write the disposals of `year` (all years when None) to `out`
+pub async fn export_tax_csv(
+    store: &RecordStore,
+    method: LotMethod,
+    year: Option<i32>,
+    out: &str,
+) -> Result<MatchOutput> {
+    let trades = store.trades(None)?;
+    let prices = match (trades.first(), trades.last()) {
+        (Some(first), Some(last)) => store
+            .load_sol_usd_prices(date_of(first.created_at), date_of(last.created_at))
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("SOL/USD prices unavailable, USD columns left blank: {}", e);
+                BTreeMap::new()
+            }),
+        _ => BTreeMap::new(),
+    };
+
+    let mut output = match_disposals(&trades, method, &prices);
+    if let Some(year) = year {
+        output.disposals.retain(|d| date_of(d.disposed_at).year() == year);
+    }
+    std::fs::write(out, disposals_to_csv(&output.disposals))?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86_400_000;
+    // 2024-01-01T00:00:00Z
+    const START: i64 = 1_704_067_200_000;
+
+    fn trade(trade_id: &str, day: i64, mint: &str, side: &str, tokens: u64, lamports: u64) -> TradeRecord {
+        TradeRecord {
+            trade_id: trade_id.to_string(),
+            created_at: START + day * DAY,
+            mint: mint.to_string(),
+            side: side.to_string(),
+            sol_amount: Some(lamports),
+            token_amount: Some(tokens),
+            price: None,
+            signature: None,
+            priority_fee_strategy: None,
+            priority_fee_lamports: None,
+        }
+    }
+
+    fn history() -> Vec<TradeRecord> {
+        vec![
+            trade("b1", 0, "A", "buy", 100, 1_000),
+            trade("b2", 1, "A", "buy", 200, 4_000),
+            trade("x1", 1, "B", "buy", 10, 100),
+            trade("s1", 2, "A", "sell", 150, 3_000),
+            trade("b3", 3, "A", "buy", 100, 500),
+            trade("x2", 3, "B", "sell", 10, 50),
+            trade("s2", 4, "A", "sell", 200, 2_000),
+            trade("s3", 5, "A", "sell", 80, 800),
+        ]
+    }
+
+    /// (acquired, disposed day, asset, quantity, proceeds, cost basis)
+    fn summary(disposals: &[Disposal]) -> Vec<(Acquired, i64, &str, u64, u64, u64)> {
+        disposals
+            .iter()
+            .map(|d| {
+                let day = (d.disposed_at - START) / DAY;
+                (d.acquired, day, d.asset.as_str(), d.quantity, d.proceeds_lamports, d.cost_basis_lamports)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fifo_splits_partial_lots_across_interleaved_sells() {
+        let output = match_disposals(&history(), LotMethod::Fifo, &BTreeMap::new());
+        let at = |day: i64| Acquired::At(START + day * DAY);
+
+        assert_eq!(
+            summary(&output.disposals),
+            vec![
+                // s1 takes all of b1 and a quarter of b2
+                (at(0), 2, "A", 100, 2_000, 1_000),
+                (at(1), 2, "A", 50, 1_000, 1_000),
+                (at(1), 3, "B", 10, 50, 100),
+                // s2 takes the rest of b2 and half of b3
+                (at(1), 4, "A", 150, 1_500, 3_000),
+                (at(3), 4, "A", 50, 500, 250),
+                // s3 runs out of recorded buys
+                (at(3), 5, "A", 50, 500, 250),
+                (Acquired::Unknown, 5, "A", 30, 300, 0),
+            ]
+        );
+        assert!(output.skipped.is_empty());
+        assert_eq!(output.disposals.iter().map(|d| d.gain_lamports()).sum::<i64>(), 5_850 - 5_600);
+    }
+
+    #[test]
+    fn average_cost_pools_the_open_lots() {
+        let trades = [trade("b1", 0, "A", "buy", 100, 1_000), trade("b2", 1, "A", "buy", 300, 5_000), trade("s1", 2, "A", "sell", 200, 4_000)];
+        let output = match_disposals(&trades, LotMethod::AverageCost, &BTreeMap::new());
+        assert_eq!(summary(&output.disposals), vec![(Acquired::Various, 2, "A", 200, 4_000, 3_000)]);
+    }
+
+    #[test]
+    fn export_is_the_same_whatever_order_trades_come_in() {
+        let prices = BTreeMap::from([(date_of(START), 100.0), (date_of(START + 2 * DAY), 110.0)]);
+        let forward = disposals_to_csv(&match_disposals(&history(), LotMethod::Fifo, &prices).disposals);
+        let mut reversed = history();
+        reversed.reverse();
+        assert_eq!(disposals_to_csv(&match_disposals(&reversed, LotMethod::Fifo, &prices).disposals), forward);
+
+        let first_row = forward.lines().nth(1).unwrap();
+        assert_eq!(first_row, "2024-01-01,2024-01-03,A,100,0.000002000,0.00,0.000001000,0.00,0.000001000,0.00");
+    }
+
+    #[test]
+    fn trades_without_amounts_are_skipped() {
+        let mut missing = trade("m1", 0, "A", "buy", 100, 1_000);
+        missing.sol_amount = None;
+        let output = match_disposals(&[missing, trade("z1", 0, "A", "buy", 0, 10)], LotMethod::Fifo, &BTreeMap::new());
+        assert_eq!(output.skipped, ["m1", "z1"]);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use rusqlite::params;
+use serde::Serialize;
+
+use super::store::{trade_from_row, RecordStore, TradeRecord};
+use crate::core::ids::{OpportunityId, TradeId};
+
+/// One thing that happened to an opportunity, from whichever store kept it
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEvent {
+    /// Unix time in milliseconds
+    pub at: i64,
+    /// `opportunity`, `record_file`, `trade` or `journal`
+    pub source: &'static str,
+    pub detail: String,
+}
+
+impl RecordStore {
+    /// Trades recorded under any attempt of `opportunity_id`
+    pub fn trades_for_opportunity(&self, opportunity_id: &OpportunityId) -> Result<Vec<TradeRecord>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT trade_id, created_at, mint, side, sol_amount, token_amount, price, signature,
+             priority_fee_strategy, priority_fee_lamports
+             FROM trades WHERE trade_id LIKE ?1 ORDER BY created_at, trade_id",
+        )?;
+        let trades = stmt
+            .query_map(params![attempts_pattern(opportunity_id)], trade_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(trades)
+    }
+
+    /// Journal ids of entries on any attempt of `opportunity_id`
+    fn journal_ids_for_opportunity(&self, opportunity_id: &OpportunityId) -> Result<Vec<i64>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare("SELECT id FROM journal WHERE trade_id LIKE ?1 ORDER BY created_at, id")?;
+        let ids = stmt
+            .query_map(params![attempts_pattern(opportunity_id)], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ids)
+    }
+}
+
+/// LIKE pattern matching every trade id of an opportunity; the `_` of the
+/// prefix is a wildcard that also matches itself, the rest is hex
+fn attempts_pattern(opportunity_id: &OpportunityId) -> String {
+    format!("{}{}%", opportunity_id, TradeId::SEPARATOR)
+}
+
+/// Everything the record store and the per-opportunity record files hold
+/// about one opportunity, oldest first
+pub fn assemble_trace(store: &RecordStore, opportunity_id: &OpportunityId, record_dir: &Path) -> Result<Vec<TraceEvent>> {
+    let mut events = Vec::new();
+
+    for opportunity in store.opportunities_by_id(opportunity_id.as_str())? {
+        events.push(TraceEvent {
+            at: opportunity.created_at,
+            source: "opportunity",
+            detail: format!(
+                "{} buy {} at {:.9} ({}), sell {} at {:.9} ({}), net {}",
+                opportunity.token,
+                opportunity.buy_dex,
+                opportunity.buy_price,
+                opportunity.buy_pool,
+                opportunity.sell_dex,
+                opportunity.sell_price,
+                opportunity.sell_pool,
+                opportunity.profit_pct.map_or("-".to_string(), |pct| format!("{:.3}%", pct)),
+            ),
+        });
+    }
+
+    if record_dir.is_dir() {
+        for entry in fs::read_dir(record_dir)?.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().map_or(true, |ext| ext != "json") {
+                continue;
+            }
+            let Ok(record) = fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|raw| Ok(serde_json::from_str::<serde_json::Value>(&raw)?))
+            else {
+                continue;
+            };
+            if record.get("opportunity_id").and_then(|id| id.as_str()) != Some(opportunity_id.as_str()) {
+                continue;
+            }
+            let at = record
+                .get("timestamp")
+                .and_then(|t| t.as_str())
+                .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y%m%d%H%M%S").ok())
+                .map_or(0, |t| Utc.from_utc_datetime(&t).timestamp_millis());
+            events.push(TraceEvent {
+                at,
+                source: "record_file",
+                detail: path.display().to_string(),
+            });
+        }
+    }
+
+    for trade in store.trades_for_opportunity(opportunity_id)? {
+        events.push(TraceEvent {
+            at: trade.created_at,
+            source: "trade",
+            detail: format!(
+                "{} {} {} sol {} tokens {} signature {}",
+                trade.trade_id,
+                trade.side,
+                trade.mint,
+                trade.sol_amount.map_or("-".to_string(), |v| v.to_string()),
+                trade.token_amount.map_or("-".to_string(), |v| v.to_string()),
+                trade.signature.as_deref().unwrap_or("-"),
+            ),
+        });
+    }
+
+    let on_trades = store.journal_ids_for_opportunity(opportunity_id)?;
+    let mut journal = store.journal_by_tag("opportunity_id", Some(opportunity_id.as_str()))?;
+    journal.extend(store.journal()?.into_iter().filter(|entry| on_trades.contains(&entry.id)));
+    journal.sort_by_key(|entry| entry.id);
+    journal.dedup_by_key(|entry| entry.id);
+    for entry in journal {
+        events.push(TraceEvent {
+            at: entry.created_at,
+            source: "journal",
+            detail: format!("[{}] {}{}", entry.source, entry.note, entry.trade_id.map_or(String::new(), |id| format!(" ({})", id))),
+        });
+    }
+
+    events.sort_by_key(|event| event.at);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::record::journal::{JournalScope, JournalSource};
+    use crate::record::opportunities::{ArbitrageOpportunity, SOURCE_LIVE};
+
+    /// 2024-03-01 12:00:00 UTC
+    const DETECTED_AT: i64 = 1_709_294_400_000;
+
+    fn opportunity(opportunity_id: &OpportunityId, buy_pool: &str) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            created_at: DETECTED_AT,
+            token: "mint".to_string(),
+            token_is_prefix: false,
+            buy_dex: "pumpswap".to_string(),
+            buy_price: 0.001,
+            buy_pool: buy_pool.to_string(),
+            sell_dex: "raydium_amm".to_string(),
+            sell_price: 0.00105,
+            sell_pool: "sell".to_string(),
+            profit_pct: Some(4.5),
+            source: SOURCE_LIVE.to_string(),
+            opportunity_id: Some(opportunity_id.to_string()),
+        }
+    }
+
+    fn trade(trade_id: &TradeId, created_at: i64) -> TradeRecord {
+        TradeRecord {
+            trade_id: trade_id.to_string(),
+            created_at,
+            mint: "mint".to_string(),
+            side: "buy".to_string(),
+            sol_amount: Some(1_000_000),
+            token_amount: Some(950),
+            price: Some(0.001),
+            signature: Some("sig".to_string()),
+            priority_fee_strategy: None,
+            priority_fee_lamports: None,
+        }
+    }
+
+    #[test]
+    fn trace_gathers_one_opportunity_from_every_store_in_order() {
+        let store = RecordStore::open_in_memory().unwrap();
+        let id = OpportunityId::derive("mint", "buy", "sell", 100, 0);
+        let other = OpportunityId::derive("mint", "buy", "sell", 100, 1);
+
+        store.record_opportunity(&opportunity(&id, "buy")).unwrap();
+        store.record_opportunity(&opportunity(&other, "buy-other")).unwrap();
+        store.record_trade(&trade(&id.trade(1), DETECTED_AT + 2_000)).unwrap();
+        store.record_trade(&trade(&other.trade(1), DETECTED_AT + 2_000)).unwrap();
+        let tags = BTreeMap::from([("opportunity_id".to_string(), id.to_string())]);
+        store.add_journal_entry(&JournalScope::Mint { mint: "mint".to_string() }, "alert sent", &tags, JournalSource::System).unwrap();
+        let on_trade = JournalScope::Trade { trade_id: id.trade(1).to_string() };
+        store.add_journal_entry(&on_trade, "filled late", &BTreeMap::new(), JournalSource::User).unwrap();
+        let unrelated = JournalScope::Trade { trade_id: other.trade(1).to_string() };
+        store.add_journal_entry(&unrelated, "not this one", &BTreeMap::new(), JournalSource::User).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("trace_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let record = |id: &OpportunityId| serde_json::json!({ "timestamp": "20240301120001", "opportunity_id": id.to_string() });
+        fs::write(dir.join("arb_mint_1.json"), record(&id).to_string()).unwrap();
+        fs::write(dir.join("arb_mint_2.json"), record(&other).to_string()).unwrap();
+        fs::write(dir.join("arb_mint_3.json"), "{ truncated").unwrap();
+
+        let events = assemble_trace(&store, &id, &dir).unwrap();
+        let sources: Vec<&str> = events.iter().map(|event| event.source).collect();
+        assert_eq!(sources, ["opportunity", "record_file", "trade", "journal", "journal"]);
+        assert_eq!(events[1].at, DETECTED_AT + 1_000);
+        assert!(events[1].detail.ends_with("arb_mint_1.json"));
+        assert!(events[2].detail.starts_with(id.trade(1).as_str()));
+        assert!(events[3].detail.contains("alert sent"));
+        assert!(events[4].detail.contains("filled late"));
+        assert!(events.iter().all(|event| !event.detail.contains(other.as_str()) && !event.detail.contains("buy-other")));
+
+        // A trade id finds the same story
+        let by_trade = OpportunityId::parse(id.trade(1).as_str()).unwrap();
+        assert_eq!(assemble_trace(&store, &by_trade, &dir).unwrap().len(), events.len());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -49,26 +49,7 @@ pub fn log_raw_transaction(
     };
     let slot = transaction.slot;
     
-    // Determine which protocol this transaction is for
-    let protocol_prefix = if is_pumpfun_transaction(log_messages) {
-        "pumpfun"
-    } else if is_pumpswap_transaction(log_messages) {
-        "pumpswap"
-    } else if is_raydium_transaction(log_messages) {
-        "raydium"
-    } else if is_raydium_cpmm_transaction(log_messages) {
-        "raydium_cpmm"
-    } else if is_whirlpool_transaction(log_messages) {
-        "whirlpool"
-    } else if is_stable_swap_transaction(log_messages) {
-        "stable_swap"
-    } else if is_meteora_pools_transaction(log_messages) {
-        "meteora_pools"
-    } else if is_meteora_dlmm_transaction(log_messages) {
-        "meteora_dlmm"
-    } else {
-        "unknown"
-    };
+    let protocol_prefix = classify_protocol(log_messages);
     
     // Create the filename with protocol, timestamp and signature
     let filename = format!("./record/{}/{}_tx_{}.log", 
@@ -128,6 +109,30 @@ pub fn log_raw_transaction(
     Ok(())
 }
 
+/// Record directory name of the protocol the logs mention first, "unknown" if none
+#[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all))]
+pub fn classify_protocol(log_messages: &[String]) -> &'static str {
+    if is_pumpfun_transaction(log_messages) {
+        "pumpfun"
+    } else if is_pumpswap_transaction(log_messages) {
+        "pumpswap"
+    } else if is_raydium_transaction(log_messages) {
+        "raydium"
+    } else if is_raydium_cpmm_transaction(log_messages) {
+        "raydium_cpmm"
+    } else if is_whirlpool_transaction(log_messages) {
+        "whirlpool"
+    } else if is_stable_swap_transaction(log_messages) {
+        "stable_swap"
+    } else if is_meteora_pools_transaction(log_messages) {
+        "meteora_pools"
+    } else if is_meteora_dlmm_transaction(log_messages) {
+        "meteora_dlmm"
+    } else {
+        "unknown"
+    }
+}
+
 /// Check if a transaction is from PumpFun
 pub fn is_pumpfun_transaction(log_messages: &[String]) -> bool {
     for log in log_messages {
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+use super::store::RecordStore;
+
+/// A program copy targets called that no DEX adapter covers
+#[derive(Debug, Clone, Serialize)]
+pub struct UnknownProgram {
+    pub program_id: String,
+    /// Unix time in milliseconds
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// Copy-target transactions that invoked it
+    pub seen_count: u64,
+    /// Copies skipped because validation against it failed
+    pub skipped_count: u64,
+}
+
+impl RecordStore {
+    /// Count one more sighting of `program_id`
+    pub fn note_unknown_program(&self, program_id: &str, now_ms: i64) -> Result<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO unknown_programs (program_id, first_seen, last_seen, seen_count, skipped_count)
+             VALUES (?1, ?2, ?2, 1, 0)
+             ON CONFLICT (program_id) DO UPDATE SET
+                last_seen = excluded.last_seen,
+                seen_count = seen_count + 1",
+            params![program_id, now_ms],
+        )?;
+        Ok(())
+    }
+
+    /// Count a copy skipped over `program_id`, after its sighting was recorded
+    pub fn note_unknown_program_skip(&self, program_id: &str) -> Result<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "UPDATE unknown_programs SET skipped_count = skipped_count + 1 WHERE program_id = ?1",
+            params![program_id],
+        )?;
+        Ok(())
+    }
+
+    /// Every unknown program, most often seen first
+    pub fn unknown_programs(&self) -> Result<Vec<UnknownProgram>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT program_id, first_seen, last_seen, seen_count, skipped_count
+             FROM unknown_programs ORDER BY seen_count DESC, program_id",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(UnknownProgram {
+                    program_id: row.get(0)?,
+                    first_seen: row.get(1)?,
+                    last_seen: row.get(2)?,
+                    seen_count: row.get::<_, i64>(3)? as u64,
+                    skipped_count: row.get::<_, i64>(4)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sightings_and_skips_are_counted_most_seen_first() {
+        let store = RecordStore::open_in_memory().unwrap();
+        store.note_unknown_program("router-a", 1_000).unwrap();
+        store.note_unknown_program("router-b", 2_000).unwrap();
+        store.note_unknown_program("router-b", 3_000).unwrap();
+        store.note_unknown_program_skip("router-b").unwrap();
+        // A skip for a program never seen doesn't invent a row
+        store.note_unknown_program_skip("router-c").unwrap();
+
+        let programs = store.unknown_programs().unwrap();
+        assert_eq!(programs.len(), 2);
+        let b = &programs[0];
+        assert_eq!(b.program_id, "router-b");
+        assert_eq!((b.first_seen, b.last_seen, b.seen_count, b.skipped_count), (2_000, 3_000, 2, 1));
+        let a = &programs[1];
+        assert_eq!((a.program_id.as_str(), a.seen_count, a.skipped_count), ("router-a", 1, 0));
+    }
+}
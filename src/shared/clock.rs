@@ -0,0 +1,190 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::time::Instant;
+
+/// Source of time for anything that measures timeouts or ages
+///
+/// Production code uses `SystemClock`; tests and backtests use `MockClock` so they
+/// can move time forward without sleeping.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    /// Current instant
+    fn now(&self) -> Instant;
+    /// Current wall-clock time, for what is recorded rather than measured
+    fn wall_now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+    /// Wait for `duration` to pass on this clock
+    async fn sleep(&self, duration: Duration);
+}
+
+/// Wall clock backed by tokio
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// Manually driven clock, time only moves on `advance` or `sleep`
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    start_wall: DateTime<Utc>,
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            start_wall: Utc::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        let mut elapsed = self.elapsed.lock().unwrap();
+        *elapsed += duration;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn wall_now(&self) -> DateTime<Utc> {
+        self.start_wall + chrono::Duration::from_std(*self.elapsed.lock().unwrap()).unwrap_or_default()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        // Sleeping on a mock clock just fast-forwards it
+        self.advance(duration);
+        tokio::task::yield_now().await;
+    }
+}
+
+/// One moment, as the monotonic instant timeouts and rates are measured on
+/// and the wall-clock time it is recorded and persisted as
+///
+/// An `Instant` means nothing after a restart and can't be serialized; a
+/// wall-clock time can jump when the host's clock is corrected. Durations
+/// are always taken between the instants, and only the wall-clock part is
+/// serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamps {
+    pub instant: Instant,
+    pub wall: DateTime<Utc>,
+}
+
+impl Timestamps {
+    pub fn now(clock: &dyn Clock) -> Self {
+        Self { instant: clock.now(), wall: clock.wall_now() }
+    }
+
+    /// A recorded wall-clock time back on `clock`'s instants, as far in the
+    /// past as it is old; a time in the future maps to now
+    pub fn from_wall(wall: DateTime<Utc>, clock: &dyn Clock) -> Self {
+        let now = clock.now();
+        let age = (clock.wall_now() - wall).to_std().unwrap_or_default();
+        Self { instant: now.checked_sub(age).unwrap_or(now), wall }
+    }
+
+    /// Monotonic time from `self` to `later`
+    pub fn until(&self, later: &Timestamps) -> Duration {
+        later.instant.saturating_duration_since(self.instant)
+    }
+
+    /// Monotonic time since `self` on `clock`
+    pub fn elapsed(&self, clock: &dyn Clock) -> Duration {
+        clock.now().saturating_duration_since(self.instant)
+    }
+}
+
+/// As RFC 3339
+impl Serialize for Timestamps {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.wall.to_rfc3339())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamps {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        let wall = DateTime::parse_from_rfc3339(&raw).map_err(serde::de::Error::custom)?.with_timezone(&Utc);
+        Ok(Self::from_wall(wall, &SystemClock))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_taken_between_the_instants() {
+        let clock = MockClock::new();
+        let opened = Timestamps::now(&clock);
+        clock.advance(Duration::from_secs(30));
+        let checked = Timestamps::now(&clock);
+
+        // What `Instant::duration_since` gave before, and nothing below zero
+        assert_eq!(opened.until(&checked), checked.instant.duration_since(opened.instant));
+        assert_eq!(opened.until(&checked), Duration::from_secs(30));
+        assert_eq!(checked.until(&opened), Duration::ZERO);
+        assert_eq!(opened.elapsed(&clock), Duration::from_secs(30));
+        assert_eq!(checked.wall - opened.wall, chrono::Duration::seconds(30));
+    }
+
+    #[test]
+    fn a_corrected_wall_clock_does_not_move_a_timeout() {
+        let clock = MockClock::new();
+        let opened = Timestamps::now(&clock);
+        // The host's clock jumps an hour between opening and the check
+        let corrected = Timestamps { wall: opened.wall + chrono::Duration::hours(1), ..opened };
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(corrected.elapsed(&clock), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn recorded_times_map_back_by_their_age() {
+        let clock = MockClock::new();
+        clock.advance(Duration::from_secs(600));
+        let recorded = Timestamps::from_wall(clock.wall_now() - chrono::Duration::minutes(5), &clock);
+        assert_eq!(recorded.elapsed(&clock), Duration::from_secs(300));
+
+        let ahead = Timestamps::from_wall(clock.wall_now() + chrono::Duration::hours(1), &clock);
+        assert_eq!(ahead.instant, clock.now());
+    }
+
+    #[test]
+    fn only_the_wall_clock_is_serialized() {
+        let clock = MockClock::new();
+        let stamp = Timestamps::now(&clock);
+        let json = serde_json::to_string(&stamp).unwrap();
+        assert_eq!(json, format!("\"{}\"", stamp.wall.to_rfc3339()));
+
+        let restored: Timestamps = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.wall, stamp.wall);
+        assert!(serde_json::from_str::<Timestamps>("\"yesterday\"").is_err());
+    }
+}
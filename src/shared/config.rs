@@ -4,13 +4,37 @@ use colored::Colorize;
 use dotenv::dotenv;
 use reqwest::Error;
 use serde::Deserialize;
-use anchor_client::solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair, signer::Signer};
+use anchor_client::solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair, signer::Signer};
 use tokio::sync::{Mutex, OnceCell};
-use std::{env, sync::Arc};
+use std::{env, str::FromStr, sync::{atomic::AtomicBool, Arc}, time::Duration};
 
 use crate::{
-    common::{constants::INIT_MSG, logger::Logger},
-    engine::swap::{SwapDirection, SwapInType},
+    common::{
+        clock::{Clock, MockClock, SystemClock, Timestamps},
+        constants::INIT_MSG,
+        logger::Logger,
+        rng::SessionRng,
+    },
+    core::{
+        priority_fee::PriorityFeeStrategy,
+        tx_sender::{NoopSender, TxBackend, TxSender},
+    },
+    dex::dex_registry::DEXRegistry,
+    engine::{
+        blockhash_cache::BlockhashCache,
+        capital::CapitalAllocator,
+        dormancy::{DormancyConfig, DormancyTracker},
+        monitor_context::MonitorContext,
+        pool_discovery::PoolCacheManager,
+        quote_currency::PriceNormalizer,
+        refresh_scheduler::RefreshScheduler,
+        swap::{SwapDirection, SwapInType},
+    },
+    record::{kv::KvStore, store::RecordStore},
+    services::{
+        notifier::Notifier,
+        rpc_pool::{PacerConfig, RpcPool},
+    },
 };
 
 static GLOBAL_CONFIG: OnceCell<Mutex<Config>> = OnceCell::const_new();
@@ -18,7 +42,7 @@ static GLOBAL_CONFIG: OnceCell<Mutex<Config>> = OnceCell::const_new();
 pub struct Config {
     pub yellowstone_grpc_http: String,
     pub yellowstone_grpc_token: String,
-    pub app_state: AppState,
+    pub app_state: Arc<AppState>,
     pub swap_config: SwapConfig,
     pub time_exceed: u64,
     pub counter_limit: u32,
@@ -40,15 +64,13 @@ impl Config {
             let yellowstone_grpc_http = import_env_var("YELLOWSTONE_GRPC_HTTP");
             let yellowstone_grpc_token = import_env_var("YELLOWSTONE_GRPC_TOKEN");
             let slippage_input = import_env_var("SLIPPAGE").parse::<u64>().unwrap_or(0);
+            let slippage_bps = slippage_to_bps("SLIPPAGE", slippage_input, config_version()).unwrap_or_else(|e| {
+                logger.log(format!("[CONFIG ERROR] => {}", e).red().bold().to_string());
+                std::process::exit(1);
+            });
             let counter_limit = import_env_var("COUNTER").parse::<u32>().unwrap_or(0_u32);
             let max_dev_buy = import_env_var("MAX_DEV_BUY").parse::<u32>().unwrap_or(0_u32);
             let min_dev_buy = import_env_var("MIN_DEV_BUY").parse::<u32>().unwrap_or(0_u32);
-            let max_slippage: u64 = 100;
-            let slippage = if slippage_input > max_slippage {
-                max_slippage
-            } else {
-                slippage_input
-            };
             let solana_price = create_coingecko_proxy().await.unwrap_or(200_f64);
             let rpc_client = create_rpc_client().unwrap();
             let rpc_nonblocking_client = create_nonblocking_rpc_client().await.unwrap();
@@ -64,7 +86,10 @@ impl Config {
                 };
 
             let wallet_cloned = wallet.clone();
-            let use_jito = true;
+            // USE_JITO: send arbitrage as Jito bundles, which needs JITO_BLOCK_ENGINE_URL
+            let use_jito = env::var("USE_JITO")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+                .unwrap_or(false);
             let swap_direction = SwapDirection::Buy; //SwapDirection::Sell
             let in_type = SwapInType::Qty; //SwapInType::Pct
             let amount_in = import_env_var("TOKEN_AMOUNT")
@@ -77,15 +102,21 @@ impl Config {
                 swap_direction,
                 in_type,
                 amount_in,
-                slippage,
+                slippage_bps,
                 use_jito,
+                priority_fee: PriorityFeeStrategy::from_env(),
             };
 
-            let app_state = AppState {
-                rpc_client,
-                rpc_nonblocking_client,
-                wallet,
-            };
+            let rng = Arc::new(SessionRng::from_env());
+            logger.log(format!("[RNG SEED]: {}", rng.seed()).purple().to_string());
+
+            let app_state = AppState::builder()
+                .rpc_client(rpc_client)
+                .rpc_nonblocking_client(rpc_nonblocking_client)
+                .wallet(wallet)
+                .rng(rng)
+                .build()
+                .expect("Failed to build app state");
 
             let time_exceed: u64 = import_env_var("TIME_EXCEED")
                 .parse()
@@ -94,13 +125,13 @@ impl Config {
             logger.log(
                 format!(
                     "[SNIPER ENVIRONMENT]: \n\t\t\t\t [Yellowstone gRpc]: {},
-                \n\t\t\t\t * [Wallet]: {:?}, * [Balance]: {} Sol, 
-                \n\t\t\t\t * [Slippage]: {}, * [Solana]: {},
+                \n\t\t\t\t * [Wallet]: {:?}, * [Balance]: {}, 
+                \n\t\t\t\t * [Slippage]: {} bps, * [Solana]: {},
                 \n\t\t\t\t * [Time Exceed]: {}, * [Amount]: {}",
                     yellowstone_grpc_http,
                     wallet_cloned.pubkey(),
-                    balance as f64 / 1_000_000_000_f64,
-                    slippage_input,
+                    crate::common::format::fmt_sol(balance),
+                    slippage_bps,
                     solana_price,
                     time_exceed,
                     amount_in,
@@ -184,7 +215,8 @@ pub struct LiquidityPool {
     pub buy_price: f64,
     pub sell_price: f64,
     pub status: Status,
-    pub timestamp: Option<tokio::time::Instant>,
+    /// When the position was opened
+    pub timestamp: Option<Timestamps>,
 }
 
 impl Eq for LiquidityPool {}
@@ -216,11 +248,296 @@ struct SolanaData {
     usd: f64,
 }
 
-#[derive(Clone)]
+/// Default location of the on-disk pool cache
+pub const POOL_CACHE_PATH: &str = "pool_cache.json";
+
+/// Shared clients and services, built once in `Config::new` and handed out as `Arc<AppState>`
 pub struct AppState {
     pub rpc_client: Arc<anchor_client::solana_client::rpc_client::RpcClient>,
     pub rpc_nonblocking_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
     pub wallet: Arc<Keypair>,
+    /// Time source for timeouts and price history, mocked in backtests
+    pub clock: Arc<dyn Clock>,
+    /// Seeded RNG shared by jitter, sampling and simulation
+    pub rng: Arc<SessionRng>,
+    pub dex_registry: Arc<DEXRegistry>,
+    pub pool_cache_manager: Arc<PoolCacheManager>,
+    pub notifier: Notifier,
+    /// SQLite store for trades and journal entries
+    pub store: Arc<RecordStore>,
+    /// Embedded KV store for positions, stream progress, mint info and target stats
+    pub kv: Arc<KvStore>,
+    /// Paced RPC endpoints for calls that should back off under 429s
+    pub rpc_pool: Arc<RpcPool>,
+    /// Splits the wallet's SOL between strategies
+    pub capital: Arc<CapitalAllocator>,
+    /// Orders pool-cache refreshes by trading relevance
+    pub refresh_scheduler: Arc<RefreshScheduler>,
+    /// Which monitored tokens are dormant and skipped
+    pub dormancy: Arc<DormancyTracker>,
+    /// Buying gate, held-token tracking and stream liveness of the monitors
+    pub monitor: Arc<MonitorContext>,
+    /// Submission backend of copy buys and liquidation sells, from `TX_BACKEND`
+    pub tx_sender: Arc<dyn TxSender>,
+    /// Recent blockhashes seen on the stream, for sends
+    pub blockhashes: Arc<BlockhashCache>,
+    /// SOL/USDC reference rate that USDC-quoted pools are priced at
+    pub price_normalizer: Arc<PriceNormalizer>,
+    /// Set once a panic liquidation starts and never cleared, buy paths check it
+    pub liquidating: Arc<AtomicBool>,
+}
+
+impl AppState {
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
+
+    /// App state wired with mock RPC clients, a throwaway wallet, a mock clock and a fixed seed
+    pub fn for_tests() -> Arc<AppState> {
+        Self::for_tests_with_rpc("succeeds", Notifier::new(None))
+    }
+
+    /// `for_tests` with RPC mocks answering as `RpcClient::new_mock(mock)` does,
+    /// e.g. "sig_not_found", and alerts going to `notifier`
+    pub fn for_tests_with_rpc(mock: &str, notifier: Notifier) -> Arc<AppState> {
+        Self::for_tests_with_mocks(mock, std::collections::HashMap::new(), notifier)
+    }
+
+    /// `for_tests_with_rpc` where the paced RPC pool answers the requests in
+    /// `mocks` with their canned result, once each
+    pub fn for_tests_with_mocks(
+        mock: &str,
+        mocks: std::collections::HashMap<anchor_client::solana_client::rpc_request::RpcRequest, serde_json::Value>,
+        notifier: Notifier,
+    ) -> Arc<AppState> {
+        // One file per state, so parallel tests never load each other's half-written cache
+        static NEXT_CACHE: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let cache_path = std::env::temp_dir().join(format!(
+            "pool_cache_test_{}_{}.json",
+            std::process::id(),
+            NEXT_CACHE.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&cache_path);
+        let pool_cache_manager = PoolCacheManager::new(&cache_path.to_string_lossy())
+            .expect("Failed to create test pool cache");
+        let clock: Arc<dyn Clock> = Arc::new(MockClock::new());
+        let mock_rpc = Arc::new(
+            anchor_client::solana_client::nonblocking::rpc_client::RpcClient::new_mock_with_mocks(mock.to_string(), mocks),
+        );
+        let rpc_pool = RpcPool::new(
+            vec![("mock".to_string(), Arc::clone(&mock_rpc))],
+            PacerConfig::from_env(),
+            Arc::clone(&clock),
+        );
+
+        AppState::builder()
+            .rpc_client(Arc::new(anchor_client::solana_client::rpc_client::RpcClient::new_mock(
+                mock.to_string(),
+            )))
+            .rpc_nonblocking_client(mock_rpc)
+            .wallet(Arc::new(Keypair::new()))
+            .clock(clock)
+            .rng(Arc::new(SessionRng::from_seed(0)))
+            .pool_cache_manager(Arc::new(pool_cache_manager))
+            .notifier(notifier)
+            .store(Arc::new(RecordStore::open_in_memory().expect("Failed to open test record store")))
+            .kv(Arc::new(KvStore::open_in_memory().expect("Failed to open test kv store")))
+            .rpc_pool(Arc::new(rpc_pool))
+            .tx_sender(Arc::new(NoopSender::default()))
+            .build()
+            .expect("Failed to build test app state")
+    }
+}
+
+/// Composition root for `AppState`; RPC clients and wallet are required, the rest default
+#[derive(Default)]
+pub struct AppStateBuilder {
+    rpc_client: Option<Arc<anchor_client::solana_client::rpc_client::RpcClient>>,
+    rpc_nonblocking_client: Option<Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>>,
+    wallet: Option<Arc<Keypair>>,
+    clock: Option<Arc<dyn Clock>>,
+    rng: Option<Arc<SessionRng>>,
+    dex_registry: Option<Arc<DEXRegistry>>,
+    pool_cache_manager: Option<Arc<PoolCacheManager>>,
+    notifier: Option<Notifier>,
+    store: Option<Arc<RecordStore>>,
+    kv: Option<Arc<KvStore>>,
+    rpc_pool: Option<Arc<RpcPool>>,
+    capital: Option<Arc<CapitalAllocator>>,
+    refresh_scheduler: Option<Arc<RefreshScheduler>>,
+    dormancy: Option<Arc<DormancyTracker>>,
+    monitor: Option<Arc<MonitorContext>>,
+    tx_sender: Option<Arc<dyn TxSender>>,
+    blockhashes: Option<Arc<BlockhashCache>>,
+    price_normalizer: Option<Arc<PriceNormalizer>>,
+    liquidating: Option<Arc<AtomicBool>>,
+}
+
+impl AppStateBuilder {
+    pub fn rpc_client(mut self, rpc_client: Arc<anchor_client::solana_client::rpc_client::RpcClient>) -> Self {
+        self.rpc_client = Some(rpc_client);
+        self
+    }
+
+    pub fn rpc_nonblocking_client(
+        mut self,
+        rpc_nonblocking_client: Arc<anchor_client::solana_client::nonblocking::rpc_client::RpcClient>,
+    ) -> Self {
+        self.rpc_nonblocking_client = Some(rpc_nonblocking_client);
+        self
+    }
+
+    pub fn wallet(mut self, wallet: Arc<Keypair>) -> Self {
+        self.wallet = Some(wallet);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn rng(mut self, rng: Arc<SessionRng>) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    pub fn dex_registry(mut self, dex_registry: Arc<DEXRegistry>) -> Self {
+        self.dex_registry = Some(dex_registry);
+        self
+    }
+
+    pub fn pool_cache_manager(mut self, pool_cache_manager: Arc<PoolCacheManager>) -> Self {
+        self.pool_cache_manager = Some(pool_cache_manager);
+        self
+    }
+
+    pub fn notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn store(mut self, store: Arc<RecordStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn kv(mut self, kv: Arc<KvStore>) -> Self {
+        self.kv = Some(kv);
+        self
+    }
+
+    pub fn rpc_pool(mut self, rpc_pool: Arc<RpcPool>) -> Self {
+        self.rpc_pool = Some(rpc_pool);
+        self
+    }
+
+    pub fn capital(mut self, capital: Arc<CapitalAllocator>) -> Self {
+        self.capital = Some(capital);
+        self
+    }
+
+    pub fn refresh_scheduler(mut self, refresh_scheduler: Arc<RefreshScheduler>) -> Self {
+        self.refresh_scheduler = Some(refresh_scheduler);
+        self
+    }
+
+    pub fn dormancy(mut self, dormancy: Arc<DormancyTracker>) -> Self {
+        self.dormancy = Some(dormancy);
+        self
+    }
+
+    pub fn monitor(mut self, monitor: Arc<MonitorContext>) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    pub fn tx_sender(mut self, tx_sender: Arc<dyn TxSender>) -> Self {
+        self.tx_sender = Some(tx_sender);
+        self
+    }
+
+    pub fn blockhashes(mut self, blockhashes: Arc<BlockhashCache>) -> Self {
+        self.blockhashes = Some(blockhashes);
+        self
+    }
+
+    pub fn price_normalizer(mut self, price_normalizer: Arc<PriceNormalizer>) -> Self {
+        self.price_normalizer = Some(price_normalizer);
+        self
+    }
+
+    pub fn liquidating(mut self, liquidating: Arc<AtomicBool>) -> Self {
+        self.liquidating = Some(liquidating);
+        self
+    }
+
+    pub fn build(self) -> Result<Arc<AppState>> {
+        let pool_cache_manager = match self.pool_cache_manager {
+            Some(manager) => manager,
+            None => Arc::new(PoolCacheManager::new(POOL_CACHE_PATH)?),
+        };
+        let store = match self.store {
+            Some(store) => store,
+            None => Arc::new(RecordStore::open_from_env()?),
+        };
+        let kv = match self.kv {
+            Some(kv) => kv,
+            None => Arc::new(KvStore::open_from_env()?),
+        };
+        let clock: Arc<dyn Clock> = self.clock.unwrap_or_else(|| Arc::new(SystemClock));
+        let rpc_pool = self
+            .rpc_pool
+            .unwrap_or_else(|| Arc::new(RpcPool::from_env(Arc::clone(&clock))));
+        // Dormancy survives restarts through the pool cache
+        let dormancy = self.dormancy.unwrap_or_else(|| {
+            let dormant = pool_cache_manager.get_cache().map(|cache| cache.dormant).unwrap_or_default();
+            Arc::new(DormancyTracker::new(DormancyConfig::from_env(), dormant, clock.now()))
+        });
+
+        let monitor = self.monitor.unwrap_or_else(|| Arc::new(MonitorContext::from_env(clock.now())));
+        let tx_sender = match self.tx_sender {
+            Some(sender) => sender,
+            None => TxBackend::from_env()?.sender(),
+        };
+
+        let rpc_client = self.rpc_client.ok_or_else(|| anyhow::anyhow!("AppState requires an rpc_client"))?;
+        let rpc_nonblocking_client = self
+            .rpc_nonblocking_client
+            .ok_or_else(|| anyhow::anyhow!("AppState requires an rpc_nonblocking_client"))?;
+        let wallet = self.wallet.ok_or_else(|| anyhow::anyhow!("AppState requires a wallet"))?;
+        let dex_registry = self.dex_registry.unwrap_or_else(|| {
+            Arc::new(DEXRegistry::new().with_default_adapters(
+                wallet.clone(),
+                rpc_client.clone(),
+                rpc_nonblocking_client.clone(),
+            ))
+        });
+
+        Ok(Arc::new(AppState {
+            rpc_client,
+            rpc_nonblocking_client,
+            wallet,
+            clock,
+            rng: self.rng.unwrap_or_else(|| Arc::new(SessionRng::from_env())),
+            dex_registry,
+            pool_cache_manager,
+            notifier: self.notifier.unwrap_or_else(Notifier::from_env),
+            store,
+            kv,
+            rpc_pool,
+            capital: self.capital.unwrap_or_else(|| Arc::new(CapitalAllocator::from_env())),
+            refresh_scheduler: self
+                .refresh_scheduler
+                .unwrap_or_else(|| Arc::new(RefreshScheduler::from_env())),
+            dormancy,
+            monitor,
+            tx_sender,
+            blockhashes: self.blockhashes.unwrap_or_else(|| Arc::new(BlockhashCache::from_env())),
+            price_normalizer: self.price_normalizer.unwrap_or_else(|| Arc::new(PriceNormalizer::from_env())),
+            liquidating: self.liquidating.unwrap_or_default(),
+        }))
+    }
 }
 
 #[derive(Clone)]
@@ -228,8 +545,147 @@ pub struct SwapConfig {
     pub swap_direction: SwapDirection,
     pub in_type: SwapInType,
     pub amount_in: f64,
-    pub slippage: u64,
+    /// Basis points on every venue: 50 lets the fill be 0.5% worse than quoted
+    pub slippage_bps: u64,
     pub use_jito: bool,
+    /// How the compute unit price is bid, `PRIORITY_FEE_STRATEGY`
+    pub priority_fee: PriorityFeeStrategy,
+}
+
+/// Watched when `MONITOR_TOKEN_MINTS` is unset: SOL, USDC, BONK and JUP
+const DEFAULT_MONITOR_TOKEN_MINTS: [&str; 4] = [
+    "So11111111111111111111111111111111111111112",
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+    "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263",
+    "JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN",
+];
+
+/// `MAX_WAIT_TIME` when unset
+const DEFAULT_MAX_WAIT_MS: u64 = 60_000;
+
+/// `MAX_WAIT_TIME` for the trading modes that don't validate their settings
+/// up front, falling back to the default when it doesn't parse
+pub fn max_wait_time() -> Duration {
+    Duration::from_millis(
+        env::var("MAX_WAIT_TIME")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MAX_WAIT_MS),
+    )
+}
+
+/// Arbitrage mode settings, read and checked together once at startup
+#[derive(Debug, Clone)]
+pub struct ArbitrageSettings {
+    /// `ARBITRAGE_THRESHOLD`: spread in percent worth acting on, net of fees (default 1.5)
+    pub threshold_pct: f64,
+    /// `MIN_LIQUIDITY`: SOL side in lamports a pool needs to be quoted (default 10 SOL)
+    pub min_liquidity: u64,
+    /// `MONITOR_TOKEN_MINTS`: comma separated, the defaults above when unset
+    pub token_mints: Vec<Pubkey>,
+    /// `THRESHOLD_BUY` / `THRESHOLD_SELL` in lamports (default 1 SOL each)
+    pub threshold_buy: u64,
+    pub threshold_sell: u64,
+    /// `MAX_WAIT_TIME` in ms (default 60s)
+    pub max_wait: Duration,
+}
+
+impl ArbitrageSettings {
+    /// Every variable is checked before returning, so one error lists all the bad ones
+    pub fn from_env() -> Result<Self> {
+        let mut errors = Vec::new();
+        let mut read = |key: &str, default: f64| -> f64 {
+            match env::var(key) {
+                Err(_) => default,
+                Ok(raw) => match raw.trim().parse::<f64>() {
+                    Ok(value) if value.is_finite() && value >= 0.0 => value,
+                    _ => {
+                        errors.push(format!("{}={:?} is not a non-negative number", key, raw));
+                        default
+                    }
+                },
+            }
+        };
+        let threshold_pct = read("ARBITRAGE_THRESHOLD", 1.5);
+        let min_liquidity = read("MIN_LIQUIDITY", 10_000_000_000.0) as u64;
+        let threshold_buy = read("THRESHOLD_BUY", 1_000_000_000.0) as u64;
+        let threshold_sell = read("THRESHOLD_SELL", 1_000_000_000.0) as u64;
+        let max_wait_ms = read("MAX_WAIT_TIME", DEFAULT_MAX_WAIT_MS as f64) as u64;
+        for (key, value) in [("THRESHOLD_BUY", threshold_buy), ("THRESHOLD_SELL", threshold_sell), ("MAX_WAIT_TIME", max_wait_ms)] {
+            if value == 0 {
+                errors.push(format!("{} must be above zero", key));
+            }
+        }
+
+        let token_mints = match env::var("MONITOR_TOKEN_MINTS").ok().filter(|raw| !raw.trim().is_empty()) {
+            Some(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|mint| !mint.is_empty())
+                .filter_map(|mint| match Pubkey::from_str(mint) {
+                    Ok(pubkey) => Some(pubkey),
+                    Err(_) => {
+                        errors.push(format!("MONITOR_TOKEN_MINTS has an invalid mint {:?}", mint));
+                        None
+                    }
+                })
+                .collect(),
+            None => DEFAULT_MONITOR_TOKEN_MINTS.iter().filter_map(|mint| Pubkey::from_str(mint).ok()).collect(),
+        };
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("invalid arbitrage settings:\n  {}", errors.join("\n  ")));
+        }
+        Ok(Self {
+            threshold_pct,
+            min_liquidity,
+            token_mints,
+            threshold_buy,
+            threshold_sell,
+            max_wait: Duration::from_millis(max_wait_ms),
+        })
+    }
+}
+
+/// Env layout version. From 2 on, `SLIPPAGE`, `EMERGENCY_SLIPPAGE` and the
+/// `EXIT_LADDER` slippages are basis points; older configs gave them in percent.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+/// 100%, the most any venue's min/max amount math can take
+pub const MAX_SLIPPAGE_BPS: u64 = 10_000;
+
+/// `CONFIG_VERSION`, 1 when unset since configs predating it have no such key
+pub fn config_version() -> u32 {
+    env::var("CONFIG_VERSION")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(1)
+}
+
+/// Read a configured slippage as basis points
+///
+/// Version 1 configs meant percent, so their values are converted with a
+/// warning rather than silently reinterpreted. Anything above 100% is refused.
+pub fn slippage_to_bps(key: &str, value: u64, version: u32) -> Result<u64> {
+    let bps = if version < CURRENT_CONFIG_VERSION {
+        let bps = value.saturating_mul(100);
+        println!(
+            "{}",
+            format!(
+                "[CONFIG] => {}={} read as {}% ({} bps) because CONFIG_VERSION is {}; set it in basis points and CONFIG_VERSION={} to silence this",
+                key, value, value, bps, version, CURRENT_CONFIG_VERSION
+            )
+            .yellow()
+            .bold()
+        );
+        bps
+    } else {
+        value
+    };
+    if bps > MAX_SLIPPAGE_BPS {
+        return Err(anyhow::anyhow!("{} is {} bps, above the {} bps maximum", key, bps, MAX_SLIPPAGE_BPS));
+    }
+    Ok(bps)
 }
 
 pub fn import_env_var(key: &str) -> String {
@@ -282,4 +738,26 @@ pub fn import_wallet() -> Result<Arc<Keypair>> {
     let wallet: Keypair = Keypair::from_base58_string(priv_key.as_str());
 
     Ok(Arc::new(wallet))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_configs_give_slippage_in_bps() {
+        assert_eq!(slippage_to_bps("SLIPPAGE", 50, CURRENT_CONFIG_VERSION).unwrap(), 50);
+        assert_eq!(slippage_to_bps("SLIPPAGE", MAX_SLIPPAGE_BPS, CURRENT_CONFIG_VERSION).unwrap(), MAX_SLIPPAGE_BPS);
+        assert!(slippage_to_bps("SLIPPAGE", MAX_SLIPPAGE_BPS + 1, CURRENT_CONFIG_VERSION).is_err());
+    }
+
+    #[test]
+    fn version_one_percent_values_are_converted() {
+        // The "0.5%" that used to mean 50%
+        assert_eq!(slippage_to_bps("SLIPPAGE", 50, 1).unwrap(), 5_000);
+        assert_eq!(slippage_to_bps("SLIPPAGE", 1, 1).unwrap(), 100);
+        assert_eq!(slippage_to_bps("SLIPPAGE", 100, 1).unwrap(), MAX_SLIPPAGE_BPS);
+        let err = slippage_to_bps("EMERGENCY_SLIPPAGE", 101, 1).unwrap_err().to_string();
+        assert!(err.contains("EMERGENCY_SLIPPAGE") && err.contains("10100"));
+        assert!(slippage_to_bps("SLIPPAGE", u64::MAX, 1).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! Broadcast channel of what the bot is doing, for observers such as the TUI
+//!
+//! Publishing never blocks and never fails: with no subscriber the event is
+//! dropped, and a subscriber that falls behind loses the oldest events.
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events a slow subscriber may fall behind by before it starts losing them
+const CHANNEL_CAPACITY: usize = 4096;
+
+lazy_static::lazy_static! {
+    static ref EVENTS: broadcast::Sender<BotEvent> = broadcast::channel(CHANNEL_CAPACITY).0;
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BotEvent {
+    /// A line the logger would otherwise print
+    Log { line: String },
+    /// gRPC stream state; `slot` is the newest slot seen on it
+    Stream { name: String, connected: bool, slot: Option<u64> },
+    PositionOpened { mint: String, strategy: String, entry_price: f64, lamports: u64 },
+    /// Latest price seen for an open position
+    PositionPrice { mint: String, price: f64 },
+    /// `exit_price` is None when the sell path doesn't report one
+    PositionClosed { mint: String, exit_price: Option<f64> },
+    /// An arbitrage opportunity and what became of it, e.g. "would_execute" or a skip reason code
+    Opportunity { token: String, buy_dex: String, sell_dex: String, net_bps: f64, outcome: String },
+}
+
+pub fn publish(event: BotEvent) {
+    // Err only means nobody is listening
+    let _ = EVENTS.send(event);
+}
+
+pub fn subscribe() -> broadcast::Receiver<BotEvent> {
+    EVENTS.subscribe()
+}
+
+/// Whether anyone is listening, so callers can skip building costly events
+pub fn has_subscribers() -> bool {
+    EVENTS.receiver_count() > 0
+}
@@ -0,0 +1,152 @@
+//! Display helpers for amounts in logs, alerts and records
+//!
+//! Everything here formats from raw integer units with integer math, so a
+//! value is never rounded through f64 on its way to the screen. Records keep
+//! the raw integer and only add these strings for reading.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+const SOL_DECIMALS: u8 = 9;
+
+lazy_static::lazy_static! {
+    /// Mint -> decimals, filled wherever a mint account has been read
+    static ref MINT_DECIMALS: RwLock<HashMap<String, u8>> = RwLock::new(HashMap::new());
+}
+
+/// Remember a mint's decimals so `fmt_token` can scale its amounts
+pub fn remember_decimals(mint: &str, decimals: u8) {
+    if let Ok(mut cache) = MINT_DECIMALS.write() {
+        cache.insert(mint.to_string(), decimals);
+    }
+}
+
+pub fn cached_decimals(mint: &str) -> Option<u8> {
+    MINT_DECIMALS.read().ok()?.get(mint).copied()
+}
+
+/// `raw` scaled by `decimals`, trailing zeros trimmed: (1_500_000, 9) -> "0.0015"
+pub fn fmt_units(raw: u64, decimals: u8) -> String {
+    let scale = 10u128.pow(decimals as u32);
+    let whole = raw as u128 / scale;
+    let frac = raw as u128 % scale;
+    if frac == 0 {
+        return whole.to_string();
+    }
+    let frac = format!("{:0width$}", frac, width = decimals as usize);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+/// "0.0015 SOL"
+pub fn fmt_sol(lamports: u64) -> String {
+    format!("{} SOL", fmt_units(lamports, SOL_DECIMALS))
+}
+
+/// Signed lamport change, e.g. a PnL or balance delta: "-0.0015 SOL"
+pub fn fmt_sol_signed(lamports: i64) -> String {
+    let sign = if lamports < 0 { "-" } else { "" };
+    format!("{}{}", sign, fmt_sol(lamports.unsigned_abs()))
+}
+
+/// "1500000 lamports (0.0015 SOL)", for thresholds and fees where the exact
+/// integer matters
+pub fn fmt_lamports_exact(lamports: u64) -> String {
+    format!("{} lamports ({})", lamports, fmt_sol(lamports))
+}
+
+/// Token amount scaled by the mint's cached decimals, or the raw amount
+/// marked as such when the mint hasn't been seen yet
+pub fn fmt_token(raw: u64, mint: &str) -> String {
+    match cached_decimals(mint) {
+        Some(decimals) => fmt_units(raw, decimals),
+        None => format!("{} (raw)", raw),
+    }
+}
+
+/// Percentage with two decimals: 1.5 -> "1.50%"
+pub fn fmt_pct(pct: f64) -> String {
+    format!("{:.2}%", pct)
+}
+
+/// Basis points with their percentage: 25.0 -> "25.0 bps (0.25%)"
+pub fn fmt_bps(bps: f64) -> String {
+    format!("{:.1} bps ({})", bps, fmt_pct(bps / 100.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn units_keep_every_digit_and_trim_zeros() {
+        assert_eq!(fmt_units(1_500_000, 9), "0.0015");
+        assert_eq!(fmt_units(1, 9), "0.000000001");
+        assert_eq!(fmt_units(10_000_000_000, 9), "10");
+        assert_eq!(fmt_units(123_456_789, 6), "123.456789");
+        assert_eq!(fmt_units(0, 9), "0");
+        assert_eq!(fmt_units(42, 0), "42");
+        // Past f64's 53 bits of mantissa, nothing is rounded off
+        assert_eq!(fmt_units(u64::MAX, 9), "18446744073.709551615");
+    }
+
+    #[test]
+    fn sol_amounts() {
+        assert_eq!(fmt_sol(10_000_000_000), "10 SOL");
+        assert_eq!(fmt_sol_signed(-1_500_000), "-0.0015 SOL");
+        assert_eq!(fmt_sol_signed(i64::MIN), "-9223372036.854775808 SOL");
+        assert_eq!(fmt_lamports_exact(1_500_000), "1500000 lamports (0.0015 SOL)");
+    }
+
+    #[test]
+    fn tokens_use_the_cached_decimals_once_known() {
+        let mint = "FormatTestMint1111111111111111111111111111";
+        assert_eq!(fmt_token(2_500_000, mint), "2500000 (raw)");
+        remember_decimals(mint, 6);
+        assert_eq!(cached_decimals(mint), Some(6));
+        assert_eq!(fmt_token(2_500_000, mint), "2.5");
+    }
+
+    #[test]
+    fn percentages_round_to_two_decimals() {
+        assert_eq!(fmt_pct(1.5), "1.50%");
+        assert_eq!(fmt_pct(0.004), "0.00%");
+        assert_eq!(fmt_pct(-12.3456), "-12.35%");
+        assert_eq!(fmt_bps(25.0), "25.0 bps (0.25%)");
+    }
+
+    /// Lines under `dir` converting lamports to SOL by hand instead of through these helpers
+    fn hand_conversions(dir: &Path, found: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                hand_conversions(&path, found);
+                continue;
+            }
+            if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            for (number, line) in source.lines().enumerate() {
+                let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+                // Built at runtime so this file doesn't match itself
+                let converts = ["1_000_000_000", "1000000000", "1e9"]
+                    .iter()
+                    .any(|divisor| compact.contains(&format!("asf64/{}", divisor)));
+                if converts {
+                    found.push(format!("{}:{}: {}", path.display(), number + 1, line.trim()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_hand_rolled_lamport_conversions() {
+        let mut found = Vec::new();
+        for dir in ["src", "benches"] {
+            hand_conversions(&Path::new(env!("CARGO_MANIFEST_DIR")).join(dir), &mut found);
+        }
+        assert!(found.is_empty(), "use shared::format instead:\n{}", found.join("\n"));
+    }
+}
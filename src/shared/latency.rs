@@ -0,0 +1,158 @@
+//! Where the milliseconds of a trade go
+//!
+//! A `LatencyTracer` travels with one stream message or opportunity and is
+//! stamped as it passes each stage, from the gRPC receive to confirmation.
+//! Paths skip the stages they don't have: a copy buy signs with the target's
+//! blockhash and fetches none, an arbitrage starts at the scan's decision.
+//! Finishing a tracer logs one `[LATENCY]` line with the time spent in each
+//! stage and feeds the `trade_stage_latency_seconds` histogram and the
+//! rolling p50/p95/p99 gauges on `/metrics`.
+//!
+//! Every message gets a tracer, but one that ends without a trade is only
+//! recorded with probability `TRACE_SAMPLE_RATE`, so the stream's volume
+//! doesn't turn into log volume. Trades are always recorded.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use colored::Colorize;
+use tokio::time::Instant;
+
+use crate::common::logger::Logger;
+use crate::common::metrics::{TRADE_STAGE_LATENCY, TRADE_STAGE_LATENCY_QUANTILE};
+use crate::common::rng::SessionRng;
+use crate::common::sync::LockExt;
+
+/// Samples per (path, stage) the rolling quantiles are taken over
+const ROLLING_WINDOW: usize = 1_024;
+
+/// Default for `TRACE_SAMPLE_RATE`
+const DEFAULT_SAMPLE_RATE: f64 = 0.01;
+
+lazy_static::lazy_static! {
+    static ref ROLLING: Mutex<HashMap<(&'static str, Stage), VecDeque<f64>>> = Mutex::new(HashMap::new());
+    static ref SAMPLE_RATE: f64 = std::env::var("TRACE_SAMPLE_RATE")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|rate| rate.is_finite())
+        .map(|rate| rate.clamp(0.0, 1.0))
+        .unwrap_or(DEFAULT_SAMPLE_RATE);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    Received,
+    Parsed,
+    Decided,
+    Built,
+    Blockhash,
+    Signed,
+    Submitted,
+    Confirmed,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Received => "received",
+            Self::Parsed => "parsed",
+            Self::Decided => "decided",
+            Self::Built => "built",
+            Self::Blockhash => "blockhash",
+            Self::Signed => "signed",
+            Self::Submitted => "submitted",
+            Self::Confirmed => "confirmed",
+        }
+    }
+}
+
+/// Stage stamps of one message or opportunity on one path
+#[derive(Debug, Clone)]
+pub struct LatencyTracer {
+    path: &'static str,
+    marks: Vec<(Stage, Instant)>,
+    /// Whether it is recorded should it end without a trade
+    sampled: bool,
+}
+
+impl LatencyTracer {
+    /// A tracer for `path` ("copy", "arbitrage", ...) whose first stage was at `at`
+    pub fn start(path: &'static str, stage: Stage, at: Instant, rng: &SessionRng) -> Self {
+        Self {
+            path,
+            marks: vec![(stage, at)],
+            sampled: rng.sample(*SAMPLE_RATE),
+        }
+    }
+
+    pub fn mark(&mut self, stage: Stage) {
+        self.mark_at(stage, Instant::now());
+    }
+
+    pub fn mark_at(&mut self, stage: Stage, at: Instant) {
+        self.marks.push((stage, at));
+    }
+
+    /// Time from the previous stamp to each stage after the first
+    pub fn breakdown(&self) -> Vec<(Stage, Duration)> {
+        self.marks
+            .windows(2)
+            .map(|pair| (pair[1].0, pair[1].1.saturating_duration_since(pair[0].1)))
+            .collect()
+    }
+
+    pub fn total(&self) -> Duration {
+        match (self.marks.first(), self.marks.last()) {
+            (Some(first), Some(last)) => last.1.saturating_duration_since(first.1),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// `path=copy id=<id> outcome=<outcome> parsed_ms=0.412 decided_ms=0.031 ... total_ms=14.907`
+    pub fn line(&self, id: &str, outcome: &str) -> String {
+        let mut line = format!("path={} id={} outcome={}", self.path, id, outcome);
+        for (stage, took) in self.breakdown() {
+            let _ = write!(line, " {}_ms={:.3}", stage.name(), took.as_secs_f64() * 1_000.0);
+        }
+        let _ = write!(line, " total_ms={:.3}", self.total().as_secs_f64() * 1_000.0);
+        line
+    }
+
+    /// Log and record a tracer that led to a trade attempt
+    pub fn finish(&self, id: &str, outcome: &str, logger: &Logger) {
+        logger.log(format!("[LATENCY] => {}", self.line(id, outcome)).cyan().to_string());
+        self.observe();
+    }
+
+    /// Record a tracer that ended without a trade, if it was sampled
+    pub fn finish_untraded(&self, id: &str, logger: &Logger) {
+        if self.sampled {
+            logger.debug_with(|| format!("[LATENCY] => {}", self.line(id, "none")));
+            self.observe();
+        }
+    }
+
+    fn observe(&self) {
+        let mut rolling = ROLLING.lock_or_recover();
+        for (stage, took) in self.breakdown() {
+            let seconds = took.as_secs_f64();
+            TRADE_STAGE_LATENCY.with_label_values(&[self.path, stage.name()]).observe(seconds);
+
+            let window = rolling.entry((self.path, stage)).or_default();
+            if window.len() >= ROLLING_WINDOW {
+                window.pop_front();
+            }
+            window.push_back(seconds);
+            let mut sorted: Vec<f64> = window.iter().copied().collect();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            for (label, q) in [("0.5", 0.5), ("0.95", 0.95), ("0.99", 0.99)] {
+                let index = ((sorted.len() - 1) as f64 * q).round() as usize;
+                TRADE_STAGE_LATENCY_QUANTILE
+                    .with_label_values(&[self.path, stage.name(), label])
+                    .set(sorted[index]);
+            }
+        }
+    }
+}
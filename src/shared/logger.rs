@@ -1,7 +1,106 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
 use chrono::Local;
 use colored::*;
 
-const LOG_LEVEL: &str = "LOG";
+use crate::common::events::{self, BotEvent};
+
+/// Severity of a log line; lines below `LOG_LEVEL` are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "trace" => Some(Self::Trace),
+            "debug" => Some(Self::Debug),
+            "info" | "log" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// `LOG_LEVEL`, read once (default: info)
+    pub fn current() -> Self {
+        static LEVEL: OnceLock<LogLevel> = OnceLock::new();
+        *LEVEL.get_or_init(|| {
+            std::env::var("LOG_LEVEL")
+                .ok()
+                .and_then(|raw| Self::parse(&raw))
+                .unwrap_or(Self::Info)
+        })
+    }
+
+    /// Whether lines at this level are written
+    pub fn enabled(self) -> bool {
+        self >= Self::current()
+    }
+}
+
+/// Set while a full-screen UI owns the terminal; lines go to the event channel instead
+static CONSOLE_CAPTURED: AtomicBool = AtomicBool::new(false);
+
+/// Route log lines to the event channel (true) or back to stdout (false)
+pub fn capture_console(captured: bool) {
+    CONSOLE_CAPTURED.store(captured, Ordering::Relaxed);
+    colored::control::set_override(!captured);
+}
+
+/// Drop ANSI color sequences from lines built before colors were turned off
+fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of the CSI sequence, always 'm' for colors
+            for c in chars.by_ref() {
+                if c == 'm' {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Lines emitted on this thread inside `lines_written`
+    static WRITTEN: std::cell::RefCell<Option<Vec<String>>> = std::cell::RefCell::new(None);
+}
+
+/// Run `f` and return the lines it would have written, instead of writing them
+#[cfg(test)]
+pub(crate) fn lines_written(f: impl FnOnce()) -> Vec<String> {
+    WRITTEN.with(|written| *written.borrow_mut() = Some(Vec::new()));
+    f();
+    WRITTEN.with(|written| written.borrow_mut().take().unwrap_or_default())
+}
+
+fn emit(line: &str) {
+    #[cfg(test)]
+    {
+        let held = WRITTEN.with(|written| written.borrow_mut().as_mut().map(|lines| lines.push(line.to_string())).is_some());
+        if held {
+            return;
+        }
+    }
+    if CONSOLE_CAPTURED.load(Ordering::Relaxed) {
+        events::publish(BotEvent::Log { line: strip_ansi(line) });
+    } else {
+        println!("{}", line);
+    }
+}
 
 #[derive(Clone)]
 pub struct Logger {
@@ -18,23 +117,58 @@ impl Logger {
         }
     }
 
-    // Method to log a message with a prefix
+    // Method to log a message with a prefix, at info level
     pub fn log(&self, message: String) -> String {
         let log = format!("{} {}", self.prefix_with_date(), message);
-        println!("{}", log);
+        if LogLevel::Info.enabled() {
+            emit(&log);
+        }
+        log
+    }
+
+    /// Per-message detail such as whole transaction dumps, off unless `LOG_LEVEL=trace`
+    pub fn trace(&self, message: String) -> String {
+        let log = format!("{} [{}] {}", self.prefix_with_date(), "TRACE", message);
+        if LogLevel::Trace.enabled() {
+            emit(&log);
+        }
         log
     }
 
     pub fn debug(&self, message: String) -> String {
         let log = format!("{} [{}] {}", self.prefix_with_date(), "DEBUG", message);
-        if LogLevel::new().is_debug() {
-            println!("{}", log);
+        if LogLevel::Debug.enabled() {
+            emit(&log);
+        }
+        log
+    }
+
+    /// Like `debug`, but `message` is only built when debug lines are written,
+    /// for hot paths where formatting alone would cost
+    pub fn debug_with(&self, message: impl FnOnce() -> String) {
+        if LogLevel::Debug.enabled() {
+            self.debug(message());
+        }
+    }
+
+    /// `trace` counterpart of `debug_with`
+    pub fn trace_with(&self, message: impl FnOnce() -> String) {
+        if LogLevel::Trace.enabled() {
+            self.trace(message());
+        }
+    }
+
+    pub fn warn(&self, message: String) -> String {
+        let log = format!("{} [{}] {}", self.prefix_with_date(), "WARN", message);
+        if LogLevel::Warn.enabled() {
+            emit(&log);
         }
         log
     }
+
     pub fn error(&self, message: String) -> String {
         let log = format!("{} [{}] {}", self.prefix_with_date(), "ERROR", message);
-        println!("{}", log);
+        emit(&log);
 
         log
     }
@@ -42,7 +176,9 @@ impl Logger {
     // Add success method to fix compilation errors in monitor.rs
     pub fn success(&self, message: String) -> String {
         let log = format!("{} [{}] {}", self.prefix_with_date(), "SUCCESS".green().bold(), message);
-        println!("{}", log);
+        if LogLevel::Info.enabled() {
+            emit(&log);
+        }
         log
     }
 
@@ -63,16 +199,3 @@ impl Logger {
         )
     }
 }
-
-struct LogLevel<'a> {
-    level: &'a str,
-}
-impl LogLevel<'_> {
-    fn new() -> Self {
-        let level = LOG_LEVEL;
-        LogLevel { level }
-    }
-    fn is_debug(&self) -> bool {
-        self.level.to_lowercase().eq("debug")
-    }
-}
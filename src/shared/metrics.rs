@@ -0,0 +1,139 @@
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+lazy_static::lazy_static! {
+    /// Process-wide registry served by the admin API at /metrics
+    pub static ref REGISTRY: Registry = Registry::new();
+
+    /// Current request rate each RPC endpoint is paced at, in requests per second
+    pub static ref RPC_PACING_RATE: GaugeVec = register(GaugeVec::new(
+        Opts::new("rpc_pacing_rate", "Requests per second currently allowed per RPC endpoint"),
+        &["endpoint"],
+    ).unwrap());
+
+    /// RPC calls answered with 429 or timing out, per endpoint and outcome
+    pub static ref RPC_THROTTLED_TOTAL: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("rpc_throttled_total", "RPC calls rate limited or timed out"),
+        &["endpoint", "kind"],
+    ).unwrap());
+
+    /// Wallet balance left for trading after reserves and the fee budget
+    pub static ref CAPITAL_INVESTABLE: IntGauge = register(IntGauge::new(
+        "capital_investable_lamports", "Investable SOL balance in lamports",
+    ).unwrap());
+
+    /// Share of the investable balance each strategy may use
+    pub static ref CAPITAL_ALLOCATION: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new("capital_allocation_lamports", "Capital allocated per strategy in lamports"),
+        &["strategy"],
+    ).unwrap());
+
+    /// Capital currently out in open positions or in-flight trades per strategy
+    pub static ref CAPITAL_EXPOSURE: IntGaugeVec = register(IntGaugeVec::new(
+        Opts::new("capital_exposure_lamports", "Outstanding exposure per strategy in lamports"),
+        &["strategy"],
+    ).unwrap());
+
+    /// Best cross-DEX spread per token on each scan, labelled by configured
+    /// token or "other"
+    pub static ref SPREAD_BPS: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("arbitrage_spread_bps", "Best cross-DEX spread per token per scan in bps")
+            .buckets(vec![5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 150.0, 200.0, 300.0, 500.0, 1000.0]),
+        &["token"],
+    ).unwrap());
+
+    /// Monitored tokens demoted to dormant or promoted back to active
+    pub static ref TOKEN_DORMANCY_TRANSITIONS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("token_dormancy_transitions_total", "Tokens demoted to or promoted from dormant"),
+        &["transition"],
+    ).unwrap());
+
+    /// Supervised background tasks restarted after a panic
+    pub static ref TASK_RESTARTS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("task_restarts_total", "Supervised tasks restarted after panicking"),
+        &["task"],
+    ).unwrap());
+
+    /// 1 while this process holds the leader lease
+    pub static ref IS_LEADER: IntGauge = register(IntGauge::new(
+        "leader", "Whether this process holds the leader lease",
+    ).unwrap());
+
+    /// 1 while prices come from RPC polling because the gRPC stream is down
+    pub static ref STREAM_FALLBACK_ACTIVE: IntGauge = register(IntGauge::new(
+        "stream_fallback_active", "Whether the RPC polling fallback is standing in for the gRPC stream",
+    ).unwrap());
+
+    /// Leader lease won or lost
+    pub static ref LEADER_TRANSITIONS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("leader_transitions_total", "Leader lease promotions and demotions"),
+        &["transition"],
+    ).unwrap());
+
+    /// Rolling mean of simulated / local-math output minus one, per venue
+    pub static ref MATH_REL_ERROR: GaugeVec = register(GaugeVec::new(
+        Opts::new("math_rel_error", "Rolling relative error of local swap math against RPC simulation"),
+        &["venue"],
+    ).unwrap());
+
+    /// Rolling median of realized over quoted fill rate per strategy
+    pub static ref FILL_QUALITY_MEDIAN: GaugeVec = register(GaugeVec::new(
+        Opts::new("fill_quality_median", "Rolling median of realized output over quoted output per strategy"),
+        &["strategy"],
+    ).unwrap());
+
+    /// 1 while bad fills keep a strategy's entries paused
+    pub static ref FILL_QUALITY_PAUSED: GaugeVec = register(GaugeVec::new(
+        Opts::new("fill_quality_paused", "Whether fill quality has paused new entries for the strategy"),
+        &["strategy"],
+    ).unwrap());
+
+    /// Current 0-100 health score per cached pool
+    pub static ref POOL_HEALTH_SCORE: GaugeVec = register(GaugeVec::new(
+        Opts::new("pool_health_score", "Pool health score combining liquidity, volume, stability and failures"),
+        &["dex", "pool"],
+    ).unwrap());
+
+    /// Time a trade path spent in each stage, from gRPC receive to confirmation
+    pub static ref TRADE_STAGE_LATENCY: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("trade_stage_latency_seconds", "Time spent in each stage of a trade path")
+            .buckets(vec![0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+        &["path", "stage"],
+    ).unwrap());
+
+    /// p50/p95/p99 of the last 1024 samples per path and stage
+    pub static ref TRADE_STAGE_LATENCY_QUANTILE: GaugeVec = register(GaugeVec::new(
+        Opts::new("trade_stage_latency_quantile_seconds", "Rolling quantiles of the time spent in each stage of a trade path"),
+        &["path", "stage", "quantile"],
+    ).unwrap());
+
+    /// Blockhashes fetched over RPC because the streamed one was too old, by caller
+    pub static ref BLOCKHASH_CACHE_FALLBACKS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("blockhash_cache_fallbacks_total", "Blockhashes fetched over RPC instead of taken from the stream"),
+        &["source"],
+    ).unwrap());
+
+    /// Locks taken back after a task panicked holding them, per guarded type
+    pub static ref LOCK_POISON_RECOVERED: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("lock_poison_recovered_total", "Poisoned mutexes recovered instead of panicking"),
+        &["type"],
+    ).unwrap());
+}
+
+fn register<M: prometheus::core::Collector + Clone + 'static>(metric: M) -> M {
+    // Only fails on duplicate names, which would be a programming error
+    REGISTRY.register(Box::new(metric.clone())).expect("metric registered twice");
+    metric
+}
+
+/// Every registered metric in the Prometheus text format
+pub fn gather_text() -> String {
+    let mut buffer = Vec::new();
+    let encoder = TextEncoder::new();
+    if encoder.encode(&REGISTRY.gather(), &mut buffer).is_err() {
+        return String::new();
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
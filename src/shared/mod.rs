@@ -1,3 +1,12 @@
 pub mod config;
 pub mod constants;
 pub mod logger;
+pub mod clock;
+pub mod rng;
+pub mod metrics;
+pub mod format;
+pub mod events;
+pub mod profiling;
+pub mod hmac;
+pub mod sync;
+pub mod latency;
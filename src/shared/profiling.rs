@@ -0,0 +1,49 @@
+//! Stage timing for the hot parsing path, built with `--features profiling`
+//!
+//! Parsing, pool extraction and protocol classification carry `trace` spans.
+//! With the feature on, `init` routes them to tracing-flame, which writes a
+//! folded stack file (`PROFILE_OUTPUT`, default `./tracing.folded`) for
+//! `inferno-flamegraph`. Without the feature both functions do nothing.
+
+#[cfg(feature = "profiling")]
+mod enabled {
+    use std::fs::File;
+    use std::io::BufWriter;
+    use std::sync::Mutex;
+
+    use tracing_flame::{FlameLayer, FlushGuard};
+    use tracing_subscriber::prelude::*;
+
+    lazy_static::lazy_static! {
+        static ref GUARD: Mutex<Option<FlushGuard<BufWriter<File>>>> = Mutex::new(None);
+    }
+
+    pub fn init() {
+        let path = std::env::var("PROFILE_OUTPUT").unwrap_or_else(|_| "./tracing.folded".to_string());
+        match FlameLayer::with_file(&path) {
+            Ok((layer, guard)) => {
+                tracing_subscriber::registry().with(layer).init();
+                *GUARD.lock().unwrap() = Some(guard);
+                println!("Profiling spans to {}", path);
+            }
+            Err(e) => eprintln!("Profiling disabled, cannot write {}: {}", path, e),
+        }
+    }
+
+    pub fn flush() {
+        if let Some(guard) = GUARD.lock().unwrap().as_ref() {
+            if let Err(e) = guard.flush() {
+                eprintln!("Failed to flush profile: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "profiling")]
+pub use enabled::{flush, init};
+
+#[cfg(not(feature = "profiling"))]
+pub fn init() {}
+
+#[cfg(not(feature = "profiling"))]
+pub fn flush() {}
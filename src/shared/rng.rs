@@ -0,0 +1,54 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Session-scoped random number generator
+///
+/// Every random decision (backoff jitter, sampling, simulated prices) draws from
+/// this handle so a run can be replayed by fixing `RNG_SEED`.
+#[derive(Debug)]
+pub struct SessionRng {
+    seed: u64,
+    rng: Mutex<StdRng>,
+}
+
+impl SessionRng {
+    /// Deterministic generator for tests and backtests
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Use `RNG_SEED` when set, otherwise seed from the OS
+    pub fn from_env() -> Self {
+        let seed = std::env::var("RNG_SEED")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or_else(rand::random::<u64>);
+        Self::from_seed(seed)
+    }
+
+    /// Seed this session was started with, log it to reproduce a run
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Uniform value in [0, 1)
+    pub fn next_f64(&self) -> f64 {
+        self.rng.lock().unwrap().gen::<f64>()
+    }
+
+    /// Returns true with the given probability
+    pub fn sample(&self, probability: f64) -> bool {
+        self.next_f64() < probability.clamp(0.0, 1.0)
+    }
+
+    /// Add up to `max_jitter_pct` percent of random jitter on top of `base`
+    pub fn jitter(&self, base: Duration, max_jitter_pct: f64) -> Duration {
+        let factor = 1.0 + self.next_f64() * (max_jitter_pct / 100.0);
+        base.mul_f64(factor)
+    }
+}
@@ -0,0 +1,73 @@
+//! Mutex locking that survives a panicking holder
+//!
+//! A task that panics while holding a std `Mutex` poisons it, and with
+//! `lock().unwrap()` every later locker panics too, so one bad message could
+//! take down price monitoring and every task sharing its maps. The guarded
+//! data is only ever left mid-update by such a panic, which for the counters,
+//! caches and flags kept behind these locks is no worse than a missed update,
+//! so the guard is taken back and the recovery counted on `/metrics`.
+//!
+//! The locks stay std mutexes: they are held for a map lookup or insert and
+//! never across an `.await`, which is what a tokio mutex would be for.
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::common::metrics::LOCK_POISON_RECOVERED;
+
+pub trait LockExt<T> {
+    /// Lock, taking the guard back from a poisoned mutex
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            LOCK_POISON_RECOVERED.with_label_values(&[std::any::type_name::<T>()]).inc();
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Stands in for a price map, typed apart so no other test moves its counter
+    #[derive(Default)]
+    struct Prices(HashMap<String, f64>);
+
+    #[tokio::test]
+    async fn a_panicking_holder_does_not_stop_later_updates() {
+        let prices = Arc::new(Mutex::new(Prices::default()));
+        let recovered = || LOCK_POISON_RECOVERED.with_label_values(&[std::any::type_name::<Prices>()]).get();
+        let before = recovered();
+
+        let crashing = tokio::spawn({
+            let prices = Arc::clone(&prices);
+            async move {
+                let mut guard = prices.lock_or_recover();
+                guard.0.insert("mint".to_string(), 1.0);
+                panic!("bad message while updating prices");
+            }
+        });
+        assert!(crashing.await.unwrap_err().is_panic());
+        assert!(prices.is_poisoned());
+
+        // The next update goes through and sees what was written before the panic
+        let updater = tokio::spawn({
+            let prices = Arc::clone(&prices);
+            async move {
+                let mut guard = prices.lock_or_recover();
+                guard.0.insert("mint".to_string(), guard.0["mint"] * 2.0);
+            }
+        });
+        updater.await.unwrap();
+        assert_eq!(prices.lock_or_recover().0["mint"], 2.0);
+        assert!(!prices.is_poisoned());
+        assert_eq!(recovered(), before + 1);
+    }
+}
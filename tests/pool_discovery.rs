@@ -0,0 +1,43 @@
+//! Pool discovery against an RPC
+//!
+//! Without features this runs on a mock client. `--features live-rpc` adds
+//! a query to the node at `RPC_HTTP`, which must allow `getProgramAccounts`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use solana_vntr_sniper::application::pool_discovery::discover_pools_for_token;
+use solana_vntr_sniper::infrastructure::services::rpc_pool::{PacerConfig, RpcPool};
+use solana_vntr_sniper::shared::clock::{Clock, MockClock};
+
+#[tokio::test]
+async fn failed_queries_leave_no_pools_behind() {
+    let clock: Arc<dyn Clock> = Arc::new(MockClock::new());
+    let client = Arc::new(RpcClient::new_mock_with_mocks("fails".to_string(), HashMap::new()));
+    let rpc_pool = RpcPool::new(vec![("mock".to_string(), client)], PacerConfig::from_env(), clock);
+
+    // Each venue's query errors; discovery carries on and finds nothing
+    let pools = discover_pools_for_token(&rpc_pool, &Pubkey::new_unique()).await.unwrap();
+    assert!(pools.is_empty());
+}
+
+#[cfg(feature = "live-rpc")]
+#[tokio::test]
+async fn a_known_token_has_a_pool() {
+    use solana_vntr_sniper::shared::clock::SystemClock;
+
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+    let token: Pubkey = std::env::var("LIVE_TEST_TOKEN").unwrap_or_else(|_| BONK.to_string()).parse().unwrap();
+    let rpc_pool = RpcPool::from_env(Arc::new(SystemClock));
+    assert!(!rpc_pool.endpoints().is_empty(), "RPC_HTTP is not set");
+
+    let pools = discover_pools_for_token(&rpc_pool, &token).await.unwrap();
+    assert!(!pools.is_empty(), "no pool found for {}", token);
+    // Quote mints are read from the pool accounts, not filled with a placeholder
+    for pool in &pools {
+        assert_eq!(pool.base_mint, token.to_string());
+        assert_ne!(pool.quote_mint, Pubkey::default().to_string(), "{} on {}", pool.pool_id, pool.dex_name);
+    }
+}